@@ -0,0 +1,72 @@
+// Scan-heavy paths
+//
+// Run with `cargo bench --features bench`. Fixtures come from the builders in
+// `testing`, so every run measures the same trees. Save a baseline before a
+// change and compare against it after:
+//
+//   cargo bench --features bench -- --save-baseline before
+//   cargo bench --features bench -- --baseline before
+//
+// Budgets, roughly 3x what a 2023 laptop SSD measures; a change that blows
+// one needs a reason:
+// - listing a 10k-entry directory: 50 ms, 60 ms with snippets
+// - counting notes in a 5k-note vault: 10 ms
+// - indexing a 5k-note vault: 40 ms
+// - hashing a 1 GiB file: 3 s
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::time::Duration;
+
+use agentvbx_desktop_lib::testing::{self, TestHome};
+
+const WIDE_ENTRIES: usize = 10_000;
+const VAULT_NOTES: usize = 5_000;
+const HASH_BYTES: u64 = 1 << 30;
+
+fn listing(c: &mut Criterion) {
+    let home = TestHome::new();
+    let dir = home.join("wide");
+    testing::wide_directory(&dir, WIDE_ENTRIES);
+
+    let mut group = c.benchmark_group("list_directory");
+    group.throughput(Throughput::Elements(WIDE_ENTRIES as u64));
+    group.bench_function("10k_entries", |b| {
+        b.iter(|| testing::list_directory(&dir).unwrap())
+    });
+    group.bench_function("10k_entries_snippets", |b| {
+        b.iter(|| testing::list_directory_with_snippets(&dir).unwrap())
+    });
+    group.finish();
+}
+
+fn vaults(c: &mut Criterion) {
+    let home = TestHome::new();
+    let vault = home.join("Vault");
+    testing::synthetic_vault(&vault, VAULT_NOTES);
+
+    let mut group = c.benchmark_group("vault");
+    group.throughput(Throughput::Elements(VAULT_NOTES as u64));
+    group.bench_function("count_markdown_5k", |b| {
+        b.iter(|| testing::count_markdown_files(&vault))
+    });
+    group.bench_function("index_5k", |b| b.iter(|| testing::index_vault(&vault)));
+    group.finish();
+}
+
+fn hashing(c: &mut Criterion) {
+    let home = TestHome::new();
+    let file = home.join("sparse.bin");
+    testing::sparse_file(&file, HASH_BYTES);
+
+    let mut group = c.benchmark_group("hash_file");
+    group.throughput(Throughput::Bytes(HASH_BYTES));
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(30));
+    group.bench_function("1gib_sparse", |b| {
+        b.iter(|| testing::hash_file(&file).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, listing, vaults, hashing);
+criterion_main!(benches);
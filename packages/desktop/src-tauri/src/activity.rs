@@ -0,0 +1,146 @@
+// Activity feed
+//
+// "Recently touched by AGENTVBX" for the home screen: files read, written,
+// hashed, or imported. Entries are JSON lines in `activity.jsonl` (state),
+// kept as a ring buffer — once the file passes `MAX_FILE_BYTES` the oldest
+// half is dropped. Unlike the audit log this is a convenience view and can
+// be cleared by the user. The feed can be narrowed to a workspace's stores
+// (see `workspaces`).
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::CommandResult;
+use crate::home;
+use crate::{file_ids, stores, workspaces};
+
+const ACTIVITY_FILE: &str = "activity.jsonl";
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+const DEFAULT_LIMIT: usize = 50;
+
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Read,
+    Write,
+    Hash,
+    Import,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ActivityEntry {
+    pub at: String,
+    pub action: ActivityKind,
+    pub path: String,
+    pub tenant_id: Option<String>,
+    /// Store containing `path`, when it is inside one.
+    pub store_id: Option<String>,
+    /// Stable id of the file (see `file_ids`), which survives renames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    /// Set on read: the file no longer exists.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Newest-first activity, optionally limited to some kinds, or to the
+/// stores of the tenant's `workspace_id`.
+#[tauri::command]
+pub fn get_recent_activity(
+    limit: Option<usize>,
+    kinds: Option<Vec<ActivityKind>>,
+    tenant_id: Option<String>,
+    workspace_id: Option<String>,
+) -> CommandResult<Vec<ActivityEntry>> {
+    let workspace = workspaces::resolve(tenant_id.as_deref(), workspace_id.as_deref())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    let raw = {
+        let _guard = FILE_LOCK.lock().unwrap();
+        activity_path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .unwrap_or_default()
+    };
+
+    Ok(raw
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<ActivityEntry>(line).ok())
+        .filter(|e| kinds.as_ref().is_none_or(|k| k.contains(&e.action)))
+        .filter(|e| {
+            workspace
+                .as_ref()
+                .is_none_or(|w| e.store_id.as_deref().is_some_and(|id| w.has_store(id)))
+        })
+        .take(limit)
+        .map(|mut e| {
+            e.missing = !Path::new(&e.path).exists();
+            e
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub fn clear_activity_history() -> CommandResult<()> {
+    let _guard = FILE_LOCK.lock().unwrap();
+    match fs::remove_file(activity_path()?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// ─── Recording ──────────────────────────────────────────────────────────────
+
+/// Record that AGENTVBX touched `path`. Failures are logged, never surfaced.
+pub fn record(action: ActivityKind, path: &Path, tenant_id: Option<&str>) {
+    let entry = ActivityEntry {
+        at: chrono::Utc::now().to_rfc3339(),
+        action,
+        path: path.to_string_lossy().to_string(),
+        tenant_id: tenant_id.map(str::to_string),
+        store_id: stores::store_for_path(path).map(|s| s.id),
+        file_id: path.is_file().then(|| file_ids::identify_path(path).id),
+        missing: false,
+    };
+    if let Err(e) = append(&entry) {
+        log::warn!("[activity] failed to record {:?}: {}", action, e);
+    }
+}
+
+fn append(entry: &ActivityEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let path = activity_path()?;
+    let _guard = FILE_LOCK.lock().unwrap();
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if size > MAX_FILE_BYTES {
+        compact(&path)?;
+    }
+    Ok(())
+}
+
+/// Drop the oldest half of the entries.
+fn compact(path: &Path) -> Result<(), String> {
+    let raw = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let lines: Vec<&str> = raw.lines().collect();
+    let kept = lines[lines.len() / 2..].join("\n") + "\n";
+    fs::write(path, kept).map_err(|e| e.to_string())
+}
+
+fn activity_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join(ACTIVITY_FILE))
+}
@@ -0,0 +1,92 @@
+// App-scoped files
+//
+// iOS and Android only let an app read its own sandbox and the files the user
+// hands it. These commands work on every platform, within those rules.
+// `list_app_directory` lists folders under the app's documents directory.
+// `read_app_text_file` reads a path relative to that directory, or a
+// `content://` or `file://` URI from the system file picker. URIs are read through
+// the fs plugin, which resolves Android content URIs.
+
+use std::path::{Component, Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_fs::{FilePath, FsExt};
+
+use super::{list_entries, FileEntry};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{file_read, settings};
+
+/// The app's documents directory: the sandbox's on mobile, the user's on
+/// desktop.
+pub fn documents_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    app.path()
+        .document_dir()
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// List a folder under the documents directory; `None` lists its root.
+#[tauri::command]
+pub fn list_app_directory(app: AppHandle, path: Option<String>) -> CommandResult<Vec<FileEntry>> {
+    let dir = resolve(&app, path.as_deref().unwrap_or(""))?;
+    if !dir.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Directory not found: {}", dir.display()),
+        )
+        .with("path", dir.to_string_lossy()));
+    }
+    let collator = crate::collation::Collator::for_listing(None, None, None);
+    list_entries(&dir, &collator).map_err(CommandError::from)
+}
+
+/// Read a text file given as a documents-relative path or a picker URI.
+/// Subject to `limits.max_text_read_bytes`, like `read_text_file`.
+#[tauri::command]
+pub fn read_app_text_file(app: AppHandle, path: String) -> CommandResult<String> {
+    let limit = settings::load_settings().limits.max_text_read_bytes;
+    let target = match path.parse::<FilePath>() {
+        Ok(FilePath::Url(url)) => FilePath::Url(url),
+        _ => {
+            let file = resolve(&app, &path)?;
+            let size = std::fs::metadata(&file)?.len();
+            if size > limit {
+                return Err(file_read::too_large(
+                    &file,
+                    size,
+                    limit,
+                    "max_text_read_bytes",
+                ));
+            }
+            FilePath::Path(file)
+        }
+    };
+
+    let bytes = app.fs().read(target)?;
+    if bytes.len() as u64 > limit {
+        return Err(file_read::too_large(
+            Path::new(&path),
+            bytes.len() as u64,
+            limit,
+            "max_text_read_bytes",
+        ));
+    }
+    String::from_utf8(bytes).map_err(|_| {
+        CommandError::new(ErrorCode::InvalidInput, "File is not valid UTF-8").with("path", &path)
+    })
+}
+
+/// `relative` under the documents directory. Absolute paths and `..` are
+/// refused so this can't reach outside it.
+fn resolve(app: &AppHandle, relative: &str) -> CommandResult<PathBuf> {
+    let relative = Path::new(relative);
+    let escapes = relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Path must be relative to the app's documents folder",
+        )
+        .with("path", relative.to_string_lossy()));
+    }
+    Ok(documents_dir(app)?.join(relative))
+}
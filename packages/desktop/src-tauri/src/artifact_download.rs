@@ -0,0 +1,437 @@
+// Artifact download
+//
+// `download_artifact` saves an orchestrator artifact into a connected store.
+// The manifest at `/api/tenants/<tenant>/artifacts/<id>` gives the SHA-256
+// and size, and `/content` serves the bytes. The bytes go to a hidden partial
+// file next to the destination, named after the artifact and its hash. Store
+// scans skip hidden files, and the partial is on the same volume, so moving
+// it into place is a rename. An interrupted download leaves the partial
+// behind, and the next call asks for the rest with a `Range` request. If the
+// server answers with the whole body instead, the download starts over.
+//
+// A correct hash is required before the file is moved into place. A partial
+// that hashes wrong is deleted. `on_conflict` decides what happens when the
+// destination already exists; with `fail`, the verified partial is kept so a
+// retry with another policy doesn't download again.
+//
+// A verified download goes through `quarantine` on its way into place. A
+// file held there fails the download with `Quarantined`, and is kept for
+// `release_quarantined`.
+//
+// Progress goes out as `operation-progress`, and `artifact-downloaded` fires
+// with the final path. The file is recorded in the artifact index as a
+// downloaded version, with the scan verdict as its `scan` field.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::activity::{self, ActivityKind};
+use crate::artifact_metadata::ArtifactMetadata;
+use crate::artifact_upload::{self, ArtifactRecord, Transfer};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::operations::{OperationHandle, OperationKind, Operations, ResultRef};
+use crate::quarantine::{self, Admitted, Arrival, ScanVerdict, Source};
+use crate::{disk_space, dry_run};
+use crate::{file_ids, file_read, filenames, http, sessions, settings, stores, write_guard};
+
+const PARTIAL_SUFFIX: &str = ".agentvbx-part";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Fail with `Conflict` and leave the existing file alone.
+    #[default]
+    Fail,
+    Overwrite,
+    /// Save as `name (2).ext`, `name (3).ext`, and so on.
+    KeepBoth,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    sha256: String,
+    size_bytes: u64,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Payload of `artifact-downloaded`.
+#[derive(Serialize, Clone)]
+pub struct DownloadedArtifact {
+    pub tenant_id: String,
+    pub artifact_id: String,
+    pub version: Option<String>,
+    /// Where the file ended up, which `keep_both` may have renamed.
+    pub path: String,
+    pub file_id: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    /// Bytes an earlier, interrupted download had already saved.
+    pub resumed_from: u64,
+    pub scan: ScanVerdict,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Download an artifact to `dest_path`, inside a connected store.
+#[tauri::command]
+pub async fn download_artifact(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: String,
+    artifact_id: String,
+    dest_path: String,
+    on_conflict: Option<ConflictPolicy>,
+) -> CommandResult<DownloadedArtifact> {
+    dry_run::check_writable(window.label(), Some(&tenant_id), "download_artifact")?;
+    for (field, id) in [("tenant_id", &tenant_id), ("artifact_id", &artifact_id)] {
+        sessions::validate_id(field, id)
+            .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    }
+    let dest = check_destination(&dest_path)?;
+    let policy = on_conflict.unwrap_or_default();
+    // Checked again before the move; this saves a download that would fail
+    if policy == ConflictPolicy::Fail && dest.exists() {
+        return Err(exists(&dest));
+    }
+
+    let op = Operations::start_async(&app, OperationKind::ArtifactDownload, &dest_path, 1).await?;
+    let result = download(&app, &op, &tenant_id, &artifact_id, &dest, policy).await;
+    let result = op.finish_with(result, |downloaded| {
+        Some(ResultRef::File {
+            path: downloaded.path.clone(),
+        })
+    });
+    if let Ok(downloaded) = &result {
+        let _ = app.emit("artifact-downloaded", downloaded.clone());
+    }
+    result
+}
+
+// ─── Download ───────────────────────────────────────────────────────────────
+
+async fn download(
+    app: &AppHandle,
+    op: &OperationHandle,
+    tenant_id: &str,
+    artifact_id: &str,
+    dest: &Path,
+    policy: ConflictPolicy,
+) -> CommandResult<DownloadedArtifact> {
+    let settings = settings::load_settings();
+    let (client, _) = http::orchestrator_transfer_client(&settings)?;
+    let base = format!(
+        "{}/api/tenants/{}/artifacts/{}",
+        settings.orchestrator_url.trim_end_matches('/'),
+        tenant_id,
+        artifact_id
+    );
+
+    op.progress("fetching_manifest", 0, None, None);
+    let manifest: Manifest = artifact_upload::checked(client.get(&base), &base)
+        .await?
+        .json()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &base))?;
+
+    let part = partial_path(dest, artifact_id, &manifest.sha256);
+    remove_stale_partials(dest, artifact_id, &part);
+    let mut offset = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+    if offset > manifest.size_bytes {
+        fs::remove_file(&part)?;
+        offset = 0;
+    }
+    let mut resumed_from = offset;
+    disk_space::ensure(dest, manifest.size_bytes - offset)?;
+
+    if offset < manifest.size_bytes {
+        let url = format!("{}/content", base);
+        let mut request = client.get(&url);
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={}-", offset));
+        }
+        let mut response = artifact_upload::checked(request, &url).await?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&part)?;
+        if offset > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            log::info!(
+                "[artifact_download] {} ignored the range; starting over",
+                url
+            );
+            file.set_len(0)?;
+            offset = 0;
+            resumed_from = 0;
+        }
+        let label = dest.to_string_lossy();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?
+        {
+            op.check_cancelled()?;
+            file.write_all(&chunk)?;
+            offset += chunk.len() as u64;
+            op.progress(
+                "downloading",
+                offset,
+                Some(manifest.size_bytes),
+                Some(&label),
+            );
+        }
+        file.sync_all()?;
+    }
+
+    op.progress(
+        "verifying",
+        manifest.size_bytes,
+        Some(manifest.size_bytes),
+        None,
+    );
+    let sha256 = file_read::hash(&part)?;
+    if !sha256.eq_ignore_ascii_case(&manifest.sha256) {
+        let _ = fs::remove_file(&part);
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("Artifact {} doesn't match its manifest hash", artifact_id),
+        )
+        .with("artifact_id", artifact_id)
+        .with("expected", &manifest.sha256)
+        .with("received", &sha256));
+    }
+
+    // Before quarantine, which needn't scan a file that can't be placed
+    if policy == ConflictPolicy::Fail && dest.exists() {
+        return Err(exists(dest));
+    }
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let arrival = Arrival {
+        source: Source::Artifact {
+            tenant_id: tenant_id.to_string(),
+            artifact_id: artifact_id.to_string(),
+            version: manifest.version.clone(),
+        },
+        destination: dest.to_path_buf(),
+        on_conflict: policy,
+        sha256: sha256.clone(),
+    };
+    let (path, scan) = match quarantine::admit_file(&part, &name, arrival)? {
+        Admitted::Placed { path, verdict } => (path, verdict),
+        Admitted::Held(file) => {
+            quarantine::announce(app, &file);
+            return Err(quarantine::held_error(&file));
+        }
+    };
+    let record = record_download(
+        tenant_id,
+        artifact_id,
+        manifest.version,
+        &path,
+        &sha256,
+        manifest.size_bytes,
+        &scan,
+    )?;
+    let downloaded = DownloadedArtifact {
+        tenant_id: record.tenant_id,
+        artifact_id: record.artifact_id,
+        version: record.version,
+        path: record.path,
+        file_id: record.file_id,
+        sha256,
+        size_bytes: manifest.size_bytes,
+        resumed_from,
+        scan,
+    };
+    log::info!(
+        "[artifact_download] {} saved to {}{}",
+        artifact_id,
+        downloaded.path,
+        if resumed_from > 0 { " (resumed)" } else { "" }
+    );
+    Ok(downloaded)
+}
+
+/// Record a downloaded file in recent activity and the artifact index.
+pub fn record_download(
+    tenant_id: &str,
+    artifact_id: &str,
+    version: Option<String>,
+    path: &Path,
+    sha256: &str,
+    size_bytes: u64,
+    scan: &ScanVerdict,
+) -> CommandResult<ArtifactRecord> {
+    activity::record(ActivityKind::Import, path, Some(tenant_id));
+    let mut metadata = ArtifactMetadata::default();
+    metadata.fields.insert(
+        "scan".into(),
+        serde_json::to_value(scan).map_err(|e| e.to_string())?,
+    );
+    let record = ArtifactRecord {
+        artifact_id: artifact_id.to_string(),
+        version,
+        tenant_id: tenant_id.to_string(),
+        file_id: file_ids::identify_path(path).id,
+        path: path.to_string_lossy().to_string(),
+        sha256: sha256.to_string(),
+        size_bytes,
+        transfer: Transfer::Downloaded,
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        metadata,
+    };
+    artifact_upload::index_artifact(record.clone())?;
+    Ok(record)
+}
+
+/// `dest_path` with its folder resolved, if the folder is inside a store.
+pub fn check_destination(dest_path: &str) -> CommandResult<PathBuf> {
+    let dest = Path::new(dest_path);
+    let Some(name) = dest.file_name() else {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Not a file path: {}", dest_path),
+        )
+        .with("path", dest_path));
+    };
+    let folder = dest.parent().unwrap_or(Path::new(""));
+    if !folder.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Folder not found: {}", folder.display()),
+        )
+        .with("path", dest_path));
+    }
+    if stores::store_containing(folder).is_none() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not inside a connected store", dest_path),
+        )
+        .with("path", dest_path));
+    }
+    if dest.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is a folder", dest_path),
+        )
+        .with("path", dest_path));
+    }
+    Ok(folder.canonicalize()?.join(name))
+}
+
+/// Move the verified partial to `dest`, following `policy` if it's taken.
+pub fn place(part: &Path, dest: &Path, policy: ConflictPolicy) -> CommandResult<PathBuf> {
+    let _guard = write_guard::lock();
+    let target = match policy {
+        _ if !dest.exists() => dest.to_path_buf(),
+        ConflictPolicy::Overwrite => dest.to_path_buf(),
+        ConflictPolicy::KeepBoth => {
+            let name = dest.file_name().unwrap_or_default().to_string_lossy();
+            filenames::unique_path(dest.parent().unwrap_or(Path::new("")), &name)
+        }
+        ConflictPolicy::Fail => return Err(exists(dest)),
+    };
+    fs::rename(part, &target)?;
+    Ok(target)
+}
+
+fn exists(dest: &Path) -> CommandError {
+    CommandError::new(
+        ErrorCode::Conflict,
+        format!("{} already exists", dest.display()),
+    )
+    .with("path", dest.to_string_lossy())
+}
+
+fn partial_path(dest: &Path, artifact_id: &str, sha256: &str) -> PathBuf {
+    let prefix = &sha256[..sha256.len().min(16)];
+    dest.with_file_name(format!(".{}-{}{}", artifact_id, prefix, PARTIAL_SUFFIX))
+}
+
+/// Partials of an earlier version of the artifact won't be resumed.
+fn remove_stale_partials(dest: &Path, artifact_id: &str, current: &Path) {
+    let Some(Ok(entries)) = dest.parent().map(fs::read_dir) else {
+        return;
+    };
+    let prefix = format!(".{}-", artifact_id);
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let hash = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(PARTIAL_SUFFIX));
+        // Another artifact's id may start with this one's
+        if hash.is_some_and(|h| h.chars().all(|c| c.is_ascii_hexdigit())) && entry.path() != current
+        {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    #[test]
+    fn destinations_must_be_inside_a_store() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let inside = home.join("Reports/new.pdf");
+        assert!(check_destination(&inside.to_string_lossy()).is_ok());
+
+        let outside = home.join("Documents/Work/new.pdf");
+        let err = check_destination(&outside.to_string_lossy()).err().unwrap();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+
+        let escape = home.join("Reports/../Documents/Work/new.pdf");
+        assert!(check_destination(&escape.to_string_lossy()).is_err());
+        let folder = home.join("Reports/archive");
+        assert!(check_destination(&folder.to_string_lossy()).is_err());
+    }
+
+    #[test]
+    fn conflict_policies() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let dest = home.join("Reports/Summary.md");
+        let partial = |contents: &str| home.write("Reports/.a1-0.agentvbx-part", contents);
+
+        let part = partial("kept");
+        let err = place(&part, &dest, ConflictPolicy::Fail).err().unwrap();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert!(part.exists());
+
+        let kept = place(&part, &dest, ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(kept, home.join("Reports/Summary (2).md"));
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "kept");
+
+        let part = partial("replaced");
+        assert_eq!(
+            place(&part, &dest, ConflictPolicy::Overwrite).unwrap(),
+            dest
+        );
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "replaced");
+        assert!(!part.exists());
+    }
+
+    #[test]
+    fn only_the_current_versions_partial_survives() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let dest = home.join("Reports/out.bin");
+        let current = partial_path(&dest, "a1", "ffff000011112222aaaa");
+        home.write("Reports/.a1-0123456789abcdef.agentvbx-part", "old");
+        home.write("Reports/.a2-0123456789abcdef.agentvbx-part", "other");
+        home.write("Reports/.a1-b-0123456789abcdef.agentvbx-part", "other");
+        fs::write(&current, "new").unwrap();
+
+        remove_stale_partials(&dest, "a1", &current);
+        assert!(current.exists());
+        assert!(!home
+            .join("Reports/.a1-0123456789abcdef.agentvbx-part")
+            .exists());
+        assert!(home
+            .join("Reports/.a2-0123456789abcdef.agentvbx-part")
+            .exists());
+        assert!(home
+            .join("Reports/.a1-b-0123456789abcdef.agentvbx-part")
+            .exists());
+    }
+}
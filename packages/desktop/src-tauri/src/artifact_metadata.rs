@@ -0,0 +1,378 @@
+// Artifact metadata
+//
+// Tags ("deliverable", "draft", "client-x") and key/value fields on the
+// artifacts in the index (see `artifact_upload`), for finding them again.
+// `set_artifact_metadata` adds and removes tags and sets fields on every
+// local version of an artifact. Each change is saved as a new metadata
+// version with when it happened, who made it (the `actor` given, or the
+// calling window), and the SHA-256 of the content it applied to, so "who
+// tagged this when" can be answered from `history`. A newer content version
+// of a file keeps its metadata. Metadata lives only in the index: removing
+// a tag never touches the files.
+//
+// `query_artifacts` filters a tenant's artifacts by tags (all must be
+// present), field equality, when the version was recorded, and the store
+// the file is in, and pages the results newest first.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::artifact_upload::{self, ArtifactRecord};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, stores};
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_TAG_CHARS: usize = 64;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct ArtifactMetadata {
+    pub tags: BTreeSet<String>,
+    pub fields: BTreeMap<String, serde_json::Value>,
+    /// Every change, oldest first.
+    pub history: Vec<MetadataVersion>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MetadataVersion {
+    /// Counts from 1.
+    pub version: usize,
+    pub changed_at: String,
+    pub changed_by: String,
+    /// The content version the change was made on.
+    pub sha256: String,
+    /// Tags and fields after the change.
+    pub tags: BTreeSet<String>,
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct MetadataPatch {
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    /// Fields to set; `null` removes one.
+    pub fields: BTreeMap<String, serde_json::Value>,
+    /// Recorded as `changed_by`, instead of the calling window.
+    pub actor: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ArtifactFilter {
+    /// Artifacts with all of these tags.
+    pub tags: Vec<String>,
+    /// Artifacts whose fields equal these.
+    pub fields: BTreeMap<String, serde_json::Value>,
+    /// RFC 3339 bounds on when the version was recorded, inclusive.
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    /// Versions whose file is in this store.
+    pub store_id: Option<String>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ArtifactPage {
+    pub artifacts: Vec<ArtifactRecord>,
+    pub total: usize,
+    /// Pass back as `offset` for the next page; `None` on the last one.
+    pub next_offset: Option<usize>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Apply `patch` to every local version of the artifact; returns its new
+/// metadata. A patch that changes nothing adds no version.
+#[tauri::command]
+pub fn set_artifact_metadata(
+    window: tauri::Window,
+    tenant_id: String,
+    artifact_id: String,
+    patch: MetadataPatch,
+) -> CommandResult<ArtifactMetadata> {
+    let actor = patch
+        .actor
+        .clone()
+        .unwrap_or_else(|| window.label().to_string());
+    let metadata = apply(&tenant_id, &artifact_id, &patch, &actor)?;
+    audit::record(
+        "set_artifact_metadata",
+        Some(&tenant_id),
+        serde_json::json!({
+            "artifact_id": artifact_id,
+            "version": metadata.history.last().map(|v| v.version),
+        }),
+    );
+    Ok(metadata)
+}
+
+#[tauri::command]
+pub fn query_artifacts(tenant_id: String, filter: ArtifactFilter) -> CommandResult<ArtifactPage> {
+    let after = filter.created_after.as_deref().map(timestamp).transpose()?;
+    let before = filter
+        .created_before
+        .as_deref()
+        .map(timestamp)
+        .transpose()?;
+    let store = filter
+        .store_id
+        .as_deref()
+        .map(stores::find_store)
+        .transpose()?;
+    let tags = tags(&filter.tags)?;
+
+    let mut matched: Vec<ArtifactRecord> = artifact_upload::load_index()
+        .into_iter()
+        .filter(|r| r.tenant_id == tenant_id)
+        .filter(|r| tags.is_subset(&r.metadata.tags))
+        .filter(|r| {
+            filter
+                .fields
+                .iter()
+                .all(|(key, value)| r.metadata.fields.get(key) == Some(value))
+        })
+        .filter(|r| {
+            let recorded = timestamp(&r.recorded_at).ok();
+            after.is_none_or(|after| recorded.is_some_and(|at| at >= after))
+                && before.is_none_or(|before| recorded.is_some_and(|at| at <= before))
+        })
+        .filter(|r| {
+            store
+                .as_ref()
+                .is_none_or(|s| Path::new(&r.path).starts_with(&s.path))
+        })
+        .collect();
+    matched.sort_by(|a, b| b.recorded_at.cmp(&a.recorded_at));
+
+    let total = matched.len();
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = filter.offset;
+    Ok(ArtifactPage {
+        artifacts: matched.into_iter().skip(offset).take(limit).collect(),
+        total,
+        next_offset: (offset + limit < total).then_some(offset + limit),
+    })
+}
+
+// ─── Changes ────────────────────────────────────────────────────────────────
+
+fn apply(
+    tenant_id: &str,
+    artifact_id: &str,
+    patch: &MetadataPatch,
+    actor: &str,
+) -> CommandResult<ArtifactMetadata> {
+    let add = tags(&patch.add_tags)?;
+    let remove = tags(&patch.remove_tags)?;
+    if let Some(key) = patch.fields.keys().find(|k| k.trim().is_empty()) {
+        return Err(
+            CommandError::new(ErrorCode::InvalidInput, "Metadata fields need a name")
+                .with("field", key),
+        );
+    }
+    artifact_upload::update_index(|index| {
+        let versions: Vec<&mut ArtifactRecord> = index
+            .iter_mut()
+            .filter(|r| r.tenant_id == tenant_id && r.artifact_id == artifact_id)
+            .collect();
+        if versions.is_empty() {
+            return Err(CommandError::new(
+                ErrorCode::NotFound,
+                format!("Unknown artifact: {}", artifact_id),
+            )
+            .with("artifact_id", artifact_id)
+            .with("tenant_id", tenant_id));
+        }
+        let mut updated = ArtifactMetadata::default();
+        for record in versions {
+            let metadata = &mut record.metadata;
+            let mut tags = metadata.tags.clone();
+            tags.extend(add.iter().cloned());
+            tags.retain(|t| !remove.contains(t));
+            let mut fields = metadata.fields.clone();
+            for (key, value) in &patch.fields {
+                match value {
+                    serde_json::Value::Null => fields.remove(key),
+                    value => fields.insert(key.clone(), value.clone()),
+                };
+            }
+            if tags != metadata.tags || fields != metadata.fields {
+                metadata.history.push(MetadataVersion {
+                    version: metadata.history.len() + 1,
+                    changed_at: Utc::now().to_rfc3339(),
+                    changed_by: actor.to_string(),
+                    sha256: record.sha256.clone(),
+                    tags: tags.clone(),
+                    fields: fields.clone(),
+                });
+                metadata.tags = tags;
+                metadata.fields = fields;
+            }
+            updated = metadata.clone();
+        }
+        Ok(updated)
+    })
+}
+
+impl ArtifactMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.fields.is_empty() && self.history.is_empty()
+    }
+}
+
+/// Tags trimmed and lowercased, so `Draft` and `draft` are one tag.
+fn tags(raw: &[String]) -> CommandResult<BTreeSet<String>> {
+    raw.iter()
+        .map(|tag| {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() || tag.chars().count() > MAX_TAG_CHARS {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid tag: {:?}", tag),
+                )
+                .with("tag", tag));
+            }
+            Ok(tag)
+        })
+        .collect()
+}
+
+fn timestamp(raw: &str) -> CommandResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid timestamp: {:?}", raw),
+            )
+            .with("timestamp", raw)
+        })
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact_upload::Transfer;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn index(home: &TestHome, file: &str, artifact_id: &str, recorded_at: &str) {
+        artifact_upload::index_artifact(ArtifactRecord {
+            artifact_id: artifact_id.into(),
+            version: None,
+            tenant_id: "acme".into(),
+            file_id: file.into(),
+            path: home.join(file).to_string_lossy().to_string(),
+            sha256: format!("sha-{}", recorded_at),
+            size_bytes: 1,
+            transfer: Transfer::Uploaded,
+            recorded_at: recorded_at.into(),
+            metadata: ArtifactMetadata::default(),
+        })
+        .unwrap();
+    }
+
+    fn patch(add: &[&str], remove: &[&str]) -> MetadataPatch {
+        MetadataPatch {
+            add_tags: add.iter().map(|t| t.to_string()).collect(),
+            remove_tags: remove.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changes_are_versioned_and_survive_new_content() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        index(&home, "Reports/q1.pdf", "art-1", "2026-01-05T10:00:00Z");
+
+        let mut first = patch(&["Draft", "client-x"], &[]);
+        first.fields.insert("owner".into(), "dana".into());
+        apply("acme", "art-1", &first, "main").unwrap();
+        let metadata = apply("acme", "art-1", &patch(&[], &["draft"]), "automation").unwrap();
+        assert_eq!(metadata.tags, BTreeSet::from(["client-x".to_string()]));
+        assert_eq!(metadata.history.len(), 2);
+        assert_eq!(metadata.history[0].changed_by, "main");
+        assert!(metadata.history[0].tags.contains("draft"));
+        // Nothing changes, so no version is added
+        let again = apply("acme", "art-1", &patch(&[], &["draft"]), "main").unwrap();
+        assert_eq!(again.history.len(), 2);
+
+        // A newer upload of the file keeps the metadata
+        index(&home, "Reports/q1.pdf", "art-1", "2026-02-01T10:00:00Z");
+        let record = &artifact_upload::load_index()[0];
+        assert_eq!(record.metadata, metadata);
+        assert!(home.join("Reports/q1.pdf").exists());
+
+        assert!(apply("acme", "art-2", &patch(&["x"], &[]), "main").is_err());
+        assert!(apply("globex", "art-1", &patch(&["x"], &[]), "main").is_err());
+        assert!(apply("acme", "art-1", &patch(&[" "], &[]), "main").is_err());
+    }
+
+    #[test]
+    fn queries_combine_filters_and_page() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        index(&home, "Reports/q1.pdf", "art-1", "2026-01-05T10:00:00Z");
+        index(&home, "Reports/Summary.md", "art-2", "2026-02-05T10:00:00Z");
+        index(
+            &home,
+            "Elsewhere/notes.txt",
+            "art-3",
+            "2026-03-05T10:00:00Z",
+        );
+        for id in ["art-1", "art-2", "art-3"] {
+            apply("acme", id, &patch(&["deliverable"], &[]), "main").unwrap();
+        }
+        apply("acme", "art-2", &patch(&["client-x"], &[]), "main").unwrap();
+
+        let query = |filter: ArtifactFilter| query_artifacts("acme".into(), filter).unwrap();
+        let tagged = query(ArtifactFilter {
+            tags: vec!["Deliverable".into()],
+            limit: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(tagged.total, 3);
+        assert_eq!(tagged.artifacts[0].artifact_id, "art-3");
+        assert_eq!(tagged.next_offset, Some(2));
+
+        let both = query(ArtifactFilter {
+            tags: vec!["deliverable".into(), "client-x".into()],
+            ..Default::default()
+        });
+        assert_eq!(both.artifacts.len(), 1);
+        assert_eq!(both.artifacts[0].artifact_id, "art-2");
+
+        let ranged = query(ArtifactFilter {
+            created_after: Some("2026-01-10T00:00:00Z".into()),
+            created_before: Some("2026-02-28T00:00:00Z".into()),
+            ..Default::default()
+        });
+        assert_eq!(ranged.total, 1);
+
+        let store_id = stores::load_registry()
+            .into_iter()
+            .find(|s| s.name == "Reports")
+            .unwrap()
+            .id;
+        let in_store = query(ArtifactFilter {
+            store_id: Some(store_id),
+            ..Default::default()
+        });
+        assert_eq!(in_store.total, 2);
+
+        let bad = query_artifacts(
+            "acme".into(),
+            ArtifactFilter {
+                created_after: Some("last week".into()),
+                ..Default::default()
+            },
+        );
+        assert!(bad.is_err());
+    }
+}
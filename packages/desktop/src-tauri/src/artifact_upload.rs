@@ -0,0 +1,860 @@
+// Artifact upload
+//
+// `upload_artifact` pushes a file from a connected store to the tenant's
+// orchestrator. The upload is queued in `artifacts/uploads.json` (data directory)
+// first and sent right away if it can be. If the orchestrator is unreachable,
+// or uploads are paused on a metered connection, it stays `queued`. The
+// `artifact_uploads` job retries it later. Every status change is emitted as
+// `artifact-upload-status`, and chunk progress as `operation-progress`.
+//
+// The protocol, under `/api/tenants/<tenant>/artifacts/uploads`:
+// 1. `POST` with the file's SHA-256, size, and metadata. If the server
+//    already has that content it answers with an `artifact_id` and nothing
+//    is sent. Otherwise it answers with an `upload_id` and a starting
+//    `offset`.
+// 2. `PATCH /<upload_id>` sends `CHUNK_BYTES` at a time from `Upload-Offset`.
+//    The server replies with its new `Upload-Offset`.
+// 3. `POST /<upload_id>/complete` returns the `artifact_id` and the SHA-256
+//    of what the server received. A hash that differs from the local one
+//    fails the upload with `Conflict`.
+// After a restart or a dropped connection, `HEAD /<upload_id>` asks for the
+// server's `Upload-Offset` and sending resumes there. A 404 means the server
+// dropped the upload, so it is negotiated again.
+//
+// When the tenant's policy redacts, a text file is sent as a redacted copy
+// staged in `artifacts/redacted/` (see `redaction`), and the hash and size
+// are the copy's. The copy is removed once the upload finishes.
+//
+// Finished uploads are recorded in `artifacts/index.json` by file
+// id, so the remote artifact follows the file through renames. Downloads from
+// `artifact_download` are recorded there too.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::artifact_metadata::ArtifactMetadata;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::operations::{OperationHandle, OperationKind, Operations, ResultRef};
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+use crate::{
+    file_ids, file_read, http, integrity, redaction, sessions, settings, stores, system_state,
+};
+
+const CHUNK_BYTES: u64 = 8 * 1024 * 1024;
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Serializes read-modify-write of the queue and the index.
+static ARTIFACTS_LOCK: Mutex<()> = Mutex::new(());
+/// Uploads being sent right now.
+static IN_FLIGHT: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    /// Waiting to be sent or resumed; `error` says why, if it was tried.
+    Queued,
+    Uploaded,
+    /// Rejected or corrupted; not retried until `upload_artifact` is called
+    /// for the file again.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArtifactUpload {
+    pub upload_id: String,
+    pub tenant_id: String,
+    pub path: String,
+    pub file_id: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub metadata: serde_json::Value,
+    pub status: UploadStatus,
+    /// Bytes the server has acknowledged.
+    pub offset: u64,
+    pub artifact_id: Option<String>,
+    /// The server already had the content, so nothing was sent.
+    pub deduplicated: bool,
+    /// Matches `redaction` replaced in what is sent.
+    #[serde(default)]
+    pub redactions: usize,
+    pub error: Option<String>,
+    pub queued_at: String,
+    allow_metered: bool,
+    /// The redacted copy that is sent instead of `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    staged_path: Option<String>,
+    /// The server's `upload_id`, once negotiated.
+    session: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Transfer {
+    Uploaded,
+    Downloaded,
+}
+
+/// A local file that is a version of an orchestrator artifact.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArtifactRecord {
+    pub artifact_id: String,
+    /// The orchestrator's version label, for downloads that had one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub tenant_id: String,
+    pub file_id: String,
+    pub path: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub transfer: Transfer,
+    pub recorded_at: String,
+    /// Tags and fields; see `artifact_metadata`.
+    #[serde(default, skip_serializing_if = "ArtifactMetadata::is_empty")]
+    pub metadata: ArtifactMetadata,
+}
+
+#[derive(Serialize)]
+struct Negotiate<'a> {
+    sha256: &'a str,
+    size_bytes: u64,
+    name: String,
+    mime_type: String,
+    file_id: &'a str,
+    metadata: &'a serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Negotiated {
+    /// Set when the server already has the content.
+    artifact_id: Option<String>,
+    upload_id: Option<String>,
+    #[serde(default)]
+    offset: u64,
+}
+
+#[derive(Deserialize)]
+struct Completed {
+    artifact_id: String,
+    sha256: String,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Queue a file for upload and try to send it now. Returns the upload,
+/// `uploaded` or, if it has to wait, `queued`.
+#[tauri::command]
+pub async fn upload_artifact(
+    app: AppHandle,
+    tenant_id: String,
+    path: String,
+    metadata: Option<serde_json::Value>,
+    allow_metered: Option<bool>,
+) -> CommandResult<ArtifactUpload> {
+    sessions::validate_id("tenant_id", &tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    let file = Path::new(&path);
+    if !file.is_file() {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+                .with("path", &path),
+        );
+    }
+    if stores::store_containing(file).is_none() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not inside a connected store", path),
+        )
+        .with("path", &path));
+    }
+
+    let upload = enqueue(
+        &tenant_id,
+        file,
+        metadata.unwrap_or_else(|| serde_json::json!({})),
+        allow_metered.unwrap_or(false),
+    )?;
+    emit_status(&app, &upload);
+    send(&app, &upload.upload_id).await
+}
+
+/// Uploads that are queued or failed, oldest first.
+#[tauri::command]
+pub fn list_artifact_uploads(tenant_id: Option<String>) -> Vec<ArtifactUpload> {
+    load_queue()
+        .into_iter()
+        .filter(|u| tenant_id.as_ref().is_none_or(|t| *t == u.tenant_id))
+        .collect()
+}
+
+/// The tenant's uploaded artifacts, by file id.
+#[tauri::command]
+pub fn list_artifacts(tenant_id: String) -> Vec<ArtifactRecord> {
+    load_index()
+        .into_iter()
+        .filter(|r| r.tenant_id == tenant_id)
+        .collect()
+}
+
+// ─── Queue ──────────────────────────────────────────────────────────────────
+
+/// Add `file` to the queue, or return its entry if the same content is
+/// already queued for the tenant. A failed entry is queued again.
+fn enqueue(
+    tenant_id: &str,
+    file: &Path,
+    metadata: serde_json::Value,
+    allow_metered: bool,
+) -> CommandResult<ArtifactUpload> {
+    let path = file.canonicalize()?.to_string_lossy().to_string();
+    let (staged, redactions) = match redaction::for_tenant(tenant_id)? {
+        Some(redactor) => stage_redacted(&redactor, file)?,
+        None => (None, 0),
+    };
+    let sent = staged.as_deref().unwrap_or(file);
+    let sha256 = file_read::hash(sent)?;
+    let size_bytes = fs::metadata(sent)?.len();
+    let file_id = file_ids::identify_path(file).id;
+
+    let _guard = ARTIFACTS_LOCK.lock().unwrap();
+    let mut queue = load_queue();
+    if let Some(existing) = queue
+        .iter_mut()
+        .find(|u| u.tenant_id == tenant_id && u.path == path && u.sha256 == sha256)
+    {
+        if existing.status == UploadStatus::Failed {
+            existing.status = UploadStatus::Queued;
+            existing.session = None;
+            existing.offset = 0;
+            existing.error = None;
+        }
+        existing.metadata = metadata;
+        existing.allow_metered |= allow_metered;
+        let upload = existing.clone();
+        save_json(&queue_path()?, &queue)?;
+        return Ok(upload);
+    }
+
+    let upload = ArtifactUpload {
+        upload_id: format!(
+            "upl-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ),
+        tenant_id: tenant_id.to_string(),
+        path,
+        file_id,
+        sha256,
+        size_bytes,
+        metadata,
+        status: UploadStatus::Queued,
+        offset: 0,
+        artifact_id: None,
+        deduplicated: false,
+        redactions,
+        error: None,
+        queued_at: chrono::Utc::now().to_rfc3339(),
+        allow_metered,
+        staged_path: staged.map(|p| p.to_string_lossy().to_string()),
+        session: None,
+    };
+    queue.push(upload.clone());
+    save_json(&queue_path()?, &queue)?;
+    Ok(upload)
+}
+
+/// Try to send one queued upload under an operation. Failures that a later
+/// retry may get past leave it queued and come back as `Ok`.
+async fn send(app: &AppHandle, upload_id: &str) -> CommandResult<ArtifactUpload> {
+    let queued = find_upload(upload_id)?;
+    let op = Operations::start_async(app, OperationKind::ArtifactUpload, &queued.path, 1).await?;
+    let result = attempt(app, &op, upload_id).await;
+    let result = op.finish_with(result, |upload| {
+        Some(ResultRef::Artifact {
+            upload_id: upload.upload_id.clone(),
+        })
+    });
+    match result {
+        Err(e) if retriable(&e) => find_upload(upload_id),
+        result => result,
+    }
+}
+
+async fn attempt(
+    app: &AppHandle,
+    op: &OperationHandle,
+    upload_id: &str,
+) -> CommandResult<ArtifactUpload> {
+    {
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        if !in_flight
+            .get_or_insert_with(HashSet::new)
+            .insert(upload_id.to_string())
+        {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyRunning,
+                format!("Upload {} is already being sent", upload_id),
+            )
+            .with("upload_id", upload_id));
+        }
+    }
+    let mut upload = find_upload(upload_id)?;
+    let result = push(op, &mut upload).await;
+    if let Some(in_flight) = IN_FLIGHT.lock().unwrap().as_mut() {
+        in_flight.remove(upload_id);
+    }
+
+    match &result {
+        Ok(()) => {
+            upload.status = UploadStatus::Uploaded;
+            upload.error = None;
+            record(&upload)?;
+            log::info!(
+                "[artifact_upload] {} is artifact {}{}",
+                upload.path,
+                upload.artifact_id.as_deref().unwrap_or_default(),
+                if upload.deduplicated {
+                    " (already on the server)"
+                } else {
+                    ""
+                }
+            );
+        }
+        Err(e) if retriable(e) => {
+            log::info!("[artifact_upload] {} stays queued: {}", upload.upload_id, e);
+            upload.error = Some(e.message.clone());
+            update_queued(&upload)?;
+        }
+        Err(e) => {
+            log::warn!("[artifact_upload] {} failed: {}", upload.upload_id, e);
+            upload.status = UploadStatus::Failed;
+            upload.error = Some(e.message.clone());
+            update_queued(&upload)?;
+        }
+    }
+    emit_status(app, &upload);
+    result.map(|()| upload)
+}
+
+/// Network trouble and throttling pass; rejections and bad content don't.
+fn retriable(error: &CommandError) -> bool {
+    match error.code {
+        ErrorCode::Throttled | ErrorCode::AlreadyRunning => true,
+        ErrorCode::Network => match error.params.get("status").and_then(|s| s.as_u64()) {
+            None => true,
+            Some(status) => status >= 500 || status == 408 || status == 429,
+        },
+        _ => false,
+    }
+}
+
+// ─── Protocol ───────────────────────────────────────────────────────────────
+
+async fn push(op: &OperationHandle, upload: &mut ArtifactUpload) -> CommandResult<()> {
+    let source = PathBuf::from(upload.staged_path.as_deref().unwrap_or(&upload.path));
+    let size = fs::metadata(&source).map(|m| m.len()).ok();
+    if size != Some(upload.size_bytes) {
+        return Err(changed(upload));
+    }
+    system_state::check_upload_allowed(upload.allow_metered)?;
+
+    let settings = settings::load_settings();
+    // A chunk on a slow link can take longer than the default timeout
+    let (client, _) = http::orchestrator_transfer_client(&settings)?;
+    let base = format!(
+        "{}/api/tenants/{}/artifacts/uploads",
+        settings.orchestrator_url.trim_end_matches('/'),
+        upload.tenant_id
+    );
+
+    if let Some(session) = &upload.session {
+        let url = format!("{}/{}", base, session);
+        op.progress("resuming", upload.offset, Some(upload.size_bytes), None);
+        match query_offset(&client, &url).await? {
+            Some(offset) => upload.offset = offset,
+            None => {
+                log::info!("[artifact_upload] server dropped {}; starting over", url);
+                upload.session = None;
+                upload.offset = 0;
+            }
+        }
+    }
+    let session = match upload.session.clone() {
+        Some(session) => session,
+        None => {
+            op.progress("negotiating", 0, Some(upload.size_bytes), None);
+            let negotiated = negotiate(&client, &base, upload).await?;
+            if let Some(artifact_id) = negotiated.artifact_id {
+                upload.artifact_id = Some(artifact_id);
+                upload.deduplicated = true;
+                upload.offset = upload.size_bytes;
+                return Ok(());
+            }
+            let Some(session) = negotiated.upload_id else {
+                return Err(CommandError::new(
+                    ErrorCode::Network,
+                    "The orchestrator returned neither an artifact nor an upload id",
+                )
+                .with("url", &base));
+            };
+            upload.session = Some(session.clone());
+            upload.offset = negotiated.offset;
+            update_queued(upload)?;
+            session
+        }
+    };
+
+    let url = format!("{}/{}", base, session);
+    let mut reader = File::open(&source)?;
+    while upload.offset < upload.size_bytes {
+        // The connection can change mid-upload; the offset stays saved
+        system_state::check_upload_allowed(upload.allow_metered)?;
+        op.check_cancelled()?;
+        let body = read_range(&mut reader, upload.offset, CHUNK_BYTES)?;
+        let sent = body.len() as u64;
+        let response = checked(
+            client
+                .patch(&url)
+                .header("Content-Type", "application/offset+octet-stream")
+                .header("Upload-Offset", upload.offset)
+                .body(body),
+            &url,
+        )
+        .await?;
+        upload.offset = upload_offset(&response).unwrap_or(upload.offset + sent);
+        update_queued(upload)?;
+        op.progress(
+            "uploading",
+            upload.offset,
+            Some(upload.size_bytes),
+            Some(&upload.path),
+        );
+    }
+
+    op.progress(
+        "verifying",
+        upload.size_bytes,
+        Some(upload.size_bytes),
+        None,
+    );
+    let complete_url = format!("{}/complete", url);
+    let completed: Completed = checked(client.post(&complete_url), &complete_url)
+        .await?
+        .json()
+        .await
+        .map_err(|e| {
+            CommandError::new(ErrorCode::Network, e.to_string()).with("url", &complete_url)
+        })?;
+    if !completed.sha256.eq_ignore_ascii_case(&upload.sha256) {
+        // Start from scratch if the upload is tried again
+        upload.session = None;
+        upload.offset = 0;
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!(
+                "The orchestrator received different content for {}",
+                upload.path
+            ),
+        )
+        .with("path", &upload.path)
+        .with("expected", &upload.sha256)
+        .with("received", &completed.sha256));
+    }
+    upload.artifact_id = Some(completed.artifact_id);
+    Ok(())
+}
+
+async fn negotiate(
+    client: &reqwest::Client,
+    url: &str,
+    upload: &ArtifactUpload,
+) -> CommandResult<Negotiated> {
+    let name = Path::new(&upload.path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let request = client.post(url).json(&Negotiate {
+        sha256: &upload.sha256,
+        size_bytes: upload.size_bytes,
+        mime_type: crate::guess_mime(&name),
+        name,
+        file_id: &upload.file_id,
+        metadata: &upload.metadata,
+    });
+    checked(request, url)
+        .await?
+        .json()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", url))
+}
+
+/// The server's offset for an upload, or `None` if it no longer has it.
+async fn query_offset(client: &reqwest::Client, url: &str) -> CommandResult<Option<u64>> {
+    let response = client
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", url))?;
+    match response.status() {
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Ok(None),
+        status if status.is_success() => Ok(Some(upload_offset(&response).unwrap_or(0))),
+        status => Err(http_error(url, status)),
+    }
+}
+
+/// Send `request`, turning transport failures and error statuses into `Network`.
+pub async fn checked(
+    request: reqwest::RequestBuilder,
+    url: &str,
+) -> CommandResult<reqwest::Response> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", url))?;
+    if !response.status().is_success() {
+        return Err(http_error(url, response.status()));
+    }
+    Ok(response)
+}
+
+fn http_error(url: &str, status: reqwest::StatusCode) -> CommandError {
+    CommandError::new(
+        ErrorCode::Network,
+        format!("Artifact upload failed: HTTP {}", status),
+    )
+    .with("url", url)
+    .with("status", status.as_u16())
+}
+
+fn upload_offset(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get("Upload-Offset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn changed(upload: &ArtifactUpload) -> CommandError {
+    CommandError::new(
+        ErrorCode::Conflict,
+        format!("{} changed since it was queued for upload", upload.path),
+    )
+    .with("path", &upload.path)
+    .with("upload_id", &upload.upload_id)
+}
+
+fn emit_status(app: &AppHandle, upload: &ArtifactUpload) {
+    let _ = app.emit("artifact-upload-status", upload.clone());
+}
+
+// ─── Scheduler ──────────────────────────────────────────────────────────────
+
+/// Register the `artifact_uploads` job, which retries queued uploads.
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(
+        "artifact_uploads",
+        "Send queued artifact uploads",
+        Schedule::Every(RETRY_INTERVAL),
+        JobLoad::Heavy,
+        true,
+        |app| tauri::async_runtime::block_on(flush(app)),
+    );
+}
+
+async fn flush(app: &AppHandle) -> Result<Option<String>, String> {
+    let queued: Vec<String> = load_queue()
+        .into_iter()
+        .filter(|u| u.status == UploadStatus::Queued)
+        .map(|u| u.upload_id)
+        .collect();
+    if queued.is_empty() {
+        return Ok(None);
+    }
+    let (mut sent, mut failed) = (0, 0);
+    for upload_id in &queued {
+        match send(app, upload_id).await {
+            Ok(upload) if upload.status == UploadStatus::Uploaded => sent += 1,
+            Ok(_) => {}
+            Err(_) => failed += 1,
+        }
+    }
+    let waiting = queued.len() - sent - failed;
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} queued uploads failed",
+            failed,
+            queued.len()
+        ));
+    }
+    Ok(Some(format!("{} uploaded, {} still queued", sent, waiting)))
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn artifacts_dir() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("artifacts"))
+}
+
+fn queue_path() -> CommandResult<PathBuf> {
+    Ok(artifacts_dir()?.join("uploads.json"))
+}
+
+fn index_path() -> CommandResult<PathBuf> {
+    Ok(artifacts_dir()?.join("index.json"))
+}
+
+fn load_queue() -> Vec<ArtifactUpload> {
+    queue_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+pub fn load_index() -> Vec<ArtifactRecord> {
+    index_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn find_upload(upload_id: &str) -> CommandResult<ArtifactUpload> {
+    load_queue()
+        .into_iter()
+        .find(|u| u.upload_id == upload_id)
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::NotFound,
+                format!("Unknown upload: {}", upload_id),
+            )
+            .with("upload_id", upload_id)
+        })
+}
+
+/// Save a queued upload's progress or outcome.
+fn update_queued(upload: &ArtifactUpload) -> CommandResult<()> {
+    let _guard = ARTIFACTS_LOCK.lock().unwrap();
+    let mut queue = load_queue();
+    if let Some(entry) = queue.iter_mut().find(|u| u.upload_id == upload.upload_id) {
+        *entry = upload.clone();
+        save_json(&queue_path()?, &queue)?;
+    }
+    Ok(())
+}
+
+/// Move a finished upload from the queue to the index.
+fn record(upload: &ArtifactUpload) -> CommandResult<()> {
+    let _guard = ARTIFACTS_LOCK.lock().unwrap();
+    index_locked(ArtifactRecord {
+        artifact_id: upload.artifact_id.clone().unwrap_or_default(),
+        version: None,
+        tenant_id: upload.tenant_id.clone(),
+        file_id: upload.file_id.clone(),
+        path: upload.path.clone(),
+        sha256: upload.sha256.clone(),
+        size_bytes: upload.size_bytes,
+        transfer: Transfer::Uploaded,
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        metadata: ArtifactMetadata::default(),
+    })?;
+
+    let mut queue = load_queue();
+    queue.retain(|u| u.upload_id != upload.upload_id);
+    save_json(&queue_path()?, &queue)?;
+    if let Some(staged) = &upload.staged_path {
+        let _ = fs::remove_file(staged);
+    }
+    Ok(())
+}
+
+/// Write a redacted copy of a text file to send in its place. `None` when
+/// nothing in it matched, or it isn't UTF-8 text.
+fn stage_redacted(
+    redactor: &redaction::Redactor,
+    file: &Path,
+) -> CommandResult<(Option<PathBuf>, usize)> {
+    let Ok(text) = String::from_utf8(fs::read(file)?) else {
+        return Ok((None, 0));
+    };
+    let redacted = redactor.redact(&text);
+    if redacted.redactions == 0 {
+        return Ok((None, 0));
+    }
+    use sha2::{Digest, Sha256};
+    let dir = artifacts_dir()?.join("redacted");
+    fs::create_dir_all(&dir)?;
+    let staged = dir.join(hex::encode(Sha256::digest(redacted.text.as_bytes())));
+    fs::write(&staged, redacted.text)?;
+    log::info!(
+        "[artifact_upload] {} redactions in {}",
+        redacted.redactions,
+        file.display()
+    );
+    Ok((Some(staged), redacted.redactions))
+}
+
+/// Add a record to the index. A newer record for the same file replaces the
+/// old one, and keeps its tags and fields.
+pub fn index_artifact(record: ArtifactRecord) -> CommandResult<()> {
+    let _guard = ARTIFACTS_LOCK.lock().unwrap();
+    index_locked(record)
+}
+
+fn index_locked(mut record: ArtifactRecord) -> CommandResult<()> {
+    let mut index = load_index();
+    let same_file =
+        |r: &ArtifactRecord| r.tenant_id == record.tenant_id && r.file_id == record.file_id;
+    if let Some(previous) = index.iter().find(|r| same_file(r)) {
+        // The new record's own tags and fields go on top
+        let mut metadata = previous.metadata.clone();
+        metadata.tags.append(&mut record.metadata.tags);
+        metadata.fields.append(&mut record.metadata.fields);
+        record.metadata = metadata;
+    }
+    index.retain(|r| !same_file(r));
+    index.push(record);
+    save_json(&index_path()?, &index)?;
+    Ok(())
+}
+
+/// Change index records under the lock; `change` returning an error saves
+/// nothing.
+pub fn update_index<T>(
+    change: impl FnOnce(&mut Vec<ArtifactRecord>) -> CommandResult<T>,
+) -> CommandResult<T> {
+    let _guard = ARTIFACTS_LOCK.lock().unwrap();
+    let mut index = load_index();
+    let changed = change(&mut index)?;
+    save_json(&index_path()?, &index)?;
+    Ok(changed)
+}
+
+fn read_range(reader: &mut File, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn save_json<T>(path: &Path, value: &[T]) -> CommandResult<()>
+where
+    [T]: integrity::Validate + Serialize,
+    T: Clone + serde::de::DeserializeOwned,
+{
+    integrity::persist_json(path, value, None)
+}
+
+impl integrity::Validate for [ArtifactUpload] {
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .iter()
+            .find(|u| u.upload_id.is_empty() || u.tenant_id.is_empty())
+        {
+            Some(upload) => Err(format!("upload of {} has no id or tenant", upload.path)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl integrity::Validate for [ArtifactRecord] {
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .iter()
+            .find(|r| r.file_id.is_empty() || r.tenant_id.is_empty())
+        {
+            Some(record) => Err(format!(
+                "record for {} has no file id or tenant",
+                record.path
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    #[test]
+    fn queueing_the_same_content_again_reuses_the_entry() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let file = home.join("Reports/Summary.md");
+        let first = enqueue("acme", &file, serde_json::json!({}), false).unwrap();
+        let again = enqueue("acme", &file, serde_json::json!({"v": 2}), true).unwrap();
+        assert_eq!(again.upload_id, first.upload_id);
+        assert_eq!(again.metadata["v"], 2);
+        assert_eq!(list_artifact_uploads(Some("acme".into())).len(), 1);
+
+        // Edited content is a new upload
+        home.write("Reports/Summary.md", "revised");
+        let edited = enqueue("acme", &file, serde_json::json!({}), false).unwrap();
+        assert_ne!(edited.upload_id, first.upload_id);
+        assert_eq!(list_artifact_uploads(None).len(), 2);
+    }
+
+    #[test]
+    fn a_failed_upload_is_queued_again_from_the_start() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let file = home.join("Reports/q1.pdf");
+        let mut upload = enqueue("acme", &file, serde_json::json!({}), false).unwrap();
+        upload.status = UploadStatus::Failed;
+        upload.session = Some("srv-1".into());
+        upload.offset = 3;
+        update_queued(&upload).unwrap();
+
+        let retried = enqueue("acme", &file, serde_json::json!({}), false).unwrap();
+        assert_eq!(retried.status, UploadStatus::Queued);
+        assert_eq!((retried.session, retried.offset), (None, 0));
+    }
+
+    #[test]
+    fn finished_uploads_move_to_the_index() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let mut upload = enqueue(
+            "acme",
+            &home.join("Reports/q1.pdf"),
+            serde_json::json!({}),
+            false,
+        )
+        .unwrap();
+        upload.artifact_id = Some("art-1".into());
+        record(&upload).unwrap();
+
+        assert!(list_artifact_uploads(None).is_empty());
+        let artifacts = list_artifacts("acme".into());
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].artifact_id, "art-1");
+        assert_eq!(artifacts[0].file_id, upload.file_id);
+        assert_eq!(artifacts[0].transfer, Transfer::Uploaded);
+        assert!(list_artifacts("other".into()).is_empty());
+    }
+
+    #[test]
+    fn only_transient_failures_keep_an_upload_queued() {
+        let network = |status: Option<u16>| {
+            let e = CommandError::new(ErrorCode::Network, "");
+            match status {
+                Some(status) => e.with("status", status),
+                None => e,
+            }
+        };
+        assert!(retriable(&network(None)));
+        assert!(retriable(&network(Some(503))));
+        assert!(retriable(&network(Some(429))));
+        assert!(!retriable(&network(Some(413))));
+        assert!(retriable(&CommandError::new(ErrorCode::Throttled, "")));
+        assert!(!retriable(&CommandError::new(ErrorCode::Conflict, "")));
+    }
+}
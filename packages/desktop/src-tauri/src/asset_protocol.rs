@@ -0,0 +1,281 @@
+// File preview protocol
+//
+// `agentvbx-asset://` serves files from connected stores straight to the
+// webview, so images, PDFs, and media load without base64 through invoke.
+// Only files inside a connected store's root are served. Symlinks are resolved
+// before the check, and anything outside a root gets a 403. Responses carry
+// the content type, honor single `Range` requests so media can seek, and are
+// revalidated against an ETag built from size and mtime. No response body is
+// larger than `MAX_OPEN_RANGE`; a request without a range for a bigger file
+// gets the first window as a 206, and the client asks for the rest.
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::http::{header, Request, Response, StatusCode};
+
+use super::guess_mime;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{mock_provider, stores};
+
+pub const SCHEME: &str = "agentvbx-asset";
+
+/// Longest body returned for an open-ended range like `bytes=0-`, or for a
+/// request with no range at all; players request the rest as they need it.
+const MAX_OPEN_RANGE: u64 = 4 * 1024 * 1024;
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// URL the webview can load `path` from.
+#[tauri::command]
+pub fn get_asset_url(path: String) -> CommandResult<String> {
+    if granted(Path::new(&path)).is_none() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not inside a connected store", path),
+        )
+        .with("path", &path));
+    }
+    Ok(asset_url(&path))
+}
+
+// ─── Protocol ───────────────────────────────────────────────────────────────
+
+/// Handler for `register_asynchronous_uri_scheme_protocol`; reads off the
+/// main thread.
+pub fn handle<R: tauri::Runtime>(
+    _ctx: tauri::UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: tauri::UriSchemeResponder,
+) {
+    tauri::async_runtime::spawn_blocking(move || responder.respond(serve(&request)));
+}
+
+fn serve(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let encoded = request.uri().path().trim_start_matches('/');
+    if let Some(name) = encoded.strip_prefix(mock_provider::PAGE_PREFIX) {
+        return mock_page(name);
+    }
+    let Some(requested) = percent_decode(encoded) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let Some(path) = granted(Path::new(&requested)) else {
+        log::warn!("[asset] refused {}", requested);
+        return status(StatusCode::FORBIDDEN);
+    };
+    let Ok(meta) = fs::metadata(&path) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    if !meta.is_file() {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    let size = meta.len();
+    let modified = meta.modified().ok();
+    let mtime = modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let etag = format!("\"{:x}-{:x}\"", size, mtime);
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, guess_mime(&name))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ETAG, &etag);
+    if let Some(modified) = modified {
+        let modified: chrono::DateTime<chrono::Utc> = modified.into();
+        response = response.header(
+            header::LAST_MODIFIED,
+            modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        );
+    }
+
+    let header_value = |name| request.headers().get(name).and_then(|v| v.to_str().ok());
+    if header_value(header::IF_NONE_MATCH).is_some_and(|tags| tags.contains(etag.as_str())) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .unwrap();
+    }
+
+    let Some((status_code, start, end)) = span(header_value(header::RANGE), size) else {
+        return response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+            .body(Vec::new())
+            .unwrap();
+    };
+    let Ok(body) = read_span(&path, start, end) else {
+        return status(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    if status_code == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end - 1, size),
+        );
+    }
+    response
+        .status(status_code)
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap()
+}
+
+/// The simulated provider's login pages; 404 unless it is enabled.
+fn mock_page(name: &str) -> Response<Vec<u8>> {
+    let Some(html) = mock_provider::page(name) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(html.as_bytes().to_vec())
+        .unwrap()
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    Response::builder().status(code).body(Vec::new()).unwrap()
+}
+
+/// Status and half-open `[start, end)` to answer with, or `None` if `range`
+/// can't be satisfied. Without a range, a file over `MAX_OPEN_RANGE` is
+/// answered like `bytes=0-` so it is never read whole.
+fn span(range: Option<&str>, size: u64) -> Option<(StatusCode, u64, u64)> {
+    match range {
+        None if size > MAX_OPEN_RANGE => Some((StatusCode::PARTIAL_CONTENT, 0, MAX_OPEN_RANGE)),
+        None => Some((StatusCode::OK, 0, size)),
+        Some(range) => {
+            let (start, end) = parse_range(range, size)?;
+            Some((StatusCode::PARTIAL_CONTENT, start, end))
+        }
+    }
+}
+
+/// Half-open `[start, end)` for a single `bytes=` range, or `None` if it
+/// can't be satisfied. Multiple ranges aren't supported.
+fn parse_range(value: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size)
+        }
+        (start, "") => {
+            let start: u64 = start.parse().ok()?;
+            (start, size.min(start.saturating_add(MAX_OPEN_RANGE)))
+        }
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, size.min(end.saturating_add(1)))
+        }
+    };
+    (start < end).then_some((start, end))
+}
+
+fn read_span(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut body = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+// ─── Paths ──────────────────────────────────────────────────────────────────
+
+/// The resolved path, if it lies inside a connected store.
+fn granted(path: &Path) -> Option<PathBuf> {
+    stores::store_containing(path)?;
+    path.canonicalize().ok()
+}
+
+/// Windows and Android webviews reach custom schemes over `http://<scheme>.localhost`.
+fn asset_url(path: &str) -> String {
+    format!("{}/{}", origin(), percent_encode(path))
+}
+
+/// Scheme and host the protocol is reached at on this platform.
+pub fn origin() -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    }
+}
+
+/// The whole path as one segment, like `encodeURIComponent`.
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_never_exceed_the_open_range_window() {
+        let big = 3 * MAX_OPEN_RANGE;
+        assert_eq!(
+            span(None, big),
+            Some((StatusCode::PARTIAL_CONTENT, 0, MAX_OPEN_RANGE))
+        );
+        assert_eq!(
+            span(Some("bytes=0-"), big),
+            Some((StatusCode::PARTIAL_CONTENT, 0, MAX_OPEN_RANGE))
+        );
+        assert_eq!(
+            span(Some("bytes=100-"), big),
+            Some((StatusCode::PARTIAL_CONTENT, 100, 100 + MAX_OPEN_RANGE))
+        );
+    }
+
+    #[test]
+    fn small_files_are_served_whole_and_ranges_are_honored() {
+        assert_eq!(span(None, 10), Some((StatusCode::OK, 0, 10)));
+        assert_eq!(span(None, 0), Some((StatusCode::OK, 0, 0)));
+        assert_eq!(
+            span(Some("bytes=2-4"), 10),
+            Some((StatusCode::PARTIAL_CONTENT, 2, 5))
+        );
+        assert_eq!(
+            span(Some("bytes=-3"), 10),
+            Some((StatusCode::PARTIAL_CONTENT, 7, 10))
+        );
+        for unsatisfiable in ["bytes=10-", "bytes=0-1,4-5", "items=0-1", "bytes=5-2"] {
+            assert_eq!(span(Some(unsatisfiable), 10), None, "{}", unsatisfiable);
+        }
+    }
+}
@@ -0,0 +1,546 @@
+// Audio upload for transcription
+//
+// Voice notes and recordings in connected stores go to the orchestrator's
+// transcription service in chunks. `prepare_audio_upload` probes the
+// container, estimates the duration, and splits the file into byte ranges of
+// roughly `chunk_seconds` each. Where the container allows, boundaries fall on
+// an MP3 frame, an Ogg page, or a WAV sample block; FLAC gets plain byte
+// ranges. Each chunk is hashed, and the plan is saved under
+// `uploads` in the state directory. `upload_audio_chunks` sends the chunks that aren't
+// marked uploaded, so an interrupted upload resumes where it stopped. On a
+// metered connection it stops with `Throttled` before the next chunk, unless
+// called with `allow_metered`.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::operations::{OperationHandle, OperationKind, Operations, ResultRef};
+use crate::{http, previews, settings, system_state};
+
+/// How far past a target offset to look for a frame or page start.
+const ALIGN_WINDOW: usize = 64 * 1024;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    Wav,
+    Mp3,
+    Ogg,
+    Flac,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AudioChunk {
+    pub index: usize,
+    pub offset: u64,
+    pub length: u64,
+    pub sha256: String,
+    /// Approximate position in the recording where the chunk starts.
+    pub start_seconds: f64,
+    pub uploaded: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UploadPlan {
+    pub plan_id: String,
+    pub path: String,
+    pub format: AudioFormat,
+    pub duration_seconds: f64,
+    pub size_bytes: u64,
+    pub chunk_seconds: u32,
+    /// Chunks fall on frame, page, or block boundaries rather than raw bytes.
+    pub aligned: bool,
+    pub chunks: Vec<AudioChunk>,
+    /// Version of the source the hashes were taken from.
+    source_key: String,
+}
+
+#[derive(Serialize, Clone)]
+struct UploadProgress {
+    plan_id: String,
+    chunk_index: usize,
+    chunks_done: usize,
+    chunks_total: usize,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UploadReport {
+    pub plan_id: String,
+    pub uploaded: usize,
+    /// Chunks already sent by an earlier run.
+    pub skipped: usize,
+}
+
+struct Probe {
+    format: AudioFormat,
+    duration_seconds: f64,
+    /// Where audio data starts; headers before it go in the first chunk.
+    audio_start: u64,
+    audio_end: u64,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Probe and split an audio file. Preparing the same unchanged file again
+/// returns the saved plan, with its upload progress.
+#[tauri::command]
+pub fn prepare_audio_upload(path: String, chunk_seconds: u32) -> CommandResult<UploadPlan> {
+    if chunk_seconds == 0 {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "chunk_seconds must be positive",
+        ));
+    }
+    let file = Path::new(&path);
+    let Some(source_key) = previews::cache_key(file) else {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+                .with("path", &path),
+        );
+    };
+    let plan_id = format!("{}-{}", &source_key[..16], chunk_seconds);
+    if let Some(plan) = load_plan(&plan_id) {
+        return Ok(plan);
+    }
+
+    let mut reader = File::open(file)?;
+    let size_bytes = reader.metadata()?.len();
+    let probe = probe(&mut reader, size_bytes).map_err(|e| e.with("path", &path))?;
+
+    let bytes_per_second = (probe.audio_end - probe.audio_start) as f64 / probe.duration_seconds;
+    let target = ((bytes_per_second * chunk_seconds as f64) as u64).max(1);
+    let mut boundaries = vec![0];
+    let mut next = probe.audio_start + target;
+    while next < probe.audio_end {
+        let aligned = align(&mut reader, probe.format, &probe, next)?;
+        if aligned >= probe.audio_end {
+            break;
+        }
+        if aligned > *boundaries.last().unwrap() {
+            boundaries.push(aligned);
+        }
+        next = aligned + target;
+    }
+    boundaries.push(size_bytes);
+
+    let mut chunks = Vec::new();
+    for (index, pair) in boundaries.windows(2).enumerate() {
+        let (offset, end) = (pair[0], pair[1]);
+        let audio_offset = offset.max(probe.audio_start) - probe.audio_start;
+        chunks.push(AudioChunk {
+            index,
+            offset,
+            length: end - offset,
+            sha256: hash_range(&mut reader, offset, end - offset)?,
+            start_seconds: audio_offset as f64 / bytes_per_second,
+            uploaded: false,
+        });
+    }
+
+    let plan = UploadPlan {
+        plan_id,
+        path,
+        format: probe.format,
+        duration_seconds: probe.duration_seconds,
+        size_bytes,
+        chunk_seconds,
+        aligned: probe.format != AudioFormat::Flac,
+        chunks,
+        source_key,
+    };
+    save_plan(&plan)?;
+    log::info!(
+        "[audio_upload] planned {} ({:?}, {:.0}s) in {} chunks",
+        plan.path,
+        plan.format,
+        plan.duration_seconds,
+        plan.chunks.len()
+    );
+    Ok(plan)
+}
+
+/// Send a plan's remaining chunks to the orchestrator, reporting
+/// `operation-progress` after each one.
+#[tauri::command]
+pub async fn upload_audio_chunks(
+    app: AppHandle,
+    plan_id: String,
+    allow_metered: Option<bool>,
+) -> CommandResult<UploadReport> {
+    let plan = load_plan(&plan_id).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("Unknown upload plan: {}", plan_id),
+        )
+        .with("plan_id", &plan_id)
+    })?;
+    let op = Operations::start_async(&app, OperationKind::AudioUpload, &plan.path, 1).await?;
+    let result = upload(&app, &op, plan, allow_metered.unwrap_or(false)).await;
+    op.finish_with(result, |report| {
+        Some(ResultRef::UploadPlan {
+            plan_id: report.plan_id.clone(),
+        })
+    })
+}
+
+async fn upload(
+    app: &AppHandle,
+    op: &OperationHandle,
+    mut plan: UploadPlan,
+    allow_metered: bool,
+) -> CommandResult<UploadReport> {
+    let source = PathBuf::from(&plan.path);
+    if previews::cache_key(&source).as_deref() != Some(plan.source_key.as_str()) {
+        return Err(changed(&plan));
+    }
+
+    let settings = settings::load_settings();
+    let (client, _) = http::orchestrator_client(&settings)?;
+    let base = format!(
+        "{}/api/transcription/uploads/{}",
+        settings.orchestrator_url.trim_end_matches('/'),
+        plan.plan_id
+    );
+    let total = plan.chunks.len();
+    let mut report = UploadReport {
+        plan_id: plan.plan_id.clone(),
+        uploaded: 0,
+        skipped: plan.chunks.iter().filter(|c| c.uploaded).count(),
+    };
+
+    let mut reader = File::open(&source)?;
+    for i in 0..total {
+        if plan.chunks[i].uploaded {
+            continue;
+        }
+        // The connection can change mid-upload; finished chunks stay saved
+        system_state::check_upload_allowed(allow_metered)?;
+        op.check_cancelled()?;
+        let chunk = plan.chunks[i].clone();
+        let body = read_range(&mut reader, chunk.offset, chunk.length)?;
+        if hex::encode(Sha256::digest(&body)) != chunk.sha256 {
+            return Err(changed(&plan));
+        }
+
+        let url = format!("{}/chunks/{}", base, chunk.index);
+        let response = client
+            .put(&url)
+            .header("Content-Type", "application/octet-stream")
+            .header("X-Chunk-Sha256", &chunk.sha256)
+            .header(
+                "Content-Range",
+                format!(
+                    "bytes {}-{}/{}",
+                    chunk.offset,
+                    chunk.offset + chunk.length - 1,
+                    plan.size_bytes
+                ),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?;
+        if !response.status().is_success() {
+            return Err(CommandError::new(
+                ErrorCode::Network,
+                format!(
+                    "Chunk {} upload failed: HTTP {}",
+                    chunk.index,
+                    response.status()
+                ),
+            )
+            .with("status", response.status().as_u16())
+            .with("chunk_index", chunk.index));
+        }
+
+        plan.chunks[i].uploaded = true;
+        save_plan(&plan)?;
+        report.uploaded += 1;
+        let done: Vec<&AudioChunk> = plan.chunks.iter().filter(|c| c.uploaded).collect();
+        op.progress("uploading", done.len() as u64, Some(total as u64), None);
+        // Deprecated: superseded by operation-progress; kept for one release
+        let _ = app.emit(
+            "audio-upload-progress",
+            UploadProgress {
+                plan_id: plan.plan_id.clone(),
+                chunk_index: chunk.index,
+                chunks_done: done.len(),
+                chunks_total: total,
+                bytes_done: done.iter().map(|c| c.length).sum(),
+                bytes_total: plan.size_bytes,
+            },
+        );
+    }
+
+    let url = format!("{}/complete", base);
+    client
+        .post(&url)
+        .json(&plan)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?;
+    log::info!(
+        "[audio_upload] {} complete ({} sent, {} resumed)",
+        plan.plan_id,
+        report.uploaded,
+        report.skipped
+    );
+    Ok(report)
+}
+
+fn changed(plan: &UploadPlan) -> CommandError {
+    CommandError::new(
+        ErrorCode::Conflict,
+        format!("{} changed since the upload was planned", plan.path),
+    )
+    .with("path", &plan.path)
+    .with("plan_id", &plan.plan_id)
+}
+
+// ─── Probing ────────────────────────────────────────────────────────────────
+
+fn probe(reader: &mut File, size: u64) -> CommandResult<Probe> {
+    let head = read_range(reader, 0, 64 * 1024)?;
+    let probed = if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE") {
+        probe_wav(&head, size)
+    } else if head.starts_with(b"OggS") {
+        probe_ogg(reader, &head, size)?
+    } else if head.starts_with(b"fLaC") {
+        probe_flac(&head, size)
+    } else if head.starts_with(b"ID3") || mp3_frame(&head, 0).is_some() {
+        probe_mp3(&head, size)
+    } else {
+        None
+    };
+    probed.filter(|p| p.duration_seconds > 0.0).ok_or_else(|| {
+        let format = container_name(&head);
+        CommandError::new(
+            ErrorCode::Unsupported,
+            format!("Can't split {} audio for upload", format),
+        )
+        .with("format", format)
+    })
+}
+
+/// Best-effort name for files `probe` can't handle.
+fn container_name(head: &[u8]) -> &'static str {
+    if head.get(4..8) == Some(b"ftyp") {
+        "mp4"
+    } else if head.starts_with(b"\x1A\x45\xDF\xA3") {
+        "webm"
+    } else if head.starts_with(b"#!AMR") {
+        "amr"
+    } else if head.starts_with(b"RIFF") {
+        "riff"
+    } else if head.starts_with(b"OggS") {
+        "ogg"
+    } else if head.starts_with(b"fLaC") {
+        "flac"
+    } else if head.starts_with(b"ID3") {
+        "mp3"
+    } else {
+        "unknown"
+    }
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn probe_wav(head: &[u8], size: u64) -> Option<Probe> {
+    let mut at = 12;
+    let mut byte_rate = None;
+    while at + 8 <= head.len() {
+        let id = &head[at..at + 4];
+        let len = le_u32(head, at + 4)? as u64;
+        if id == b"fmt " {
+            byte_rate = le_u32(head, at + 16).filter(|&r| r > 0);
+        } else if id == b"data" {
+            let audio_start = at as u64 + 8;
+            let audio_end = (audio_start + len).min(size);
+            return Some(Probe {
+                format: AudioFormat::Wav,
+                duration_seconds: (audio_end - audio_start) as f64 / byte_rate? as f64,
+                audio_start,
+                audio_end,
+            });
+        }
+        at += 8 + len as usize + (len as usize & 1);
+    }
+    None
+}
+
+fn wav_block_align(head: &[u8]) -> u64 {
+    le_u16(head, 32).filter(|&b| b > 0).unwrap_or(1) as u64
+}
+
+/// Bitrate in kbps and sample rate of an MPEG audio frame header at `at`.
+fn mp3_frame(bytes: &[u8], at: usize) -> Option<(u32, u32)> {
+    let h = bytes.get(at..at + 4)?;
+    if h[0] != 0xFF || h[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = (h[1] >> 3) & 0x03; // 3 = MPEG1, 2 = MPEG2, 0 = MPEG2.5
+    let layer = (h[1] >> 1) & 0x03; // 1 = Layer III
+    let bitrate_index = (h[2] >> 4) as usize;
+    let rate_index = ((h[2] >> 2) & 0x03) as usize;
+    if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+    const MPEG1: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    let base_rate = [44100, 48000, 32000][rate_index];
+    Some(match version {
+        3 => (MPEG1[bitrate_index], base_rate),
+        2 => (MPEG2[bitrate_index], base_rate / 2),
+        _ => (MPEG2[bitrate_index], base_rate / 4),
+    })
+}
+
+fn probe_mp3(head: &[u8], size: u64) -> Option<Probe> {
+    let mut start = 0;
+    if head.starts_with(b"ID3") {
+        let s = head.get(6..10)?;
+        let tag =
+            (s[0] as usize) << 21 | (s[1] as usize) << 14 | (s[2] as usize) << 7 | s[3] as usize;
+        start = 10 + tag;
+    }
+    let first = (start..head.len().saturating_sub(4)).find(|&i| mp3_frame(head, i).is_some())?;
+    let (kbps, _) = mp3_frame(head, first)?;
+    let audio_start = first as u64;
+    // Constant-bitrate estimate; VBR files come out approximate
+    Some(Probe {
+        format: AudioFormat::Mp3,
+        duration_seconds: (size - audio_start) as f64 * 8.0 / (kbps as f64 * 1000.0),
+        audio_start,
+        audio_end: size,
+    })
+}
+
+fn probe_ogg(reader: &mut File, head: &[u8], size: u64) -> CommandResult<Option<Probe>> {
+    let rate = if find(head, b"OpusHead").is_some() {
+        // Opus granule positions always count 48 kHz samples
+        48_000
+    } else if let Some(at) = find(head, b"\x01vorbis") {
+        match le_u32(head, at + 12) {
+            Some(rate) if rate > 0 => rate,
+            _ => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    let tail_start = size.saturating_sub(ALIGN_WINDOW as u64);
+    let tail = read_range(reader, tail_start, size - tail_start)?;
+    let granule = rfind(&tail, b"OggS")
+        .and_then(|at| tail.get(at + 6..at + 14))
+        .map(|g| i64::from_le_bytes(g.try_into().unwrap()))
+        .filter(|&g| g > 0);
+    Ok(granule.map(|granule| Probe {
+        format: AudioFormat::Ogg,
+        duration_seconds: granule as f64 / rate as f64,
+        audio_start: 0,
+        audio_end: size,
+    }))
+}
+
+fn probe_flac(head: &[u8], size: u64) -> Option<Probe> {
+    // STREAMINFO is always the first metadata block
+    let info = head.get(8..8 + 34)?;
+    let b = &info[10..18];
+    let sample_rate = (b[0] as u64) << 12 | (b[1] as u64) << 4 | (b[2] as u64) >> 4;
+    let samples =
+        ((b[3] & 0x0F) as u64) << 32 | u32::from_be_bytes(b[4..8].try_into().ok()?) as u64;
+    if sample_rate == 0 || samples == 0 {
+        return None;
+    }
+    Some(Probe {
+        format: AudioFormat::Flac,
+        duration_seconds: samples as f64 / sample_rate as f64,
+        audio_start: 0,
+        audio_end: size,
+    })
+}
+
+/// The first boundary at or after `target` that the container allows.
+fn align(reader: &mut File, format: AudioFormat, probe: &Probe, target: u64) -> CommandResult<u64> {
+    let found = match format {
+        AudioFormat::Flac => return Ok(target),
+        AudioFormat::Wav => {
+            let head = read_range(reader, 0, probe.audio_start)?;
+            let block = wav_block_align(&head);
+            let into = target - probe.audio_start;
+            return Ok(probe.audio_start + into.div_ceil(block) * block);
+        }
+        AudioFormat::Mp3 => {
+            let window = read_range(reader, target, ALIGN_WINDOW as u64)?;
+            (0..window.len()).find(|&i| mp3_frame(&window, i).is_some())
+        }
+        AudioFormat::Ogg => {
+            let window = read_range(reader, target, ALIGN_WINDOW as u64)?;
+            find(&window, b"OggS")
+        }
+    };
+    Ok(found.map_or(target, |i| target + i as u64))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+// ─── Files ──────────────────────────────────────────────────────────────────
+
+fn read_range(reader: &mut File, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn hash_range(reader: &mut File, offset: u64, length: u64) -> io::Result<String> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader.take(length), &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn plans_dir() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join("uploads"))
+}
+
+fn load_plan(plan_id: &str) -> Option<UploadPlan> {
+    if plan_id.contains(['/', '\\', '.']) {
+        return None;
+    }
+    let raw = fs::read(plans_dir().ok()?.join(format!("{}.json", plan_id))).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+fn save_plan(plan: &UploadPlan) -> CommandResult<()> {
+    let dir = plans_dir()?;
+    fs::create_dir_all(&dir)?;
+    let raw = serde_json::to_vec_pretty(plan).map_err(|e| e.to_string())?;
+    fs::write(dir.join(format!("{}.json", plan.plan_id)), raw)?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+// Audit log
+//
+// Append-only JSON lines in `audit.log`, in the state directory, recording
+// security-relevant actions (rejected requests, session changes). Entries
+// carry identifiers and outcomes only — never secret values, cookies, or
+// request bodies.
+
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::error::CommandResult;
+use crate::home;
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    at: String,
+    action: &'a str,
+    tenant_id: Option<&'a str>,
+    detail: serde_json::Value,
+}
+
+pub fn audit_log_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join("audit.log"))
+}
+
+/// Record an action. Failures are logged, never surfaced: auditing must not
+/// turn a successful operation into a failed one.
+pub fn record(action: &str, tenant_id: Option<&str>, detail: serde_json::Value) {
+    let entry = AuditEntry {
+        at: chrono::Utc::now().to_rfc3339(),
+        action,
+        tenant_id,
+        detail,
+    };
+    let result = serde_json::to_string(&entry)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(audit_log_path()?)
+                .map_err(|e| e.to_string())?;
+            writeln!(file, "{}", line).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        log::warn!("[audit] failed to record {}: {}", action, e);
+    }
+}
@@ -0,0 +1,519 @@
+// Automation listener
+//
+// A local HTTP API for scripts such as Raycast commands and Hazel rules. It's
+// off by default and turned on with `settings.automation`. It binds to
+// 127.0.0.1 only, on `automation.port`, and every request needs
+// `Authorization: Bearer <token>`. The token lives in the keychain. A token
+// set through `update_settings` is moved there and never saved in the
+// settings file, and `get_automation_token` creates one if none is set.
+//
+// Endpoints, all JSON:
+// - `GET /health`: version and each store's status.
+// - `POST /stores/<id>/rescan`: starts `rescan_store` and answers 202.
+// - `POST /quick-capture` with `{ tenant_id, vault_path, text }`, or a
+//   `workspace_id` in place of `vault_path`: runs `submit_quick_capture`.
+// Bodies need a `Content-Length`, and ones over `automation.max_body_bytes`
+// get 413. Requests with an `Origin` header come from a browser page, not a
+// script, and get 403. Every request is audited as `automation_request`,
+// whatever its outcome.
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::maintenance::MaintenanceMode;
+use crate::policy::{self, CommandGroup};
+use crate::read_only::ReadOnlyMode;
+use crate::settings::{self, AutomationSettings};
+use crate::{audit, dry_run, quick_capture, secrets, stores};
+
+const TOKEN_KEY: &str = "automation-token";
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+const MAX_HEADERS: usize = 32;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+static RUNNING: Mutex<Option<Running>> = Mutex::new(None);
+
+struct Running {
+    port: u16,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Running {
+    fn stop(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the blocked `accept` so the thread sees the flag
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+/// Reported by `get_diagnostics`.
+#[derive(Serialize, Clone)]
+pub struct ListenerStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Health,
+    Rescan(String),
+    QuickCapture,
+}
+
+/// A request line and headers, before the body is read.
+#[derive(Debug)]
+struct Head {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    /// Bytes of `buf` up to the end of the headers.
+    len: usize,
+}
+
+impl Head {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct CaptureRequest {
+    tenant_id: String,
+    #[serde(default)]
+    vault_path: Option<String>,
+    text: String,
+    #[serde(default)]
+    workspace_id: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// The listener's bearer token, created on first use.
+#[tauri::command]
+pub fn get_automation_token() -> CommandResult<String> {
+    policy::check(None, CommandGroup::Automation)?;
+    if let Some(token) = secrets::load(TOKEN_KEY)? {
+        return Ok(token);
+    }
+    let token = new_token();
+    secrets::store(TOKEN_KEY, &token)?;
+    Ok(token)
+}
+
+// ─── Lifecycle ──────────────────────────────────────────────────────────────
+
+/// Start the listener if it's enabled; run at startup.
+pub fn spawn(app: AppHandle) {
+    let _ = APP.set(app);
+    apply(&settings::load_settings().automation);
+}
+
+/// Start, stop, or move the listener to match `settings`.
+pub fn apply(settings: &AutomationSettings) {
+    let Some(app) = APP.get() else {
+        return;
+    };
+    let mut running = RUNNING.lock().unwrap();
+    // The managed policy overrides the setting
+    let allowed = policy::allows(None, CommandGroup::Automation);
+    let wanted = (settings.enabled && allowed).then_some(settings.port);
+    if running.as_ref().map(|r| r.port) == wanted {
+        return;
+    }
+    if let Some(old) = running.take() {
+        log::info!("[automation] stopped listening on {}", old.port);
+        old.stop();
+    }
+    let Some(port) = wanted else {
+        return;
+    };
+    match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => {
+            let stopping = Arc::new(AtomicBool::new(false));
+            let app = app.clone();
+            let flag = stopping.clone();
+            std::thread::spawn(move || serve(&app, listener, &flag));
+            log::info!("[automation] listening on 127.0.0.1:{}", port);
+            *running = Some(Running { port, stopping });
+        }
+        Err(e) => log::error!("[automation] can't listen on port {}: {}", port, e),
+    }
+}
+
+pub fn status() -> ListenerStatus {
+    let port = RUNNING.lock().unwrap().as_ref().map(|r| r.port);
+    ListenerStatus {
+        running: port.is_some(),
+        port,
+    }
+}
+
+pub fn shutdown() {
+    if let Some(running) = RUNNING.lock().unwrap().take() {
+        running.stop();
+    }
+}
+
+/// Move a token set in settings into the keychain; an empty one removes it.
+pub fn stash_token(settings: &mut AutomationSettings) -> Result<(), String> {
+    match settings.token.take() {
+        Some(token) if token.trim().is_empty() => secrets::delete(TOKEN_KEY),
+        Some(token) => secrets::store(TOKEN_KEY, token.trim()),
+        None => Ok(()),
+    }
+}
+
+// ─── Requests ───────────────────────────────────────────────────────────────
+
+fn serve(app: &AppHandle, listener: TcpListener, stopping: &AtomicBool) {
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let app = app.clone();
+        std::thread::spawn(move || handle(&app, stream));
+    }
+}
+
+fn handle(app: &AppHandle, mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let mut method = String::new();
+    let mut path = String::new();
+    let mut tenant_id = None;
+    let result = read_head(&mut stream).and_then(|(head, buf)| {
+        method = head.method.clone();
+        path = head.path.clone();
+        respond(app, &mut stream, head, buf, &mut tenant_id)
+    });
+    let (status, body) = match result {
+        Ok(ok) => ok,
+        Err(e) => (http_status(&e), serde_json::json!(e)),
+    };
+    audit::record(
+        "automation_request",
+        tenant_id.as_deref(),
+        serde_json::json!({ "method": method, "path": path, "status": status }),
+    );
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        log::debug!("[automation] couldn't answer {} {}: {}", method, path, e);
+    }
+}
+
+/// The status and JSON body. `tenant_id` is set to the tenant the request
+/// acted for, for the audit record.
+fn respond(
+    app: &AppHandle,
+    stream: &mut TcpStream,
+    head: Head,
+    mut buf: Vec<u8>,
+    tenant_id: &mut Option<String>,
+) -> CommandResult<(u16, serde_json::Value)> {
+    let settings = settings::load_settings().automation;
+    if head.header("Origin").is_some() {
+        return Err(CommandError::new(
+            ErrorCode::PolicyDenied,
+            "Browser requests aren't allowed",
+        ));
+    }
+    let expected = secrets::load(TOKEN_KEY)?;
+    if !authorized(head.header("Authorization"), expected.as_deref()) {
+        return Err(CommandError::new(
+            ErrorCode::PermissionDenied,
+            "Missing or wrong bearer token",
+        ));
+    }
+    let Some(route) = route(&head.method, &head.path) else {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("No such endpoint: {} {}", head.method, head.path),
+        ));
+    };
+    let length = body_length(&head, settings.max_body_bytes)?;
+    buf.drain(..head.len);
+    buf.truncate(length);
+    if buf.len() < length {
+        let mut rest = vec![0; length - buf.len()];
+        stream.read_exact(&mut rest)?;
+        buf.extend(rest);
+    }
+
+    match route {
+        Route::Health => {
+            let stores: Vec<serde_json::Value> = stores::list_stores()
+                .into_iter()
+                .map(|s| {
+                    serde_json::json!({
+                        "id": s.id,
+                        "name": s.name,
+                        "status": s.status,
+                        "last_scanned_at": s.last_scanned_at,
+                    })
+                })
+                .collect();
+            let health = serde_json::json!({
+                "ok": true,
+                "version": env!("CARGO_PKG_VERSION"),
+                "stores": stores,
+            });
+            Ok((200, health))
+        }
+        Route::Rescan(store_id) => {
+            app.state::<ReadOnlyMode>().check("rescan_store")?;
+            app.state::<MaintenanceMode>().check("rescan_store")?;
+            stores::find_store(&store_id)?;
+            let app = app.clone();
+            let id = store_id.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = stores::rescan_store(app, id.clone()).await {
+                    log::warn!("[automation] rescan of {} failed: {}", id, e);
+                }
+            });
+            Ok((202, serde_json::json!({ "store_id": store_id })))
+        }
+        Route::QuickCapture => {
+            let capture: CaptureRequest = serde_json::from_slice(&buf).map_err(|e| {
+                CommandError::new(ErrorCode::InvalidInput, format!("Invalid body: {}", e))
+            })?;
+            *tenant_id = Some(capture.tenant_id.clone());
+            app.state::<ReadOnlyMode>().check("submit_quick_capture")?;
+            app.state::<MaintenanceMode>()
+                .check("submit_quick_capture")?;
+            dry_run::check_writable("automation", Some(&capture.tenant_id), "quick_capture")?;
+            let vault_path = quick_capture::target(
+                &capture.tenant_id,
+                capture.vault_path,
+                capture.workspace_id.as_deref(),
+            )?;
+            let note = quick_capture::capture(app, &capture.tenant_id, &vault_path, &capture.text)?;
+            Ok((200, serde_json::json!({ "path": note })))
+        }
+    }
+}
+
+/// Read until the headers are complete; the buffer may hold part of the body.
+fn read_head(stream: &mut TcpStream) -> CommandResult<(Head, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                "Connection closed mid-request",
+            ));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(head) = parse_head(&buf)? {
+            return Ok((head, buf));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err(CommandError::new(
+                ErrorCode::TooLarge,
+                format!("Request headers over {} bytes", MAX_HEAD_BYTES),
+            )
+            .with("size", buf.len())
+            .with("limit", MAX_HEAD_BYTES));
+        }
+    }
+}
+
+/// `None` while the headers are still incomplete.
+fn parse_head(buf: &[u8]) -> CommandResult<Option<Head>> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    let len = match request.parse(buf) {
+        Ok(httparse::Status::Complete(len)) => len,
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(e) => {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Malformed request: {}", e),
+            ))
+        }
+    };
+    Ok(Some(Head {
+        method: request.method.unwrap_or_default().to_string(),
+        path: request.path.unwrap_or_default().to_string(),
+        headers: request
+            .headers
+            .iter()
+            .map(|h| {
+                (
+                    h.name.to_string(),
+                    String::from_utf8_lossy(h.value).into_owned(),
+                )
+            })
+            .collect(),
+        len,
+    }))
+}
+
+/// The declared body size, checked against `limit`. Chunked bodies aren't
+/// accepted; every client we expect sends a length.
+fn body_length(head: &Head, limit: u64) -> CommandResult<usize> {
+    if head.header("Transfer-Encoding").is_some() {
+        return Err(CommandError::new(
+            ErrorCode::Unsupported,
+            "Chunked request bodies aren't supported",
+        )
+        .with("feature", "chunked_body"));
+    }
+    let length = match head.header("Content-Length") {
+        Some(value) => value
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| CommandError::new(ErrorCode::InvalidInput, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > limit {
+        return Err(CommandError::new(
+            ErrorCode::TooLarge,
+            format!("Request body over {} bytes", limit),
+        )
+        .with("size", length)
+        .with("limit", limit)
+        .with("setting", "automation.max_body_bytes"));
+    }
+    Ok(length as usize)
+}
+
+fn route(method: &str, url: &str) -> Option<Route> {
+    let path = url.split('?').next().unwrap_or_default();
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    match (method, parts.as_slice()) {
+        ("GET", ["health"]) => Some(Route::Health),
+        ("POST", ["stores", id, "rescan"]) => Some(Route::Rescan(id.to_string())),
+        ("POST", ["quick-capture"]) => Some(Route::QuickCapture),
+        _ => None,
+    }
+}
+
+/// No token configured means nothing is authorized.
+fn authorized(header: Option<&str>, expected: Option<&str>) -> bool {
+    let (Some(header), Some(expected)) = (header, expected) else {
+        return false;
+    };
+    let Some(given) = header.strip_prefix("Bearer ") else {
+        return false;
+    };
+    // Compare every byte so timing doesn't reveal the matching prefix
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn http_status(error: &CommandError) -> u16 {
+    match error.code {
+        ErrorCode::InvalidInput => 400,
+        ErrorCode::PermissionDenied => 401,
+        ErrorCode::PolicyDenied => 403,
+        ErrorCode::NotFound => 404,
+        ErrorCode::AlreadyRunning => 409,
+        ErrorCode::TooLarge => 413,
+        ErrorCode::ReadOnlyMode => 423,
+        ErrorCode::MaintenanceMode => 503,
+        ErrorCode::Unsupported => 501,
+        _ => 500,
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+fn new_token() -> String {
+    use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes() {
+        assert_eq!(route("GET", "/health"), Some(Route::Health));
+        assert_eq!(
+            route("POST", "/stores/store-abc/rescan?now=1"),
+            Some(Route::Rescan("store-abc".into()))
+        );
+        assert_eq!(route("POST", "/quick-capture"), Some(Route::QuickCapture));
+        assert_eq!(route("POST", "/health"), None);
+        assert_eq!(route("GET", "/stores/store-abc/rescan"), None);
+    }
+
+    #[test]
+    fn bearer_tokens() {
+        assert!(authorized(Some("Bearer s3cret"), Some("s3cret")));
+        assert!(!authorized(Some("Bearer s3cres"), Some("s3cret")));
+        assert!(!authorized(Some("Bearer s3cret-longer"), Some("s3cret")));
+        assert!(!authorized(Some("s3cret"), Some("s3cret")));
+        assert!(!authorized(None, Some("s3cret")));
+        assert!(!authorized(Some("Bearer "), None));
+    }
+
+    #[test]
+    fn parses_heads_and_checks_body_size() {
+        let raw = b"POST /quick-capture HTTP/1.1\r\nHost: localhost\r\n\
+                    authorization: Bearer t\r\nContent-Length: 12\r\n\r\n{\"text\"";
+        assert!(parse_head(&raw[..20]).unwrap().is_none());
+        let head = parse_head(raw).unwrap().unwrap();
+        assert_eq!(head.method, "POST");
+        assert_eq!(head.path, "/quick-capture");
+        assert_eq!(head.header("Authorization"), Some("Bearer t"));
+        assert_eq!(&raw[head.len..], b"{\"text\"");
+
+        assert_eq!(body_length(&head, 12).unwrap(), 12);
+        let err = body_length(&head, 11).unwrap_err();
+        assert_eq!(err.code, ErrorCode::TooLarge);
+        assert_eq!(http_status(&err), 413);
+        assert_eq!(err.params["setting"], "automation.max_body_bytes");
+
+        assert!(parse_head(b"NOT HTTP\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn generated_tokens_differ() {
+        let token = new_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, new_token());
+    }
+}
@@ -0,0 +1,649 @@
+// Backup & restore
+//
+// `create_backup` writes `agentvbx-backup-<timestamp>.tar.gz` holding the
+// AGENTVBX config, store registry and snapshots, tenant data, and —
+// if asked — session payloads exactly as they sit on disk (sealed payloads
+// are never decrypted). `manifest.json` lists every file with its size and
+// SHA-256.
+//
+// `restore_backup` unpacks into a staging directory in the data directory and
+// verifies it against the manifest before touching live data. `replace`
+// moves the current data to the trash and swaps the staged copy in; `merge`
+// only adds what's missing locally. Both report progress as
+// `operation-progress` events since archives can be gigabytes.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+use crate::audit;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home::{self, DataDirs};
+use crate::operations::{OperationHandle, OperationKind, Operations, ResultRef};
+use crate::stores::{self, ConnectedStore};
+use crate::trash::{self, TrashKind};
+use crate::{disk_space, dry_run};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DATA_DIR: &str = "data";
+const FORMAT_VERSION: u32 = 1;
+/// Operation weight of a backup or restore; both read and write everything.
+const BACKUP_WEIGHT: u32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupCategory {
+    Config,
+    Stores,
+    Tenants,
+    Sessions,
+}
+
+impl BackupCategory {
+    /// Paths as laid out in `~/.agentvbx`, relative and `/`-separated, that
+    /// make up the category. Each is found in its own root; see `home`.
+    fn roots(self) -> &'static [&'static str] {
+        match self {
+            BackupCategory::Config => &["config.json"],
+            BackupCategory::Stores => &["stores.json", "stores"],
+            BackupCategory::Tenants => &["tenants"],
+            // vault.json carries the salt needed to open passphrase-sealed payloads
+            BackupCategory::Sessions => &["sessions", "vault.json"],
+        }
+    }
+
+    /// Path components forming the unit `merge` restores whole or not at all,
+    /// e.g. `sessions/<tenant>/<provider>`.
+    fn merge_depth(self) -> usize {
+        match self {
+            BackupCategory::Config => 1,
+            BackupCategory::Stores | BackupCategory::Tenants => 2,
+            BackupCategory::Sessions => 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    Replace,
+    Merge,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupManifest {
+    format_version: u32,
+    created_at: String,
+    app_version: String,
+    categories: Vec<BackupCategory>,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    path: String,
+    category: BackupCategory,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CategoryReport {
+    pub category: BackupCategory,
+    pub files: usize,
+    pub bytes: u64,
+    /// Merge only: files left alone because local data already existed.
+    pub skipped: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BackupReport {
+    pub archive_path: String,
+    pub categories: Vec<CategoryReport>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RestoreReport {
+    pub categories: Vec<CategoryReport>,
+    /// Replace only: trash entry holding the data that was replaced.
+    pub replaced_trash_entry: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Write a backup archive into the `dest_path` directory.
+#[tauri::command]
+pub async fn create_backup(
+    app: AppHandle,
+    dest_path: String,
+    include_sessions: bool,
+) -> CommandResult<BackupReport> {
+    let op =
+        Operations::start_async(&app, OperationKind::Backup, &dest_path, BACKUP_WEIGHT).await?;
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let report = backup(&op, Path::new(&dest_path), include_sessions);
+        op.finish_with(report, |report| {
+            Some(ResultRef::File {
+                path: report.archive_path.clone(),
+            })
+        })
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))??;
+    audit::record(
+        "backup_created",
+        None,
+        serde_json::json!({ "archive": report.archive_path, "include_sessions": include_sessions }),
+    );
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    archive_path: String,
+    mode: RestoreMode,
+) -> CommandResult<RestoreReport> {
+    dry_run::check_writable(window.label(), None, "restore_backup")?;
+    let archive = archive_path.clone();
+    let op =
+        Operations::start_async(&app, OperationKind::Restore, &archive_path, BACKUP_WEIGHT).await?;
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let report = restore(&op, Path::new(&archive), mode);
+        op.finish(report)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))??;
+    audit::record(
+        "backup_restored",
+        None,
+        serde_json::json!({
+            "archive": archive_path,
+            "mode": format!("{:?}", mode).to_lowercase(),
+            "replaced_trash_entry": report.replaced_trash_entry,
+        }),
+    );
+    Ok(report)
+}
+
+// ─── Backup ─────────────────────────────────────────────────────────────────
+
+fn backup(
+    op: &OperationHandle,
+    dest: &Path,
+    include_sessions: bool,
+) -> CommandResult<BackupReport> {
+    if !dest.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Backup destination not found: {}", dest.display()),
+        )
+        .with("path", dest.to_string_lossy()));
+    }
+    let dirs = home::data_dirs()?;
+    let mut categories = vec![
+        BackupCategory::Config,
+        BackupCategory::Stores,
+        BackupCategory::Tenants,
+    ];
+    if include_sessions {
+        categories.push(BackupCategory::Sessions);
+    }
+    let files = collect_files(&dirs, &categories);
+    let bytes_total = files.iter().map(|(_, _, size)| size).sum();
+    // Compression only shrinks it, so the total is an upper bound
+    disk_space::ensure(dest, bytes_total)?;
+
+    let name = format!(
+        "agentvbx-backup-{}.tar.gz",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let archive_path = dest.join(&name);
+    let partial = dest.join(format!("{}.partial", name));
+
+    let result = write_archive(op, &dirs, &partial, &categories, &files, bytes_total);
+    let manifest = match result {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+    fs::rename(&partial, &archive_path)?;
+
+    log::info!(
+        "[backup] wrote {} ({} files)",
+        archive_path.display(),
+        manifest.files.len()
+    );
+    Ok(BackupReport {
+        archive_path: archive_path.to_string_lossy().to_string(),
+        categories: summarize(&manifest, &BTreeMap::new()),
+    })
+}
+
+/// Every file to back up as (relative path, category, size).
+fn collect_files(
+    dirs: &DataDirs,
+    categories: &[BackupCategory],
+) -> Vec<(String, BackupCategory, u64)> {
+    let mut files = Vec::new();
+    for &category in categories {
+        for root in category.roots() {
+            let walker = walkdir::WalkDir::new(dirs.locate(root))
+                .into_iter()
+                // Webview profiles are large and machine-specific; logins are in sessions
+                .filter_entry(|e| !(e.file_type().is_dir() && e.file_name() == "webview"));
+            for entry in walker.flatten() {
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if !meta.is_file() {
+                    continue;
+                }
+                if let Some(rel) = relative(dirs.root_for(root), entry.path()) {
+                    files.push((rel, category, meta.len()));
+                }
+            }
+        }
+    }
+    files
+}
+
+fn write_archive(
+    op: &OperationHandle,
+    dirs: &DataDirs,
+    archive_path: &Path,
+    categories: &[BackupCategory],
+    files: &[(String, BackupCategory, u64)],
+    bytes_total: u64,
+) -> CommandResult<BackupManifest> {
+    let out = File::create(archive_path)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(out, flate2::Compression::default()));
+    let mut progress = Progress::new(op, "archiving", bytes_total);
+    let mut manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        categories: categories.to_vec(),
+        files: Vec::new(),
+    };
+
+    for (rel, category, size) in files {
+        // Bounded so a file growing mid-backup can't overrun its header size
+        let mut reader = HashingReader::new(File::open(dirs.locate(rel))?.take(*size));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(*size);
+        header.set_mode(0o600);
+        header.set_mtime(chrono::Utc::now().timestamp() as u64);
+        builder.append_data(&mut header, format!("{}/{}", DATA_DIR, rel), &mut reader)?;
+        manifest.files.push(ManifestFile {
+            path: rel.clone(),
+            category: *category,
+            size: *size,
+            sha256: reader.finish(),
+        });
+        progress.advance(*size, rel);
+    }
+
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    builder.append_data(&mut header, MANIFEST_FILE, json.as_slice())?;
+    builder.into_inner()?.finish()?;
+    progress.done();
+    Ok(manifest)
+}
+
+// ─── Restore ────────────────────────────────────────────────────────────────
+
+fn restore(
+    op: &OperationHandle,
+    archive: &Path,
+    mode: RestoreMode,
+) -> CommandResult<RestoreReport> {
+    let dirs = home::data_dirs()?;
+    // The staged files are at least as large as the compressed archive
+    disk_space::ensure(&dirs.data, fs::metadata(archive).map_or(0, |m| m.len()))?;
+    // Inside the data directory so the final swap is a rename, not a copy
+    let staging = dirs
+        .data
+        .join(".staging")
+        .join(format!("restore-{}", chrono::Utc::now().timestamp_millis()));
+    fs::create_dir_all(&staging)?;
+
+    let result = extract(op, archive, &staging)
+        .and_then(|()| verify(op, archive, &staging))
+        .and_then(|manifest| match mode {
+            RestoreMode::Replace => apply_replace(&dirs, &staging, &manifest),
+            RestoreMode::Merge => apply_merge(&dirs, &staging, &manifest),
+        });
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        log::warn!("[backup] failed to clean up {}: {}", staging.display(), e);
+    }
+    result
+}
+
+fn extract(op: &OperationHandle, archive: &Path, staging: &Path) -> CommandResult<()> {
+    let file = File::open(archive).map_err(|e| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("Can't open backup {}: {}", archive.display(), e),
+        )
+        .with("path", archive.to_string_lossy())
+    })?;
+    let bytes_total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let read = Arc::new(AtomicU64::new(0));
+    let counting = CountingReader {
+        inner: file,
+        read: read.clone(),
+    };
+    let mut progress = Progress::new(op, "extracting", bytes_total);
+
+    let mut tar = tar::Archive::new(GzDecoder::new(counting));
+    for entry in tar.entries().map_err(|e| corrupt(archive, e))? {
+        let mut entry = entry.map_err(|e| corrupt(archive, e))?;
+        let path = entry.path().map_err(|e| corrupt(archive, e))?.into_owned();
+        let name = path.to_string_lossy().to_string();
+        if name != MANIFEST_FILE && !path.starts_with(DATA_DIR) {
+            return Err(corrupt(archive, format!("unexpected entry {}", name)));
+        }
+        // unpack_in refuses entries that would land outside `staging`
+        if !entry.unpack_in(staging).map_err(|e| corrupt(archive, e))? {
+            return Err(corrupt(archive, format!("unsafe entry {}", name)));
+        }
+        progress.set(read.load(Ordering::Relaxed), &name);
+    }
+    progress.done();
+    Ok(())
+}
+
+/// Check the staged files against the manifest: same set, sizes, and hashes.
+fn verify(op: &OperationHandle, archive: &Path, staging: &Path) -> CommandResult<BackupManifest> {
+    let raw = fs::read(staging.join(MANIFEST_FILE))
+        .map_err(|_| corrupt(archive, "no manifest; not an AGENTVBX backup"))?;
+    let manifest: BackupManifest = serde_json::from_slice(&raw).map_err(|e| corrupt(archive, e))?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Backup format {} is newer than this version supports",
+                manifest.format_version
+            ),
+        )
+        .with("format_version", manifest.format_version));
+    }
+
+    let data = staging.join(DATA_DIR);
+    let listed: HashSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    for entry in walkdir::WalkDir::new(&data).into_iter().flatten() {
+        if entry.file_type().is_file() {
+            let rel = relative(&data, entry.path()).unwrap_or_default();
+            if !listed.contains(rel.as_str()) {
+                return Err(corrupt(archive, format!("{} is not in the manifest", rel)));
+            }
+        }
+    }
+
+    let bytes_total = manifest.files.iter().map(|f| f.size).sum();
+    let mut progress = Progress::new(op, "verifying", bytes_total);
+    for file in &manifest.files {
+        if !is_safe_relative(&file.path) {
+            return Err(corrupt(archive, format!("unsafe path {}", file.path)));
+        }
+        let staged = data.join(&file.path);
+        let mut reader = HashingReader::new(
+            File::open(&staged)
+                .map_err(|_| corrupt(archive, format!("{} is missing", file.path)))?,
+        );
+        let size = io::copy(&mut reader, &mut io::sink())?;
+        if size != file.size || reader.finish() != file.sha256 {
+            return Err(corrupt(
+                archive,
+                format!("{} failed its checksum", file.path),
+            ));
+        }
+        progress.advance(file.size, &file.path);
+    }
+    progress.done();
+    Ok(manifest)
+}
+
+/// Move the current data for each category in the backup to the trash and
+/// swap the staged copy in; on failure put the current data back.
+fn apply_replace(
+    dirs: &DataDirs,
+    staging: &Path,
+    manifest: &BackupManifest,
+) -> CommandResult<RestoreReport> {
+    let roots: Vec<&str> = manifest
+        .categories
+        .iter()
+        .flat_map(|c| c.roots().iter().copied())
+        .collect();
+    let current: Vec<PathBuf> = roots.iter().map(|r| dirs.locate(r)).collect();
+    let previous = if current.iter().any(|p| p.exists()) {
+        Some(trash::move_to_trash(
+            TrashKind::Restore,
+            "before-restore",
+            &current,
+        )?)
+    } else {
+        None
+    };
+
+    let swap = || -> io::Result<()> {
+        for root in &roots {
+            let staged = staging.join(DATA_DIR).join(root);
+            if staged.exists() {
+                let target = dirs.locate(root);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(staged, target)?;
+            }
+        }
+        Ok(())
+    };
+    if let Err(e) = swap() {
+        log::error!("[backup] restore failed, rolling back: {}", e);
+        for path in &current {
+            if path.is_dir() {
+                let _ = fs::remove_dir_all(path);
+            } else {
+                let _ = fs::remove_file(path);
+            }
+        }
+        if let Some(previous) = &previous {
+            trash::restore(&previous.id, true, false)?;
+        }
+        return Err(e.into());
+    }
+
+    Ok(RestoreReport {
+        categories: summarize(manifest, &BTreeMap::new()),
+        replaced_trash_entry: previous.map(|p| p.id),
+    })
+}
+
+/// Add only what's missing locally: whole merge units (a session, a tenant,
+/// a store's snapshot) that don't exist yet, plus unknown stores in the
+/// registry. Local data always wins.
+fn apply_merge(
+    dirs: &DataDirs,
+    staging: &Path,
+    manifest: &BackupManifest,
+) -> CommandResult<RestoreReport> {
+    let data = staging.join(DATA_DIR);
+    let mut skipped: BTreeMap<BackupCategory, usize> = BTreeMap::new();
+    // Units created by this merge, so their remaining files still go in
+    let mut created: BTreeSet<String> = BTreeSet::new();
+
+    for file in &manifest.files {
+        if file.path == "stores.json" {
+            let raw = fs::read_to_string(data.join(&file.path))?;
+            let incoming: Vec<ConnectedStore> =
+                serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+            stores::merge_registry(incoming)?;
+            continue;
+        }
+        let unit: String = file
+            .path
+            .split('/')
+            .take(file.category.merge_depth())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !created.contains(&unit) {
+            if dirs.locate(&unit).exists() {
+                *skipped.entry(file.category).or_default() += 1;
+                continue;
+            }
+            created.insert(unit);
+        }
+        let target = dirs.locate(&file.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(data.join(&file.path), target)?;
+    }
+
+    Ok(RestoreReport {
+        categories: summarize(manifest, &skipped),
+        replaced_trash_entry: None,
+    })
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+fn summarize(
+    manifest: &BackupManifest,
+    skipped: &BTreeMap<BackupCategory, usize>,
+) -> Vec<CategoryReport> {
+    manifest
+        .categories
+        .iter()
+        .map(|&category| {
+            let files = manifest.files.iter().filter(|f| f.category == category);
+            let skipped = skipped.get(&category).copied().unwrap_or(0);
+            CategoryReport {
+                category,
+                files: files.clone().count() - skipped,
+                bytes: files.map(|f| f.size).sum(),
+                skipped,
+            }
+        })
+        .collect()
+}
+
+fn corrupt(archive: &Path, detail: impl std::fmt::Display) -> CommandError {
+    CommandError::new(
+        ErrorCode::InvalidInput,
+        format!("Backup {} is invalid: {}", archive.display(), detail),
+    )
+    .with("path", archive.to_string_lossy())
+}
+
+fn relative(base: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(base).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some(parts.join("/"))
+}
+
+fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Progress reporter for one phase, reported through the operation.
+struct Progress<'a> {
+    op: &'a OperationHandle,
+    phase: &'static str,
+    bytes_done: u64,
+    bytes_total: u64,
+}
+
+impl<'a> Progress<'a> {
+    fn new(op: &'a OperationHandle, phase: &'static str, total: u64) -> Self {
+        Self {
+            op,
+            phase,
+            bytes_done: 0,
+            bytes_total: total,
+        }
+    }
+
+    fn advance(&mut self, bytes: u64, current: &str) {
+        self.set(self.bytes_done + bytes, current);
+    }
+
+    fn set(&mut self, bytes_done: u64, current: &str) {
+        self.bytes_done = bytes_done;
+        self.op.progress(
+            self.phase,
+            bytes_done,
+            Some(self.bytes_total),
+            Some(current),
+        );
+    }
+
+    fn done(&mut self) {
+        self.bytes_done = self.bytes_total.max(self.bytes_done);
+        self.op
+            .progress(self.phase, self.bytes_done, Some(self.bytes_total), None);
+    }
+}
@@ -0,0 +1,307 @@
+// Folder grants
+//
+// In the macOS sandbox, on iOS, and on Android, a folder picked in the
+// dialog can only be read until the app quits. To keep a store reachable
+// across restarts, its grant is saved with the store when it is connected:
+// - macOS and iOS: a security-scoped bookmark (base64 in `stores.json`).
+// - Android: the `content://` tree URI, with its permission taken as
+//   persistable.
+// Unsandboxed desktop builds read any path, so they save nothing.
+//
+// At startup `stores::restore_grants` resolves each saved grant and starts
+// access for the life of the process. A bookmark may resolve to a new path if
+// the folder was moved, and that path becomes the store root. A grant that
+// no longer resolves marks the store `needs_reauthorization` until
+// `reauthorize_store` picks the folder again.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Grant {
+    /// Base64 bookmark data.
+    SecurityScoped { bookmark: String },
+    /// Persisted SAF permission on the store's tree URI.
+    PersistedUri { uri: String },
+}
+
+/// A grant as resolved at startup.
+pub struct Resolved {
+    /// Where the folder is now.
+    pub root: String,
+    /// A fresher grant to save in place of the old one.
+    pub refreshed: Option<Grant>,
+}
+
+/// Android folders come back from the picker as `content://` tree URIs.
+pub fn is_content_uri(path: &str) -> bool {
+    path.starts_with("content://")
+}
+
+/// Save the grant for a folder the user just picked. `Ok(None)` where none is
+/// needed.
+pub fn create(path: &str) -> Result<Option<Grant>, String> {
+    if is_content_uri(path) {
+        return persist_uri(path).map(|()| {
+            Some(Grant::PersistedUri {
+                uri: path.to_string(),
+            })
+        });
+    }
+    if !needs_bookmark() {
+        return Ok(None);
+    }
+    let data = apple::create(&PathBuf::from(path))?;
+    Ok(Some(Grant::SecurityScoped {
+        bookmark: base64::engine::general_purpose::STANDARD.encode(data),
+    }))
+}
+
+/// Re-open a saved grant. `Err` means the user has to pick the folder again.
+pub fn resolve(grant: &Grant) -> Result<Resolved, String> {
+    match grant {
+        Grant::SecurityScoped { bookmark } => {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(bookmark)
+                .map_err(|e| e.to_string())?;
+            let (path, stale) = apple::resolve(&data)?;
+            let refreshed = if stale {
+                apple::create(&path).ok().map(|data| Grant::SecurityScoped {
+                    bookmark: base64::engine::general_purpose::STANDARD.encode(data),
+                })
+            } else {
+                None
+            };
+            Ok(Resolved {
+                root: path.to_string_lossy().to_string(),
+                refreshed,
+            })
+        }
+        Grant::PersistedUri { uri } => {
+            if !uri_permission_held(uri)? {
+                return Err(format!("permission for {} was revoked", uri));
+            }
+            Ok(Resolved {
+                root: uri.clone(),
+                refreshed: None,
+            })
+        }
+    }
+}
+
+// ─── macOS / iOS ────────────────────────────────────────────────────────────
+
+/// iOS is always sandboxed; macOS only with the App Sandbox entitlement,
+/// which sets `APP_SANDBOX_CONTAINER_ID`.
+fn needs_bookmark() -> bool {
+    cfg!(target_os = "ios")
+        || (cfg!(target_os = "macos") && std::env::var_os("APP_SANDBOX_CONTAINER_ID").is_some())
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod apple {
+    use core_foundation::base::TCFType;
+    use core_foundation::data::CFData;
+    use core_foundation::url::CFURL;
+    use core_foundation_sys::base::{kCFAllocatorDefault, Boolean};
+    use core_foundation_sys::url::{
+        CFURLBookmarkCreationOptions, CFURLBookmarkResolutionOptions, CFURLCreateBookmarkData,
+        CFURLCreateByResolvingBookmarkData, CFURLStartAccessingSecurityScopedResource,
+    };
+    use std::path::{Path, PathBuf};
+
+    #[cfg(target_os = "macos")]
+    fn creation_options() -> CFURLBookmarkCreationOptions {
+        core_foundation_sys::url::kCFURLBookmarkCreationWithSecurityScope
+    }
+
+    // iOS bookmarks carry the scope implicitly
+    #[cfg(target_os = "ios")]
+    fn creation_options() -> CFURLBookmarkCreationOptions {
+        0
+    }
+
+    #[cfg(target_os = "macos")]
+    const RESOLUTION_OPTIONS: CFURLBookmarkResolutionOptions =
+        core_foundation_sys::url::kCFURLBookmarkResolutionWithSecurityScope
+            | core_foundation_sys::url::kCFURLBookmarkResolutionWithoutUIMask;
+
+    #[cfg(target_os = "ios")]
+    const RESOLUTION_OPTIONS: CFURLBookmarkResolutionOptions =
+        core_foundation_sys::url::kCFURLBookmarkResolutionWithoutUIMask;
+
+    pub fn create(path: &Path) -> Result<Vec<u8>, String> {
+        let url = CFURL::from_path(path, true)
+            .ok_or_else(|| format!("not a valid path: {}", path.display()))?;
+        let data = unsafe {
+            CFURLCreateBookmarkData(
+                kCFAllocatorDefault,
+                url.as_concrete_TypeRef(),
+                creation_options(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+            )
+        };
+        if data.is_null() {
+            return Err(format!("could not bookmark {}", path.display()));
+        }
+        let data = unsafe { CFData::wrap_under_create_rule(data) };
+        Ok(data.bytes().to_vec())
+    }
+
+    /// The bookmarked path, and whether the bookmark is stale. Access is
+    /// started and never stopped, so it lasts until the app quits.
+    pub fn resolve(bookmark: &[u8]) -> Result<(PathBuf, bool), String> {
+        let data = CFData::from_buffer(bookmark);
+        let mut stale: Boolean = 0;
+        let url = unsafe {
+            CFURLCreateByResolvingBookmarkData(
+                kCFAllocatorDefault,
+                data.as_concrete_TypeRef(),
+                RESOLUTION_OPTIONS,
+                std::ptr::null(),
+                std::ptr::null(),
+                &mut stale,
+                std::ptr::null_mut(),
+            )
+        };
+        if url.is_null() {
+            return Err("bookmark no longer resolves".into());
+        }
+        let url = unsafe { CFURL::wrap_under_create_rule(url) };
+        if unsafe { CFURLStartAccessingSecurityScopedResource(url.as_concrete_TypeRef()) } == 0 {
+            return Err("access to the bookmarked folder was denied".into());
+        }
+        let path = url
+            .to_path()
+            .ok_or_else(|| "bookmark is not a file URL".to_string())?;
+        Ok((path, stale != 0))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+mod apple {
+    use std::path::{Path, PathBuf};
+
+    pub fn create(_path: &Path) -> Result<Vec<u8>, String> {
+        Err("security-scoped bookmarks need macOS or iOS".into())
+    }
+
+    pub fn resolve(_bookmark: &[u8]) -> Result<(PathBuf, bool), String> {
+        Err("security-scoped bookmarks need macOS or iOS".into())
+    }
+}
+
+// ─── Android ────────────────────────────────────────────────────────────────
+
+#[cfg(target_os = "android")]
+fn persist_uri(uri: &str) -> Result<(), String> {
+    android::take_persistable(uri)
+}
+
+#[cfg(target_os = "android")]
+fn uri_permission_held(uri: &str) -> Result<bool, String> {
+    android::is_persisted(uri)
+}
+
+#[cfg(not(target_os = "android"))]
+fn persist_uri(uri: &str) -> Result<(), String> {
+    Err(format!(
+        "content URIs are only supported on Android: {}",
+        uri
+    ))
+}
+
+#[cfg(not(target_os = "android"))]
+fn uri_permission_held(_uri: &str) -> Result<bool, String> {
+    Ok(false)
+}
+
+#[cfg(target_os = "android")]
+mod android {
+    use jni::objects::{JObject, JString, JValue};
+    use jni::{JNIEnv, JavaVM};
+
+    /// `FLAG_GRANT_READ_URI_PERMISSION | FLAG_GRANT_WRITE_URI_PERMISSION`
+    const READ_WRITE: i32 = 0x1 | 0x2;
+
+    pub fn take_persistable(uri: &str) -> Result<(), String> {
+        with_resolver(|env, resolver| {
+            let uri = parse(env, uri)?;
+            env.call_method(
+                resolver,
+                "takePersistableUriPermission",
+                "(Landroid/net/Uri;I)V",
+                &[JValue::Object(&uri), JValue::Int(READ_WRITE)],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn is_persisted(uri: &str) -> Result<bool, String> {
+        with_resolver(|env, resolver| {
+            let list = env
+                .call_method(
+                    resolver,
+                    "getPersistedUriPermissions",
+                    "()Ljava/util/List;",
+                    &[],
+                )?
+                .l()?;
+            let count = env.call_method(&list, "size", "()I", &[])?.i()?;
+            for i in 0..count {
+                let permission = env
+                    .call_method(&list, "get", "(I)Ljava/lang/Object;", &[JValue::Int(i)])?
+                    .l()?;
+                let granted = env
+                    .call_method(&permission, "getUri", "()Landroid/net/Uri;", &[])?
+                    .l()?;
+                let text = env
+                    .call_method(&granted, "toString", "()Ljava/lang/String;", &[])?
+                    .l()?;
+                let text: String = env.get_string(&JString::from(text))?.into();
+                if text == uri {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })
+    }
+
+    fn parse<'a>(env: &mut JNIEnv<'a>, uri: &str) -> jni::errors::Result<JObject<'a>> {
+        let text = env.new_string(uri)?;
+        env.call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&text)],
+        )?
+        .l()
+    }
+
+    fn with_resolver<T>(
+        f: impl FnOnce(&mut JNIEnv, &JObject) -> jni::errors::Result<T>,
+    ) -> Result<T, String> {
+        let context = ndk_context::android_context();
+        let vm = unsafe { JavaVM::from_raw(context.vm().cast()) }.map_err(|e| e.to_string())?;
+        let mut env = vm.attach_current_thread().map_err(|e| e.to_string())?;
+        let activity = unsafe { JObject::from_raw(context.context().cast()) };
+        let result = env
+            .call_method(
+                &activity,
+                "getContentResolver",
+                "()Landroid/content/ContentResolver;",
+                &[],
+            )
+            .and_then(|r| r.l())
+            .and_then(|resolver| f(&mut *env, &resolver));
+        // A SecurityException surfaces as a pending Java exception
+        if env.exception_check().unwrap_or(false) {
+            let _ = env.exception_clear();
+        }
+        result.map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,401 @@
+// Bulk frontmatter edits
+//
+// `bulk_edit_frontmatter` applies a list of `frontmatter::Edit`s (set,
+// rename, delete, append) to every Markdown note in a vault that a
+// `query_files` selector matches. The edits run in order on each note, and
+// only the lines of the fields they touch change (see `frontmatter::apply`).
+//
+// `dry_run` only plans: each note that would change comes back with a
+// unified diff, and nothing is written. A policy that forces dry runs (see
+// `dry_run`) makes every run one. A real run still plans each note
+// first, then writes it atomically under the `write_guard` lock, provided
+// the note still hashes to what was planned. A note that changed since it
+// was read, or that an edit can't apply to (a rename onto an existing key,
+// a nested value, an unclosed block), is a conflict. So is a note that
+// can't be read or written. The first conflict stops the run, and the notes
+// after it are reported `not_attempted`, unless `continue_on_conflict` is
+// set. Notes already written stay written. CRLF line endings and a BOM are
+// kept.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_query::{self, FileQuery};
+use crate::frontmatter::{self, Edit};
+use crate::text_style::{self, TextWriteOptions};
+use crate::{notes, stores, write_guard};
+
+/// Unchanged lines shown around a change.
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct BulkEditOptions {
+    pub continue_on_conflict: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EditStatus {
+    /// Would change; dry runs only.
+    Planned,
+    Updated,
+    Unchanged,
+    Conflict,
+    /// Not reached after the run stopped.
+    NotAttempted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct NoteEdit {
+    pub path: String,
+    pub relative_path: String,
+    pub status: EditStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BulkEditReport {
+    pub dry_run: bool,
+    pub notes: Vec<NoteEdit>,
+    /// Planned in a dry run, written otherwise.
+    pub changed: usize,
+    pub unchanged: usize,
+    pub conflicts: usize,
+    /// A conflict ended the run early.
+    pub stopped: bool,
+    #[serde(flatten)]
+    pub planned: DryRun,
+}
+
+struct Plan {
+    /// Of the bytes the plan was made from.
+    hash: String,
+    text: String,
+    bytes: Vec<u8>,
+    diff: String,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn bulk_edit_frontmatter(
+    window: tauri::WebviewWindow,
+    vault_path: String,
+    selector: FileQuery,
+    edits: Vec<Edit>,
+    dry_run: bool,
+    options: Option<BulkEditOptions>,
+) -> CommandResult<BulkEditReport> {
+    let dry_run = dry_run::requested(window.label(), None, Some(dry_run));
+    let options = options.unwrap_or_default();
+    let vault = Path::new(&vault_path).to_path_buf();
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", &vault_path));
+    }
+    for (i, edit) in edits.iter().enumerate() {
+        if let Some(problem) = edit.problem() {
+            return Err(CommandError::new(ErrorCode::InvalidInput, problem).with("edit", i));
+        }
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        bulk_edit(&vault, &selector, &edits, dry_run, &options)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Editing ────────────────────────────────────────────────────────────────
+
+fn bulk_edit(
+    vault: &Path,
+    selector: &FileQuery,
+    edits: &[Edit],
+    dry_run: bool,
+    options: &BulkEditOptions,
+) -> CommandResult<BulkEditReport> {
+    let snapshot = stores::scan_for_index(vault)?;
+    let selected = file_query::matching(vault, &snapshot, selector)?
+        .into_iter()
+        .filter(|f| f.relative_path.to_lowercase().ends_with(".md"));
+    let mut report = BulkEditReport {
+        dry_run,
+        notes: Vec::new(),
+        changed: 0,
+        unchanged: 0,
+        conflicts: 0,
+        stopped: false,
+        planned: DryRun::default(),
+    };
+    for file in selected {
+        let mut note = NoteEdit {
+            path: file.path,
+            relative_path: file.relative_path,
+            status: EditStatus::NotAttempted,
+            diff: None,
+            error: None,
+        };
+        if report.stopped {
+            report.notes.push(note);
+            continue;
+        }
+        let path = Path::new(&note.path);
+        let outcome = plan(path, edits).and_then(|plan| match plan {
+            Some(plan) if !dry_run => write(path, &plan).map(|()| Some(plan)),
+            plan => Ok(plan),
+        });
+        match outcome {
+            Ok(Some(plan)) => {
+                note.status = if dry_run {
+                    EditStatus::Planned
+                } else {
+                    EditStatus::Updated
+                };
+                note.diff = Some(plan.diff);
+                report.changed += 1;
+            }
+            Ok(None) => {
+                note.status = EditStatus::Unchanged;
+                report.unchanged += 1;
+            }
+            Err(e) => {
+                log::warn!("[frontmatter] not editing {}: {}", note.path, e.message);
+                note.status = EditStatus::Conflict;
+                note.error = Some(e);
+                report.conflicts += 1;
+                report.stopped = !dry_run && !options.continue_on_conflict;
+            }
+        }
+        report.notes.push(note);
+    }
+    report.planned = DryRun::outcome(dry_run, || {
+        format!("Edit the frontmatter of {} notes", report.changed)
+    });
+    Ok(report)
+}
+
+/// The note after `edits`, or `None` if they change nothing.
+fn plan(path: &Path, edits: &[Edit]) -> CommandResult<Option<Plan>> {
+    let bytes = fs::read(path)?;
+    let style = text_style::detect(&bytes);
+    let (ending, bom) = text_style::resolve(path, Some(&style), &TextWriteOptions::default())?;
+    let text = text_style::decode(&bytes, &style).replace("\r\n", "\n");
+    let edited = frontmatter::apply(&text, edits).map_err(|reason| {
+        CommandError::new(
+            ErrorCode::Conflict,
+            format!("Can't edit {}: {}", path.display(), reason),
+        )
+        .with("path", path.to_string_lossy())
+    })?;
+    if edited == text {
+        return Ok(None);
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    Ok(Some(Plan {
+        hash: write_guard::sha256(&bytes),
+        diff: unified_diff(&name, &text, &edited),
+        bytes: text_style::encode(&edited, ending, bom),
+        text: edited,
+    }))
+}
+
+fn write(path: &Path, plan: &Plan) -> CommandResult<()> {
+    let _guard = write_guard::lock();
+    let current = write_guard::read_current(path)?;
+    write_guard::check(path, current.as_deref(), &plan.hash, Some(&plan.text))?;
+    notes::write_atomic(path, &plan.bytes)?;
+    activity::record(ActivityKind::Write, path, None);
+    Ok(())
+}
+
+/// `old` to `new` as a single-hunk unified diff.
+fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let start = prefix.saturating_sub(DIFF_CONTEXT);
+    let old_end = (old.len() - suffix + DIFF_CONTEXT).min(old.len());
+    let new_end = (new.len() - suffix + DIFF_CONTEXT).min(new.len());
+
+    let mut diff = format!(
+        "--- a/{name}\n+++ b/{name}\n@@ -{},{} +{},{} @@\n",
+        start + 1,
+        old_end - start,
+        start + 1,
+        new_end - start,
+    );
+    for line in &old[start..prefix] {
+        diff.push_str(&format!(" {}\n", line));
+    }
+    for (mark, line) in line_diff(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ) {
+        diff.push_str(&format!("{}{}\n", mark, line));
+    }
+    for line in &old[old.len() - suffix..old_end] {
+        diff.push_str(&format!(" {}\n", line));
+    }
+    diff
+}
+
+/// `old` to `new` line by line, keeping their longest common subsequence,
+/// with removals before additions.
+fn line_diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    // common[i][j]: longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn edits(json: serde_json::Value) -> Vec<Edit> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn selector(json: serde_json::Value) -> FileQuery {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn vault(home: &TestHome) -> std::path::PathBuf {
+        home.write(
+            "Vault/a.md",
+            "---\n# owner first\ntype: 'meeting' # legacy\ntags:\n  - work\n---\nBody\n",
+        );
+        home.write("Vault/b.md", "---\r\ntype: call\r\n---\r\nBody\r\n");
+        home.write("Vault/c.md", "No frontmatter #work\n");
+        home.join("Vault")
+    }
+
+    #[test]
+    fn dry_runs_return_diffs_and_write_nothing() {
+        let home = TestHome::new();
+        let vault = vault(&home);
+        let before = fs::read_to_string(vault.join("a.md")).unwrap();
+        let report = bulk_edit(
+            &vault,
+            &selector(serde_json::json!({ "tags": ["work"] })),
+            &edits(serde_json::json!([
+                { "op": "rename", "from": "type", "to": "kind" },
+                { "op": "append", "key": "tags", "value": "review" },
+            ])),
+            true,
+            &BulkEditOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.changed, 2);
+        let a = &report.notes[0];
+        assert_eq!(a.status, EditStatus::Planned);
+        let diff = a.diff.as_deref().unwrap();
+        assert!(diff.contains("-type: 'meeting' # legacy\n+kind: 'meeting' # legacy\n"));
+        assert!(diff.contains("   - work\n+  - review\n"));
+        // c.md gains a block
+        assert!(report.notes[1]
+            .diff
+            .as_deref()
+            .unwrap()
+            .contains("+tags:\n+  - review\n"));
+        assert_eq!(fs::read_to_string(vault.join("a.md")).unwrap(), before);
+    }
+
+    #[test]
+    fn writes_keep_line_endings_and_stop_at_a_conflict() {
+        let home = TestHome::new();
+        let vault = vault(&home);
+        // a.md already has `kind`, so the rename conflicts there
+        home.write("Vault/a.md", "---\ntype: x\nkind: y\n---\n");
+        let rename = edits(serde_json::json!([{ "op": "rename", "from": "type", "to": "kind" }]));
+        let all = FileQuery::default();
+
+        let report = bulk_edit(&vault, &all, &rename, false, &BulkEditOptions::default()).unwrap();
+        let statuses: Vec<EditStatus> = report.notes.iter().map(|n| n.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                EditStatus::Conflict,
+                EditStatus::NotAttempted,
+                EditStatus::NotAttempted
+            ]
+        );
+        assert!(report.stopped);
+        assert_eq!(
+            report.notes[0].error.as_ref().unwrap().code,
+            ErrorCode::Conflict
+        );
+
+        let options = BulkEditOptions {
+            continue_on_conflict: true,
+        };
+        let report = bulk_edit(&vault, &all, &rename, false, &options).unwrap();
+        let statuses: Vec<EditStatus> = report.notes.iter().map(|n| n.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                EditStatus::Conflict,
+                EditStatus::Updated,
+                EditStatus::Unchanged
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(vault.join("b.md")).unwrap(),
+            "---\r\nkind: call\r\n---\r\nBody\r\n"
+        );
+    }
+
+    #[test]
+    fn diffs_show_context_around_the_change() {
+        let diff = unified_diff(
+            "n.md",
+            "---\na: 1\nb: 2\n---\nx\n",
+            "---\na: 1\nb: 3\n---\nx\n",
+        );
+        assert_eq!(
+            diff,
+            "--- a/n.md\n+++ b/n.md\n@@ -1,5 +1,5 @@\n ---\n a: 1\n-b: 2\n+b: 3\n ---\n x\n"
+        );
+    }
+}
@@ -0,0 +1,903 @@
+// Bulk rename
+//
+// `bulk_rename` renames a batch of files, each within its own folder, to a
+// template like `{date}-{counter:3}-{original}`. The tokens:
+// - `{original}`: the old name without its extension
+// - `{ext}`: the extension without its dot. Without `{ext}` in the template,
+//   each file keeps its extension.
+// - `{date}`: the modified date in local time as `%Y-%m-%d`, or in any
+//   `chrono` format with `{date:%Y%m%d}`
+// - `{counter}`: the file's place in `paths`, from `counter_start` (1);
+//   `{counter:3}` pads it with zeros to three digits
+// - `{match:1}`, `{match:name}`: a capture of `pattern`, a regex run on the
+//   old name without its extension
+// `{{` and `}}` are literal braces.
+//
+// A call without `confirm` is a preview: it returns each old → new pair with
+// its warnings, and a `plan_token`, and renames nothing. Passing the token
+// back as `confirm` runs the plan, provided the files still plan to the same
+// token; if they don't, it's a `Conflict` and the UI previews again. A policy
+// that forces dry runs (see `dry_run`) keeps every call a preview.
+//
+// New names are made valid with `filenames::sanitize_filename`, with an
+// `invalid_name` warning saying what changed. A file is skipped, with a
+// warning, when it's missing, when another file in the batch would get the
+// same name (compared case-insensitively, as macOS and Windows do), or when
+// the name belongs to a file that isn't moving. When a new name is another
+// file's old one, every file first moves to a temporary name beside it,
+// then to its new name, so no rename lands on a file that hasn't moved yet.
+//
+// In an Obsidian vault store, `update_links` moves each note through
+// `note_links`, which rewrites the links to it; a preview lists the notes
+// that would change. Files are renamed one at a time, and every entry's
+// `path` says where its file is afterwards. A failure stops the run, leaving
+// the files after it `not_attempted`, unless `continue_on_error` is set. In
+// the first phase of a two-phase run, a failure always stops it and moves the
+// files already at temporary names back. In the second, the run carries on,
+// and a file that can't take its new name goes back to its old one if that's
+// still free.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::filenames::{self, NameChange, SanitizeOptions};
+use crate::store_kinds::StoreKind;
+use crate::{note_links, stores};
+
+const MAX_RENAMES: usize = 10_000;
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct BulkRenameOptions {
+    /// Run on the old name without its extension, for `{match:…}`.
+    pub pattern: Option<String>,
+    /// The first `{counter}`; 1 when unset.
+    pub counter_start: Option<u64>,
+    pub update_links: bool,
+    pub continue_on_error: bool,
+    pub sanitize: SanitizeOptions,
+    /// A preview's `plan_token`; without one the call is a preview.
+    pub confirm: Option<String>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RenameWarning {
+    /// Skipped: not found, or not a file.
+    Missing,
+    /// Skipped: another file in the batch would get the same name.
+    Collision { with: String },
+    /// Skipped: a file that isn't moving has the name.
+    Exists,
+    /// The name was made valid.
+    InvalidName { changes: Vec<NameChange> },
+    /// `pattern` didn't match; its captures are empty.
+    NoMatch,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameStatus {
+    /// Would be renamed; previews only.
+    Planned,
+    Renamed,
+    /// Already has its new name.
+    Unchanged,
+    /// Held back by a warning.
+    Skipped,
+    Failed,
+    /// Not reached after a failure stopped the run.
+    NotAttempted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RenameEntry {
+    pub from: String,
+    pub to: String,
+    pub status: RenameStatus,
+    /// Where the file is now.
+    pub path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RenameWarning>,
+    /// Vault-relative notes whose links to the file changed, or would.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub updated_notes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BulkRenameReport {
+    pub dry_run: bool,
+    pub entries: Vec<RenameEntry>,
+    /// Planned in a preview, done otherwise.
+    pub renamed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// Files go through temporary names.
+    pub two_phase: bool,
+    /// Some files are in a vault, where `update_links` applies.
+    pub links_available: bool,
+    /// Pass back as `confirm` to run this plan.
+    pub plan_token: String,
+    #[serde(flatten)]
+    pub planned: DryRun,
+}
+
+enum Part {
+    Text(String),
+    Original,
+    Ext,
+    Date(String),
+    /// Zero-padded to this width.
+    Counter(usize),
+    Capture(Group),
+}
+
+enum Group {
+    Index(usize),
+    Name(String),
+}
+
+struct Template {
+    parts: Vec<Part>,
+    pattern: Option<Regex>,
+}
+
+struct Rename {
+    from: PathBuf,
+    to: PathBuf,
+    /// The vault's resolved root and the file's path inside it.
+    vault: Option<(PathBuf, PathBuf)>,
+    entry: RenameEntry,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn bulk_rename(
+    window: tauri::WebviewWindow,
+    paths: Vec<String>,
+    template: String,
+    options: Option<BulkRenameOptions>,
+) -> CommandResult<BulkRenameReport> {
+    let options = options.unwrap_or_default();
+    let dry_run = dry_run::requested(window.label(), None, Some(options.confirm.is_none()));
+    if paths.len() > MAX_RENAMES {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Can't rename more than {} files at once", MAX_RENAMES),
+        )
+        .with("limit", MAX_RENAMES));
+    }
+    let template = Template::parse(&template, options.pattern.as_deref())?;
+    tauri::async_runtime::spawn_blocking(move || bulk(&paths, &template, dry_run, &options))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Templates ──────────────────────────────────────────────────────────────
+
+impl Template {
+    fn parse(template: &str, pattern: Option<&str>) -> CommandResult<Self> {
+        let invalid = |reason: String| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid rename template: {}", reason),
+            )
+            .with("template", template)
+        };
+        let pattern = pattern
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| invalid(format!("bad pattern: {}", e)))?;
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => return Err(invalid("unclosed `{`".into())),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Self::token(&token, pattern.as_ref()).map_err(invalid)?);
+                }
+                '}' => return Err(invalid("unmatched `}`".into())),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        if parts.is_empty() {
+            return Err(invalid("it's empty".into()));
+        }
+        Ok(Self { parts, pattern })
+    }
+
+    fn token(token: &str, pattern: Option<&Regex>) -> Result<Part, String> {
+        let (name, arg) = match token.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (token, None),
+        };
+        match (name, arg) {
+            ("original", None) => Ok(Part::Original),
+            ("ext", None) => Ok(Part::Ext),
+            ("date", format) => {
+                let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+                let valid = chrono::format::StrftimeItems::new(format)
+                    .all(|item| !matches!(item, chrono::format::Item::Error));
+                match valid {
+                    true => Ok(Part::Date(format.to_string())),
+                    false => Err(format!("bad date format `{}`", format)),
+                }
+            }
+            ("counter", None) => Ok(Part::Counter(0)),
+            ("counter", Some(width)) => width
+                .parse()
+                .map(Part::Counter)
+                .map_err(|_| format!("bad counter width `{}`", width)),
+            ("match", Some(group)) => {
+                let pattern = pattern.ok_or("`{match:…}` needs a pattern")?;
+                let group = match group.parse() {
+                    Ok(i) if i < pattern.captures_len() => Group::Index(i),
+                    Ok(i) => return Err(format!("the pattern has no group {}", i)),
+                    Err(_) if pattern.capture_names().flatten().any(|n| n == group) => {
+                        Group::Name(group.to_string())
+                    }
+                    Err(_) => return Err(format!("the pattern has no group `{}`", group)),
+                };
+                Ok(Part::Capture(group))
+            }
+            _ => Err(format!("unknown token `{{{}}}`", token)),
+        }
+    }
+
+    /// The new name for the file `name`, before sanitizing.
+    fn render(
+        &self,
+        name: &str,
+        meta: &fs::Metadata,
+        counter: u64,
+        warnings: &mut Vec<RenameWarning>,
+    ) -> String {
+        let (stem, ext) = filenames::split_extension(name);
+        let captures = self.pattern.as_ref().and_then(|p| p.captures(stem));
+        if self.pattern.is_some() && captures.is_none() {
+            warnings.push(RenameWarning::NoMatch);
+        }
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Original => rendered.push_str(stem),
+                Part::Ext => rendered.push_str(ext.trim_start_matches('.')),
+                Part::Date(format) => {
+                    let modified: chrono::DateTime<chrono::Local> = meta
+                        .modified()
+                        .map(Into::into)
+                        .unwrap_or_else(|_| chrono::Local::now());
+                    rendered.push_str(&modified.format(format).to_string());
+                }
+                Part::Counter(width) => {
+                    rendered.push_str(&format!("{:0width$}", counter, width = *width))
+                }
+                Part::Capture(group) => {
+                    let capture = captures.as_ref().and_then(|c| match group {
+                        Group::Index(i) => c.get(*i),
+                        Group::Name(name) => c.name(name),
+                    });
+                    rendered.push_str(capture.map_or("", |m| m.as_str()));
+                }
+            }
+        }
+        if !self.parts.iter().any(|p| matches!(p, Part::Ext)) {
+            rendered.push_str(ext);
+        }
+        rendered
+    }
+}
+
+// ─── Planning ───────────────────────────────────────────────────────────────
+
+fn bulk(
+    paths: &[String],
+    template: &Template,
+    dry_run: bool,
+    options: &BulkRenameOptions,
+) -> CommandResult<BulkRenameReport> {
+    let mut renames = plan(paths, template, options);
+    let two_phase = overlaps(&renames);
+    let plan_token = token(&renames);
+    if !dry_run && options.confirm.as_deref() != Some(plan_token.as_str()) {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            "The files changed since the rename was previewed; preview it again",
+        ));
+    }
+
+    if dry_run && options.update_links {
+        for rename in renames
+            .iter_mut()
+            .filter(|r| r.entry.status == RenameStatus::Planned)
+        {
+            let Some((root, relative)) = &rename.vault else {
+                continue;
+            };
+            let to = relative.with_file_name(rename.to.file_name().unwrap_or_default());
+            match note_links::move_with_links(root, relative, &to, true) {
+                Ok(notes) => rename.entry.updated_notes = notes,
+                Err(e) => log::warn!("[rename] can't plan links to {}: {}", rename.entry.from, e),
+            }
+        }
+    }
+    if !dry_run {
+        match two_phase {
+            true => run_two_phase(&mut renames, &plan_token, options),
+            false => run_direct(&mut renames, options),
+        }
+    }
+
+    let count = |wanted: &[RenameStatus]| {
+        renames
+            .iter()
+            .filter(|r| wanted.contains(&r.entry.status))
+            .count()
+    };
+    let renamed = count(&[RenameStatus::Planned, RenameStatus::Renamed]);
+    Ok(BulkRenameReport {
+        dry_run,
+        renamed,
+        skipped: count(&[RenameStatus::Skipped]),
+        failed: count(&[RenameStatus::Failed]),
+        two_phase,
+        links_available: renames.iter().any(|r| r.vault.is_some()),
+        planned: DryRun::outcome(dry_run, || format!("Rename {} files", renamed)),
+        plan_token,
+        entries: renames.into_iter().map(|r| r.entry).collect(),
+    })
+}
+
+fn plan(paths: &[String], template: &Template, options: &BulkRenameOptions) -> Vec<Rename> {
+    let start = options.counter_start.unwrap_or(1);
+    let mut vaults: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    let mut renames: Vec<Rename> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let from = PathBuf::from(path);
+            let name = from.file_name().unwrap_or_default().to_string_lossy();
+            let mut warnings = Vec::new();
+            let (to, status) = match fs::metadata(&from) {
+                Ok(meta) if meta.is_file() => {
+                    let rendered = template.render(&name, &meta, start + i as u64, &mut warnings);
+                    let sanitized = filenames::sanitize_filename(&rendered, &options.sanitize);
+                    if !sanitized.changes.is_empty() {
+                        warnings.push(RenameWarning::InvalidName {
+                            changes: sanitized.changes,
+                        });
+                    }
+                    let to = from.with_file_name(&sanitized.name);
+                    let status = match to == from {
+                        true => RenameStatus::Unchanged,
+                        false => RenameStatus::Planned,
+                    };
+                    (to, status)
+                }
+                _ => {
+                    warnings.push(RenameWarning::Missing);
+                    (from.clone(), RenameStatus::Skipped)
+                }
+            };
+            let vault = match status {
+                RenameStatus::Planned => vault_of(&from, &mut vaults),
+                _ => None,
+            };
+            Rename {
+                entry: RenameEntry {
+                    from: path.clone(),
+                    to: to.to_string_lossy().into_owned(),
+                    status,
+                    path: path.clone(),
+                    warnings,
+                    updated_notes: Vec::new(),
+                    error: None,
+                },
+                from,
+                to,
+                vault,
+            }
+        })
+        .collect();
+    skip_collisions(&mut renames);
+    renames
+}
+
+/// The vault holding `file`, as its resolved root and `file` inside it.
+fn vault_of(
+    file: &Path,
+    vaults: &mut HashMap<PathBuf, Option<PathBuf>>,
+) -> Option<(PathBuf, PathBuf)> {
+    let folder = file.parent()?.canonicalize().ok()?;
+    let root = vaults
+        .entry(folder.clone())
+        .or_insert_with(|| {
+            stores::store_containing(&folder)
+                .filter(|s| s.kind == StoreKind::ObsidianVault)
+                .and_then(|s| Path::new(&s.path).canonicalize().ok())
+        })
+        .clone()?;
+    let relative = folder.strip_prefix(&root).ok()?.join(file.file_name()?);
+    Some((root, relative))
+}
+
+/// Case-folded, as macOS and Windows compare names.
+fn fold(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// Skip files whose new name is taken, until none are: holding one file back
+/// keeps its old name taken.
+fn skip_collisions(renames: &mut [Rename]) {
+    loop {
+        let moving = |r: &&Rename| r.entry.status == RenameStatus::Planned;
+        let sources: HashSet<String> = renames
+            .iter()
+            .filter(moving)
+            .map(|r| fold(&r.from))
+            .collect();
+        let staying: HashSet<String> = renames
+            .iter()
+            .filter(|r| !moving(r) && r.from.exists())
+            .map(|r| fold(&r.from))
+            .collect();
+        let mut claimed: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, r) in renames.iter().enumerate().filter(|(_, r)| moving(r)) {
+            claimed.entry(fold(&r.to)).or_default().push(i);
+        }
+
+        let mut skipped = Vec::new();
+        for (i, r) in renames.iter().enumerate().filter(|(_, r)| moving(r)) {
+            let target = fold(&r.to);
+            let case_only = target == fold(&r.from);
+            let warning = if let Some(&other) = claimed[&target].iter().find(|&&j| j != i) {
+                Some(RenameWarning::Collision {
+                    with: renames[other].entry.from.clone(),
+                })
+            } else if !case_only
+                && (staying.contains(&target) || (r.to.exists() && !sources.contains(&target)))
+            {
+                Some(RenameWarning::Exists)
+            } else {
+                None
+            };
+            if let Some(warning) = warning {
+                skipped.push((i, warning));
+            }
+        }
+        if skipped.is_empty() {
+            return;
+        }
+        for (i, warning) in skipped {
+            renames[i].entry.status = RenameStatus::Skipped;
+            renames[i].entry.warnings.push(warning);
+            renames[i].vault = None;
+        }
+    }
+}
+
+/// Whether a new name is another moving file's old one.
+fn overlaps(renames: &[Rename]) -> bool {
+    let moving: Vec<&Rename> = renames
+        .iter()
+        .filter(|r| r.entry.status == RenameStatus::Planned)
+        .collect();
+    let sources: HashSet<String> = moving.iter().map(|r| fold(&r.from)).collect();
+    moving
+        .iter()
+        .any(|r| fold(&r.to) != fold(&r.from) && sources.contains(&fold(&r.to)))
+}
+
+/// Changes when a file, its new name, or whether it moves does.
+fn token(renames: &[Rename]) -> String {
+    let mut hasher = Sha256::new();
+    for rename in renames {
+        let (size, modified) = fs::metadata(&rename.from)
+            .map(|m| (m.len(), m.modified().ok()))
+            .unwrap_or_default();
+        hasher.update(format!(
+            "{}\0{}\0{:?}\0{}\0{:?}\n",
+            rename.entry.from, rename.entry.to, rename.entry.status, size, modified
+        ));
+    }
+    hex::encode(hasher.finalize())[..16].to_string()
+}
+
+// ─── Renaming ───────────────────────────────────────────────────────────────
+
+fn run_direct(renames: &mut [Rename], options: &BulkRenameOptions) {
+    let mut stopped = false;
+    for rename in renames
+        .iter_mut()
+        .filter(|r| r.entry.status == RenameStatus::Planned)
+    {
+        if stopped {
+            rename.entry.status = RenameStatus::NotAttempted;
+            continue;
+        }
+        let result = move_file(rename, &rename.from, &rename.to, options.update_links);
+        stopped = !finish(rename, result) && !options.continue_on_error;
+    }
+}
+
+fn run_two_phase(renames: &mut [Rename], plan_token: &str, options: &BulkRenameOptions) {
+    let moving: Vec<usize> = (0..renames.len())
+        .filter(|&i| renames[i].entry.status == RenameStatus::Planned)
+        .collect();
+    let mut parked: Vec<(usize, PathBuf)> = Vec::new();
+    for &i in &moving {
+        let rename = &mut renames[i];
+        let temp = temp_path(&rename.from, plan_token);
+        match move_file(rename, &rename.from, &temp, options.update_links) {
+            Ok(notes) => {
+                rename.entry.updated_notes = notes;
+                rename.entry.path = temp.to_string_lossy().into_owned();
+                parked.push((i, temp));
+            }
+            Err(e) => {
+                finish(rename, Err(e));
+                unpark(renames, parked, options);
+                for &j in &moving {
+                    if renames[j].entry.status == RenameStatus::Planned {
+                        renames[j].entry.status = RenameStatus::NotAttempted;
+                    }
+                }
+                return;
+            }
+        }
+    }
+
+    for (i, temp) in parked {
+        let rename = &mut renames[i];
+        let result = move_file(rename, &temp, &rename.to, options.update_links);
+        if let Err(e) = result {
+            log::warn!("[rename] couldn't rename {}: {}", rename.entry.from, e);
+            if !rename.from.exists()
+                && move_file(rename, &temp, &rename.from, options.update_links).is_ok()
+            {
+                rename.entry.path = rename.entry.from.clone();
+            }
+            rename.entry.status = RenameStatus::Failed;
+            rename.entry.error = Some(e);
+            continue;
+        }
+        let notes = result.unwrap_or_default();
+        for note in notes {
+            if !rename.entry.updated_notes.contains(&note) {
+                rename.entry.updated_notes.push(note);
+            }
+        }
+        finish(rename, Ok(Vec::new()));
+    }
+}
+
+/// Move files parked at temporary names back after the first phase failed.
+fn unpark(renames: &mut [Rename], parked: Vec<(usize, PathBuf)>, options: &BulkRenameOptions) {
+    for (i, temp) in parked.into_iter().rev() {
+        let rename = &mut renames[i];
+        match move_file(rename, &temp, &rename.from, options.update_links) {
+            Ok(_) => {
+                rename.entry.path = rename.entry.from.clone();
+                rename.entry.updated_notes.clear();
+            }
+            Err(e) => {
+                log::error!(
+                    "[rename] couldn't move {} back from {}: {}",
+                    rename.entry.from,
+                    temp.display(),
+                    e
+                );
+                rename.entry.status = RenameStatus::Failed;
+                rename.entry.error = Some(e);
+            }
+        }
+    }
+}
+
+/// Beside `from`, keeping its extension so links to it still resolve.
+fn temp_path(from: &Path, plan_token: &str) -> PathBuf {
+    let name = from.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, ext) = filenames::split_extension(&name);
+    from.with_file_name(format!("{}.renaming-{}{}", stem, &plan_token[..8], ext))
+}
+
+/// Rename `from` to `to`, through `note_links` in a vault when `links`.
+fn move_file(rename: &Rename, from: &Path, to: &Path, links: bool) -> CommandResult<Vec<String>> {
+    if to.exists() && fold(to) != fold(from) {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("{} already exists", to.display()),
+        )
+        .with("path", to.to_string_lossy()));
+    }
+    match rename.vault.as_ref().filter(|_| links) {
+        Some((root, relative)) => note_links::move_with_links(
+            root,
+            &relative.with_file_name(from.file_name().unwrap_or_default()),
+            &relative.with_file_name(to.file_name().unwrap_or_default()),
+            false,
+        ),
+        None => {
+            fs::rename(from, to)
+                .map_err(|e| CommandError::from(e).with("path", from.to_string_lossy()))?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Record how the final rename of `rename` went; whether it succeeded.
+fn finish(rename: &mut Rename, result: CommandResult<Vec<String>>) -> bool {
+    match result {
+        Ok(notes) => {
+            activity::record(ActivityKind::Write, &rename.to, None);
+            rename.entry.status = RenameStatus::Renamed;
+            rename.entry.path = rename.entry.to.clone();
+            rename.entry.updated_notes.extend(notes);
+            true
+        }
+        Err(e) => {
+            log::warn!("[rename] couldn't rename {}: {}", rename.entry.from, e);
+            rename.entry.status = RenameStatus::Failed;
+            rename.entry.error = Some(e);
+            false
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn options(json: serde_json::Value) -> BulkRenameOptions {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn paths(home: &TestHome, names: &[&str]) -> Vec<String> {
+        names
+            .iter()
+            .map(|n| home.join(n).to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn names(report: &BulkRenameReport) -> Vec<String> {
+        report
+            .entries
+            .iter()
+            .map(|e| {
+                Path::new(&e.to)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    /// Preview, then run what was previewed.
+    fn run(paths: &[String], template: &str, json: serde_json::Value) -> BulkRenameReport {
+        let template = Template::parse(template, json["pattern"].as_str()).unwrap();
+        let mut options = options(json);
+        let preview = bulk(paths, &template, true, &options).unwrap();
+        options.confirm = Some(preview.plan_token);
+        bulk(paths, &template, false, &options).unwrap()
+    }
+
+    #[test]
+    fn templates_fill_in_tokens_and_previews_rename_nothing() {
+        let home = TestHome::new();
+        home.write("Media/IMG-20240105-WA0007.jpg", "a");
+        home.write("Media/IMG-20240105-WA0012.jpg", "b");
+        let paths = paths(
+            &home,
+            &[
+                "Media/IMG-20240105-WA0007.jpg",
+                "Media/IMG-20240105-WA0012.jpg",
+            ],
+        );
+        let template = Template::parse(
+            "{match:day}-{counter:3}-{match:2}.{ext}",
+            Some(r"IMG-(?<day>\d{8})-(WA\d+)"),
+        )
+        .unwrap();
+        let report = bulk(&paths, &template, true, &options(serde_json::json!({}))).unwrap();
+        assert_eq!(
+            names(&report),
+            ["20240105-001-WA0007.jpg", "20240105-002-WA0012.jpg"]
+        );
+        assert_eq!(report.renamed, 2);
+        assert!(report.planned.would_have);
+        assert!(home.join("Media/IMG-20240105-WA0007.jpg").exists());
+
+        let template = Template::parse("{date}_{original}", None).unwrap();
+        let report = bulk(
+            &paths[..1],
+            &template,
+            true,
+            &options(serde_json::json!({})),
+        )
+        .unwrap();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            names(&report),
+            [format!("{}_IMG-20240105-WA0007.jpg", today)]
+        );
+
+        for bad in [
+            "{nope}",
+            "{counter:x}",
+            "{match:1}",
+            "{date:%Q}",
+            "a}",
+            "{original",
+        ] {
+            assert!(Template::parse(bad, None).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn running_needs_the_previewed_plan() {
+        let home = TestHome::new();
+        home.write("Scans/scan 1.pdf", "a");
+        let paths = paths(&home, &["Scans/scan 1.pdf"]);
+        let template = Template::parse("Invoice {counter:2}", None).unwrap();
+        let mut options = options(serde_json::json!({ "confirm": "0123456789abcdef" }));
+        let err = bulk(&paths, &template, false, &options).err().unwrap();
+        assert_eq!(err.code, ErrorCode::Conflict);
+
+        options.confirm = None;
+        let preview = bulk(&paths, &template, true, &options).unwrap();
+        home.write("Scans/scan 1.pdf", "changed since");
+        options.confirm = Some(preview.plan_token);
+        assert!(bulk(&paths, &template, false, &options).is_err());
+        assert!(home.join("Scans/scan 1.pdf").exists());
+
+        let report = run(&paths, "Invoice {counter:2}", serde_json::json!({}));
+        assert_eq!(report.entries[0].status, RenameStatus::Renamed);
+        assert!(home.join("Scans/Invoice 01.pdf").exists());
+        assert!(!home.join("Scans/scan 1.pdf").exists());
+    }
+
+    #[test]
+    fn collisions_and_taken_names_are_skipped_and_invalid_names_fixed() {
+        let home = TestHome::new();
+        for name in ["a.txt", "b.txt", "c.txt", "keep.txt"] {
+            home.write(&format!("Dir/{}", name), name);
+        }
+        let paths = paths(
+            &home,
+            &["Dir/a.txt", "Dir/b.txt", "Dir/c.txt", "Dir/gone.txt"],
+        );
+        let report = run(
+            &paths,
+            "{match:1}",
+            serde_json::json!({ "pattern": "^(a|b|c)$" }),
+        );
+        assert_eq!(report.renamed, 0);
+        assert_eq!(report.entries[3].warnings, [RenameWarning::Missing]);
+
+        let template = Template::parse("same", None).unwrap();
+        let report = bulk(
+            &paths[..2],
+            &template,
+            true,
+            &options(serde_json::json!({})),
+        )
+        .unwrap();
+        assert_eq!(report.skipped, 2);
+        assert!(matches!(
+            &report.entries[0].warnings[..],
+            [RenameWarning::Collision { with }] if *with == paths[1]
+        ));
+
+        let template = Template::parse("keep", None).unwrap();
+        let report = bulk(
+            &paths[..1],
+            &template,
+            true,
+            &options(serde_json::json!({})),
+        )
+        .unwrap();
+        assert_eq!(report.entries[0].warnings, [RenameWarning::Exists]);
+
+        let template = Template::parse("Q3: {original}?", None).unwrap();
+        let report = bulk(
+            &paths[..1],
+            &template,
+            true,
+            &options(serde_json::json!({})),
+        )
+        .unwrap();
+        assert_eq!(names(&report), ["Q3_ a_.txt"]);
+        assert!(matches!(
+            report.entries[0].warnings[..],
+            [RenameWarning::InvalidName { .. }]
+        ));
+    }
+
+    #[test]
+    fn swapped_names_go_through_temporary_names() {
+        let home = TestHome::new();
+        home.write("Dir/1.txt", "one");
+        home.write("Dir/2.txt", "two");
+        home.write("Dir/3.txt", "three");
+        let paths = paths(&home, &["Dir/1.txt", "Dir/2.txt", "Dir/3.txt"]);
+        // 1 → 2, 2 → 3, 3 → 4
+        let report = run(
+            &paths,
+            "{counter}",
+            serde_json::json!({ "counter_start": 2 }),
+        );
+        assert!(report.two_phase);
+        assert_eq!(report.renamed, 3);
+        assert_eq!(fs::read_to_string(home.join("Dir/2.txt")).unwrap(), "one");
+        assert_eq!(fs::read_to_string(home.join("Dir/4.txt")).unwrap(), "three");
+        assert!(!home.join("Dir/1.txt").exists());
+        let leftovers = fs::read_dir(home.join("Dir")).unwrap().count();
+        assert_eq!(leftovers, 3);
+    }
+
+    #[test]
+    fn notes_in_a_vault_keep_their_links() {
+        let home = TestHome::new();
+        home.write("Vault/.obsidian/app.json", "{}");
+        home.write("Vault/Plan.md", "# Plan\n");
+        home.write("Vault/Index.md", "See [[Plan]] and [plan](Plan.md).\n");
+        let vault = home.join("Vault").to_string_lossy().into_owned();
+        stores::register_store("Vault".into(), vault, Some(StoreKind::ObsidianVault)).unwrap();
+        let paths = paths(&home, &["Vault/Plan.md"]);
+        let template = Template::parse("{original}-2024", None).unwrap();
+        let options = options(serde_json::json!({ "update_links": true }));
+        let preview = bulk(&paths, &template, true, &options).unwrap();
+        assert!(preview.links_available);
+        assert_eq!(preview.entries[0].updated_notes, ["Index.md"]);
+
+        let report = run(
+            &paths,
+            "{original}-2024",
+            serde_json::json!({ "update_links": true }),
+        );
+        assert_eq!(report.entries[0].status, RenameStatus::Renamed);
+        assert_eq!(
+            fs::read_to_string(home.join("Vault/Index.md")).unwrap(),
+            "See [[Plan-2024]] and [plan](Plan-2024.md).\n"
+        );
+    }
+}
@@ -0,0 +1,1352 @@
+// Calendars
+//
+// `parse_ics` lists the events of an iCalendar file or feed (RFC 5545) in a
+// window; `get_events` does the same across the calendars in
+// `settings.calendars`, for today, the next event, or any range, so a
+// meeting note can start from the meeting.
+// - Feeds are `http`, `https`, or `webcal` URLs. They're fetched like
+//   smart-paste links (see `url_metadata::get_public`), so internal
+//   addresses are refused, and cached for `CACHE_TTL`.
+// - Times are given in the local zone. A `TZID` is looked up in the
+//   calendar's own `VTIMEZONE`s; UTC names are UTC, and a zone the calendar
+//   doesn't define is read as local time. All-day events keep their dates
+//   and have `all_day` set.
+// - Recurring events are expanded in their own zone, so a 09:00 meeting
+//   stays at 09:00 across daylight-saving changes. `RRULE` supports FREQ
+//   DAILY to YEARLY with INTERVAL, COUNT, UNTIL, BYDAY, BYMONTHDAY,
+//   BYMONTH, BYSETPOS, and WKST. `RDATE`s add instances, `EXDATE`s remove
+//   them, and an event with a `RECURRENCE-ID` replaces the instance it
+//   names.
+// Each calendar fails on its own: `get_events` lists the ones it couldn't
+// read beside the events of the rest.
+
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+    Weekday,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::settings::{self, Settings};
+use crate::url_metadata::{self, get_public, is_public, read_capped};
+
+const MAX_BYTES: usize = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+/// How far ahead `next` looks.
+const NEXT_HORIZON_DAYS: i64 = 90;
+/// Periods of a rule walked before giving up, e.g. 50 years of days.
+const MAX_PERIODS: usize = 20_000;
+
+static CACHE: Mutex<Option<HashMap<String, (Instant, String)>>> = Mutex::new(None);
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Attendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// `PARTSTAT`, lowercase: `accepted`, `declined`, `tentative`, …
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Event {
+    pub uid: String,
+    pub title: String,
+    /// RFC 3339 in the local zone, or `YYYY-MM-DD` for all-day events.
+    pub start: String,
+    /// Exclusive, in the same form as `start`.
+    pub end: String,
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub organizer: Option<Attendee>,
+    pub attendees: Vec<Attendee>,
+    /// `STATUS`, lowercase: `confirmed`, `tentative`, or `cancelled`.
+    pub status: Option<String>,
+    /// An instance of a recurring event.
+    pub recurring: bool,
+    /// The calendar's name in settings, from `get_events`.
+    pub calendar: Option<String>,
+    #[serde(skip)]
+    starts_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Calendar {
+    /// `X-WR-CALNAME`, when the calendar has one.
+    pub name: Option<String>,
+    pub events: Vec<Event>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventRange {
+    /// Local midnight to midnight.
+    Today,
+    /// The first timed event starting from now, with any starting with it.
+    Next,
+    /// Dates (`YYYY-MM-DD`, local midnight) or RFC 3339 times.
+    Between { start: String, end: String },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CalendarEvents {
+    pub events: Vec<Event>,
+    pub failures: Vec<CalendarFailure>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CalendarFailure {
+    pub calendar: String,
+    pub error: CommandError,
+}
+
+type Window = (DateTime<Utc>, DateTime<Utc>);
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Events of one calendar between `start` and `end`, from today for
+/// `DEFAULT_WINDOW_DAYS` when they're left out.
+#[tauri::command]
+pub async fn parse_ics(
+    path_or_url: String,
+    start: Option<String>,
+    end: Option<String>,
+) -> CommandResult<Calendar> {
+    let today = local_midnight(Local::now().date_naive());
+    let window = (
+        match start {
+            Some(start) => parse_bound(&start)?,
+            None => today,
+        },
+        match end {
+            Some(end) => parse_bound(&end)?,
+            None => today + TimeDelta::days(DEFAULT_WINDOW_DAYS),
+        },
+    );
+    let text = load(&path_or_url, &settings::load_settings(), is_public).await?;
+    calendar(&text, window).map_err(|e| e.with("source", &path_or_url))
+}
+
+#[tauri::command]
+pub async fn get_events(range: EventRange) -> CommandResult<CalendarEvents> {
+    let settings = settings::load_settings();
+    let now = Utc::now();
+    let window = match &range {
+        EventRange::Today => {
+            let today = Local::now().date_naive();
+            (
+                local_midnight(today),
+                local_midnight(today + TimeDelta::days(1)),
+            )
+        }
+        EventRange::Next => (now, now + TimeDelta::days(NEXT_HORIZON_DAYS)),
+        EventRange::Between { start, end } => (parse_bound(start)?, parse_bound(end)?),
+    };
+
+    let mut events = Vec::new();
+    let mut failures = Vec::new();
+    for source in settings.calendars.iter().filter(|c| c.enabled) {
+        let loaded = match load(&source.location, &settings, is_public).await {
+            Ok(text) => calendar(&text, window),
+            Err(e) => Err(e),
+        };
+        match loaded {
+            Ok(found) => events.extend(found.events.into_iter().map(|mut event| {
+                event.calendar = Some(source.name.clone());
+                event
+            })),
+            Err(error) => {
+                log::warn!(
+                    "[calendar] couldn't read {}: {}",
+                    source.name,
+                    error.message
+                );
+                failures.push(CalendarFailure {
+                    calendar: source.name.clone(),
+                    error,
+                });
+            }
+        }
+    }
+    events.sort_by(|a, b| a.starts_at.cmp(&b.starts_at).then(a.title.cmp(&b.title)));
+    if matches!(range, EventRange::Next) {
+        events = next_events(events, now);
+    }
+    Ok(CalendarEvents { events, failures })
+}
+
+/// The timed events starting first at or after `now`.
+fn next_events(events: Vec<Event>, now: DateTime<Utc>) -> Vec<Event> {
+    let mut upcoming = events
+        .into_iter()
+        .filter(|e| !e.all_day && e.starts_at >= now)
+        .peekable();
+    let Some(first) = upcoming.peek().map(|e| e.starts_at) else {
+        return Vec::new();
+    };
+    upcoming.take_while(|e| e.starts_at == first).collect()
+}
+
+fn parse_bound(raw: &str) -> CommandResult<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d") {
+        return Ok(local_midnight(date));
+    }
+    DateTime::parse_from_rfc3339(raw.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid date or time: {}", raw),
+            )
+            .with("value", raw)
+        })
+}
+
+// ─── Sources ────────────────────────────────────────────────────────────────
+
+async fn load(
+    location: &str,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<String> {
+    let trimmed = location.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let feed = match lower.strip_prefix("webcal://") {
+        Some(_) => Some(format!("https://{}", &trimmed["webcal://".len()..])),
+        None if lower.starts_with("http://") || lower.starts_with("https://") => {
+            Some(trimmed.to_string())
+        }
+        None => None,
+    };
+    if let Some(feed) = feed {
+        return fetch_feed(&url_metadata::parse_url(&feed)?, settings, allowed).await;
+    }
+
+    let path = Path::new(trimmed).to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || {
+        let size = fs::metadata(&path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => CommandError::new(
+                    ErrorCode::NotFound,
+                    format!("File not found: {}", path.display()),
+                )
+                .with("path", path.to_string_lossy()),
+                _ => e.into(),
+            })?
+            .len();
+        if size > MAX_BYTES as u64 {
+            return Err(too_large(&path.to_string_lossy(), size));
+        }
+        Ok(String::from_utf8_lossy(&fs::read(&path)?).to_string())
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+async fn fetch_feed(
+    url: &reqwest::Url,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<String> {
+    if let Some(text) = cached(url.as_str()) {
+        return Ok(text);
+    }
+    let fetch = async {
+        let accept = "text/calendar,*/*;q=0.5";
+        let (final_url, response) = get_public(url, settings, accept, allowed).await?;
+        let (body, truncated) = read_capped(response, &final_url, MAX_BYTES).await?;
+        if truncated {
+            return Err(too_large(url.as_str(), MAX_BYTES as u64 + 1));
+        }
+        Ok(String::from_utf8_lossy(&body).to_string())
+    };
+    let text = tokio::time::timeout(FETCH_TIMEOUT, fetch)
+        .await
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::Network,
+                format!("{} took longer than {:?}", url, FETCH_TIMEOUT),
+            )
+            .with("url", url.as_str())
+        })??;
+    remember(url.as_str(), &text);
+    Ok(text)
+}
+
+fn cached(url: &str) -> Option<String> {
+    let cache = CACHE.lock().unwrap();
+    let (at, text) = cache.as_ref()?.get(url)?;
+    (at.elapsed() < CACHE_TTL).then(|| text.clone())
+}
+
+fn remember(url: &str, text: &str) {
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    cache.insert(url.to_string(), (Instant::now(), text.to_string()));
+}
+
+fn too_large(source: &str, size: u64) -> CommandError {
+    CommandError::new(
+        ErrorCode::TooLarge,
+        format!("{} is over the {} byte limit", source, MAX_BYTES),
+    )
+    .with("path", source)
+    .with("size", size)
+    .with("limit", MAX_BYTES)
+}
+
+// ─── Events ─────────────────────────────────────────────────────────────────
+
+/// The events of `text` that overlap `window`, by start time.
+pub fn calendar(text: &str, window: Window) -> CommandResult<Calendar> {
+    let root = parse_components(text);
+    let Some(vcalendar) = root.children.iter().find(|c| c.name == "VCALENDAR") else {
+        return Err(CommandError::new(
+            ErrorCode::ParseError,
+            "Malformed calendar: no VCALENDAR".to_string(),
+        )
+        .with("line", 1)
+        .with("column", 1)
+        .with("offset", 0));
+    };
+    let zones = Zones(
+        vcalendar
+            .children
+            .iter()
+            .filter(|c| c.name == "VTIMEZONE")
+            .filter_map(|c| Some((c.text("TZID")?, Zone::parse(c))))
+            .collect(),
+    );
+    let vevents: Vec<&Component> = vcalendar
+        .children
+        .iter()
+        .filter(|c| c.name == "VEVENT")
+        .collect();
+
+    // Overridden instances, by UID, at the time they replace
+    let mut overridden: HashMap<String, HashSet<DateTime<Utc>>> = HashMap::new();
+    for vevent in &vevents {
+        if let (Some(uid), Some(id)) = (vevent.text("UID"), vevent.moment("RECURRENCE-ID")) {
+            overridden
+                .entry(uid)
+                .or_default()
+                .insert(zones.instant(&id));
+        }
+    }
+
+    let mut events = Vec::new();
+    for vevent in vevents {
+        let Some(start) = vevent.moment("DTSTART") else {
+            continue;
+        };
+        let duration = duration_of(vevent, &start, &zones);
+        let uid = vevent.text("UID").unwrap_or_default();
+        let is_override = vevent.prop("RECURRENCE-ID").is_some();
+        let recurring =
+            is_override || vevent.prop("RRULE").is_some() || vevent.prop("RDATE").is_some();
+        let instances = match is_override {
+            true => vec![start.clone()],
+            false => {
+                let skip = overridden.get(&uid);
+                instances(vevent, &start, window, &zones)
+                    .into_iter()
+                    .filter(|i| !skip.is_some_and(|s| s.contains(&zones.instant(i))))
+                    .collect()
+            }
+        };
+        for instance in instances {
+            let end = Moment {
+                wall: instance.wall + duration,
+                kind: instance.kind.clone(),
+            };
+            let (starts_at, ends_at) = (zones.instant(&instance), zones.instant(&end));
+            let overlaps = starts_at < window.1
+                && (ends_at > window.0 || (ends_at <= starts_at && starts_at >= window.0));
+            if overlaps {
+                events.push(event(vevent, &uid, &instance, &end, &zones, recurring));
+            }
+        }
+    }
+    events.sort_by(|a, b| a.starts_at.cmp(&b.starts_at).then(a.title.cmp(&b.title)));
+    Ok(Calendar {
+        name: vcalendar.text("X-WR-CALNAME"),
+        events,
+    })
+}
+
+/// When `vevent` happens, up to the end of `window`: its start, its rule's
+/// occurrences and `RDATE`s, less its `EXDATE`s.
+fn instances(vevent: &Component, start: &Moment, window: Window, zones: &Zones) -> Vec<Moment> {
+    let rules: Vec<Rule> = vevent
+        .props("RRULE")
+        .filter_map(|p| Rule::parse(&p.value))
+        .collect();
+    let mut walls = vec![start.wall];
+    // A day past the window in the event's own zone, to be safe around offsets
+    let stop = zones.wall(window.1, &start.kind) + TimeDelta::days(1);
+    for rule in &rules {
+        let until = rule.until.as_ref().map(|until| match until.kind {
+            Kind::Date => {
+                until.wall.date().and_time(NaiveTime::MIN) + TimeDelta::days(1)
+                    - TimeDelta::seconds(1)
+            }
+            // UNTIL in UTC is compared in the event's zone
+            Kind::Utc => zones.wall(until.wall.and_utc(), &start.kind),
+            Kind::Floating | Kind::Zoned(_) => until.wall,
+        });
+        walls.extend(rule.expand(start.wall, until, stop));
+    }
+    for rdate in vevent.moments("RDATE") {
+        walls.push(match (&start.kind, &rdate.kind) {
+            (Kind::Date, _) => rdate.wall.date().and_time(NaiveTime::MIN),
+            (kind, _) => zones.wall(zones.instant(&rdate), kind),
+        });
+    }
+    let excluded: HashSet<DateTime<Utc>> = vevent
+        .moments("EXDATE")
+        .iter()
+        .map(|exdate| match start.kind {
+            // A date excludes the instance on that day
+            Kind::Date => zones.instant(&Moment {
+                wall: exdate.wall.date().and_time(NaiveTime::MIN),
+                kind: Kind::Date,
+            }),
+            _ => zones.instant(exdate),
+        })
+        .collect();
+
+    walls.sort();
+    walls.dedup();
+    walls
+        .into_iter()
+        .map(|wall| Moment {
+            wall,
+            kind: start.kind.clone(),
+        })
+        .filter(|instance| !excluded.contains(&zones.instant(instance)))
+        .collect()
+}
+
+fn duration_of(vevent: &Component, start: &Moment, zones: &Zones) -> TimeDelta {
+    if let Some(end) = vevent.moment("DTEND") {
+        // Wall-clock length when both are in the same zone, so an instance
+        // across a DST change keeps its length on the clock
+        return match end.kind == start.kind {
+            true => end.wall - start.wall,
+            false => zones.instant(&end) - zones.instant(start),
+        }
+        .max(TimeDelta::zero());
+    }
+    if let Some(duration) = vevent
+        .prop("DURATION")
+        .and_then(|p| parse_duration(&p.value))
+    {
+        return duration;
+    }
+    match start.kind {
+        Kind::Date => TimeDelta::days(1),
+        _ => TimeDelta::zero(),
+    }
+}
+
+fn event(
+    vevent: &Component,
+    uid: &str,
+    start: &Moment,
+    end: &Moment,
+    zones: &Zones,
+    recurring: bool,
+) -> Event {
+    let all_day = start.kind == Kind::Date;
+    let format = |moment: &Moment| match all_day {
+        true => moment.wall.date().format("%Y-%m-%d").to_string(),
+        false => zones.instant(moment).with_timezone(&Local).to_rfc3339(),
+    };
+    Event {
+        uid: uid.to_string(),
+        title: vevent.text("SUMMARY").unwrap_or_default(),
+        start: format(start),
+        end: format(end),
+        all_day,
+        location: vevent.text("LOCATION"),
+        description: vevent.text("DESCRIPTION"),
+        organizer: vevent.prop("ORGANIZER").map(attendee),
+        attendees: vevent.props("ATTENDEE").map(attendee).collect(),
+        status: vevent.text("STATUS").map(|s| s.to_ascii_lowercase()),
+        recurring,
+        calendar: None,
+        starts_at: zones.instant(start),
+    }
+}
+
+fn attendee(prop: &Prop) -> Attendee {
+    let value = prop.value.trim();
+    let email = match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => Some(value[7..].to_string()),
+        _ => None,
+    };
+    Attendee {
+        name: prop.params.get("CN").cloned().filter(|n| !n.is_empty()),
+        email: email.filter(|e| !e.is_empty()),
+        status: prop.params.get("PARTSTAT").map(|s| s.to_ascii_lowercase()),
+    }
+}
+
+// ─── Parsing ────────────────────────────────────────────────────────────────
+
+#[derive(Default)]
+struct Component {
+    name: String,
+    props: Vec<Prop>,
+    children: Vec<Component>,
+}
+
+struct Prop {
+    name: String,
+    /// Names uppercase, values unquoted.
+    params: HashMap<String, String>,
+    value: String,
+}
+
+/// When something happens: a wall-clock time and what it's relative to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Moment {
+    wall: NaiveDateTime,
+    kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    /// A whole day; `wall` is its midnight.
+    Date,
+    /// Local time wherever the reader is.
+    Floating,
+    Utc,
+    /// Local time in a `TZID`.
+    Zoned(String),
+}
+
+/// Every component of `text`, under an unnamed root.
+fn parse_components(text: &str) -> Component {
+    // Lines starting with a space or tab continue the one before
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut stack = vec![Component::default()];
+    for prop in lines.iter().filter_map(|line| content_line(line)) {
+        match prop.name.as_str() {
+            "BEGIN" => stack.push(Component {
+                name: prop.value.trim().to_ascii_uppercase(),
+                ..Default::default()
+            }),
+            "END" if stack.len() > 1 => {
+                let done = stack.pop().unwrap_or_default();
+                stack.last_mut().unwrap().children.push(done);
+            }
+            "END" => {}
+            _ => stack.last_mut().unwrap().props.push(prop),
+        }
+    }
+    // Components left open at the end of the file
+    while stack.len() > 1 {
+        let done = stack.pop().unwrap_or_default();
+        stack.last_mut().unwrap().children.push(done);
+    }
+    stack.pop().unwrap_or_default()
+}
+
+/// `NAME;PARAM=value;PARAM="quoted":value`
+fn content_line(line: &str) -> Option<Prop> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(i),
+            _ => {}
+        }
+        None
+    })?;
+    let mut head = split_unquoted(&line[..colon], ';').into_iter();
+    let name = head.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = head
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (key.trim().to_ascii_uppercase(), value.to_string())
+        })
+        .collect();
+    Some(Prop {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            items.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+    items.push(&text[start..]);
+    items
+}
+
+impl Component {
+    fn prop(&self, name: &str) -> Option<&Prop> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    fn props<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Prop> + 'a {
+        self.props.iter().filter(move |p| p.name == name)
+    }
+
+    /// A text value with its escapes undone, if it isn't empty.
+    fn text(&self, name: &str) -> Option<String> {
+        self.prop(name)
+            .map(|p| unescape(p.value.trim()))
+            .filter(|v| !v.is_empty())
+    }
+
+    fn moment(&self, name: &str) -> Option<Moment> {
+        let prop = self.prop(name)?;
+        moment(prop.value.split(',').next()?, &prop.params)
+    }
+
+    /// Every value of every `name` property, which may each hold a list.
+    fn moments(&self, name: &str) -> Vec<Moment> {
+        self.props(name)
+            .flat_map(|p| {
+                p.value
+                    .split(',')
+                    // A period's start
+                    .filter_map(|v| moment(v.split('/').next().unwrap_or(v), &p.params))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+fn moment(value: &str, params: &HashMap<String, String>) -> Option<Moment> {
+    let value = value.trim();
+    let date_only = params
+        .get("VALUE")
+        .is_some_and(|v| v.eq_ignore_ascii_case("DATE"))
+        || (value.len() == 8 && !value.contains('T'));
+    if date_only {
+        let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+        return Some(Moment {
+            wall: date.and_time(NaiveTime::MIN),
+            kind: Kind::Date,
+        });
+    }
+    let (raw, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(raw) => (raw, true),
+        None => (value, false),
+    };
+    let wall = NaiveDateTime::parse_from_str(raw, "%Y%m%dT%H%M%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y%m%dT%H%M"))
+        .ok()?;
+    let kind = match (utc, params.get("TZID")) {
+        (true, _) => Kind::Utc,
+        (false, Some(tzid)) => Kind::Zoned(tzid.clone()),
+        (false, None) => Kind::Floating,
+    };
+    Some(Moment { wall, kind })
+}
+
+/// `P1D`, `PT1H30M`, `P2W`, `-PT15M`
+fn parse_duration(raw: &str) -> Option<TimeDelta> {
+    let raw = raw.trim();
+    let (sign, raw) = match raw.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    let mut rest = raw.strip_prefix('P')?;
+    let mut seconds: i64 = 0;
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = after;
+            continue;
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let number: i64 = rest[..digits].parse().ok()?;
+        let unit = match (rest[digits..].chars().next()?, in_time) {
+            ('W', false) => 7 * 86_400,
+            ('D', false) => 86_400,
+            ('H', true) => 3_600,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None,
+        };
+        seconds += number * unit;
+        rest = &rest[digits + 1..];
+    }
+    Some(TimeDelta::seconds(sign * seconds))
+}
+
+// ─── Time Zones ─────────────────────────────────────────────────────────────
+
+/// The calendar's `VTIMEZONE`s, by `TZID`.
+struct Zones(HashMap<String, Zone>);
+
+struct Zone {
+    observances: Vec<Observance>,
+}
+
+/// A `STANDARD` or `DAYLIGHT` part of a zone: the offset it switches to,
+/// and when.
+struct Observance {
+    start: NaiveDateTime,
+    offset_from: i32,
+    offset_to: i32,
+    rules: Vec<Rule>,
+    until: Option<NaiveDateTime>,
+    rdates: Vec<NaiveDateTime>,
+}
+
+impl Zones {
+    /// The instant `moment` names.
+    fn instant(&self, moment: &Moment) -> DateTime<Utc> {
+        match &moment.kind {
+            Kind::Utc => moment.wall.and_utc(),
+            Kind::Date | Kind::Floating => local(moment.wall),
+            Kind::Zoned(tzid) if is_utc(tzid) => moment.wall.and_utc(),
+            Kind::Zoned(tzid) => match self.0.get(tzid).and_then(|z| z.offset(moment.wall)) {
+                Some(offset) => (moment.wall - TimeDelta::seconds(offset as i64)).and_utc(),
+                None => local(moment.wall),
+            },
+        }
+    }
+
+    /// The wall-clock time `instant` is in a moment of `kind`.
+    fn wall(&self, instant: DateTime<Utc>, kind: &Kind) -> NaiveDateTime {
+        match kind {
+            Kind::Utc => instant.naive_utc(),
+            Kind::Date | Kind::Floating => instant.with_timezone(&Local).naive_local(),
+            Kind::Zoned(tzid) if is_utc(tzid) => instant.naive_utc(),
+            Kind::Zoned(tzid) => match self.0.get(tzid) {
+                Some(zone) => {
+                    // The offset is looked up at a guess of the wall time;
+                    // only the hour around a transition can be off
+                    let guess = instant.naive_utc();
+                    let offset = zone.offset(guess).unwrap_or(0);
+                    guess + TimeDelta::seconds(offset as i64)
+                }
+                None => instant.with_timezone(&Local).naive_local(),
+            },
+        }
+    }
+}
+
+impl Zone {
+    fn parse(vtimezone: &Component) -> Zone {
+        let observances = vtimezone
+            .children
+            .iter()
+            .filter(|c| c.name == "STANDARD" || c.name == "DAYLIGHT")
+            .filter_map(|c| {
+                let offset_from = parse_offset(&c.prop("TZOFFSETFROM")?.value)?;
+                let rules: Vec<Rule> = c
+                    .props("RRULE")
+                    .filter_map(|p| Rule::parse(&p.value))
+                    .collect();
+                // Transitions are written in the time before them
+                let until = rules
+                    .iter()
+                    .find_map(|r| r.until.as_ref())
+                    .map(|u| match u.kind {
+                        Kind::Utc => u.wall + TimeDelta::seconds(offset_from as i64),
+                        _ => u.wall,
+                    });
+                Some(Observance {
+                    start: c.moment("DTSTART")?.wall,
+                    offset_from,
+                    offset_to: parse_offset(&c.prop("TZOFFSETTO")?.value)?,
+                    rules,
+                    until,
+                    rdates: c.moments("RDATE").into_iter().map(|m| m.wall).collect(),
+                })
+            })
+            .collect();
+        Zone { observances }
+    }
+
+    /// Seconds east of UTC at wall-clock time `wall`.
+    fn offset(&self, wall: NaiveDateTime) -> Option<i32> {
+        let latest = self
+            .observances
+            .iter()
+            .filter_map(|o| o.last_onset(wall).map(|onset| (onset, o.offset_to)))
+            .max_by_key(|(onset, _)| *onset);
+        match latest {
+            Some((_, offset)) => Some(offset),
+            // Before the first transition
+            None => self
+                .observances
+                .iter()
+                .min_by_key(|o| o.start)
+                .map(|o| o.offset_from),
+        }
+    }
+}
+
+impl Observance {
+    fn last_onset(&self, wall: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut onsets: Vec<NaiveDateTime> = vec![self.start];
+        for rule in &self.rules {
+            onsets.extend(rule.expand(self.start, self.until, wall));
+        }
+        onsets.extend(self.rdates.iter().copied());
+        onsets.into_iter().filter(|onset| *onset <= wall).max()
+    }
+}
+
+fn is_utc(tzid: &str) -> bool {
+    let tzid = tzid.trim().trim_start_matches('/');
+    ["UTC", "GMT", "Z", "Etc/UTC", "Etc/GMT", "Etc/Zulu", "Zulu"]
+        .iter()
+        .any(|name| tzid.eq_ignore_ascii_case(name))
+}
+
+/// `+0100`, `-0530`, `+013000`
+fn parse_offset(raw: &str) -> Option<i32> {
+    let raw = raw.trim();
+    let sign = match raw.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = &raw[1..];
+    let part =
+        |range: std::ops::Range<usize>| digits.get(range).and_then(|d| d.parse::<i32>().ok());
+    let seconds = match digits.len() {
+        4 => part(0..2)? * 3600 + part(2..4)? * 60,
+        6 => part(0..2)? * 3600 + part(2..4)? * 60 + part(4..6)?,
+        _ => return None,
+    };
+    Some(sign * seconds)
+}
+
+/// `wall` in the local zone; in a DST gap, the time just after it.
+fn local(wall: NaiveDateTime) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&wall)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(wall + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| wall.and_utc())
+}
+
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    local(date.and_time(NaiveTime::MIN))
+}
+
+// ─── Recurrence ─────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<Moment>,
+    /// Weekdays, each with an ordinal: 0 for every one, `2` for the second,
+    /// `-1` for the last.
+    by_day: Vec<(i32, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    by_set_pos: Vec<i32>,
+    week_start: Weekday,
+}
+
+impl Rule {
+    /// `FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20261231T000000Z`; rules more often
+    /// than daily aren't supported.
+    fn parse(value: &str) -> Option<Rule> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+        let mut frequency = None;
+        for part in value.trim().split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            let list = || value.split(',').map(str::trim);
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.trim().to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = value.trim().parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => rule.count = value.trim().parse().ok(),
+                "UNTIL" => rule.until = moment(value, &HashMap::new()),
+                "BYDAY" => rule.by_day = list().filter_map(by_day).collect(),
+                "BYMONTHDAY" => rule.by_month_day = list().filter_map(|d| d.parse().ok()).collect(),
+                "BYMONTH" => rule.by_month = list().filter_map(|m| m.parse().ok()).collect(),
+                "BYSETPOS" => rule.by_set_pos = list().filter_map(|p| p.parse().ok()).collect(),
+                "WKST" => rule.week_start = weekday(value.trim())?,
+                _ => {}
+            }
+        }
+        rule.frequency = frequency?;
+        Some(rule)
+    }
+
+    /// Occurrences after `start` (which is always the first), up to `until`
+    /// and `stop`, within `count`.
+    fn expand(
+        &self,
+        start: NaiveDateTime,
+        until: Option<NaiveDateTime>,
+        stop: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        let mut occurrences = Vec::new();
+        let mut count = 1;
+        let time = start.time();
+        for period in 0..MAX_PERIODS {
+            let Some((first_day, mut days)) = self.period(start.date(), period) else {
+                break;
+            };
+            if first_day.and_time(NaiveTime::MIN) > stop {
+                break;
+            }
+            days.sort();
+            days.dedup();
+            if !self.by_set_pos.is_empty() {
+                days = pick(&days, &self.by_set_pos);
+            }
+            for day in days {
+                let at = day.and_time(time);
+                if at <= start {
+                    continue;
+                }
+                if until.is_some_and(|until| at > until)
+                    || self.count.is_some_and(|limit| count >= limit)
+                    || at > stop
+                {
+                    return occurrences;
+                }
+                count += 1;
+                occurrences.push(at);
+            }
+        }
+        occurrences
+    }
+
+    /// The first day of the `n`th period after the one `start` is in, and
+    /// the days in it the rule picks.
+    fn period(&self, start: NaiveDate, n: usize) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let step = n as i64 * self.interval as i64;
+        match self.frequency {
+            Frequency::Daily => {
+                let day = start.checked_add_signed(TimeDelta::days(step))?;
+                let keep = self.in_months(day)
+                    && self.on_month_days(day)
+                    && (self.by_day.is_empty()
+                        || self.by_day.iter().any(|(_, wd)| *wd == day.weekday()));
+                Some((day, if keep { vec![day] } else { Vec::new() }))
+            }
+            Frequency::Weekly => {
+                let since = (start.weekday().num_days_from_monday() + 7
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let first = start.checked_add_signed(TimeDelta::days(step * 7 - since as i64))?;
+                let days = (0..7)
+                    .filter_map(|i| first.checked_add_signed(TimeDelta::days(i)))
+                    .filter(|day| match self.by_day.is_empty() {
+                        true => day.weekday() == start.weekday(),
+                        false => self.by_day.iter().any(|(_, wd)| *wd == day.weekday()),
+                    })
+                    .filter(|day| self.in_months(*day) && self.on_month_days(*day))
+                    .collect();
+                Some((first, days))
+            }
+            Frequency::Monthly => {
+                let months = start.year() as i64 * 12 + start.month0() as i64 + step;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = months.rem_euclid(12) as u32 + 1;
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let days = match self.by_month.is_empty() || self.by_month.contains(&month) {
+                    true => self.month_days(year, month, start.day()),
+                    false => Vec::new(),
+                };
+                Some((first, days))
+            }
+            Frequency::Yearly => {
+                let year = start.year().checked_add(i32::try_from(step).ok()?)?;
+                let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                let days = if !self.by_month.is_empty() {
+                    self.by_month
+                        .iter()
+                        .flat_map(|&month| self.month_days(year, month, start.day()))
+                        .collect()
+                } else if !self.by_day.is_empty() && self.by_month_day.is_empty() {
+                    // Weekdays counted through the year, like the 20th Monday
+                    nth_weekdays(
+                        first,
+                        NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+                        &self.by_day,
+                    )
+                } else if !self.by_month_day.is_empty() {
+                    (1..=12)
+                        .flat_map(|month| self.month_days(year, month, start.day()))
+                        .collect()
+                } else {
+                    NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                };
+                Some((first, days))
+            }
+        }
+    }
+
+    /// The days of a month the rule picks; `default_day` when it names none.
+    fn month_days(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            return Vec::new();
+        };
+        let next = match month {
+            12 => NaiveDate::from_ymd_opt(year + 1, 1, 1),
+            _ => NaiveDate::from_ymd_opt(year, month + 1, 1),
+        };
+        let Some(next) = next else {
+            return Vec::new();
+        };
+        if self.by_day.is_empty() && self.by_month_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, month, default_day)
+                .into_iter()
+                .collect();
+        }
+        let mut days: Vec<NaiveDate> = match self.by_day.is_empty() {
+            true => first.iter_days().take_while(|d| *d < next).collect(),
+            false => nth_weekdays(first, next, &self.by_day),
+        };
+        days.retain(|day| self.on_month_days(*day));
+        days
+    }
+
+    fn in_months(&self, day: NaiveDate) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&day.month())
+    }
+
+    fn on_month_days(&self, day: NaiveDate) -> bool {
+        if self.by_month_day.is_empty() {
+            return true;
+        }
+        let length = days_in_month(day);
+        let from_end = day.day() as i32 - length as i32 - 1;
+        self.by_month_day
+            .iter()
+            .any(|&d| d == day.day() as i32 || d == from_end)
+    }
+}
+
+/// The days from `first` up to `next` that are the given weekdays, by
+/// their ordinal within that span.
+fn nth_weekdays(first: NaiveDate, next: NaiveDate, by_day: &[(i32, Weekday)]) -> Vec<NaiveDate> {
+    let span: Vec<NaiveDate> = first.iter_days().take_while(|d| *d < next).collect();
+    let mut days = Vec::new();
+    for &(ordinal, weekday) in by_day {
+        let matching: Vec<NaiveDate> = span
+            .iter()
+            .copied()
+            .filter(|d| d.weekday() == weekday)
+            .collect();
+        match ordinal {
+            0 => days.extend(matching),
+            n => days.extend(pick(&matching, &[n])),
+        }
+    }
+    days
+}
+
+/// The items at 1-based `positions`, negative ones counting from the end.
+fn pick<T: Copy + Ord>(items: &[T], positions: &[i32]) -> Vec<T> {
+    let mut picked: Vec<T> = positions
+        .iter()
+        .filter_map(|&p| match p {
+            p if p > 0 => items.get(p as usize - 1).copied(),
+            p if p < 0 => items
+                .len()
+                .checked_sub(p.unsigned_abs() as usize)
+                .and_then(|i| items.get(i).copied()),
+            _ => None,
+        })
+        .collect();
+    picked.sort();
+    picked.dedup();
+    picked
+}
+
+fn days_in_month(day: NaiveDate) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|&d| NaiveDate::from_ymd_opt(day.year(), day.month(), d).is_some())
+        .unwrap_or(28)
+}
+
+/// `MO`, `2TU`, `-1FR`
+fn by_day(raw: &str) -> Option<(i32, Weekday)> {
+    let split = raw.len().checked_sub(2)?;
+    let ordinal = match &raw[..split] {
+        "" => 0,
+        n => n.trim_start_matches('+').parse().ok()?,
+    };
+    Some((ordinal, weekday(&raw[split..])?))
+}
+
+fn weekday(raw: &str) -> Option<Weekday> {
+    Some(match raw.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ProxyMode;
+    use crate::testing::MockServer;
+
+    const NEW_YORK: &str = "BEGIN:VTIMEZONE\r\n\
+        TZID:America/New_York\r\n\
+        BEGIN:DAYLIGHT\r\n\
+        TZOFFSETFROM:-0500\r\n\
+        TZOFFSETTO:-0400\r\n\
+        DTSTART:20070311T020000\r\n\
+        RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r\n\
+        END:DAYLIGHT\r\n\
+        BEGIN:STANDARD\r\n\
+        TZOFFSETFROM:-0400\r\n\
+        TZOFFSETTO:-0500\r\n\
+        DTSTART:20071104T020000\r\n\
+        RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r\n\
+        END:STANDARD\r\n\
+        END:VTIMEZONE\r\n";
+
+    fn ics(body: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-WR-CALNAME:Work\r\n{}{}END:VCALENDAR\r\n",
+            NEW_YORK, body
+        )
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn local_time(time: DateTime<Utc>) -> String {
+        time.with_timezone(&Local).to_rfc3339()
+    }
+
+    #[test]
+    fn expands_weekly_meetings_across_dst_with_exceptions() {
+        let text = ics("BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            SUMMARY:Standup\\, daily-ish\r\n\
+            DTSTART;TZID=America/New_York:20260302T090000\r\n\
+            DTEND;TZID=America/New_York:20260302T091500\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=MO,TH;UNTIL=20260320T000000Z\r\n\
+            EXDATE;TZID=America/New_York:20260305T090000\r\n\
+            ORGANIZER;CN=Ana:mailto:ana@example.com\r\n\
+            ATTENDEE;CN=\"Bo, B\";PARTSTAT=ACCEPTED:MAILTO:bo@example.com\r\n\
+            LOCATION:Room 1\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            RECURRENCE-ID;TZID=America/New_York:20260312T090000\r\n\
+            SUMMARY:Standup (moved)\r\n\
+            DTSTART;TZID=America/New_York:20260312T130000\r\n\
+            DTEND;TZID=America/New_York:20260312T131500\r\n\
+            END:VEVENT\r\n");
+        let calendar = calendar(&text, (utc(2026, 3, 1, 0, 0), utc(2026, 4, 1, 0, 0))).unwrap();
+        assert_eq!(calendar.name.as_deref(), Some("Work"));
+        let starts: Vec<(&str, String)> = calendar
+            .events
+            .iter()
+            .map(|e| (e.title.as_str(), e.start.clone()))
+            .collect();
+        // EST (-5) until March 8, EDT (-4) after; always 09:00 in New York.
+        // The 5th is excluded, and the 12th moved to 13:00.
+        assert_eq!(
+            starts,
+            vec![
+                ("Standup, daily-ish", local_time(utc(2026, 3, 2, 14, 0))),
+                ("Standup, daily-ish", local_time(utc(2026, 3, 9, 13, 0))),
+                ("Standup (moved)", local_time(utc(2026, 3, 12, 17, 0))),
+                ("Standup, daily-ish", local_time(utc(2026, 3, 16, 13, 0))),
+                ("Standup, daily-ish", local_time(utc(2026, 3, 19, 13, 0))),
+            ]
+        );
+        let first = &calendar.events[0];
+        assert!(first.recurring && !first.all_day);
+        assert_eq!(first.end, local_time(utc(2026, 3, 2, 14, 15)));
+        assert_eq!(first.location.as_deref(), Some("Room 1"));
+        assert_eq!(
+            first.organizer,
+            Some(Attendee {
+                name: Some("Ana".to_string()),
+                email: Some("ana@example.com".to_string()),
+                status: None,
+            })
+        );
+        assert_eq!(
+            first.attendees,
+            vec![Attendee {
+                name: Some("Bo, B".to_string()),
+                email: Some("bo@example.com".to_string()),
+                status: Some("accepted".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn all_day_and_monthly_rules() {
+        let text = ics("BEGIN:VEVENT\r\n\
+            UID:offsite\r\n\
+            SUMMARY:Offsite\r\n\
+            DTSTART;VALUE=DATE:20260310\r\n\
+            DTEND;VALUE=DATE:20260312\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:review\r\n\
+            SUMMARY:Review\r\n\
+            DTSTART:20260130T160000Z\r\n\
+            DURATION:PT1H\r\n\
+            RRULE:FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1;COUNT=4\r\n\
+            END:VEVENT\r\n");
+        let calendar = calendar(&text, (utc(2026, 1, 1, 0, 0), utc(2027, 1, 1, 0, 0))).unwrap();
+        let offsite = calendar.events.iter().find(|e| e.uid == "offsite").unwrap();
+        assert!(offsite.all_day && !offsite.recurring);
+        assert_eq!(
+            (offsite.start.as_str(), offsite.end.as_str()),
+            ("2026-03-10", "2026-03-12")
+        );
+        // The last weekday of each month, four times
+        let reviews: Vec<String> = calendar
+            .events
+            .iter()
+            .filter(|e| e.uid == "review")
+            .map(|e| e.start.clone())
+            .collect();
+        assert_eq!(
+            reviews,
+            vec![
+                local_time(utc(2026, 1, 30, 16, 0)),
+                local_time(utc(2026, 2, 27, 16, 0)),
+                local_time(utc(2026, 3, 31, 16, 0)),
+                local_time(utc(2026, 4, 30, 16, 0)),
+            ]
+        );
+
+        let err = super::calendar(
+            "BEGIN:VCARD\r\nEND:VCARD\r\n",
+            (utc(2026, 1, 1, 0, 0), utc(2026, 2, 1, 0, 0)),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::ParseError);
+    }
+
+    #[test]
+    fn feeds_are_fetched_once_and_cached() {
+        let body = ics(
+            "BEGIN:VEVENT\r\nUID:a\r\nSUMMARY:Lunch\r\nDTSTART:20260310T120000Z\r\nEND:VEVENT\r\n",
+        );
+        let server = MockServer::start(vec![MockServer::response(
+            200,
+            &[("Content-Type", "text/calendar")],
+            &body,
+        )]);
+        let mut settings = Settings::default();
+        settings.proxy.mode = ProxyMode::None;
+        let url = server.url("/cal.ics");
+        for _ in 0..2 {
+            let text = tauri::async_runtime::block_on(load(&url, &settings, |_| true)).unwrap();
+            assert!(text.contains("SUMMARY:Lunch"));
+        }
+        assert_eq!(server.requests().len(), 1);
+
+        let err =
+            tauri::async_runtime::block_on(load("http://127.0.0.1:9/x.ics", &settings, is_public))
+                .unwrap_err();
+        assert_eq!(err.code, ErrorCode::AddressNotAllowed);
+    }
+}
@@ -0,0 +1,314 @@
+// Obsidian canvases
+//
+// `read_canvas` parses a `.canvas` file (JSON Canvas) into typed nodes and
+// edges. File nodes are resolved against the vault the canvas lives in, so
+// each carries its absolute path and whether it exists. Node types this
+// doesn't know are passed through with their raw JSON.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+
+#[derive(Deserialize)]
+struct RawCanvas {
+    #[serde(default)]
+    nodes: Vec<Value>,
+    #[serde(default)]
+    edges: Vec<CanvasEdge>,
+}
+
+/// Fields shared by every node type.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NodeFrame {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CanvasNode {
+    Text {
+        #[serde(flatten)]
+        frame: NodeFrame,
+        text: String,
+    },
+    File {
+        #[serde(flatten)]
+        frame: NodeFrame,
+        /// Vault-relative, as stored in the canvas.
+        file: String,
+        subpath: Option<String>,
+        resolved_path: String,
+        exists: bool,
+    },
+    Link {
+        #[serde(flatten)]
+        frame: NodeFrame,
+        url: String,
+    },
+    Group {
+        #[serde(flatten)]
+        frame: NodeFrame,
+        label: Option<String>,
+        background: Option<String>,
+    },
+    Unknown {
+        node_type: Option<String>,
+        raw: Value,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_end: Option<String>,
+    pub to_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Canvas {
+    pub path: String,
+    pub vault_root: String,
+    pub nodes: Vec<CanvasNode>,
+    pub edges: Vec<CanvasEdge>,
+}
+
+#[derive(Deserialize)]
+struct FileFields {
+    file: String,
+    subpath: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GroupFields {
+    label: Option<String>,
+    background: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn read_canvas(path: String) -> CommandResult<Canvas> {
+    let file = Path::new(&path);
+    let raw = fs::read_to_string(file).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+                .with("path", &path)
+        }
+        _ => e.into(),
+    })?;
+    let root = vault_root(file);
+    parse(&raw, &root)
+        .map(|(nodes, edges)| Canvas {
+            path: path.clone(),
+            vault_root: root.to_string_lossy().to_string(),
+            nodes,
+            edges,
+        })
+        .map_err(|e| e.with("path", &path))
+}
+
+// ─── Parsing ────────────────────────────────────────────────────────────────
+
+pub fn parse(raw: &str, vault_root: &Path) -> CommandResult<(Vec<CanvasNode>, Vec<CanvasEdge>)> {
+    let canvas: RawCanvas = serde_json::from_str(raw).map_err(|e| parse_error(raw, &e))?;
+    let nodes = canvas
+        .nodes
+        .into_iter()
+        .map(|node| typed_node(node, vault_root))
+        .collect();
+    Ok((nodes, canvas.edges))
+}
+
+fn typed_node(raw: Value, vault_root: &Path) -> CanvasNode {
+    let node_type = raw.get("type").and_then(Value::as_str).map(str::to_string);
+    let unknown = |raw: Value| CanvasNode::Unknown {
+        node_type: node_type.clone(),
+        raw,
+    };
+    let Ok(frame) = NodeFrame::deserialize(&raw) else {
+        return unknown(raw);
+    };
+    let field = |name: &str| raw.get(name).and_then(Value::as_str).map(str::to_string);
+
+    match node_type.as_deref() {
+        Some("text") => match field("text") {
+            Some(text) => CanvasNode::Text { frame, text },
+            None => unknown(raw),
+        },
+        Some("file") => match FileFields::deserialize(&raw) {
+            Ok(fields) => {
+                let resolved = vault_root.join(&fields.file);
+                CanvasNode::File {
+                    frame,
+                    exists: resolved.exists(),
+                    resolved_path: resolved.to_string_lossy().to_string(),
+                    file: fields.file,
+                    subpath: fields.subpath,
+                }
+            }
+            Err(_) => unknown(raw),
+        },
+        Some("link") => match field("url") {
+            Some(url) => CanvasNode::Link { frame, url },
+            None => unknown(raw),
+        },
+        Some("group") => match GroupFields::deserialize(&raw) {
+            Ok(fields) => CanvasNode::Group {
+                frame,
+                label: fields.label,
+                background: fields.background,
+            },
+            Err(_) => unknown(raw),
+        },
+        _ => unknown(raw),
+    }
+}
+
+fn parse_error(raw: &str, e: &serde_json::Error) -> CommandError {
+    // serde_json reports 1-based lines and columns; columns count bytes
+    let offset: usize = raw
+        .split_inclusive('\n')
+        .take(e.line().saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>()
+        + e.column().saturating_sub(1);
+    CommandError::new(ErrorCode::ParseError, format!("Malformed canvas: {}", e))
+        .with("line", e.line())
+        .with("column", e.column())
+        .with("offset", offset.min(raw.len()))
+}
+
+/// The nearest ancestor with a `.obsidian` folder, else the canvas's folder.
+fn vault_root(canvas: &Path) -> PathBuf {
+    let parent = canvas.parent().unwrap_or(Path::new(""));
+    parent
+        .ancestors()
+        .find(|dir| dir.join(".obsidian").is_dir())
+        .unwrap_or(parent)
+        .to_path_buf()
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    const BOARD: &str = r##"{
+        "nodes": [
+            {"id": "t", "type": "text", "x": 0, "y": 0, "width": 200, "height": 80, "text": "Hi"},
+            {"id": "f", "type": "file", "x": 0, "y": 100, "width": 200, "height": 80,
+             "file": "notes/Plan.md", "subpath": "#Goals"},
+            {"id": "m", "type": "file", "x": 0, "y": 200, "width": 200, "height": 80,
+             "file": "missing.md"},
+            {"id": "l", "type": "link", "x": 300, "y": 0, "width": 200, "height": 80,
+             "url": "https://example.com", "color": "4"},
+            {"id": "g", "type": "group", "x": -10, "y": -10, "width": 600, "height": 400,
+             "label": "Week"},
+            {"id": "s", "type": "sticker", "x": 0, "y": 0, "width": 10, "height": 10},
+            {"id": "broken", "type": "text", "text": "no frame"}
+        ],
+        "edges": [{"id": "e", "fromNode": "t", "toNode": "f", "toEnd": "arrow"}]
+    }"##;
+
+    #[test]
+    fn nodes_are_typed_and_files_resolve_against_the_vault() {
+        let home = TestHome::new();
+        home.write("vault/.obsidian/app.json", "{}");
+        home.write("vault/notes/Plan.md", "# Plan\n");
+        let path = home.write("vault/boards/week.canvas", BOARD);
+
+        let canvas = read_canvas(path.to_string_lossy().to_string()).unwrap();
+        assert_eq!(Path::new(&canvas.vault_root), home.join("vault"));
+        let kinds: Vec<&str> = canvas
+            .nodes
+            .iter()
+            .map(|node| match node {
+                CanvasNode::Text { .. } => "text",
+                CanvasNode::File { exists: true, .. } => "file",
+                CanvasNode::File { exists: false, .. } => "missing file",
+                CanvasNode::Link { .. } => "link",
+                CanvasNode::Group { .. } => "group",
+                CanvasNode::Unknown { .. } => "unknown",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "text",
+                "file",
+                "missing file",
+                "link",
+                "group",
+                "unknown",
+                "unknown"
+            ]
+        );
+        let CanvasNode::File {
+            subpath,
+            resolved_path,
+            ..
+        } = &canvas.nodes[1]
+        else {
+            unreachable!()
+        };
+        assert_eq!(subpath.as_deref(), Some("#Goals"));
+        assert_eq!(Path::new(resolved_path), home.join("vault/notes/Plan.md"));
+        let CanvasNode::Unknown { node_type, raw } = &canvas.nodes[5] else {
+            unreachable!()
+        };
+        assert_eq!(node_type.as_deref(), Some("sticker"));
+        assert_eq!(raw["id"], "s");
+
+        assert_eq!(canvas.edges.len(), 1);
+        assert_eq!(canvas.edges[0].to_end.as_deref(), Some("arrow"));
+    }
+
+    #[test]
+    fn a_canvas_outside_a_vault_resolves_beside_itself() {
+        let home = TestHome::new();
+        let path = home.write("loose/board.canvas", r#"{"nodes": []}"#);
+        assert_eq!(vault_root(&path), home.join("loose"));
+    }
+
+    #[test]
+    fn parse_errors_carry_the_byte_offset() {
+        // `é` is two bytes, so the offset runs ahead of the character count
+        let raw = "{\n  \"nodes\": [{\"id\": \"é\", \"x\": oops}]\n}";
+        let Err(err) = parse(raw, Path::new("")) else {
+            panic!("parsed a malformed canvas");
+        };
+        assert_eq!(err.code, ErrorCode::ParseError);
+        assert_eq!(err.params["line"], 2);
+        let offset = err.params["offset"].as_u64().unwrap() as usize;
+        assert!(raw[offset..].starts_with("oops"), "{}", &raw[offset..]);
+
+        let Err(empty) = parse("", Path::new("")) else {
+            panic!("parsed an empty canvas");
+        };
+        assert_eq!(empty.params["offset"], 0);
+    }
+}
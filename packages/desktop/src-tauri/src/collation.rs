@@ -0,0 +1,317 @@
+// Name collation
+//
+// Listings sort names the way Finder and Explorer do, not by their bytes:
+// - Runs of digits compare as numbers, so `note2` comes before `note10`.
+//   `natural_sort: false` compares them digit by digit instead.
+// - Letters compare by their base letter first, so `Änderungen` sorts with
+//   the A's. Accents break ties, then case (upper first), then the raw name,
+//   so the order is total and stable.
+// - The locale tailors this where its alphabet differs. In Swedish and
+//   Finnish `å`, `ä`, `ö` come after `z`; in Danish and Norwegian `æ`, `ø`,
+//   `å` do.
+// Punctuation and spaces sort before digits, and digits before letters.
+// Letters are split into base and accent with Unicode NFD. `sort: legacy` is
+// the old order, lowercased names compared byte by byte, for callers that
+// depend on it.
+
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Locale,
+    Legacy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Tailoring {
+    Root,
+    /// Swedish, Finnish: `å ä ö` after `z`.
+    SwedishFinnish,
+    /// Danish, Norwegian: `æ ø å` after `z`.
+    DanishNorwegian,
+}
+
+/// One unit of a primary key, in sort order: punctuation, then numbers, then
+/// letters, then letters a tailoring puts after `z`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Unit {
+    Other(char),
+    /// A digit run without its leading zeros, shorter runs first.
+    Number(usize, String),
+    Letter(char),
+    AfterZ(u8),
+}
+
+/// Sort key of one name.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum SortKey {
+    Keyed {
+        primary: Vec<Unit>,
+        /// Lowercased, accents kept.
+        accents: String,
+        /// Upper before lower.
+        case: Vec<bool>,
+        raw: String,
+    },
+    Legacy(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Collator {
+    order: SortOrder,
+    natural: bool,
+    tailoring: Tailoring,
+}
+
+impl Collator {
+    pub fn new(order: SortOrder, natural: bool, locale: &str) -> Self {
+        let language = locale
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        let tailoring = match language.as_str() {
+            "sv" | "fi" => Tailoring::SwedishFinnish,
+            "da" | "nb" | "nn" | "no" => Tailoring::DanishNorwegian,
+            _ => Tailoring::Root,
+        };
+        Self {
+            order,
+            natural,
+            tailoring,
+        }
+    }
+
+    /// The collator for a listing command's options: natural sort unless
+    /// turned off, in `locale` or else the app's locale.
+    pub fn for_listing(
+        sort: Option<SortOrder>,
+        natural_sort: Option<bool>,
+        locale: Option<&str>,
+    ) -> Self {
+        let locale = match locale {
+            Some(locale) => locale.to_string(),
+            None => crate::i18n::current_locale(),
+        };
+        Self::new(
+            sort.unwrap_or_default(),
+            natural_sort.unwrap_or(true),
+            &locale,
+        )
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.order == SortOrder::Legacy
+    }
+
+    pub fn key(&self, name: &str) -> SortKey {
+        if self.order == SortOrder::Legacy {
+            return SortKey::Legacy(name.to_lowercase());
+        }
+        let mut primary = Vec::new();
+        let mut chars = name.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_ascii_digit() && self.natural {
+                let mut digits = String::from(c);
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    chars.next();
+                }
+                let significant = digits.trim_start_matches('0').to_string();
+                primary.push(Unit::Number(significant.len(), significant));
+            } else if c.is_ascii_digit() {
+                primary.push(Unit::Number(1, c.to_string()));
+            } else {
+                self.push_letters(c, &mut primary);
+            }
+        }
+        SortKey::Keyed {
+            primary,
+            accents: name.to_lowercase(),
+            case: name
+                .chars()
+                .filter(|c| c.is_alphabetic())
+                .map(|c| !c.is_uppercase())
+                .collect(),
+            raw: name.to_string(),
+        }
+    }
+
+    fn push_letters(&self, c: char, primary: &mut Vec<Unit>) {
+        for lower in c.to_lowercase() {
+            if let Some(rank) = self.after_z(lower) {
+                primary.push(Unit::AfterZ(rank));
+                continue;
+            }
+            match lower {
+                'ß' => primary.extend([Unit::Letter('s'), Unit::Letter('s')]),
+                'æ' => primary.extend([Unit::Letter('a'), Unit::Letter('e')]),
+                'œ' => primary.extend([Unit::Letter('o'), Unit::Letter('e')]),
+                'ø' => primary.push(Unit::Letter('o')),
+                'ł' => primary.push(Unit::Letter('l')),
+                'đ' => primary.push(Unit::Letter('d')),
+                _ => {
+                    let decomposed = DecomposingNormalizerBorrowed::new_nfd()
+                        .normalize_iter(std::iter::once(lower))
+                        .filter(|c| !is_combining_mark(*c));
+                    for base in decomposed {
+                        primary.push(match base.is_alphanumeric() {
+                            true => Unit::Letter(base),
+                            false => Unit::Other(base),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn after_z(&self, c: char) -> Option<u8> {
+        let alphabet: &[char] = match self.tailoring {
+            Tailoring::Root => return None,
+            Tailoring::SwedishFinnish => &['å', 'ä', 'ö'],
+            Tailoring::DanishNorwegian => &['æ', 'ø', 'å'],
+        };
+        alphabet.iter().position(|&a| a == c).map(|i| i as u8)
+    }
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collator: Collator, names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by_cached_key(|n| collator.key(n));
+        names
+    }
+
+    fn natural(locale: &str) -> Collator {
+        Collator::new(SortOrder::Locale, true, locale)
+    }
+
+    #[test]
+    fn numeric_runs_compare_as_numbers() {
+        let names = [
+            "note10.md",
+            "note2.md",
+            "note1.md",
+            "note02.md",
+            "note100.md",
+        ];
+        assert_eq!(
+            sorted(natural("en"), &names),
+            [
+                "note1.md",
+                "note02.md",
+                "note2.md",
+                "note10.md",
+                "note100.md"
+            ]
+        );
+        assert_eq!(
+            sorted(Collator::new(SortOrder::Locale, false, "en"), &names),
+            [
+                "note02.md",
+                "note1.md",
+                "note10.md",
+                "note100.md",
+                "note2.md"
+            ]
+        );
+        // Longer than any integer type
+        let huge = ["v99999999999999999999999", "v100000000000000000000000"];
+        assert_eq!(sorted(natural("en"), &huge), huge);
+    }
+
+    #[test]
+    fn case_only_breaks_ties() {
+        assert_eq!(
+            sorted(
+                natural("en"),
+                &["banana", "Cherry", "apple", "Banana", "APPLE"]
+            ),
+            ["APPLE", "apple", "Banana", "banana", "Cherry"]
+        );
+    }
+
+    #[test]
+    fn accented_initials_sort_with_their_letter() {
+        let names = [
+            "Zebra.md",
+            "Änderungen.md",
+            "Anfang.md",
+            "Abend.md",
+            "Ende.md",
+            "Éclair.md",
+        ];
+        assert_eq!(
+            sorted(natural("de"), &names),
+            [
+                "Abend.md",
+                "Änderungen.md",
+                "Anfang.md",
+                "Éclair.md",
+                "Ende.md",
+                "Zebra.md"
+            ]
+        );
+        assert_eq!(
+            sorted(natural("sv-SE"), &names),
+            [
+                "Abend.md",
+                "Anfang.md",
+                "Éclair.md",
+                "Ende.md",
+                "Zebra.md",
+                "Änderungen.md"
+            ]
+        );
+        assert_eq!(
+            sorted(natural("da"), &["Øst", "Åben", "Ærø", "Zulu"]),
+            ["Zulu", "Ærø", "Øst", "Åben"]
+        );
+        // Accents only break ties
+        assert_eq!(
+            sorted(natural("fr"), &["résumé", "resume"]),
+            ["resume", "résumé"]
+        );
+        assert_eq!(
+            sorted(natural("de"), &["Straße", "Strasse", "Strand"]),
+            ["Strand", "Strasse", "Straße"]
+        );
+    }
+
+    #[test]
+    fn punctuation_then_digits_then_letters() {
+        assert_eq!(
+            sorted(natural("en"), &["b", "2", "_draft", "a"]),
+            ["_draft", "2", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn legacy_is_lowercased_bytes() {
+        let legacy = Collator::new(SortOrder::Legacy, true, "en");
+        assert_eq!(
+            sorted(
+                legacy,
+                &["note2.md", "note10.md", "Änderungen.md", "Zebra.md"]
+            ),
+            ["note10.md", "note2.md", "Zebra.md", "Änderungen.md"]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+// Devtools
+//
+// Debug builds open devtools on the main window at launch only when asked:
+// with `AGENTVBX_DEVTOOLS=1`, or with `developer.open_devtools` set.
+// `set_devtools` opens or closes them on any window at runtime, so QA can
+// get at them without a rebuild or a relaunch. Release builds have no
+// devtools, and there `set_devtools` fails with `Unsupported` so the UI can
+// hide its menu item; `get_debug_flags` tells it up front.
+
+use serde::Serialize;
+use tauri::AppHandle;
+#[cfg(debug_assertions)]
+use tauri::Manager;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{mock_provider, platform, settings};
+
+const ENV_VAR: &str = "AGENTVBX_DEVTOOLS";
+const MAIN_WINDOW: &str = "main";
+
+#[derive(Serialize, Debug)]
+pub struct DebugFlags {
+    pub debug_build: bool,
+    /// `set_devtools` can work here.
+    pub devtools_available: bool,
+    /// Devtools open on the main window at launch.
+    pub devtools_at_launch: bool,
+    /// Set by `AGENTVBX_DEVTOOLS=1` rather than the setting.
+    pub devtools_from_env: bool,
+    pub mock_provider: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Open or close devtools on the window labelled `window_label`.
+#[tauri::command]
+pub fn set_devtools(app: AppHandle, window_label: String, open: bool) -> CommandResult<()> {
+    platform::desktop_only("devtools")?;
+    #[cfg(debug_assertions)]
+    {
+        let window = app.get_webview_window(&window_label).ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::NotFound,
+                format!("No window labelled {}", window_label),
+            )
+            .with("window_label", &window_label)
+        })?;
+        match open {
+            true => window.open_devtools(),
+            false => window.close_devtools(),
+        }
+        log::info!(
+            "[devtools] {} on {}",
+            if open { "opened" } else { "closed" },
+            window_label
+        );
+        Ok(())
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = (app, window_label, open);
+        Err(CommandError::new(
+            ErrorCode::Unsupported,
+            "Devtools aren't available in release builds",
+        ))
+    }
+}
+
+#[tauri::command]
+pub fn get_debug_flags() -> DebugFlags {
+    let from_env = from_env();
+    DebugFlags {
+        debug_build: cfg!(debug_assertions),
+        devtools_available: available(),
+        devtools_at_launch: available()
+            && (from_env || settings::load_settings().developer.open_devtools),
+        devtools_from_env: from_env,
+        mock_provider: mock_provider::enabled(),
+    }
+}
+
+// ─── Launch ─────────────────────────────────────────────────────────────────
+
+/// Open devtools on the main window if asked to. A missing main window is
+/// logged rather than fatal.
+pub fn open_at_launch(app: &AppHandle) {
+    if !get_debug_flags().devtools_at_launch {
+        return;
+    }
+    #[cfg(debug_assertions)]
+    match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window.open_devtools(),
+        None => log::warn!("[devtools] no {} window to open devtools on", MAIN_WINDOW),
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = (app, MAIN_WINDOW);
+}
+
+fn available() -> bool {
+    cfg!(debug_assertions) && !cfg!(mobile)
+}
+
+fn from_env() -> bool {
+    std::env::var(ENV_VAR).is_ok_and(|v| v == "1")
+}
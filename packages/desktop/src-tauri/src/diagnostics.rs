@@ -0,0 +1,75 @@
+// Diagnostics
+//
+// A single snapshot of how the backend is configured, for the settings
+// screen and support requests. Never includes secret values.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::automation::{self, ListenerStatus};
+use crate::fs_deadline::{self, TimeoutCount};
+use crate::home::{self, DataDirs};
+use crate::http::{self, EffectiveProxy};
+use crate::http_retry::{self, CircuitStatus};
+use crate::integrity::{self, CorruptionCount};
+use crate::secrets::{self, SecretsBackend};
+use crate::settings;
+use crate::startup::{self, StartupTimeline};
+use crate::system_state::{self, SystemState};
+use crate::{provider_cache, trash};
+
+#[derive(Serialize, Clone)]
+pub struct Diagnostics {
+    pub version: String,
+    pub platform: String,
+    /// Config, data, cache, and state roots. `None` when no home directory
+    /// could be found; see `get_health`.
+    pub data_dirs: Option<DataDirs>,
+    pub settings_path: Option<String>,
+    pub orchestrator_url: String,
+    pub proxy: EffectiveProxy,
+    /// Where generic secrets are stored on this machine.
+    pub secrets_backend: SecretsBackend,
+    /// Bytes held in the trash.
+    pub trash_bytes: u64,
+    /// Bytes of cached provider responses; see `provider_cache`.
+    pub provider_cache_bytes: u64,
+    /// Battery and network state, and what it's currently throttling.
+    pub system_state: SystemState,
+    /// The local automation listener, if it's serving.
+    pub automation: ListenerStatus,
+    /// Hosts whose circuit breaker is open or half-open.
+    pub http_circuits: Vec<CircuitStatus>,
+    /// Data files found damaged, at startup or on load, since install.
+    pub corruption: BTreeMap<String, CorruptionCount>,
+    /// File commands that hit their deadline since launch, by command.
+    pub fs_timeouts: BTreeMap<String, TimeoutCount>,
+    /// How long each startup phase took; see `startup`.
+    pub startup: StartupTimeline,
+}
+
+#[tauri::command]
+pub fn get_diagnostics() -> Diagnostics {
+    let settings = settings::load_settings();
+    Diagnostics {
+        version: env!("CARGO_PKG_VERSION").into(),
+        platform: std::env::consts::OS.into(),
+        data_dirs: home::data_dirs().ok(),
+        settings_path: settings::settings_path().ok().map(display),
+        orchestrator_url: settings.orchestrator_url.clone(),
+        proxy: http::effective_proxy(&settings),
+        secrets_backend: secrets::backend(),
+        trash_bytes: trash::trash_size(),
+        provider_cache_bytes: provider_cache::total_size(),
+        system_state: system_state::current(),
+        automation: automation::status(),
+        http_circuits: http_retry::circuits(),
+        corruption: integrity::corruption_counts(),
+        fs_timeouts: fs_deadline::timeout_counts(),
+        startup: startup::timeline(),
+    }
+}
+
+fn display(path: std::path::PathBuf) -> String {
+    path.to_string_lossy().to_string()
+}
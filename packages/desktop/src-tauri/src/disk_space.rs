@@ -0,0 +1,227 @@
+// Free-space guard
+//
+// Large writes check the destination volume first, so a full disk fails them
+// up front instead of leaving a half-written archive. A caller estimates the
+// bytes it will write (the source size, or the size the server announced)
+// and calls `ensure` for the destination. The check needs that much free
+// space plus `limits.space_margin_bytes` to spare, or it fails with
+// `InsufficientSpace` and the `required` and `available` bytes. Writes
+// under `limits.space_check_min_bytes` aren't checked. Neither is a volume
+// whose free space the OS won't report; the write just goes ahead.
+//
+// `get_volume_free_space` reports the same numbers for the UI to warn with
+// before the user starts anything.
+
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::settings;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Space {
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VolumeSpace {
+    /// The existing directory the numbers are for: `path` or its nearest
+    /// ancestor.
+    pub path: String,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+    /// Space large writes leave free.
+    pub margin_bytes: u64,
+    /// Less than the margin is free, so large writes will fail.
+    pub low: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Free space on the volume holding `path`, which needn't exist yet.
+#[tauri::command]
+pub fn get_volume_free_space(path: String) -> CommandResult<VolumeSpace> {
+    let dir = existing_ancestor(Path::new(&path)).ok_or_else(|| {
+        CommandError::new(ErrorCode::NotFound, format!("No such volume: {}", path))
+            .with("path", &path)
+    })?;
+    let space = volume_space(&dir).map_err(|e| {
+        CommandError::new(
+            ErrorCode::Unsupported,
+            format!("Can't read free space for {}: {}", dir.display(), e),
+        )
+        .with("path", dir.to_string_lossy())
+    })?;
+    let margin_bytes = settings::load_settings().limits.space_margin_bytes;
+    Ok(VolumeSpace {
+        path: dir.to_string_lossy().to_string(),
+        available_bytes: space.available_bytes,
+        total_bytes: space.total_bytes,
+        margin_bytes,
+        low: space.available_bytes < margin_bytes,
+    })
+}
+
+// ─── Checks ─────────────────────────────────────────────────────────────────
+
+/// `InsufficientSpace` unless `dest`'s volume can take `required` more bytes
+/// and keep its margin.
+pub fn ensure(dest: &Path, required: u64) -> CommandResult<()> {
+    ensure_with(dest, required, volume_space)
+}
+
+fn ensure_with(
+    dest: &Path,
+    required: u64,
+    space_of: impl Fn(&Path) -> io::Result<Space>,
+) -> CommandResult<()> {
+    let limits = settings::load_settings().limits;
+    if required < limits.space_check_min_bytes {
+        return Ok(());
+    }
+    let Some(dir) = existing_ancestor(dest) else {
+        return Ok(());
+    };
+    let available = match space_of(&dir) {
+        Ok(space) => space.available_bytes,
+        Err(e) => {
+            log::debug!(
+                "[disk_space] can't read free space for {}: {}",
+                dir.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+    if available >= required.saturating_add(limits.space_margin_bytes) {
+        return Ok(());
+    }
+    log::warn!(
+        "[disk_space] {} needs {} bytes but has {} free",
+        dir.display(),
+        required,
+        available
+    );
+    Err(CommandError::new(
+        ErrorCode::InsufficientSpace,
+        format!(
+            "Not enough free space on {}: {} bytes needed, {} available",
+            dir.display(),
+            required,
+            available
+        ),
+    )
+    .with("path", dest.to_string_lossy())
+    .with("required", required)
+    .with("available", available)
+    .with("margin", limits.space_margin_bytes))
+}
+
+/// `path` or the closest directory above it that exists.
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.is_dir())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(unix)]
+fn volume_space(dir: &Path) -> io::Result<Space> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let block = stats.f_frsize as u64;
+    Ok(Space {
+        available_bytes: (stats.f_bavail as u64).saturating_mul(block),
+        total_bytes: (stats.f_blocks as u64).saturating_mul(block),
+    })
+}
+
+#[cfg(windows)]
+fn volume_space(dir: &Path) -> io::Result<Space> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let (mut available, mut total) = (0u64, 0u64);
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            &mut total,
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Space {
+        available_bytes: available,
+        total_bytes: total,
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn volume_space(_dir: &Path) -> io::Result<Space> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space isn't available on this platform",
+    ))
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn free(available_bytes: u64) -> impl Fn(&Path) -> io::Result<Space> {
+        move |_: &Path| {
+            Ok(Space {
+                available_bytes,
+                total_bytes: 100 * 1024 * MIB,
+            })
+        }
+    }
+
+    #[test]
+    fn large_writes_need_room_to_spare() {
+        let home = TestHome::new();
+        let mut settings = settings::load_settings();
+        settings.limits.space_check_min_bytes = MIB;
+        settings.limits.space_margin_bytes = 10 * MIB;
+        settings::save_settings(&settings).unwrap();
+        let dest = home.join("Backups/not-yet-created.tar.gz");
+
+        ensure_with(&dest, 50 * MIB, free(60 * MIB)).unwrap();
+        let err = ensure_with(&dest, 50 * MIB, free(59 * MIB)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InsufficientSpace);
+        assert_eq!(err.params["required"], 50 * MIB);
+        assert_eq!(err.params["available"], 59 * MIB);
+
+        // Small writes and unreadable volumes aren't checked
+        ensure_with(&dest, MIB / 2, free(0)).unwrap();
+        ensure_with(&dest, 50 * MIB, |_: &Path| {
+            Err(io::ErrorKind::Unsupported.into())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn reports_the_real_volume() {
+        let home = TestHome::new();
+        let space =
+            get_volume_free_space(home.join("missing/dir").to_string_lossy().to_string()).unwrap();
+        assert!(space.total_bytes >= space.available_bytes);
+        assert!(Path::new(&space.path).is_dir());
+    }
+}
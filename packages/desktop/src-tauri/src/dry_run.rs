@@ -0,0 +1,118 @@
+// Dry runs
+//
+// Mutating commands take `dry_run`. With it set they do all their
+// validation and conflict checks, then stop before changing anything and
+// return their usual result with `would_have: true` and a `planned`
+// description of the change. Commands that already returned a plan, like
+// `bulk_edit_frontmatter`, keep it and gain the marker.
+//
+// A policy with `force_dry_run` (see `policy`) turns every mutation for its
+// tenant, from the tenant's windows or naming it, into a dry run whatever the
+// caller asks; set at the managed policy's top level, it covers windows
+// outside tenants too. Writes
+// that can't be planned, such as downloads and exports, are refused with
+// `PolicyDenied` while it's on.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{policy, tenant_windows};
+
+/// Flattened into a command's result; empty after a real change.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct DryRun {
+    /// Nothing was changed; `planned` is what would have been.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub would_have: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub planned: Option<String>,
+}
+
+impl DryRun {
+    /// The marker for a dry run that would have done `change`.
+    pub fn planned(change: impl Into<String>) -> Self {
+        Self {
+            would_have: true,
+            planned: Some(change.into()),
+        }
+    }
+
+    /// `planned(change)` for a dry run, nothing otherwise.
+    pub fn outcome(dry_run: bool, change: impl FnOnce() -> String) -> Self {
+        match dry_run {
+            true => Self::planned(change()),
+            false => Self::default(),
+        }
+    }
+}
+
+/// Whether a mutation called from the window `caller_label`, for
+/// `tenant_id` if it names one, should only be planned: the caller asked, or
+/// policy forces it.
+pub fn requested(caller_label: &str, tenant_id: Option<&str>, requested: Option<bool>) -> bool {
+    if forced(caller_label, tenant_id) {
+        if requested != Some(true) {
+            log::info!("[dry_run] policy forces a dry run for {}", caller_label);
+        }
+        return true;
+    }
+    requested.unwrap_or(false)
+}
+
+/// `PolicyDenied` for a command that can't plan its change while policy
+/// forces dry runs on the caller.
+pub fn check_writable(
+    caller_label: &str,
+    tenant_id: Option<&str>,
+    command: &str,
+) -> CommandResult<()> {
+    if !forced(caller_label, tenant_id) {
+        return Ok(());
+    }
+    log::info!("[dry_run] refused {} for {}", command, caller_label);
+    Err(CommandError::new(
+        ErrorCode::PolicyDenied,
+        format!("{} can't run while policy forces dry runs", command),
+    )
+    .with("command", command)
+    .with("force_dry_run", true))
+}
+
+fn forced(caller_label: &str, tenant_id: Option<&str>) -> bool {
+    let window_tenant = tenant_windows::window_tenant(caller_label);
+    policy::forces_dry_run(window_tenant.as_deref())
+        || tenant_id.is_some_and(|t| policy::forces_dry_run(Some(t)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn tenant_policy_forces_dry_runs_for_its_windows() {
+        let home = TestHome::new();
+        home.write(
+            ".agentvbx/tenants/acme/policy.json",
+            r#"{ "force_dry_run": true }"#,
+        );
+        assert!(requested("tenant-acme", None, Some(false)));
+        assert!(requested("main", Some("acme"), None));
+        assert!(!requested("main", None, None));
+        assert!(!requested("main", Some("globex"), Some(false)));
+
+        let err = check_writable("tenant-acme", None, "export_results").unwrap_err();
+        assert_eq!(err.code, ErrorCode::PolicyDenied);
+        assert_eq!(err.params["command"], "export_results");
+        check_writable("main", None, "export_results").unwrap();
+    }
+
+    #[test]
+    fn real_changes_serialize_no_marker() {
+        let empty = serde_json::to_value(DryRun::outcome(false, || unreachable!())).unwrap();
+        assert_eq!(empty, serde_json::json!({}));
+        let planned = serde_json::to_value(DryRun::planned("Delete a.md")).unwrap();
+        assert_eq!(planned["would_have"], true);
+    }
+}
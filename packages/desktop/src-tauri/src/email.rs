@@ -0,0 +1,934 @@
+// Email messages
+//
+// Mail clients export threads as `.eml` files (RFC 5322 messages with MIME
+// parts) and mailboxes as `.mbox` files:
+// - `parse_email` reads an `.eml` into its addresses, subject, date, a
+//   plain-text body, and the list of attachments, and
+//   `extract_email_attachment` saves one of those attachments.
+// - `list_mbox_messages` pages through an mbox. Messages start at `From `
+//   lines at the top of the file or after a blank line, and `>From ` escapes
+//   are undone. The whole file is scanned to count the messages, but only
+//   the requested page is parsed.
+// Headers are unfolded, with RFC 2047 encoded words and RFC 2231 parameters
+// decoded. Parts are decoded from base64 or quoted-printable and from their
+// charset. The body is the text/plain part, or the first one that has text
+// in each multipart/alternative; a message with only HTML gets its HTML
+// converted to text (see `html`). Every other leaf part is an attachment,
+// inline images and forwarded messages included, numbered in document order.
+//
+// Malformed MIME never fails a parse. A header block that stops making
+// sense, a missing boundary, or broken base64 leave the raw headers and
+// body as they are, with `malformed` set.
+
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use encoding_rs::{Encoding, UTF_8};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::filenames::{sanitize_filename, NameChange, SanitizeOptions};
+use crate::html::{self, Document};
+use crate::write_guard;
+
+const MAX_MESSAGE_BYTES: u64 = 64 * 1024 * 1024;
+/// Nested multiparts past this are read as plain text.
+const MAX_DEPTH: usize = 20;
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+/// Lenient about padding and stray characters, as mail often is.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Address {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Email {
+    pub from: Vec<Address>,
+    pub to: Vec<Address>,
+    pub cc: Vec<Address>,
+    pub subject: Option<String>,
+    /// RFC 3339 when the `Date` header parses, otherwise as written.
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+    pub body: String,
+    /// The message had no plain-text part, so `body` is its HTML as text.
+    pub body_from_html: bool,
+    pub attachments: Vec<EmailAttachment>,
+    /// Every header in order, unfolded and decoded.
+    pub headers: Vec<Header>,
+    /// Part of the message couldn't be read as MIME and was kept raw.
+    pub malformed: bool,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmailAttachment {
+    /// For `extract_email_attachment`.
+    pub index: usize,
+    pub name: String,
+    /// Decoded size in bytes.
+    pub size: usize,
+    pub content_type: String,
+    /// Shown in the body rather than attached, like an embedded image.
+    pub inline: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MboxMessage {
+    pub index: usize,
+    /// Where the message's `From ` line starts in the file.
+    pub byte_offset: u64,
+    #[serde(flatten)]
+    pub email: Email,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MboxPage {
+    pub messages: Vec<MboxMessage>,
+    pub total: usize,
+    /// Pass back as `offset` for the next page; `None` on the last one.
+    pub next_offset: Option<usize>,
+}
+
+/// A header as written: unfolded, not yet decoded.
+type RawHeader = (String, String);
+
+/// What a walk over the parts found.
+#[derive(Default)]
+struct Parts {
+    plain: Vec<String>,
+    html: Vec<String>,
+    attachments: Vec<(EmailAttachment, Vec<u8>)>,
+    malformed: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn parse_email(path: String) -> CommandResult<Email> {
+    tauri::async_runtime::spawn_blocking(move || {
+        read_message(Path::new(&path)).map(|raw| parse(&raw))
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Save attachment `attachment_index` of an `.eml` into `dest_dir`, under
+/// its own name made unique there; returns the path written.
+#[tauri::command]
+pub async fn extract_email_attachment(
+    window: tauri::WebviewWindow,
+    path: String,
+    attachment_index: usize,
+    dest_dir: String,
+) -> CommandResult<String> {
+    dry_run::check_writable(window.label(), None, "extract_email_attachment")?;
+    tauri::async_runtime::spawn_blocking(move || {
+        extract(Path::new(&path), attachment_index, Path::new(&dest_dir))
+            .map(|written| written.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+#[tauri::command]
+pub async fn list_mbox_messages(
+    path: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> CommandResult<MboxPage> {
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    tauri::async_runtime::spawn_blocking(move || mbox_page(Path::new(&path), offset, limit))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Reading ────────────────────────────────────────────────────────────────
+
+pub fn parse(raw: &[u8]) -> Email {
+    let (headers, _, _) = split_headers(raw);
+    let mut parts = Parts::default();
+    walk(raw, 0, &mut parts);
+
+    let (body, body_from_html) = match (parts.plain.is_empty(), parts.html.is_empty()) {
+        (true, false) => {
+            let text = parts
+                .html
+                .iter()
+                .map(|html| {
+                    let doc = html::parse(html);
+                    doc.plain_text(doc.find("body").unwrap_or(Document::ROOT))
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            (text, true)
+        }
+        _ => (parts.plain.join("\n\n").replace("\r\n", "\n"), false),
+    };
+    let text = |name| header(&headers, name).map(|v| decode_words(v.trim()));
+    Email {
+        from: addresses(header(&headers, "from")),
+        to: addresses(header(&headers, "to")),
+        cc: addresses(header(&headers, "cc")),
+        subject: text("subject"),
+        date: header(&headers, "date").map(date),
+        message_id: text("message-id"),
+        body,
+        body_from_html,
+        attachments: parts.attachments.into_iter().map(|(a, _)| a).collect(),
+        headers: headers
+            .iter()
+            .map(|(name, value)| Header {
+                name: name.clone(),
+                value: decode_words(value.trim()),
+            })
+            .collect(),
+        malformed: parts.malformed,
+    }
+}
+
+pub fn extract(path: &Path, index: usize, dest_dir: &Path) -> CommandResult<PathBuf> {
+    let raw = read_message(path)?;
+    let mut parts = Parts::default();
+    walk(&raw, 0, &mut parts);
+    let count = parts.attachments.len();
+    let Some((attachment, bytes)) = parts.attachments.into_iter().nth(index) else {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("{} has no attachment {}", path.display(), index),
+        )
+        .with("path", path.to_string_lossy())
+        .with("attachment_index", index)
+        .with("attachments", count));
+    };
+
+    let options = SanitizeOptions::default();
+    let mut name = sanitize_filename(&attachment.name, &options);
+    if name.changes.contains(&NameChange::Blank) {
+        name = sanitize_filename(&format!("attachment-{}", index + 1), &options);
+    }
+    fs::create_dir_all(dest_dir)?;
+    let _guard = write_guard::lock();
+    let target = name.unique_in(dest_dir, &options);
+    fs::write(&target, &bytes)?;
+    activity::record(ActivityKind::Write, &target, None);
+    Ok(target)
+}
+
+fn mbox_page(path: &Path, offset: usize, limit: usize) -> CommandResult<MboxPage> {
+    let mut reader = BufReader::new(open(path)?);
+    let mut messages = Vec::new();
+    let mut total = 0;
+    // The message being read: its index, where it starts, and its bytes
+    // when it's on the page
+    let mut current: Option<(usize, u64, Option<Vec<u8>>)> = None;
+    let mut line = Vec::new();
+    let mut position = 0;
+    let mut after_blank = true;
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        if after_blank && line.starts_with(b"From ") {
+            messages.extend(current.take().and_then(finish_mbox_message));
+            let on_page = (offset..offset + limit).contains(&total);
+            current = Some((total, position, on_page.then(Vec::new)));
+            total += 1;
+        } else if let Some((_, _, Some(bytes))) = &mut current {
+            let quoted = line.iter().take_while(|&&b| b == b'>').count();
+            let unescaped = match quoted > 0 && line[quoted..].starts_with(b"From ") {
+                true => &line[1..],
+                false => &line[..],
+            };
+            if bytes.len() as u64 + unescaped.len() as u64 <= MAX_MESSAGE_BYTES {
+                bytes.extend_from_slice(unescaped);
+            }
+        }
+        after_blank = line == b"\n" || line == b"\r\n";
+        position += read as u64;
+    }
+    messages.extend(current.and_then(finish_mbox_message));
+    Ok(MboxPage {
+        messages,
+        total,
+        next_offset: (offset + limit < total).then_some(offset + limit),
+    })
+}
+
+fn finish_mbox_message(
+    (index, byte_offset, bytes): (usize, u64, Option<Vec<u8>>),
+) -> Option<MboxMessage> {
+    let mut bytes = bytes?;
+    // The blank line before the next `From ` separates messages
+    let trimmed = trim_newline(&bytes).len();
+    bytes.truncate(trimmed);
+    Some(MboxMessage {
+        index,
+        byte_offset,
+        email: parse(&bytes),
+    })
+}
+
+fn read_message(path: &Path) -> CommandResult<Vec<u8>> {
+    let size = open(path)?.metadata()?.len();
+    if size > MAX_MESSAGE_BYTES {
+        return Err(CommandError::new(
+            ErrorCode::TooLarge,
+            format!(
+                "{} is {} bytes, over the {} byte limit",
+                path.display(),
+                size,
+                MAX_MESSAGE_BYTES
+            ),
+        )
+        .with("path", path.to_string_lossy())
+        .with("size", size)
+        .with("limit", MAX_MESSAGE_BYTES));
+    }
+    Ok(fs::read(path)?)
+}
+
+fn open(path: &Path) -> CommandResult<File> {
+    File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => CommandError::new(
+            ErrorCode::NotFound,
+            format!("File not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()),
+        _ => e.into(),
+    })
+}
+
+// ─── MIME ───────────────────────────────────────────────────────────────────
+
+/// Headers, the body after them, and whether the header block broke off
+/// without the blank line that ends it.
+fn split_headers(raw: &[u8]) -> (Vec<RawHeader>, &[u8], bool) {
+    let mut headers: Vec<RawHeader> = Vec::new();
+    let mut at = 0;
+    while at < raw.len() {
+        let end = line_end(raw, at);
+        let line = trim_newline(&raw[at..end]);
+        if line.is_empty() {
+            return (headers, &raw[end..], false);
+        }
+        let text = String::from_utf8_lossy(line);
+        // The envelope line of a message saved from an mbox
+        if at == 0 && text.starts_with("From ") {
+            at = end;
+            continue;
+        }
+        if text.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push_str(&text);
+                at = end;
+                continue;
+            }
+        }
+        match text.split_once(':') {
+            Some((name, value)) if is_header_name(name.trim_end()) => {
+                headers.push((name.trim_end().to_string(), value.to_string()));
+            }
+            _ => return (headers, &raw[at..], true),
+        }
+        at = end;
+    }
+    (headers, &raw[raw.len()..], false)
+}
+
+fn is_header_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn header<'a>(headers: &'a [RawHeader], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn walk(raw: &[u8], depth: usize, parts: &mut Parts) {
+    let (headers, body, broken) = split_headers(raw);
+    parts.malformed |= broken;
+    let (mime, params) = parameters(header(&headers, "content-type").unwrap_or("text/plain"));
+    let disposition = header(&headers, "content-disposition").map(parameters);
+    let attached = disposition.as_ref().is_some_and(|(d, _)| d == "attachment");
+    let filename = disposition
+        .as_ref()
+        .and_then(|(_, p)| p.get("filename"))
+        .or_else(|| params.get("name"))
+        .filter(|name| !name.trim().is_empty());
+
+    if mime.starts_with("multipart/") && depth < MAX_DEPTH {
+        let Some((children, closed)) = params.get("boundary").and_then(|b| multipart(body, b))
+        else {
+            parts.malformed = true;
+            parts.plain.push(String::from_utf8_lossy(body).to_string());
+            return;
+        };
+        parts.malformed |= !closed;
+        if mime != "multipart/alternative" {
+            for child in children {
+                walk(child, depth + 1, parts);
+            }
+            return;
+        }
+        // Alternatives of the same content: keep one, preferring plain text
+        let choices: Vec<Parts> = children
+            .iter()
+            .map(|child| {
+                let mut choice = Parts::default();
+                walk(child, depth + 1, &mut choice);
+                choice
+            })
+            .collect();
+        let chosen = choices
+            .iter()
+            .position(|c| !c.plain.is_empty())
+            .or_else(|| choices.iter().position(|c| !c.html.is_empty()));
+        for (i, choice) in choices.into_iter().enumerate() {
+            parts.malformed |= choice.malformed;
+            if Some(i) == chosen {
+                parts.plain.extend(choice.plain);
+                parts.html.extend(choice.html);
+            }
+            for (mut attachment, bytes) in choice.attachments {
+                attachment.index = parts.attachments.len();
+                parts.attachments.push((attachment, bytes));
+            }
+        }
+        return;
+    }
+
+    let encoding = header(&headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let bytes = match encoding.as_str() {
+        "base64" => {
+            let data: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/'))
+                .collect();
+            match BASE64.decode(&data) {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    parts.malformed = true;
+                    body.to_vec()
+                }
+            }
+        }
+        "quoted-printable" => quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+
+    let text = matches!(mime.as_str(), "text/plain" | "text/html");
+    if text && !attached && filename.is_none() {
+        let decoded = decode_text(&bytes, params.get("charset").map(String::as_str));
+        match mime.as_str() {
+            "text/html" => parts.html.push(decoded),
+            _ => parts.plain.push(decoded),
+        }
+        return;
+    }
+    let index = parts.attachments.len();
+    let name = match filename {
+        Some(name) => name.trim().to_string(),
+        None if mime == "message/rfc822" => format!("message-{}.eml", index + 1),
+        None => format!("attachment-{}", index + 1),
+    };
+    let attachment = EmailAttachment {
+        index,
+        name,
+        size: bytes.len(),
+        content_type: mime,
+        inline: !attached && disposition.is_some(),
+    };
+    parts.attachments.push((attachment, bytes));
+}
+
+/// The parts of a multipart body, and whether its closing delimiter was
+/// there; `None` when the boundary never appears.
+fn multipart<'a>(body: &'a [u8], boundary: &str) -> Option<(Vec<&'a [u8]>, bool)> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut at = 0;
+    while at < body.len() {
+        let end = line_end(body, at);
+        let line = trim_newline(&body[at..end]);
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            let close = rest.starts_with(b"--");
+            let rest = if close { &rest[2..] } else { rest };
+            if rest.iter().all(u8::is_ascii_whitespace) {
+                // The line break before a delimiter belongs to it
+                if let Some(start) = start {
+                    parts.push(trim_newline(&body[start..at]));
+                }
+                if close {
+                    return Some((parts, true));
+                }
+                start = Some(end);
+            }
+        }
+        at = end;
+    }
+    let start = start?;
+    parts.push(&body[start..]);
+    Some((parts, false))
+}
+
+/// A header value's lowercase first item and its `key=value` parameters,
+/// with RFC 2231 continuations joined and decoded, e.g. for
+/// `attachment; filename*=UTF-8''na%C3%AFve.txt`.
+fn parameters(value: &str) -> (String, HashMap<String, String>) {
+    let mut items = split_outside_quotes(value, ';').into_iter();
+    let first = items.next().unwrap_or_default().trim().to_ascii_lowercase();
+    // Name → (section number, value, percent-encoded)
+    let mut sections: HashMap<String, Vec<(usize, String, bool)>> = HashMap::new();
+    for item in items {
+        let Some((key, raw)) = item.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let (key, encoded) = match key.strip_suffix('*') {
+            Some(key) => (key.to_string(), true),
+            None => (key, false),
+        };
+        let (key, section) = match key.rsplit_once('*') {
+            Some((name, n)) if n.parse::<usize>().is_ok() => {
+                (name.to_string(), n.parse().unwrap_or_default())
+            }
+            _ => (key, 0),
+        };
+        sections
+            .entry(key)
+            .or_default()
+            .push((section, unquote(raw.trim()), encoded));
+    }
+
+    let params = sections
+        .into_iter()
+        .map(|(key, mut pieces)| {
+            pieces.sort_by_key(|(section, _, _)| *section);
+            if !pieces.iter().any(|(_, _, encoded)| *encoded) {
+                let joined: String = pieces.into_iter().map(|(_, v, _)| v).collect();
+                return (key, decode_words(&joined));
+            }
+            let mut charset = None;
+            let mut bytes = Vec::new();
+            for (i, (_, value, encoded)) in pieces.iter().enumerate() {
+                if !encoded {
+                    bytes.extend_from_slice(value.as_bytes());
+                    continue;
+                }
+                let mut value = value.as_str();
+                // The first section starts with `charset'language'`
+                if i == 0 {
+                    let mut fields = value.splitn(3, '\'');
+                    if let (Some(cs), Some(_), Some(rest)) =
+                        (fields.next(), fields.next(), fields.next())
+                    {
+                        charset = Some(cs.to_string());
+                        value = rest;
+                    }
+                }
+                bytes.extend(percent_decode(value.as_bytes()));
+            }
+            (key, decode_text(&bytes, charset.as_deref()))
+        })
+        .collect();
+    (first, params)
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut angle = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' if !quoted => angle += 1,
+            '>' if !quoted && angle > 0 => angle -= 1,
+            c if c == separator && !quoted && angle == 0 => {
+                items.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items
+}
+
+fn unquote(text: &str) -> String {
+    let Some(inner) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) else {
+        return text.to_string();
+    };
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ─── Decoding ───────────────────────────────────────────────────────────────
+
+fn decode_text(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|label| Encoding::for_label(label.trim().as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// `=?charset?B?...?=` and `=?charset?Q?...?=` words decoded; the space
+/// between two encoded words is dropped.
+fn decode_words(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let before = &rest[..start];
+        match encoded_word(&rest[start..]) {
+            Some((decoded, len)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    out.push_str(before);
+                }
+                out.push_str(&decoded);
+                rest = &rest[start + len..];
+                after_word = true;
+            }
+            None => {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The decoded text of the encoded word `text` starts with, and its length.
+fn encoded_word(text: &str) -> Option<(String, usize)> {
+    let inner = text.strip_prefix("=?")?;
+    let (charset, rest) = inner.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let end = rest.find("?=")?;
+    let data = &rest[..end];
+    if data.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "B" | "b" => BASE64.decode(data.trim_end_matches('=')).ok()?,
+        "Q" | "q" => quoted_printable(data.as_bytes(), true),
+        _ => return None,
+    };
+    // RFC 2231 allows `charset*language`
+    let charset = charset.split('*').next().unwrap_or_default();
+    let len = text.len() - rest[end + 2..].len();
+    Some((decode_text(&bytes, Some(charset)), len))
+}
+
+/// `=XX` escapes and soft line breaks undone; in headers, `_` is a space.
+fn quoted_printable(data: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'=' => {
+                let rest = &data[i + 1..];
+                if rest.starts_with(b"\r\n") {
+                    i += 3;
+                } else if rest.starts_with(b"\n") {
+                    i += 2;
+                } else if let Some(byte) = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    out.push(byte);
+                    i += 3;
+                } else {
+                    out.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn percent_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let byte = (data[i] == b'%')
+            .then(|| data.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// ─── Fields ─────────────────────────────────────────────────────────────────
+
+/// `Name <a@b.com>`, `"Last, First" <c@d.com>`, `e@f.com (Name)`, and
+/// bare addresses, comma-separated.
+fn addresses(raw: Option<&str>) -> Vec<Address> {
+    let Some(raw) = raw else {
+        return Vec::new();
+    };
+    split_outside_quotes(raw, ',')
+        .into_iter()
+        .filter_map(|item| {
+            let item = item.trim().trim_end_matches(';').trim();
+            let (name, email) = match (item.rfind('<'), item.rfind('>')) {
+                (Some(open), Some(close)) if open < close => {
+                    (item[..open].trim(), item[open + 1..close].trim())
+                }
+                _ => match (item.find('('), item.rfind(')')) {
+                    (Some(open), Some(close)) if open < close => {
+                        (item[open + 1..close].trim(), item[..open].trim())
+                    }
+                    _ => ("", item),
+                },
+            };
+            // A group's `name:` prefix, as in `Team: a@b.com`
+            let email = email.rsplit(':').next().unwrap_or_default().trim();
+            if email.is_empty() {
+                return None;
+            }
+            let name = decode_words(&unquote(name));
+            Some(Address {
+                name: (!name.is_empty()).then_some(name),
+                email: email.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn date(raw: &str) -> String {
+    let raw = raw.trim();
+    // A trailing comment like `(UTC)`
+    let bare = match raw.rfind('(') {
+        Some(open) if raw.ends_with(')') => raw[..open].trim_end(),
+        _ => raw,
+    };
+    chrono::DateTime::parse_from_rfc2822(bare)
+        .map(|date| date.to_rfc3339())
+        .unwrap_or_else(|_| raw.to_string())
+}
+
+fn line_end(data: &[u8], at: usize) -> usize {
+    data[at..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| at + i + 1)
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    const MESSAGE: &str = "From: =?UTF-8?Q?Zo=C3=AB?= <zoe@example.com>\r\n\
+        To: \"Doe, Jane\" <jane@example.com>, bob@example.com (Bob)\r\n\
+        Cc: Team: carol@example.com;\r\n\
+        Subject: =?UTF-8?B?UmU6IFF1YXJ0ZXJseQ==?=\r\n =?UTF-8?Q?_report?=\r\n\
+        Date: Tue, 3 Mar 2026 09:30:00 +0100 (CET)\r\n\
+        Message-ID: <abc@example.com>\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        Preamble nobody reads.\r\n\
+        --outer\r\n\
+        Content-Type: multipart/alternative; boundary=inner\r\n\
+        \r\n\
+        --inner\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Numbers are =E2=80=9Cup=E2=80=9D, see the a=\r\n\
+        ttached report.\r\n\
+        --inner\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <p>HTML version</p>\r\n\
+        --inner--\r\n\
+        --outer\r\n\
+        Content-Type: application/pdf\r\n\
+        Content-Disposition: attachment; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERi0x\r\n\
+        LjcK\r\n\
+        --outer--\r\n\
+        Epilogue.\r\n";
+
+    #[test]
+    fn reads_addresses_body_and_attachments() {
+        let email = parse(MESSAGE.as_bytes());
+        let address = |name: Option<&str>, email: &str| Address {
+            name: name.map(str::to_string),
+            email: email.to_string(),
+        };
+        assert_eq!(email.from, vec![address(Some("Zoë"), "zoe@example.com")]);
+        assert_eq!(
+            email.to,
+            vec![
+                address(Some("Doe, Jane"), "jane@example.com"),
+                address(Some("Bob"), "bob@example.com"),
+            ]
+        );
+        assert_eq!(email.cc, vec![address(None, "carol@example.com")]);
+        assert_eq!(email.subject.as_deref(), Some("Re: Quarterly report"));
+        assert_eq!(email.date.as_deref(), Some("2026-03-03T09:30:00+01:00"));
+        assert_eq!(email.message_id.as_deref(), Some("<abc@example.com>"));
+        assert_eq!(email.body, "Numbers are “up”, see the attached report.");
+        assert!(!email.body_from_html);
+        assert!(!email.malformed);
+        assert_eq!(
+            email.attachments,
+            vec![EmailAttachment {
+                index: 0,
+                name: "résumé.pdf".to_string(),
+                size: 9,
+                content_type: "application/pdf".to_string(),
+                inline: false,
+            }]
+        );
+
+        let home = TestHome::new();
+        home.write("mail/thread.eml", MESSAGE);
+        let eml = home.join("mail/thread.eml");
+        let first = extract(&eml, 0, &home.join("out")).unwrap();
+        assert_eq!(fs::read(&first).unwrap(), b"%PDF-1.7\n");
+        let second = extract(&eml, 0, &home.join("out")).unwrap();
+        assert_eq!(second, home.join("out/résumé (2).pdf"));
+        let err = extract(&eml, 1, &home.join("out")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn converts_html_only_mail_to_text() {
+        let raw = "Subject: Caf\u{e9}\n\
+            Content-Type: text/html; charset=iso-8859-1\n\
+            Content-Transfer-Encoding: quoted-printable\n\
+            \n\
+            <html><head><style>p {color: red}</style></head><body>\
+            <p>Men=FC for <b>today</b>:</p><ul><li>Soup</li><li>Bread</li></ul>\
+            <p>Line one<br>Line two</p></body></html>\n";
+        let email = parse(raw.as_bytes());
+        assert!(email.body_from_html);
+        assert_eq!(
+            email.body,
+            "Menü for today:\n\n- Soup\n- Bread\n\nLine one\nLine two"
+        );
+        assert_eq!(email.subject.as_deref(), Some("Café"));
+    }
+
+    #[test]
+    fn malformed_mime_keeps_the_raw_body() {
+        let raw = "Subject: No boundary\nContent-Type: multipart/mixed\n\nJust text.\n";
+        let email = parse(raw.as_bytes());
+        assert!(email.malformed);
+        assert_eq!(email.body, "Just text.\n");
+        assert_eq!(email.subject.as_deref(), Some("No boundary"));
+
+        let email = parse(b"not an email at all\nsecond line");
+        assert!(email.malformed);
+        assert!(email.headers.is_empty());
+        assert_eq!(email.body, "not an email at all\nsecond line");
+    }
+
+    #[test]
+    fn pages_through_an_mbox() {
+        let mut mbox = String::new();
+        for n in 1..=3 {
+            mbox.push_str(&format!(
+                "From sender@example.com Tue Mar  3 09:30:00 2026\n\
+                 Subject: Message {}\n\
+                 \n\
+                 Hello\n\
+                 >From the archive\n\
+                 \n",
+                n
+            ));
+        }
+        let home = TestHome::new();
+        home.write("mail/inbox.mbox", &mbox);
+        let path = home.join("mail/inbox.mbox");
+
+        let page = mbox_page(&path, 1, 1).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.next_offset, Some(2));
+        assert_eq!(page.messages.len(), 1);
+        let message = &page.messages[0];
+        assert_eq!(message.index, 1);
+        assert_eq!(message.byte_offset, (mbox.len() / 3) as u64);
+        assert_eq!(message.email.subject.as_deref(), Some("Message 2"));
+        assert_eq!(message.email.body, "Hello\nFrom the archive\n");
+
+        let last = mbox_page(&path, 2, 50).unwrap();
+        assert_eq!(last.next_offset, None);
+        assert_eq!(last.messages[0].email.subject.as_deref(), Some("Message 3"));
+    }
+}
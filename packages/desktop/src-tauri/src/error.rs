@@ -0,0 +1,164 @@
+// Structured command errors
+//
+// Commands that the frontend needs to branch on return `CommandError`, which
+// serializes as `{ "code": "PortInUse", "message": "...", "params": {...} }`.
+// `code` is stable; `message` is for logs and fallback display; `params`
+// carries the values a UI message needs (a port, a path, a limit). The UI's
+// text for each code comes from `i18n::get_error_catalog`, so a new code
+// needs a template in `locales/en.json`.
+
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Unexpected failure with no more specific code.
+    Internal,
+    /// Filesystem or OS-level I/O failure.
+    Io,
+    NotFound,
+    InvalidInput,
+    /// No captured session for the tenant/provider; the user must log in.
+    SessionNotFound,
+    /// A provider request targeted a host outside its allowed domains.
+    DomainNotAllowed,
+    /// The remote host could not be reached or the connection failed.
+    Network,
+    /// Too many concurrent provider streams; `params.limit` has the cap.
+    StreamLimitReached,
+    /// Master passphrase mode is on and the vault hasn't been unlocked.
+    VaultLocked,
+    WrongPassphrase,
+    /// Too many attempts; `params.retry_after_secs` says when to retry.
+    RateLimited,
+    /// The OS or another app already holds the requested global shortcut.
+    ShortcutConflict,
+    /// Malformed file content; `params` has `line`, `column`, and byte `offset`.
+    ParseError,
+    /// Over a configured size limit; `params` has `size`, `limit`, and the
+    /// `setting` that controls it.
+    TooLarge,
+    /// Existing data is newer than what would replace it; retry with `force`.
+    Conflict,
+    /// Turned off in settings; `params.setting` names the switch.
+    FeatureDisabled,
+    /// Not available in this build or for this file; `params` says which
+    /// `feature` or `format`.
+    Unsupported,
+    /// No WhatsApp account is linked for `params.tenant_id`.
+    NotLinked,
+    /// `params` has the requested `page` and the `page_count`.
+    PageOutOfRange,
+    /// A policy doesn't allow this. For a chat, `params` has `chat_id` and
+    /// `policy`; for a tenant's policy, `provider_id` or `command`, and
+    /// whether it's `managed` by IT.
+    PolicyDenied,
+    /// Stopped by `cancel_operation`; `params.operation_id` says which.
+    Cancelled,
+    /// Held back by the power or network state; `params` has the `reason`
+    /// and the `setting` that controls it.
+    Throttled,
+    /// Desktop-only command called on mobile; `params` has the `feature`
+    /// and the `platform`.
+    NotSupportedOnPlatform,
+    /// The OS wouldn't let us keep access to a picked folder; `params.path`
+    /// says which.
+    PermissionDenied,
+    PortInUse,
+    AlreadyRunning,
+    NotRunning,
+    /// A newer version of the app wrote this file, so saving over it would
+    /// lose data; `params` has the `path`, its `schema_version`, and the
+    /// `supported` version.
+    DataFromNewerVersion,
+    /// No home directory could be determined, so there's nowhere to keep
+    /// data; `params.found` has the non-absolute path the OS gave, if any.
+    HomeUnavailable,
+    /// A link resolved to a loopback, private, or other internal address,
+    /// which the app won't fetch; `params` has the `url` and the `address`.
+    AddressNotAllowed,
+    /// A page had no article text worth keeping, or wasn't HTML at all;
+    /// `params` has the `url` and the `text_chars` found.
+    ContentNotExtractable,
+    /// The destination volume is too full for a write; `params` has the
+    /// `path`, the `required` and `available` bytes, and the `margin` kept
+    /// free.
+    InsufficientSpace,
+    /// A filesystem command gave up waiting on a path, usually on a stalled
+    /// network volume; `params` has the `command`, `path`, `timeout_ms`,
+    /// and whether it's a `network_volume`.
+    Timeout,
+    /// Read-only mode is on; `params` has the `command` and the `reason`
+    /// the mode was turned on with.
+    ReadOnlyMode,
+    /// A folder picked to connect is inside a connected store, or inside
+    /// another folder picked with it; `params` has the `path` and the
+    /// `store_name`, and the `store_id` for a connected store.
+    AlreadyInStore,
+    /// A file from outside was held in quarantine rather than saved;
+    /// `params` has its quarantine `id`, the scan `outcome`, and the
+    /// `reasons`.
+    Quarantined,
+    /// A command was called while the backend was still starting and it
+    /// didn't finish in time; `params` has the `command` and the last
+    /// startup `phase` that finished.
+    NotReady,
+    /// Maintenance mode is on; `params` has the `command` or `operation`
+    /// refused and `since`, when the mode was turned on.
+    MaintenanceMode,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            params: serde_json::Map::new(),
+        }
+    }
+
+    /// Attach a parameter for the frontend to format its message with.
+    pub fn with(mut self, key: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.params.insert(key.to_string(), value);
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Plain string errors from the older helpers become `Internal`.
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+/// And the other way, for helpers that still return `String` errors.
+impl From<CommandError> for String {
+    fn from(e: CommandError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(ErrorCode::Io, e.to_string())
+    }
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
@@ -0,0 +1,185 @@
+// Event journal
+//
+// Events emitted while the webview is reloading (a dev hot reload, or a
+// restart after a crash) are lost, which would leave finished operations
+// looking stuck. `operation-progress`, `operation-finished`, and
+// `assigned-task-status` therefore go through `emit` here. It gives each one the next sequence number, adds it
+// to the payload as `seq`, and keeps a copy in a journal held in managed
+// state. That state outlives the webview.
+//
+// After a reload the frontend calls `catch_up_events` with the last `seq`
+// it saw and replays what it missed. The journal keeps the newest
+// `MAX_ENTRIES`. If the frontend asks for events older than that, it gets
+// `gap_detected` instead of a partial replay and should reload its state
+// from `list_operations`.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+const MAX_ENTRIES: usize = 4000;
+const DEFAULT_LIMIT: usize = 500;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub event: String,
+    /// The payload as emitted, `seq` included.
+    pub payload: serde_json::Value,
+    pub at: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CatchUp {
+    /// Everything after `since_seq`, oldest first, up to `limit`. With
+    /// `more`, call again from the last `seq` returned.
+    Events {
+        events: Vec<JournalEntry>,
+        latest_seq: u64,
+        more: bool,
+    },
+    /// Events after `since_seq` have already dropped out of the journal, or
+    /// were from before the app restarted.
+    GapDetected { oldest_seq: u64, latest_seq: u64 },
+}
+
+#[derive(Default)]
+pub struct EventJournal(Mutex<Journal>);
+
+#[derive(Default)]
+struct Journal {
+    /// The last sequence number given out; the first event is 1.
+    latest: u64,
+    entries: VecDeque<JournalEntry>,
+}
+
+impl Journal {
+    fn record(&mut self, event: &str, mut payload: serde_json::Value) -> serde_json::Value {
+        self.latest += 1;
+        if let serde_json::Value::Object(fields) = &mut payload {
+            fields.insert("seq".into(), self.latest.into());
+        }
+        self.entries.push_back(JournalEntry {
+            seq: self.latest,
+            event: event.to_string(),
+            payload: payload.clone(),
+            at: chrono::Utc::now().to_rfc3339(),
+        });
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        payload
+    }
+
+    fn since(&self, since_seq: u64, limit: usize) -> CatchUp {
+        let oldest = self.entries.front().map_or(self.latest + 1, |e| e.seq);
+        // Ahead of `latest` means the app restarted and numbering began again
+        if since_seq + 1 < oldest || since_seq > self.latest {
+            return CatchUp::GapDetected {
+                oldest_seq: oldest,
+                latest_seq: self.latest,
+            };
+        }
+        let mut missed = self.entries.iter().filter(|e| e.seq > since_seq);
+        let events: Vec<_> = missed.by_ref().take(limit).cloned().collect();
+        CatchUp::Events {
+            events,
+            latest_seq: self.latest,
+            more: missed.next().is_some(),
+        }
+    }
+}
+
+// ─── Emitting ───────────────────────────────────────────────────────────────
+
+/// Journal `payload` and emit it as `event` with its `seq`.
+pub fn emit<T: Serialize>(app: &AppHandle, event: &str, payload: T) {
+    let payload = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            log::warn!("[event_journal] can't serialize {}: {}", event, e);
+            return;
+        }
+    };
+    let payload = app
+        .state::<EventJournal>()
+        .0
+        .lock()
+        .unwrap()
+        .record(event, payload);
+    let _ = app.emit(event, payload);
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Journaled events after `since_seq`; pass 0 for everything still kept.
+#[tauri::command]
+pub fn catch_up_events(
+    journal: State<'_, EventJournal>,
+    since_seq: u64,
+    limit: Option<usize>,
+) -> CatchUp {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    journal.0.lock().unwrap().since(since_seq, limit)
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seqs(catch_up: &CatchUp) -> Vec<u64> {
+        match catch_up {
+            CatchUp::Events { events, .. } => events.iter().map(|e| e.seq).collect(),
+            CatchUp::GapDetected { .. } => panic!("unexpected gap: {:?}", catch_up),
+        }
+    }
+
+    #[test]
+    fn replays_missed_events_in_pages() {
+        let mut journal = Journal::default();
+        assert!(matches!(
+            journal.since(0, 10),
+            CatchUp::Events {
+                latest_seq: 0,
+                more: false,
+                ..
+            }
+        ));
+        for id in 0..5 {
+            let sent = journal.record("operation-progress", serde_json::json!({ "id": id }));
+            assert_eq!(sent["seq"], id + 1);
+        }
+
+        assert_eq!(seqs(&journal.since(2, 10)), vec![3, 4, 5]);
+        let page = journal.since(0, 2);
+        assert_eq!(seqs(&page), vec![1, 2]);
+        assert!(matches!(page, CatchUp::Events { more: true, .. }));
+        assert!(seqs(&journal.since(5, 10)).is_empty());
+        assert!(matches!(
+            journal.since(9, 10),
+            CatchUp::GapDetected { latest_seq: 5, .. }
+        ));
+    }
+
+    #[test]
+    fn events_past_the_cap_are_a_gap() {
+        let mut journal = Journal::default();
+        for _ in 0..MAX_ENTRIES + 10 {
+            journal.record("operation-finished", serde_json::json!({}));
+        }
+        assert_eq!(journal.entries.len(), MAX_ENTRIES);
+
+        assert!(matches!(
+            journal.since(5, 10),
+            CatchUp::GapDetected { oldest_seq: 11, .. }
+        ));
+        // The last one dropped is fine: nothing after it is missing
+        assert_eq!(seqs(&journal.since(10, 1)), vec![11]);
+    }
+}
@@ -0,0 +1,475 @@
+// Result exports
+//
+// `export_results` writes task extraction, vault discovery, or store health
+// results, or a tenant's provider usage ledger, to a report file in a
+// connected store, as CSV or pretty-printed JSON. The rows come from a completed operation that
+// kept its result (see `ResultRef::Stored`), from a payload the caller sends,
+// or for usage from the ledger itself. A streamed task extraction keeps no
+// tasks, so its rows have to be sent as a payload.
+//
+// Each kind has a fixed set of columns, in this order, used for both formats:
+// - `tasks`: path, relative_path, line, description, status, completed, due,
+//   scheduled, start, completed_at, raw
+// - `vaults`: name, path, note_count
+// - `store_health`: check, path, detail (the issues a report lists)
+// - `provider_usage`: at, tenant_id, provider_id, session, category, method,
+//   streamed, status, outcome, request_bytes, response_bytes, duration_ms
+// Missing fields are written as empty cells, or `null` in JSON.
+//
+// Rows are written one at a time to a hidden partial file next to the
+// destination, which is renamed into place when complete, so a large export
+// is never built in memory. CSV follows RFC 4180 with `\r\n` line endings;
+// `excel_bom` adds a UTF-8 byte order mark so Excel detects the encoding.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+use crate::artifact_download::{self, ConflictPolicy};
+use crate::dry_run;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::operations::{OperationKind, Operations};
+use crate::provider_usage::{self, UsageRange};
+
+const TASK_COLUMNS: &[&str] = &[
+    "path",
+    "relative_path",
+    "line",
+    "description",
+    "status",
+    "completed",
+    "due",
+    "scheduled",
+    "start",
+    "completed_at",
+    "raw",
+];
+const VAULT_COLUMNS: &[&str] = &["name", "path", "note_count"];
+const HEALTH_COLUMNS: &[&str] = &["check", "path", "detail"];
+const USAGE_COLUMNS: &[&str] = &[
+    "at",
+    "tenant_id",
+    "provider_id",
+    "session",
+    "category",
+    "method",
+    "streamed",
+    "status",
+    "outcome",
+    "request_bytes",
+    "response_bytes",
+    "duration_ms",
+];
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultKind {
+    Tasks,
+    Vaults,
+    StoreHealth,
+    ProviderUsage,
+}
+
+impl ResultKind {
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            ResultKind::Tasks => TASK_COLUMNS,
+            ResultKind::Vaults => VAULT_COLUMNS,
+            ResultKind::StoreHealth => HEALTH_COLUMNS,
+            ResultKind::ProviderUsage => USAGE_COLUMNS,
+        }
+    }
+
+    /// The operation that produces these rows; usage comes from the ledger.
+    fn operation_kind(self) -> Option<OperationKind> {
+        match self {
+            ResultKind::Tasks => Some(OperationKind::TaskExtraction),
+            ResultKind::Vaults => Some(OperationKind::VaultDiscovery),
+            ResultKind::StoreHealth => Some(OperationKind::StoreHealth),
+            ResultKind::ProviderUsage => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ResultKind::Tasks => "tasks",
+            ResultKind::Vaults => "vaults",
+            ResultKind::StoreHealth => "store_health",
+            ResultKind::ProviderUsage => "provider_usage",
+        }
+    }
+}
+
+/// `{ "operation_id": 7 }`, `{ "payload": ... }`, or
+/// `{ "provider_usage": { "tenant_id": "acme", "range": ... } }`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportSource {
+    OperationId(u64),
+    /// A list of rows, or for tasks and store health the whole response.
+    Payload(serde_json::Value),
+    ProviderUsage {
+        tenant_id: String,
+        #[serde(default)]
+        range: UsageRange,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Start CSV files with a UTF-8 byte order mark, for Excel.
+    pub excel_bom: bool,
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ExportReport {
+    /// Where the file ended up, which `keep_both` may have renamed.
+    pub path: String,
+    pub rows: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Write `result_kind` rows from `source` to `dest_path`, inside a connected
+/// store.
+#[tauri::command]
+pub async fn export_results(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    result_kind: ResultKind,
+    source: ExportSource,
+    format: ExportFormat,
+    dest_path: String,
+    options: Option<ExportOptions>,
+) -> CommandResult<ExportReport> {
+    dry_run::check_writable(window.label(), None, "export_results")?;
+    let options = options.unwrap_or_default();
+    let dest = artifact_download::check_destination(&dest_path)?;
+    let stored = match source {
+        ExportSource::OperationId(id) => {
+            let (kind, output) = app.state::<Operations>().stored_result(id)?;
+            if Some(kind) != result_kind.operation_kind() {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!(
+                        "Operation {} is a {}, not {}",
+                        id,
+                        kind.name(),
+                        result_kind.name()
+                    ),
+                )
+                .with("operation_id", id)
+                .with("result_kind", result_kind.name()));
+            }
+            output
+        }
+        ExportSource::Payload(payload) => Arc::new(payload),
+        ExportSource::ProviderUsage { tenant_id, range } => {
+            if result_kind != ResultKind::ProviderUsage {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("The usage ledger has no {}", result_kind.name()),
+                )
+                .with("result_kind", result_kind.name()));
+            }
+            let entries = tauri::async_runtime::spawn_blocking(move || {
+                provider_usage::entries(&tenant_id, &range)
+            })
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))??;
+            Arc::new(serde_json::to_value(entries).map_err(|e| e.to_string())?)
+        }
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let rows = rows_of(result_kind, &stored)?;
+        export(result_kind, rows, format, &dest, &options)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Writing ────────────────────────────────────────────────────────────────
+
+fn export(
+    kind: ResultKind,
+    rows: &[serde_json::Value],
+    format: ExportFormat,
+    dest: &Path,
+    options: &ExportOptions,
+) -> CommandResult<ExportReport> {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let part = dest.with_file_name(format!(".{}.partial", name));
+    let written = File::create(&part)
+        .map_err(CommandError::from)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            match format {
+                ExportFormat::Csv => write_csv(&mut out, kind.columns(), rows, options.excel_bom)?,
+                ExportFormat::Json => write_json(&mut out, kind.columns(), rows)?,
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(())
+        })
+        .and_then(|()| artifact_download::place(&part, dest, options.on_conflict));
+    match written {
+        Ok(path) => Ok(ExportReport {
+            path: path.to_string_lossy().to_string(),
+            rows: rows.len(),
+        }),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
+fn rows_of(kind: ResultKind, value: &serde_json::Value) -> CommandResult<&[serde_json::Value]> {
+    let rows = match (kind, value) {
+        (_, serde_json::Value::Array(rows)) => Some(rows),
+        (ResultKind::Tasks, serde_json::Value::Object(scan)) => {
+            scan.get("tasks").and_then(|t| t.as_array())
+        }
+        (ResultKind::StoreHealth, serde_json::Value::Object(report)) => {
+            report.get("issues").and_then(|i| i.as_array())
+        }
+        _ => None,
+    };
+    let Some(rows) = rows else {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Expected a list of {}", kind.name()),
+        )
+        .with("result_kind", kind.name()));
+    };
+    if let Some(at) = rows.iter().position(|r| !r.is_object()) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Row {} is not an object", at),
+        )
+        .with("row", at));
+    }
+    Ok(rows)
+}
+
+fn write_csv(
+    out: &mut impl Write,
+    columns: &[&str],
+    rows: &[serde_json::Value],
+    bom: bool,
+) -> std::io::Result<()> {
+    if bom {
+        out.write_all("\u{feff}".as_bytes())?;
+    }
+    let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+    write!(out, "{}\r\n", header.join(","))?;
+    for row in rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|c| csv_field(&cell(row.get(*c))))
+            .collect();
+        write!(out, "{}\r\n", cells.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_json(
+    out: &mut impl Write,
+    columns: &[&str],
+    rows: &[serde_json::Value],
+) -> std::io::Result<()> {
+    use serde::ser::{SerializeSeq, Serializer};
+    let mut serializer = serde_json::Serializer::pretty(&mut *out);
+    let mut seq = serializer.serialize_seq(Some(rows.len()))?;
+    for row in rows {
+        let picked: serde_json::Map<String, serde_json::Value> = columns
+            .iter()
+            .map(|c| (c.to_string(), row.get(*c).cloned().unwrap_or_default()))
+            .collect();
+        seq.serialize_element(&picked)?;
+    }
+    seq.end()?;
+    out.write_all(b"\n")
+}
+
+fn cell(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Quoted when it holds a comma, quote, or line break, or has edge spaces.
+fn csv_field(value: &str) -> String {
+    let needs_quotes =
+        value.contains([',', '"', '\n', '\r']) || value.starts_with(' ') || value.ends_with(' ');
+    if needs_quotes {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn tasks() -> serde_json::Value {
+        serde_json::json!({
+            "tasks": [
+                {
+                    "path": "/v/Inbox.md",
+                    "relative_path": "Inbox.md",
+                    "line": 3,
+                    "description": "Call Acme, then \"sign\"",
+                    "status": " ",
+                    "completed": false,
+                    "due": "2026-10-20",
+                },
+                { "path": "/v/Done.md", "line": 1, "description": "Ship", "completed": true },
+            ],
+            "total": 2,
+            "files_scanned": 2,
+        })
+    }
+
+    #[test]
+    fn csv_quotes_fields_and_keeps_columns_stable() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let dest = home.join("Reports/tasks.csv");
+        let scan = tasks();
+        let rows = rows_of(ResultKind::Tasks, &scan).unwrap();
+        let options = ExportOptions {
+            excel_bom: true,
+            ..Default::default()
+        };
+        let report = export(ResultKind::Tasks, rows, ExportFormat::Csv, &dest, &options).unwrap();
+        assert_eq!(report.rows, 2);
+
+        let bytes = fs::read(&dest).unwrap();
+        assert!(bytes.starts_with(&[0xef, 0xbb, 0xbf]));
+        let text = String::from_utf8(bytes[3..].to_vec()).unwrap();
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert_eq!(lines[0], TASK_COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "/v/Inbox.md,Inbox.md,3,\"Call Acme, then \"\"sign\"\"\",\" \",false,2026-10-20,,,,"
+        );
+        assert_eq!(lines[2], "/v/Done.md,,1,Ship,,true,,,,,");
+        assert_eq!(lines[3], "");
+        // No partial left behind
+        assert!(!home.join("Reports/.tasks.csv.partial").exists());
+    }
+
+    #[test]
+    fn json_keeps_only_documented_columns() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let dest = home.join("Reports/vaults.json");
+        let vaults = serde_json::json!([
+            { "name": "Work", "path": "/v/Work", "note_count": 12, "count_stale": true },
+        ]);
+        let rows = rows_of(ResultKind::Vaults, &vaults).unwrap();
+        let options = ExportOptions::default();
+        export(
+            ResultKind::Vaults,
+            rows,
+            ExportFormat::Json,
+            &dest,
+            &options,
+        )
+        .unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&dest).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!([{ "name": "Work", "path": "/v/Work", "note_count": 12 }])
+        );
+
+        let err = export(
+            ResultKind::Vaults,
+            rows,
+            ExportFormat::Json,
+            &dest,
+            &options,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert!(!home.join("Reports/.vaults.json.partial").exists());
+    }
+
+    #[test]
+    fn usage_ledger_exports_its_entries() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let call = provider_usage::Call::start(
+            "acme",
+            "chatgpt",
+            Some("2026-10-01T09:00:00Z".into()),
+            "POST",
+            "conversation".into(),
+            12,
+            true,
+        );
+        call.add_response_bytes(340);
+        call.finish_with_status(200);
+
+        let entries = provider_usage::entries("acme", &UsageRange::default()).unwrap();
+        let ledger = serde_json::to_value(entries).unwrap();
+        let rows = rows_of(ResultKind::ProviderUsage, &ledger).unwrap();
+        let dest = home.join("Reports/usage.csv");
+        let options = ExportOptions::default();
+        export(
+            ResultKind::ProviderUsage,
+            rows,
+            ExportFormat::Csv,
+            &dest,
+            &options,
+        )
+        .unwrap();
+
+        let text = fs::read_to_string(&dest).unwrap();
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert_eq!(lines[0], USAGE_COLUMNS.join(","));
+        let cells: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(
+            cells[1..11],
+            [
+                "acme",
+                "chatgpt",
+                "2026-10-01T09:00:00Z",
+                "conversation",
+                "POST",
+                "true",
+                "200",
+                "completed",
+                "12",
+                "340"
+            ]
+        );
+    }
+
+    #[test]
+    fn payloads_must_be_lists_of_rows() {
+        let not_rows = serde_json::json!({ "tasks": "none" });
+        assert!(rows_of(ResultKind::Tasks, &not_rows).is_err());
+        // Only task scans and health reports are wrapped in an object
+        assert!(rows_of(ResultKind::Vaults, &tasks()).is_err());
+        let err = rows_of(ResultKind::Vaults, &serde_json::json!([1]))
+            .err()
+            .unwrap();
+        assert_eq!(err.params["row"], 0);
+    }
+}
@@ -0,0 +1,171 @@
+// File identity
+//
+// The orchestrator tracks artifacts by id instead of path, so a renamed file
+// keeps its history. A file's id is derived when a store scan first sees it:
+// - from its native identity, device and inode on Unix or volume serial and
+//   file index on Windows;
+// - else from its content hash, or its fingerprint when it's too large to
+//   hash (see `fingerprint`);
+// - else from its path.
+// The store snapshot keeps the id. From then on the id belongs to the file
+// and survives renames.
+//
+// A rescan pairs a file at a new path with one that disappeared:
+// - `exact`: same native identity, and the same size or mtime. Requiring one
+//   of those to match keeps a freed inode reused by a new file from passing
+//   as a rename.
+// - `content`: same size and the same content under one scheme, hash or
+//   fingerprint (see `fingerprint::same_content`). This catches moves across
+//   volumes, where the native identity changes.
+// Cloud placeholders are never opened. The first rescan after upgrading
+// hashes or fingerprints every file once.
+//
+// Native identities are reused once a file is deleted, so ids are only
+// guaranteed unique among the files that exist at the same time.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::stores::{self, SnapshotEntry};
+use crate::{fingerprint, fs_deadline, io_throttle, settings};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchConfidence {
+    /// Same file on disk.
+    Exact,
+    /// Same content; most likely the same file, moved across volumes.
+    Content,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FileIdentity {
+    pub id: String,
+    pub store_id: Option<String>,
+    /// The id comes from the store's last scan, so it has followed the file
+    /// through renames. Otherwise it was derived just now.
+    pub tracked: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn get_file_id(path: String, timeout_ms: Option<u64>) -> CommandResult<FileIdentity> {
+    let file = PathBuf::from(&path);
+    fs_deadline::run("get_file_id", &file.clone(), timeout_ms, move || {
+        if !file.is_file() {
+            return Err(CommandError::new(
+                ErrorCode::NotFound,
+                format!("File not found: {}", path),
+            )
+            .with("path", &path));
+        }
+        Ok(identify_path(&file))
+    })
+    .await
+}
+
+// ─── Identity ───────────────────────────────────────────────────────────────
+
+/// The id of `path`: the tracked one if a store scan has seen it.
+pub fn identify_path(path: &Path) -> FileIdentity {
+    if let Some((store, entry)) = stores::snapshot_entry(path) {
+        if let Some(id) = entry.id {
+            return FileIdentity {
+                id,
+                store_id: Some(store.id),
+                tracked: true,
+            };
+        }
+    }
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+    let native = native_identity(path);
+    let content = native
+        .is_none()
+        .then(|| {
+            let full_hash_max = settings::load_settings().limits.full_hash_max_bytes;
+            fingerprint::digest(path, size, full_hash_max).ok()
+        })
+        .flatten();
+    FileIdentity {
+        id: derive(
+            native.as_deref(),
+            content.as_ref().map(|c| c.value.as_str()),
+            &path.to_string_lossy(),
+        ),
+        store_id: None,
+        tracked: false,
+    }
+}
+
+/// Fill in the native identity and content hash or fingerprint of a freshly
+/// scanned entry; `full_hash_max` is `limits.full_hash_max_bytes`.
+pub fn identify(path: &Path, entry: &mut SnapshotEntry, full_hash_max: u64) {
+    if entry.placeholder {
+        return;
+    }
+    entry.native = native_identity(path);
+    entry.hash = None;
+    entry.fingerprint = None;
+    fingerprint::record(path, entry, full_hash_max);
+    io_throttle::file_done();
+}
+
+/// How surely `new` is the file `old` was before it moved.
+pub fn matches(old: &SnapshotEntry, new: &SnapshotEntry) -> Option<MatchConfidence> {
+    if old.native.is_some()
+        && old.native == new.native
+        && (old.size == new.size || old.modified == new.modified)
+    {
+        return Some(MatchConfidence::Exact);
+    }
+    // Every empty file hashes the same
+    if old.size > 0 && old.size == new.size && fingerprint::same_content(old, new) == Some(true) {
+        return Some(MatchConfidence::Content);
+    }
+    None
+}
+
+/// Id for a file first seen with this identity; `content` is its hash or
+/// fingerprint, and `key` the fallback source.
+pub fn derive(native: Option<&str>, content: Option<&str>, key: &str) -> String {
+    let (kind, source) = match (native, content) {
+        (Some(native), _) => ('n', native),
+        (None, Some(content)) => ('c', content),
+        (None, None) => ('p', key),
+    };
+    let digest = hex::encode(Sha256::digest(source.as_bytes()));
+    format!("{}{}", kind, &digest[..16])
+}
+
+#[cfg(unix)]
+fn native_identity(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path).ok()?;
+    Some(format!("{:x}:{:x}", metadata.dev(), metadata.ino()))
+}
+
+#[cfg(windows)]
+fn native_identity(path: &Path) -> Option<String> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let file = fs::File::open(path).ok()?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        return None;
+    }
+    let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+    Some(format!("{:x}:{:x}", info.dwVolumeSerialNumber, index))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn native_identity(_path: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,666 @@
+// Metadata queries over a store
+//
+// `query_files` answers questions like "Markdown notes modified in the last
+// 7 days, tagged #client-x, over 500 words" from a store's last scan. A
+// `FileQuery` is a filter, optionally with `any_of` groups. A file matches
+// when it passes the top-level filter and at least one group; within a
+// filter every condition must hold:
+// - `modified` / `created`: `after` and `before` (RFC 3339, inclusive) or
+//   `within_days`
+// - `extensions`: any of them, without the dot, in any case
+// - `tags`: all of them, without `#`, in any case; `project` also matches
+//   `project/alpha`
+// - `frontmatter`: each key equals the given value. A string, number, or
+//   bool compares to the field's text, and is also met by a list field that
+//   contains it; an array compares item by item. A key the note doesn't
+//   have doesn't match.
+// - `min_words` / `max_words`: inclusive
+// Tags, frontmatter, and word counts come from the note itself, read only
+// when a condition needs them, so they only ever match Markdown notes.
+// `created` only matches where the filesystem records it.
+//
+// A workspace's stores (see `workspaces`) can be queried together. Results
+// page like `list_whatsapp_chats`, sorted by path the way `list_directory`
+// sorts names (see `collation`): natural sort unless `natural_sort` is
+// false, or plain path order with `sort: legacy`. Each result
+// carries the values of the fields the query filtered on, and whether the
+// vault bookmarks it.
+
+use chrono::{DateTime, Duration, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::collation::{Collator, SortOrder};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::frontmatter::{self, FieldValue, Frontmatter};
+use crate::mime_map::MimeMap;
+use crate::stores::{self, ConnectedStore, Snapshot, SnapshotEntry, StoreStatus};
+use crate::workspaces::{self, Workspace};
+use crate::{note_stats, text_style};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct FileQuery {
+    #[serde(flatten)]
+    pub filter: FileFilter,
+    /// A file must also match at least one of these, when there are any.
+    pub any_of: Vec<FileFilter>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct FileFilter {
+    pub modified: Option<TimeRange>,
+    pub created: Option<TimeRange>,
+    pub extensions: Vec<String>,
+    pub tags: Vec<String>,
+    pub frontmatter: BTreeMap<String, serde_json::Value>,
+    pub min_words: Option<usize>,
+    pub max_words: Option<usize>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct TimeRange {
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub within_days: Option<u32>,
+}
+
+/// The values behind a match, for the fields the query used.
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct MatchedFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub frontmatter: BTreeMap<String, FieldValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<usize>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QueriedFile {
+    pub path: String,
+    pub relative_path: String,
+    /// Bookmarked in the vault, for callers that rank results.
+    pub bookmarked: bool,
+    pub fields: MatchedFields,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FilePage {
+    pub files: Vec<QueriedFile>,
+    pub total: usize,
+    /// Pass back as `offset` for the next page; `None` on the last one.
+    pub next_offset: Option<usize>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// A page of the files in `store_id`'s last scan that match `query`. With
+/// the tenant's `workspace_id` and no `store_id`, every reachable member
+/// store is searched; with both, `store_id` must be a member.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn query_files(
+    store_id: Option<String>,
+    query: FileQuery,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    tenant_id: Option<String>,
+    workspace_id: Option<String>,
+    natural_sort: Option<bool>,
+    sort: Option<SortOrder>,
+) -> CommandResult<FilePage> {
+    let collator = Collator::for_listing(sort, natural_sort, None);
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let workspace = workspaces::resolve(tenant_id.as_deref(), workspace_id.as_deref())?;
+    tauri::async_runtime::spawn_blocking(move || match (store_id, workspace) {
+        (Some(store_id), Some(workspace)) if !workspace.has_store(&store_id) => {
+            Err(CommandError::new(
+                ErrorCode::NotFound,
+                format!("Store {} is not in workspace {}", store_id, workspace.name),
+            )
+            .with("store_id", store_id)
+            .with("workspace_id", workspace.id))
+        }
+        (Some(store_id), _) => query_store(&store_id, &collator, &query, offset, limit),
+        (None, Some(workspace)) => query_workspace(&workspace, &collator, &query, offset, limit),
+        (None, None) => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Query a store_id or a workspace_id",
+        )),
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+pub fn query_store(
+    store_id: &str,
+    collator: &Collator,
+    query: &FileQuery,
+    offset: usize,
+    limit: usize,
+) -> CommandResult<FilePage> {
+    let store = reachable(stores::find_store(store_id)?)?;
+    let matched = matching(
+        Path::new(&store.path),
+        &stores::load_snapshot(&store.id),
+        query,
+    )?;
+    Ok(page(matched, collator, offset, limit))
+}
+
+/// `query_store` over the workspace's stores, sorted across them. Stores
+/// that aren't reachable are left out.
+pub fn query_workspace(
+    workspace: &Workspace,
+    collator: &Collator,
+    query: &FileQuery,
+    offset: usize,
+    limit: usize,
+) -> CommandResult<FilePage> {
+    let mut matched = Vec::new();
+    for store in workspace.stores() {
+        let Ok(store) = reachable(store) else {
+            continue;
+        };
+        matched.extend(matching(
+            Path::new(&store.path),
+            &stores::load_snapshot(&store.id),
+            query,
+        )?);
+    }
+    Ok(page(matched, collator, offset, limit))
+}
+
+fn reachable(store: ConnectedStore) -> CommandResult<ConnectedStore> {
+    if store.status == StoreStatus::NeedsReauthorization || !Path::new(&store.path).is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Store {} is not reachable at {}", store.id, store.path),
+        )
+        .with("store_id", &store.id)
+        .with("path", &store.path));
+    }
+    Ok(store)
+}
+
+fn page(
+    mut matched: Vec<QueriedFile>,
+    collator: &Collator,
+    offset: usize,
+    limit: usize,
+) -> FilePage {
+    if collator.is_legacy() {
+        matched.sort_by(|a, b| a.path.cmp(&b.path));
+    } else {
+        // Folder by folder, so `clients/Acme.md` stays with `clients/`
+        matched.sort_by_cached_key(|f| {
+            Path::new(&f.path)
+                .iter()
+                .map(|part| collator.key(&part.to_string_lossy()))
+                .collect::<Vec<_>>()
+        });
+    }
+    let total = matched.len();
+    FilePage {
+        files: matched.into_iter().skip(offset).take(limit).collect(),
+        total,
+        next_offset: (offset + limit < total).then_some(offset + limit),
+    }
+}
+
+/// The files in `snapshot`, a scan of `root`, that match `query`, in path
+/// order.
+pub fn matching(
+    root: &Path,
+    snapshot: &Snapshot,
+    query: &FileQuery,
+) -> CommandResult<Vec<QueriedFile>> {
+    let compiled = Compiled::new(query, Utc::now())?;
+    let mimes = MimeMap::load();
+    let entries: Vec<(&String, &SnapshotEntry)> = snapshot.iter().collect();
+    Ok(entries
+        .par_iter()
+        .filter_map(|&(key, entry)| {
+            let file = FileFacts::new(root, key, entry, &mimes);
+            compiled.matches(&file).then(|| QueriedFile {
+                path: root.join(key).to_string_lossy().to_string(),
+                relative_path: key.clone(),
+                bookmarked: entry.bookmarked,
+                fields: compiled.fields(&file),
+            })
+        })
+        .collect())
+}
+
+// ─── Compiling ──────────────────────────────────────────────────────────────
+
+struct Compiled {
+    filter: Filter,
+    any_of: Vec<Filter>,
+}
+
+struct Filter {
+    modified: Option<Range>,
+    created: Option<Range>,
+    extensions: Vec<String>,
+    tags: Vec<String>,
+    frontmatter: Vec<(String, serde_json::Value)>,
+    min_words: Option<usize>,
+    max_words: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct Range {
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+}
+
+impl Compiled {
+    fn new(query: &FileQuery, now: DateTime<Utc>) -> CommandResult<Self> {
+        Ok(Compiled {
+            filter: Filter::new(&query.filter, now)?,
+            any_of: query
+                .any_of
+                .iter()
+                .map(|f| Filter::new(f, now))
+                .collect::<CommandResult<_>>()?,
+        })
+    }
+
+    fn matches(&self, file: &FileFacts) -> bool {
+        self.filter.matches(file)
+            && (self.any_of.is_empty() || self.any_of.iter().any(|f| f.matches(file)))
+    }
+
+    fn filters(&self) -> impl Iterator<Item = &Filter> {
+        std::iter::once(&self.filter).chain(&self.any_of)
+    }
+
+    fn fields(&self, file: &FileFacts) -> MatchedFields {
+        let uses = |used: fn(&Filter) -> bool| self.filters().any(used);
+        let mut fields = MatchedFields::default();
+        if uses(|f| f.modified.is_some()) {
+            fields.modified = file.modified().map(|t| t.to_rfc3339());
+        }
+        if uses(|f| f.created.is_some()) {
+            fields.created = file.created().map(|t| t.to_rfc3339());
+        }
+        if uses(|f| !f.extensions.is_empty()) {
+            fields.extension = Some(file.extension());
+        }
+        if uses(|f| !f.tags.is_empty()) {
+            fields.tags = file.note().map(|n| n.tags.clone());
+        }
+        if uses(|f| f.min_words.is_some() || f.max_words.is_some()) {
+            fields.words = file.note().map(|n| n.words);
+        }
+        if let Some(note) = file.note().filter(|_| uses(|f| !f.frontmatter.is_empty())) {
+            for (key, _) in self.filters().flat_map(|f| &f.frontmatter) {
+                if let Some(value) = note.frontmatter.get(key) {
+                    fields.frontmatter.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        fields
+    }
+}
+
+impl Filter {
+    fn new(filter: &FileFilter, now: DateTime<Utc>) -> CommandResult<Self> {
+        Ok(Filter {
+            modified: filter
+                .modified
+                .as_ref()
+                .map(|r| Range::new(r, "modified", now))
+                .transpose()?,
+            created: filter
+                .created
+                .as_ref()
+                .map(|r| Range::new(r, "created", now))
+                .transpose()?,
+            extensions: filter
+                .extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect(),
+            tags: filter
+                .tags
+                .iter()
+                .map(|t| t.trim_start_matches('#').to_lowercase())
+                .collect(),
+            frontmatter: filter
+                .frontmatter
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            min_words: filter.min_words,
+            max_words: filter.max_words,
+        })
+    }
+
+    fn matches(&self, file: &FileFacts) -> bool {
+        if let Some(range) = self.modified {
+            if !file.modified().is_some_and(|t| range.contains(t)) {
+                return false;
+            }
+        }
+        if let Some(range) = self.created {
+            if !file.created().is_some_and(|t| range.contains(t)) {
+                return false;
+            }
+        }
+        if !self.extensions.is_empty() && !self.extensions.contains(&file.extension()) {
+            return false;
+        }
+        let needs_note = !self.tags.is_empty()
+            || !self.frontmatter.is_empty()
+            || self.min_words.is_some()
+            || self.max_words.is_some();
+        if !needs_note {
+            return true;
+        }
+        let Some(note) = file.note() else {
+            return false;
+        };
+        self.tags.iter().all(|wanted| {
+            note.tags.iter().any(|tag| {
+                let tag = tag.to_lowercase();
+                tag == *wanted
+                    || tag
+                        .strip_prefix(wanted.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        }) && self.frontmatter.iter().all(|(key, expected)| {
+            note.frontmatter
+                .get(key)
+                .is_some_and(|value| equals(value, expected))
+        }) && self.min_words.is_none_or(|min| note.words >= min)
+            && self.max_words.is_none_or(|max| note.words <= max)
+    }
+}
+
+impl Range {
+    fn new(range: &TimeRange, field: &str, now: DateTime<Utc>) -> CommandResult<Self> {
+        let parse = |value: &Option<String>| -> CommandResult<Option<DateTime<Utc>>> {
+            value
+                .as_deref()
+                .map(|raw| {
+                    DateTime::parse_from_rfc3339(raw)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|e| {
+                            CommandError::new(
+                                ErrorCode::InvalidInput,
+                                format!("{} isn't an RFC 3339 time: {}", raw, e),
+                            )
+                            .with("field", field)
+                            .with("value", raw)
+                        })
+                })
+                .transpose()
+        };
+        let mut after = parse(&range.after)?;
+        if let Some(days) = range.within_days {
+            let since = now - Duration::days(days as i64);
+            after = Some(after.map_or(since, |a| a.max(since)));
+        }
+        Ok(Range {
+            after,
+            before: parse(&range.before)?,
+        })
+    }
+
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.after.is_none_or(|a| time >= a) && self.before.is_none_or(|b| time <= b)
+    }
+}
+
+fn equals(value: &FieldValue, expected: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    let scalar_equals = |text: &str, expected: &Value| match expected {
+        Value::String(s) => text == s,
+        Value::Number(n) => text.parse::<f64>().ok() == n.as_f64(),
+        Value::Bool(b) => text.eq_ignore_ascii_case(if *b { "true" } else { "false" }),
+        Value::Null => matches!(text, "" | "~" | "null"),
+        _ => false,
+    };
+    match (value, expected) {
+        (FieldValue::List(items), Value::Array(wanted)) => {
+            items.len() == wanted.len()
+                && items.iter().zip(wanted).all(|(i, w)| scalar_equals(i, w))
+        }
+        (FieldValue::List(items), wanted) => items.iter().any(|i| scalar_equals(i, wanted)),
+        (FieldValue::Scalar(text), wanted) => scalar_equals(text, wanted),
+        (FieldValue::Nested(_), _) => false,
+    }
+}
+
+// ─── Files ──────────────────────────────────────────────────────────────────
+
+/// One file from the scan, reading what a condition asks for at most once.
+struct FileFacts<'a> {
+    root: &'a Path,
+    key: &'a str,
+    entry: &'a SnapshotEntry,
+    mimes: &'a MimeMap,
+    created: OnceLock<Option<DateTime<Utc>>>,
+    note: OnceLock<Option<NoteFacts>>,
+}
+
+struct NoteFacts {
+    tags: Vec<String>,
+    frontmatter: Frontmatter,
+    words: usize,
+}
+
+impl<'a> FileFacts<'a> {
+    fn new(root: &'a Path, key: &'a str, entry: &'a SnapshotEntry, mimes: &'a MimeMap) -> Self {
+        FileFacts {
+            root,
+            key,
+            entry,
+            mimes,
+            created: OnceLock::new(),
+            note: OnceLock::new(),
+        }
+    }
+
+    fn modified(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.entry.modified, 0)
+    }
+
+    fn created(&self) -> Option<DateTime<Utc>> {
+        *self.created.get_or_init(|| {
+            let created = fs::metadata(self.root.join(self.key))
+                .ok()?
+                .created()
+                .ok()?;
+            Some(DateTime::<Utc>::from(created))
+        })
+    }
+
+    fn extension(&self) -> String {
+        Path::new(self.key)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    /// `None` for anything but a Markdown note on disk, by `mime_map`.
+    fn note(&self) -> Option<&NoteFacts> {
+        self.note
+            .get_or_init(|| {
+                if !self.mimes.is_markdown(self.key) || self.entry.placeholder {
+                    return None;
+                }
+                let bytes = fs::read(self.root.join(self.key)).ok()?;
+                let text = text_style::decode(&bytes, &text_style::detect(&bytes));
+                let stats = note_stats::compute(&text);
+                Some(NoteFacts {
+                    tags: stats.tag_names,
+                    frontmatter: frontmatter::parse(&text).unwrap_or_default(),
+                    words: stats.words,
+                })
+            })
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn store(home: &TestHome) -> String {
+        home.write(
+            "Notes/clients/Acme.md",
+            "---\nstatus: active\nclient: [acme, globex]\npriority: 2\n---\n\
+             Kickoff notes #client-x/acme with enough words to count here.\n",
+        );
+        home.write(
+            "Notes/clients/Globex.md",
+            "---\nstatus: paused\n---\n#client-x short\n",
+        );
+        home.write("Notes/Journal.md", "#personal today was fine\n");
+        home.write("Notes/brief.pdf", "%PDF-1.7");
+        let root = home.join("Notes").to_string_lossy().to_string();
+        stores::register_store("Notes".to_string(), root, None)
+            .unwrap()
+            .id
+    }
+
+    fn english() -> Collator {
+        Collator::new(SortOrder::Locale, true, "en")
+    }
+
+    fn paths(page: &FilePage) -> Vec<&str> {
+        page.files
+            .iter()
+            .map(|f| f.relative_path.as_str())
+            .collect()
+    }
+
+    fn query(json: serde_json::Value) -> FileQuery {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn conditions_are_anded_with_or_groups() {
+        let home = TestHome::new();
+        let id = store(&home);
+
+        let tagged = query(serde_json::json!({ "tags": ["#Client-X"], "min_words": 5 }));
+        let page = query_store(&id, &english(), &tagged, 0, 10).unwrap();
+        assert_eq!(paths(&page), ["clients/Acme.md"]);
+        let fields = &page.files[0].fields;
+        assert_eq!(
+            fields.tags.as_deref(),
+            Some(&["client-x/acme".to_string()][..])
+        );
+        assert!(fields.words.unwrap() >= 5);
+        assert!(fields.modified.is_none());
+
+        let either = query(serde_json::json!({
+            "modified": { "within_days": 1 },
+            "any_of": [
+                { "frontmatter": { "status": "paused" } },
+                { "extensions": [".PDF"] },
+            ],
+        }));
+        let page = query_store(&id, &english(), &either, 0, 10).unwrap();
+        assert_eq!(paths(&page), ["brief.pdf", "clients/Globex.md"]);
+        assert!(page.files[0].fields.modified.is_some());
+        assert_eq!(page.files[0].fields.extension.as_deref(), Some("pdf"));
+
+        let old = query(serde_json::json!({ "modified": { "before": "2001-01-01T00:00:00Z" } }));
+        assert_eq!(query_store(&id, &english(), &old, 0, 10).unwrap().total, 0);
+    }
+
+    #[test]
+    fn frontmatter_values_compare_by_type_and_unknown_keys_never_match() {
+        let home = TestHome::new();
+        let id = store(&home);
+        let matching = |fm: serde_json::Value| {
+            let page = query_store(
+                &id,
+                &english(),
+                &query(serde_json::json!({ "frontmatter": fm })),
+                0,
+                10,
+            );
+            page.unwrap()
+                .files
+                .into_iter()
+                .map(|f| f.relative_path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            matching(serde_json::json!({ "priority": 2 })),
+            ["clients/Acme.md"]
+        );
+        assert_eq!(
+            matching(serde_json::json!({ "client": "globex" })),
+            ["clients/Acme.md"]
+        );
+        assert_eq!(
+            matching(serde_json::json!({ "client": ["acme", "globex"] })),
+            ["clients/Acme.md"]
+        );
+        assert!(matching(serde_json::json!({ "owner": "sam" })).is_empty());
+
+        let bad = query(serde_json::json!({ "created": { "after": "last tuesday" } }));
+        let err = query_store(&id, &english(), &bad, 0, 10).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert_eq!(err.params["field"], "created");
+    }
+
+    #[test]
+    fn pages_through_matches_in_name_order() {
+        let home = TestHome::new();
+        let id = store(&home);
+        let all = FileQuery::default();
+        let first = query_store(&id, &english(), &all, 0, 3).unwrap();
+        assert_eq!(first.total, 4);
+        assert_eq!(
+            paths(&first),
+            ["brief.pdf", "clients/Acme.md", "clients/Globex.md"]
+        );
+        assert_eq!(first.next_offset, Some(3));
+        let last = query_store(&id, &english(), &all, 3, 3).unwrap();
+        assert_eq!(paths(&last), ["Journal.md"]);
+        assert_eq!(last.next_offset, None);
+    }
+
+    #[test]
+    fn legacy_sort_keeps_path_order() {
+        let home = TestHome::new();
+        let id = store(&home);
+        let legacy = Collator::new(SortOrder::Legacy, true, "en");
+        let page = query_store(&id, &legacy, &FileQuery::default(), 0, 10).unwrap();
+        assert_eq!(
+            paths(&page),
+            [
+                "Journal.md",
+                "brief.pdf",
+                "clients/Acme.md",
+                "clients/Globex.md"
+            ]
+        );
+    }
+}
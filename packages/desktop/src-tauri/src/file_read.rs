@@ -0,0 +1,152 @@
+// Size-limited file reads
+//
+// Read and hash limits come from `settings.limits` so they can be tuned per
+// device. Exceeding one fails with `TooLarge`, whose params carry the size,
+// the limit, and the setting name for the UI to explain. Anything past the
+// text-read limit can still be read in `max_preview_bytes` ranges via
+// `read_text_range`, which is also the path `read_text_file` takes when
+// forced.
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{fs_deadline, io_throttle, settings};
+
+#[derive(Serialize, Clone)]
+pub struct TextRange {
+    pub content: String,
+    pub offset: u64,
+    /// Where the next range starts; adjusted to a UTF-8 character boundary.
+    pub next_offset: u64,
+    pub total_bytes: u64,
+    pub eof: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Read up to `length` bytes of text from `offset`, capped at
+/// `limits.max_preview_bytes`.
+#[tauri::command]
+pub async fn read_text_range(
+    path: String,
+    offset: u64,
+    length: Option<u64>,
+    timeout_ms: Option<u64>,
+) -> CommandResult<TextRange> {
+    let cap = settings::load_settings().limits.max_preview_bytes;
+    let length = length.unwrap_or(cap).min(cap);
+    fs_deadline::run(
+        "read_text_range",
+        Path::new(&path.clone()),
+        timeout_ms,
+        move || {
+            let mut file = open(Path::new(&path))?;
+            read_range(&mut file, &path, offset, length)
+        },
+    )
+    .await
+}
+
+// ─── Reading ────────────────────────────────────────────────────────────────
+
+/// Read a whole text file range by range, for files past the text-read limit.
+pub fn read_text_streaming(path: &Path) -> CommandResult<String> {
+    let chunk = settings::load_settings().limits.max_preview_bytes.max(4);
+    let label = path.to_string_lossy();
+    let mut file = open(path)?;
+    let mut content = String::new();
+    let mut offset = 0;
+    loop {
+        let range = read_range(&mut file, &label, offset, chunk)?;
+        content.push_str(&range.content);
+        if range.eof {
+            return Ok(content);
+        }
+        offset = range.next_offset;
+    }
+}
+
+/// Stream a file through SHA-256, enforcing `limits.max_hash_bytes`.
+pub fn hash(path: &Path) -> CommandResult<String> {
+    use sha2::{Digest, Sha256};
+
+    let limit = settings::load_settings().limits.max_hash_bytes;
+    let file = open(path)?;
+    let size = file.metadata()?.len();
+    if limit > 0 && size > limit {
+        return Err(too_large(path, size, limit, "max_hash_bytes"));
+    }
+    let mut hasher = Sha256::new();
+    io::copy(&mut io_throttle::Paced(file), &mut hasher)?;
+    io_throttle::file_done();
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn too_large(path: &Path, size: u64, limit: u64, setting: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::TooLarge,
+        format!(
+            "{} is {} bytes, over the {} byte limit",
+            path.display(),
+            size,
+            limit
+        ),
+    )
+    .with("path", path.to_string_lossy())
+    .with("size", size)
+    .with("limit", limit)
+    .with("setting", setting)
+}
+
+fn open(path: &Path) -> CommandResult<File> {
+    File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => CommandError::new(
+            ErrorCode::NotFound,
+            format!("File not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()),
+        _ => e.into(),
+    })
+}
+
+fn read_range(file: &mut File, path: &str, offset: u64, length: u64) -> CommandResult<TextRange> {
+    let total_bytes = file.metadata()?.len();
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::with_capacity(length.min(total_bytes) as usize);
+    file.take(length).read_to_end(&mut bytes)?;
+    let eof = offset + bytes.len() as u64 >= total_bytes;
+
+    // An offset inside a character starts at the next one
+    let skip = bytes
+        .iter()
+        .take(3)
+        .take_while(|b| *b & 0xC0 == 0x80)
+        .count();
+    bytes.drain(..skip);
+    let offset = offset + skip as u64;
+
+    // A range may end mid-character; leave the partial bytes for the next one
+    let valid = match std::str::from_utf8(&bytes) {
+        Ok(_) => bytes.len(),
+        Err(e) if e.error_len().is_none() && !eof => e.valid_up_to(),
+        Err(_) => {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("{} is not valid UTF-8 text", path),
+            )
+            .with("path", path))
+        }
+    };
+    bytes.truncate(valid);
+    let next_offset = offset + valid as u64;
+    Ok(TextRange {
+        content: String::from_utf8(bytes).map_err(|e| e.to_string())?,
+        offset,
+        next_offset,
+        total_bytes,
+        eof,
+    })
+}
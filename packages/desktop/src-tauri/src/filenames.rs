@@ -0,0 +1,427 @@
+// Filenames
+//
+// Agents suggest names like `Q3: Plan / Review?`, which Windows refuses and
+// macOS Finder shows with the colon turned into a slash. `sanitize_filename`
+// turns any suggested name into one the target filesystem accepts:
+// - characters it reserves, and control characters, become `_`
+// - leading dots (hidden files) and trailing dots and spaces are trimmed
+// - Windows device names (`CON`, `NUL`, `COM1`…) get a `_` appended, with or
+//   without an extension
+// - the name is cut to `max_bytes` of UTF-8 on a character boundary, keeping
+//   the extension
+// - with `ascii`, accented letters and typographic punctuation are
+//   transliterated (`é` → `e`, `–` → `-`) and anything else becomes `_`
+//
+// The default target is `portable`, the union of every platform's rules, since
+// stores are synced between machines. Every change made is listed so the UI
+// can say "saved as …". `Sanitized::unique_in` then adds ` (2)`, ` (3)`… when
+// the name is taken in the destination folder.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::CommandResult;
+
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const BLANK_NAME: &str = "untitled";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// Valid everywhere.
+    #[default]
+    Portable,
+    Windows,
+    Macos,
+    Linux,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SanitizeOptions {
+    pub target: Target,
+    /// Most filesystems allow 255 bytes.
+    pub max_bytes: usize,
+    pub ascii: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            target: Target::Portable,
+            max_bytes: 255,
+            ascii: false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NameChange {
+    /// Reserved or control characters, each replaced with `_`.
+    Replaced {
+        characters: Vec<char>,
+    },
+    Transliterated {
+        characters: Vec<char>,
+    },
+    /// Leading dots, or trailing dots and spaces.
+    Trimmed,
+    ReservedName {
+        name: String,
+    },
+    /// Cut down from `bytes`.
+    Truncated {
+        bytes: usize,
+    },
+    /// Nothing usable was left; named `untitled`.
+    Blank,
+    /// The name was taken; ` (number)` was added.
+    Numbered {
+        number: u32,
+    },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Sanitized {
+    pub name: String,
+    /// Empty when `name` is what was asked for.
+    pub changes: Vec<NameChange>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// What `name` would be saved as, in `folder` when given.
+#[tauri::command]
+pub fn preview_filename(
+    name: String,
+    folder: Option<String>,
+    options: Option<SanitizeOptions>,
+) -> CommandResult<Sanitized> {
+    let options = options.unwrap_or_default();
+    let mut sanitized = sanitize_filename(&name, &options);
+    if let Some(folder) = folder {
+        sanitized.unique_in(Path::new(&folder), &options);
+    }
+    Ok(sanitized)
+}
+
+// ─── Sanitizing ─────────────────────────────────────────────────────────────
+
+pub fn sanitize_filename(name: &str, options: &SanitizeOptions) -> Sanitized {
+    let mut changes = Vec::new();
+
+    let mut replaced = Vec::new();
+    let mut transliterated = Vec::new();
+    let mut cleaned = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_control() || reserved_char(options.target, c) {
+            replaced.push(c);
+            cleaned.push('_');
+        } else if options.ascii && !c.is_ascii() {
+            match transliterate(c) {
+                // `“` → `"` is no help where `"` is reserved
+                Some(ascii) if !ascii.chars().any(|a| reserved_char(options.target, a)) => {
+                    transliterated.push(c);
+                    cleaned.push_str(ascii);
+                }
+                _ => {
+                    replaced.push(c);
+                    cleaned.push('_');
+                }
+            }
+        } else {
+            cleaned.push(c);
+        }
+    }
+    if !replaced.is_empty() {
+        changes.push(NameChange::Replaced {
+            characters: replaced,
+        });
+    }
+    if !transliterated.is_empty() {
+        changes.push(NameChange::Transliterated {
+            characters: transliterated,
+        });
+    }
+
+    let trimmed = cleaned
+        .trim()
+        .trim_end_matches(['.', ' '])
+        .trim_start_matches(['.', ' ']);
+    // Surrounding whitespace alone isn't worth mentioning
+    if trimmed != cleaned.trim() {
+        changes.push(NameChange::Trimmed);
+    }
+    if trimmed.is_empty() {
+        changes.push(NameChange::Blank);
+        return Sanitized {
+            name: BLANK_NAME.to_string(),
+            changes,
+        };
+    }
+
+    let (stem, ext) = split_extension(trimmed);
+    let mut stem = stem.to_string();
+    if options.target != Target::Linux
+        && options.target != Target::Macos
+        && RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(&stem))
+    {
+        changes.push(NameChange::ReservedName { name: stem.clone() });
+        stem.push('_');
+    }
+
+    let name = fit(&stem, ext, options.max_bytes);
+    let full = stem.len() + ext.len();
+    if name.len() < full {
+        changes.push(NameChange::Truncated { bytes: full });
+    }
+    Sanitized { name, changes }
+}
+
+impl Sanitized {
+    /// Number the name if it's taken in `folder`, still within `max_bytes`,
+    /// and return the path to write.
+    pub fn unique_in(&mut self, folder: &Path, options: &SanitizeOptions) -> PathBuf {
+        let candidate = folder.join(&self.name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        let (stem, ext) = split_extension(&self.name);
+        let (stem, ext) = (stem.to_string(), ext.to_string());
+        for number in 2.. {
+            let suffix = format!(" ({}){}", number, ext);
+            let name = fit(&stem, &suffix, options.max_bytes);
+            let candidate = folder.join(&name);
+            if !candidate.exists() {
+                self.name = name;
+                self.changes.push(NameChange::Numbered { number });
+                return candidate;
+            }
+        }
+        unreachable!()
+    }
+}
+
+/// `name` in `folder`, with ` (2)`, ` (3)`… added if it's taken.
+pub fn unique_path(folder: &Path, name: &str) -> PathBuf {
+    let mut sanitized = Sanitized {
+        name: name.to_string(),
+        changes: Vec::new(),
+    };
+    let options = SanitizeOptions {
+        max_bytes: usize::MAX,
+        ..Default::default()
+    };
+    sanitized.unique_in(folder, &options)
+}
+
+fn reserved_char(target: Target, c: char) -> bool {
+    match target {
+        Target::Portable | Target::Windows => {
+            matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+        }
+        // Finder displays `:` as `/`, and older APIs treat it as a separator
+        Target::Macos => matches!(c, '/' | ':'),
+        Target::Linux => c == '/',
+    }
+}
+
+/// `"report.final.pdf"` → `("report.final", ".pdf")`. Dotfiles have no
+/// extension.
+pub(crate) fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    }
+}
+
+/// `stem` + `ext` in at most `max_bytes`, taking from the end of the stem on
+/// a character boundary. An extension that doesn't fit on its own is cut too.
+fn fit(stem: &str, ext: &str, max_bytes: usize) -> String {
+    if ext.len() >= max_bytes {
+        let mut name = format!("{}{}", stem, ext);
+        let mut end = max_bytes.min(name.len());
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+        return name;
+    }
+    let mut end = (max_bytes - ext.len()).min(stem.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Cutting can leave a trailing space or dot before the extension
+    let stem = stem[..end].trim_end_matches(['.', ' ']);
+    format!("{}{}", stem, ext)
+}
+
+/// The ASCII spelling of common Latin letters and punctuation.
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'ď' | 'đ' | 'ð' => "d",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'Ğ' => "G",
+        'ğ' => "g",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'İ' => "I",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'Ł' | 'Ľ' => "L",
+        'ł' | 'ľ' => "l",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ř' => "R",
+        'ř' => "r",
+        'Ś' | 'Š' | 'Ş' => "S",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'Ť' | 'Ţ' => "T",
+        'ť' | 'ţ' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' => "U",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'Ý' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        '‘' | '’' | '‚' | '′' => "'",
+        '“' | '”' | '„' | '″' => "\"",
+        '–' | '—' | '‐' | '−' => "-",
+        '…' => "...",
+        '\u{a0}' | '\u{2009}' | '\u{202f}' => " ",
+        '€' => "EUR",
+        '×' => "x",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn portable(name: &str) -> Sanitized {
+        sanitize_filename(name, &SanitizeOptions::default())
+    }
+
+    #[test]
+    fn agent_titles_become_portable_names() {
+        let s = portable("Q3: Plan / Review?.md");
+        assert_eq!(s.name, "Q3_ Plan _ Review_.md");
+        assert_eq!(
+            s.changes,
+            vec![NameChange::Replaced {
+                characters: vec![':', '/', '?']
+            }]
+        );
+
+        let s = portable("..hidden notes. . ");
+        assert_eq!(s.name, "hidden notes");
+        assert_eq!(s.changes, vec![NameChange::Trimmed]);
+
+        // Device names are reserved with any extension
+        let s = portable("nul.txt");
+        assert_eq!(s.name, "nul_.txt");
+        assert_eq!(
+            s.changes,
+            vec![NameChange::ReservedName { name: "nul".into() }]
+        );
+
+        let s = portable(" . . ");
+        assert_eq!(s.name, "untitled");
+        assert!(s.changes.contains(&NameChange::Blank));
+
+        assert!(portable("Plan.md").changes.is_empty());
+    }
+
+    #[test]
+    fn targets_reserve_their_own_characters() {
+        let linux = SanitizeOptions {
+            target: Target::Linux,
+            ..Default::default()
+        };
+        let s = sanitize_filename("CON: a|b?", &linux);
+        assert_eq!(s.name, "CON: a|b?");
+        assert!(s.changes.is_empty());
+
+        let macos = SanitizeOptions {
+            target: Target::Macos,
+            ..Default::default()
+        };
+        assert_eq!(sanitize_filename("a:b|c", &macos).name, "a_b|c");
+    }
+
+    #[test]
+    fn long_names_are_cut_on_character_boundaries() {
+        let options = SanitizeOptions {
+            max_bytes: 12,
+            ..Default::default()
+        };
+        // "é" is two bytes; the cut can't land inside one
+        let s = sanitize_filename("ééééééé.md", &options);
+        assert_eq!(s.name, "éééé.md");
+        assert_eq!(s.changes, vec![NameChange::Truncated { bytes: 17 }]);
+        assert!(s.name.len() <= 12);
+    }
+
+    #[test]
+    fn ascii_transliterates_what_it_can() {
+        let options = SanitizeOptions {
+            ascii: true,
+            ..Default::default()
+        };
+        let s = sanitize_filename("Café “Müller” – 日本.md", &options);
+        assert_eq!(s.name, "Cafe _Muller_ - __.md");
+        assert_eq!(
+            s.changes,
+            vec![
+                NameChange::Replaced {
+                    characters: vec!['“', '”', '日', '本']
+                },
+                NameChange::Transliterated {
+                    characters: vec!['é', 'ü', '–']
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn collisions_are_numbered() {
+        let home = TestHome::new();
+        home.write("Inbox/Plan_.md", "");
+        home.write("Inbox/Plan_ (2).md", "");
+        let options = SanitizeOptions::default();
+        let mut s = sanitize_filename("Plan?.md", &options);
+        let path = s.unique_in(&home.join("Inbox"), &options);
+        assert_eq!(path, home.join("Inbox/Plan_ (3).md"));
+        assert_eq!(s.name, "Plan_ (3).md");
+        assert_eq!(s.changes.last(), Some(&NameChange::Numbered { number: 3 }));
+
+        // The suffix stays within the limit
+        let tight = SanitizeOptions {
+            max_bytes: 10,
+            ..Default::default()
+        };
+        let mut s = sanitize_filename("Plan_.md", &tight);
+        assert_eq!(
+            s.unique_in(&home.join("Inbox"), &tight),
+            home.join("Inbox/Pla (2).md")
+        );
+    }
+}
@@ -0,0 +1,274 @@
+// Content fingerprints
+//
+// Snapshots record what each file holds so a rescan can pair a moved file
+// with the one that disappeared (see `file_ids`). Hashing a multi-gigabyte
+// video end to end just for that costs more than it tells, so the scheme
+// depends on size:
+// - up to `limits.full_hash_max_bytes`: `hash`, the SHA-256 of every byte
+// - above it: `fingerprint`, the size plus the SHA-256 of `WINDOW_BYTES` at
+//   the head, middle, and tail. Equal fingerprints almost always mean equal
+//   files, but an edit between the windows goes unseen, which is why it's
+//   never called a hash.
+// The field an entry carries says which scheme produced it, and
+// `same_content` only compares two entries under the same scheme: hashes
+// when both have one, fingerprints when neither does, and otherwise not at
+// all. Entries keep their scheme until the file changes, so a new threshold
+// takes effect file by file.
+//
+// Two files in one scan with the same fingerprint are copies or a
+// collision. `confirm_collisions` hashes them in full, and from then on they
+// only compare by hash. `hash_file`, which artifact versioning uses, always
+// hashes in full; `get_content_digest` gives either scheme on demand, for
+// pre-passes such as finding duplicates.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::stores::{Snapshot, SnapshotEntry};
+use crate::{fs_deadline, io_throttle, settings};
+
+const WINDOW_BYTES: u64 = 64 * 1024;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Scheme {
+    /// SHA-256 of every byte.
+    Hash,
+    /// Size and sampled windows.
+    Fingerprint,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ContentDigest {
+    pub scheme: Scheme,
+    pub value: String,
+    pub size: u64,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// The digest a snapshot would record for `path`, or the full hash when
+/// `full` is set.
+#[tauri::command]
+pub async fn get_content_digest(
+    path: String,
+    full: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> CommandResult<ContentDigest> {
+    let file = PathBuf::from(&path);
+    let threshold = match full {
+        Some(true) => 0,
+        _ => settings::load_settings().limits.full_hash_max_bytes,
+    };
+    fs_deadline::run("get_content_digest", &file.clone(), timeout_ms, move || {
+        if !file.is_file() {
+            return Err(
+                CommandError::new(ErrorCode::NotFound, format!("Not a file: {}", path))
+                    .with("path", &path),
+            );
+        }
+        let size = fs::metadata(&file)?.len();
+        Ok(digest(&file, size, threshold)?)
+    })
+    .await
+}
+
+// ─── Digests ────────────────────────────────────────────────────────────────
+
+/// Hash `path` in full up to `full_hash_max` bytes (0: always), else
+/// fingerprint it.
+pub fn digest(path: &Path, size: u64, full_hash_max: u64) -> io::Result<ContentDigest> {
+    let (scheme, value) = match full_hash_max == 0 || size <= full_hash_max {
+        true => (Scheme::Hash, full_hash(path)?),
+        false => (Scheme::Fingerprint, sampled(path, size)?),
+    };
+    Ok(ContentDigest {
+        scheme,
+        value,
+        size,
+    })
+}
+
+/// Set `entry`'s `hash` or `fingerprint`, whichever its size calls for.
+pub fn record(path: &Path, entry: &mut SnapshotEntry, full_hash_max: u64) {
+    let Ok(digest) = digest(path, entry.size, full_hash_max) else {
+        return;
+    };
+    match digest.scheme {
+        Scheme::Hash => entry.hash = Some(digest.value),
+        Scheme::Fingerprint => entry.fingerprint = Some(digest.value),
+    }
+}
+
+fn full_hash(path: &Path) -> io::Result<String> {
+    let mut file = io_throttle::Paced(fs::File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// `<size>:<SHA-256 of the head, middle, and tail windows>`.
+fn sampled(path: &Path, size: u64) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut window = Vec::with_capacity(WINDOW_BYTES as usize);
+    let middle = (size / 2).saturating_sub(WINDOW_BYTES / 2);
+    for offset in [0, middle, size.saturating_sub(WINDOW_BYTES)] {
+        file.seek(SeekFrom::Start(offset))?;
+        window.clear();
+        (&mut file).take(WINDOW_BYTES).read_to_end(&mut window)?;
+        hasher.update(&window);
+    }
+    Ok(format!("{}:{}", size, hex::encode(hasher.finalize())))
+}
+
+// ─── Comparing ──────────────────────────────────────────────────────────────
+
+/// Whether `a` and `b` hold the same bytes; `None` when they weren't
+/// recorded under the same scheme.
+pub fn same_content(a: &SnapshotEntry, b: &SnapshotEntry) -> Option<bool> {
+    match (&a.hash, &b.hash, &a.fingerprint, &b.fingerprint) {
+        (Some(a), Some(b), _, _) => Some(a == b),
+        (None, None, Some(a), Some(b)) => Some(a == b),
+        _ => None,
+    }
+}
+
+/// Hash in full the fingerprinted files of `snapshot` that share a
+/// fingerprint with another, unless they already are.
+pub fn confirm_collisions(root: &Path, snapshot: &mut Snapshot) {
+    let mut by_fingerprint: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, entry) in snapshot.iter() {
+        if let Some(fingerprint) = &entry.fingerprint {
+            by_fingerprint
+                .entry(fingerprint.clone())
+                .or_default()
+                .push(key.clone());
+        }
+    }
+    for keys in by_fingerprint.into_values().filter(|k| k.len() > 1) {
+        log::info!(
+            "[fingerprint] {} files share a fingerprint; hashing them in full",
+            keys.len()
+        );
+        for key in keys {
+            let entry = snapshot.get_mut(&key).expect("key is in the snapshot");
+            if entry.hash.is_some() {
+                continue;
+            }
+            match full_hash(&root.join(&key)) {
+                Ok(hash) => entry.hash = Some(hash),
+                Err(e) => log::warn!("[fingerprint] couldn't hash {}: {}", key, e),
+            }
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn entry(size: u64) -> SnapshotEntry {
+        SnapshotEntry {
+            size,
+            modified: 0,
+            placeholder: false,
+            id: None,
+            native: None,
+            hash: None,
+            fingerprint: None,
+            bookmarked: false,
+        }
+    }
+
+    #[test]
+    fn large_files_are_fingerprinted_and_small_ones_hashed() {
+        let home = TestHome::new();
+        let small = home.write("small.txt", "hello");
+        let big = home.join("big.bin");
+        fs::write(&big, vec![7u8; 4 * WINDOW_BYTES as usize]).unwrap();
+
+        let d = digest(&small, 5, 1024).unwrap();
+        assert_eq!(d.scheme, Scheme::Hash);
+        assert_eq!(
+            d.value,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        let size = 4 * WINDOW_BYTES;
+        let d = digest(&big, size, 1024).unwrap();
+        assert_eq!(d.scheme, Scheme::Fingerprint);
+        assert!(d.value.starts_with(&format!("{}:", size)));
+        assert_eq!(digest(&big, size, 0).unwrap().scheme, Scheme::Hash);
+    }
+
+    #[test]
+    fn fingerprints_see_the_windows_but_not_between_them() {
+        let home = TestHome::new();
+        let size = 8 * WINDOW_BYTES as usize;
+        let path = home.join("video.mp4");
+        let mut bytes = vec![0u8; size];
+        fs::write(&path, &bytes).unwrap();
+        let before = sampled(&path, size as u64).unwrap();
+
+        bytes[WINDOW_BYTES as usize + 10] = 1;
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(sampled(&path, size as u64).unwrap(), before);
+        bytes[size - 1] = 1;
+        fs::write(&path, &bytes).unwrap();
+        assert_ne!(sampled(&path, size as u64).unwrap(), before);
+    }
+
+    #[test]
+    fn schemes_are_never_compared_with_each_other() {
+        let (mut a, mut b) = (entry(10), entry(10));
+        a.hash = Some("x".into());
+        b.fingerprint = Some("x".into());
+        assert_eq!(same_content(&a, &b), None);
+        b.hash = Some("x".into());
+        assert_eq!(same_content(&a, &b), Some(true));
+        a.hash = None;
+        a.fingerprint = Some("x".into());
+        // `b` was confirmed by a full hash; `a` only has its fingerprint
+        assert_eq!(same_content(&a, &b), None);
+        b.hash = None;
+        assert_eq!(same_content(&a, &b), Some(true));
+    }
+
+    #[test]
+    fn shared_fingerprints_are_confirmed_with_full_hashes() {
+        let home = TestHome::new();
+        let size = 4 * WINDOW_BYTES as usize;
+        let mut one = vec![0u8; size];
+        fs::write(home.join("one.mov"), &one).unwrap();
+        // Past the head window, before the middle one
+        one[WINDOW_BYTES as usize + 10] = 9;
+        fs::write(home.join("two.mov"), &one).unwrap();
+        fs::write(home.join("three.mov"), vec![1u8; size]).unwrap();
+
+        let mut snapshot = Snapshot::new();
+        for name in ["one.mov", "two.mov", "three.mov"] {
+            let mut e = entry(size as u64);
+            record(&home.join(name), &mut e, 1024);
+            snapshot.insert(name.to_string(), e);
+        }
+        assert_eq!(
+            snapshot["one.mov"].fingerprint,
+            snapshot["two.mov"].fingerprint
+        );
+        confirm_collisions(home.path(), &mut snapshot);
+        assert_ne!(snapshot["one.mov"].hash, snapshot["two.mov"].hash);
+        assert_eq!(
+            same_content(&snapshot["one.mov"], &snapshot["two.mov"]),
+            Some(false)
+        );
+        assert_eq!(snapshot["three.mov"].hash, None);
+    }
+}
@@ -0,0 +1,744 @@
+// Note frontmatter
+//
+// The YAML block between `---` lines at the top of a note, read field by
+// field without a YAML library. Top-level keys are understood in the forms
+// notes use:
+// - `key: value`, with the value unquoted from `"..."` or `'...'` and an
+//   unquoted value's ` # comment` dropped
+// - `key: [a, b]`
+// - `key:` followed by indented `- a` items
+// Anything else under a key (nested maps, block scalars) is kept as
+// `Nested` and never equals a value. Comment lines are skipped. The block
+// must end with `---` or `...`; a note without one has no frontmatter.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ops::Range;
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Scalar(String),
+    List(Vec<String>),
+    /// A nested map or block scalar, as its raw lines.
+    Nested(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Field {
+    pub key: String,
+    pub value: FieldValue,
+    /// Lines of the note, 0-based, from the key through the last line of its
+    /// value.
+    pub lines: Range<usize>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Frontmatter {
+    /// In file order. A repeated key keeps its first value for `get`.
+    pub fields: Vec<Field>,
+    /// Line of the closing `---`.
+    pub end: usize,
+}
+
+impl Frontmatter {
+    pub fn get(&self, key: &str) -> Option<&FieldValue> {
+        self.field(key).map(|f| &f.value)
+    }
+
+    pub fn field(&self, key: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.key == key)
+    }
+}
+
+/// The frontmatter of `text`, if it has any.
+pub fn parse(text: &str) -> Option<Frontmatter> {
+    let mut lines = text.lines();
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+    let mut block = Vec::new();
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some(parse_block(&block));
+        }
+        block.push(line);
+    }
+    None
+}
+
+/// What's wrong with the frontmatter of `text`, if it has a block that
+/// `parse` can't read in full: one that's never closed, a line that's
+/// neither a key nor part of one, or a key given twice.
+pub fn block_problem(text: &str) -> Option<String> {
+    if !opens_block(text) {
+        return None;
+    }
+    let mut lines = text.lines().enumerate().skip(1);
+    let mut keys: Vec<&str> = Vec::new();
+    for (n, line) in lines.by_ref() {
+        if matches!(line.trim_end(), "---" | "...") {
+            return None;
+        }
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if line.starts_with([' ', '\t', '-']) {
+            if keys.is_empty() {
+                return Some(format!("Line {} belongs to no key", n + 1));
+            }
+            continue;
+        }
+        let Some((key, _)) = split_key(line) else {
+            return Some(format!("Line {} isn't `key: value`", n + 1));
+        };
+        if keys.contains(&key) {
+            return Some(format!("`{}` is set twice", key));
+        }
+        keys.push(key);
+    }
+    Some("The frontmatter block is never closed".to_string())
+}
+
+/// Whether `text` opens a frontmatter block, closed or not.
+fn opens_block(text: &str) -> bool {
+    text.lines().next().is_some_and(|l| l.trim_end() == "---")
+}
+
+/// `lines` are the block's, so line `i` is line `i + 1` of the note.
+fn parse_block(lines: &[&str]) -> Frontmatter {
+    let mut fields: Vec<Field> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if line.starts_with([' ', '\t', '-']) {
+            // Belongs to no key we could read
+            continue;
+        }
+        let Some((key, rest)) = split_key(line) else {
+            continue;
+        };
+        let start = i - 1;
+        let rest = rest.trim();
+        let value = if rest.is_empty() {
+            while i < lines.len()
+                && (lines[i].trim().is_empty() || lines[i].starts_with([' ', '\t', '-']))
+            {
+                i += 1;
+            }
+            let nested: Vec<&str> = lines[start + 1..i]
+                .iter()
+                .copied()
+                .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+                .collect();
+            if nested
+                .iter()
+                .all(|l| l.trim_start().starts_with("- ") || l.trim() == "-")
+            {
+                FieldValue::List(
+                    nested
+                        .iter()
+                        .map(|l| scalar(l.trim_start().trim_start_matches('-')))
+                        .filter(|item| !item.is_empty())
+                        .collect(),
+                )
+            } else {
+                FieldValue::Nested(nested.join("\n"))
+            }
+        } else if let Some(items) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            FieldValue::List(
+                items
+                    .split(',')
+                    .map(scalar)
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            )
+        } else if rest.starts_with(['|', '>']) {
+            while i < lines.len()
+                && (lines[i].trim().is_empty() || lines[i].starts_with([' ', '\t']))
+            {
+                i += 1;
+            }
+            FieldValue::Nested(lines[start + 1..i].join("\n"))
+        } else {
+            FieldValue::Scalar(scalar(rest))
+        };
+        // Trailing blank and comment lines belong to no field
+        let filler = |l: &str| l.trim().is_empty() || l.trim_start().starts_with('#');
+        let mut end = i;
+        while end > start + 1 && filler(lines[end - 1]) {
+            end -= 1;
+        }
+        fields.push(Field {
+            key: key.to_string(),
+            value,
+            lines: start + 1..end + 1,
+        });
+    }
+    Frontmatter {
+        fields,
+        end: lines.len() + 1,
+    }
+}
+
+/// `key` and the rest of a `key: value` line.
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let colon = line
+        .char_indices()
+        .find(|&(i, c)| c == ':' && line[i + 1..].chars().next().is_none_or(char::is_whitespace))?
+        .0;
+    let key = line[..colon].trim();
+    let key = key
+        .strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')))
+        .unwrap_or(key);
+    (!key.is_empty()).then_some((key, &line[colon + 1..]))
+}
+
+/// A scalar's text: unquoted, or trimmed with any ` # comment` removed.
+fn scalar(raw: &str) -> String {
+    let raw = raw.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = raw.strip_prefix(quote) {
+            if let Some(end) = inner.rfind(quote) {
+                return inner[..end].to_string();
+            }
+        }
+    }
+    match raw.find(" #") {
+        Some(comment) => raw[..comment].trim_end().to_string(),
+        None => raw.to_string(),
+    }
+}
+
+// ─── Editing ────────────────────────────────────────────────────────────────
+
+/// One change to a note's frontmatter, e.g.
+/// `{ "op": "rename", "from": "type", "to": "kind" }`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Edit {
+    /// Add the key, or replace its value.
+    Set {
+        key: String,
+        value: Value,
+    },
+    /// A note without `from` is left alone.
+    Rename {
+        from: String,
+        to: String,
+    },
+    Delete {
+        key: String,
+    },
+    /// Add the values a list doesn't have yet. A missing key becomes a
+    /// list, and a single value becomes a list holding it.
+    Append {
+        key: String,
+        value: Value,
+    },
+}
+
+impl Edit {
+    /// Why this edit can't apply to any note, if it can't.
+    pub fn problem(&self) -> Option<String> {
+        let bad_key = |key: &str| key.trim().is_empty() || key.contains(['\n', '\r']);
+        match self {
+            Edit::Set { key, value } | Edit::Append { key, value } => {
+                if bad_key(key) {
+                    return Some(format!("`{}` isn't a usable key", key));
+                }
+                let scalars = match value {
+                    Value::Array(items) => items.iter().all(is_scalar),
+                    value => is_scalar(value),
+                };
+                (!scalars).then(|| {
+                    format!(
+                        "`{}`: values must be text, numbers, true/false, null, or lists of them",
+                        key
+                    )
+                })
+            }
+            Edit::Rename { from, to } if bad_key(from) || bad_key(to) => {
+                Some(format!("can't rename `{}` to `{}`", from, to))
+            }
+            Edit::Rename { from, to } if from == to => {
+                Some(format!("`{}` would be renamed to itself", from))
+            }
+            Edit::Delete { key } if bad_key(key) => Some(format!("`{}` isn't a usable key", key)),
+            _ => None,
+        }
+    }
+}
+
+/// `text` (LF line endings) with `edits` applied in order. Only the lines of
+/// the fields an edit touches change; other fields, comments, and the body
+/// are kept as they are, and a replaced scalar keeps its quoting and comment.
+/// Fails with the reason when an edit can't apply to this note.
+pub fn apply(text: &str, edits: &[Edit]) -> Result<String, String> {
+    let mut text = text.to_string();
+    for edit in edits {
+        text = apply_one(&text, edit)?;
+    }
+    Ok(text)
+}
+
+fn apply_one(text: &str, edit: &Edit) -> Result<String, String> {
+    let Some(fm) = parse(text) else {
+        if opens_block(text) {
+            return Err("its frontmatter block isn't closed".to_string());
+        }
+        return Ok(match edit {
+            Edit::Set { key, value } => format!(
+                "---\n{}---\n{}",
+                new_field(key, value, &ListStyle::default())?,
+                text
+            ),
+            Edit::Append { key, value } => format!(
+                "---\n{}---\n{}",
+                new_field(key, &as_list(value), &ListStyle::default())?,
+                text
+            ),
+            Edit::Rename { .. } | Edit::Delete { .. } => text.to_string(),
+        });
+    };
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+    match edit {
+        Edit::Set { key, value } => match fm.field(key) {
+            Some(field) => {
+                let replaced = set_lines(&lines, field, value)?;
+                lines.splice(field.lines.clone(), replaced);
+            }
+            None => lines.insert(fm.end, new_field(key, value, &list_style(&fm, &lines))?),
+        },
+        Edit::Rename { from, to } => {
+            let Some(field) = fm.field(from) else {
+                return Ok(text.to_string());
+            };
+            if fm.field(to).is_some() {
+                return Err(format!("it already has `{}`", to));
+            }
+            let line = &lines[field.lines.start];
+            let rest = split_key(line).map_or("\n", |(_, rest)| rest);
+            lines[field.lines.start] = format!("{}:{}", render_key(to), rest);
+        }
+        Edit::Delete { key } => {
+            if let Some(field) = fm.field(key) {
+                lines.drain(field.lines.clone());
+            }
+        }
+        Edit::Append { key, value } => {
+            let Some(field) = fm.field(key) else {
+                lines.insert(
+                    fm.end,
+                    new_field(key, &as_list(value), &list_style(&fm, &lines))?,
+                );
+                return Ok(lines.concat());
+            };
+            let existing: Vec<String> = match &field.value {
+                FieldValue::Nested(_) => return Err(format!("`{}` holds a nested value", key)),
+                FieldValue::List(items) => items.clone(),
+                FieldValue::Scalar(item) => vec![item.clone()],
+            };
+            let mut added: Vec<&Value> = Vec::new();
+            for value in as_values(value) {
+                let text = scalar_text(value);
+                if !existing.contains(&text) && !added.iter().any(|a| scalar_text(a) == text) {
+                    added.push(value);
+                }
+            }
+            if added.is_empty() {
+                return Ok(text.to_string());
+            }
+            append_lines(&mut lines, field, &added)?;
+        }
+    }
+    Ok(lines.concat())
+}
+
+/// The lines replacing `field` when it's set to `value`.
+fn set_lines(lines: &[String], field: &Field, value: &Value) -> Result<Vec<String>, String> {
+    let (prefix, raw) = key_parts(&lines[field.lines.start]);
+    match (&field.value, value) {
+        (FieldValue::Nested(_), _) => Err(format!("`{}` holds a nested value", field.key)),
+        (FieldValue::List(_), Value::Array(items)) if !raw.starts_with('[') => {
+            render_list(prefix, items, &ListStyle::Block(item_prefix(lines, field)))
+        }
+        (_, Value::Array(items)) => render_list(prefix, items, &ListStyle::Flow),
+        (FieldValue::Scalar(_), value) => {
+            let (quote, comment) = scalar_style(raw);
+            let comment = comment.map(|c| format!(" {}", c)).unwrap_or_default();
+            Ok(vec![format!(
+                "{} {}{}\n",
+                prefix,
+                render_scalar(value, quote, false)?,
+                comment
+            )])
+        }
+        (FieldValue::List(_), value) => Ok(vec![format!(
+            "{} {}\n",
+            prefix,
+            render_scalar(value, Quote::Plain, false)?
+        )]),
+    }
+}
+
+fn append_lines(lines: &mut Vec<String>, field: &Field, added: &[&Value]) -> Result<(), String> {
+    let start = field.lines.start;
+    let (prefix, raw) = key_parts(&lines[start]);
+    let (prefix, raw) = (prefix.to_string(), raw.to_string());
+    let rendered = added
+        .iter()
+        .map(|v| render_scalar(v, Quote::Plain, true))
+        .collect::<Result<Vec<_>, _>>()?;
+    match &field.value {
+        FieldValue::List(_) if raw.starts_with('[') => {
+            let line = &lines[start];
+            let close = line.rfind(']').ok_or("its list isn't closed")?;
+            let before = line[..close].trim_end();
+            let separator = if before.ends_with('[') { "" } else { ", " };
+            lines[start] = format!(
+                "{}{}{}{}",
+                before,
+                separator,
+                rendered.join(", "),
+                &line[close..]
+            );
+        }
+        FieldValue::List(_) => {
+            let item = item_prefix(lines, field);
+            if raw == "[]" {
+                lines[start] = format!("{}\n", prefix);
+            }
+            let at = field.lines.end;
+            for (i, value) in added.iter().enumerate() {
+                let value = render_scalar(value, Quote::Plain, false)?;
+                lines.insert(at + i, format!("{}{}\n", item, value));
+            }
+        }
+        FieldValue::Scalar(old) => {
+            // The single value becomes the list's first item
+            let (quote, comment) = scalar_style(&raw);
+            let old = render_scalar(&Value::String(old.clone()), quote, true)?;
+            let comment = comment.map(|c| format!(" {}", c)).unwrap_or_default();
+            lines[start] = format!("{} [{}, {}]{}\n", prefix, old, rendered.join(", "), comment);
+        }
+        FieldValue::Nested(_) => unreachable!("checked by the caller"),
+    }
+    Ok(())
+}
+
+/// A key line's `key:` and its trimmed value text.
+fn key_parts(line: &str) -> (&str, &str) {
+    match split_key(line) {
+        Some((_, rest)) => (&line[..line.len() - rest.len()], rest.trim()),
+        None => (line.trim_end(), ""),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Quote {
+    Plain,
+    Single,
+    Double,
+}
+
+/// How a scalar was quoted, and its trailing `# comment`.
+fn scalar_style(raw: &str) -> (Quote, Option<&str>) {
+    for (mark, quote) in [('"', Quote::Double), ('\'', Quote::Single)] {
+        if let Some(inner) = raw.strip_prefix(mark) {
+            if let Some(end) = inner.rfind(mark) {
+                let after = inner[end + 1..].trim();
+                return (quote, after.starts_with('#').then_some(after));
+            }
+        }
+    }
+    (Quote::Plain, raw.find(" #").map(|i| raw[i..].trim()))
+}
+
+enum ListStyle {
+    /// `key: [a, b]`
+    Flow,
+    /// `key:` then one `- a` line per item, each starting with this.
+    Block(String),
+}
+
+impl Default for ListStyle {
+    fn default() -> Self {
+        ListStyle::Block("  - ".to_string())
+    }
+}
+
+/// The style of the block's first list, for new lists to match.
+fn list_style(fm: &Frontmatter, lines: &[String]) -> ListStyle {
+    for field in &fm.fields {
+        if !matches!(field.value, FieldValue::List(_)) {
+            continue;
+        }
+        let (_, raw) = key_parts(&lines[field.lines.start]);
+        if raw.starts_with('[') {
+            return ListStyle::Flow;
+        }
+        if field.lines.len() > 1 {
+            return ListStyle::Block(item_prefix(lines, field));
+        }
+    }
+    ListStyle::default()
+}
+
+/// What comes before the value on `field`'s item lines, like `  - `.
+fn item_prefix(lines: &[String], field: &Field) -> String {
+    lines[field.lines.start + 1..field.lines.end]
+        .iter()
+        .find_map(|line| {
+            let dash = line.find('-')?;
+            line[..dash]
+                .chars()
+                .all(|c| c == ' ' || c == '\t')
+                .then(|| format!("{}- ", &line[..dash]))
+        })
+        .unwrap_or_else(|| "  - ".to_string())
+}
+
+fn new_field(key: &str, value: &Value, style: &ListStyle) -> Result<String, String> {
+    let prefix = format!("{}:", render_key(key));
+    Ok(match value {
+        Value::Array(items) => render_list(&prefix, items, style)?.concat(),
+        value => format!(
+            "{} {}\n",
+            prefix,
+            render_scalar(value, Quote::Plain, false)?
+        ),
+    })
+}
+
+fn render_list(prefix: &str, items: &[Value], style: &ListStyle) -> Result<Vec<String>, String> {
+    if items.is_empty() {
+        return Ok(vec![format!("{} []\n", prefix)]);
+    }
+    Ok(match style {
+        ListStyle::Flow => {
+            let items = items
+                .iter()
+                .map(|v| render_scalar(v, Quote::Plain, true))
+                .collect::<Result<Vec<_>, _>>()?;
+            vec![format!("{} [{}]\n", prefix, items.join(", "))]
+        }
+        ListStyle::Block(item) => {
+            let mut lines = vec![format!("{}\n", prefix)];
+            for value in items {
+                lines.push(format!(
+                    "{}{}\n",
+                    item,
+                    render_scalar(value, Quote::Plain, false)?
+                ));
+            }
+            lines
+        }
+    })
+}
+
+fn render_key(key: &str) -> String {
+    if plain_safe(key, true) {
+        key.to_string()
+    } else {
+        double_quoted(key)
+    }
+}
+
+fn render_scalar(value: &Value, quote: Quote, in_flow: bool) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(match quote {
+            Quote::Single if !s.contains(['\n', '\r']) => format!("'{}'", s.replace('\'', "''")),
+            Quote::Plain if plain_safe(s, in_flow) => s.clone(),
+            _ => double_quoted(s),
+        }),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok("null".to_string()),
+        _ => Err("lists can only hold text, numbers, true/false, and null".to_string()),
+    }
+}
+
+fn double_quoted(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whether `s` reads back as the same string without quotes.
+fn plain_safe(s: &str, in_flow: bool) -> bool {
+    !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !s.contains(": ")
+        && !s.ends_with(':')
+        && !s.contains(" #")
+        && !s.chars().any(char::is_control)
+        && !(in_flow && s.contains([',', '[', ']', '{', '}']))
+        && !matches!(
+            s.to_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "null" | "~"
+        )
+        && s.parse::<f64>().is_err()
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+fn as_values(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        value => vec![value],
+    }
+}
+
+fn as_list(value: &Value) -> Value {
+    match value {
+        Value::Array(_) => value.clone(),
+        value => Value::Array(vec![value.clone()]),
+    }
+}
+
+/// A value as `parse` would read it back.
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_scalars_lists_and_nested_values() {
+        let text = "---\n\
+            title: \"Plan: Q3\"\n\
+            status: draft # for now\n\
+            # a comment\n\
+            tags: [client-x, 'ops']\n\
+            aliases:\n  - Roadmap\n  - \"Q3 plan\"\n\
+            meta:\n  owner: sam\n\
+            summary: |\n  Two lines\n  of text\n\
+            url: https://example.com/a\n\
+            ---\n# Body\n";
+        let fm = parse(text).unwrap();
+        let keys: Vec<&str> = fm.fields.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["title", "status", "tags", "aliases", "meta", "summary", "url"]
+        );
+        assert_eq!(
+            fm.get("title"),
+            Some(&FieldValue::Scalar("Plan: Q3".into()))
+        );
+        assert_eq!(fm.get("status"), Some(&FieldValue::Scalar("draft".into())));
+        assert_eq!(
+            fm.get("tags"),
+            Some(&FieldValue::List(vec!["client-x".into(), "ops".into()]))
+        );
+        assert_eq!(
+            fm.get("aliases"),
+            Some(&FieldValue::List(vec!["Roadmap".into(), "Q3 plan".into()]))
+        );
+        assert!(matches!(fm.get("meta"), Some(FieldValue::Nested(_))));
+        assert!(matches!(fm.get("summary"), Some(FieldValue::Nested(_))));
+        assert_eq!(
+            fm.get("url"),
+            Some(&FieldValue::Scalar("https://example.com/a".into()))
+        );
+    }
+
+    #[test]
+    fn edits_touch_only_their_fields_and_keep_quoting() {
+        let text = "---\n# people\ntitle: 'Old' # keep\naliases: [a]\nstatus: draft\n\
+            meta:\n  x: 1\n---\nBody\n";
+        let edits: Vec<Edit> = serde_json::from_value(serde_json::json!([
+            { "op": "set", "key": "title", "value": "New's" },
+            { "op": "append", "key": "aliases", "value": ["b", "a"] },
+            { "op": "append", "key": "status", "value": "review" },
+            { "op": "delete", "key": "meta" },
+            { "op": "set", "key": "count", "value": 3 },
+            { "op": "set", "key": "flag", "value": "yes" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            apply(text, &edits).unwrap(),
+            "---\n# people\ntitle: 'New''s' # keep\naliases: [a, b]\n\
+             status: [draft, review]\ncount: 3\nflag: \"yes\"\n---\nBody\n"
+        );
+
+        let nested = Edit::Set {
+            key: "meta".into(),
+            value: 1.into(),
+        };
+        assert!(apply(text, &[nested]).unwrap_err().contains("nested"));
+        let onto = Edit::Rename {
+            from: "title".into(),
+            to: "status".into(),
+        };
+        assert!(apply(text, &[onto]).is_err());
+        let object = Edit::Set {
+            key: "x".into(),
+            value: serde_json::json!({ "a": 1 }),
+        };
+        assert!(object.problem().is_some());
+    }
+
+    #[test]
+    fn needs_a_closed_block_at_the_top() {
+        assert_eq!(parse("# Note\n---\ntitle: x\n---\n"), None);
+        assert_eq!(parse("---\ntitle: x\n"), None);
+        assert_eq!(
+            parse("---\n---\nBody"),
+            Some(Frontmatter {
+                fields: Vec::new(),
+                end: 1
+            })
+        );
+    }
+
+    #[test]
+    fn block_problems() {
+        assert_eq!(block_problem("# Note\n"), None);
+        assert_eq!(block_problem("---\ntags:\n  - a\n# c\n---\n"), None);
+        assert_eq!(
+            block_problem("---\ntitle: x\n").as_deref(),
+            Some("The frontmatter block is never closed")
+        );
+        assert_eq!(
+            block_problem("---\n  - a\n---\n").as_deref(),
+            Some("Line 2 belongs to no key")
+        );
+        assert_eq!(
+            block_problem("---\ntitle: x\njust text\n---\n").as_deref(),
+            Some("Line 3 isn't `key: value`")
+        );
+        assert_eq!(
+            block_problem("---\ntag: a\ntag: b\n---\n").as_deref(),
+            Some("`tag` is set twice")
+        );
+    }
+}
@@ -0,0 +1,293 @@
+// Filesystem deadlines
+//
+// One `stat()` on a dead SMB or NFS mount can block for a minute or more,
+// which makes the app look frozen. File commands therefore run their path
+// work through `run`. The work goes to a blocking worker and the command
+// waits for it until a deadline. If the deadline passes, the command fails
+// with `Timeout`, naming the `path` and `command`, and the worker is
+// abandoned. It finishes, or stays stuck on the mount, on its own. A write
+// that times out may still land.
+//
+// Each command takes an optional `timeout_ms`. Without one, it gets
+// `limits.timeout_ms.<command>` if set, or else the default in `DEFAULTS`.
+// The default is shorter for paths on network volumes (see
+// `is_network_volume`), so a dead mount fails fast. Finding the volume only
+// reads the mount table, never the path itself.
+//
+// Timeouts are counted per command since launch and reported as
+// `fs_timeouts` in diagnostics.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::settings;
+
+/// Command, then its deadline in milliseconds on local and network volumes.
+const DEFAULTS: &[(&str, u64, u64)] = &[
+    ("list_directory", 10_000, 4_000),
+    ("read_text_file", 15_000, 6_000),
+    ("read_text_range", 10_000, 4_000),
+    ("read_text_file_with_hash", 15_000, 6_000),
+    ("write_text_file", 15_000, 6_000),
+    ("get_file_id", 10_000, 4_000),
+    // Whole files are read
+    ("hash_file", 120_000, 30_000),
+    ("get_content_digest", 120_000, 30_000),
+];
+const FALLBACK: (u64, u64) = (10_000, 4_000);
+
+static TIMEOUTS: Mutex<BTreeMap<String, TimeoutCount>> = Mutex::new(BTreeMap::new());
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct TimeoutCount {
+    pub count: u64,
+    pub network: u64,
+    pub last_path: String,
+    pub last_at: String,
+}
+
+// ─── Deadlines ──────────────────────────────────────────────────────────────
+
+/// Run `work` on a worker and wait for it until the command's deadline.
+pub async fn run<T, F>(
+    command: &str,
+    path: &Path,
+    timeout_ms: Option<u64>,
+    work: F,
+) -> CommandResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> CommandResult<T> + Send + 'static,
+{
+    let network = is_network_volume(path);
+    let timeout_ms = timeout_ms.unwrap_or_else(|| default_timeout(command, network));
+    let worker = tauri::async_runtime::spawn_blocking(work);
+    match tokio::time::timeout(Duration::from_millis(timeout_ms), worker).await {
+        Ok(joined) => joined.map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?,
+        Err(_) => {
+            record(command, path, network);
+            log::warn!(
+                "[fs_deadline] {} on {} took over {} ms",
+                command,
+                path.display(),
+                timeout_ms
+            );
+            Err(CommandError::new(
+                ErrorCode::Timeout,
+                format!("{} took too long: {}", command, path.display()),
+            )
+            .with("command", command)
+            .with("path", path.to_string_lossy())
+            .with("timeout_ms", timeout_ms)
+            .with("network_volume", network))
+        }
+    }
+}
+
+fn default_timeout(command: &str, network: bool) -> u64 {
+    if let Some(ms) = settings::load_settings().limits.timeout_ms.get(command) {
+        return *ms;
+    }
+    let (local, remote) = DEFAULTS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map_or(FALLBACK, |&(_, local, remote)| (local, remote));
+    if network {
+        remote
+    } else {
+        local
+    }
+}
+
+fn record(command: &str, path: &Path, network: bool) {
+    let mut timeouts = TIMEOUTS.lock().unwrap();
+    let count = timeouts.entry(command.to_string()).or_default();
+    count.count += 1;
+    count.network += u64::from(network);
+    count.last_path = path.to_string_lossy().to_string();
+    count.last_at = chrono::Utc::now().to_rfc3339();
+}
+
+/// Timeouts by command since launch, for diagnostics.
+pub fn timeout_counts() -> BTreeMap<String, TimeoutCount> {
+    TIMEOUTS.lock().unwrap().clone()
+}
+
+// ─── Volumes ────────────────────────────────────────────────────────────────
+
+/// Mount types served over the network.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+];
+
+/// Whether `path` is on a network mount, judged from the mount table so a
+/// dead mount can't block the answer. Relative paths count as local.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn is_network_volume(path: &Path) -> bool {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    mount_type(&mounts, path).is_some_and(|fs| NETWORK_FILESYSTEMS.contains(&fs.as_str()))
+}
+
+/// The type of the innermost mount in `mounts` holding `path`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mount_type(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let point = PathBuf::from(unescape(fields.next()?));
+            let fs_type = fields.next()?;
+            path.starts_with(&point)
+                .then(|| (point.components().count(), fs_type.to_string()))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// Mount points write spaces and the like as octal escapes, e.g. `\040`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+        match escaped.and_then(|octal| u8::from_str_radix(std::str::from_utf8(octal).ok()?, 8).ok())
+        {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_network_volume(path: &Path) -> bool {
+    use std::ffi::CStr;
+
+    let mut mounts: *mut libc::statfs = std::ptr::null_mut();
+    // MNT_NOWAIT answers from the kernel's cache without asking each mount
+    let count = unsafe { libc::getmntinfo(&mut mounts, libc::MNT_NOWAIT) };
+    if count <= 0 || mounts.is_null() {
+        return false;
+    }
+    let mounts = unsafe { std::slice::from_raw_parts(mounts, count as usize) };
+    mounts
+        .iter()
+        .filter_map(|mount| {
+            let point = unsafe { CStr::from_ptr(mount.f_mntonname.as_ptr()) };
+            let point = Path::new(point.to_str().ok()?);
+            path.starts_with(point)
+                .then(|| (point.components().count(), mount.f_flags))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .is_some_and(|(_, flags)| flags & libc::MNT_LOCAL as u32 == 0)
+}
+
+#[cfg(windows)]
+pub fn is_network_volume(path: &Path) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Prefix};
+    use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+    const DRIVE_REMOTE: u32 = 4;
+
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::UNC(..) | Prefix::VerbatimUNC(..) => true,
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                let root: Vec<u16> = std::ffi::OsStr::new(&format!("{}:\\", letter as char))
+                    .encode_wide()
+                    .chain([0])
+                    .collect();
+                unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    windows
+)))]
+pub fn is_network_volume(_path: &Path) -> bool {
+    false
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn innermost_mount_decides() {
+        let mounts = "\
+/dev/sda1 / ext4 rw 0 0
+//nas/share /mnt/team\\040drive cifs rw 0 0
+tmpfs /mnt/team\\040drive/cache tmpfs rw 0 0
+";
+        let kind = |path: &str| mount_type(mounts, Path::new(path));
+        assert_eq!(kind("/home/me/notes").as_deref(), Some("ext4"));
+        assert_eq!(kind("/mnt/team drive/Reports").as_deref(), Some("cifs"));
+        assert_eq!(kind("/mnt/team drive/cache/x").as_deref(), Some("tmpfs"));
+        // Not a component match
+        assert_eq!(kind("/mnt/team drive2").as_deref(), Some("ext4"));
+        assert_eq!(kind("relative/path"), None);
+    }
+
+    #[test]
+    fn stalled_work_times_out_with_the_path() {
+        let _home = TestHome::new();
+        let path = Path::new("/mnt/stalled/notes");
+        let err = tauri::async_runtime::block_on(run("list_directory", path, Some(20), || {
+            std::thread::sleep(Duration::from_millis(500));
+            Ok(())
+        }))
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Timeout);
+        assert_eq!(err.params["path"], "/mnt/stalled/notes");
+        assert_eq!(err.params["command"], "list_directory");
+        assert_eq!(
+            timeout_counts()["list_directory"].last_path,
+            "/mnt/stalled/notes"
+        );
+
+        let done = tauri::async_runtime::block_on(run("hash_file", path, None, || Ok(7)));
+        assert_eq!(done.unwrap(), 7);
+        assert_eq!(default_timeout("hash_file", true), 30_000);
+        assert_eq!(default_timeout("something_new", false), FALLBACK.0);
+    }
+}
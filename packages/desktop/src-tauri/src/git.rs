@@ -0,0 +1,185 @@
+// Git awareness
+//
+// Many vaults live in git repositories. `get_git_status` reports the repo a
+// path is in so the UI can show branch and dirty state, and stores record
+// whether they're inside one. `git_snapshot` commits a store's changes
+// locally, but only with `git_snapshots_enabled` on; it never pushes and
+// never touches a repo otherwise.
+
+use git2::{BranchType, IndexAddOption, Repository, Signature, StatusOptions};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, dry_run, settings, stores};
+
+#[derive(Serialize, Clone, Default)]
+pub struct GitStatus {
+    pub is_repo: bool,
+    pub repo_root: Option<String>,
+    /// `None` on a detached HEAD or a repo with no commits.
+    pub branch: Option<String>,
+    /// Modified, staged, deleted, or untracked files (ignored files excluded).
+    pub dirty_files: usize,
+    /// Upstream of the current branch, e.g. `origin/main`.
+    pub upstream: Option<String>,
+    pub ahead: Option<usize>,
+    pub behind: Option<usize>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct GitSnapshot {
+    /// `None` when there was nothing to commit.
+    pub commit_id: Option<String>,
+    pub changed_files: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_git_status(path: String) -> CommandResult<GitStatus> {
+    let Ok(repo) = Repository::discover(&path) else {
+        return Ok(GitStatus::default());
+    };
+    let mut status = GitStatus {
+        is_repo: true,
+        repo_root: repo.workdir().map(|p| p.to_string_lossy().to_string()),
+        dirty_files: dirty_count(&repo, None).map_err(internal)?,
+        ..GitStatus::default()
+    };
+
+    let Ok(head) = repo.head() else {
+        return Ok(status);
+    };
+    if !head.is_branch() {
+        return Ok(status);
+    }
+    status.branch = head.shorthand().map(str::to_string);
+
+    let upstream = status
+        .branch
+        .as_deref()
+        .and_then(|name| repo.find_branch(name, BranchType::Local).ok())
+        .and_then(|branch| branch.upstream().ok());
+    if let Some(upstream) = upstream {
+        status.upstream = upstream.name().ok().flatten().map(str::to_string);
+        if let (Some(local), Some(remote)) = (head.target(), upstream.get().target()) {
+            if let Ok((ahead, behind)) = repo.graph_ahead_behind(local, remote) {
+                status.ahead = Some(ahead);
+                status.behind = Some(behind);
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// Stage and commit every change under the store's folder. Requires
+/// `git_snapshots_enabled`; never pushes.
+#[tauri::command]
+pub fn git_snapshot(
+    window: tauri::WebviewWindow,
+    store_id: String,
+    message: String,
+) -> CommandResult<GitSnapshot> {
+    dry_run::check_writable(window.label(), None, "git_snapshot")?;
+    if !settings::load_settings().git_snapshots_enabled {
+        return Err(CommandError::new(
+            ErrorCode::FeatureDisabled,
+            "Git snapshots are turned off in settings",
+        )
+        .with("setting", "git_snapshots_enabled"));
+    }
+    let store = stores::find_store(&store_id)?;
+    let repo = Repository::discover(&store.path).map_err(|_| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Store {} is not in a git repository", store_id),
+        )
+        .with("store_id", &store_id)
+    })?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| CommandError::new(ErrorCode::InvalidInput, "Repository has no work tree"))?
+        .to_path_buf();
+
+    // Limit staging to the store when it's a subfolder of the repo
+    let root = Path::new(&store.path)
+        .canonicalize()
+        .map_err(CommandError::from)?;
+    let workdir = workdir.canonicalize().map_err(CommandError::from)?;
+    let prefix = root
+        .strip_prefix(&workdir)
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    let pathspec = if prefix.is_empty() {
+        "*".to_string()
+    } else {
+        format!("{}/*", prefix)
+    };
+
+    let changed_files = dirty_count(&repo, Some(&pathspec)).map_err(internal)?;
+    if changed_files == 0 {
+        return Ok(GitSnapshot {
+            commit_id: None,
+            changed_files,
+        });
+    }
+    let commit_id = commit(&repo, &pathspec, &message).map_err(internal)?;
+
+    log::info!("[git] snapshot {} of store {}", commit_id, store_id);
+    audit::record(
+        "git_snapshot",
+        None,
+        serde_json::json!({ "store_id": store_id, "commit": commit_id, "files": changed_files }),
+    );
+    Ok(GitSnapshot {
+        commit_id: Some(commit_id),
+        changed_files,
+    })
+}
+
+// ─── Repository ─────────────────────────────────────────────────────────────
+
+pub fn is_repo(path: &Path) -> bool {
+    Repository::discover(path).is_ok()
+}
+
+fn dirty_count(repo: &Repository, pathspec: Option<&str>) -> Result<usize, git2::Error> {
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    if let Some(pathspec) = pathspec {
+        options.pathspec(pathspec);
+    }
+    Ok(repo.statuses(Some(&mut options))?.len())
+}
+
+fn commit(repo: &Repository, pathspec: &str, message: &str) -> Result<String, git2::Error> {
+    let mut index = repo.index()?;
+    index.add_all([pathspec], IndexAddOption::DEFAULT, None)?;
+    // Picks up deletions, which add_all doesn't stage
+    index.update_all([pathspec], None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("AGENTVBX", "agentvbx@localhost"))?;
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let oid = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    Ok(oid.to_string())
+}
+
+fn internal(e: git2::Error) -> CommandError {
+    CommandError::new(ErrorCode::Internal, format!("git: {}", e.message()))
+}
@@ -0,0 +1,583 @@
+// Data directories
+//
+// What we store is split into four roots, each an `agentvbx` folder in the
+// place the platform expects:
+// - config: `$XDG_CONFIG_HOME` on Linux, `~/Library/Application Support` on
+//   macOS, `AppData\Roaming` on Windows. Settings, provider overrides, and
+//   the store registry.
+// - data: `$XDG_DATA_HOME`, Application Support, `AppData\Roaming`.
+//   Sessions, tenants, store data, the vault, and secrets.
+// - cache: `$XDG_CACHE_HOME`, `~/Library/Caches`, `AppData\Local`.
+//   Thumbnails and counts, anything that can be rebuilt.
+// - state: `$XDG_STATE_HOME`, Application Support, `AppData\Local`. Logs,
+//   activity, job history, and upload staging.
+// `ENTRIES` says which root each file and folder belongs in.
+//
+// Older builds kept everything in `~/.agentvbx`. While that directory exists
+// it's still used for all four roots, with the cache in its `cache` folder.
+// `migrate_to_platform_dirs` moves its entries into place and leaves
+// `moved.json` behind, which tells later launches the data now lives in the
+// platform directories. The cache is dropped rather than moved.
+//
+// The home directory comes from `dirs`: `$HOME` and then the password
+// database on Unix, the profile folder on Windows, so a process started by
+// launchd or a service manager without `HOME` still finds it. When that gives
+// no absolute path, every root fails with `HomeUnavailable` instead of
+// guessing. Older builds fell back to the working directory and left a
+// `.agentvbx` wherever the app happened to be started; startup now emits
+// `startup-error` and `get_health` reports `degraded`. `get_stray_data_dir`
+// finds such a directory and `move_stray_data_dir` moves its entries to
+// their roots. Entries already present are left where they are and reported.
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use crate::audit;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+
+const LEGACY_DIR: &str = ".agentvbx";
+const APP_DIR: &str = "agentvbx";
+/// Left in the legacy directory once its contents have moved.
+const POINTER_FILE: &str = "moved.json";
+const LEGACY_CACHE: &str = "cache";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Root {
+    Config,
+    Data,
+    Cache,
+    State,
+}
+
+/// Top-level entries of `~/.agentvbx` that aren't data, matched by name or
+/// by name and a suffix such as `.bak`.
+const ENTRIES: &[(&str, Root)] = &[
+    ("config.json", Root::Config),
+    ("providers.json", Root::Config),
+    ("stores.json", Root::Config),
+    ("audit.log", Root::State),
+    ("activity.jsonl", Root::State),
+    ("scheduler", Root::State),
+    ("uploads", Root::State),
+    ("startup-report.json", Root::State),
+    ("corruption-counts.json", Root::State),
+    ("crashes", Root::State),
+    (LEGACY_CACHE, Root::Cache),
+];
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct DataDirs {
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub cache: PathBuf,
+    pub state: PathBuf,
+    /// Still using `~/.agentvbx`.
+    pub legacy: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StrayDataDir {
+    pub path: String,
+    /// The data directory `move_stray_data_dir` would move it into.
+    pub target: String,
+    pub entries: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StrayMoveReport {
+    pub moved: Vec<String>,
+    /// Already present in the data directories; left in the stray one.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlatformMigrationReport {
+    /// `~/.agentvbx` entries and where each went.
+    pub moved: Vec<(String, String)>,
+    pub dirs: DataDirs,
+}
+
+// ─── Resolving ──────────────────────────────────────────────────────────────
+
+/// The user's home directory.
+pub fn user_home() -> CommandResult<PathBuf> {
+    match dirs::home_dir() {
+        Some(home) if home.is_absolute() => Ok(home),
+        found => Err(unavailable(found)),
+    }
+}
+
+fn unavailable(found: Option<PathBuf>) -> CommandError {
+    CommandError::new(
+        ErrorCode::HomeUnavailable,
+        "Couldn't determine the home directory",
+    )
+    .with("found", found.map(|p| p.to_string_lossy().to_string()))
+}
+
+/// The four roots, legacy or not. Nothing is created.
+pub fn data_dirs() -> CommandResult<DataDirs> {
+    let legacy = user_home()?.join(LEGACY_DIR);
+    if legacy.is_dir() && !legacy.join(POINTER_FILE).exists() {
+        return Ok(DataDirs {
+            config: legacy.clone(),
+            data: legacy.clone(),
+            cache: legacy.join(LEGACY_CACHE),
+            state: legacy,
+            legacy: true,
+        });
+    }
+    platform_dirs()
+}
+
+fn platform_dirs() -> CommandResult<DataDirs> {
+    let pick = |dir: Option<PathBuf>| match dir {
+        Some(dir) if dir.is_absolute() => Ok(dir.join(APP_DIR)),
+        other => Err(unavailable(other)),
+    };
+    Ok(DataDirs {
+        config: pick(dirs::config_dir())?,
+        data: pick(dirs::data_dir())?,
+        cache: pick(dirs::cache_dir())?,
+        // Only Linux has a state directory
+        state: pick(dirs::state_dir().or_else(dirs::data_local_dir))?,
+        legacy: false,
+    })
+}
+
+/// Settings, provider overrides, and the store registry; created if missing.
+pub fn config_dir() -> CommandResult<PathBuf> {
+    ensure(data_dirs()?.config)
+}
+
+/// Sessions, tenants, store data, the vault, and secrets; created if missing.
+pub fn data_dir() -> CommandResult<PathBuf> {
+    ensure(data_dirs()?.data)
+}
+
+/// Anything that can be rebuilt; created if missing.
+pub fn cache_dir() -> CommandResult<PathBuf> {
+    ensure(data_dirs()?.cache)
+}
+
+/// Logs, history, and in-progress work; created if missing.
+pub fn state_dir() -> CommandResult<PathBuf> {
+    ensure(data_dirs()?.state)
+}
+
+fn ensure(dir: PathBuf) -> CommandResult<PathBuf> {
+    let _ = fs::create_dir_all(&dir);
+    Ok(dir)
+}
+
+impl DataDirs {
+    pub fn root(&self, root: Root) -> &Path {
+        match root {
+            Root::Config => &self.config,
+            Root::Data => &self.data,
+            Root::Cache => &self.cache,
+            Root::State => &self.state,
+        }
+    }
+
+    /// The root a top-level entry such as `config.json` or `sessions`
+    /// belongs in.
+    pub fn root_for(&self, entry: &str) -> &Path {
+        self.root(root_of(entry))
+    }
+
+    /// `relative`, a `/`-separated path as laid out in `~/.agentvbx`, in its
+    /// root.
+    pub fn locate(&self, relative: &str) -> PathBuf {
+        let entry = relative.split('/').next().unwrap_or_default();
+        self.root_for(entry).join(relative)
+    }
+
+    /// The roots, leaving out any inside another, e.g. the legacy cache.
+    pub fn distinct(&self) -> Vec<&Path> {
+        let all = [&self.config, &self.data, &self.state, &self.cache];
+        let mut distinct: Vec<&Path> = Vec::new();
+        for dir in all {
+            let inside = all
+                .iter()
+                .any(|other| *other != dir && dir.starts_with(other));
+            if !inside && !distinct.contains(&dir.as_path()) {
+                distinct.push(dir);
+            }
+        }
+        distinct
+    }
+}
+
+fn root_of(entry: &str) -> Root {
+    // Temp files and backups follow the file they belong to
+    let name = entry.trim_start_matches('.');
+    ENTRIES
+        .iter()
+        .find(|(known, _)| {
+            name == *known
+                || name
+                    .strip_prefix(known)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .map_or(Root::Data, |(_, root)| *root)
+}
+
+/// Report an unresolvable home, or a stray data directory, to the UI.
+pub fn check_at_startup(app: &AppHandle) {
+    let dirs = match data_dirs() {
+        Ok(dirs) => dirs,
+        Err(e) => {
+            log::error!("[home] {}; nothing will be saved", e.message);
+            let _ = app.emit("startup-error", &e);
+            return;
+        }
+    };
+    if dirs.legacy {
+        log::info!("[home] using {}", dirs.data.display());
+    }
+    if let Some(stray) = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| find_stray(&cwd, &dirs))
+    {
+        log::warn!("[home] found data from an older version in {}", stray.path);
+        let _ = app.emit("stray-data-dir-found", &stray);
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// A `.agentvbx` in the working directory that isn't the data directory.
+#[tauri::command]
+pub fn get_stray_data_dir() -> CommandResult<Option<StrayDataDir>> {
+    let dirs = data_dirs()?;
+    Ok(find_stray(&std::env::current_dir()?, &dirs))
+}
+
+/// Move the stray directory's entries into the data directories.
+#[tauri::command]
+pub fn move_stray_data_dir() -> CommandResult<StrayMoveReport> {
+    let dirs = data_dirs()?;
+    let cwd = std::env::current_dir()?;
+    let Some(stray) = find_stray(&cwd, &dirs) else {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            "No stray data directory to move",
+        ));
+    };
+    let report = move_entries(Path::new(&stray.path), &dirs)?;
+    audit::record(
+        "stray_data_dir_moved",
+        None,
+        serde_json::json!({
+            "from": stray.path,
+            "moved": report.moved,
+            "skipped": report.skipped,
+        }),
+    );
+    Ok(report)
+}
+
+/// Move `~/.agentvbx` into the platform directories. Fails with `Conflict`,
+/// moving nothing, if any entry already exists there; a failed move puts
+/// back what was moved. Close tenant and login windows first, since their
+/// webview profiles live in the sessions folder.
+#[tauri::command]
+pub fn migrate_to_platform_dirs() -> CommandResult<PlatformMigrationReport> {
+    let legacy_dirs = data_dirs()?;
+    if !legacy_dirs.legacy {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Data is already in the platform directories",
+        ));
+    }
+    let dirs = platform_dirs()?;
+    let report = migrate(&legacy_dirs.data, &dirs)?;
+    audit::record(
+        "migrated_to_platform_dirs",
+        None,
+        serde_json::json!({ "from": legacy_dirs.data, "moved": report.moved.len() }),
+    );
+    Ok(report)
+}
+
+// ─── Moving ─────────────────────────────────────────────────────────────────
+
+fn migrate(legacy: &Path, dirs: &DataDirs) -> CommandResult<PlatformMigrationReport> {
+    let mut plan = Vec::new();
+    let mut conflicts = Vec::new();
+    for name in entry_names(legacy)? {
+        if name == LEGACY_CACHE {
+            continue;
+        }
+        let target = dirs.root_for(&name).join(&name);
+        if target.exists() {
+            conflicts.push(target.to_string_lossy().to_string());
+        }
+        plan.push((legacy.join(&name), target));
+    }
+    if !conflicts.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("{} already exists", conflicts[0]),
+        )
+        .with("path", &conflicts[0])
+        .with("conflicts", conflicts));
+    }
+
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (from, to) in plan {
+        let result = to
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::rename(&from, &to));
+        if let Err(e) = result {
+            log::error!(
+                "[home] moving {} failed, putting everything back: {}",
+                from.display(),
+                e
+            );
+            for (from, to) in moved.iter().rev() {
+                let _ = fs::rename(to, from);
+            }
+            return Err(e.into());
+        }
+        moved.push((from, to));
+    }
+    let _ = fs::remove_dir_all(legacy.join(LEGACY_CACHE));
+
+    let pointer = serde_json::json!({
+        "moved_at": chrono::Utc::now().to_rfc3339(),
+        "config": dirs.config,
+        "data": dirs.data,
+        "cache": dirs.cache,
+        "state": dirs.state,
+    });
+    fs::write(
+        legacy.join(POINTER_FILE),
+        serde_json::to_vec_pretty(&pointer).map_err(|e| e.to_string())?,
+    )?;
+    log::info!(
+        "[home] moved {} entries from {} to the platform directories",
+        moved.len(),
+        legacy.display()
+    );
+    Ok(PlatformMigrationReport {
+        moved: moved
+            .into_iter()
+            .map(|(from, to)| {
+                let name = from.file_name().unwrap_or_default().to_string_lossy();
+                (name.to_string(), to.to_string_lossy().to_string())
+            })
+            .collect(),
+        dirs: dirs.clone(),
+    })
+}
+
+/// Sorted, without the pointer file.
+fn entry_names(dir: &Path) -> CommandResult<Vec<String>> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name != POINTER_FILE)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn find_stray(cwd: &Path, dirs: &DataDirs) -> Option<StrayDataDir> {
+    let candidate = cwd.join(LEGACY_DIR);
+    let legacy = user_home().ok()?.join(LEGACY_DIR);
+    if !candidate.is_dir() || same_dir(&candidate, &legacy) {
+        return None;
+    }
+    let entries = entry_names(&candidate).ok()?;
+    if entries.is_empty() {
+        return None;
+    }
+    Some(StrayDataDir {
+        path: candidate.to_string_lossy().to_string(),
+        target: dirs.data.to_string_lossy().to_string(),
+        entries,
+    })
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn move_entries(from: &Path, dirs: &DataDirs) -> CommandResult<StrayMoveReport> {
+    let mut report = StrayMoveReport {
+        moved: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for name in entry_names(from)? {
+        let target = match name.as_str() {
+            // The legacy cache folder is the cache root itself
+            LEGACY_CACHE => dirs.cache.clone(),
+            _ => dirs.root_for(&name).join(&name),
+        };
+        if target.exists() {
+            report.skipped.push(name);
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from.join(&name), &target)?;
+        report.moved.push(name);
+    }
+    if report.skipped.is_empty() {
+        let _ = fs::remove_dir(from);
+    }
+    log::info!(
+        "[home] moved {} entries from {} ({} already present)",
+        report.moved.len(),
+        from.display(),
+        report.skipped.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn an_existing_agentvbx_directory_holds_every_root() {
+        let home = TestHome::new();
+        let dirs = data_dirs().unwrap();
+        assert!(dirs.legacy);
+        assert_eq!(data_dir().unwrap(), home.agentvbx());
+        assert_eq!(config_dir().unwrap(), home.agentvbx());
+        assert_eq!(cache_dir().unwrap(), home.agentvbx().join("cache"));
+        assert_eq!(dirs.distinct(), vec![home.agentvbx().as_path()]);
+        assert_eq!(
+            dirs.locate("sessions/acme/claude"),
+            home.agentvbx().join("sessions/acme/claude")
+        );
+    }
+
+    #[test]
+    fn fresh_installs_use_the_platform_directories() {
+        let home = TestHome::platform();
+        let dirs = data_dirs().unwrap();
+        assert!(!dirs.legacy);
+        for dir in [&dirs.config, &dirs.data, &dirs.cache, &dirs.state] {
+            assert!(dir.starts_with(home.path()), "{}", dir.display());
+            assert!(dir.ends_with(APP_DIR));
+        }
+        if cfg!(target_os = "linux") {
+            assert_eq!(dirs.config, home.join(".config/agentvbx"));
+            assert_eq!(dirs.state, home.join(".local/state/agentvbx"));
+        }
+        // Each module's files land in their root
+        assert!(crate::settings::settings_path()
+            .unwrap()
+            .starts_with(&dirs.config));
+        assert!(crate::sessions::sessions_root()
+            .unwrap()
+            .starts_with(&dirs.data));
+        assert!(crate::audit::audit_log_path()
+            .unwrap()
+            .starts_with(&dirs.state));
+        assert!(!home.agentvbx().exists());
+    }
+
+    #[test]
+    fn entries_are_sorted_into_roots() {
+        assert_eq!(root_of("config.json"), Root::Config);
+        assert_eq!(root_of("config.json.bak"), Root::Config);
+        assert_eq!(root_of(".stores.json.agentvbx-tmp"), Root::Config);
+        assert_eq!(root_of("config.jsonx"), Root::Data);
+        assert_eq!(root_of("audit.log"), Root::State);
+        assert_eq!(root_of("sessions"), Root::Data);
+        assert_eq!(root_of("cache"), Root::Cache);
+    }
+
+    #[test]
+    fn migration_moves_everything_and_leaves_a_pointer() {
+        let home = TestHome::platform();
+        home.write(".agentvbx/config.json", "{}");
+        home.write(".agentvbx/sessions/acme/claude/session.json", "{}");
+        home.write(".agentvbx/audit.log", "");
+        home.write(".agentvbx/cache/vault-counts.json", "{}");
+        assert!(data_dirs().unwrap().legacy);
+
+        let report = migrate_to_platform_dirs().unwrap();
+        let dirs = data_dirs().unwrap();
+        assert!(!dirs.legacy);
+        assert_eq!(report.dirs, dirs);
+        assert_eq!(report.moved.len(), 3);
+        assert!(dirs.config.join("config.json").is_file());
+        assert!(dirs
+            .data
+            .join("sessions/acme/claude/session.json")
+            .is_file());
+        assert!(dirs.state.join("audit.log").is_file());
+        assert!(!home.agentvbx().join("cache").exists());
+        assert!(home.agentvbx().join(POINTER_FILE).is_file());
+
+        // The pointer isn't a stray data directory
+        assert!(find_stray(home.path(), &dirs).is_none());
+        assert!(migrate_to_platform_dirs().is_err());
+    }
+
+    #[test]
+    fn migration_refuses_to_overwrite() {
+        let home = TestHome::platform();
+        let dirs = platform_dirs().unwrap();
+        home.write(".agentvbx/config.json", "{\"old\": true}");
+        home.write(".agentvbx/tenants/acme/config.json", "{}");
+        fs::create_dir_all(&dirs.config).unwrap();
+        fs::write(dirs.config.join("config.json"), "{}").unwrap();
+
+        let err = migrate_to_platform_dirs().unwrap_err();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        // Nothing moved
+        assert!(home.agentvbx().join("tenants/acme").is_dir());
+        assert!(!dirs.data.join("tenants").exists());
+        assert!(data_dirs().unwrap().legacy);
+    }
+
+    #[test]
+    fn stray_directories_move_without_clobbering() {
+        let home = TestHome::new();
+        let dirs = data_dirs().unwrap();
+        fs::write(dirs.config.join("config.json"), "{}").unwrap();
+        let cwd = home.join("launched-here");
+        home.write("launched-here/.agentvbx/config.json", "{\"old\": true}");
+        home.write(
+            "launched-here/.agentvbx/sessions/acme/claude/session.json",
+            "{}",
+        );
+
+        // The data directory itself is never stray
+        assert!(find_stray(home.path(), &dirs).is_none());
+        let stray = find_stray(&cwd, &dirs).unwrap();
+        assert_eq!(stray.entries, vec!["config.json", "sessions"]);
+
+        let report = move_entries(Path::new(&stray.path), &dirs).unwrap();
+        assert_eq!(report.moved, vec!["sessions"]);
+        assert_eq!(report.skipped, vec!["config.json"]);
+        assert!(dirs
+            .data
+            .join("sessions/acme/claude/session.json")
+            .is_file());
+        assert_eq!(
+            fs::read_to_string(dirs.config.join("config.json")).unwrap(),
+            "{}"
+        );
+        // Still offered, for the entry that was left behind
+        assert_eq!(
+            find_stray(&cwd, &dirs).unwrap().entries,
+            vec!["config.json"]
+        );
+    }
+}
@@ -0,0 +1,1082 @@
+// HTML reading
+//
+// Enough HTML for link previews and web clipping, without a browser engine:
+// - `tag`, `attr`, and `decode_entities` read one tag at a time, for quick
+//   scans like `url_metadata::parse_head`.
+// - `parse` builds a forgiving tree. Stray end tags are ignored, void
+//   elements never get children, and an open `p`, `li`, `dt`/`dd`, `tr`, or
+//   table cell closes when the next one starts. Scripts, styles, and
+//   `noscript` keep their contents as raw text and are never rendered.
+// - `main_content` picks the element holding the article, readability style:
+//   every paragraph of 25+ characters adds to its parent's score, and half as
+//   much to its grandparent's, by length and commas. Class and id names like
+//   `article` or `sidebar` weigh in, and link-heavy elements are marked down.
+//   Navigation, asides, forms, hidden elements, and names like `comment` or
+//   `share` are skipped.
+// - `to_markdown` renders an element as CommonMark with GFM tables and
+//   strikethrough. Links and images are made absolute against the page's
+//   URL, and the caller can point images elsewhere, such as at local copies.
+// - `plain_text` keeps just the text, with paragraphs, line breaks, and list
+//   items, for HTML email bodies.
+
+use reqwest::Url;
+use std::collections::HashMap;
+
+/// A tag attribute's lowercase name and decoded value.
+pub type Attr = (String, String);
+
+pub type NodeId = usize;
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+/// Elements whose contents aren't markup.
+const RAW_TEXT: &[&str] = &[
+    "script", "style", "noscript", "template", "textarea", "title", "xmp",
+];
+/// Never part of the content.
+const SKIPPED: &[&str] = &[
+    "aside", "button", "canvas", "dialog", "embed", "footer", "form", "head", "iframe", "input",
+    "nav", "noscript", "object", "script", "select", "style", "svg", "template", "textarea",
+    "title",
+];
+const BLOCK: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+/// Class and id words of page furniture. Words under four letters match
+/// exactly, longer ones as prefixes (`comment` matches `comments`).
+const UNLIKELY: &[&str] = &[
+    "ad",
+    "ads",
+    "advert",
+    "banner",
+    "breadcrumb",
+    "comment",
+    "cookie",
+    "disqus",
+    "footer",
+    "menu",
+    "modal",
+    "nav",
+    "navbar",
+    "navigation",
+    "newsletter",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+    "subscribe",
+];
+/// Words that keep an element with an unlikely word, like `main-nav-content`.
+const MAYBE: &[&str] = &["article", "body", "column", "content", "main", "story"];
+const POSITIVE: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+const NEGATIVE: &[&str] = &[
+    "comment", "footer", "masthead", "meta", "modal", "nav", "related", "share", "sidebar",
+    "sponsor", "widget",
+];
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+pub struct Document {
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    data: Data,
+}
+
+enum Data {
+    Element { name: String, attrs: Vec<Attr> },
+    Text(String),
+}
+
+// ─── Tokens ─────────────────────────────────────────────────────────────────
+
+/// The lowercase name and the attributes of the tag starting at `text` (just
+/// past its `<`), and where the tag ends, just past its `>`.
+pub fn tag(text: &str) -> Option<(String, Vec<Attr>, usize)> {
+    let name_len = text
+        .find(|c: char| c.is_whitespace() || c == '>' || (c == '/' && !text.starts_with('/')))
+        .unwrap_or(text.len());
+    let name = text[..name_len].to_ascii_lowercase();
+    let mut attrs = Vec::new();
+    let mut rest = &text[name_len..];
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Some((name, attrs, text.len() - after.len()));
+        }
+        let key_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let key = rest[..key_len].to_ascii_lowercase();
+        rest = rest[key_len..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'');
+            let (raw, remainder) = match quote {
+                Some(q) => {
+                    let inner = &after[1..];
+                    let end = inner.find(q)?;
+                    (&inner[..end], &inner[end + 1..])
+                }
+                None => {
+                    let end = after
+                        .find(|c: char| c.is_whitespace() || c == '>')
+                        .unwrap_or(after.len());
+                    after.split_at(end)
+                }
+            };
+            value = decode_entities(raw);
+            rest = remainder;
+        }
+        if !key.is_empty() {
+            attrs.push((key, value));
+        }
+    }
+}
+
+pub fn attr<'a>(attrs: &'a [Attr], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let entity = &rest[1..1 + end];
+                let c = match entity {
+                    "amp" => '&',
+                    "lt" => '<',
+                    "gt" => '>',
+                    "quot" => '"',
+                    "apos" => '\'',
+                    "nbsp" => ' ',
+                    "ndash" => '–',
+                    "mdash" => '—',
+                    "hellip" => '…',
+                    "lsquo" => '‘',
+                    "rsquo" => '’',
+                    "ldquo" => '“',
+                    "rdquo" => '”',
+                    "copy" => '©',
+                    _ => {
+                        let code = match entity.strip_prefix('#') {
+                            Some(hex) if hex.starts_with(['x', 'X']) => {
+                                u32::from_str_radix(&hex[1..], 16).ok()
+                            }
+                            Some(decimal) => decimal.parse().ok(),
+                            None => None,
+                        };
+                        char::from_u32(code?)?
+                    }
+                };
+                Some((c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whitespace runs collapsed to single spaces, trimmed.
+pub fn clean(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `url` as a Markdown link destination.
+pub fn link_destination(url: &str) -> String {
+    url.replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace('<', "%3C")
+        .replace('>', "%3E")
+}
+
+// ─── Tree ───────────────────────────────────────────────────────────────────
+
+pub fn parse(html: &str) -> Document {
+    let lower = html.to_ascii_lowercase();
+    let mut doc = Document {
+        nodes: vec![Node {
+            parent: None,
+            children: Vec::new(),
+            data: Data::Element {
+                name: String::new(),
+                attrs: Vec::new(),
+            },
+        }],
+    };
+    let mut open: Vec<NodeId> = vec![Document::ROOT];
+    let mut at = 0;
+    while at < html.len() {
+        let top = *open.last().unwrap();
+        let Some(lt) = html[at..].find('<') else {
+            doc.add_text(top, &html[at..]);
+            break;
+        };
+        doc.add_text(top, &html[at..at + lt]);
+        let start = at + lt + 1;
+        let rest = &lower[start..];
+        if rest.starts_with("!--") {
+            at = rest.find("-->").map_or(html.len(), |end| start + end + 3);
+            continue;
+        }
+        if rest.starts_with(['!', '?']) {
+            at = rest.find('>').map_or(html.len(), |end| start + end + 1);
+            continue;
+        }
+        let letter = |s: &str| s.starts_with(|c: char| c.is_ascii_alphabetic());
+        if !letter(rest) && !rest.strip_prefix('/').is_some_and(letter) {
+            doc.add_text(top, "<");
+            at = start;
+            continue;
+        }
+        let Some((name, attrs, len)) = tag(&html[start..]) else {
+            break;
+        };
+        at = start + len;
+        if let Some(name) = name.strip_prefix('/') {
+            if let Some(i) = open.iter().rposition(|&id| doc.name(id) == Some(name)) {
+                open.truncate(i.max(1));
+            }
+            continue;
+        }
+
+        doc.close_implied(&mut open, &name);
+        let top = *open.last().unwrap();
+        let self_closing = html[..at].ends_with("/>");
+        let raw = RAW_TEXT.contains(&name.as_str());
+        let void = VOID.contains(&name.as_str());
+        let close = format!("</{}", name);
+        let id = doc.push(top, Data::Element { name, attrs });
+        if raw && !self_closing {
+            let end = lower[at..].find(&close).map_or(html.len(), |i| at + i);
+            doc.push(id, Data::Text(html[at..end].to_string()));
+            at = end;
+        } else if !void && !self_closing {
+            open.push(id);
+        }
+    }
+    doc
+}
+
+impl Document {
+    pub const ROOT: NodeId = 0;
+
+    pub fn name(&self, id: NodeId) -> Option<&str> {
+        match &self.nodes[id].data {
+            Data::Element { name, .. } => Some(name),
+            Data::Text(_) => None,
+        }
+    }
+
+    pub fn attr(&self, id: NodeId, name: &str) -> Option<&str> {
+        match &self.nodes[id].data {
+            Data::Element { attrs, .. } => attr(attrs, name),
+            Data::Text(_) => None,
+        }
+    }
+
+    /// The first element named `name`, in document order.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        (0..self.nodes.len()).find(|&id| self.name(id) == Some(name))
+    }
+
+    /// The rendered text inside `id`, whitespace collapsed.
+    pub fn text(&self, id: NodeId) -> String {
+        let mut text = String::new();
+        self.collect_text(id, &mut text, false);
+        clean(&text)
+    }
+
+    fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id].children
+    }
+
+    fn push(&mut self, parent: NodeId, data: Data) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            parent: Some(parent),
+            children: Vec::new(),
+            data,
+        });
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    fn add_text(&mut self, parent: NodeId, raw: &str) {
+        if !raw.is_empty() {
+            self.push(parent, Data::Text(decode_entities(raw)));
+        }
+    }
+
+    /// Close the elements that starting a `name` ends, the way browsers do.
+    fn close_implied(&self, open: &mut Vec<NodeId>, name: &str) {
+        let (closes, within): (&[&str], &[&str]) = match name {
+            "li" => (&["li"], &["ul", "ol"]),
+            "dt" | "dd" => (&["dt", "dd"], &["dl"]),
+            "tr" => (&["tr", "td", "th"], &["table", "thead", "tbody", "tfoot"]),
+            "td" | "th" => (&["td", "th"], &["tr", "table"]),
+            "thead" | "tbody" | "tfoot" => {
+                (&["thead", "tbody", "tfoot", "tr", "td", "th"], &["table"])
+            }
+            name if BLOCK.contains(&name) => (
+                &["p"],
+                &[
+                    "div",
+                    "li",
+                    "td",
+                    "th",
+                    "blockquote",
+                    "section",
+                    "article",
+                    "body",
+                    "table",
+                ],
+            ),
+            _ => return,
+        };
+        for i in (1..open.len()).rev() {
+            let Some(open_name) = self.name(open[i]) else {
+                continue;
+            };
+            if closes.contains(&open_name) {
+                open.truncate(i);
+                return;
+            }
+            if within.contains(&open_name) {
+                return;
+            }
+        }
+    }
+
+    fn collect_text(&self, id: NodeId, out: &mut String, raw: bool) {
+        match &self.nodes[id].data {
+            Data::Text(text) => out.push_str(text),
+            Data::Element { name, .. } => {
+                if name == "br" {
+                    out.push('\n');
+                }
+                if !raw && (SKIPPED.contains(&name.as_str()) || self.hidden(id)) {
+                    return;
+                }
+                for &child in self.children(id) {
+                    self.collect_text(child, out, raw);
+                }
+                if !raw && BLOCK.contains(&name.as_str()) {
+                    out.push(' ');
+                }
+            }
+        }
+    }
+
+    /// Text inside `id` exactly as written, for code.
+    fn raw_text(&self, id: NodeId) -> String {
+        let mut text = String::new();
+        self.collect_text(id, &mut text, true);
+        text
+    }
+
+    fn hidden(&self, id: NodeId) -> bool {
+        let style = self.attr(id, "style").unwrap_or_default().replace(' ', "");
+        self.attr(id, "hidden").is_some()
+            || self.attr(id, "aria-hidden") == Some("true")
+            || style.contains("display:none")
+            || matches!(
+                self.attr(id, "role"),
+                Some("navigation" | "complementary" | "dialog")
+            )
+    }
+
+    /// Lowercase words of the class and id.
+    fn words(&self, id: NodeId) -> Vec<String> {
+        let names = format!(
+            "{} {}",
+            self.attr(id, "class").unwrap_or_default(),
+            self.attr(id, "id").unwrap_or_default()
+        );
+        names
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn named(&self, id: NodeId, list: &[&str]) -> bool {
+        self.words(id).iter().any(|word| {
+            list.iter().any(|pattern| {
+                if pattern.len() < 4 {
+                    word == pattern
+                } else {
+                    word.starts_with(pattern)
+                }
+            })
+        })
+    }
+
+    fn skipped(&self, id: NodeId) -> bool {
+        let Some(name) = self.name(id) else {
+            return false;
+        };
+        if SKIPPED.contains(&name) || self.hidden(id) {
+            return true;
+        }
+        !matches!(name, "html" | "body" | "article" | "main")
+            && self.named(id, UNLIKELY)
+            && !self.named(id, MAYBE)
+    }
+}
+
+// ─── Readability ────────────────────────────────────────────────────────────
+
+impl Document {
+    /// The element most likely to hold the page's article, if any paragraph
+    /// is long enough to count.
+    pub fn main_content(&self) -> Option<NodeId> {
+        let mut scores: HashMap<NodeId, f64> = HashMap::new();
+        let mut stack = vec![self.find("body").unwrap_or(Document::ROOT)];
+        while let Some(id) = stack.pop() {
+            if self.skipped(id) {
+                continue;
+            }
+            stack.extend(self.children(id).iter().rev());
+            if !matches!(self.name(id), Some("p" | "pre" | "td" | "blockquote")) {
+                continue;
+            }
+            let text = self.text(id);
+            let chars = text.chars().count();
+            if chars < MIN_PARAGRAPH_CHARS {
+                continue;
+            }
+            let score =
+                1.0 + text.matches([',', '，']).count() as f64 + (chars / 100).min(3) as f64;
+            let parent = self.nodes[id].parent;
+            let grandparent = parent.and_then(|p| self.nodes[p].parent);
+            for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+                let Some(ancestor) = ancestor else {
+                    continue;
+                };
+                *scores
+                    .entry(ancestor)
+                    .or_insert_with(|| self.base_score(ancestor)) += score * share;
+            }
+        }
+        scores
+            .into_iter()
+            .map(|(id, score)| (id, score * (1.0 - self.link_density(id))))
+            // Ties go to the element that comes first
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(id, _)| id)
+    }
+
+    fn base_score(&self, id: NodeId) -> f64 {
+        let by_tag = match self.name(id).unwrap_or_default() {
+            "div" | "article" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+        let mut by_name = 0.0;
+        if self.named(id, POSITIVE) {
+            by_name += 25.0;
+        }
+        if self.named(id, NEGATIVE) {
+            by_name -= 25.0;
+        }
+        by_tag + by_name
+    }
+
+    /// Share of the text inside `id` that is link text.
+    fn link_density(&self, id: NodeId) -> f64 {
+        let total = self.text(id).chars().count();
+        if total == 0 {
+            return 0.0;
+        }
+        let mut linked = 0;
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            if self.name(node) == Some("a") {
+                linked += self.text(node).chars().count();
+            } else {
+                stack.extend(self.children(node));
+            }
+        }
+        linked as f64 / total as f64
+    }
+}
+
+// ─── Markdown ───────────────────────────────────────────────────────────────
+
+impl Document {
+    /// Everything in `id` as Markdown. `image` can give the link to use for an
+    /// image instead of its own URL.
+    pub fn to_markdown(
+        &self,
+        id: NodeId,
+        base: &Url,
+        image: &dyn Fn(&Url) -> Option<String>,
+    ) -> String {
+        let writer = Markdown {
+            doc: self,
+            base,
+            image,
+        };
+        let mut blocks = Vec::new();
+        writer.blocks(id, &mut blocks);
+        let mut text = blocks.join("\n\n");
+        text.push('\n');
+        text
+    }
+
+    /// The absolute URLs of the images `to_markdown` would render, in order,
+    /// without repeats.
+    pub fn image_urls(&self, id: NodeId, base: &Url) -> Vec<Url> {
+        let mut urls: Vec<Url> = Vec::new();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            if self.skipped(node) {
+                continue;
+            }
+            if self.name(node) == Some("img") {
+                if let Some(url) = self.image_source(node, base) {
+                    if !urls.contains(&url) {
+                        urls.push(url);
+                    }
+                }
+            }
+            stack.extend(self.children(node).iter().rev());
+        }
+        urls
+    }
+
+    /// `src`, or the lazy-loading attributes pages use instead of it.
+    fn image_source(&self, id: NodeId, base: &Url) -> Option<Url> {
+        let srcset = |name| {
+            self.attr(id, name)
+                .and_then(|set| set.split(',').next())
+                .and_then(|first| first.split_whitespace().next())
+        };
+        let raw = ["data-src", "data-original", "src"]
+            .iter()
+            .filter_map(|name| self.attr(id, name))
+            .map(str::trim)
+            .find(|src| !src.is_empty() && !src.starts_with("data:"))
+            .or_else(|| srcset("data-srcset"))
+            .or_else(|| srcset("srcset"))?;
+        base.join(raw)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+    }
+}
+
+struct Markdown<'a> {
+    doc: &'a Document,
+    base: &'a Url,
+    image: &'a dyn Fn(&Url) -> Option<String>,
+}
+
+impl Markdown<'_> {
+    /// The blocks inside `id`; runs of inline content become paragraphs.
+    fn blocks(&self, id: NodeId, out: &mut Vec<String>) {
+        let mut run = String::new();
+        for &child in self.doc.children(id) {
+            if self.doc.skipped(child) {
+                continue;
+            }
+            match self.doc.name(child) {
+                Some(name) if BLOCK.contains(&name) => {
+                    paragraph(&mut run, out);
+                    self.block(child, name, out);
+                }
+                _ => run.push_str(&self.inline(child)),
+            }
+        }
+        paragraph(&mut run, out);
+    }
+
+    fn blocks_of(&self, id: NodeId) -> Vec<String> {
+        let mut blocks = Vec::new();
+        self.blocks(id, &mut blocks);
+        blocks
+    }
+
+    fn block(&self, id: NodeId, name: &str, out: &mut Vec<String>) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = clean(&self.inlines(id));
+                if !text.is_empty() {
+                    let level: usize = name[1..].parse().unwrap_or(1);
+                    out.push(format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "ul" | "ol" => {
+                let list = self.list(id, name == "ol");
+                if !list.is_empty() {
+                    out.push(list);
+                }
+            }
+            "blockquote" => {
+                let inner = self.blocks_of(id).join("\n\n");
+                if !inner.is_empty() {
+                    let quoted: Vec<String> = inner
+                        .lines()
+                        .map(|line| match line {
+                            "" => ">".to_string(),
+                            line => format!("> {}", line),
+                        })
+                        .collect();
+                    out.push(quoted.join("\n"));
+                }
+            }
+            "pre" => {
+                let code = self.code_block(id);
+                if !code.is_empty() {
+                    out.push(code);
+                }
+            }
+            "hr" => out.push("---".to_string()),
+            "table" => out.extend(self.table(id)),
+            "figcaption" => {
+                let text = clean(&self.inlines(id));
+                if !text.is_empty() {
+                    out.push(format!("*{}*", text));
+                }
+            }
+            _ => self.blocks(id, out),
+        }
+    }
+
+    fn inlines(&self, id: NodeId) -> String {
+        self.doc
+            .children(id)
+            .iter()
+            .map(|&child| self.inline(child))
+            .collect()
+    }
+
+    fn inline(&self, id: NodeId) -> String {
+        let name = match &self.doc.nodes[id].data {
+            Data::Text(text) => return escape(&collapse(text)),
+            Data::Element { name, .. } => name.as_str(),
+        };
+        if self.doc.skipped(id) {
+            return String::new();
+        }
+        match name {
+            "br" => "\n".to_string(),
+            "strong" | "b" => wrap(&self.inlines(id), "**"),
+            "em" | "i" => wrap(&self.inlines(id), "*"),
+            "del" | "s" | "strike" => wrap(&self.inlines(id), "~~"),
+            "code" | "kbd" | "samp" => code_span(&self.doc.raw_text(id)),
+            "a" => self.link(id),
+            "img" => self.image(id),
+            name if BLOCK.contains(&name) => format!(" {} ", self.inlines(id)),
+            _ => self.inlines(id),
+        }
+    }
+
+    fn link(&self, id: NodeId) -> String {
+        let text = self.inlines(id);
+        let target = self
+            .doc
+            .attr(id, "href")
+            .map(str::trim)
+            .filter(|href| !href.starts_with('#'))
+            .and_then(|href| self.base.join(href).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https" | "mailto"));
+        let (Some(target), false) = (target, text.trim().is_empty()) else {
+            return text;
+        };
+        let lead = if text.starts_with(' ') { " " } else { "" };
+        let trail = if text.ends_with(' ') { " " } else { "" };
+        format!(
+            "{}[{}]({}){}",
+            lead,
+            text.trim(),
+            link_destination(target.as_str()),
+            trail
+        )
+    }
+
+    fn image(&self, id: NodeId) -> String {
+        let Some(url) = self.doc.image_source(id, self.base) else {
+            return String::new();
+        };
+        let alt = escape(&clean(self.doc.attr(id, "alt").unwrap_or_default()));
+        let target = (self.image)(&url).unwrap_or_else(|| url.to_string());
+        format!("![{}]({})", alt, link_destination(&target))
+    }
+
+    fn list(&self, id: NodeId, ordered: bool) -> String {
+        let mut number: usize = self
+            .doc
+            .attr(id, "start")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        for &child in self.doc.children(id) {
+            if self.doc.name(child) != Some("li") || self.doc.skipped(child) {
+                continue;
+            }
+            let marker = match ordered {
+                true => format!("{}. ", number),
+                false => "- ".to_string(),
+            };
+            number += 1;
+            let indent = " ".repeat(marker.len());
+            let body = self.blocks_of(child).join("\n");
+            let mut lines = body.lines();
+            let mut item = format!("{}{}", marker, lines.next().unwrap_or_default())
+                .trim_end()
+                .to_string();
+            for line in lines {
+                item.push('\n');
+                if !line.is_empty() {
+                    item.push_str(&indent);
+                    item.push_str(line);
+                }
+            }
+            items.push(item);
+        }
+        items.join("\n")
+    }
+
+    fn code_block(&self, id: NodeId) -> String {
+        let text = self.doc.raw_text(id);
+        let text = text.strip_prefix('\n').unwrap_or(&text).trim_end();
+        if text.trim().is_empty() {
+            return String::new();
+        }
+        let code = self
+            .doc
+            .children(id)
+            .iter()
+            .copied()
+            .find(|&c| self.doc.name(c) == Some("code"));
+        let language = [Some(id), code]
+            .into_iter()
+            .flatten()
+            .filter_map(|node| self.doc.attr(node, "class"))
+            .flat_map(str::split_whitespace)
+            .find_map(|class| {
+                class
+                    .strip_prefix("language-")
+                    .or_else(|| class.strip_prefix("lang-"))
+            })
+            .unwrap_or_default();
+        let fence = "`".repeat(longest_run(text, '`').max(2) + 1);
+        format!("{}{}\n{}\n{}", fence, language, text, fence)
+    }
+
+    fn table(&self, id: NodeId) -> Option<String> {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            match self.doc.name(node) {
+                Some("table") if node != id => continue,
+                Some("tr") => {
+                    let cells = self
+                        .doc
+                        .children(node)
+                        .iter()
+                        .filter(|&&c| matches!(self.doc.name(c), Some("td" | "th")))
+                        .map(|&c| clean(&self.inlines(c)).replace('|', "\\|"))
+                        .collect();
+                    rows.push(cells);
+                }
+                _ => stack.extend(self.doc.children(node).iter().rev()),
+            }
+        }
+        let columns = rows.iter().map(Vec::len).max().filter(|&n| n > 0)?;
+        let line = |cells: &[String]| {
+            let mut padded: Vec<&str> = cells.iter().map(String::as_str).collect();
+            padded.resize(columns, "");
+            format!("| {} |", padded.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        Some(lines.join("\n"))
+    }
+}
+
+/// Finish a run of inline content as a paragraph.
+fn paragraph(run: &mut String, out: &mut Vec<String>) {
+    let lines: Vec<String> = run
+        .lines()
+        .map(str::trim)
+        .map(|line| {
+            // Text that would start a heading, quote, or list
+            let numbered = line
+                .find(". ")
+                .is_some_and(|dot| dot > 0 && line[..dot].chars().all(|c| c.is_ascii_digit()));
+            if line.starts_with(['#', '>', '+', '-']) || numbered {
+                match numbered {
+                    true => line.replacen(". ", "\\. ", 1),
+                    false => format!("\\{}", line),
+                }
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    let text = lines.join("\n");
+    let text = text.trim_matches('\n');
+    if !text.is_empty() {
+        out.push(text.to_string());
+    }
+    run.clear();
+}
+
+/// Whitespace runs as single spaces, keeping one at either end.
+fn collapse(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(c);
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `inner` between `marker`s, with its outer spaces kept outside them.
+fn wrap(inner: &str, marker: &str) -> String {
+    let trimmed = inner.trim();
+    if trimmed.is_empty() {
+        return inner.to_string();
+    }
+    let lead = if inner.starts_with(' ') { " " } else { "" };
+    let trail = if inner.ends_with(' ') { " " } else { "" };
+    format!("{}{}{}{}{}", lead, marker, trimmed, marker, trail)
+}
+
+fn code_span(text: &str) -> String {
+    let text = clean(text);
+    if text.is_empty() {
+        return String::new();
+    }
+    let fence = "`".repeat(longest_run(&text, '`') + 1);
+    let pad = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, pad, text, pad, fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in text.chars() {
+        run = if ch == c { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+// ─── Plain Text ─────────────────────────────────────────────────────────────
+
+impl Document {
+    /// The text inside `id`, with a blank line between blocks, a line per
+    /// `br`, and `- ` before list items.
+    pub fn plain_text(&self, id: NodeId) -> String {
+        let mut raw = String::new();
+        self.collect_plain(id, &mut raw);
+        let mut out = String::new();
+        let mut blank = false;
+        for line in raw.lines().map(clean) {
+            if line.is_empty() {
+                blank = true;
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+                if blank {
+                    out.push('\n');
+                }
+            }
+            blank = false;
+            out.push_str(&line);
+        }
+        out
+    }
+
+    fn collect_plain(&self, id: NodeId, out: &mut String) {
+        let name = match &self.nodes[id].data {
+            Data::Text(text) => return out.push_str(&text.replace(['\r', '\n'], " ")),
+            Data::Element { name, .. } => name.as_str(),
+        };
+        if SKIPPED.contains(&name) || self.hidden(id) {
+            return;
+        }
+        let (before, after) = match name {
+            "br" => ("\n", ""),
+            "li" => ("\n- ", ""),
+            "tr" => ("\n", ""),
+            "td" | "th" => (" ", " "),
+            name if BLOCK.contains(&name) => ("\n\n", "\n\n"),
+            _ => ("", ""),
+        };
+        out.push_str(before);
+        for &child in self.children(id) {
+            self.collect_plain(child, out);
+        }
+        out.push_str(after);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: &str = "Paragraphs with enough words, and a comma or two, count toward \
+        the score of the element that holds them.";
+
+    #[test]
+    fn parses_sloppy_markup_the_way_browsers_do() {
+        let doc = parse(
+            "<ul><li>One<li>Two <b>bold</ul><p>A<p>B<br>C</div>\
+             <script>if (a < b) {}</script><!-- <p>gone</p> -->",
+        );
+        let ul = doc.find("ul").unwrap();
+        let items: Vec<_> = doc.children(ul).iter().map(|&c| doc.name(c)).collect();
+        assert_eq!(items, vec![Some("li"), Some("li")]);
+        assert_eq!(doc.text(Document::ROOT), "One Two bold A B C");
+        let script = doc.find("script").unwrap();
+        assert_eq!(doc.raw_text(script), "if (a < b) {}");
+        assert_eq!(doc.find("p").map(|p| doc.text(p)).as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn finds_the_article_among_page_furniture() {
+        let html = format!(
+            "<body><div class=\"sidebar\"><p>{0}</p><p>{0}</p><p>{0}</p></div>\
+             <div id=\"story\"><p>{0}</p><p>{0}</p></div>\
+             <div class=\"links\"><p><a href=\"/a\">{0}</a></p></div></body>",
+            LONG
+        );
+        let doc = parse(&html);
+        let main = doc.main_content().unwrap();
+        assert_eq!(doc.attr(main, "id"), Some("story"));
+        assert_eq!(parse("<p>Too short.</p>").main_content(), None);
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let doc = parse(
+            "<div><h1>Title</h1>\
+             <p>Some <strong>bold</strong> and <em>it</em> with <a href=\"/x (1)\">a link</a>, \
+             <code>a`b</code> and 5 * 3_</p>\
+             <ul><li>One<ul><li>Nested</li></ul></li><li>Two</li></ul>\
+             <ol start=\"3\"><li>Three</li></ol>\
+             <blockquote><p>Quoted</p></blockquote>\
+             <pre><code class=\"language-rust\">fn main() {}\n</code></pre>\
+             <table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td>2|3</td></tr></table>\
+             <p><img data-src=\"/i.png\" src=\"data:image/gif;base64,R0\" alt=\"Pic\"></p>\
+             <p># not a heading</p></div>",
+        );
+        let base = Url::parse("https://e.com/post/").unwrap();
+        let div = doc.find("div").unwrap();
+        let image = Url::parse("https://e.com/i.png").unwrap();
+        assert_eq!(doc.image_urls(div, &base), vec![image.clone()]);
+        let local = |url: &Url| (*url == image).then(|| "local.png".to_string());
+        assert_eq!(
+            doc.to_markdown(div, &base, &local),
+            "# Title\n\n\
+             Some **bold** and *it* with [a link](https://e.com/x%20%281%29), ``a`b`` and 5 \\* 3\\_\n\n\
+             - One\n  - Nested\n- Two\n\n\
+             3. Three\n\n\
+             > Quoted\n\n\
+             ```rust\nfn main() {}\n```\n\n\
+             | A | B |\n| --- | --- |\n| 1 | 2\\|3 |\n\n\
+             ![Pic](local.png)\n\n\
+             \\# not a heading\n"
+        );
+    }
+}
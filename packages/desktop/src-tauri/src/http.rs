@@ -0,0 +1,477 @@
+// Outbound HTTP
+//
+// Every backend request (orchestrator calls, provider requests) starts from
+// `builder()` so proxy settings apply uniformly. Proxy credentials
+// are kept in the keychain and only joined back onto the URL here.
+//
+// Orchestrator calls use `orchestrator_client()`, which adds the self-hosted
+// TLS options (custom CA bundle, SPKI pin, invalid-cert escape hatch) and
+// records the certificate chain the server presented.
+//
+// Requests that are safe to repeat are sent with `http_retry::send`, which
+// adds retries and a per-host circuit breaker.
+
+use reqwest::{NoProxy, Proxy, Url};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::secrets;
+use crate::settings::{OrchestratorTlsSettings, ProxyMode, ProxySettings, Settings};
+
+const PROXY_CREDENTIALS_KEY: &str = "proxy-credentials";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+/// The proxy configuration actually in effect, for diagnostics.
+#[derive(Serialize, Clone)]
+pub struct EffectiveProxy {
+    pub mode: ProxyMode,
+    /// Proxy URL without credentials, when one is known.
+    pub url: Option<String>,
+    /// Where the URL came from: `settings`, `env`, `platform`, or `none`.
+    pub source: String,
+    pub bypass: Vec<String>,
+    pub has_credentials: bool,
+}
+
+/// Summary of one certificate in the chain an orchestrator presented.
+#[derive(Serialize, Clone)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    /// `sha256/<base64>` of the SubjectPublicKeyInfo, ready to paste as a pin.
+    pub spki_sha256: String,
+}
+
+/// Certificates seen during the most recent handshake of a client.
+pub type CapturedChain = Arc<Mutex<Vec<CertificateDer<'static>>>>;
+
+// ─── Client ─────────────────────────────────────────────────────────────────
+
+/// Client for orchestrator calls, with the orchestrator TLS settings applied.
+pub fn orchestrator_client(
+    settings: &Settings,
+) -> Result<(reqwest::Client, CapturedChain), String> {
+    orchestrator_client_from(builder(settings)?, settings)
+}
+
+/// `orchestrator_client` with `streaming_builder`'s timeouts, for transfers
+/// that can take longer than a request normally may.
+pub fn orchestrator_transfer_client(
+    settings: &Settings,
+) -> Result<(reqwest::Client, CapturedChain), String> {
+    orchestrator_client_from(streaming_builder(settings)?, settings)
+}
+
+fn orchestrator_client_from(
+    builder: reqwest::ClientBuilder,
+    settings: &Settings,
+) -> Result<(reqwest::Client, CapturedChain), String> {
+    let captured = CapturedChain::default();
+    let tls = tls_config(settings, captured.clone())?;
+    let client = builder
+        .use_preconfigured_tls(tls)
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok((client, captured))
+}
+
+/// Client builder with the proxy settings and default timeout applied.
+pub fn builder(settings: &Settings) -> Result<reqwest::ClientBuilder, String> {
+    Ok(proxied_builder(settings)?.timeout(DEFAULT_TIMEOUT))
+}
+
+/// For long-lived responses (SSE): no overall deadline, but a connection
+/// that goes quiet for `STREAM_IDLE_TIMEOUT` is treated as dropped.
+pub fn streaming_builder(settings: &Settings) -> Result<reqwest::ClientBuilder, String> {
+    Ok(proxied_builder(settings)?
+        .connect_timeout(DEFAULT_TIMEOUT)
+        .read_timeout(STREAM_IDLE_TIMEOUT))
+}
+
+fn proxied_builder(settings: &Settings) -> Result<reqwest::ClientBuilder, String> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("agentvbx-desktop/", env!("CARGO_PKG_VERSION")));
+
+    match settings.proxy.mode {
+        // reqwest reads HTTP(S)_PROXY / NO_PROXY and the platform settings
+        ProxyMode::System => {}
+        ProxyMode::None => builder = builder.no_proxy(),
+        ProxyMode::Manual => {
+            let url = proxy_url_with_credentials(&settings.proxy)?;
+            let proxy = Proxy::all(url)
+                .map_err(|e| format!("Invalid proxy URL: {}", e))?
+                .no_proxy(NoProxy::from_string(&settings.proxy.bypass.join(",")));
+            builder = builder.proxy(proxy);
+        }
+    }
+
+    Ok(builder)
+}
+
+// ─── Orchestrator TLS ───────────────────────────────────────────────────────
+
+fn tls_config(
+    settings: &Settings,
+    captured: CapturedChain,
+) -> Result<rustls::ClientConfig, String> {
+    let tls = &settings.orchestrator_tls;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = tls.ca_bundle_path.as_deref().filter(|p| !p.is_empty()) {
+        let added = add_ca_bundle(&mut roots, path)?;
+        log::info!("[http] trusting {} CA certificate(s) from {}", added, path);
+    }
+
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    if tls.allow_invalid_certs {
+        log::warn!(
+            "[http] allow_invalid_certs is ON — orchestrator certificates are NOT being validated ({})",
+            settings.orchestrator_url
+        );
+    }
+
+    let verifier = OrchestratorVerifier {
+        inner,
+        pin: pin_for(tls, &settings.orchestrator_url)?,
+        allow_invalid: tls.allow_invalid_certs,
+        captured,
+    };
+
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(config)
+}
+
+fn add_ca_bundle(roots: &mut RootCertStore, path: &str) -> Result<usize, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| format!("Cannot read CA bundle {}: {}", path, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid CA bundle {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("CA bundle {} contains no certificates", path));
+    }
+    let (added, _ignored) = roots.add_parsable_certificates(certs);
+    if added == 0 {
+        return Err(format!(
+            "CA bundle {} contains no usable certificates",
+            path
+        ));
+    }
+    Ok(added)
+}
+
+/// The pin applies to the orchestrator host only, never to other hosts the
+/// same client might be redirected to.
+fn pin_for(
+    tls: &OrchestratorTlsSettings,
+    orchestrator_url: &str,
+) -> Result<Option<(String, String)>, String> {
+    let Some(pin) = tls.spki_pin.as_deref().filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+    let host = Url::parse(orchestrator_url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .ok_or_else(|| format!("Cannot pin: invalid orchestrator URL {}", orchestrator_url))?;
+    let pin = pin.strip_prefix("sha256/").unwrap_or(pin).to_string();
+    Ok(Some((host, pin)))
+}
+
+#[derive(Debug)]
+struct OrchestratorVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pin: Option<(String, String)>,
+    allow_invalid: bool,
+    captured: CapturedChain,
+}
+
+impl ServerCertVerifier for OrchestratorVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Ok(mut chain) = self.captured.lock() {
+            *chain = std::iter::once(end_entity)
+                .chain(intermediates)
+                .map(|c| c.clone().into_owned())
+                .collect();
+        }
+
+        if !self.allow_invalid {
+            self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        if let Some((host, expected)) = &self.pin {
+            if server_name.to_str() == host.as_str() {
+                let actual = spki_sha256(end_entity).ok_or_else(|| {
+                    rustls::Error::General("unparseable server certificate".into())
+                })?;
+                if &actual != expected {
+                    return Err(rustls::Error::General(format!(
+                        "SPKI pin mismatch for {}: got sha256/{}",
+                        host, actual
+                    )));
+                }
+            }
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn spki_sha256(cert: &CertificateDer<'_>) -> Option<String> {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    let digest = Sha256::digest(parsed.tbs_certificate.subject_pki.raw);
+    Some(base64::engine::general_purpose::STANDARD.encode(digest))
+}
+
+pub fn describe_chain(captured: &CapturedChain) -> Vec<CertificateInfo> {
+    let chain = captured.lock().map(|c| c.clone()).unwrap_or_default();
+    chain
+        .iter()
+        .filter_map(|der| {
+            let (_, cert) = x509_parser::parse_x509_certificate(der.as_ref()).ok()?;
+            let validity = cert.validity();
+            Some(CertificateInfo {
+                subject: cert.subject().to_string(),
+                issuer: cert.issuer().to_string(),
+                not_before: asn1_to_rfc3339(validity.not_before.timestamp()),
+                not_after: asn1_to_rfc3339(validity.not_after.timestamp()),
+                spki_sha256: format!("sha256/{}", spki_sha256(der)?),
+            })
+        })
+        .collect()
+}
+
+fn asn1_to_rfc3339(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+// ─── Proxy ──────────────────────────────────────────────────────────────────
+
+/// Move any `user:pass@` in the proxy URL into the secret store so it never
+/// reaches config.json. A URL without credentials keeps the stored ones only
+/// while it points at the same proxy; clearing the URL or moving to another
+/// scheme, host or port clears them. Otherwise the store isn't touched.
+pub fn stash_proxy_credentials(
+    proxy: &mut ProxySettings,
+    previous: &ProxySettings,
+) -> Result<(), String> {
+    let Some(raw) = proxy.url.as_deref().filter(|u| !u.is_empty()) else {
+        proxy.url = None;
+        if previous.url.as_deref().is_some_and(|u| !u.is_empty()) {
+            return secrets::delete_app(PROXY_CREDENTIALS_KEY);
+        }
+        return Ok(());
+    };
+    let mut url = Url::parse(raw).map_err(|e| format!("Invalid proxy URL {}: {}", raw, e))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        return Err(format!("Unsupported proxy scheme: {}", url.scheme()));
+    }
+
+    if !url.username().is_empty() {
+        let credentials = format!("{}:{}", url.username(), url.password().unwrap_or(""));
+        secrets::store_app(PROXY_CREDENTIALS_KEY, &credentials)?;
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        proxy.url = Some(url.to_string());
+    } else if !same_proxy(&url, previous.url.as_deref()) {
+        // Credentials for the old proxy must not be sent to a new one
+        return secrets::delete_app(PROXY_CREDENTIALS_KEY);
+    }
+    Ok(())
+}
+
+/// Whether `url` and the `previous` proxy URL share scheme, host and port.
+/// No previous URL means nothing was stored for any proxy.
+fn same_proxy(url: &Url, previous: Option<&str>) -> bool {
+    let Some(previous) = previous.filter(|u| !u.is_empty()) else {
+        return true;
+    };
+    Url::parse(previous).is_ok_and(|previous| {
+        previous.scheme() == url.scheme()
+            && previous.host_str() == url.host_str()
+            && previous.port_or_known_default() == url.port_or_known_default()
+    })
+}
+
+fn proxy_url_with_credentials(proxy: &ProxySettings) -> Result<Url, String> {
+    let raw = proxy
+        .url
+        .as_deref()
+        .ok_or("Manual proxy mode requires a proxy URL")?;
+    let mut url = Url::parse(raw).map_err(|e| format!("Invalid proxy URL {}: {}", raw, e))?;
+
+    if let Some(credentials) = secrets::load_app(PROXY_CREDENTIALS_KEY)? {
+        let (user, pass) = credentials.split_once(':').unwrap_or((&credentials, ""));
+        let _ = url.set_username(user);
+        let _ = url.set_password((!pass.is_empty()).then_some(pass));
+    }
+    Ok(url)
+}
+
+pub fn effective_proxy(settings: &Settings) -> EffectiveProxy {
+    let proxy = &settings.proxy;
+    let has_credentials = matches!(secrets::load_app(PROXY_CREDENTIALS_KEY), Ok(Some(_)));
+
+    let (url, source) = match proxy.mode {
+        ProxyMode::Manual => (proxy.url.clone(), "settings".to_string()),
+        ProxyMode::None => (None, "none".to_string()),
+        ProxyMode::System => match env_proxy() {
+            Some(url) => (Some(redact_credentials(&url)), "env".to_string()),
+            None => (None, "platform".to_string()),
+        },
+    };
+
+    EffectiveProxy {
+        mode: proxy.mode,
+        url,
+        source,
+        bypass: proxy.bypass.clone(),
+        has_credentials,
+    }
+}
+
+/// Proxy to hand to a provider login webview for `target`, if any.
+///
+/// Only manual http/socks5 proxies are passed explicitly — in system mode the
+/// platform webviews already follow the OS settings. Webview proxies can't
+/// carry credentials, so authenticated proxies rely on the OS prompt.
+pub fn webview_proxy_url(settings: &Settings, target: &Url) -> Option<Url> {
+    let proxy = &settings.proxy;
+    if proxy.mode != ProxyMode::Manual {
+        return None;
+    }
+    let url = Url::parse(proxy.url.as_deref()?).ok()?;
+    if !matches!(url.scheme(), "http" | "socks5") {
+        return None;
+    }
+    let host = target.host_str()?;
+    if proxy.bypass.iter().any(|rule| bypass_matches(rule, host)) {
+        return None;
+    }
+    Some(url)
+}
+
+fn bypass_matches(rule: &str, host: &str) -> bool {
+    let rule = rule.trim().trim_start_matches('*').to_lowercase();
+    let host = host.to_lowercase();
+    if rule.is_empty() {
+        return false;
+    }
+    match rule.strip_prefix('.') {
+        Some(suffix) => host == suffix || host.ends_with(&rule),
+        None => host == rule || host.ends_with(&format!(".{}", rule)),
+    }
+}
+
+fn env_proxy() -> Option<String> {
+    [
+        "HTTPS_PROXY",
+        "https_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+        "ALL_PROXY",
+        "all_proxy",
+    ]
+    .iter()
+    .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()))
+}
+
+fn redact_credentials(raw: &str) -> String {
+    match Url::parse(raw) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => "<unparseable>".into(),
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(raw: &str) -> Url {
+        Url::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn stored_proxy_credentials_follow_scheme_host_and_port() {
+        let proxy = url("http://proxy.corp:8080");
+        assert!(same_proxy(&proxy, Some("http://proxy.corp:8080")));
+        assert!(same_proxy(&proxy, Some("http://PROXY.corp:8080/")));
+        assert!(same_proxy(
+            &url("http://proxy.corp"),
+            Some("http://proxy.corp:80")
+        ));
+        // Nothing can be stored without a previous URL
+        assert!(same_proxy(&proxy, None));
+        assert!(same_proxy(&proxy, Some("")));
+
+        assert!(!same_proxy(&proxy, Some("https://proxy.corp:8080")));
+        assert!(!same_proxy(&proxy, Some("http://other.corp:8080")));
+        assert!(!same_proxy(&proxy, Some("http://proxy.corp:3128")));
+        assert!(!same_proxy(&proxy, Some("not a url")));
+    }
+}
@@ -0,0 +1,462 @@
+// Retrying requests
+//
+// `send` is used in place of `RequestBuilder::send` for requests that can
+// safely be repeated, such as health checks and probes. It retries when the
+// connection fails or times out, and on 408, 429, 502, 503, and 504. It waits
+// as long as `Retry-After` asks, in seconds or as a date, up to
+// `http.max_retry_after_secs`. A longer wait isn't attempted, and the
+// response goes back to the caller. Without `Retry-After`, it backs off
+// exponentially from `http.retry_base_delay_ms`, with jitter so clients
+// don't retry in step.
+//
+// Each host has a circuit breaker. Connection failures, 429s, and 5xx
+// responses count against it, and any other response resets it. After
+// `http.circuit_failure_threshold` failures in a row the circuit opens, and
+// requests to that host fail at once with `Throttled` for
+// `http.circuit_cooldown_secs`. After that, requests are let through again,
+// and the first failure reopens the circuit. `get_diagnostics` lists every
+// host whose circuit isn't closed.
+//
+// Each attempt is logged at debug level with its status, the request and
+// response sizes, and its duration. Bodies and headers are never logged.
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{CommandError, ErrorCode};
+use crate::settings::HttpSettings;
+
+static CIRCUITS: Mutex<BTreeMap<String, Circuit>> = Mutex::new(BTreeMap::new());
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub max_retry_after: Duration,
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &HttpSettings) -> Self {
+        Self {
+            max_attempts: settings.max_attempts.max(1),
+            base_delay: Duration::from_millis(settings.retry_base_delay_ms),
+            max_delay: Duration::from_millis(settings.retry_max_delay_ms),
+            max_retry_after: Duration::from_secs(settings.max_retry_after_secs),
+            failure_threshold: settings.circuit_failure_threshold,
+            cooldown: Duration::from_secs(settings.circuit_cooldown_secs),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    /// Failing requests without sending them.
+    Open,
+    /// Cooled down; the next failure reopens it.
+    HalfOpen,
+}
+
+/// A host's circuit, for diagnostics.
+#[derive(Serialize, Clone, Debug)]
+pub struct CircuitStatus {
+    pub host: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub opened_at: Option<String>,
+}
+
+#[derive(Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    opened: Option<Opened>,
+}
+
+struct Opened {
+    at: Instant,
+    at_rfc3339: String,
+    cooldown: Duration,
+}
+
+#[derive(Debug)]
+pub enum SendError {
+    Request(reqwest::Error),
+    /// The host's circuit is open; nothing was sent.
+    CircuitOpen {
+        host: String,
+        retry_in: Duration,
+    },
+    /// The request's body is a stream, so it can't be sent twice.
+    NotRepeatable,
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Request(e) => e.fmt(f),
+            SendError::CircuitOpen { host, retry_in } => write!(
+                f,
+                "{} failed repeatedly; not retrying for {}s",
+                host,
+                retry_in.as_secs().max(1)
+            ),
+            SendError::NotRepeatable => write!(f, "Request body can't be retried"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SendError::Request(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<SendError> for CommandError {
+    fn from(e: SendError) -> Self {
+        match &e {
+            SendError::Request(inner) => {
+                let mut error = CommandError::new(ErrorCode::Network, e.to_string());
+                if let Some(url) = inner.url() {
+                    error = error.with("url", url.as_str());
+                }
+                error
+            }
+            SendError::CircuitOpen { host, retry_in } => {
+                CommandError::new(ErrorCode::Throttled, e.to_string())
+                    .with("reason", "circuit_open")
+                    .with("host", host)
+                    .with("retry_after_secs", retry_in.as_secs().max(1))
+                    .with("setting", "http.circuit_failure_threshold")
+            }
+            SendError::NotRepeatable => CommandError::new(ErrorCode::Internal, e.to_string()),
+        }
+    }
+}
+
+// ─── Sending ────────────────────────────────────────────────────────────────
+
+/// Send `request`, retrying and circuit breaking as `policy` says.
+pub async fn send(request: RequestBuilder, policy: &RetryPolicy) -> Result<Response, SendError> {
+    let mut attempt = 1;
+    loop {
+        let Some(this_try) = request.try_clone() else {
+            return Err(SendError::NotRepeatable);
+        };
+        let (client, built) = this_try.build_split();
+        let built = built.map_err(SendError::Request)?;
+        let host = host_key(built.url());
+        let method = built.method().clone();
+        let path = built.url().path().to_string();
+        let sent_bytes = built
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map_or(0, |b| b.len());
+        admit(&host, policy)?;
+
+        let started = Instant::now();
+        let result = client.execute(built).await;
+        let elapsed = started.elapsed().as_millis();
+        let last = attempt >= policy.max_attempts;
+        match result {
+            Ok(response) => {
+                let status = response.status();
+                log::debug!(
+                    "[http] {} {}{} -> {} ({} B sent, {} B received, {} ms, try {})",
+                    method,
+                    host,
+                    path,
+                    status.as_u16(),
+                    sent_bytes,
+                    response
+                        .content_length()
+                        .map_or("?".to_string(), |n| n.to_string()),
+                    elapsed,
+                    attempt
+                );
+                record(&host, counts_as_failure(status), policy);
+                if last || !retriable_status(status) {
+                    return Ok(response);
+                }
+                let delay = match retry_after(&response) {
+                    Some(wait) if wait > policy.max_retry_after => return Ok(response),
+                    Some(wait) => wait,
+                    None => backoff(policy, attempt, jitter()),
+                };
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => {
+                log::debug!(
+                    "[http] {} {}{} failed ({} B sent, {} ms, try {}): {}",
+                    method,
+                    host,
+                    path,
+                    sent_bytes,
+                    elapsed,
+                    attempt,
+                    e
+                );
+                let transient = e.is_connect() || e.is_timeout();
+                record(&host, transient, policy);
+                if last || !transient {
+                    return Err(SendError::Request(e));
+                }
+                tokio::time::sleep(backoff(policy, attempt, jitter())).await;
+            }
+        }
+        attempt += 1;
+    }
+}
+
+/// Every host whose circuit isn't closed.
+pub fn circuits() -> Vec<CircuitStatus> {
+    let circuits = CIRCUITS.lock().unwrap();
+    circuits
+        .iter()
+        .filter(|(_, c)| c.opened.is_some())
+        .map(|(host, c)| CircuitStatus {
+            host: host.clone(),
+            state: match &c.opened {
+                Some(opened) if opened.at.elapsed() < opened.cooldown => CircuitState::Open,
+                Some(_) => CircuitState::HalfOpen,
+                None => CircuitState::Closed,
+            },
+            consecutive_failures: c.consecutive_failures,
+            opened_at: c.opened.as_ref().map(|o| o.at_rfc3339.clone()),
+        })
+        .collect()
+}
+
+fn admit(host: &str, policy: &RetryPolicy) -> Result<(), SendError> {
+    let circuits = CIRCUITS.lock().unwrap();
+    if let Some(opened) = circuits.get(host).and_then(|c| c.opened.as_ref()) {
+        let open_for = opened.at.elapsed();
+        if open_for < policy.cooldown {
+            return Err(SendError::CircuitOpen {
+                host: host.to_string(),
+                retry_in: policy.cooldown - open_for,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn record(host: &str, failed: bool, policy: &RetryPolicy) {
+    let mut circuits = CIRCUITS.lock().unwrap();
+    if !failed {
+        circuits.remove(host);
+        return;
+    }
+    let circuit = circuits.entry(host.to_string()).or_default();
+    circuit.consecutive_failures += 1;
+    let threshold = policy.failure_threshold;
+    if threshold > 0 && circuit.consecutive_failures >= threshold {
+        if circuit.opened.is_none() {
+            log::warn!(
+                "[http] {} failed {} times in a row; pausing requests",
+                host,
+                circuit.consecutive_failures
+            );
+        }
+        // Half-open circuits reopen on their first failure
+        circuit.opened = Some(Opened {
+            at: Instant::now(),
+            at_rfc3339: chrono::Utc::now().to_rfc3339(),
+            cooldown: policy.cooldown,
+        });
+    }
+}
+
+fn host_key(url: &reqwest::Url) -> String {
+    match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        _ => url.as_str().to_string(),
+    }
+}
+
+fn retriable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 502 | 503 | 504)
+}
+
+fn counts_as_failure(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+    parse_retry_after(value, chrono::Utc::now())
+}
+
+/// Seconds, or an HTTP date (a date in the past means now).
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// The wait before retry number `attempt`: half the exponential delay, plus
+/// up to as much again scaled by `jitter` (0 to 1).
+fn backoff(policy: &RetryPolicy, attempt: u32, jitter: f64) -> Duration {
+    let exponential = policy
+        .base_delay
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(policy.max_delay);
+    exponential / 2 + exponential.mul_f64(jitter.clamp(0.0, 1.0) / 2.0)
+}
+
+fn jitter() -> f64 {
+    use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+    OsRng.next_u32() as f64 / u32::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockServer;
+
+    fn quick() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            max_retry_after: Duration::from_secs(2),
+            failure_threshold: 0,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+
+    fn get(url: &str) -> RequestBuilder {
+        reqwest::Client::new().get(url)
+    }
+
+    #[test]
+    fn retries_a_429_after_its_retry_after() {
+        let server = MockServer::start(vec![
+            MockServer::response(429, &[("Retry-After", "1")], ""),
+            MockServer::response(200, &[], "{\"ok\":true}"),
+        ]);
+        let started = Instant::now();
+        let response =
+            tauri::async_runtime::block_on(send(get(&server.url("/api/health")), &quick()))
+                .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts_or_a_long_retry_after() {
+        let server = MockServer::start(vec![MockServer::response(503, &[], "")]);
+        let response =
+            tauri::async_runtime::block_on(send(get(&server.url("/")), &quick())).unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(server.requests().len(), 3);
+
+        let server = MockServer::start(vec![MockServer::response(
+            429,
+            &[("Retry-After", "3600")],
+            "",
+        )]);
+        let response =
+            tauri::async_runtime::block_on(send(get(&server.url("/")), &quick())).unwrap();
+        assert_eq!(response.status(), 429);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let server = MockServer::start(vec![MockServer::response(404, &[], "")]);
+        let response =
+            tauri::async_runtime::block_on(send(get(&server.url("/")), &quick())).unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let server = MockServer::start(vec![MockServer::response(500, &[], "")]);
+        let policy = RetryPolicy {
+            failure_threshold: 2,
+            ..quick()
+        };
+        for _ in 0..2 {
+            let response =
+                tauri::async_runtime::block_on(send(get(&server.url("/")), &policy)).unwrap();
+            assert_eq!(response.status(), 500);
+        }
+        let err = tauri::async_runtime::block_on(send(get(&server.url("/")), &policy))
+            .err()
+            .unwrap();
+        assert!(matches!(err, SendError::CircuitOpen { .. }));
+        assert_eq!(server.requests().len(), 2);
+        let error = CommandError::from(err);
+        assert_eq!(error.code, ErrorCode::Throttled);
+        assert_eq!(error.params["reason"], "circuit_open");
+
+        // After the cooldown one request goes through and closes it again
+        let cooled = RetryPolicy {
+            cooldown: Duration::ZERO,
+            ..policy
+        };
+        let host = host_key(&reqwest::Url::parse(&server.url("/")).unwrap());
+        assert!(admit(&host, &cooled).is_ok());
+        record(&host, false, &cooled);
+        assert!(admit(&host, &policy).is_ok());
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-10-14T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 14 Oct 2026 12:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 14 Oct 2026 11:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            ..quick()
+        };
+        assert_eq!(backoff(&policy, 1, 0.0), Duration::from_millis(50));
+        assert_eq!(backoff(&policy, 1, 1.0), Duration::from_millis(100));
+        assert_eq!(backoff(&policy, 3, 0.0), Duration::from_millis(200));
+        assert_eq!(backoff(&policy, 10, 1.0), Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,111 @@
+// Localized messages
+//
+// Errors reach the frontend as a stable `code` plus `params` (see `error`).
+// Their `message` is English, for logs and as a last-resort fallback.
+// `get_error_catalog` returns a locale's message templates so the UI formats
+// every error the same way, filling each `{name}` in a template from
+// `params`. Strings Rust shows itself, such as window titles and health
+// warnings, go through `text` and the same catalog.
+//
+// Catalogs live in `locales/<lang>.json`, bundled at build time. A key
+// missing from a translation falls back to English. An unknown locale gets
+// the English catalog rather than an error.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::settings;
+
+const DEFAULT_LOCALE: &str = "en";
+
+const BUNDLED: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+];
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Catalog {
+    /// The locale served, after fallback.
+    pub locale: String,
+    /// Templates by error code.
+    pub errors: BTreeMap<String, String>,
+    /// Other backend strings by key.
+    pub strings: BTreeMap<String, String>,
+}
+
+#[tauri::command]
+pub fn get_error_catalog(locale: Option<String>) -> Catalog {
+    catalog(&locale.unwrap_or_else(current_locale))
+}
+
+/// `key` in the user's locale with `{name}` filled from `params`.
+pub fn text(key: &str, params: &[(&str, &str)]) -> String {
+    let catalog = catalog(&current_locale());
+    let template = catalog.strings.get(key).map(String::as_str).unwrap_or(key);
+    format(template, params)
+}
+
+/// The catalog for `requested`, matching `de-AT` or `de_AT` to `de`, with
+/// English filling any gaps.
+pub fn catalog(requested: &str) -> Catalog {
+    let mut catalog = parse(DEFAULT_LOCALE);
+    catalog.locale = DEFAULT_LOCALE.into();
+    let Some(locale) = match_locale(requested) else {
+        return catalog;
+    };
+    if locale != DEFAULT_LOCALE {
+        let translated = parse(locale);
+        catalog.errors.extend(translated.errors);
+        catalog.strings.extend(translated.strings);
+        catalog.locale = locale.into();
+    }
+    catalog
+}
+
+/// `settings.locale`, else the system's `LC_ALL`/`LC_MESSAGES`/`LANG`.
+pub fn current_locale() -> String {
+    if let Some(locale) = settings::load_settings().locale {
+        return locale;
+    }
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .unwrap_or_else(|| DEFAULT_LOCALE.into())
+}
+
+fn match_locale(requested: &str) -> Option<&'static str> {
+    // `de_DE.UTF-8` → `de-de`
+    let normalized = requested
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-")
+        .to_lowercase();
+    let language = normalized.split('-').next().unwrap_or_default();
+    BUNDLED
+        .iter()
+        .map(|(locale, _)| *locale)
+        .find(|locale| *locale == normalized || *locale == language)
+}
+
+fn parse(locale: &str) -> Catalog {
+    BUNDLED
+        .iter()
+        .find(|(name, _)| *name == locale)
+        .and_then(|(_, raw)| {
+            serde_json::from_str(raw)
+                .map_err(|e| log::error!("[i18n] bundled {} catalog is invalid: {}", locale, e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn format(template: &str, params: &[(&str, &str)]) -> String {
+    params
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
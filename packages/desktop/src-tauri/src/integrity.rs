@@ -0,0 +1,625 @@
+// Startup integrity check
+//
+// A hard crash can leave a data file truncated or a migration half done, and
+// either can stop the app from loading its data. `run` checks the data
+// directories (see `home`) before the Tauri app is built, so before the
+// window loads:
+// - Every `.json` file must parse. A corrupt one is replaced by its `.bak`
+//   if that parses. Otherwise it is quarantined: renamed to
+//   `<name>.corrupt-<timestamp>` so the app starts without it.
+// - A `.jsonl` file whose last line was cut off is trimmed to its last full line.
+// - SQLite files must have a valid header and a whole number of pages.
+// - Temp files left by an interrupted atomic write are removed.
+// - A migration leaves `migrations/<name>.in-progress` while it runs. If the
+//   marker is still there at startup, that migration's recovery step runs.
+//
+// The result is saved as `startup-report.json` and returned by
+// `get_startup_report`. When anything was repaired, `get_health` adds a
+// warning so the UI can show a banner.
+//
+// The registries are written with `persist_json`, which keeps the `.bak` copy
+// this relies on. It checks the value's `Validate` invariants, serializes it,
+// reads the bytes back as the same type and checks them again, and only then
+// replaces the file: through an fsynced temp file and a rename, after copying
+// the old file to `.bak`. `load_json` is the matching reader. When a file
+// doesn't parse it falls back to the `.bak` and counts the corruption in
+// `corruption-counts.json`, as startup repairs do. The counts are kept across
+// launches and shown in diagnostics.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::schema::Schema;
+use crate::{home, i18n, sessions};
+
+const REPORT_FILE: &str = "startup-report.json";
+const MIGRATIONS_DIR: &str = "migrations";
+const MARKER_SUFFIX: &str = ".in-progress";
+const TEMP_SUFFIX: &str = ".agentvbx-tmp";
+/// Deep enough for `tenants/<t>/whatsapp/*.json` and session metadata.
+const MAX_DEPTH: usize = 5;
+/// Not app state: the trash keeps user data as it was when deleted.
+const SKIP_DIRS: &[&str] = &["trash"];
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+const CORRUPTION_FILE: &str = "corruption-counts.json";
+
+/// Finishes or undoes an interrupted migration; `Ok` describes what it did.
+type Recovery = fn() -> Result<String, String>;
+
+/// Interrupted migrations and how to recover each.
+const MIGRATIONS: &[(&str, Recovery)] = &[(
+    sessions::LAYOUT_MIGRATION,
+    sessions::resume_layout_migration,
+)];
+
+static REPORT: Mutex<Option<StartupReport>> = Mutex::new(None);
+/// Serializes updates to `corruption-counts.json`.
+static COUNTS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct StartupReport {
+    pub ran_at: String,
+    pub files_checked: usize,
+    pub findings: Vec<Finding>,
+    /// Something was restored, quarantined, or resumed.
+    pub repaired: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Finding {
+    pub path: String,
+    pub action: RepairAction,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairAction {
+    RestoredFromBackup,
+    Quarantined,
+    Truncated,
+    TempRemoved,
+    MigrationResumed,
+    /// Recovery failed or the migration is unknown; the marker is kept.
+    MigrationFailed,
+}
+
+impl RepairAction {
+    fn is_repair(self) -> bool {
+        !matches!(self, RepairAction::TempRemoved)
+    }
+
+    /// The file itself was damaged, as opposed to an interrupted migration.
+    fn is_corruption(self) -> bool {
+        matches!(
+            self,
+            RepairAction::RestoredFromBackup | RepairAction::Quarantined | RepairAction::Truncated
+        )
+    }
+}
+
+/// How often one file has been found damaged, across launches.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct CorruptionCount {
+    pub count: u64,
+    /// Times the `.bak` copy was good and used instead.
+    pub recovered_from_backup: u64,
+    pub last_at: Option<String>,
+    pub last_error: String,
+}
+
+/// Invariants a data file must hold before it replaces the previous version.
+pub trait Validate {
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// List files are validated as slices and read back as `Vec`s.
+impl<T> Validate for Vec<T>
+where
+    [T]: Validate,
+{
+    fn validate(&self) -> Result<(), String> {
+        self.as_slice().validate()
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// The report from this launch's check.
+#[tauri::command]
+pub fn get_startup_report() -> Option<StartupReport> {
+    REPORT.lock().unwrap().clone()
+}
+
+// ─── Check ──────────────────────────────────────────────────────────────────
+
+/// Check and repair the data directory. Runs once, before the app is built.
+pub fn run() {
+    // Nothing to check; `home::check_at_startup` reports why
+    let Ok(dirs) = home::data_dirs() else {
+        return;
+    };
+    let mut report = StartupReport {
+        ran_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+
+    for path in dirs.distinct().into_iter().flat_map(data_files) {
+        report.files_checked += 1;
+        if let Some(finding) = check_file(&path) {
+            if finding.action.is_corruption() {
+                let recovered = finding.action == RepairAction::RestoredFromBackup;
+                record_corruption(&path, &finding.detail, recovered);
+            }
+            report.findings.push(finding);
+        }
+    }
+    resume_migrations(&dirs.data.join(MIGRATIONS_DIR), &mut report.findings);
+
+    report.repaired = report.findings.iter().any(|f| f.action.is_repair());
+    if let Ok(json) = serde_json::to_vec_pretty(&report) {
+        let _ = fs::create_dir_all(&dirs.state);
+        let _ = fs::write(dirs.state.join(REPORT_FILE), json);
+    }
+    *REPORT.lock().unwrap() = Some(report);
+}
+
+/// Log the findings; logging isn't set up yet when `run` runs.
+pub fn log_report() {
+    let Some(report) = get_startup_report() else {
+        return;
+    };
+    for finding in &report.findings {
+        log::warn!(
+            "[integrity] {}: {:?} ({})",
+            finding.path,
+            finding.action,
+            finding.detail
+        );
+    }
+    log::info!(
+        "[integrity] checked {} files, {} findings",
+        report.files_checked,
+        report.findings.len()
+    );
+}
+
+/// Health warning when this launch repaired anything.
+pub fn health_warning() -> Option<String> {
+    let report = get_startup_report()?;
+    report.repaired.then(|| {
+        let count = report
+            .findings
+            .iter()
+            .filter(|f| f.action.is_repair())
+            .count();
+        i18n::text("health.startup_repaired", &[("count", &count.to_string())])
+    })
+}
+
+fn data_files(root: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(root)
+        .max_depth(MAX_DEPTH)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() != 1
+                || !e.file_type().is_dir()
+                || !SKIP_DIRS.contains(&e.file_name().to_string_lossy().as_ref())
+        })
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            name.ends_with(TEMP_SUFFIX)
+                || matches!(
+                    p.extension().and_then(|e| e.to_str()),
+                    Some("json" | "jsonl" | "db" | "sqlite" | "sqlite3")
+                )
+        })
+        .collect()
+}
+
+fn check_file(path: &Path) -> Option<Finding> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    if name.ends_with(TEMP_SUFFIX) {
+        fs::remove_file(path).ok()?;
+        return Some(finding(
+            path,
+            RepairAction::TempRemoved,
+            "left by an interrupted write",
+        ));
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => {
+            let problem = json_problem(path)?;
+            Some(restore_or_quarantine(path, &problem))
+        }
+        Some("jsonl") => trim_jsonl(path),
+        _ => {
+            let problem = sqlite_problem(path)?;
+            Some(quarantine(path, &problem))
+        }
+    }
+}
+
+fn json_problem(path: &Path) -> Option<String> {
+    let raw = match fs::read(path) {
+        Ok(raw) => raw,
+        Err(e) => return Some(e.to_string()),
+    };
+    if raw.iter().all(u8::is_ascii_whitespace) {
+        return Some("empty".into());
+    }
+    serde_json::from_slice::<serde::de::IgnoredAny>(&raw)
+        .err()
+        .map(|e| e.to_string())
+}
+
+fn restore_or_quarantine(path: &Path, problem: &str) -> Finding {
+    let backup = backup_path(path);
+    if backup.is_file() && json_problem(&backup).is_none() && fs::copy(&backup, path).is_ok() {
+        return finding(
+            path,
+            RepairAction::RestoredFromBackup,
+            &format!("{}; restored {}", problem, backup.display()),
+        );
+    }
+    quarantine(path, problem)
+}
+
+fn quarantine(path: &Path, problem: &str) -> Finding {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = path.with_file_name(format!("{}.corrupt-{}", name, stamp));
+    match fs::rename(path, &target) {
+        Ok(()) => finding(
+            path,
+            RepairAction::Quarantined,
+            &format!("{}; moved to {}", problem, target.display()),
+        ),
+        Err(e) => finding(
+            path,
+            RepairAction::Quarantined,
+            &format!("{}; could not move aside: {}", problem, e),
+        ),
+    }
+}
+
+/// Drop a torn last line. Damage anywhere else is left for the reader,
+/// which already skips lines it can't parse.
+fn trim_jsonl(path: &Path) -> Option<Finding> {
+    let raw = fs::read(path).ok()?;
+    let body = raw.strip_suffix(b"\n").unwrap_or(&raw);
+    let start = body.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let last = &body[start..];
+    if last.is_empty() || serde_json::from_slice::<serde::de::IgnoredAny>(last).is_ok() {
+        return None;
+    }
+    fs::write(path, &raw[..start]).ok()?;
+    Some(finding(
+        path,
+        RepairAction::Truncated,
+        &format!("dropped an incomplete last line ({} bytes)", last.len()),
+    ))
+}
+
+fn sqlite_problem(path: &Path) -> Option<String> {
+    let size = fs::metadata(path).ok()?.len();
+    let mut header = [0u8; 100];
+    let read = fs::File::open(path).and_then(|mut f| f.read_exact(&mut header));
+    if size == 0 {
+        // SQLite creates empty files itself
+        return None;
+    }
+    if read.is_err() || !header.starts_with(SQLITE_HEADER) {
+        return Some("not a SQLite database header".into());
+    }
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        1 => 65536,
+        n => n as u64,
+    };
+    if page_size < 512 || size % page_size != 0 {
+        return Some(format!(
+            "{} bytes is not a whole number of {}-byte pages",
+            size, page_size
+        ));
+    }
+    None
+}
+
+fn finding(path: &Path, action: RepairAction, detail: &str) -> Finding {
+    Finding {
+        path: path.to_string_lossy().to_string(),
+        action,
+        detail: detail.to_string(),
+    }
+}
+
+// ─── Migrations ─────────────────────────────────────────────────────────────
+
+/// Marks a migration as running until `finish`. Dropping it without
+/// finishing, or crashing, leaves the marker for the next startup.
+pub struct Migration {
+    marker: PathBuf,
+}
+
+impl Migration {
+    pub fn begin(name: &str) -> Result<Self, String> {
+        let dir = home::data_dir()?.join(MIGRATIONS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let marker = dir.join(format!("{}{}", name, MARKER_SUFFIX));
+        fs::write(&marker, chrono::Utc::now().to_rfc3339()).map_err(|e| e.to_string())?;
+        Ok(Self { marker })
+    }
+
+    pub fn finish(self) {
+        let _ = fs::remove_file(&self.marker);
+    }
+}
+
+fn resume_migrations(dir: &Path, findings: &mut Vec<Finding>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let marker = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(name) = file_name.strip_suffix(MARKER_SUFFIX) else {
+            continue;
+        };
+        let Some((_, recover)) = MIGRATIONS.iter().find(|(n, _)| *n == name) else {
+            findings.push(finding(
+                &marker,
+                RepairAction::MigrationFailed,
+                "unknown migration",
+            ));
+            continue;
+        };
+        match recover() {
+            Ok(detail) => {
+                let _ = fs::remove_file(&marker);
+                findings.push(finding(&marker, RepairAction::MigrationResumed, &detail));
+            }
+            Err(e) => findings.push(finding(&marker, RepairAction::MigrationFailed, &e)),
+        }
+    }
+}
+
+// ─── Backups ────────────────────────────────────────────────────────────────
+
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.bak", name))
+}
+
+/// Write `path` atomically, first copying the current file to `.bak` if it
+/// parses, so `.bak` is always the last good version.
+pub fn write_with_backup(path: &Path, bytes: &[u8]) -> CommandResult<()> {
+    if path.is_file() && json_problem(path).is_none() {
+        fs::copy(path, backup_path(path))?;
+    }
+    write_synced(path, bytes)
+}
+
+/// Like `notes::write_atomic`, but the data is on disk before the rename, so
+/// a power cut leaves either the old file or the new one.
+fn write_synced(path: &Path, bytes: &[u8]) -> CommandResult<()> {
+    let temp = write_staged(path, bytes)?;
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    // The rename itself is durable once the directory is synced
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = File::open(dir).and_then(|d| d.sync_all());
+    }
+    Ok(())
+}
+
+/// Where `write_staged` puts the next version of `path`. Startup removes
+/// any left behind.
+pub fn staged_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}{}", name, TEMP_SUFFIX))
+}
+
+/// Write and sync `bytes` beside `path` without replacing it, for callers
+/// that rename several files into place together. Returns the staged path.
+pub fn write_staged(path: &Path, bytes: &[u8]) -> CommandResult<PathBuf> {
+    let temp = staged_path(path);
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(temp)
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+/// Validate, serialize, check the round trip, and write `value` to `path`
+/// with a `.bak` of the previous version. `value` may be borrowed, such as
+/// a slice for a list file; it's read back as its owned form. With a
+/// `schema` the file is versioned and a newer one is never replaced.
+pub fn persist_json<T>(path: &Path, value: &T, schema: Option<&Schema>) -> CommandResult<()>
+where
+    T: Serialize + Validate + ToOwned + ?Sized,
+    T::Owned: DeserializeOwned + Validate,
+{
+    let invalid = |reason: String| {
+        CommandError::new(
+            ErrorCode::Internal,
+            format!("Refusing to write invalid {}: {}", path.display(), reason),
+        )
+        .with("path", path.to_string_lossy())
+        .with("reason", reason)
+    };
+    value.validate().map_err(invalid)?;
+    let bytes = match schema {
+        Some(schema) => {
+            schema.guard_path(path)?;
+            schema.encode(value, true)?
+        }
+        None => serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?,
+    };
+    let read_back: T::Owned = decode(&bytes, schema).map_err(invalid)?;
+    read_back.validate().map_err(invalid)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_with_backup(path, &bytes)
+}
+
+/// Read a file written by `persist_json`. A missing file is `None`; one that
+/// doesn't parse is counted as corrupt and replaced by its `.bak`, if that
+/// parses.
+pub fn load_json<T: DeserializeOwned>(path: &Path, schema: Option<&Schema>) -> Option<T> {
+    let problem = match fs::read(path) {
+        Ok(raw) => match decode(&raw, schema) {
+            Ok(value) => return Some(value),
+            Err(e) => e,
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => e.to_string(),
+    };
+    let backup = fs::read(backup_path(path))
+        .ok()
+        .and_then(|raw| decode(&raw, schema).ok());
+    log::warn!(
+        "[integrity] {} is unreadable ({}); {}",
+        path.display(),
+        problem,
+        if backup.is_some() {
+            "using its backup"
+        } else {
+            "no usable backup"
+        }
+    );
+    record_corruption(path, &problem, backup.is_some());
+    backup
+}
+
+fn decode<T: DeserializeOwned>(raw: &[u8], schema: Option<&Schema>) -> Result<T, String> {
+    match schema {
+        Some(schema) => schema.decode(raw),
+        None => serde_json::from_slice(raw).map_err(|e| e.to_string()),
+    }
+}
+
+// ─── Corruption counts ──────────────────────────────────────────────────────
+
+/// Damaged-file counts by path under the data directories, for diagnostics.
+pub fn corruption_counts() -> BTreeMap<String, CorruptionCount> {
+    home::data_dirs()
+        .ok()
+        .and_then(|dirs| fs::read(dirs.state.join(CORRUPTION_FILE)).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn record_corruption(path: &Path, problem: &str, recovered: bool) {
+    let Ok(state) = home::state_dir() else {
+        return;
+    };
+    let _guard = COUNTS_LOCK.lock().unwrap();
+    let mut counts = corruption_counts();
+    let key = home::data_dirs()
+        .ok()
+        .and_then(|dirs| {
+            let roots = dirs.distinct();
+            let root = roots.iter().find(|root| path.starts_with(root))?;
+            Some(path.strip_prefix(root).ok()?.to_path_buf())
+        })
+        .unwrap_or_else(|| path.to_path_buf());
+    let entry = counts
+        .entry(key.to_string_lossy().replace('\\', "/"))
+        .or_default();
+    entry.count += 1;
+    entry.recovered_from_backup += recovered as u64;
+    entry.last_at = Some(chrono::Utc::now().to_rfc3339());
+    entry.last_error = problem.to_string();
+    let written = serde_json::to_vec_pretty(&counts)
+        .map_err(|e| CommandError::from(e.to_string()))
+        .and_then(|json| write_synced(&state.join(CORRUPTION_FILE), &json));
+    if let Err(e) = written {
+        log::warn!("[integrity] failed to record corruption: {}", e.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+    struct Registry {
+        names: Vec<String>,
+    }
+
+    impl Validate for Registry {
+        fn validate(&self) -> Result<(), String> {
+            if self.names.iter().any(String::is_empty) {
+                return Err("empty name".into());
+            }
+            Ok(())
+        }
+    }
+
+    fn registry(names: &[&str]) -> Registry {
+        Registry {
+            names: names.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn persist_keeps_the_previous_version_and_refuses_invalid_values() {
+        let home = TestHome::new();
+        let path = home.agentvbx().join("registry.json");
+        persist_json(&path, &registry(&["a"]), None).unwrap();
+        persist_json(&path, &registry(&["a", "b"]), None).unwrap();
+        assert_eq!(load_json(&backup_path(&path), None), Some(registry(&["a"])));
+
+        let err = persist_json(&path, &registry(&["a", ""]), None).unwrap_err();
+        assert_eq!(err.params["reason"], "empty name");
+        assert_eq!(load_json(&path, None), Some(registry(&["a", "b"])));
+        // Nothing left over from the refused or completed writes
+        let temp = path.with_file_name(format!(".registry.json{}", TEMP_SUFFIX));
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn loads_fall_back_to_the_backup_and_are_counted() {
+        let home = TestHome::new();
+        let path = home.agentvbx().join("registry.json");
+        persist_json(&path, &registry(&["a"]), None).unwrap();
+        persist_json(&path, &registry(&["a", "b"]), None).unwrap();
+        fs::write(&path, "null").unwrap();
+
+        assert_eq!(load_json(&path, None), Some(registry(&["a"])));
+        fs::remove_file(backup_path(&path)).unwrap();
+        assert_eq!(load_json::<Registry>(&path, None), None);
+        assert_eq!(
+            load_json::<Registry>(&home.join("missing.json"), None),
+            None
+        );
+
+        let counts = corruption_counts();
+        let count = &counts["registry.json"];
+        assert_eq!((count.count, count.recovered_from_backup), (2, 1));
+        assert!(count.last_at.is_some());
+        assert_eq!(counts.len(), 1);
+    }
+}
@@ -0,0 +1,285 @@
+// IO throttling
+//
+// Background work paces its disk reads so a rescan of a big store doesn't
+// spin up fans or starve the UI. An operation that runs at background
+// priority takes an `IoThrottle` from `OperationHandle::background_io` and
+// runs its work under `scoped`. Everything it reads on that thread then counts
+// against two limits from `settings.io_throttle`:
+// - a bytes-per-second ceiling, enforced by sleeping once reads get ahead of
+//   it;
+// - a pause after each file.
+// Reads count through `Paced` (file hashing, file ids) or `charge`, and files
+// end with `file_done`. Both are free outside `scoped`, so the same code runs
+// unpaced for interactive callers.
+//
+// On battery, or in battery saver, the stricter `battery_*` limits apply.
+// `boost_operation` lifts the limits on one operation for when the user is
+// waiting on it; a boosted throttle stops sleeping at once. Operations show
+// their effective limits as `io_limits` in `list_operations`.
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{settings, system_state};
+
+/// Reads may run this far ahead of the ceiling after a quiet spell.
+const MAX_CREDIT: Duration = Duration::from_secs(1);
+/// Longest single sleep, so a boost takes effect promptly.
+const SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+thread_local! {
+    static CURRENT: RefCell<Option<IoThrottle>> = const { RefCell::new(None) };
+}
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+/// The limits an operation runs under.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IoLimits {
+    /// 0 is unlimited.
+    pub bytes_per_second: u64,
+    pub file_pause_ms: u64,
+    /// The battery limits were chosen.
+    pub on_battery: bool,
+    pub boosted: bool,
+}
+
+impl IoLimits {
+    /// Background limits for the current power state.
+    pub fn background() -> Self {
+        let state = system_state::current();
+        let on_battery = state.on_battery == Some(true) || state.battery_saver;
+        Self::from_settings(&settings::load_settings().io_throttle, on_battery)
+    }
+
+    pub fn from_settings(settings: &settings::IoThrottleSettings, on_battery: bool) -> Self {
+        if !on_battery {
+            return Self {
+                bytes_per_second: settings.bytes_per_second,
+                file_pause_ms: settings.file_pause_ms,
+                on_battery,
+                boosted: false,
+            };
+        }
+        Self {
+            bytes_per_second: stricter(
+                settings.bytes_per_second,
+                settings.battery_bytes_per_second,
+            ),
+            file_pause_ms: settings.file_pause_ms.max(settings.battery_file_pause_ms),
+            on_battery,
+            boosted: false,
+        }
+    }
+
+    /// What a boost leaves: no limits at all.
+    pub fn boosted(self) -> Self {
+        Self {
+            bytes_per_second: 0,
+            file_pause_ms: 0,
+            boosted: true,
+            ..self
+        }
+    }
+}
+
+/// The lower ceiling, where 0 is unlimited.
+fn stricter(a: u64, b: u64) -> u64 {
+    match (a, b) {
+        (0, limit) | (limit, 0) => limit,
+        (a, b) => a.min(b),
+    }
+}
+
+/// Paces reads against one set of limits.
+pub struct IoThrottle {
+    limits: IoLimits,
+    /// Set by `boost_operation`.
+    boost: Arc<AtomicBool>,
+    window_start: Instant,
+    window_bytes: u64,
+}
+
+impl IoThrottle {
+    pub fn new(limits: IoLimits, boost: Arc<AtomicBool>) -> Self {
+        Self {
+            limits,
+            boost,
+            window_start: Instant::now(),
+            window_bytes: 0,
+        }
+    }
+
+    fn is_boosted(&self) -> bool {
+        self.limits.boosted || self.boost.load(Ordering::Relaxed)
+    }
+
+    /// Count `bytes` read, sleeping while reads are ahead of the ceiling.
+    pub fn charge(&mut self, bytes: u64) {
+        let rate = self.limits.bytes_per_second;
+        if rate == 0 || self.is_boosted() {
+            return;
+        }
+        let due = |bytes: u64| Duration::from_secs_f64(bytes as f64 / rate as f64);
+        if self.window_start.elapsed() > due(self.window_bytes) + MAX_CREDIT {
+            self.window_start = Instant::now();
+            self.window_bytes = 0;
+        }
+        self.window_bytes += bytes;
+        let ahead = due(self.window_bytes).saturating_sub(self.window_start.elapsed());
+        self.sleep(ahead);
+    }
+
+    /// The pause between files.
+    pub fn file_done(&mut self) {
+        if !self.is_boosted() {
+            self.sleep(Duration::from_millis(self.limits.file_pause_ms));
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        loop {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() || self.is_boosted() {
+                return;
+            }
+            std::thread::sleep(left.min(SLEEP_SLICE));
+        }
+    }
+}
+
+// ─── Scope ──────────────────────────────────────────────────────────────────
+
+/// Run `work` with reads on this thread paced by `throttle`.
+pub fn scoped<T>(throttle: IoThrottle, work: impl FnOnce() -> T) -> T {
+    struct Restore(Option<IoThrottle>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CURRENT.with(|current| current.borrow_mut().replace(throttle)));
+    work()
+}
+
+/// Count `bytes` read against the scoped throttle, if any.
+pub fn charge(bytes: u64) {
+    CURRENT.with(|current| {
+        if let Some(throttle) = current.borrow_mut().as_mut() {
+            throttle.charge(bytes);
+        }
+    });
+}
+
+/// End a file under the scoped throttle, if any.
+pub fn file_done() {
+    CURRENT.with(|current| {
+        if let Some(throttle) = current.borrow_mut().as_mut() {
+            throttle.file_done();
+        }
+    });
+}
+
+/// A reader whose reads count against the scoped throttle.
+pub struct Paced<R>(pub R);
+
+impl<R: Read> Read for Paced<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buf)?;
+        charge(read as u64);
+        Ok(read)
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn hash_all(home: &TestHome) -> Duration {
+        let started = Instant::now();
+        for file in [
+            "Reports/q1.pdf",
+            "Reports/Summary.md",
+            "Reports/archive/q4.pdf",
+        ] {
+            crate::file_read::hash(&home.join(file)).unwrap();
+        }
+        started.elapsed()
+    }
+
+    fn limits(bytes_per_second: u64, file_pause_ms: u64) -> IoLimits {
+        IoLimits {
+            bytes_per_second,
+            file_pause_ms,
+            on_battery: false,
+            boosted: false,
+        }
+    }
+
+    #[test]
+    fn throttled_hashing_takes_measurably_longer() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        home.write("Reports/Summary.md", &"x".repeat(64 * 1024));
+        let unthrottled = hash_all(&home);
+
+        let throttle = IoThrottle::new(limits(256 * 1024, 20), Arc::default());
+        let throttled = scoped(throttle, || hash_all(&home));
+
+        // 64 KiB at 256 KiB/s, plus three 20ms pauses
+        assert!(throttled >= Duration::from_millis(250), "{:?}", throttled);
+        assert!(
+            throttled > unthrottled * 2,
+            "{:?} vs {:?}",
+            throttled,
+            unthrottled
+        );
+    }
+
+    #[test]
+    fn boost_stops_pacing() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        home.write("Reports/Summary.md", &"x".repeat(64 * 1024));
+        let boost = Arc::new(AtomicBool::new(true));
+        let throttle = IoThrottle::new(limits(1024, 1_000), boost);
+
+        assert!(scoped(throttle, || hash_all(&home)) < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn the_throttle_is_left_behind_with_its_scope() {
+        scoped(IoThrottle::new(limits(1, 0), Arc::default()), || {});
+        let started = Instant::now();
+        charge(1024 * 1024);
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn battery_limits_are_never_looser() {
+        let settings = settings::IoThrottleSettings {
+            bytes_per_second: 0,
+            file_pause_ms: 10,
+            battery_bytes_per_second: 4 * 1024 * 1024,
+            battery_file_pause_ms: 5,
+        };
+        let plugged = IoLimits::from_settings(&settings, false);
+        assert_eq!((plugged.bytes_per_second, plugged.file_pause_ms), (0, 10));
+
+        let battery = IoLimits::from_settings(&settings, true);
+        assert!(battery.on_battery);
+        assert_eq!(
+            (battery.bytes_per_second, battery.file_pause_ms),
+            (4 * 1024 * 1024, 10)
+        );
+        assert_eq!(battery.boosted().bytes_per_second, 0);
+    }
+}
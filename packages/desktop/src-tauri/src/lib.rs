@@ -5,18 +5,126 @@
 //    captures session cookies for the session adapter layer
 // 2. File access — local filesystem, Obsidian vault discovery, cloud storage
 // 3. WhatsApp bridge — QR code scan to link WhatsApp account
-// 4. Orchestrator bridge — connects to local or remote API server
+// 4. Orchestrator bridge — connects to local or remote API server, honoring
+//    the configured HTTP/SOCKS proxy
 //
 // The Rust backend provides native capabilities that the webview can't:
 // - Filesystem scanning and file reading
 // - Obsidian vault discovery (scan for .obsidian directories)
-// - Session data directory management
+// - Session data directory management (nested tenant/provider layout)
+// - Provider requests authenticated with stored session cookies
 // - Content hashing for artifact versioning
 
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
-use tauri::Manager;
+
+use error::{CommandError, CommandResult, ErrorCode};
+use operations::{OperationHandle, OperationKind, Operations};
+
+mod activity;
+mod app_files;
+mod artifact_download;
+mod artifact_metadata;
+mod artifact_upload;
+mod asset_protocol;
+mod audio_upload;
+mod audit;
+mod automation;
+mod backup;
+mod bookmarks;
+mod bulk_frontmatter;
+mod bulk_rename;
+mod calendar;
+mod canvas;
+mod collation;
+mod crash_reports;
+mod devtools;
+mod diagnostics;
+mod disk_space;
+mod dry_run;
+mod email;
+mod error;
+mod event_journal;
+mod exports;
+mod file_ids;
+mod file_query;
+mod file_read;
+mod filenames;
+mod fingerprint;
+mod frontmatter;
+mod fs_deadline;
+mod git;
+mod home;
+mod html;
+mod http;
+mod http_retry;
+mod i18n;
+mod integrity;
+mod io_throttle;
+mod local_orchestrator;
+mod maintenance;
+mod mime_map;
+mod mock_provider;
+mod note_counts;
+mod note_encryption;
+mod note_links;
+mod note_stats;
+mod notes;
+mod obsidian_bookmarks;
+mod operations;
+mod orchestrator;
+mod pdf_preview;
+mod platform;
+mod policy;
+mod power;
+mod previews;
+mod provider_cache;
+mod provider_fetch;
+mod provider_stream;
+mod provider_usage;
+mod providers;
+mod quarantine;
+mod quick_capture;
+mod read_only;
+mod recipes;
+mod redaction;
+mod rollups;
+mod scheduler;
+mod schema;
+mod secrets;
+mod session_expiry;
+mod session_profile;
+mod sessions;
+mod settings;
+mod similar_notes;
+mod snippets;
+mod startup;
+mod store_health;
+mod store_kinds;
+mod store_relink;
+mod stores;
+mod sync_rules;
+mod system_state;
+mod task_inbox;
+mod tasks;
+mod templates;
+mod tenant_config;
+mod tenant_windows;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod text_style;
+mod trash;
+mod url_metadata;
+mod vault;
+mod vault_graph;
+mod vault_packs;
+mod web_archive;
+mod whatsapp;
+mod whatsapp_media;
+mod whatsapp_send;
+mod workspaces;
+mod write_guard;
 
 // ─── Types ──────────────────────────────────────────────────────────────────
 
@@ -25,6 +133,11 @@ struct HealthInfo {
     status: String,
     version: String,
     platform: String,
+    /// Configuration the user should know is unsafe or degraded.
+    warnings: Vec<String>,
+    vault: vault::VaultStatus,
+    read_only: read_only::ReadOnlyStatus,
+    maintenance: maintenance::MaintenanceStatus,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -35,6 +148,12 @@ struct FileEntry {
     size_bytes: u64,
     modified_at: String,
     mime_type: String,
+    /// An encrypted note; see `note_encryption`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    encrypted: bool,
+    /// The opening lines of a text file, with `include_snippets`; see
+    /// `snippets`.
+    snippet: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -42,62 +161,110 @@ struct ObsidianVault {
     name: String,
     path: String,
     note_count: usize,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct ProviderLoginConfig {
-    provider_id: String,
-    login_url: String,
-    success_indicators: Vec<String>,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct ConnectedStore {
-    id: String,
-    name: String,
-    store_type: String,
-    path: String,
-    file_count: usize,
+    /// `note_count` is from the cache and may be out of date; a
+    /// `vault-note-count-updated` event follows with the current count.
+    count_stale: bool,
 }
 
 // ─── Core Commands ──────────────────────────────────────────────────────────
 
 #[tauri::command]
-fn get_health() -> serde_json::Value {
-    serde_json::json!({
-        "status": "healthy",
-        "version": env!("CARGO_PKG_VERSION"),
-        "platform": std::env::consts::OS,
-    })
+fn get_health(
+    read_only: tauri::State<'_, read_only::ReadOnlyMode>,
+    maintenance: tauri::State<'_, maintenance::MaintenanceMode>,
+) -> HealthInfo {
+    let settings = settings::load_settings();
+    let mut warnings = Vec::new();
+    if settings.orchestrator_tls.allow_invalid_certs {
+        warnings.push(i18n::text(
+            "health.tls_disabled",
+            &[("url", &settings.orchestrator_url)],
+        ));
+    }
+    warnings.extend(integrity::health_warning());
+    let home = home::data_dirs();
+    if home.is_err() {
+        warnings.push(i18n::text("health.home_unavailable", &[]));
+    }
+
+    HealthInfo {
+        status: if home.is_ok() { "healthy" } else { "degraded" }.into(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        platform: std::env::consts::OS.into(),
+        warnings,
+        vault: vault::status(),
+        read_only: read_only.status(),
+        maintenance: maintenance.status(),
+    }
 }
 
 #[tauri::command]
-fn get_tenant_path(tenant_id: String) -> String {
-    let home = agentvbx_home();
-    format!("{}/tenants/{}", home, tenant_id)
+fn get_tenant_path(tenant_id: String) -> CommandResult<String> {
+    let path = home::data_dir()?.join("tenants").join(tenant_id);
+    Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-fn get_sessions_path() -> String {
-    let home = agentvbx_home();
-    format!("{}/sessions", home)
+fn get_sessions_path() -> CommandResult<String> {
+    Ok(home::data_dir()?
+        .join("sessions")
+        .to_string_lossy()
+        .to_string())
 }
 
 // ─── File Store Commands ────────────────────────────────────────────────────
 
 /// List files in a directory (for the file store connection flow).
+///
+/// This and the other file commands give up with `Timeout` after
+/// `timeout_ms`; see `fs_deadline`. `include_snippets` adds the opening
+/// lines of text files, up to `snippet_bytes`.
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
-    let dir = Path::new(&path);
-    if !dir.exists() {
-        return Err(format!("Directory not found: {}", path));
-    }
-    if !dir.is_dir() {
-        return Err(format!("Not a directory: {}", path));
-    }
+async fn list_directory(
+    path: String,
+    store_rules: Option<bool>,
+    timeout_ms: Option<u64>,
+    natural_sort: Option<bool>,
+    sort: Option<collation::SortOrder>,
+    include_snippets: Option<bool>,
+    snippet_bytes: Option<usize>,
+) -> CommandResult<Vec<FileEntry>> {
+    let dir = PathBuf::from(&path);
+    let collator = collation::Collator::for_listing(sort, natural_sort, None);
+    fs_deadline::run("list_directory", &dir.clone(), timeout_ms, move || {
+        if !dir.exists() {
+            return Err(CommandError::new(
+                ErrorCode::NotFound,
+                format!("Directory not found: {}", path),
+            )
+            .with("path", &path));
+        }
+        if !dir.is_dir() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Not a directory: {}", path),
+            )
+            .with("path", &path));
+        }
+        let mut entries = list_entries(&dir, &collator)?;
+        // Hide what the containing store's sync rules leave out
+        if store_rules.unwrap_or(false) {
+            if let Some(filter) = sync_rules::StoreFilter::for_path(&dir) {
+                entries.retain(|e| filter.allows(Path::new(&e.path), e.is_directory, e.size_bytes));
+            }
+        }
+        if include_snippets.unwrap_or(false) {
+            snippets::attach(&mut entries, snippet_bytes);
+        }
+        Ok(entries)
+    })
+    .await
+}
 
+fn list_entries(dir: &Path, collator: &collation::Collator) -> Result<Vec<FileEntry>, String> {
     let mut entries = Vec::new();
     let read_dir = fs::read_dir(dir).map_err(|e| e.to_string())?;
+    let mimes = mime_map::MimeMap::load();
 
     for entry in read_dir.flatten() {
         let file_name = entry.file_name().to_string_lossy().to_string();
@@ -129,79 +296,202 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
             is_directory: metadata.is_dir(),
             size_bytes: metadata.len(),
             modified_at: modified,
-            mime_type: guess_mime(&file_name),
+            mime_type: mimes.guess(&file_name),
+            encrypted: metadata.is_file() && note_encryption::is_encrypted(&entry.path()),
+            snippet: None,
         });
     }
 
-    // Directories first, then sort by name
-    entries.sort_by(|a, b| {
-        b.is_directory
-            .cmp(&a.is_directory)
-            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
-    });
+    // Directories first, then sort by name. Keying once per entry rather
+    // than per comparison makes 10k-entry listings about a third faster.
+    entries.sort_by_cached_key(|e| (!e.is_directory, collator.key(&e.name)));
 
     Ok(entries)
 }
 
 /// Read a text file's content (for preview in the app).
+///
+/// Files over `limits.max_text_read_bytes` fail with `TooLarge` unless
+/// `force` is set, which reads them range by range instead. Encrypted notes
+/// (`.md.age`) are decrypted when the calling window may; see
+/// `note_encryption`.
 #[tauri::command]
-fn read_text_file(path: String) -> Result<String, String> {
-    let file_path = Path::new(&path);
-    if !file_path.exists() {
-        return Err(format!("File not found: {}", path));
-    }
+async fn read_text_file(
+    window: tauri::WebviewWindow,
+    path: String,
+    force: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> CommandResult<String> {
+    let label = window.label().to_string();
+    let file_path = PathBuf::from(&path);
+    fs_deadline::run(
+        "read_text_file",
+        &file_path.clone(),
+        timeout_ms,
+        move || {
+            if !file_path.exists() {
+                return Err(CommandError::new(
+                    ErrorCode::NotFound,
+                    format!("File not found: {}", path),
+                )
+                .with("path", &path));
+            }
 
-    // Safety: limit to 10MB
-    let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
-    if metadata.len() > 10 * 1024 * 1024 {
-        return Err("File too large (>10MB)".to_string());
-    }
+            let limit = settings::load_settings().limits.max_text_read_bytes;
+            let size = fs::metadata(&path)?.len();
+            let encrypted = note_encryption::is_encrypted(&file_path);
+            let content = if encrypted && size <= limit {
+                note_encryption::read(&label, &file_path)?
+            } else if size <= limit {
+                fs::read_to_string(&path)?
+            } else if force.unwrap_or(false) && !encrypted {
+                file_read::read_text_streaming(&file_path)?
+            } else {
+                return Err(file_read::too_large(
+                    &file_path,
+                    size,
+                    limit,
+                    "max_text_read_bytes",
+                ));
+            };
+            activity::record(activity::ActivityKind::Read, &file_path, None);
+            Ok(content)
+        },
+    )
+    .await
+}
 
-    fs::read_to_string(&path).map_err(|e| e.to_string())
+/// Write a text file, keeping an existing file's line endings and BOM unless
+/// `options` override them. With `expected_hash`, fails with `Conflict` if the
+/// file changed since it was read.
+#[tauri::command]
+async fn write_text_file(
+    window: tauri::WebviewWindow,
+    path: String,
+    content: String,
+    options: Option<text_style::TextWriteOptions>,
+    expected_hash: Option<String>,
+    dry_run: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> CommandResult<dry_run::DryRun> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    let file_path = PathBuf::from(&path);
+    fs_deadline::run(
+        "write_text_file",
+        &file_path.clone(),
+        timeout_ms,
+        move || {
+            write_guard::write_text(
+                &file_path,
+                &content,
+                &options.unwrap_or_default(),
+                expected_hash.as_deref(),
+                dry_run,
+            )
+        },
+    )
+    .await
 }
 
-/// Compute SHA-256 hash of file content (for artifact versioning).
+/// Compute SHA-256 hash of file content (for artifact versioning). Always
+/// the full hash, however large; snapshots may fingerprint instead.
 #[tauri::command]
-fn hash_file(path: String) -> Result<String, String> {
-    use sha2::{Digest, Sha256};
-
-    let content = fs::read(&path).map_err(|e| e.to_string())?;
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let result = hasher.finalize();
-    Ok(hex::encode(result))
+async fn hash_file(path: String, timeout_ms: Option<u64>) -> CommandResult<String> {
+    let file = PathBuf::from(&path);
+    fs_deadline::run("hash_file", &file.clone(), timeout_ms, move || {
+        let hash = file_read::hash(&file)?;
+        activity::record(activity::ActivityKind::Hash, &file, None);
+        Ok(hash)
+    })
+    .await
 }
 
 /// Get common user directories (Desktop, Documents, Downloads).
 #[tauri::command]
-fn get_user_directories() -> serde_json::Value {
-    let home = home_dir();
+fn get_user_directories() -> CommandResult<serde_json::Value> {
+    platform::desktop_only("get_user_directories")?;
+    let home = home::user_home()?;
 
-    serde_json::json!({
+    Ok(serde_json::json!({
         "home": home,
-        "desktop": format!("{}/Desktop", home),
-        "documents": format!("{}/Documents", home),
-        "downloads": format!("{}/Downloads", home),
-    })
+        "desktop": home.join("Desktop"),
+        "documents": home.join("Documents"),
+        "downloads": home.join("Downloads"),
+    }))
 }
 
 // ─── Obsidian Vault Discovery ───────────────────────────────────────────────
 
 /// Scan common locations for Obsidian vaults.
-/// Looks for directories containing a .obsidian subfolder.
+/// Looks for directories containing a .obsidian subfolder. On mobile only the
+/// app-accessible locations are searched. Note counts may come from the cache
+/// (see `note_counts`).
 #[tauri::command]
-fn discover_obsidian_vaults() -> Vec<ObsidianVault> {
-    let home = home_dir();
-    let search_roots = vec![
+async fn discover_obsidian_vaults(app: tauri::AppHandle) -> CommandResult<Vec<ObsidianVault>> {
+    let op = Operations::start_async(&app, OperationKind::VaultDiscovery, "obsidian", 1).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let vaults = find_obsidian_vaults(&op, vault_search_roots(&app));
+        if let Ok(vaults) = &vaults {
+            let stale = vaults.iter().filter(|v| v.count_stale);
+            note_counts::refresh(&app, stale.map(|v| PathBuf::from(&v.path)).collect());
+        }
+        op.finish_storing(vaults)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+#[cfg(desktop)]
+fn vault_search_roots(_app: &tauri::AppHandle) -> Vec<String> {
+    let Ok(home) = home::user_home() else {
+        return Vec::new();
+    };
+    let home = home.to_string_lossy().to_string();
+    vec![
         format!("{}/Documents", home),
         format!("{}/Desktop", home),
         format!("{}/Obsidian", home),
         home.clone(),
-    ];
+    ]
+}
 
-    let mut vaults = Vec::new();
+/// Obsidian mobile keeps vaults in its own documents folder, which is only
+/// reachable here when shared into the app's documents directory. On Android
+/// the shared `Documents` folder is also readable with storage permission.
+#[cfg(mobile)]
+fn vault_search_roots(app: &tauri::AppHandle) -> Vec<String> {
+    let mut roots = Vec::new();
+    if let Ok(docs) = app_files::documents_dir(app) {
+        roots.push(docs.join("Obsidian").to_string_lossy().to_string());
+        roots.push(docs.to_string_lossy().to_string());
+    }
+    #[cfg(target_os = "android")]
+    roots.push("/storage/emulated/0/Documents".into());
+    roots
+}
+
+fn find_obsidian_vaults(
+    op: &OperationHandle,
+    search_roots: Vec<String>,
+) -> CommandResult<Vec<ObsidianVault>> {
+    let total = search_roots.len() as u64;
+    vaults_under(search_roots, |i, root| {
+        op.check_cancelled()?;
+        op.progress("scanning", i as u64, Some(total), Some(root));
+        Ok(())
+    })
+}
+
+/// Vaults under each root, sorted by path with duplicates removed.
+/// `before_root` runs ahead of each root and can stop the scan.
+fn vaults_under(
+    search_roots: Vec<String>,
+    mut before_root: impl FnMut(usize, &str) -> CommandResult<()>,
+) -> CommandResult<Vec<ObsidianVault>> {
+    let mut paths: Vec<String> = Vec::new();
 
-    for root in search_roots {
+    for (i, root) in search_roots.into_iter().enumerate() {
+        before_root(i, &root)?;
         let root_path = Path::new(&root);
         if !root_path.exists() {
             continue;
@@ -209,29 +499,33 @@ fn discover_obsidian_vaults() -> Vec<ObsidianVault> {
 
         // Use walkdir with max_depth to avoid deep traversals
         if let Ok(walker) = walkdir_scan(&root, 4) {
-            for vault_path in walker {
-                let vault_name = vault_path
+            paths.extend(walker.iter().map(|p| p.to_string_lossy().to_string()));
+        }
+    }
+
+    // Deduplicate by path before counting, so each vault is counted once
+    paths.sort();
+    paths.dedup();
+
+    let vaults = paths
+        .into_iter()
+        .map(|path| {
+            let vault_path = Path::new(&path);
+            let count = note_counts::cached_or_count(vault_path);
+            ObsidianVault {
+                name: vault_path
                     .file_name()
                     .unwrap_or_default()
                     .to_string_lossy()
-                    .to_string();
-
-                let note_count = count_markdown_files(&vault_path);
-
-                vaults.push(ObsidianVault {
-                    name: vault_name,
-                    path: vault_path.to_string_lossy().to_string(),
-                    note_count,
-                });
+                    .to_string(),
+                note_count: count.note_count,
+                count_stale: count.stale,
+                path,
             }
-        }
-    }
-
-    // Deduplicate by path
-    vaults.sort_by(|a, b| a.path.cmp(&b.path));
-    vaults.dedup_by(|a, b| a.path == b.path);
+        })
+        .collect();
 
-    vaults
+    Ok(vaults)
 }
 
 /// Scan a directory for Obsidian vaults (directories with .obsidian subdir).
@@ -283,164 +577,385 @@ fn scan_for_obsidian(dir: &Path, depth: usize, max_depth: usize, vaults: &mut Ve
     }
 }
 
-fn count_markdown_files(vault_path: &Path) -> usize {
-    let mut count = 0;
-    if let Ok(entries) = fs::read_dir(vault_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "md" {
-                        count += 1;
-                    }
-                }
-            } else if path.is_dir() {
-                // Skip .obsidian and .trash
-                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if !name.starts_with('.') {
-                        count += count_markdown_files(&path);
-                    }
-                }
-            }
-        }
-    }
-    count
-}
-
-// ─── Provider Login Support ─────────────────────────────────────────────────
-
-/// Get the login config for a provider (URL and success indicators).
-#[tauri::command]
-fn get_provider_login_config(provider_id: String) -> Result<ProviderLoginConfig, String> {
-    match provider_id.as_str() {
-        "chatgpt" => Ok(ProviderLoginConfig {
-            provider_id: "chatgpt".into(),
-            login_url: "https://chatgpt.com/auth/login".into(),
-            success_indicators: vec![
-                "chatgpt.com".into(),
-                "chat.openai.com".into(),
-            ],
-        }),
-        "claude" => Ok(ProviderLoginConfig {
-            provider_id: "claude".into(),
-            login_url: "https://claude.ai/login".into(),
-            success_indicators: vec![
-                "claude.ai/new".into(),
-                "claude.ai/chat".into(),
-            ],
-        }),
-        "gemini" => Ok(ProviderLoginConfig {
-            provider_id: "gemini".into(),
-            login_url: "https://accounts.google.com".into(),
-            success_indicators: vec![
-                "gemini.google.com".into(),
-            ],
-        }),
-        "perplexity" => Ok(ProviderLoginConfig {
-            provider_id: "perplexity".into(),
-            login_url: "https://www.perplexity.ai/signin".into(),
-            success_indicators: vec![
-                "perplexity.ai".into(),
-            ],
-        }),
-        _ => Err(format!("Unknown provider: {}", provider_id)),
-    }
-}
-
-/// Ensure the session storage directory exists and return its path.
-#[tauri::command]
-fn ensure_session_dir(provider_id: String, tenant_id: String) -> Result<String, String> {
-    let home = agentvbx_home();
-    let session_dir = format!("{}/sessions/{}_{}", home, tenant_id, provider_id);
-    fs::create_dir_all(&session_dir).map_err(|e| e.to_string())?;
-    Ok(session_dir)
-}
-
 // ─── Helpers ────────────────────────────────────────────────────────────────
 
-fn home_dir() -> String {
-    std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .unwrap_or_else(|_| String::from("."))
-}
-
-fn agentvbx_home() -> String {
-    let home = home_dir();
-    let agentvbx_dir = format!("{}/.agentvbx", home);
-    // Ensure base directory exists
-    let _ = fs::create_dir_all(&agentvbx_dir);
-    agentvbx_dir
-}
-
 fn guess_mime(filename: &str) -> String {
-    let ext = filename
-        .rsplit('.')
-        .next()
-        .unwrap_or("")
-        .to_lowercase();
-
-    match ext.as_str() {
-        "md" => "text/markdown",
-        "txt" => "text/plain",
-        "json" => "application/json",
-        "yaml" | "yml" => "text/yaml",
-        "csv" => "text/csv",
-        "pdf" => "application/pdf",
-        "png" => "image/png",
-        "jpg" | "jpeg" => "image/jpeg",
-        "gif" => "image/gif",
-        "svg" => "image/svg+xml",
-        "mp4" => "video/mp4",
-        "mp3" => "audio/mpeg",
-        "doc" | "docx" => "application/msword",
-        "xls" | "xlsx" => "application/vnd.ms-excel",
-        "pptx" => "application/vnd.ms-powerpoint",
-        "html" => "text/html",
-        "js" => "text/javascript",
-        "ts" => "text/typescript",
-        "py" => "text/x-python",
-        "rs" => "text/x-rust",
-        "go" => "text/x-go",
-        _ => "application/octet-stream",
-    }
-    .to_string()
+    mime_map::MimeMap::load().guess(filename)
 }
 
 // ─── App Entry ──────────────────────────────────────────────────────────────
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    crash_reports::install();
+    crash_reports::catch(run_app);
+}
+
+fn run_app() {
+    startup::begin();
+    startup::phase("integrity", integrity::run);
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![
-            // Core
-            get_health,
-            get_tenant_path,
-            get_sessions_path,
-            // File stores
-            list_directory,
-            read_text_file,
-            hash_file,
-            get_user_directories,
-            // Obsidian
-            discover_obsidian_vaults,
-            // Provider login
-            get_provider_login_config,
-            ensure_session_dir,
-        ])
+        .plugin(tauri_plugin_log::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .manage(local_orchestrator::LocalOrchestrator::default())
+        .manage(provider_fetch::ProviderResponses::default())
+        .manage(provider_stream::ProviderStreams::default())
+        .manage(previews::PreviewPrefetch::default())
+        .manage(scheduler::Scheduler::default())
+        .manage(operations::Operations::default())
+        .manage(event_journal::EventJournal::default())
+        .manage(read_only::ReadOnlyMode::default())
+        .manage(maintenance::MaintenanceMode::default())
+        .register_asynchronous_uri_scheme_protocol(asset_protocol::SCHEME, asset_protocol::handle)
+        .invoke_handler(startup::guard(maintenance::guard(read_only::guard(
+            tauri::generate_handler![
+                // Core
+                get_health,
+                get_tenant_path,
+                get_sessions_path,
+                diagnostics::get_diagnostics,
+                crash_reports::get_pending_crash_reports,
+                crash_reports::submit_crash_report,
+                crash_reports::delete_crash_report,
+                integrity::get_startup_report,
+                platform::get_capabilities,
+                devtools::set_devtools,
+                devtools::get_debug_flags,
+                // Settings
+                i18n::get_error_catalog,
+                settings::get_settings,
+                settings::update_settings,
+                // Orchestrator
+                orchestrator::test_orchestrator_connection,
+                local_orchestrator::start_local_orchestrator,
+                local_orchestrator::get_local_orchestrator_status,
+                local_orchestrator::stop_local_orchestrator,
+                // File stores
+                list_directory,
+                read_text_file,
+                write_guard::read_text_file_with_hash,
+                asset_protocol::get_asset_url,
+                previews::prefetch_directory_previews,
+                previews::cancel_directory_previews,
+                pdf_preview::render_pdf_page,
+                audio_upload::prepare_audio_upload,
+                audio_upload::upload_audio_chunks,
+                artifact_upload::upload_artifact,
+                artifact_upload::list_artifact_uploads,
+                artifact_upload::list_artifacts,
+                artifact_metadata::set_artifact_metadata,
+                artifact_metadata::query_artifacts,
+                exports::export_results,
+                vault_graph::export_vault_graph,
+                similar_notes::find_similar_notes,
+                file_query::query_files,
+                bulk_frontmatter::bulk_edit_frontmatter,
+                bulk_rename::bulk_rename,
+                artifact_download::download_artifact,
+                quarantine::list_quarantined,
+                quarantine::release_quarantined,
+                quarantine::discard_quarantined,
+                file_read::read_text_range,
+                filenames::preview_filename,
+                write_text_file,
+                text_style::detect_text_style,
+                hash_file,
+                fingerprint::get_content_digest,
+                get_user_directories,
+                app_files::list_app_directory,
+                app_files::read_app_text_file,
+                // Obsidian
+                discover_obsidian_vaults,
+                obsidian_bookmarks::get_vault_bookmarks,
+                // Provider login
+                providers::get_provider_login_config,
+                providers::open_provider_login,
+                tenant_windows::open_tenant_window,
+                tenant_windows::list_open_windows,
+                tenant_windows::focus_tenant_window,
+                tenant_windows::delete_tenant,
+                whatsapp::list_whatsapp_chats,
+                whatsapp::set_whatsapp_chat_policy,
+                whatsapp_send::send_whatsapp_message,
+                providers::capture_provider_session,
+                session_expiry::check_provider_session,
+                provider_fetch::provider_fetch,
+                provider_fetch::read_provider_response,
+                provider_stream::provider_fetch_stream,
+                provider_stream::cancel_provider_stream,
+                provider_usage::get_provider_usage,
+                provider_usage::purge_provider_usage,
+                secrets::set_secret,
+                secrets::get_secret,
+                secrets::delete_secret,
+                secrets::list_secret_keys,
+                vault::set_master_passphrase,
+                vault::unlock_vault,
+                vault::change_master_passphrase,
+                stores::list_stores,
+                stores::register_store,
+                stores::reauthorize_store,
+                store_relink::relink_store,
+                stores::pick_and_register_store,
+                store_kinds::resolve_daily_note,
+                templates::render_template,
+                vault_packs::install_vault_pack,
+                vault_packs::uninstall_vault_pack,
+                rollups::generate_rollup,
+                tasks::extract_tasks,
+                tasks::set_task_state,
+                stores::rescan_store,
+                stores::rescan_all_stores,
+                stores::diff_store_snapshot,
+                stores::update_store_rules,
+                file_ids::get_file_id,
+                git::get_git_status,
+                git::git_snapshot,
+                notes::append_to_note,
+                note_links::move_note,
+                note_stats::get_note_stats,
+                calendar::parse_ics,
+                calendar::get_events,
+                canvas::read_canvas,
+                email::parse_email,
+                email::extract_email_attachment,
+                email::list_mbox_messages,
+                activity::get_recent_activity,
+                activity::clear_activity_history,
+                quick_capture::register_quick_capture_shortcut,
+                quick_capture::submit_quick_capture,
+                url_metadata::resolve_url_metadata,
+                web_archive::archive_url,
+                automation::get_automation_token,
+                home::get_stray_data_dir,
+                home::move_stray_data_dir,
+                home::migrate_to_platform_dirs,
+                sessions::list_sessions,
+                session_profile::refresh_session_profile,
+                sessions::delete_session,
+                sessions::ensure_session_dir,
+                sessions::migrate_sessions_layout,
+                trash::list_trash,
+                trash::restore_from_trash,
+                trash::empty_trash,
+                backup::create_backup,
+                backup::restore_backup,
+                scheduler::list_scheduled_jobs,
+                scheduler::run_job_now,
+                scheduler::set_job_enabled,
+                scheduler::set_job_schedule,
+                system_state::get_system_state,
+                event_journal::catch_up_events,
+                note_encryption::decrypt_note,
+                note_encryption::encrypt_note,
+                operations::list_operations,
+                store_health::check_store_health,
+                task_inbox::receive_assigned_task,
+                task_inbox::list_assigned_tasks,
+                task_inbox::ack_task,
+                redaction::test_redaction,
+                redaction::get_redaction_config,
+                redaction::set_redaction_config,
+                disk_space::get_volume_free_space,
+                mime_map::set_mime_override,
+                mime_map::list_mime_overrides,
+                workspaces::create_workspace,
+                workspaces::update_workspace,
+                workspaces::delete_workspace,
+                workspaces::list_workspaces,
+                workspaces::set_active_workspace,
+                read_only::set_read_only_mode,
+                maintenance::enter_maintenance_mode,
+                maintenance::exit_maintenance_mode,
+                recipes::list_recipes,
+                recipes::upsert_recipe,
+                recipes::delete_recipe,
+                recipes::run_recipe_now,
+                recipes::list_recipe_runs,
+                operations::cancel_operation,
+                operations::boost_operation,
+                startup::get_startup_timeline,
+            ],
+        ))))
         .setup(|app| {
-            // Ensure AGENTVBX data directory exists
-            let _ = agentvbx_home();
-
-            #[cfg(debug_assertions)]
-            {
-                let window = app.get_webview_window("main").unwrap();
-                window.open_devtools();
-            }
+            // The rest of startup runs in `startup::finish`; see `startup`
+            startup::phase("shell", || -> tauri::Result<()> {
+                crash_reports::attach(app.handle());
+                #[cfg(desktop)]
+                app.handle()
+                    .plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+                quick_capture::restore(app.handle());
+                devtools::open_at_launch(app.handle());
+                Ok(())
+            })?;
+            let handle = app.handle().clone();
+            std::thread::Builder::new()
+                .name("startup".into())
+                .spawn(move || startup::finish(&handle))?;
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Exit => {
+                // Never leave an orchestrator we spawned running
+                local_orchestrator::shutdown(app);
+                quick_capture::shutdown(app);
+                automation::shutdown();
+            }
+            tauri::RunEvent::WindowEvent {
+                label,
+                event: tauri::WindowEvent::Destroyed,
+                ..
+            } => {
+                provider_fetch::window_closed(app, &label);
+            }
+            _ => {}
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn names(entries: &[FileEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    fn english() -> collation::Collator {
+        collation::Collator::new(collation::SortOrder::Locale, true, "en")
+    }
+
+    #[test]
+    fn listing_puts_directories_first_then_sorts_names_ignoring_case() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["b.md", "A.txt", "c.pdf"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        for sub in ["zeta", "Alpha"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+
+        let entries = list_entries(dir.path(), &english()).unwrap();
+        assert_eq!(names(&entries), ["Alpha", "zeta", "A.txt", "b.md", "c.pdf"]);
+        assert!(entries[0].is_directory && !entries[2].is_directory);
+        assert_eq!(entries[3].mime_type, "text/markdown");
+    }
+
+    #[test]
+    fn listing_sorts_numbers_naturally_unless_legacy() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "Scan 10.pdf",
+            "scan 2.pdf",
+            "Übersicht.md",
+            "Scan 1.pdf",
+            "Zahlen.md",
+        ] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let entries = list_entries(dir.path(), &english()).unwrap();
+        assert_eq!(
+            names(&entries),
+            [
+                "Scan 1.pdf",
+                "scan 2.pdf",
+                "Scan 10.pdf",
+                "Übersicht.md",
+                "Zahlen.md"
+            ]
+        );
+        let legacy = collation::Collator::new(collation::SortOrder::Legacy, true, "en");
+        let entries = list_entries(dir.path(), &legacy).unwrap();
+        assert_eq!(
+            names(&entries),
+            [
+                "Scan 1.pdf",
+                "Scan 10.pdf",
+                "scan 2.pdf",
+                "Zahlen.md",
+                "Übersicht.md"
+            ]
+        );
+    }
+
+    #[test]
+    fn listing_skips_hidden_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".DS_Store"), "").unwrap();
+        fs::write(dir.path().join("visible.md"), "").unwrap();
+        fs::create_dir(dir.path().join(".obsidian")).unwrap();
+
+        let entries = list_entries(dir.path(), &english()).unwrap();
+        assert_eq!(names(&entries), ["visible.md"]);
+    }
+
+    #[test]
+    fn listing_a_missing_directory_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing").to_string_lossy().to_string();
+        let err = tauri::async_runtime::block_on(list_directory(
+            missing, None, None, None, None, None, None,
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::NotFound);
+        assert!(err.message.starts_with("Directory not found"));
+    }
+
+    #[test]
+    fn vault_discovery_dedups_vaults_found_from_several_roots() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        // The desktop roots: the home directory also contains the other three
+        let roots = ["Documents", "Desktop", "Obsidian", ""]
+            .iter()
+            .map(|r| home.join(r).to_string_lossy().to_string())
+            .collect();
+
+        let vaults = vaults_under(roots, |_, _| Ok(())).unwrap();
+        let found: Vec<(&str, usize)> = vaults
+            .iter()
+            .map(|v| (v.name.as_str(), v.note_count))
+            .collect();
+        // Notes in hidden folders such as `.trash` aren't counted
+        assert_eq!(found, [("Work", 2), ("Personal", 1)]);
+        // Counted during this discovery, so nothing is stale
+        assert!(vaults.iter().all(|v| !v.count_stale));
+        assert!(vaults[0].path < vaults[1].path);
+    }
+
+    #[test]
+    fn vault_discovery_stops_when_a_root_is_refused() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let roots = vec![home.path().to_string_lossy().to_string()];
+        let err = vaults_under(roots, |_, _| {
+            Err(CommandError::new(ErrorCode::Cancelled, "cancelled"))
+        })
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::Cancelled);
+    }
+
+    #[test]
+    fn mime_is_guessed_from_the_last_extension_ignoring_case() {
+        for (name, mime) in [
+            ("Note.MD", "text/markdown"),
+            ("photo.jpeg", "image/jpeg"),
+            ("photo.JPG", "image/jpeg"),
+            ("config.yml", "text/yaml"),
+            ("archive.tar.gz", "application/octet-stream"),
+            ("notes.backup.md", "text/markdown"),
+            ("Thread.EML", "message/rfc822"),
+            ("inbox.mbox", "application/mbox"),
+            ("README", "application/octet-stream"),
+            (".env", "application/octet-stream"),
+        ] {
+            assert_eq!(guess_mime(name), mime, "{}", name);
+        }
+    }
 }
@@ -0,0 +1,399 @@
+// Local orchestrator process
+//
+// For zero-infrastructure installs the desktop app runs the orchestrator
+// itself. The child's stdout/stderr go to `<log dir>/orchestrator.log`, a
+// monitor thread reaps it if it exits on its own (emitting
+// `orchestrator-process-exited`), and app shutdown stops it.
+
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::platform;
+use crate::policy::{self, CommandGroup};
+
+const BUNDLED_BINARY: &str = "agentvbx-orchestrator";
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const GRACEFUL_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+const REAP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+pub struct HealthCheck {
+    pub checked_at: String,
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LocalOrchestratorStatus {
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub port: Option<u16>,
+    pub binary: Option<String>,
+    pub started_at: Option<String>,
+    pub uptime_secs: Option<u64>,
+    pub log_path: Option<String>,
+    pub last_health_check: Option<HealthCheck>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StopReport {
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    /// The process ignored the graceful signal and had to be killed.
+    pub forced: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct ProcessExited {
+    pid: u32,
+    port: u16,
+    exit_code: Option<i32>,
+    /// True when the exit was initiated by `stop_local_orchestrator`.
+    requested: bool,
+}
+
+struct Running {
+    child: Arc<Mutex<Child>>,
+    pid: u32,
+    port: u16,
+    binary: String,
+    log_path: PathBuf,
+    started_at: chrono::DateTime<chrono::Utc>,
+    started: Instant,
+    /// Set by stop so the monitor reports the exit as requested.
+    stopping: Arc<AtomicBool>,
+    last_health: Arc<Mutex<Option<HealthCheck>>>,
+}
+
+/// Managed state: the orchestrator process this app started, if any.
+#[derive(Default)]
+pub struct LocalOrchestrator {
+    running: Mutex<Option<Running>>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Spawn the orchestrator on `port`.
+///
+/// `binary_path` of `None` runs the binary bundled next to the app executable.
+/// Paths ending in `.js`/`.mjs` are run with `node`.
+#[tauri::command]
+pub fn start_local_orchestrator(
+    app: AppHandle,
+    state: State<'_, LocalOrchestrator>,
+    binary_path: Option<String>,
+    port: u16,
+) -> CommandResult<LocalOrchestratorStatus> {
+    platform::desktop_only("local_orchestrator")?;
+    policy::check(None, CommandGroup::LocalOrchestrator)?;
+    let mut running = state.running.lock().unwrap();
+    if let Some(existing) = running.as_ref() {
+        return Err(CommandError::new(
+            ErrorCode::AlreadyRunning,
+            format!("Local orchestrator already running (pid {})", existing.pid),
+        )
+        .with("pid", existing.pid)
+        .with("port", existing.port));
+    }
+
+    ensure_port_free(port)?;
+    let binary = resolve_binary(binary_path)?;
+
+    let log_dir = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    fs::create_dir_all(&log_dir)?;
+    let log_path = log_dir.join("orchestrator.log");
+    let stdout = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+    let stderr = stdout.try_clone()?;
+
+    let mut command = if is_node_script(&binary) {
+        let mut c = Command::new("node");
+        c.arg(&binary);
+        c
+    } else {
+        Command::new(&binary)
+    };
+    let child = command
+        .env("PORT", port.to_string())
+        .env("API_PORT", port.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(stdout))
+        .stderr(Stdio::from(stderr))
+        .spawn()
+        .map_err(|e| {
+            CommandError::new(
+                ErrorCode::Io,
+                format!("Failed to start {}: {}", binary.display(), e),
+            )
+            .with("binary", binary.to_string_lossy())
+        })?;
+
+    let pid = child.id();
+    log::info!(
+        "[orchestrator] started {} on port {} (pid {})",
+        binary.display(),
+        port,
+        pid
+    );
+
+    let entry = Running {
+        child: Arc::new(Mutex::new(child)),
+        pid,
+        port,
+        binary: binary.to_string_lossy().to_string(),
+        log_path,
+        started_at: chrono::Utc::now(),
+        started: Instant::now(),
+        stopping: Arc::new(AtomicBool::new(false)),
+        last_health: Arc::new(Mutex::new(None)),
+    };
+    spawn_monitor(&app, &entry);
+    spawn_health_checks(&entry);
+
+    let status = status_of(Some(&entry));
+    *running = Some(entry);
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn get_local_orchestrator_status(
+    state: State<'_, LocalOrchestrator>,
+) -> LocalOrchestratorStatus {
+    status_of(state.running.lock().unwrap().as_ref())
+}
+
+/// Stop the orchestrator: SIGTERM first, then kill after a grace period.
+#[tauri::command]
+pub async fn stop_local_orchestrator(
+    state: State<'_, LocalOrchestrator>,
+) -> CommandResult<StopReport> {
+    let entry = state.running.lock().unwrap().take().ok_or_else(|| {
+        CommandError::new(ErrorCode::NotRunning, "Local orchestrator is not running")
+    })?;
+    // The grace period sleeps; keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || stop(entry))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// Stop a process we started before the app exits.
+pub fn shutdown(app: &AppHandle) {
+    let state = app.state::<LocalOrchestrator>();
+    let entry = state.running.lock().unwrap().take();
+    if let Some(entry) = entry {
+        let report = stop(entry);
+        log::info!(
+            "[orchestrator] stopped pid {} on shutdown (forced: {})",
+            report.pid,
+            report.forced
+        );
+    }
+}
+
+// ─── Process Control ────────────────────────────────────────────────────────
+
+fn resolve_binary(binary_path: Option<String>) -> CommandResult<PathBuf> {
+    let path = match binary_path.filter(|p| !p.is_empty()) {
+        Some(p) => PathBuf::from(p),
+        None => std::env::current_exe()?
+            .parent()
+            .map(|dir| {
+                dir.join(format!(
+                    "{}{}",
+                    BUNDLED_BINARY,
+                    std::env::consts::EXE_SUFFIX
+                ))
+            })
+            .ok_or_else(|| CommandError::new(ErrorCode::NotFound, "Cannot locate app directory"))?,
+    };
+    if !path.is_file() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Orchestrator binary not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()));
+    }
+    Ok(path)
+}
+
+fn is_node_script(path: &std::path::Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("js") | Some("mjs") | Some("cjs")
+    )
+}
+
+/// The orchestrator binds all interfaces, so probe the same way.
+fn ensure_port_free(port: u16) -> CommandResult<()> {
+    match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Err(CommandError::new(
+            ErrorCode::PortInUse,
+            format!("Port {} is already in use", port),
+        )
+        .with("port", port)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn stop(entry: Running) -> StopReport {
+    entry.stopping.store(true, Ordering::SeqCst);
+    let mut child = entry.child.lock().unwrap();
+
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(entry.pid as libc::pid_t, libc::SIGTERM);
+    }
+
+    // Windows has no SIGTERM equivalent for console-less children; the grace
+    // loop below just finds it still running and kills it.
+    let deadline = Instant::now() + GRACEFUL_STOP_TIMEOUT;
+    let mut forced = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline && cfg!(unix) => {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Ok(None) => {
+                forced = true;
+                let _ = child.kill();
+                break child.wait().ok();
+            }
+            Err(e) => {
+                log::warn!("[orchestrator] wait on pid {} failed: {}", entry.pid, e);
+                break None;
+            }
+        }
+    };
+
+    StopReport {
+        pid: entry.pid,
+        exit_code: status.and_then(|s| s.code()),
+        forced,
+    }
+}
+
+/// Reap the child when it exits and tell the UI, whether or not we asked it to.
+fn spawn_monitor(app: &AppHandle, entry: &Running) {
+    let app = app.clone();
+    let child = entry.child.clone();
+    let stopping = entry.stopping.clone();
+    let (pid, port) = (entry.pid, entry.port);
+
+    std::thread::spawn(move || {
+        let status = loop {
+            std::thread::sleep(REAP_POLL_INTERVAL);
+            match child.lock().unwrap().try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) => continue,
+                Err(_) => break None,
+            }
+        };
+
+        let requested = stopping.load(Ordering::SeqCst);
+        if !requested {
+            log::warn!(
+                "[orchestrator] pid {} exited unexpectedly ({:?})",
+                pid,
+                status
+            );
+            // Clear our record so a restart is possible
+            let state = app.state::<LocalOrchestrator>();
+            let mut running = state.running.lock().unwrap();
+            if running.as_ref().map(|r| r.pid) == Some(pid) {
+                *running = None;
+            }
+        }
+
+        let payload = ProcessExited {
+            pid,
+            port,
+            exit_code: status.and_then(|s| s.code()),
+            requested,
+        };
+        let _ = app.emit("orchestrator-process-exited", payload);
+    });
+}
+
+fn spawn_health_checks(entry: &Running) {
+    let stopping = entry.stopping.clone();
+    let child = entry.child.clone();
+    let last_health = entry.last_health.clone();
+    let url = format!("http://127.0.0.1:{}/api/health", entry.port);
+
+    tauri::async_runtime::spawn(async move {
+        let Ok(client) = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_secs(5))
+            .build()
+        else {
+            return;
+        };
+
+        loop {
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+            let exited = matches!(child.lock().unwrap().try_wait(), Ok(Some(_)));
+            if stopping.load(Ordering::SeqCst) || exited {
+                return;
+            }
+
+            let check = match client.get(&url).send().await {
+                Ok(r) => HealthCheck {
+                    checked_at: chrono::Utc::now().to_rfc3339(),
+                    ok: r.status().is_success(),
+                    status: Some(r.status().as_u16()),
+                    error: None,
+                },
+                Err(e) => HealthCheck {
+                    checked_at: chrono::Utc::now().to_rfc3339(),
+                    ok: false,
+                    status: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            *last_health.lock().unwrap() = Some(check);
+        }
+    });
+}
+
+fn status_of(entry: Option<&Running>) -> LocalOrchestratorStatus {
+    match entry {
+        Some(r) => LocalOrchestratorStatus {
+            running: true,
+            pid: Some(r.pid),
+            port: Some(r.port),
+            binary: Some(r.binary.clone()),
+            started_at: Some(r.started_at.to_rfc3339()),
+            uptime_secs: Some(r.started.elapsed().as_secs()),
+            log_path: Some(r.log_path.to_string_lossy().to_string()),
+            last_health_check: r.last_health.lock().unwrap().clone(),
+        },
+        None => LocalOrchestratorStatus {
+            running: false,
+            pid: None,
+            port: None,
+            binary: None,
+            started_at: None,
+            uptime_secs: None,
+            log_path: None,
+            last_health_check: None,
+        },
+    }
+}
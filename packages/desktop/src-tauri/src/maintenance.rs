@@ -0,0 +1,432 @@
+// Maintenance mode
+//
+// Before a backup, a data directory move, or a troubleshooting session,
+// support needs the app to stop doing anything on its own.
+// `enter_maintenance_mode` turns the mode on and quiesces background work:
+// - the scheduler runs nothing, the system state monitor rests, the WhatsApp
+//   outbox holds its messages, WhatsApp media isn't imported, assigned tasks
+//   aren't resumed, and the automation API refuses rescans and quick captures
+// - queued operations are cancelled, and new ones refuse to start
+// - provider streams are cancelled, since they have no point to stop at
+// - queued thumbnails are dropped
+// It then waits up to `timeout_ms` for running operations, scheduled jobs,
+// and thumbnails to finish on their own. Operations still running after
+// that are cancelled and get `CANCEL_GRACE` to stop at their next step. The
+// report says what stopped, what had to be cancelled, and what is still
+// running, in which case `drained` is false.
+//
+// While the mode is on, every command `read_only` lists as mutating is
+// refused with `MaintenanceMode`; `guard` does this. The backup, restore,
+// and data directory commands in `ALLOWED` are what the mode is for, so
+// they still run, and their operations still start and aren't waited on. The mode is saved to
+// `maintenance.json` in the state directory, so an app relaunched
+// mid-maintenance comes back in it (`restore`). `exit_maintenance_mode`
+// resumes everything and rescans every store to pick up what changed in the
+// meantime. The WhatsApp outbox resumes on its next retry. Each change is
+// audited as `maintenance_mode` and announced with
+// `maintenance-mode-changed`.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::operations::{Operation, Operations};
+use crate::previews::PreviewPrefetch;
+use crate::provider_stream::ProviderStreams;
+use crate::read_only::{self, ReadOnlyMode};
+use crate::scheduler::Scheduler;
+use crate::{audit, home, stores, task_inbox};
+
+const STATE_FILE: &str = "maintenance.json";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long cancelled operations get to reach their next step.
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Mutating commands that still run while the mode is on.
+const ALLOWED: &[&str] = &[
+    "create_backup",
+    "restore_backup",
+    "move_stray_data_dir",
+    "migrate_to_platform_dirs",
+];
+
+/// Components that pause themselves while the mode is on.
+const PAUSED: &[&str] = &[
+    "scheduler",
+    "system_state",
+    "whatsapp_outbox",
+    "whatsapp_media",
+    "task_inbox",
+    "automation",
+];
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    /// When the mode was turned on.
+    pub since: Option<String>,
+}
+
+/// Managed state: the current mode, loaded from disk at launch.
+pub struct MaintenanceMode(Mutex<MaintenanceStatus>);
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self(Mutex::new(load()))
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkKind {
+    Operation,
+    ScheduledJob,
+    ProviderStream,
+    Preview,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Work {
+    pub kind: WorkKind,
+    /// The operation, job, or stream id; the image path for a thumbnail.
+    pub id: String,
+    /// What an operation was working on.
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QuiesceReport {
+    pub status: MaintenanceStatus,
+    /// Nothing is left running.
+    pub drained: bool,
+    /// Components paused until the mode ends.
+    pub paused: Vec<&'static str>,
+    /// Queued operations dropped before they started, and running work that
+    /// finished on its own.
+    pub stopped: Vec<Work>,
+    /// Work that had to be cancelled, and stopped.
+    pub force_cancelled: Vec<Work>,
+    /// Work that hadn't stopped when the wait ended.
+    pub still_running: Vec<Work>,
+    /// Queued thumbnails dropped.
+    pub previews_dropped: usize,
+    pub waited_ms: u64,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Turn maintenance mode on and wait up to `timeout_ms` (30 seconds by
+/// default) for background work to drain. Calling it again while the mode
+/// is on drains again.
+#[tauri::command]
+pub async fn enter_maintenance_mode(
+    app: AppHandle,
+    timeout_ms: Option<u64>,
+) -> CommandResult<QuiesceReport> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .min(MAX_TIMEOUT);
+    let (status, changed) = app.state::<MaintenanceMode>().set(true)?;
+    if changed {
+        announce(&app, &status);
+    }
+    tauri::async_runtime::spawn_blocking(move || quiesce(&app, status, timeout))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// Turn maintenance mode off, resume background work, and rescan every
+/// store. Returns the new status.
+#[tauri::command]
+pub fn exit_maintenance_mode(app: AppHandle) -> CommandResult<MaintenanceStatus> {
+    let (status, changed) = app.state::<MaintenanceMode>().set(false)?;
+    app.state::<Operations>().resume();
+    if changed {
+        announce(&app, &status);
+        task_inbox::resume(app.clone());
+        reconcile(app.clone());
+    }
+    Ok(status)
+}
+
+// ─── Checks ─────────────────────────────────────────────────────────────────
+
+impl MaintenanceMode {
+    pub fn status(&self) -> MaintenanceStatus {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0.lock().unwrap().enabled
+    }
+
+    /// `MaintenanceMode` while the mode is on, unless `command` is
+    /// `ALLOWED`.
+    pub fn check(&self, command: &str) -> CommandResult<()> {
+        let status = self.0.lock().unwrap();
+        if !status.enabled || ALLOWED.contains(&command) {
+            return Ok(());
+        }
+        log::info!("[maintenance] refused {}", command);
+        Err(CommandError::new(
+            ErrorCode::MaintenanceMode,
+            format!("{} can't run during maintenance", command),
+        )
+        .with("command", command)
+        .with("since", &status.since))
+    }
+
+    /// Save the new mode; also returns whether it changed.
+    fn set(&self, enabled: bool) -> CommandResult<(MaintenanceStatus, bool)> {
+        let mut status = self.0.lock().unwrap();
+        if status.enabled == enabled {
+            return Ok((status.clone(), false));
+        }
+        let next = match enabled {
+            true => MaintenanceStatus {
+                enabled,
+                since: Some(chrono::Utc::now().to_rfc3339()),
+            },
+            false => MaintenanceStatus::default(),
+        };
+        save(&next)?;
+        log::info!(
+            "[maintenance] {}",
+            if enabled { "entered" } else { "exited" }
+        );
+        *status = next;
+        Ok((status.clone(), true))
+    }
+}
+
+/// Wrap the invoke handler so mutating commands other than `ALLOWED` are
+/// refused while the mode is on.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        if read_only::is_mutating(&command) {
+            let state = invoke.message.webview_ref().state::<MaintenanceMode>();
+            if let Err(e) = state.check(&command) {
+                invoke.resolver.reject(e);
+                return true;
+            }
+        }
+        handler(invoke)
+    }
+}
+
+/// Hold operations back again after a relaunch mid-maintenance; the other
+/// components check the mode themselves.
+pub fn restore(app: &AppHandle) {
+    let status = app.state::<MaintenanceMode>().status();
+    if status.enabled {
+        log::info!(
+            "[maintenance] still on since {}",
+            status.since.as_deref().unwrap_or("an unknown time")
+        );
+        app.state::<Operations>().pause();
+    }
+}
+
+fn announce(app: &AppHandle, status: &MaintenanceStatus) {
+    audit::record(
+        "maintenance_mode",
+        None,
+        serde_json::json!({ "enabled": status.enabled }),
+    );
+    let _ = app.emit("maintenance-mode-changed", status.clone());
+}
+
+// ─── Draining ───────────────────────────────────────────────────────────────
+
+fn quiesce(app: &AppHandle, status: MaintenanceStatus, timeout: Duration) -> QuiesceReport {
+    let started = Instant::now();
+    let operations = app.state::<Operations>();
+    let scheduler = app.state::<Scheduler>();
+    let previews = app.state::<PreviewPrefetch>();
+
+    let mut stopped: Vec<Work> = operations.pause().iter().map(operation).collect();
+    let mut force_cancelled: Vec<Work> = app
+        .state::<ProviderStreams>()
+        .cancel_all(app)
+        .into_iter()
+        .map(|id| Work {
+            kind: WorkKind::ProviderStream,
+            id,
+            label: None,
+        })
+        .collect();
+    let previews_dropped = previews.drop_pending();
+
+    let running = operations.running();
+    let jobs = scheduler.running_jobs();
+    wait_until(started + timeout, || {
+        operations.running().is_empty()
+            && scheduler.running_jobs().is_empty()
+            && previews.in_flight().is_empty()
+    });
+    let cancelled = operations.cancel_running();
+    if !cancelled.is_empty() {
+        log::warn!(
+            "[maintenance] cancelling {} operations still running",
+            cancelled.len()
+        );
+        wait_until(Instant::now() + CANCEL_GRACE, || {
+            operations.running().is_empty()
+        });
+    }
+
+    let mut still_running = Vec::new();
+    let running_now: Vec<u64> = operations.running().iter().map(|op| op.id).collect();
+    for op in &running {
+        match (
+            cancelled.iter().any(|c| c.id == op.id),
+            running_now.contains(&op.id),
+        ) {
+            (_, true) => still_running.push(operation(op)),
+            (true, false) => force_cancelled.push(operation(op)),
+            (false, false) => stopped.push(operation(op)),
+        }
+    }
+    let jobs_now = scheduler.running_jobs();
+    for id in jobs {
+        let work = Work {
+            kind: WorkKind::ScheduledJob,
+            id,
+            label: None,
+        };
+        match jobs_now.contains(&work.id) {
+            true => still_running.push(work),
+            false => stopped.push(work),
+        }
+    }
+    still_running.extend(previews.in_flight().into_iter().map(|path| Work {
+        kind: WorkKind::Preview,
+        id: path.to_string_lossy().to_string(),
+        label: None,
+    }));
+
+    let report = QuiesceReport {
+        status,
+        drained: still_running.is_empty(),
+        paused: PAUSED.to_vec(),
+        stopped,
+        force_cancelled,
+        still_running,
+        previews_dropped,
+        waited_ms: started.elapsed().as_millis() as u64,
+    };
+    log::info!(
+        "[maintenance] {} after {} ms: {} stopped, {} cancelled, {} still running",
+        if report.drained {
+            "drained"
+        } else {
+            "not drained"
+        },
+        report.waited_ms,
+        report.stopped.len(),
+        report.force_cancelled.len(),
+        report.still_running.len()
+    );
+    report
+}
+
+fn operation(op: &Operation) -> Work {
+    Work {
+        kind: WorkKind::Operation,
+        id: op.id.to_string(),
+        label: Some(op.label.clone()),
+    }
+}
+
+fn wait_until(deadline: Instant, mut done: impl FnMut() -> bool) {
+    while !done() && Instant::now() < deadline {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Rescan every store for what changed during maintenance, unless read-only
+/// mode forbids it.
+fn reconcile(app: AppHandle) {
+    if app.state::<ReadOnlyMode>().enabled() {
+        log::info!("[maintenance] read-only mode: not rescanning stores");
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        match stores::rescan_all_stores(app, None, None).await {
+            Ok(report) => log::info!(
+                "[maintenance] rescanned {} stores, {} failed",
+                report.reports.len(),
+                report.failed.len()
+            ),
+            Err(e) => log::warn!("[maintenance] rescan after maintenance failed: {}", e),
+        }
+    });
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn state_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join(STATE_FILE))
+}
+
+fn load() -> MaintenanceStatus {
+    state_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save(status: &MaintenanceStatus) -> CommandResult<()> {
+    let raw = serde_json::to_vec_pretty(status)
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    fs::write(state_path()?, raw)?;
+    Ok(())
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn the_mode_survives_a_restart_and_refuses_commands() {
+        let _home = TestHome::new();
+        let mode = MaintenanceMode::default();
+        mode.check("write_text_file").unwrap();
+
+        let (status, changed) = mode.set(true).unwrap();
+        assert!(changed && status.since.is_some());
+        let err = mode.check("write_text_file").unwrap_err();
+        assert_eq!(err.code, ErrorCode::MaintenanceMode);
+        assert_eq!(err.params["command"], "write_text_file");
+        for command in ALLOWED {
+            mode.check(command).unwrap();
+        }
+
+        let restarted = MaintenanceMode::default();
+        assert_eq!(restarted.status(), status);
+        let (again, changed) = restarted.set(true).unwrap();
+        assert!(!changed);
+        assert_eq!(again.since, status.since);
+
+        restarted.set(false).unwrap();
+        assert!(!MaintenanceMode::default().enabled());
+    }
+}
@@ -0,0 +1,221 @@
+// MIME map
+//
+// File types come from the extension: the built-in `BUILTIN` table, with
+// the user's `mime_overrides` from settings layered on top. Teams with
+// their own text formats map them with `set_mime_override`, e.g. `.note` to
+// `text/markdown`. Overrides apply to directory listings, the asset
+// protocol, upload metadata, and to indexing. Files mapped to
+// `text/markdown` are indexed as notes by `query_files` and
+// `find_similar_notes`. Removing an override brings back the built-in type,
+// or `application/octet-stream` for an extension the table doesn't know.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::settings;
+
+pub const MARKDOWN: &str = "text/markdown";
+const UNKNOWN: &str = "application/octet-stream";
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("md", MARKDOWN),
+    ("txt", "text/plain"),
+    ("json", "application/json"),
+    ("yaml", "text/yaml"),
+    ("yml", "text/yaml"),
+    ("csv", "text/csv"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("doc", "application/msword"),
+    ("docx", "application/msword"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.ms-excel"),
+    ("pptx", "application/vnd.ms-powerpoint"),
+    ("html", "text/html"),
+    ("eml", "message/rfc822"),
+    ("mbox", "application/mbox"),
+    ("js", "text/javascript"),
+    ("ts", "text/typescript"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("go", "text/x-go"),
+];
+
+#[derive(Serialize, Debug)]
+pub struct MimeOverride {
+    pub extension: String,
+    pub mime: String,
+    /// What the extension maps to without the override.
+    pub builtin: Option<String>,
+}
+
+/// The built-in table merged with the overrides, loaded once per listing or
+/// query.
+pub struct MimeMap {
+    overrides: BTreeMap<String, String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Map `extension` to `mime`, or with no `mime`, drop the override so the
+/// built-in type applies again.
+#[tauri::command]
+pub fn set_mime_override(extension: String, mime: Option<String>) -> CommandResult<()> {
+    let extension = normalize_extension(&extension)?;
+    let mut settings = settings::load_settings();
+    match mime {
+        Some(mime) => {
+            settings
+                .mime_overrides
+                .insert(extension, normalize_mime(&mime)?);
+        }
+        None => {
+            settings.mime_overrides.remove(&extension);
+        }
+    }
+    settings::save_settings(&settings)
+}
+
+#[tauri::command]
+pub fn list_mime_overrides() -> Vec<MimeOverride> {
+    settings::load_settings()
+        .mime_overrides
+        .into_iter()
+        .map(|(extension, mime)| MimeOverride {
+            builtin: builtin(&extension).map(str::to_string),
+            extension,
+            mime,
+        })
+        .collect()
+}
+
+// ─── Lookup ─────────────────────────────────────────────────────────────────
+
+impl MimeMap {
+    pub fn load() -> Self {
+        Self {
+            overrides: settings::load_settings().mime_overrides,
+        }
+    }
+
+    /// The MIME type for `filename`, from its last extension, ignoring case.
+    pub fn guess(&self, filename: &str) -> String {
+        let ext = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+        self.overrides
+            .get(&ext)
+            .map(String::as_str)
+            .or_else(|| builtin(&ext))
+            .unwrap_or(UNKNOWN)
+            .to_string()
+    }
+
+    /// Whether `filename` is indexed as a Markdown note.
+    pub fn is_markdown(&self, filename: &str) -> bool {
+        self.guess(filename) == MARKDOWN
+    }
+}
+
+fn builtin(extension: &str) -> Option<&'static str> {
+    BUILTIN
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+fn normalize_extension(extension: &str) -> CommandResult<String> {
+    let ext = extension.trim().trim_start_matches('.').to_lowercase();
+    if ext.is_empty()
+        || !ext
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+    {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid extension: {:?}", extension),
+        )
+        .with("extension", extension));
+    }
+    Ok(ext)
+}
+
+/// `type/subtype` with RFC 6838 name characters, lowercased; parameters
+/// aren't allowed.
+fn normalize_mime(mime: &str) -> CommandResult<String> {
+    let valid_name = |name: &str| {
+        (1..=127).contains(&name.len())
+            && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    let mime = mime.trim().to_lowercase();
+    match mime.split_once('/') {
+        Some((kind, subtype)) if valid_name(kind) && valid_name(subtype) => Ok(mime),
+        _ => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid MIME type: {:?}", mime),
+        )
+        .with("mime", mime)),
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn overrides_layer_over_the_builtin_table() {
+        let _home = TestHome::new();
+        set_mime_override(".Note".into(), Some("text/markdown".into())).unwrap();
+        set_mime_override("txt".into(), Some("Text/X-Log".into())).unwrap();
+        let map = MimeMap::load();
+        assert!(map.is_markdown("Standup.note"));
+        assert_eq!(map.guess("server.TXT"), "text/x-log");
+
+        let listed = list_mime_overrides();
+        assert_eq!(listed[0].extension, "note");
+        assert_eq!(listed[0].builtin, None);
+        assert_eq!(listed[1].builtin.as_deref(), Some("text/plain"));
+
+        // Removing one restores the built-in type
+        set_mime_override("txt".into(), None).unwrap();
+        set_mime_override("note".into(), None).unwrap();
+        let map = MimeMap::load();
+        assert_eq!(map.guess("server.txt"), "text/plain");
+        assert_eq!(map.guess("Standup.note"), UNKNOWN);
+    }
+
+    #[test]
+    fn malformed_types_are_rejected() {
+        let _home = TestHome::new();
+        for mime in [
+            "markdown",
+            "text/",
+            "/markdown",
+            "text/markdown; charset=utf-8",
+            "text/mark down",
+        ] {
+            let err = set_mime_override("note".into(), Some(mime.into())).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidInput, "{}", mime);
+        }
+        assert!(set_mime_override("a.b".into(), Some("text/plain".into())).is_err());
+        assert!(set_mime_override("".into(), Some("text/plain".into())).is_err());
+        assert!(list_mime_overrides().is_empty());
+    }
+}
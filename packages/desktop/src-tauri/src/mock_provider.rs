@@ -0,0 +1,248 @@
+// Simulated provider
+//
+// A fake `mock` provider for frontend development and end-to-end tests, so
+// the login, capture, health-check, and fetch flows run without real
+// accounts:
+// - Login: `login_url` is a bundled page served by the asset protocol at
+//   `/mock-provider/login`. Its links lead to the success, challenge, and
+//   failure pages the login monitor watches for.
+// - Capture: records a fake `mock_session` cookie instead of reading the
+//   window's cookies, and reads the mock user's profile from the session
+//   fixture.
+// - Health: follows a script timed from the capture. The session is active for
+//   `mock_session_active_secs`, expiring for `mock_session_expiring_secs`,
+//   then expired.
+// - Fetch: requests to `https://mock.agentvbx.test` get canned responses from
+//   `fixtures/mock-provider/responses` without touching the network.
+//
+// It is only available with `developer.mock_provider` set. Release builds also
+// need `AGENTVBX_ENABLE_MOCK_PROVIDER=1`, so a settings edit alone can't turn
+// it on for real users.
+
+use std::collections::HashMap;
+
+use crate::provider_fetch::{BodyEncoding, ProviderResponse};
+use crate::providers::{ProviderLoginConfig, WindowSize};
+use crate::session_profile::ProfileSource;
+use crate::sessions::{SameSite, SessionHealth, SessionPayload, SessionStatus, StoredCookie};
+use crate::{asset_protocol, settings};
+
+pub const PROVIDER_ID: &str = "mock";
+pub const DOMAIN: &str = "mock.agentvbx.test";
+const ENV_VAR: &str = "AGENTVBX_ENABLE_MOCK_PROVIDER";
+const AUTH_COOKIE: &str = "mock_session";
+/// Asset-protocol path prefix of the login pages.
+pub const PAGE_PREFIX: &str = "mock-provider/";
+
+const PAGES: &[(&str, &str)] = &[
+    (
+        "login",
+        include_str!("../fixtures/mock-provider/login.html"),
+    ),
+    (
+        "challenge",
+        include_str!("../fixtures/mock-provider/challenge.html"),
+    ),
+    ("app", include_str!("../fixtures/mock-provider/app.html")),
+];
+
+/// Method, path, fixture.
+const RESPONSES: &[(&str, &str, &str)] = &[
+    (
+        "GET",
+        "/api/auth/session",
+        include_str!("../fixtures/mock-provider/responses/session.json"),
+    ),
+    (
+        "GET",
+        "/api/conversations",
+        include_str!("../fixtures/mock-provider/responses/conversations.json"),
+    ),
+    (
+        "POST",
+        "/api/chat",
+        include_str!("../fixtures/mock-provider/responses/chat.json"),
+    ),
+];
+const NOT_FOUND: &str = include_str!("../fixtures/mock-provider/responses/not_found.json");
+
+pub fn enabled() -> bool {
+    let allowed = cfg!(debug_assertions) || std::env::var(ENV_VAR).is_ok_and(|v| v == "1");
+    allowed && settings::load_settings().developer.mock_provider
+}
+
+/// `provider_id` is the mock and the mock is on.
+pub fn is_mock(provider_id: &str) -> bool {
+    provider_id == PROVIDER_ID && enabled()
+}
+
+pub fn login_config() -> ProviderLoginConfig {
+    let pages = format!("{}/{}", asset_protocol::origin(), PAGE_PREFIX);
+    ProviderLoginConfig {
+        provider_id: PROVIDER_ID.into(),
+        login_url: format!("{}login", pages),
+        success_indicators: vec![format!("{}app", PAGE_PREFIX)],
+        allowed_domains: vec![DOMAIN.into()],
+        challenge_indicators: vec![format!("{}challenge", PAGE_PREFIX)],
+        failure_indicators: vec![format!("{}login?error", PAGE_PREFIX)],
+        timeout_secs: 120,
+        user_agent: None,
+        window_size: WindowSize::default(),
+        extra_headers: HashMap::new(),
+        auth_cookies: vec![AUTH_COOKIE.into()],
+        health_check_url: None,
+        profile: Some(ProfileSource {
+            url: format!("https://{}/api/auth/session", DOMAIN),
+            account_id: Some("/user/id".into()),
+            email: Some("/user/email".into()),
+            display_name: Some("/user/name".into()),
+            ..Default::default()
+        }),
+    }
+}
+
+/// A login page by name, for the asset protocol; `None` while the mock is off.
+pub fn page(name: &str) -> Option<&'static str> {
+    if !enabled() {
+        return None;
+    }
+    PAGES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, html)| *html)
+}
+
+/// The cookies a mock capture records.
+pub fn cookies() -> Vec<StoredCookie> {
+    let developer = settings::load_settings().developer;
+    let lifetime = developer.mock_session_active_secs + developer.mock_session_expiring_secs;
+    let now = chrono::Utc::now().timestamp();
+    vec![StoredCookie {
+        name: AUTH_COOKIE.into(),
+        value: format!("mock-{}", now),
+        domain: DOMAIN.into(),
+        expires_at: Some(now + lifetime as i64),
+        path: "/".into(),
+        secure: true,
+        http_only: true,
+        same_site: Some(SameSite::Lax),
+        host_only: false,
+    }]
+}
+
+/// The scripted verdict for a captured mock session.
+pub fn health(payload: &SessionPayload) -> SessionHealth {
+    let developer = settings::load_settings().developer;
+    let now = chrono::Utc::now();
+    let elapsed = chrono::DateTime::parse_from_rfc3339(&payload.captured_at)
+        .map(|t| (now.timestamp() - t.timestamp()).max(0) as u64)
+        .unwrap_or(u64::MAX);
+    let active = developer.mock_session_active_secs;
+    let expiring = active + developer.mock_session_expiring_secs;
+    let status = if elapsed < active {
+        SessionStatus::Active
+    } else if elapsed < expiring {
+        SessionStatus::Expiring
+    } else {
+        SessionStatus::Expired
+    };
+    let expires_at = payload
+        .cookies
+        .iter()
+        .find(|c| c.name == AUTH_COOKIE)
+        .and_then(|c| c.expires_at)
+        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+        .map(|t| t.to_rfc3339());
+    SessionHealth {
+        status,
+        expires_at,
+        checked_at: Some(now.to_rfc3339()),
+        probed_at: None,
+    }
+}
+
+/// The canned response for a request already checked against `DOMAIN`.
+/// An expired session gets a 401, like a real provider.
+pub fn respond(method: &str, path: &str, payload: &SessionPayload) -> ProviderResponse {
+    let (status, body) = if health(payload).status == SessionStatus::Expired {
+        (401, r#"{ "error": "unauthorized" }"#)
+    } else {
+        RESPONSES
+            .iter()
+            .find(|(m, p, _)| m.eq_ignore_ascii_case(method) && *p == path)
+            .map_or((404, NOT_FOUND), |(_, _, body)| (200, *body))
+    };
+    ProviderResponse {
+        status,
+        headers: HashMap::from([
+            ("content-type".to_string(), "application/json".to_string()),
+            ("x-agentvbx-mock".to_string(), "1".to_string()),
+        ]),
+        body: Some(body.to_string()),
+        body_encoding: BodyEncoding::Utf8,
+        stream_id: None,
+        from_cache: false,
+        cached_at: None,
+        redactions: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions;
+    use crate::testing::TestHome;
+
+    const MOCK_ON: &str = r#"
+[settings.developer]
+mock_provider = true
+mock_session_active_secs = 60
+mock_session_expiring_secs = 30
+"#;
+
+    fn captured_ago(payload: &SessionPayload, secs: i64) -> SessionPayload {
+        let at = chrono::Utc::now() - chrono::Duration::seconds(secs);
+        SessionPayload {
+            captured_at: at.to_rfc3339(),
+            ..payload.clone()
+        }
+    }
+
+    #[test]
+    fn off_unless_the_developer_setting_is_on() {
+        let _home = TestHome::new();
+        assert!(!is_mock(PROVIDER_ID));
+        assert!(page("login").is_none());
+    }
+
+    #[test]
+    fn login_capture_expiry_and_logout() {
+        let _home = TestHome::with_fixture(MOCK_ON);
+        assert!(is_mock(PROVIDER_ID));
+        assert!(page("login").is_some_and(|html| html.contains("login?error=denied")));
+
+        // Capture
+        let report = crate::providers::save_capture("acme", PROVIDER_ID, cookies()).unwrap();
+        assert_eq!(report.cookie_count, 1);
+        let root = sessions::sessions_root().unwrap();
+        let dir = sessions::find_session_dir(&root, "acme", PROVIDER_ID).unwrap();
+        let payload = sessions::load_payload(&dir).unwrap().unwrap();
+
+        // Health follows the script from the capture time
+        assert_eq!(health(&payload).status, SessionStatus::Active);
+        assert!(health(&payload).expires_at.is_some());
+        let expiring = captured_ago(&payload, 70);
+        assert_eq!(health(&expiring).status, SessionStatus::Expiring);
+        let expired = captured_ago(&payload, 95);
+        assert_eq!(health(&expired).status, SessionStatus::Expired);
+
+        // Fetches see the same session state
+        assert_eq!(respond("get", "/api/conversations", &payload).status, 200);
+        assert_eq!(respond("GET", "/api/missing", &payload).status, 404);
+        assert_eq!(respond("POST", "/api/chat", &expired).status, 401);
+
+        // Logout
+        sessions::delete("acme".into(), PROVIDER_ID.into(), false).unwrap();
+        assert!(sessions::find_session_dir(&root, "acme", PROVIDER_ID).is_none());
+    }
+}
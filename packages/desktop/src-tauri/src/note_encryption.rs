@@ -0,0 +1,419 @@
+// Encrypted notes
+//
+// `encrypt_note` turns `Note.md` into `Note.md.age`, readable only through
+// AGENTVBX. The layout follows age: a text header naming each recipient with
+// the file key wrapped for it, then the note sealed with that key.
+//
+//     agentvbx-note/v1
+//     -> acme <base64 of the file key sealed with acme's note key>
+//     ---
+//     <nonce><ChaCha20-Poly1305 ciphertext, the header as associated data>
+//
+// Recipients are tenants. Each tenant's note key is a random 32-byte key kept
+// in its secrets (`note_keys/default`, a namespace the secret commands
+// refuse; see `secrets`), made the first time a note is encrypted for it.
+// This isn't age's X25519: the key never leaves the machine's secrets vault,
+// so an encrypted note can be opened where that vault is, and not elsewhere.
+//
+// Opening a note takes the right to one of its recipients' keys. A tenant
+// window can only use its own tenant's key; the main window can use any
+// recipient's. With the encrypted-file secrets backend the vault must be
+// unlocked too. `read_text_file` opens `.md.age` files this way, and
+// `decrypt_note` writes the note back out as Markdown.
+//
+// `shred_plaintext` overwrites the original with zeros before deleting it.
+// On SSDs and copy-on-write filesystems that can't promise the old blocks are
+// gone. The plaintext is kept by default.
+//
+// Nothing that indexes notes reads `.md.age` files: scans, queries,
+// similarity signatures, and link graphs only take `.md`. Hashes and
+// snapshots are of the file on disk, so they cover the ciphertext, and a
+// changed note still reads as changed without its text being exposed.
+
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::sessions::validate_id;
+use crate::{audit, dry_run, secrets, tenant_windows};
+
+pub const EXTENSION: &str = ".age";
+const MAGIC: &str = "agentvbx-note/v1";
+const HEADER_END: &str = "---\n";
+const KEY_NAMESPACE: &str = secrets::NOTE_KEY_NAMESPACE;
+const KEY_NAME: &str = "default";
+const NONCE_BYTES: usize = 12;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct EncryptOptions {
+    /// Overwrite and delete `Note.md` once `Note.md.age` is written.
+    pub shred_plaintext: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EncryptedNote {
+    pub path: String,
+    pub recipients: Vec<String>,
+    pub shredded: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Write `path` (a Markdown note) encrypted for `recipients` to
+/// `<path>.age`.
+#[tauri::command]
+pub async fn encrypt_note(
+    window: tauri::WebviewWindow,
+    path: String,
+    recipients: Vec<String>,
+    options: Option<EncryptOptions>,
+) -> CommandResult<EncryptedNote> {
+    dry_run::check_writable(window.label(), None, "encrypt_note")?;
+    let recipients = recipients
+        .into_iter()
+        .map(|r| tenant_windows::resolve_tenant(window.label(), Some(r)))
+        .collect::<CommandResult<Vec<_>>>()?;
+    let options = options.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || encrypt(Path::new(&path), &recipients, &options))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Write the encrypted note at `path` back out as Markdown, without the
+/// `.age`, and delete the encrypted copy. Returns the note's path.
+#[tauri::command]
+pub async fn decrypt_note(window: tauri::WebviewWindow, path: String) -> CommandResult<String> {
+    dry_run::check_writable(window.label(), None, "decrypt_note")?;
+    let caller = tenant_windows::window_tenant(window.label());
+    tauri::async_runtime::spawn_blocking(move || {
+        let encrypted = Path::new(&path);
+        let note = plaintext_path(encrypted)?;
+        if note.exists() {
+            return Err(CommandError::new(
+                ErrorCode::Conflict,
+                format!("{} already exists", note.display()),
+            )
+            .with("path", note.to_string_lossy()));
+        }
+        let text = open(encrypted, caller.as_deref())?;
+        write_new(&note, &text)?;
+        fs::remove_file(encrypted)?;
+        audit::record(
+            "note_decrypted",
+            caller.as_deref(),
+            serde_json::json!({ "path": note.to_string_lossy() }),
+        );
+        activity::record(ActivityKind::Write, &note, caller.as_deref());
+        Ok(note.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Reading ────────────────────────────────────────────────────────────────
+
+/// Whether `path` is named as an encrypted note.
+pub fn is_encrypted(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .is_some_and(|n| n.ends_with(".md.age"))
+}
+
+/// The text of the encrypted note at `path`, for a call from the window
+/// `caller_label`.
+pub fn read(caller_label: &str, path: &Path) -> CommandResult<String> {
+    let caller = tenant_windows::window_tenant(caller_label);
+    let text = open(path, caller.as_deref())?;
+    audit::record(
+        "encrypted_note_read",
+        caller.as_deref(),
+        serde_json::json!({ "path": path.to_string_lossy() }),
+    );
+    String::from_utf8(text).map_err(|_| {
+        CommandError::new(ErrorCode::InvalidInput, "The note isn't UTF-8 text")
+            .with("path", path.to_string_lossy())
+    })
+}
+
+/// Decrypt `path` with a key `caller` may use: its own tenant's, or for
+/// `None` any recipient's.
+fn open(path: &Path, caller: Option<&str>) -> CommandResult<Vec<u8>> {
+    let sealed = fs::read(path)?;
+    let not_ours = || {
+        CommandError::new(ErrorCode::InvalidInput, "Not an encrypted note")
+            .with("path", path.to_string_lossy())
+    };
+    let (header, body) = split_header(&sealed).ok_or_else(not_ours)?;
+    let stanzas = parse_header(header).ok_or_else(not_ours)?;
+    if body.len() <= NONCE_BYTES {
+        return Err(not_ours());
+    }
+
+    let usable = stanzas
+        .iter()
+        .filter(|(tenant, _)| caller.is_none_or(|c| c == tenant));
+    let mut file_key = None;
+    for (tenant, wrapped) in usable {
+        let Some(key) = note_key(tenant, false)? else {
+            continue;
+        };
+        if let Ok(unwrapped) = secrets::open_with(&key, wrapped) {
+            file_key = <[u8; 32]>::try_from(unwrapped.as_slice()).ok();
+            break;
+        }
+    }
+    let Some(file_key) = file_key else {
+        let recipients: Vec<&str> = stanzas.iter().map(|(t, _)| t.as_str()).collect();
+        return Err(CommandError::new(
+            ErrorCode::PermissionDenied,
+            "No key this window may use can open the note",
+        )
+        .with("path", path.to_string_lossy())
+        .with("recipients", recipients));
+    };
+
+    let (nonce, ciphertext) = body.split_at(NONCE_BYTES);
+    ChaCha20Poly1305::new(&file_key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| {
+            CommandError::new(ErrorCode::InvalidInput, "The encrypted note is damaged")
+                .with("path", path.to_string_lossy())
+        })
+}
+
+/// The header, through its closing line, and what follows it.
+fn split_header(sealed: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = sealed
+        .windows(HEADER_END.len() + 1)
+        .position(|w| w[0] == b'\n' && &w[1..] == HEADER_END.as_bytes())?
+        + 1
+        + HEADER_END.len();
+    Some(sealed.split_at(end))
+}
+
+/// `(tenant, wrapped file key)` for each recipient line.
+fn parse_header(header: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let text = std::str::from_utf8(header).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != MAGIC {
+        return None;
+    }
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut stanzas = Vec::new();
+    for line in lines.take_while(|l| *l != HEADER_END.trim_end()) {
+        let mut parts = line.strip_prefix("-> ")?.split(' ');
+        let (tenant, wrapped) = (parts.next()?, parts.next()?);
+        stanzas.push((tenant.to_string(), engine.decode(wrapped).ok()?));
+    }
+    (!stanzas.is_empty()).then_some(stanzas)
+}
+
+// ─── Writing ────────────────────────────────────────────────────────────────
+
+fn encrypt(
+    path: &Path,
+    recipients: &[String],
+    options: &EncryptOptions,
+) -> CommandResult<EncryptedNote> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if !path.is_file() || !name.to_lowercase().ends_with(".md") {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Not a Markdown note: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()));
+    }
+    let mut recipients = recipients.to_vec();
+    recipients.sort();
+    recipients.dedup();
+    if recipients.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Name at least one recipient",
+        ));
+    }
+    for tenant in &recipients {
+        validate_id("recipient", tenant)
+            .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    }
+    let dest = path.with_file_name(format!("{}{}", name, EXTENSION));
+    if dest.exists() {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("{} already exists", dest.display()),
+        )
+        .with("path", dest.to_string_lossy()));
+    }
+
+    let plaintext = fs::read(path)?;
+    let file_key: [u8; 32] = ChaCha20Poly1305::generate_key(&mut OsRng).into();
+    let sealed = seal(&plaintext, &file_key, &recipients)?;
+    write_new(&dest, &sealed)?;
+
+    let shredded = options.shred_plaintext;
+    if shredded {
+        shred(path)?;
+    }
+    audit::record(
+        "note_encrypted",
+        None,
+        serde_json::json!({
+            "path": dest.to_string_lossy(),
+            "recipients": recipients,
+            "shredded": shredded,
+        }),
+    );
+    activity::record(ActivityKind::Write, &dest, None);
+    Ok(EncryptedNote {
+        path: dest.to_string_lossy().to_string(),
+        recipients,
+        shredded,
+    })
+}
+
+fn seal(plaintext: &[u8], file_key: &[u8; 32], recipients: &[String]) -> CommandResult<Vec<u8>> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut header = format!("{}\n", MAGIC);
+    for tenant in recipients {
+        let key = note_key(tenant, true)?.ok_or_else(|| {
+            CommandError::new(ErrorCode::Internal, "The note key wasn't saved")
+                .with("tenant_id", tenant)
+        })?;
+        let wrapped = secrets::seal_with(&key, file_key)?;
+        header.push_str(&format!("-> {} {}\n", tenant, engine.encode(wrapped)));
+    }
+    header.push_str(HEADER_END);
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(file_key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: header.as_bytes(),
+            },
+        )
+        .map_err(|_| CommandError::new(ErrorCode::Internal, "Encryption failed"))?;
+    let mut sealed = header.into_bytes();
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// `tenant`'s note key, made and saved first when `create` is set.
+fn note_key(tenant: &str, create: bool) -> CommandResult<Option<[u8; 32]>> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    if let Some(encoded) = secrets::read(tenant, KEY_NAMESPACE, KEY_NAME)? {
+        let key = engine
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        return match key {
+            Some(key) => Ok(Some(key)),
+            None => Err(CommandError::new(
+                ErrorCode::Internal,
+                format!("The note key for {} is damaged", tenant),
+            )
+            .with("tenant_id", tenant)),
+        };
+    }
+    if !create {
+        return Ok(None);
+    }
+    let key: [u8; 32] = ChaCha20Poly1305::generate_key(&mut OsRng).into();
+    secrets::write(tenant, KEY_NAMESPACE, KEY_NAME, &engine.encode(key))?;
+    log::info!("[note_encryption] created a note key for {}", tenant);
+    Ok(Some(key))
+}
+
+/// Write `contents` to `dest`, which must not exist, through a hidden
+/// partial file so a crash never leaves half a note.
+fn write_new(dest: &Path, contents: &[u8]) -> CommandResult<()> {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let part = dest.with_file_name(format!(".{}.partial", name));
+    let written = fs::File::create(&part)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&part, dest));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&part);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Overwrite `path` with zeros, then delete it.
+fn shred(path: &Path) -> CommandResult<()> {
+    let len = fs::metadata(path)?.len() as usize;
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    file.write_all(&vec![0; len])?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn plaintext_path(encrypted: &Path) -> CommandResult<PathBuf> {
+    let name = encrypted.file_name().unwrap_or_default().to_string_lossy();
+    match is_encrypted(encrypted) {
+        true => Ok(encrypted.with_file_name(&name[..name.len() - EXTENSION.len()])),
+        false => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Not an encrypted note: {}", encrypted.display()),
+        )
+        .with("path", encrypted.to_string_lossy())),
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_notes_parse_and_reject_tampering() {
+        let key = [7u8; 32];
+        let wrapped = secrets::seal_with(&key, &[1u8; 32]).unwrap();
+        let engine = base64::engine::general_purpose::STANDARD;
+        let header = format!("{}\n-> acme {}\n---\n", MAGIC, engine.encode(&wrapped));
+        let mut sealed = header.clone().into_bytes();
+        sealed.extend_from_slice(b"0123456789ab-body");
+
+        let (head, body) = split_header(&sealed).unwrap();
+        assert_eq!(head, header.as_bytes());
+        assert_eq!(body, b"0123456789ab-body");
+        let stanzas = parse_header(head).unwrap();
+        assert_eq!(stanzas, vec![("acme".to_string(), wrapped)]);
+
+        assert!(parse_header(b"age-encryption.org/v1\n---\n").is_none());
+        assert!(parse_header(format!("{}\n---\n", MAGIC).as_bytes()).is_none());
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_encrypted(Path::new("/v/Keys.md.age")));
+        assert!(is_encrypted(Path::new("/v/Keys.MD.AGE")));
+        assert!(!is_encrypted(Path::new("/v/Keys.md")));
+        assert!(!is_encrypted(Path::new("/v/photo.age")));
+        assert_eq!(
+            plaintext_path(Path::new("/v/Keys.md.age")).unwrap(),
+            Path::new("/v/Keys.md")
+        );
+    }
+}
@@ -0,0 +1,739 @@
+// Note moves with link rewriting
+//
+// `move_note` moves a file inside a vault and, when asked, rewrites the
+// links that pointed at it:
+// - Wikilinks (`[[Note]]`, `[[folder/Note#Heading|alias]]`, `![[image.png]]`)
+//   matched by vault path or, as Obsidian does, by a name that's unique in
+//   the vault. Anchors and aliases are kept.
+// - Relative Markdown links (`[text](../Note.md)`), including the moved
+//   note's own outgoing links, which now start from a different folder.
+// Code fences and inline code are left alone. Every rewrite is computed
+// before anything is written; if a write fails, the notes already written
+// are put back from their original contents and the move is undone. A dry
+// run (see `dry_run`) stops after the planning and reports the same notes.
+//
+// `bulk_rename` moves each note through `move_with_links` when asked to keep
+// links working.
+//
+// `LinkIndex` reads and resolves links by the same rules without changing
+// anything, for `export_vault_graph`.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{store_kinds, stores};
+
+#[derive(Serialize, Clone)]
+pub struct MoveNoteReport {
+    /// Vault-relative paths, `/`-separated.
+    pub from: String,
+    pub to: String,
+    pub updated_notes: Vec<String>,
+    #[serde(flatten)]
+    pub dry_run: DryRun,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Move `src` to `dst` (vault-relative or absolute paths inside the vault).
+#[tauri::command]
+pub fn move_note(
+    window: tauri::WebviewWindow,
+    vault_path: String,
+    src: String,
+    dst: String,
+    update_links: bool,
+    dry_run: Option<bool>,
+) -> CommandResult<MoveNoteReport> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    let vault = Path::new(&vault_path);
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", &vault_path));
+    }
+    let src_rel = vault_relative(vault, &src)?;
+    let dst_rel = vault_relative(vault, &dst)?;
+    let src_abs = vault.join(&src_rel);
+    let dst_abs = vault.join(&dst_rel);
+    if !src_abs.is_file() {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("Not found: {}", src))
+                .with("path", &src),
+        );
+    }
+    if dst_abs.exists() {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("Destination already exists: {}", dst),
+        )
+        .with("path", &dst));
+    }
+
+    let rewrites = if update_links {
+        plan_rewrites(vault, &src_rel, &dst_rel)?
+    } else {
+        Vec::new()
+    };
+    let (from, to) = (key(&src_rel), key(&dst_rel));
+    let planned = DryRun::outcome(dry_run, || {
+        format!(
+            "Move {} to {}, updating links in {} notes",
+            from,
+            to,
+            rewrites.len()
+        )
+    });
+    if !dry_run {
+        apply(&src_abs, &dst_abs, &rewrites)?;
+        activity::record(ActivityKind::Write, &dst_abs, None);
+    }
+    Ok(MoveNoteReport {
+        from,
+        to,
+        updated_notes: rewrites.into_iter().map(|r| r.note).collect(),
+        dry_run: planned,
+    })
+}
+
+/// Move one note the way `move_note` does, for `bulk_rename`; paths are
+/// vault-relative. The notes whose links changed, or would with `dry_run`.
+pub(crate) fn move_with_links(
+    vault: &Path,
+    src: &Path,
+    dst: &Path,
+    dry_run: bool,
+) -> CommandResult<Vec<String>> {
+    let rewrites = plan_rewrites(vault, src, dst)?;
+    if !dry_run {
+        apply(&vault.join(src), &vault.join(dst), &rewrites)?;
+    }
+    Ok(rewrites.into_iter().map(|r| r.note).collect())
+}
+
+// ─── Planning ───────────────────────────────────────────────────────────────
+
+struct Rewrite {
+    /// Vault-relative key of the note as it will be after the move.
+    note: String,
+    path: PathBuf,
+    original: String,
+    updated: String,
+}
+
+fn plan_rewrites(vault: &Path, src: &Path, dst: &Path) -> CommandResult<Vec<Rewrite>> {
+    let files: Vec<String> = stores::scan(vault, store_kinds::detect(vault))
+        .into_keys()
+        .collect();
+    let src_key = key(src);
+    let dst_key = key(dst);
+
+    // Name-only wikilinks resolve when the name is unique in the vault
+    let mut names: HashMap<String, usize> = HashMap::new();
+    for file in &files {
+        *names.entry(link_name(file).to_lowercase()).or_default() += 1;
+    }
+    let unique_before = names.get(&link_name(&src_key).to_lowercase()) == Some(&1);
+    if let Some(count) = names.get_mut(&link_name(&src_key).to_lowercase()) {
+        *count = count.saturating_sub(1);
+    }
+    *names.entry(link_name(&dst_key).to_lowercase()).or_default() += 1;
+    let unique_after = names.get(&link_name(&dst_key).to_lowercase()) == Some(&1);
+
+    let target = MoveTarget {
+        src: &src_key,
+        dst: &dst_key,
+        unique_before,
+        unique_after,
+    };
+    let mut rewrites = Vec::new();
+    for file in files.iter().filter(|f| f.ends_with(".md")) {
+        let path = vault.join(file);
+        let Ok(original) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let is_moved = *file == src_key;
+        let note_before = file.as_str();
+        let note_after = if is_moved {
+            dst_key.as_str()
+        } else {
+            note_before
+        };
+        let updated = rewrite_links(&original, &target, note_before, note_after);
+        if updated != original {
+            rewrites.push(Rewrite {
+                note: note_after.to_string(),
+                path: if is_moved { vault.join(dst) } else { path },
+                original,
+                updated,
+            });
+        }
+    }
+    Ok(rewrites)
+}
+
+struct MoveTarget<'a> {
+    src: &'a str,
+    dst: &'a str,
+    unique_before: bool,
+    unique_after: bool,
+}
+
+/// Rewrite every link in `text` outside code. `note_before`/`note_after`
+/// are the note's own key before and after the move.
+fn rewrite_links(text: &str, target: &MoveTarget, note_before: &str, note_after: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (segment, code) in segments(text) {
+        if code {
+            out.push_str(segment);
+        } else {
+            let segment = rewrite_wikilinks(segment, target);
+            out.push_str(&rewrite_markdown_links(
+                &segment,
+                target,
+                note_before,
+                note_after,
+            ));
+        }
+    }
+    out
+}
+
+/// `text` split into `(segment, is_code)` pieces that join back into it.
+/// Code is fenced blocks, inline code, and the backticks around it.
+fn segments(text: &str) -> Vec<(&str, bool)> {
+    let mut pieces = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (fence, marker) {
+            (None, Some(m)) => {
+                fence = Some(m);
+                pieces.push((line, true));
+                continue;
+            }
+            (Some(open), Some(m)) if open == m => {
+                fence = None;
+                pieces.push((line, true));
+                continue;
+            }
+            (Some(_), _) => {
+                pieces.push((line, true));
+                continue;
+            }
+            (None, None) => {}
+        }
+        // Odd backtick-delimited segments are inline code
+        for (i, segment) in line.split('`').enumerate() {
+            if i > 0 {
+                pieces.push(("`", true));
+            }
+            pieces.push((segment, i % 2 == 1));
+        }
+    }
+    pieces
+}
+
+fn rewrite_wikilinks(text: &str, target: &MoveTarget) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let Some(len) = rest[start + 2..].find("]]") else {
+            break;
+        };
+        out.push_str(&rest[..start + 2]);
+        let inner = &rest[start + 2..start + 2 + len];
+        let (link, alias) = match inner.split_once('|') {
+            Some((link, alias)) => (link, Some(alias)),
+            None => (inner, None),
+        };
+        let (path, anchor) = match link.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (link, None),
+        };
+        match wikilink_replacement(path.trim(), target) {
+            Some(new_path) => {
+                out.push_str(&new_path);
+                if let Some(anchor) = anchor {
+                    out.push('#');
+                    out.push_str(anchor);
+                }
+                if let Some(alias) = alias {
+                    out.push('|');
+                    out.push_str(alias);
+                }
+            }
+            None => out.push_str(inner),
+        }
+        out.push_str("]]");
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// New link text for a wikilink path pointing at the moved file, if it does.
+fn wikilink_replacement(path: &str, target: &MoveTarget) -> Option<String> {
+    let had_ext = path.to_lowercase().ends_with(".md");
+    let bare = if had_ext {
+        &path[..path.len() - 3]
+    } else {
+        path
+    };
+    let with_ext = |key: &str| {
+        let stripped = link_path(key);
+        if had_ext {
+            format!("{}.md", stripped)
+        } else {
+            stripped.to_string()
+        }
+    };
+
+    if bare.contains('/') {
+        if !bare.eq_ignore_ascii_case(link_path(target.src)) {
+            return None;
+        }
+        return Some(with_ext(target.dst));
+    }
+    if !target.unique_before || !bare.eq_ignore_ascii_case(link_name(target.src)) {
+        return None;
+    }
+    if target.unique_after {
+        let name = link_name(target.dst);
+        if name == link_name(target.src) {
+            // Still resolves by name from the new folder
+            return None;
+        }
+        Some(if had_ext {
+            format!("{}.md", name)
+        } else {
+            name.to_string()
+        })
+    } else {
+        Some(with_ext(target.dst))
+    }
+}
+
+fn rewrite_markdown_links(
+    text: &str,
+    target: &MoveTarget,
+    note_before: &str,
+    note_after: &str,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        out.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        let (dest, angled) = match rest.strip_prefix('<') {
+            Some(after) => match after.find('>') {
+                Some(end) => (&after[..end], true),
+                None => continue,
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| c == ')' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (&rest[..end], false)
+            }
+        };
+        let consumed = dest.len() + if angled { 2 } else { 0 };
+        match markdown_replacement(dest, target, note_before, note_after) {
+            Some(new_dest) if angled => out.push_str(&format!("<{}>", new_dest)),
+            Some(new_dest) => out.push_str(&new_dest),
+            None => out.push_str(&rest[..consumed]),
+        }
+        rest = &rest[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn markdown_replacement(
+    dest: &str,
+    target: &MoveTarget,
+    note_before: &str,
+    note_after: &str,
+) -> Option<String> {
+    // Anchors within the note, URLs, and `mailto:`-style links
+    if dest.is_empty() || dest.starts_with('#') || dest.contains(':') {
+        return None;
+    }
+    let (path, anchor) = match dest.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor)),
+        None => (dest, None),
+    };
+    let encoded = path.contains("%20");
+    let decoded = percent_decode(path);
+    let resolved = resolve_relative(note_before, &decoded)?;
+
+    let moved_own_link = note_before != note_after;
+    let new_target = if resolved.eq_ignore_ascii_case(target.src) {
+        target.dst.to_string()
+    } else if moved_own_link && !decoded.starts_with('/') {
+        resolved
+    } else {
+        return None;
+    };
+
+    let mut relative = relative_path(note_after, &new_target);
+    if encoded {
+        relative = relative.replace(' ', "%20");
+    }
+    if let Some(anchor) = anchor {
+        relative = format!("{}#{}", relative, anchor);
+    }
+    Some(relative)
+}
+
+// ─── Reading ───────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// `[[Note]]`
+    Wikilink,
+    /// `![[image.png]]` or `![alt](image.png)`
+    Embed,
+    /// `[text](Note.md)`
+    Markdown,
+}
+
+#[derive(Clone, Debug)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// The path as written, without anchor or alias.
+    pub target: String,
+    /// Key of the vault file it points at, if there is one.
+    pub resolved: Option<String>,
+}
+
+/// Resolves links to the keys of a vault's files, the way `move_note`
+/// matches them.
+pub struct LinkIndex {
+    /// Lowercased key to key.
+    by_key: HashMap<String, String>,
+    /// Lowercased wikilink path to key.
+    by_link_path: HashMap<String, String>,
+    /// Lowercased name to key, `None` when more than one file has it.
+    by_name: HashMap<String, Option<String>>,
+}
+
+impl LinkIndex {
+    pub fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut index = LinkIndex {
+            by_key: HashMap::new(),
+            by_link_path: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+        for key in keys {
+            index.by_key.insert(key.to_lowercase(), key.to_string());
+            index
+                .by_link_path
+                .insert(link_path(key).to_lowercase(), key.to_string());
+            index
+                .by_name
+                .entry(link_name(key).to_lowercase())
+                .and_modify(|found| *found = None)
+                .or_insert_with(|| Some(key.to_string()));
+        }
+        index
+    }
+
+    /// The links in `text`, the note with key `note`, outside code.
+    pub fn links(&self, note: &str, text: &str) -> Vec<Link> {
+        let mut links = Vec::new();
+        for (segment, code) in segments(text) {
+            if code {
+                continue;
+            }
+            for (path, embed) in wikilinks_in(segment) {
+                links.push(Link {
+                    kind: if embed {
+                        LinkKind::Embed
+                    } else {
+                        LinkKind::Wikilink
+                    },
+                    target: path.to_string(),
+                    resolved: self.resolve_wikilink(path),
+                });
+            }
+            for (dest, embed) in markdown_links_in(segment) {
+                let resolved = resolve_relative(note, &dest)
+                    .and_then(|key| self.by_key.get(&key.to_lowercase()))
+                    .cloned();
+                links.push(Link {
+                    kind: if embed {
+                        LinkKind::Embed
+                    } else {
+                        LinkKind::Markdown
+                    },
+                    target: dest,
+                    resolved,
+                });
+            }
+        }
+        links
+    }
+
+    fn resolve_wikilink(&self, path: &str) -> Option<String> {
+        let bare = match path.to_lowercase().strip_suffix(".md") {
+            Some(bare) => bare.to_string(),
+            None => path.to_lowercase(),
+        };
+        if bare.contains('/') {
+            self.by_link_path.get(&bare).cloned()
+        } else {
+            self.by_name.get(&bare).cloned().flatten()
+        }
+    }
+}
+
+/// `(path, is_embed)` for each wikilink in `text`.
+fn wikilinks_in(text: &str) -> Vec<(&str, bool)> {
+    let mut links = Vec::new();
+    let mut at = 0;
+    while let Some(pos) = text[at..].find("[[") {
+        let start = at + pos;
+        let Some(len) = text[start + 2..].find("]]") else {
+            break;
+        };
+        let inner = &text[start + 2..start + 2 + len];
+        let link = inner.split('|').next().unwrap_or(inner);
+        let path = link.split('#').next().unwrap_or(link).trim();
+        if !path.is_empty() {
+            links.push((path, text[..start].ends_with('!')));
+        }
+        at = start + 2 + len + 2;
+    }
+    links
+}
+
+/// `(decoded destination, is_embed)` for each Markdown link in `text` to a
+/// file, leaving out URLs and anchors within the note.
+fn markdown_links_in(text: &str) -> Vec<(String, bool)> {
+    let mut links = Vec::new();
+    let mut at = 0;
+    while let Some(pos) = text[at..].find("](") {
+        let close = at + pos;
+        at = close + 2;
+        let rest = &text[at..];
+        let dest = match rest.strip_prefix('<') {
+            Some(after) => match after.find('>') {
+                Some(end) => &after[..end],
+                None => continue,
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| c == ')' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                &rest[..end]
+            }
+        };
+        if dest.is_empty() || dest.starts_with('#') || dest.contains(':') {
+            continue;
+        }
+        let Some(open) = text[..close].rfind('[') else {
+            continue;
+        };
+        if text[open..close].contains("[[") {
+            continue;
+        }
+        let path = dest.split('#').next().unwrap_or(dest);
+        links.push((percent_decode(path), text[..open].ends_with('!')));
+    }
+    links
+}
+
+// ─── Applying ───────────────────────────────────────────────────────────────
+
+fn apply(src: &Path, dst: &Path, rewrites: &[Rewrite]) -> CommandResult<()> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(src, dst)?;
+
+    let mut written: Vec<&Rewrite> = Vec::new();
+    for rewrite in rewrites {
+        if let Err(e) = fs::write(&rewrite.path, &rewrite.updated) {
+            log::error!(
+                "[notes] failed to update {}: {}; rolling back",
+                rewrite.path.display(),
+                e
+            );
+            for done in written {
+                if let Err(e) = fs::write(&done.path, &done.original) {
+                    log::error!("[notes] rollback of {} failed: {}", done.path.display(), e);
+                }
+            }
+            if let Err(e) = fs::rename(dst, src) {
+                log::error!("[notes] failed to move {} back: {}", dst.display(), e);
+            }
+            return Err(CommandError::from(e).with("path", rewrite.path.to_string_lossy()));
+        }
+        written.push(rewrite);
+    }
+    Ok(())
+}
+
+// ─── Paths ──────────────────────────────────────────────────────────────────
+
+/// `path` relative to the vault, rejecting anything that escapes it.
+fn vault_relative(vault: &Path, path: &str) -> CommandResult<PathBuf> {
+    let path = Path::new(path);
+    let relative = if path.is_absolute() {
+        path.strip_prefix(vault).map(Path::to_path_buf).ok()
+    } else {
+        Some(path.to_path_buf())
+    };
+    relative
+        .filter(|r| {
+            r.components().next().is_some()
+                && r.components().all(|c| matches!(c, Component::Normal(_)))
+        })
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("{} is not inside the vault", path.display()),
+            )
+            .with("path", path.to_string_lossy())
+        })
+}
+
+fn key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Key as written in a wikilink: notes drop `.md`, attachments keep theirs.
+fn link_path(key: &str) -> &str {
+    key.strip_suffix(".md").unwrap_or(key)
+}
+
+fn link_name(key: &str) -> &str {
+    let path = link_path(key);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Resolve a Markdown link from `note` (a key) to a key; `/` is the vault root.
+fn resolve_relative(note: &str, link: &str) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    if !link.starts_with('/') {
+        parts.extend(note.split('/'));
+        parts.pop();
+    }
+    for part in link.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Relative link from `note` to `target`, both keys.
+fn relative_path(note: &str, target: &str) -> String {
+    let from: Vec<&str> = note.split('/').collect();
+    let from = &from[..from.len() - 1];
+    let to: Vec<&str> = target.split('/').collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<&str> = vec![".."; from.len() - common];
+    parts.extend(&to[common..]);
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn read(home: &TestHome, relative: &str) -> String {
+        fs::read_to_string(home.join(relative)).unwrap()
+    }
+
+    #[test]
+    fn links_in_code_stay_and_anchors_and_aliases_survive() {
+        let home = TestHome::new();
+        home.write("Vault/Ideas/Plan.md", "# Plan\n");
+        home.write("Vault/Other/Plan.md", "# Another plan\n");
+        home.write(
+            "Vault/Index.md",
+            "See [[Ideas/Plan#Goals|the plan]] and [text](Ideas/Plan.md#goals).\n\
+             Inline `[[Ideas/Plan]]` and `[x](Ideas/Plan.md)` stay.\n\
+             ```\n[[Ideas/Plan]]\n[x](Ideas/Plan.md)\n```\n",
+        );
+
+        let vault = home.join("Vault");
+        let updated = move_with_links(
+            &vault,
+            Path::new("Ideas/Plan.md"),
+            Path::new("Archive/Plan.md"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(updated, ["Index.md"]);
+        assert_eq!(
+            read(&home, "Vault/Index.md"),
+            "See [[Archive/Plan#Goals|the plan]] and [text](Archive/Plan.md#goals).\n\
+             Inline `[[Ideas/Plan]]` and `[x](Ideas/Plan.md)` stay.\n\
+             ```\n[[Ideas/Plan]]\n[x](Ideas/Plan.md)\n```\n"
+        );
+        assert!(home.join("Vault/Archive/Plan.md").is_file());
+    }
+
+    #[test]
+    fn a_failed_write_puts_every_note_and_the_move_back() {
+        let home = TestHome::new();
+        home.write("Vault/Plan.md", "# Plan\n");
+        home.write("Vault/a.md", "[plan](Plan.md)\n");
+        home.write("Vault/b.md", "[plan](Plan.md)\n");
+
+        let vault = home.join("Vault");
+        let (src, dst) = (Path::new("Plan.md"), Path::new("Done/Plan.md"));
+        let rewrites = plan_rewrites(&vault, src, dst).unwrap();
+        assert_eq!(rewrites.len(), 2);
+
+        // `a.md` is written first; `b.md` can't be once it's a directory
+        fs::remove_file(home.join("Vault/b.md")).unwrap();
+        fs::create_dir(home.join("Vault/b.md")).unwrap();
+        assert!(apply(&vault.join(src), &vault.join(dst), &rewrites).is_err());
+
+        assert_eq!(read(&home, "Vault/a.md"), "[plan](Plan.md)\n");
+        assert_eq!(read(&home, "Vault/Plan.md"), "# Plan\n");
+        assert!(!home.join("Vault/Done/Plan.md").exists());
+    }
+}
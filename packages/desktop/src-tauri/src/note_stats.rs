@@ -0,0 +1,348 @@
+// Note statistics
+//
+// `get_note_stats` gives the preview pane word and character counts, reading
+// time, the heading outline, and link/tag/task/code-block counts in one pass
+// over the note. Text in scripts written without spaces (CJK) counts one word
+// per character. Frontmatter is excluded from word counts but its `tags`
+// are counted; code is excluded from everything but words.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::tasks;
+use crate::text_style::{self, TextStyle};
+
+/// Typical silent reading speeds.
+const WORDS_PER_MINUTE: usize = 238;
+const CJK_CHARS_PER_MINUTE: usize = 500;
+
+#[derive(Serialize, Clone)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// 1-based.
+    pub line: usize,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct NoteStats {
+    pub words: usize,
+    /// Excluding line breaks.
+    pub characters: usize,
+    pub characters_excluding_spaces: usize,
+    pub reading_time_minutes: usize,
+    pub headings: Vec<Heading>,
+    /// Wikilinks and Markdown links.
+    pub links: usize,
+    /// `![[...]]` and `![](...)`.
+    pub embeds: usize,
+    pub tags: usize,
+    /// Distinct tags without the `#`, in order of appearance.
+    #[serde(skip)]
+    pub tag_names: Vec<String>,
+    pub tasks_open: usize,
+    pub tasks_done: usize,
+    pub code_blocks: usize,
+    pub style: Option<TextStyle>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_note_stats(path: String) -> CommandResult<NoteStats> {
+    let bytes = fs::read(Path::new(&path)).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+                .with("path", &path)
+        }
+        _ => e.into(),
+    })?;
+    let style = text_style::detect(&bytes);
+    let mut stats = compute(&text_style::decode(&bytes, &style));
+    stats.style = Some(style);
+    Ok(stats)
+}
+
+// ─── Counting ───────────────────────────────────────────────────────────────
+
+pub fn compute(text: &str) -> NoteStats {
+    let mut stats = NoteStats::default();
+    let mut latin_words = 0;
+    let mut cjk_chars = 0;
+    let mut fence: Option<&str> = None;
+    let mut frontmatter = false;
+    let mut in_frontmatter_tags = false;
+
+    for (i, line) in text.lines().enumerate() {
+        if i == 0 && line.trim_end() == "---" {
+            frontmatter = true;
+            continue;
+        }
+        if frontmatter {
+            if line.trim_end() == "---" || line.trim_end() == "..." {
+                frontmatter = false;
+            } else {
+                for tag in frontmatter_tags(line, &mut in_frontmatter_tags) {
+                    add_tag(&mut stats, tag);
+                }
+            }
+            continue;
+        }
+
+        let chars = line.chars().count();
+        stats.characters += chars;
+        stats.characters_excluding_spaces += line.chars().filter(|c| !c.is_whitespace()).count();
+        let (words, cjk) = count_words(line);
+        latin_words += words;
+        cjk_chars += cjk;
+
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (fence, marker) {
+            (None, Some(m)) => {
+                fence = Some(m);
+                stats.code_blocks += 1;
+                continue;
+            }
+            (Some(open), Some(m)) if open == m => {
+                fence = None;
+                continue;
+            }
+            (Some(_), _) => continue,
+            (None, None) => {}
+        }
+
+        if let Some(heading) = parse_heading(trimmed, i + 1) {
+            stats.headings.push(heading);
+        }
+        match task_state(trimmed) {
+            Some(true) => stats.tasks_done += 1,
+            Some(false) => stats.tasks_open += 1,
+            None => {}
+        }
+        // Odd backtick-delimited segments are inline code
+        for segment in line.split('`').step_by(2) {
+            count_links(segment, &mut stats);
+            for tag in segment
+                .split(|c: char| c.is_whitespace())
+                .filter_map(tag_name)
+            {
+                add_tag(&mut stats, tag);
+            }
+        }
+    }
+
+    stats.words = latin_words + cjk_chars;
+    let minutes = latin_words as f64 / WORDS_PER_MINUTE as f64
+        + cjk_chars as f64 / CJK_CHARS_PER_MINUTE as f64;
+    stats.reading_time_minutes = match stats.words {
+        0 => 0,
+        _ => (minutes.ceil() as usize).max(1),
+    };
+    stats
+}
+
+/// (space-separated words, CJK characters) in `line`.
+fn count_words(line: &str) -> (usize, usize) {
+    let mut words = 0;
+    let mut cjk = 0;
+    let mut in_word = false;
+    for c in line.chars() {
+        if is_cjk(c) {
+            cjk += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() || (in_word && matches!(c, '\'' | '’' | '-' | '_')) {
+            if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        } else {
+            in_word = false;
+        }
+    }
+    (words, cjk)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana, Katakana
+        | 0x3400..=0x4DBF    // CJK Extension A
+        | 0x4E00..=0x9FFF    // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF    // Hangul syllables
+        | 0xF900..=0xFAFF    // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F) // Extensions B–F, compatibility supplement
+}
+
+fn parse_heading(line: &str, number: usize) -> Option<Heading> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some(Heading {
+        level: level as u8,
+        text: text.to_string(),
+        line: number,
+    })
+}
+
+/// `Some(done)` for a task list item.
+fn task_state(line: &str) -> Option<bool> {
+    match tasks::parse_checkbox(line)?.status {
+        ' ' => Some(false),
+        'x' | 'X' => Some(true),
+        _ => None,
+    }
+}
+
+fn count_links(text: &str, stats: &mut NoteStats) {
+    let bytes = text.as_bytes();
+    let embedded = |at: usize| at > 0 && bytes[at - 1] == b'!';
+    let mut at = 0;
+    while let Some(pos) = text[at..].find("[[") {
+        let start = at + pos;
+        if text[start..].contains("]]") {
+            if embedded(start) {
+                stats.embeds += 1;
+            } else {
+                stats.links += 1;
+            }
+        }
+        at = start + 2;
+    }
+    at = 0;
+    while let Some(pos) = text[at..].find("](") {
+        let close = at + pos;
+        if let Some(open) = text[..close].rfind('[') {
+            if !text[open..close].contains("[[") {
+                if embedded(open) {
+                    stats.embeds += 1;
+                } else {
+                    stats.links += 1;
+                }
+            }
+        }
+        at = close + 2;
+    }
+}
+
+fn add_tag(stats: &mut NoteStats, tag: &str) {
+    stats.tags += 1;
+    if !stats.tag_names.iter().any(|t| t == tag) {
+        stats.tag_names.push(tag.to_string());
+    }
+}
+
+/// Obsidian tags: `#` then letters, digits, `_`, `-`, `/`, not all digits.
+fn tag_name(word: &str) -> Option<&str> {
+    let tag = word
+        .strip_prefix('#')?
+        .trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '_');
+    let valid = !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+        && !tag.chars().all(|c| c.is_ascii_digit());
+    valid.then_some(tag)
+}
+
+/// `tags: a, b`, `tags: [a, b]`, or a `tags:` list of `- a` items.
+fn frontmatter_tags<'a>(line: &'a str, in_tags: &mut bool) -> Vec<&'a str> {
+    let clean = |t: &'a str| t.trim().trim_matches(['"', '\'']);
+    if *in_tags {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            return Some(clean(item))
+                .filter(|t| !t.is_empty())
+                .into_iter()
+                .collect();
+        }
+        *in_tags = false;
+    }
+    let Some(value) = line
+        .strip_prefix("tags:")
+        .or_else(|| line.strip_prefix("tag:"))
+    else {
+        return Vec::new();
+    };
+    let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+    if value.is_empty() {
+        *in_tags = true;
+        return Vec::new();
+    }
+    value
+        .split([',', ' '])
+        .map(clean)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cjk_text_counts_a_word_per_character() {
+        assert_eq!(count_words("日本語のテキスト and two words"), (3, 8));
+        assert_eq!(count_words("漢字abc"), (1, 2));
+        assert_eq!(count_words("안녕하세요 세계"), (0, 7));
+        assert_eq!(count_words("don't re-use it — 42 %"), (4, 0));
+
+        let stats = compute("日本語のテキスト\nand two words\n");
+        assert_eq!(stats.words, 11);
+        assert_eq!(stats.characters, 21);
+        assert_eq!(stats.characters_excluding_spaces, 19);
+        assert_eq!(stats.reading_time_minutes, 1);
+    }
+
+    #[test]
+    fn reading_time_rounds_up_and_is_zero_only_when_empty() {
+        assert_eq!(compute("").reading_time_minutes, 0);
+        assert_eq!(
+            compute("word ".repeat(500).as_str()).reading_time_minutes,
+            3
+        );
+        assert_eq!(compute(&"字".repeat(1000)).reading_time_minutes, 2);
+    }
+
+    #[test]
+    fn frontmatter_is_skipped_but_its_tags_count() {
+        let stats = compute("---\ntitle: Plan\ntags: [work, q3]\n---\nBody #urgent #work\n");
+        assert_eq!(stats.words, 3);
+        assert_eq!(stats.tags, 4);
+        assert_eq!(stats.tag_names, ["work", "q3", "urgent"]);
+
+        let listed = compute("---\ntags:\n  - a\n  - \"b\"\naliases: x\n---\n");
+        assert_eq!(listed.tag_names, ["a", "b"]);
+        assert_eq!(listed.words, 0);
+    }
+
+    #[test]
+    fn code_hides_headings_links_and_tags() {
+        let note = "```\n# not a heading [[x]] #tag\n```\n## Real ##\n#123 #ok\n\
+                    `[[inline]]` [[Link]] ![[img.png]] [md](a.md) ![alt](b.png)\n";
+        let stats = compute(note);
+        assert_eq!(stats.code_blocks, 1);
+        assert_eq!(stats.headings.len(), 1);
+        assert_eq!(
+            (stats.headings[0].level, &*stats.headings[0].text),
+            (2, "Real")
+        );
+        assert_eq!(stats.headings[0].line, 4);
+        assert_eq!((stats.links, stats.embeds), (2, 2));
+        assert_eq!(stats.tag_names, ["ok"]);
+    }
+
+    #[test]
+    fn only_open_and_done_checkboxes_are_tasks() {
+        let stats = compute("- [ ] open\n* [x] done\n1. [X] also done\n- [-] cancelled\n-[ ] no\n");
+        assert_eq!((stats.tasks_open, stats.tasks_done), (1, 2));
+    }
+}
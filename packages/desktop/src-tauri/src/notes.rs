@@ -0,0 +1,114 @@
+// Note editing
+//
+// Writes into Markdown notes in a user's vault or store. Appends keep the
+// note's existing line endings and BOM (see `text_style`), and can be dry
+// runs (see `dry_run`).
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::text_style::{self, TextWriteOptions};
+use crate::write_guard;
+
+/// Append `text` to a Markdown note, creating it (and its folder) if needed.
+/// The text always starts on a fresh line and ends with a newline. With
+/// `expected_hash`, fails with `Conflict` if the note changed since it was read.
+#[tauri::command]
+pub fn append_to_note(
+    window: tauri::WebviewWindow,
+    path: String,
+    text: String,
+    options: Option<TextWriteOptions>,
+    expected_hash: Option<String>,
+    dry_run: Option<bool>,
+) -> CommandResult<DryRun> {
+    append_with(
+        Path::new(&path),
+        &text,
+        &options.unwrap_or_default(),
+        expected_hash.as_deref(),
+        dry_run::requested(window.label(), None, dry_run),
+    )
+}
+
+/// Replace `path` via a temporary sibling and a rename, so readers never see
+/// a half-written file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> CommandResult<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.agentvbx-tmp", name));
+    fs::write(&temp, bytes)?;
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+pub fn append(path: &Path, text: &str) -> CommandResult<()> {
+    append_with(path, text, &TextWriteOptions::default(), None, false).map(|_| ())
+}
+
+pub fn append_with(
+    path: &Path,
+    text: &str,
+    options: &TextWriteOptions,
+    expected_hash: Option<&str>,
+    dry_run: bool,
+) -> CommandResult<DryRun> {
+    if path.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Not a Markdown note: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()));
+    }
+
+    let _guard = write_guard::lock();
+    let current = write_guard::read_current(path)?;
+    if let Some(expected) = expected_hash {
+        write_guard::check(path, current.as_deref(), expected, None)?;
+    }
+    let existing = current.unwrap_or_default();
+    let style = (!existing.is_empty()).then(|| text_style::detect(&existing));
+    let (ending, bom) = text_style::resolve(path, style.as_ref(), options)?;
+    let needs_newline = !existing.is_empty() && !existing.ends_with(b"\n");
+
+    let mut out = Vec::new();
+    if existing.is_empty() && bom {
+        out.extend_from_slice(text_style::UTF8_BOM);
+    }
+    if needs_newline {
+        out.extend_from_slice(ending.as_str().as_bytes());
+    }
+    let text = text.trim_end_matches(['\r', '\n']);
+    out.extend_from_slice(text_style::normalize(text, ending).as_bytes());
+    out.extend_from_slice(ending.as_str().as_bytes());
+    if dry_run {
+        let verb = if existing.is_empty() {
+            "Create"
+        } else {
+            "Append to"
+        };
+        return Ok(DryRun::planned(format!(
+            "{} {} ({} bytes)",
+            verb,
+            path.display(),
+            out.len()
+        )));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&out)?;
+    activity::record(ActivityKind::Write, path, None);
+    Ok(DryRun::default())
+}
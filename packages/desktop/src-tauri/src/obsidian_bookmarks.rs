@@ -0,0 +1,412 @@
+// Obsidian bookmarks
+//
+// The shortcuts a user curates in Obsidian's Bookmarks core plugin are good
+// context for agents, so they're surfaced here. `get_vault_bookmarks` reads
+// `.obsidian/bookmarks.json` into a tree: groups nest, and every other entry
+// is a file, folder, heading, block, search, URL, or graph bookmark. Headings
+// and blocks are file bookmarks with a `subpath` (`#Heading`, `#^block-id`);
+// older vaults tag them `heading` and `block`, and both read the same.
+// Paths come back vault-relative and absolute, with whether they still exist.
+//
+// Vaults from before Bookmarks have the Starred plugin's
+// `.obsidian/starred.json`, a flat list in the same entry shape; it's read when
+// there's no usable `bookmarks.json`. Entries that don't parse, or whose path
+// leaves the vault, are skipped with a warning naming where they were.
+//
+// Scans of a vault mark bookmarked files `bookmarked` in the snapshot, and
+// `query_files` and `find_similar_notes` report the flag; similarity ranking
+// gives bookmarked notes a small boost.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+
+const BOOKMARKS_FILE: &str = ".obsidian/bookmarks.json";
+const STARRED_FILE: &str = ".obsidian/starred.json";
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BookmarkKind {
+    File,
+    Folder,
+    Heading,
+    Block,
+    Search,
+    Url,
+    Graph,
+    Group,
+}
+
+impl BookmarkKind {
+    /// Bookmarks of one note, or a place in it.
+    fn is_note(self) -> bool {
+        matches!(self, Self::File | Self::Heading | Self::Block)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Bookmark {
+    pub kind: BookmarkKind,
+    pub title: Option<String>,
+    /// Vault-relative, for files, folders, headings, and blocks.
+    pub relative_path: Option<String>,
+    pub path: Option<String>,
+    /// `#Heading` or `#^block-id` within the file.
+    pub subpath: Option<String>,
+    pub query: Option<String>,
+    pub url: Option<String>,
+    /// Whether the file or folder is there; `None` for kinds without one.
+    pub exists: Option<bool>,
+    /// A group's bookmarks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Bookmark>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BookmarkSource {
+    Bookmarks,
+    Starred,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BookmarkWarning {
+    /// The file, then the entry: `bookmarks.json items[2].items[0]`.
+    pub location: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VaultBookmarks {
+    /// `None` when the vault has neither file.
+    pub source: Option<BookmarkSource>,
+    pub bookmarks: Vec<Bookmark>,
+    pub warnings: Vec<BookmarkWarning>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_vault_bookmarks(vault_path: String) -> CommandResult<VaultBookmarks> {
+    let vault = Path::new(&vault_path);
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", &vault_path));
+    }
+    Ok(read(vault))
+}
+
+// ─── Reading ────────────────────────────────────────────────────────────────
+
+/// The vault's bookmarks, from `bookmarks.json` or else `starred.json`.
+pub fn read(vault: &Path) -> VaultBookmarks {
+    let mut found = VaultBookmarks {
+        source: None,
+        bookmarks: Vec::new(),
+        warnings: Vec::new(),
+    };
+    for (source, file) in [
+        (BookmarkSource::Bookmarks, BOOKMARKS_FILE),
+        (BookmarkSource::Starred, STARRED_FILE),
+    ] {
+        let Ok(raw) = fs::read_to_string(vault.join(file)) else {
+            continue;
+        };
+        let name = file.trim_start_matches(".obsidian/");
+        let items = match serde_json::from_str::<Value>(&raw) {
+            Ok(Value::Object(mut root)) => root.remove("items"),
+            Ok(_) => None,
+            Err(e) => {
+                found.warn(name, format!("not valid JSON: {}", e));
+                continue;
+            }
+        };
+        let Some(Value::Array(items)) = items else {
+            found.warn(name, "no `items` list".to_string());
+            continue;
+        };
+        let mut parser = Parser {
+            vault,
+            warnings: &mut found.warnings,
+            groups: source == BookmarkSource::Bookmarks,
+        };
+        found.bookmarks = parser.items(&items, &format!("{} items", name));
+        found.source = Some(source);
+        break;
+    }
+    found
+}
+
+/// Vault-relative paths of the notes bookmarked in `vault`.
+pub fn bookmarked_paths(vault: &Path) -> BTreeSet<String> {
+    fn collect(bookmarks: &[Bookmark], paths: &mut BTreeSet<String>) {
+        for bookmark in bookmarks {
+            if bookmark.kind.is_note() {
+                paths.extend(bookmark.relative_path.clone());
+            }
+            collect(&bookmark.children, paths);
+        }
+    }
+
+    let mut paths = BTreeSet::new();
+    if vault.join(BOOKMARKS_FILE).is_file() || vault.join(STARRED_FILE).is_file() {
+        collect(&read(vault).bookmarks, &mut paths);
+    }
+    paths
+}
+
+impl VaultBookmarks {
+    fn warn(&mut self, location: &str, message: String) {
+        log::debug!("[obsidian_bookmarks] {}: {}", location, message);
+        self.warnings.push(BookmarkWarning {
+            location: location.to_string(),
+            message,
+        });
+    }
+}
+
+struct Parser<'a> {
+    vault: &'a Path,
+    warnings: &'a mut Vec<BookmarkWarning>,
+    /// Starred lists don't nest.
+    groups: bool,
+}
+
+impl Parser<'_> {
+    fn items(&mut self, items: &[Value], location: &str) -> Vec<Bookmark> {
+        items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                let location = format!("{}[{}]", location, i);
+                match self.item(item, &location) {
+                    Ok(bookmark) => Some(bookmark),
+                    Err(message) => {
+                        log::debug!("[obsidian_bookmarks] {}: {}", location, message);
+                        self.warnings.push(BookmarkWarning { location, message });
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn item(&mut self, item: &Value, location: &str) -> Result<Bookmark, String> {
+        let text = |field: &str| -> Result<Option<String>, String> {
+            match item.get(field) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => Err(format!("`{}` is not a string", field)),
+            }
+        };
+        let required = |field: &str| -> Result<String, String> {
+            text(field)?
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| format!("missing `{}`", field))
+        };
+        if !item.is_object() {
+            return Err("not an object".into());
+        }
+        let kind = match required("type")?.as_str() {
+            "file" => match text("subpath")? {
+                Some(sub) if sub.starts_with("#^") => BookmarkKind::Block,
+                Some(sub) if sub.starts_with('#') => BookmarkKind::Heading,
+                _ => BookmarkKind::File,
+            },
+            "heading" => BookmarkKind::Heading,
+            "block" => BookmarkKind::Block,
+            "folder" => BookmarkKind::Folder,
+            "search" => BookmarkKind::Search,
+            "url" => BookmarkKind::Url,
+            "graph" => BookmarkKind::Graph,
+            "group" if self.groups => BookmarkKind::Group,
+            other => return Err(format!("unknown bookmark type `{}`", other)),
+        };
+
+        let mut bookmark = Bookmark {
+            kind,
+            title: text("title")?.filter(|t| !t.is_empty()),
+            relative_path: None,
+            path: None,
+            subpath: None,
+            query: None,
+            url: None,
+            exists: None,
+            children: Vec::new(),
+        };
+        match kind {
+            BookmarkKind::File
+            | BookmarkKind::Folder
+            | BookmarkKind::Heading
+            | BookmarkKind::Block => {
+                let relative = required("path")?;
+                if !inside_vault(&relative) {
+                    return Err(format!("path `{}` leaves the vault", relative));
+                }
+                let path = self.vault.join(&relative);
+                bookmark.exists = Some(match kind {
+                    BookmarkKind::Folder => path.is_dir(),
+                    _ => path.is_file(),
+                });
+                bookmark.path = Some(path.to_string_lossy().to_string());
+                bookmark.relative_path = Some(relative);
+                bookmark.subpath = text("subpath")?.filter(|s| !s.is_empty());
+            }
+            BookmarkKind::Search => bookmark.query = Some(required("query")?),
+            BookmarkKind::Url => bookmark.url = Some(required("url")?),
+            BookmarkKind::Graph => {}
+            BookmarkKind::Group => {
+                let Some(Value::Array(items)) = item.get("items") else {
+                    return Err("group has no `items` list".into());
+                };
+                bookmark.children = self.items(items, &format!("{}.items", location));
+            }
+        }
+        Ok(bookmark)
+    }
+}
+
+/// A relative path that stays inside the vault.
+fn inside_vault(relative: &str) -> bool {
+    Path::new(relative)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store_kinds::StoreKind;
+    use crate::stores;
+
+    const BOOKMARKS: &str = r##"{"items": [
+        {"type": "file", "ctime": 1, "path": "Projects/Plan.md", "title": "Plan"},
+        {"type": "group", "ctime": 2, "title": "Reading", "items": [
+            {"type": "file", "path": "Notes/Book.md", "subpath": "#Chapter 1"},
+            {"type": "file", "path": "Notes/Book.md", "subpath": "#^quote"},
+            {"type": "heading", "path": "Notes/Gone.md", "subpath": "#Old"},
+            {"type": "search", "query": "tag:#todo"}
+        ]},
+        {"type": "folder", "path": "Projects"},
+        {"type": "url", "url": "https://example.com"},
+        {"type": "graph"}
+    ]}"##;
+
+    fn vault() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["Projects/Plan.md", "Notes/Book.md", "Notes/Other.md"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "# Note").unwrap();
+        }
+        fs::create_dir_all(dir.path().join(".obsidian")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_nested_groups_and_every_kind() {
+        let dir = vault();
+        fs::write(dir.path().join(BOOKMARKS_FILE), BOOKMARKS).unwrap();
+
+        let found = read(dir.path());
+        assert_eq!(found.source, Some(BookmarkSource::Bookmarks));
+        assert!(found.warnings.is_empty(), "{:?}", found.warnings);
+        let kinds: Vec<BookmarkKind> = found.bookmarks.iter().map(|b| b.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                BookmarkKind::File,
+                BookmarkKind::Group,
+                BookmarkKind::Folder,
+                BookmarkKind::Url,
+                BookmarkKind::Graph
+            ]
+        );
+        assert_eq!(found.bookmarks[0].title.as_deref(), Some("Plan"));
+        assert_eq!(found.bookmarks[0].exists, Some(true));
+        assert_eq!(found.bookmarks[2].exists, Some(true));
+
+        let group = &found.bookmarks[1];
+        let kinds: Vec<BookmarkKind> = group.children.iter().map(|b| b.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                BookmarkKind::Heading,
+                BookmarkKind::Block,
+                BookmarkKind::Heading,
+                BookmarkKind::Search
+            ]
+        );
+        assert_eq!(group.children[1].subpath.as_deref(), Some("#^quote"));
+        assert_eq!(group.children[2].exists, Some(false));
+        assert_eq!(group.children[3].query.as_deref(), Some("tag:#todo"));
+        assert_eq!(group.children[3].exists, None);
+    }
+
+    #[test]
+    fn malformed_entries_are_skipped_with_warnings() {
+        let dir = vault();
+        let raw = r#"{"items": [
+            {"type": "file", "path": "Projects/Plan.md"},
+            {"type": "file"},
+            {"type": "file", "path": "../outside.md"},
+            "just a string",
+            {"type": "group", "items": [{"type": "hologram"}]}
+        ]}"#;
+        fs::write(dir.path().join(BOOKMARKS_FILE), raw).unwrap();
+
+        let found = read(dir.path());
+        assert_eq!(found.bookmarks.len(), 2);
+        let locations: Vec<&str> = found.warnings.iter().map(|w| w.location.as_str()).collect();
+        assert_eq!(
+            locations,
+            [
+                "bookmarks.json items[1]",
+                "bookmarks.json items[2]",
+                "bookmarks.json items[3]",
+                "bookmarks.json items[4].items[0]"
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_starred() {
+        let dir = vault();
+        fs::write(dir.path().join(BOOKMARKS_FILE), "{not json").unwrap();
+        let starred = r#"{"items": [
+            {"type": "file", "title": "Other", "path": "Notes/Other.md"},
+            {"type": "search", "title": "Open", "query": "- [ ]"},
+            {"type": "group", "items": []}
+        ]}"#;
+        fs::write(dir.path().join(STARRED_FILE), starred).unwrap();
+
+        let found = read(dir.path());
+        assert_eq!(found.source, Some(BookmarkSource::Starred));
+        assert_eq!(found.bookmarks.len(), 2);
+        assert_eq!(found.warnings.len(), 2);
+        assert_eq!(found.warnings[0].location, "bookmarks.json");
+        assert_eq!(found.warnings[1].location, "starred.json items[2]");
+    }
+
+    #[test]
+    fn scans_mark_bookmarked_notes() {
+        let dir = vault();
+        fs::write(dir.path().join(BOOKMARKS_FILE), BOOKMARKS).unwrap();
+
+        let snapshot = stores::scan(dir.path(), StoreKind::ObsidianVault);
+        assert!(snapshot["Projects/Plan.md"].bookmarked);
+        assert!(snapshot["Notes/Book.md"].bookmarked);
+        assert!(!snapshot["Notes/Other.md"].bookmarked);
+    }
+}
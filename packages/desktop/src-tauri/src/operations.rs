@@ -0,0 +1,687 @@
+// Long-running operations
+//
+// Store rescans, vault discovery, backups, restores, task extraction, store
+// health checks, and audio and artifact transfers all go through this
+// registry. Each one registers with a kind
+// and a weight. It waits as `queued` while starting would go over one of two
+// limits: the total weight in `settings.operations.max_concurrent_weight`, or
+// that kind's own cap. Queued operations start in order as room frees up.
+//
+// `list_operations` feeds the UI's activity panel. `cancel_operation` drops
+// a queued operation before it ever starts. On a running operation it sets
+// the cancellation flag, which the work checks between steps. Finished
+// operations stay listed for `RETAIN_FINISHED` so the UI can read how they
+// ended.
+//
+// Every operation reports through the same two events. `operation-progress`
+// carries `{ operation_id, kind, current, total, message, phase }` and is
+// throttled to about ten per second per operation. `operation-finished` fires
+// once with the outcome and a `ResultRef` saying where the result lives.
+// Both are journaled with a `seq` (see `event_journal`), so a reloaded
+// webview can catch up on what it missed.
+//
+// Rescans, store health checks, and task extraction read at background
+// priority, paced by `io_throttle`; their `io_limits` say how. The UI calls
+// `boost_operation` on one the user is waiting for to lift the limits.
+//
+// During maintenance (see `maintenance`) the queue is emptied and `start`
+// refuses new operations with `MaintenanceMode`. Backups and restores, which
+// maintenance is for, keep running and can still start.
+//
+// Task extraction, vault discovery, and store health checks keep their
+// result with the operation (`ResultRef::Stored`) for as long as it stays
+// listed, so `export_results` can write it out without the UI sending it
+// back.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::io_throttle::{IoLimits, IoThrottle};
+use crate::{event_journal, settings};
+
+const RETAIN_FINISHED: Duration = Duration::from_secs(5 * 60);
+const MAX_FINISHED: usize = 100;
+/// Minimum gap between `operation-progress` events of one operation.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    StoreRescan,
+    VaultDiscovery,
+    Backup,
+    Restore,
+    TaskExtraction,
+    AudioUpload,
+    ArtifactUpload,
+    ArtifactDownload,
+    StoreHealth,
+}
+
+impl OperationKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            OperationKind::StoreRescan => "store_rescan",
+            OperationKind::VaultDiscovery => "vault_discovery",
+            OperationKind::Backup => "backup",
+            OperationKind::Restore => "restore",
+            OperationKind::TaskExtraction => "task_extraction",
+            OperationKind::AudioUpload => "audio_upload",
+            OperationKind::ArtifactUpload => "artifact_upload",
+            OperationKind::ArtifactDownload => "artifact_download",
+            OperationKind::StoreHealth => "store_health",
+        }
+    }
+
+    /// Whether it may start while operations are paused for maintenance.
+    pub fn runs_during_maintenance(self) -> bool {
+        matches!(self, OperationKind::Backup | OperationKind::Restore)
+    }
+
+    /// How many of this kind may run at once, unless settings say otherwise.
+    fn default_limit(self) -> u32 {
+        match self {
+            OperationKind::StoreRescan
+            | OperationKind::TaskExtraction
+            | OperationKind::ArtifactUpload
+            | OperationKind::ArtifactDownload
+            | OperationKind::StoreHealth => 2,
+            OperationKind::VaultDiscovery
+            | OperationKind::Backup
+            | OperationKind::Restore
+            | OperationKind::AudioUpload => 1,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Progress {
+    pub phase: String,
+    pub current: u64,
+    pub total: Option<u64>,
+    pub message: Option<String>,
+}
+
+/// Payload of `operation-progress`.
+#[derive(Serialize, Clone)]
+struct ProgressEvent {
+    operation_id: u64,
+    kind: OperationKind,
+    #[serde(flatten)]
+    progress: Progress,
+}
+
+/// Where a finished operation's result can be found.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResultRef {
+    /// A file the operation wrote, such as a backup archive.
+    File {
+        path: String,
+    },
+    /// A store whose snapshot was refreshed.
+    Store {
+        store_id: String,
+    },
+    UploadPlan {
+        plan_id: String,
+    },
+    /// An artifact upload; see `list_artifact_uploads` and `list_artifacts`.
+    Artifact {
+        upload_id: String,
+    },
+    /// Kept with the operation; see `Operations::stored_result`.
+    Stored {
+        operation_id: u64,
+    },
+}
+
+/// Payload of `operation-finished`.
+#[derive(Serialize, Clone)]
+struct FinishedEvent {
+    operation_id: u64,
+    kind: OperationKind,
+    outcome: OperationState,
+    message: Option<String>,
+    result: Option<ResultRef>,
+}
+
+/// Rate limit for progress events: lets one through per `interval`, plus
+/// every phase change.
+pub struct EventThrottle {
+    interval: Duration,
+    last: Option<(Instant, String)>,
+}
+
+impl EventThrottle {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Whether an event for `phase` should go out now.
+    pub fn ready(&mut self, phase: &str) -> bool {
+        let due = match &self.last {
+            Some((at, last_phase)) => last_phase != phase || at.elapsed() >= self.interval,
+            None => true,
+        };
+        if due {
+            self.last = Some((Instant::now(), phase.to_string()));
+        }
+        due
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    /// What it's working on: a store id, a path.
+    pub label: String,
+    pub weight: u32,
+    pub state: OperationState,
+    pub progress: Option<Progress>,
+    pub result: Option<ResultRef>,
+    pub queued_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// The error, for failed operations.
+    pub message: Option<String>,
+    /// Limits on its reads, once it has started reading at background
+    /// priority.
+    pub io_limits: Option<IoLimits>,
+}
+
+struct Entry {
+    operation: Operation,
+    cancel: Arc<AtomicBool>,
+    boost: Arc<AtomicBool>,
+    finished: Option<Instant>,
+    /// The completed result, for `finish_storing`.
+    output: Option<Arc<serde_json::Value>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// Ids are assigned in registration order, so this is also queue order.
+    entries: BTreeMap<u64, Entry>,
+    next_id: u64,
+    /// Held back for maintenance.
+    paused: bool,
+}
+
+/// Managed state: every queued, running, and recently finished operation.
+#[derive(Default)]
+pub struct Operations {
+    inner: Arc<(Mutex<Registry>, Condvar)>,
+}
+
+/// A started operation. Finishing it, or dropping it, frees its slot.
+pub struct OperationHandle {
+    inner: Arc<(Mutex<Registry>, Condvar)>,
+    app: AppHandle,
+    id: u64,
+    kind: OperationKind,
+    cancel: Arc<AtomicBool>,
+    boost: Arc<AtomicBool>,
+    throttle: Mutex<EventThrottle>,
+    done: bool,
+}
+
+// ─── Registration ───────────────────────────────────────────────────────────
+
+impl Operations {
+    /// Operations not yet finished, without waiting for the registry: `None`
+    /// while it's held, e.g. by a thread that is panicking.
+    pub fn try_active(&self) -> Option<Vec<Operation>> {
+        let registry = match self.inner.0.try_lock() {
+            Ok(registry) => registry,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return None,
+        };
+        Some(
+            registry
+                .entries
+                .values()
+                .filter(|e| e.finished.is_none())
+                .map(|e| e.operation.clone())
+                .collect(),
+        )
+    }
+
+    /// Register an operation and block until it may start. Returns
+    /// `Cancelled` if it's cancelled while queued.
+    pub fn start(
+        &self,
+        app: &AppHandle,
+        kind: OperationKind,
+        label: impl Into<String>,
+        weight: u32,
+    ) -> CommandResult<OperationHandle> {
+        let (lock, wake) = &*self.inner;
+        let mut registry = lock.lock().unwrap();
+        registry.prune();
+        if registry.paused && !kind.runs_during_maintenance() {
+            return Err(CommandError::new(
+                ErrorCode::MaintenanceMode,
+                format!("{} can't start during maintenance", kind.name()),
+            )
+            .with("operation", kind.name()));
+        }
+        let id = registry.next_id;
+        registry.next_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let boost = Arc::new(AtomicBool::new(false));
+        registry.entries.insert(
+            id,
+            Entry {
+                operation: Operation {
+                    id,
+                    kind,
+                    label: label.into(),
+                    weight: weight.max(1),
+                    state: OperationState::Queued,
+                    progress: None,
+                    result: None,
+                    queued_at: chrono::Utc::now().to_rfc3339(),
+                    started_at: None,
+                    finished_at: None,
+                    message: None,
+                    io_limits: None,
+                },
+                cancel: cancel.clone(),
+                boost: boost.clone(),
+                finished: None,
+                output: None,
+            },
+        );
+
+        let limits = settings::load_settings().operations;
+        loop {
+            match registry.entries.get(&id).map(|e| e.operation.state) {
+                Some(OperationState::Queued) => {}
+                // Cancelled while queued
+                _ => {
+                    return Err(CommandError::new(
+                        ErrorCode::Cancelled,
+                        format!("{} was cancelled before it started", kind.name()),
+                    )
+                    .with("operation_id", id));
+                }
+            }
+            if registry.next_startable(&limits) == Some(id) {
+                break;
+            }
+            registry = wake.wait(registry).unwrap();
+        }
+
+        let entry = registry.entries.get_mut(&id).unwrap();
+        entry.operation.state = OperationState::Running;
+        entry.operation.started_at = Some(chrono::Utc::now().to_rfc3339());
+        // Others queued behind this one may fit too
+        wake.notify_all();
+        Ok(OperationHandle {
+            inner: self.inner.clone(),
+            app: app.clone(),
+            id,
+            kind,
+            cancel,
+            boost,
+            throttle: Mutex::new(EventThrottle::new(PROGRESS_INTERVAL)),
+            done: false,
+        })
+    }
+
+    /// `start` for async commands, without holding up the runtime.
+    pub async fn start_async(
+        app: &AppHandle,
+        kind: OperationKind,
+        label: impl Into<String>,
+        weight: u32,
+    ) -> CommandResult<OperationHandle> {
+        let app = app.clone();
+        let label = label.into();
+        tauri::async_runtime::spawn_blocking(move || {
+            app.state::<Operations>().start(&app, kind, label, weight)
+        })
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+    }
+
+    /// The result a completed operation kept with `finish_storing`.
+    pub fn stored_result(&self, id: u64) -> CommandResult<(OperationKind, Arc<serde_json::Value>)> {
+        let mut registry = self.inner.0.lock().unwrap();
+        registry.prune();
+        let not_kept = |message: String| {
+            CommandError::new(ErrorCode::NotFound, message).with("operation_id", id)
+        };
+        let Some(entry) = registry.entries.get(&id) else {
+            return Err(not_kept(format!("No operation {}", id)));
+        };
+        if entry.operation.state != OperationState::Completed {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Operation {} hasn't completed", id),
+            )
+            .with("operation_id", id)
+            .with("state", serde_json::json!(entry.operation.state)));
+        }
+        match &entry.output {
+            Some(output) => Ok((entry.operation.kind, output.clone())),
+            None => Err(not_kept(format!("Operation {} kept no result", id))),
+        }
+    }
+}
+
+// ─── Maintenance ────────────────────────────────────────────────────────────
+
+impl Operations {
+    /// Cancel the queued operations, returning them, and refuse new ones
+    /// until `resume`. Kinds that run during maintenance stay queued.
+    pub fn pause(&self) -> Vec<Operation> {
+        let (lock, wake) = &*self.inner;
+        let mut registry = lock.lock().unwrap();
+        registry.paused = true;
+        let mut cancelled = Vec::new();
+        for entry in registry.entries.values_mut() {
+            if entry.operation.state == OperationState::Queued
+                && !entry.operation.kind.runs_during_maintenance()
+            {
+                entry.operation.state = OperationState::Cancelled;
+                entry.operation.finished_at = Some(chrono::Utc::now().to_rfc3339());
+                entry.finished = Some(Instant::now());
+                cancelled.push(entry.operation.clone());
+            }
+        }
+        wake.notify_all();
+        cancelled
+    }
+
+    pub fn resume(&self) {
+        self.inner.0.lock().unwrap().paused = false;
+    }
+
+    /// Running operations maintenance waits for: all but the kinds that run
+    /// during it.
+    pub fn running(&self) -> Vec<Operation> {
+        self.inner
+            .0
+            .lock()
+            .unwrap()
+            .running()
+            .filter(|op| !op.kind.runs_during_maintenance())
+            .cloned()
+            .collect()
+    }
+
+    /// Ask every running operation `running` lists to stop at its next
+    /// step; returns them.
+    pub fn cancel_running(&self) -> Vec<Operation> {
+        let registry = self.inner.0.lock().unwrap();
+        registry
+            .entries
+            .values()
+            .filter(|e| e.operation.state == OperationState::Running)
+            .filter(|e| !e.operation.kind.runs_during_maintenance())
+            .map(|e| {
+                e.cancel.store(true, Ordering::Relaxed);
+                e.operation.clone()
+            })
+            .collect()
+    }
+}
+
+impl Registry {
+    fn running(&self) -> impl Iterator<Item = &Operation> {
+        self.entries
+            .values()
+            .map(|e| &e.operation)
+            .filter(|op| op.state == OperationState::Running)
+    }
+
+    /// The first queued operation that fits under both limits.
+    fn next_startable(&self, limits: &settings::OperationSettings) -> Option<u64> {
+        let weight_in_use: u32 = self.running().map(|op| op.weight).sum();
+        self.entries
+            .values()
+            .map(|e| &e.operation)
+            .filter(|op| op.state == OperationState::Queued)
+            .find(|op| {
+                let kind_limit = limits
+                    .per_kind_limits
+                    .get(op.kind.name())
+                    .copied()
+                    .unwrap_or(op.kind.default_limit())
+                    .max(1);
+                let same_kind = self.running().filter(|r| r.kind == op.kind).count() as u32;
+                // Something heavier than the whole budget still runs, alone
+                let fits = weight_in_use == 0
+                    || weight_in_use + op.weight <= limits.max_concurrent_weight.max(1);
+                fits && same_kind < kind_limit
+            })
+            .map(|op| op.id)
+    }
+
+    fn prune(&mut self) {
+        self.entries
+            .retain(|_, e| e.finished.is_none_or(|at| at.elapsed() < RETAIN_FINISHED));
+        let mut finished: Vec<u64> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.finished.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+        if finished.len() > MAX_FINISHED {
+            finished.truncate(finished.len() - MAX_FINISHED);
+            for id in finished {
+                self.entries.remove(&id);
+            }
+        }
+    }
+}
+
+impl OperationHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// `Cancelled` once `cancel_operation` has been called; check between steps.
+    pub fn check_cancelled(&self) -> CommandResult<()> {
+        if self.is_cancelled() {
+            return Err(
+                CommandError::new(ErrorCode::Cancelled, "Operation cancelled")
+                    .with("operation_id", self.id),
+            );
+        }
+        Ok(())
+    }
+
+    /// A throttle for reading at background priority, under the current
+    /// power state's limits; run the reads under `io_throttle::scoped`.
+    pub fn background_io(&self) -> IoThrottle {
+        let mut limits = IoLimits::background();
+        if self.boost.load(Ordering::Relaxed) {
+            limits = limits.boosted();
+        }
+        if let Some(entry) = self.inner.0.lock().unwrap().entries.get_mut(&self.id) {
+            entry.operation.io_limits = Some(limits);
+        }
+        IoThrottle::new(limits, self.boost.clone())
+    }
+
+    /// Record progress and emit `operation-progress`, throttled.
+    pub fn progress(&self, phase: &str, current: u64, total: Option<u64>, message: Option<&str>) {
+        let progress = Progress {
+            phase: phase.to_string(),
+            current,
+            total,
+            message: message.map(str::to_string),
+        };
+        if let Some(entry) = self.inner.0.lock().unwrap().entries.get_mut(&self.id) {
+            entry.operation.progress = Some(progress.clone());
+        }
+        if self.throttle.lock().unwrap().ready(phase) {
+            event_journal::emit(
+                &self.app,
+                "operation-progress",
+                ProgressEvent {
+                    operation_id: self.id,
+                    kind: self.kind,
+                    progress,
+                },
+            );
+        }
+    }
+
+    /// Record how the operation ended and pass its result through.
+    pub fn finish<T>(self, result: CommandResult<T>) -> CommandResult<T> {
+        self.finish_with(result, |_| None)
+    }
+
+    /// `finish`, pointing `operation-finished` at where the result lives.
+    pub fn finish_with<T>(
+        mut self,
+        result: CommandResult<T>,
+        result_ref: impl FnOnce(&T) -> Option<ResultRef>,
+    ) -> CommandResult<T> {
+        let (state, message, pointer) = match &result {
+            Ok(value) => (OperationState::Completed, None, result_ref(value)),
+            Err(e) if e.code == ErrorCode::Cancelled => (OperationState::Cancelled, None, None),
+            Err(e) => (OperationState::Failed, Some(e.message.clone()), None),
+        };
+        self.end(state, message, pointer);
+        result
+    }
+
+    /// `finish`, keeping a copy of the result with the operation.
+    pub fn finish_storing<T: Serialize>(self, result: CommandResult<T>) -> CommandResult<T> {
+        if let Ok(value) = &result {
+            match serde_json::to_value(value) {
+                Ok(output) => {
+                    if let Some(entry) = self.inner.0.lock().unwrap().entries.get_mut(&self.id) {
+                        entry.output = Some(Arc::new(output));
+                    }
+                }
+                Err(e) => log::warn!("[operations] can't keep result of {}: {}", self.id, e),
+            }
+        }
+        let id = self.id;
+        self.finish_with(result, |_| Some(ResultRef::Stored { operation_id: id }))
+    }
+
+    fn end(&mut self, state: OperationState, message: Option<String>, result: Option<ResultRef>) {
+        self.done = true;
+        let (lock, wake) = &*self.inner;
+        {
+            let mut registry = lock.lock().unwrap();
+            if let Some(entry) = registry.entries.get_mut(&self.id) {
+                entry.operation.state = state;
+                entry.operation.message = message.clone();
+                entry.operation.result = result.clone();
+                entry.operation.finished_at = Some(chrono::Utc::now().to_rfc3339());
+                entry.finished = Some(Instant::now());
+            }
+        }
+        wake.notify_all();
+        event_journal::emit(
+            &self.app,
+            "operation-finished",
+            FinishedEvent {
+                operation_id: self.id,
+                kind: self.kind,
+                outcome: state,
+                message,
+                result,
+            },
+        );
+    }
+}
+
+impl Drop for OperationHandle {
+    fn drop(&mut self) {
+        if !self.done {
+            self.end(OperationState::Failed, Some("Interrupted".into()), None);
+        }
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Queued and running operations, then recently finished ones, oldest first.
+#[tauri::command]
+pub fn list_operations(state: State<'_, Operations>) -> Vec<Operation> {
+    let mut registry = state.inner.0.lock().unwrap();
+    registry.prune();
+    registry
+        .entries
+        .values()
+        .map(|e| e.operation.clone())
+        .collect()
+}
+
+/// Cancel an operation: a queued one is removed before it starts; a running
+/// one is asked to stop at its next step.
+#[tauri::command]
+pub fn cancel_operation(state: State<'_, Operations>, id: u64) -> CommandResult<Operation> {
+    let (lock, wake) = &*state.inner;
+    let mut registry = lock.lock().unwrap();
+    let Some(entry) = registry.entries.get_mut(&id) else {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("No operation {}", id))
+                .with("operation_id", id),
+        );
+    };
+    match entry.operation.state {
+        OperationState::Queued => {
+            entry.operation.state = OperationState::Cancelled;
+            entry.operation.finished_at = Some(chrono::Utc::now().to_rfc3339());
+            entry.finished = Some(Instant::now());
+            wake.notify_all();
+        }
+        OperationState::Running => entry.cancel.store(true, Ordering::Relaxed),
+        _ => {}
+    }
+    Ok(entry.operation.clone())
+}
+
+/// Lift the IO limits on a queued or running operation, for when the user is
+/// waiting on it. Finished operations are returned unchanged.
+#[tauri::command]
+pub fn boost_operation(state: State<'_, Operations>, id: u64) -> CommandResult<Operation> {
+    let mut registry = state.inner.0.lock().unwrap();
+    let Some(entry) = registry.entries.get_mut(&id) else {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("No operation {}", id))
+                .with("operation_id", id),
+        );
+    };
+    if entry.finished.is_none() {
+        entry.boost.store(true, Ordering::Relaxed);
+        entry.operation.io_limits = entry.operation.io_limits.map(IoLimits::boosted);
+        log::info!(
+            "[operations] boosted {} ({})",
+            id,
+            entry.operation.kind.name()
+        );
+    }
+    Ok(entry.operation.clone())
+}
@@ -0,0 +1,83 @@
+// Orchestrator bridge
+//
+// Calls from the desktop backend to the AGENTVBX API server, local or remote.
+
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::http::{self, CertificateInfo};
+use crate::http_retry::{self, RetryPolicy};
+use crate::settings;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+pub struct OrchestratorConnection {
+    pub url: String,
+    pub reachable: bool,
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// Body of `/api/health` when the server answered with JSON.
+    pub health: Option<serde_json::Value>,
+    /// Certificate chain the server presented (HTTPS only), leaf first.
+    pub certificates: Vec<CertificateInfo>,
+    pub error: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Ping the orchestrator's health endpoint.
+///
+/// `url` defaults to the configured orchestrator URL so the settings screen
+/// can test a value before saving it.
+#[tauri::command]
+pub async fn test_orchestrator_connection(
+    url: Option<String>,
+) -> Result<OrchestratorConnection, String> {
+    let settings = settings::load_settings();
+    let base = url.unwrap_or_else(|| settings.orchestrator_url.clone());
+    let (client, chain) = http::orchestrator_client(&settings)?;
+
+    let started = Instant::now();
+    let request = client.get(format!("{}/api/health", base.trim_end_matches('/')));
+    let result = http_retry::send(request, &RetryPolicy::from_settings(&settings.http)).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    Ok(match result {
+        Ok(response) => {
+            let status = response.status();
+            let health = response.json::<serde_json::Value>().await.ok();
+            OrchestratorConnection {
+                url: base,
+                reachable: true,
+                status: Some(status.as_u16()),
+                latency_ms,
+                health,
+                certificates: http::describe_chain(&chain),
+                error: (!status.is_success()).then(|| format!("HTTP {}", status)),
+            }
+        }
+        Err(e) => OrchestratorConnection {
+            url: base,
+            reachable: false,
+            status: None,
+            latency_ms,
+            health: None,
+            certificates: Vec::new(),
+            error: Some(error_chain(&e)),
+        },
+    })
+}
+
+/// reqwest's top-level message hides TLS failures ("error sending request");
+/// include the sources so a pin mismatch or unknown CA is visible.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
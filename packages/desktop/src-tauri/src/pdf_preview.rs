@@ -0,0 +1,249 @@
+// PDF page previews
+//
+// Scanned PDFs have no text to extract, so the preview pane shows page images
+// instead. `render_pdf_page` rasterizes one page to a PNG in the preview cache,
+// keyed like thumbnails so a changed file is re-rendered. Rendering goes
+// through pdfium, loaded at runtime and only compiled in with the `pdfium`
+// feature; without it the command fails with `Unsupported`. Renders run on the
+// blocking pool, at most `MAX_RENDERS_PER_DOCUMENT` per file at once.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::previews;
+
+const DEFAULT_MAX_EDGE: u32 = 1024;
+const MAX_EDGE_LIMIT: u32 = 4096;
+const MAX_RENDERS_PER_DOCUMENT: usize = 2;
+
+static ACTIVE_RENDERS: Mutex<Option<HashMap<PathBuf, usize>>> = Mutex::new(None);
+static RENDER_DONE: Condvar = Condvar::new();
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PdfPageImage {
+    pub image_path: String,
+    /// 1-based.
+    pub page: u32,
+    pub page_count: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(skip_deserializing)]
+    pub cached: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Render page `page` (1-based) so its longer edge is at most `max_edge_px`.
+#[tauri::command]
+pub async fn render_pdf_page(
+    path: String,
+    page: u32,
+    max_edge_px: Option<u32>,
+) -> CommandResult<PdfPageImage> {
+    let edge = max_edge_px
+        .unwrap_or(DEFAULT_MAX_EDGE)
+        .clamp(1, MAX_EDGE_LIMIT);
+    tauri::async_runtime::spawn_blocking(move || render(Path::new(&path), page, edge))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Rendering ──────────────────────────────────────────────────────────────
+
+fn render(path: &Path, page: u32, edge: u32) -> CommandResult<PdfPageImage> {
+    let Some(key) = previews::cache_key(path) else {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("File not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()));
+    };
+    let image_path = previews::cache_dir()?
+        .join("pdf")
+        .join(format!("{}-p{}-{}.png", key, page, edge));
+    if let Some(cached) = read_cached(&image_path) {
+        return Ok(cached);
+    }
+
+    let _slot = RenderSlot::acquire(path);
+    let temp = image_path.with_extension("png.tmp");
+    let (page_count, width, height) = backend::with_document(path, |document| {
+        let page_count = document.page_count();
+        if page == 0 || page > page_count {
+            return Err(CommandError::new(
+                ErrorCode::PageOutOfRange,
+                format!("{} has {} pages", path.display(), page_count),
+            )
+            .with("page", page)
+            .with("page_count", page_count));
+        }
+        if let Some(parent) = temp.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let (width, height) = document.render(page - 1, edge, &temp)?;
+        Ok((page_count, width, height))
+    })?;
+    let result = PdfPageImage {
+        image_path: image_path.to_string_lossy().to_string(),
+        page,
+        page_count,
+        width,
+        height,
+        cached: false,
+    };
+    let sidecar = serde_json::to_vec(&result).map_err(|e| e.to_string())?;
+    fs::write(image_path.with_extension("json"), sidecar)?;
+    fs::rename(&temp, &image_path)?;
+    Ok(result)
+}
+
+fn read_cached(image_path: &Path) -> Option<PdfPageImage> {
+    if !image_path.is_file() {
+        return None;
+    }
+    let raw = fs::read(image_path.with_extension("json")).ok()?;
+    let mut cached: PdfPageImage = serde_json::from_slice(&raw).ok()?;
+    cached.cached = true;
+    Some(cached)
+}
+
+/// One of a document's render slots, released on drop.
+struct RenderSlot(PathBuf);
+
+impl RenderSlot {
+    fn acquire(path: &Path) -> Self {
+        let mut active = ACTIVE_RENDERS.lock().unwrap();
+        loop {
+            let count = active
+                .get_or_insert_with(HashMap::new)
+                .entry(path.to_path_buf())
+                .or_default();
+            if *count < MAX_RENDERS_PER_DOCUMENT {
+                *count += 1;
+                return Self(path.to_path_buf());
+            }
+            active = RENDER_DONE.wait(active).unwrap();
+        }
+    }
+}
+
+impl Drop for RenderSlot {
+    fn drop(&mut self) {
+        let mut active = ACTIVE_RENDERS.lock().unwrap();
+        if let Some(map) = active.as_mut() {
+            if let Some(count) = map.get_mut(&self.0) {
+                *count -= 1;
+                if *count == 0 {
+                    map.remove(&self.0);
+                }
+            }
+        }
+        RENDER_DONE.notify_all();
+    }
+}
+
+// ─── Backends ───────────────────────────────────────────────────────────────
+
+#[cfg(feature = "pdfium")]
+mod backend {
+    use pdfium_render::prelude::*;
+    use std::path::Path;
+
+    use crate::error::{CommandError, CommandResult, ErrorCode};
+
+    thread_local! {
+        // Bindings aren't `Send`, so each blocking-pool thread loads its own
+        static PDFIUM: Option<Pdfium> = bind();
+    }
+
+    /// The system pdfium, else one shipped next to the executable.
+    fn bind() -> Option<Pdfium> {
+        let beside_exe = std::env::current_exe().ok().and_then(|exe| {
+            exe.parent()
+                .map(Pdfium::pdfium_platform_library_name_at_path)
+        });
+        Pdfium::bind_to_system_library()
+            .or_else(|e| match beside_exe {
+                Some(path) => Pdfium::bind_to_library(path),
+                None => Err(e),
+            })
+            .map_err(|e| log::warn!("[pdf_preview] pdfium unavailable: {}", e))
+            .ok()
+            .map(Pdfium::new)
+    }
+
+    pub struct Document<'a>(PdfDocument<'a>);
+
+    pub fn with_document<T>(
+        path: &Path,
+        f: impl FnOnce(&Document<'_>) -> CommandResult<T>,
+    ) -> CommandResult<T> {
+        PDFIUM.with(|pdfium| {
+            let pdfium = pdfium.as_ref().ok_or_else(|| {
+                CommandError::new(ErrorCode::Unsupported, "The pdfium library wasn't found")
+                    .with("feature", "pdfium")
+            })?;
+            let document = pdfium.load_pdf_from_file(path, None).map_err(|e| {
+                CommandError::new(ErrorCode::ParseError, format!("Can't open PDF: {}", e))
+                    .with("path", path.to_string_lossy())
+            })?;
+            f(&Document(document))
+        })
+    }
+
+    impl Document<'_> {
+        pub fn page_count(&self) -> u32 {
+            self.0.pages().len() as u32
+        }
+
+        /// Render the 0-based `index` to a PNG at `out`; returns its size.
+        pub fn render(&self, index: u32, edge: u32, out: &Path) -> CommandResult<(u32, u32)> {
+            let failed = |e: PdfiumError| {
+                CommandError::new(ErrorCode::Internal, format!("PDF render failed: {}", e))
+            };
+            let page = self.0.pages().get(index as PdfPageIndex).map_err(failed)?;
+            let config = PdfRenderConfig::new()
+                .set_maximum_width(edge as Pixels)
+                .set_maximum_height(edge as Pixels);
+            let image = page.render_with_config(&config).map_err(failed)?.as_image();
+            image
+                .save_with_format(out, image::ImageFormat::Png)
+                .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+            Ok((image.width(), image.height()))
+        }
+    }
+}
+
+#[cfg(not(feature = "pdfium"))]
+mod backend {
+    use std::path::Path;
+
+    use crate::error::{CommandError, CommandResult, ErrorCode};
+
+    pub enum Document {}
+
+    pub fn with_document<T>(
+        _path: &Path,
+        _f: impl FnOnce(&Document) -> CommandResult<T>,
+    ) -> CommandResult<T> {
+        Err(CommandError::new(
+            ErrorCode::Unsupported,
+            "This build was compiled without PDF rendering",
+        )
+        .with("feature", "pdfium"))
+    }
+
+    impl Document {
+        pub fn page_count(&self) -> u32 {
+            match *self {}
+        }
+
+        pub fn render(&self, _index: u32, _edge: u32, _out: &Path) -> CommandResult<(u32, u32)> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Platform capabilities
+//
+// The same commands ship on desktop and mobile, but some need things the iOS
+// and Android sandboxes don't allow. Scanning the home directory, spawning an
+// orchestrator, global shortcuts, and extra windows are desktop only. Those
+// commands call `desktop_only` first, and on mobile they fail with
+// `NotSupportedOnPlatform` instead of panicking or quietly doing nothing.
+// `get_capabilities` tells the UI which groups it can offer, so it can hide
+// the rest, taking the tenant's policy into account (see `policy`). On mobile,
+// file access goes through the `app_files` commands.
+
+use serde::Serialize;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::policy::{self, CommandGroup, EffectivePolicy};
+use crate::tenant_windows;
+
+#[derive(Serialize, Clone)]
+pub struct Capabilities {
+    pub platform: String,
+    pub mobile: bool,
+    /// `list_directory` and friends on arbitrary paths.
+    pub filesystem: bool,
+    /// `list_app_directory` / `read_app_text_file`; always available.
+    pub app_files: bool,
+    /// `get_user_directories`.
+    pub user_directories: bool,
+    pub local_orchestrator: bool,
+    pub global_shortcuts: bool,
+    /// Tenant windows and provider login windows.
+    pub extra_windows: bool,
+    pub devtools: bool,
+    pub pdf_rendering: bool,
+    pub whatsapp: bool,
+    pub automation: bool,
+    pub provider_fetch: bool,
+    /// Which providers may be logged into, and why groups are off.
+    pub policy: EffectivePolicy,
+}
+
+/// From a tenant window, or with `tenant_id`, includes that tenant's policy.
+#[tauri::command]
+pub fn get_capabilities(window: tauri::WebviewWindow, tenant_id: Option<String>) -> Capabilities {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id).ok();
+    capabilities(tenant_id.as_deref())
+}
+
+pub fn capabilities(tenant_id: Option<&str>) -> Capabilities {
+    let desktop = !cfg!(mobile);
+    let policy = policy::effective(tenant_id);
+    Capabilities {
+        platform: std::env::consts::OS.into(),
+        mobile: !desktop,
+        filesystem: desktop,
+        app_files: true,
+        user_directories: desktop,
+        local_orchestrator: desktop && policy.allows(CommandGroup::LocalOrchestrator),
+        global_shortcuts: desktop,
+        extra_windows: desktop,
+        devtools: desktop && cfg!(debug_assertions),
+        pdf_rendering: cfg!(feature = "pdfium"),
+        whatsapp: policy.allows(CommandGroup::Whatsapp),
+        automation: policy.allows(CommandGroup::Automation),
+        provider_fetch: policy.allows(CommandGroup::ProviderFetch),
+        policy: (*policy).clone(),
+    }
+}
+
+/// `NotSupportedOnPlatform` on iOS and Android.
+pub fn desktop_only(feature: &str) -> CommandResult<()> {
+    if cfg!(mobile) {
+        return Err(CommandError::new(
+            ErrorCode::NotSupportedOnPlatform,
+            format!("{} is only available on desktop", feature),
+        )
+        .with("feature", feature)
+        .with("platform", std::env::consts::OS));
+    }
+    Ok(())
+}
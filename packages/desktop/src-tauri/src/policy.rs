@@ -0,0 +1,423 @@
+// Tenant policy
+//
+// Enterprise tenants can restrict which providers their users may log into
+// and which groups of commands they may use. Rules come from two places:
+// - The managed policy at `$AGENTVBX_POLICY_FILE`, provisioned by IT and never
+//   written by the app. Its top-level rules apply to every tenant and to
+//   commands that don't belong to one, such as starting the local
+//   orchestrator; `tenants.<id>` adds rules for a single tenant.
+// - `tenants/<tenant>/policy.json` in the data directory, the tenant's own.
+//
+// Each layer can only take away. The effective provider allowlist is the
+// intersection of every list given, and a group disabled anywhere is
+// disabled, so neither the tenant's file nor a setting such as
+// `automation.enabled` can turn back on what the managed policy blocks. A
+// managed policy that exists but can't be read blocks everything it could
+// have restricted rather than nothing. Group names this version doesn't know
+// are ignored, so a newer policy still loads.
+//
+// `force_dry_run` set in any layer makes every mutation the tenant's windows
+// ask for a dry run (see `dry_run`), and `redact` has the tenant's content
+// redacted before it leaves the machine (see `redaction`). Like the rest, a
+// layer can't unset either.
+//
+// Blocked commands fail with `PolicyDenied`, and `get_capabilities` carries
+// the effective policy so the UI can hide them. Evaluations are cached per
+// tenant and redone when either file's size or modification time changes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{integrity, tenant_windows};
+
+const MANAGED_ENV_VAR: &str = "AGENTVBX_POLICY_FILE";
+
+static CACHE: Mutex<BTreeMap<Option<String>, Cached>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CommandGroup {
+    /// The WhatsApp bridge: chats, sending, and media import.
+    Whatsapp,
+    LocalOrchestrator,
+    /// The local automation listener.
+    Automation,
+    /// Requests replayed with a captured provider session.
+    ProviderFetch,
+}
+
+impl CommandGroup {
+    const ALL: [CommandGroup; 4] = [
+        CommandGroup::Whatsapp,
+        CommandGroup::LocalOrchestrator,
+        CommandGroup::Automation,
+        CommandGroup::ProviderFetch,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandGroup::Whatsapp => "whatsapp",
+            CommandGroup::LocalOrchestrator => "local_orchestrator",
+            CommandGroup::Automation => "automation",
+            CommandGroup::ProviderFetch => "provider_fetch",
+        }
+    }
+}
+
+/// One layer of rules, as written in either file.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct PolicyRules {
+    /// `None` allows every provider.
+    pub allowed_providers: Option<Vec<String>>,
+    /// `CommandGroup` names.
+    pub disabled_commands: Vec<String>,
+    /// Treat every mutation as a dry run, whatever the caller asks.
+    pub force_dry_run: bool,
+    /// Apply the tenant's redaction rules to content sent off the machine.
+    pub redact: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ManagedPolicy {
+    #[serde(flatten)]
+    rules: PolicyRules,
+    tenants: BTreeMap<String, PolicyRules>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct EffectivePolicy {
+    pub tenant_id: Option<String>,
+    /// A managed policy is in effect.
+    pub managed: bool,
+    /// Sorted; `None` allows every provider.
+    pub allowed_providers: Option<Vec<String>>,
+    pub disabled_commands: Vec<String>,
+    pub force_dry_run: bool,
+    pub redact: bool,
+    /// Why the managed policy couldn't be read, which blocks everything.
+    pub error: Option<String>,
+}
+
+impl EffectivePolicy {
+    pub fn allows(&self, group: CommandGroup) -> bool {
+        !self.disabled_commands.iter().any(|g| g == group.name())
+    }
+
+    pub fn allows_provider(&self, provider_id: &str) -> bool {
+        self.allowed_providers
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|p| p == provider_id))
+    }
+}
+
+/// Size and modification time, or `None` for a missing file.
+type Stamp = Option<(SystemTime, u64)>;
+
+struct Cached {
+    /// Managed and tenant files.
+    paths: [Option<PathBuf>; 2],
+    stamps: [Stamp; 2],
+    policy: Arc<EffectivePolicy>,
+}
+
+// ─── Checks ─────────────────────────────────────────────────────────────────
+
+/// `PolicyDenied` unless `group` is enabled. `tenant_id` is `None` for
+/// commands that don't act for a tenant, which only the managed policy's
+/// top-level rules cover.
+pub fn check(tenant_id: Option<&str>, group: CommandGroup) -> CommandResult<()> {
+    let policy = effective(tenant_id);
+    if policy.allows(group) {
+        return Ok(());
+    }
+    Err(
+        denied(&policy, format!("{} is disabled by policy", group.name()))
+            .with("command", group.name()),
+    )
+}
+
+/// `PolicyDenied` unless the tenant may use `provider_id`.
+pub fn check_provider(tenant_id: Option<&str>, provider_id: &str) -> CommandResult<()> {
+    let policy = effective(tenant_id);
+    if policy.allows_provider(provider_id) {
+        return Ok(());
+    }
+    Err(denied(
+        &policy,
+        format!("Provider {} is not allowed by policy", provider_id),
+    )
+    .with("provider_id", provider_id))
+}
+
+pub fn allows(tenant_id: Option<&str>, group: CommandGroup) -> bool {
+    effective(tenant_id).allows(group)
+}
+
+pub fn forces_dry_run(tenant_id: Option<&str>) -> bool {
+    effective(tenant_id).force_dry_run
+}
+
+pub fn redacts(tenant_id: Option<&str>) -> bool {
+    effective(tenant_id).redact
+}
+
+fn denied(policy: &EffectivePolicy, message: String) -> CommandError {
+    log::info!("[policy] {}", message);
+    CommandError::new(ErrorCode::PolicyDenied, message)
+        .with("tenant_id", policy.tenant_id.clone())
+        .with("managed", policy.managed)
+}
+
+// ─── Evaluation ─────────────────────────────────────────────────────────────
+
+/// The policy in force for `tenant_id`, re-read when a file changed.
+pub fn effective(tenant_id: Option<&str>) -> Arc<EffectivePolicy> {
+    let paths = [managed_path(), tenant_id.and_then(|t| user_path(t).ok())];
+    let stamps = [
+        paths[0].as_deref().and_then(stamp),
+        paths[1].as_deref().and_then(stamp),
+    ];
+    let key = tenant_id.map(str::to_string);
+
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(cached) = cache.get(&key) {
+        if cached.paths == paths && cached.stamps == stamps {
+            return cached.policy.clone();
+        }
+    }
+    let policy = Arc::new(evaluate(
+        tenant_id,
+        paths[0].as_deref(),
+        paths[1].as_deref(),
+    ));
+    cache.insert(
+        key,
+        Cached {
+            paths,
+            stamps,
+            policy: policy.clone(),
+        },
+    );
+    policy
+}
+
+fn evaluate(
+    tenant_id: Option<&str>,
+    managed_path: Option<&Path>,
+    user_path: Option<&Path>,
+) -> EffectivePolicy {
+    let mut layers = Vec::new();
+    let mut managed = false;
+    if let Some(path) = managed_path {
+        match read_managed(path) {
+            Ok(None) => {}
+            Ok(Some(mut policy)) => {
+                managed = true;
+                layers.push(policy.rules);
+                if let Some(rules) = tenant_id.and_then(|t| policy.tenants.remove(t)) {
+                    layers.push(rules);
+                }
+            }
+            Err(e) => {
+                log::error!("[policy] can't read {}: {}", path.display(), e);
+                return EffectivePolicy {
+                    tenant_id: tenant_id.map(str::to_string),
+                    managed: true,
+                    allowed_providers: Some(Vec::new()),
+                    disabled_commands: CommandGroup::ALL
+                        .iter()
+                        .map(|g| g.name().to_string())
+                        .collect(),
+                    force_dry_run: true,
+                    redact: true,
+                    error: Some(e),
+                };
+            }
+        }
+    }
+    if let Some(rules) = user_path.and_then(|p| integrity::load_json::<PolicyRules>(p, None)) {
+        layers.push(rules);
+    }
+    combine(tenant_id, managed, layers)
+}
+
+fn combine(tenant_id: Option<&str>, managed: bool, layers: Vec<PolicyRules>) -> EffectivePolicy {
+    let mut allowed: Option<BTreeSet<String>> = None;
+    let mut disabled = BTreeSet::new();
+    let mut force_dry_run = false;
+    let mut redact = false;
+    for layer in layers {
+        force_dry_run |= layer.force_dry_run;
+        redact |= layer.redact;
+        if let Some(list) = layer.allowed_providers {
+            let list: BTreeSet<String> = list.into_iter().collect();
+            allowed = Some(match allowed {
+                Some(previous) => previous.intersection(&list).cloned().collect(),
+                None => list,
+            });
+        }
+        for name in layer.disabled_commands {
+            if CommandGroup::ALL.iter().any(|g| g.name() == name) {
+                disabled.insert(name);
+            } else {
+                log::debug!("[policy] ignoring unknown command group {}", name);
+            }
+        }
+    }
+    EffectivePolicy {
+        tenant_id: tenant_id.map(str::to_string),
+        managed,
+        allowed_providers: allowed.map(|a| a.into_iter().collect()),
+        disabled_commands: disabled.into_iter().collect(),
+        force_dry_run,
+        redact,
+        error: None,
+    }
+}
+
+/// `None` when the variable points at nothing; IT may remove the file.
+fn read_managed(path: &Path) -> Result<Option<ManagedPolicy>, String> {
+    match fs::read(path) {
+        Ok(raw) => serde_json::from_slice(&raw)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn managed_path() -> Option<PathBuf> {
+    std::env::var_os(MANAGED_ENV_VAR)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+fn user_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?.join("policy.json"))
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    /// Point the managed policy at `contents` for the life of the guard.
+    struct Managed;
+
+    impl Managed {
+        fn write(home: &TestHome, contents: &str) -> Self {
+            home.write("it/policy.json", contents);
+            std::env::set_var(MANAGED_ENV_VAR, home.join("it/policy.json"));
+            Managed
+        }
+    }
+
+    impl Drop for Managed {
+        fn drop(&mut self) {
+            std::env::remove_var(MANAGED_ENV_VAR);
+        }
+    }
+
+    fn write_user(home: &TestHome, tenant: &str, contents: &str) {
+        home.write(
+            &format!(".agentvbx/tenants/{}/policy.json", tenant),
+            contents,
+        );
+    }
+
+    #[test]
+    fn no_policy_allows_everything() {
+        let _home = TestHome::new();
+        let policy = effective(Some("acme"));
+        assert!(!policy.managed);
+        assert!(policy.allows_provider("claude"));
+        assert!(CommandGroup::ALL.iter().all(|g| policy.allows(*g)));
+    }
+
+    #[test]
+    fn tenant_files_cannot_loosen_the_managed_policy() {
+        let home = TestHome::new();
+        let _managed = Managed::write(
+            &home,
+            r#"{
+                "allowed_providers": ["claude", "chatgpt"],
+                "disabled_commands": ["local_orchestrator", "from_a_newer_version"],
+                "tenants": { "acme": { "disabled_commands": ["whatsapp"] } }
+            }"#,
+        );
+        // Lists gemini and re-enables nothing it can't
+        write_user(
+            &home,
+            "acme",
+            r#"{ "allowed_providers": ["claude", "gemini"], "disabled_commands": ["automation"] }"#,
+        );
+
+        let acme = effective(Some("acme"));
+        assert!(acme.managed);
+        assert!(!acme.force_dry_run);
+        assert_eq!(acme.allowed_providers, Some(vec!["claude".to_string()]));
+        assert_eq!(
+            acme.disabled_commands,
+            vec!["automation", "local_orchestrator", "whatsapp"]
+        );
+
+        // Other tenants get only the top-level rules
+        let other = effective(Some("globex"));
+        assert!(other.allows(CommandGroup::Whatsapp));
+        assert!(other.allows_provider("chatgpt"));
+        assert!(!other.allows(CommandGroup::LocalOrchestrator));
+
+        let err = check_provider(Some("acme"), "gemini").unwrap_err();
+        assert_eq!(err.code, ErrorCode::PolicyDenied);
+        assert_eq!(err.params["provider_id"], "gemini");
+        assert_eq!(err.params["managed"], true);
+        let err = check(None, CommandGroup::LocalOrchestrator).unwrap_err();
+        assert_eq!(err.params["command"], "local_orchestrator");
+        check(None, CommandGroup::Automation).unwrap();
+    }
+
+    #[test]
+    fn unreadable_managed_policy_blocks_everything() {
+        let home = TestHome::new();
+        let _managed = Managed::write(&home, "{ not json");
+        let policy = effective(Some("acme"));
+        assert!(policy.error.is_some());
+        assert!(!policy.allows_provider("claude"));
+        assert!(CommandGroup::ALL.iter().all(|g| !policy.allows(*g)));
+        assert!(policy.force_dry_run);
+        assert!(policy.redact);
+
+        // A missing file is no policy
+        fs::remove_file(home.join("it/policy.json")).unwrap();
+        assert!(!effective(Some("acme")).managed);
+    }
+
+    #[test]
+    fn changed_files_are_re_read() {
+        let home = TestHome::new();
+        write_user(&home, "acme", r#"{ "allowed_providers": ["claude"] }"#);
+        assert!(!effective(Some("acme")).allows_provider("chatgpt"));
+        assert!(Arc::ptr_eq(
+            &effective(Some("acme")),
+            &effective(Some("acme"))
+        ));
+
+        write_user(
+            &home,
+            "acme",
+            r#"{ "allowed_providers": ["claude", "chatgpt"] }"#,
+        );
+        assert!(effective(Some("acme")).allows_provider("chatgpt"));
+    }
+}
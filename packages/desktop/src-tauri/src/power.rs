@@ -0,0 +1,168 @@
+// Power state
+//
+// The battery, and whether the OS is in its battery-saving mode. That mode is
+// Low Power Mode on macOS, Battery Saver on Windows, and the `low-power` ACPI
+// platform profile on Linux. Anything that can't be read counts as "plugged in,
+// not saving", so a missing probe never stops background work.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Battery {
+    /// Running on battery rather than external power.
+    pub discharging: bool,
+    pub percent: Option<u8>,
+}
+
+/// `None` on machines without a battery, or when it can't be read.
+pub fn battery() -> Option<Battery> {
+    imp::battery()
+}
+
+/// True when the OS reports a battery-saving mode.
+pub fn battery_saver_active() -> bool {
+    imp::battery_saver_active()
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use super::Battery;
+    use std::process::Command;
+
+    fn pmset(args: &[&str]) -> Option<String> {
+        let output = Command::new("pmset").args(args).output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn battery() -> Option<Battery> {
+        // Now drawing from 'Battery Power'
+        //  -InternalBattery-0 (id=1234)	85%; discharging; 4:12 remaining
+        let output = pmset(&["-g", "batt"])?;
+        let line = output.lines().find(|l| l.contains("InternalBattery"))?;
+        let percent = line
+            .split_whitespace()
+            .find_map(|word| word.strip_suffix("%;"))
+            .and_then(|p| p.parse().ok());
+        Some(Battery {
+            discharging: output.contains("'Battery Power'"),
+            percent,
+        })
+    }
+
+    pub fn battery_saver_active() -> bool {
+        pmset(&["-g"]).is_some_and(|output| {
+            output.lines().any(|line| {
+                let mut parts = line.split_whitespace();
+                parts.next() == Some("lowpowermode") && parts.next() == Some("1")
+            })
+        })
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::Battery;
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)] // filled in by the OS
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    fn status() -> Option<SystemPowerStatus> {
+        let mut status = SystemPowerStatus::default();
+        // SAFETY: the struct matches SYSTEM_POWER_STATUS and outlives the call
+        let ok = unsafe { GetSystemPowerStatus(&mut status) } != 0;
+        ok.then_some(status)
+    }
+
+    pub fn battery() -> Option<Battery> {
+        const NO_SYSTEM_BATTERY: u8 = 128;
+        const UNKNOWN: u8 = 255;
+        let status = status()?;
+        if status.battery_flag == UNKNOWN || status.battery_flag & NO_SYSTEM_BATTERY != 0 {
+            return None;
+        }
+        Some(Battery {
+            discharging: status.ac_line_status == 0,
+            percent: (status.battery_life_percent <= 100).then_some(status.battery_life_percent),
+        })
+    }
+
+    pub fn battery_saver_active() -> bool {
+        status().is_some_and(|status| status.system_status_flag == 1)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::Battery;
+    use std::fs;
+    use std::path::Path;
+
+    const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+    fn read(dir: &Path, name: &str) -> Option<String> {
+        fs::read_to_string(dir.join(name))
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    pub fn battery() -> Option<Battery> {
+        let mut found = false;
+        let mut on_mains = false;
+        let mut percents: Vec<u32> = Vec::new();
+        for entry in fs::read_dir(POWER_SUPPLY).ok()?.flatten() {
+            let dir = entry.path();
+            match read(&dir, "type").as_deref() {
+                // Peripheral batteries (mice, headsets) say `scope: Device`
+                Some("Battery") if read(&dir, "scope").as_deref() != Some("Device") => {
+                    found = true;
+                    if let Some(percent) = read(&dir, "capacity").and_then(|c| c.parse().ok()) {
+                        percents.push(percent);
+                    }
+                }
+                Some("Mains") | Some("USB") => {
+                    on_mains |= read(&dir, "online").as_deref() == Some("1");
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            return None;
+        }
+        let percent = (!percents.is_empty())
+            .then(|| (percents.iter().sum::<u32>() / percents.len() as u32) as u8);
+        Some(Battery {
+            discharging: !on_mains,
+            percent,
+        })
+    }
+
+    pub fn battery_saver_active() -> bool {
+        fs::read_to_string("/sys/firmware/acpi/platform_profile")
+            .is_ok_and(|profile| profile.trim() == "low-power")
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+mod imp {
+    use super::Battery;
+
+    pub fn battery() -> Option<Battery> {
+        None
+    }
+
+    pub fn battery_saver_active() -> bool {
+        false
+    }
+}
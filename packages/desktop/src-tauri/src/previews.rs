@@ -0,0 +1,327 @@
+// Directory preview prefetch
+//
+// Opening a folder of images used to render thumbnails on demand, one at a
+// time. `prefetch_directory_previews` queues a folder's images on a small
+// worker pool and emits `preview-ready` as each thumbnail lands. Thumbnails
+// are cached under `thumbnails` in the cache directory, keyed by path, size, and
+// mtime, so re-entering a folder costs nothing until a file changes. The pool
+// uses at most half the cores so interactive commands stay responsive, and
+// `cancel_directory_previews` drops a job's pending work when the user
+// navigates away. Workers read at background priority (see `io_throttle`).
+
+use image::{GenericImageView, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::io_throttle::{self, IoLimits, IoThrottle};
+
+/// Longest edge of a generated thumbnail, in pixels.
+const THUMBNAIL_EDGE: u32 = 256;
+const DEFAULT_MAX_ITEMS: usize = 200;
+const MAX_WORKERS: usize = 4;
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+pub struct PreviewReady {
+    pub job_id: u64,
+    pub path: String,
+    pub thumbnail_path: String,
+    /// Dimensions of the original image.
+    pub width: u32,
+    pub height: u32,
+    /// Served from the cache without decoding.
+    pub cached: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PrefetchJob {
+    pub job_id: u64,
+    /// Files sent to the worker pool.
+    pub queued: usize,
+    /// Files answered from the cache straight away.
+    pub cached: usize,
+}
+
+/// Sidecar next to each cached thumbnail.
+#[derive(Serialize, Deserialize)]
+struct CachedInfo {
+    width: u32,
+    height: u32,
+}
+
+struct Item {
+    job_id: u64,
+    path: PathBuf,
+    thumbnail: PathBuf,
+    app: AppHandle,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: VecDeque<Item>,
+    in_flight: HashSet<PathBuf>,
+    workers: usize,
+}
+
+/// Managed state: the thumbnail work queue.
+#[derive(Default)]
+pub struct PreviewPrefetch {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    next_id: AtomicU64,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Queue thumbnails for the first `max_items` images in a folder, in listing
+/// order. Cached thumbnails are emitted before this returns.
+#[tauri::command]
+pub fn prefetch_directory_previews(
+    app: AppHandle,
+    state: State<'_, PreviewPrefetch>,
+    path: String,
+    max_items: Option<usize>,
+) -> CommandResult<PrefetchJob> {
+    let dir = Path::new(&path);
+    if !dir.is_dir() {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("Not a directory: {}", path))
+                .with("path", &path),
+        );
+    }
+    let job_id = state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let mut job = PrefetchJob {
+        job_id,
+        queued: 0,
+        cached: 0,
+    };
+
+    let (lock, ready) = &*state.queue;
+    let mut queue = lock.lock().unwrap();
+    for file in previewable(dir, max_items.unwrap_or(DEFAULT_MAX_ITEMS))? {
+        let Some(thumbnail) = thumbnail_path(&file) else {
+            continue;
+        };
+        if let Some(info) = read_cached(&thumbnail) {
+            emit_ready(&app, job_id, &file, &thumbnail, info, true);
+            job.cached += 1;
+            continue;
+        }
+        let duplicate =
+            queue.in_flight.contains(&file) || queue.pending.iter().any(|i| i.path == file);
+        if duplicate {
+            continue;
+        }
+        queue.pending.push_back(Item {
+            job_id,
+            path: file,
+            thumbnail,
+            app: app.clone(),
+        });
+        job.queued += 1;
+    }
+
+    let wanted = pool_size().min(queue.pending.len());
+    while queue.workers < wanted {
+        queue.workers += 1;
+        let shared = state.queue.clone();
+        std::thread::spawn(move || worker(shared));
+    }
+    ready.notify_all();
+    Ok(job)
+}
+
+/// Drop a job's queued work; thumbnails already rendering still finish.
+/// Returns how many items were dropped.
+#[tauri::command]
+pub fn cancel_directory_previews(state: State<'_, PreviewPrefetch>, job_id: u64) -> usize {
+    let mut queue = state.queue.0.lock().unwrap();
+    let before = queue.pending.len();
+    queue.pending.retain(|item| item.job_id != job_id);
+    before - queue.pending.len()
+}
+
+impl PreviewPrefetch {
+    /// Drop every job's queued work; returns how many items were dropped.
+    pub fn drop_pending(&self) -> usize {
+        let mut queue = self.queue.0.lock().unwrap();
+        let dropped = queue.pending.len();
+        queue.pending.clear();
+        dropped
+    }
+
+    /// Images whose thumbnails are rendering now.
+    pub fn in_flight(&self) -> Vec<PathBuf> {
+        self.queue
+            .0
+            .lock()
+            .unwrap()
+            .in_flight
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+// ─── Workers ────────────────────────────────────────────────────────────────
+
+fn pool_size() -> usize {
+    let cores = std::thread::available_parallelism().map_or(2, |n| n.get());
+    (cores / 2).clamp(1, MAX_WORKERS)
+}
+
+fn worker(shared: Arc<(Mutex<Queue>, Condvar)>) {
+    let (lock, ready) = &*shared;
+    loop {
+        let item = {
+            let mut queue = lock.lock().unwrap();
+            loop {
+                if let Some(item) = queue.pending.pop_front() {
+                    queue.in_flight.insert(item.path.clone());
+                    break item;
+                }
+                queue = ready.wait(queue).unwrap();
+            }
+        };
+
+        let throttle = IoThrottle::new(IoLimits::background(), Arc::default());
+        let rendered = io_throttle::scoped(throttle, || {
+            let rendered = render(&item.path, &item.thumbnail);
+            let size = fs::metadata(&item.path).map_or(0, |m| m.len());
+            io_throttle::charge(size);
+            io_throttle::file_done();
+            rendered
+        });
+        match rendered {
+            Ok(info) => emit_ready(
+                &item.app,
+                item.job_id,
+                &item.path,
+                &item.thumbnail,
+                info,
+                false,
+            ),
+            Err(e) => log::warn!("[previews] {}: {}", item.path.display(), e),
+        }
+        lock.lock().unwrap().in_flight.remove(&item.path);
+        // Let interactive work in between thumbnails
+        std::thread::yield_now();
+    }
+}
+
+fn render(path: &Path, thumbnail: &Path) -> Result<CachedInfo, String> {
+    let image = ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| e.to_string())?;
+    let (width, height) = image.dimensions();
+
+    if let Some(parent) = thumbnail.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let temp = thumbnail.with_extension("png.tmp");
+    image
+        .thumbnail(THUMBNAIL_EDGE, THUMBNAIL_EDGE)
+        .save_with_format(&temp, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+
+    // Sidecar first: a thumbnail without one would look uncached
+    let info = CachedInfo { width, height };
+    let sidecar = serde_json::to_vec(&info).map_err(|e| e.to_string())?;
+    fs::write(thumbnail.with_extension("json"), sidecar).map_err(|e| e.to_string())?;
+    fs::rename(&temp, thumbnail).map_err(|e| e.to_string())?;
+    Ok(info)
+}
+
+fn emit_ready(
+    app: &AppHandle,
+    job_id: u64,
+    path: &Path,
+    thumbnail: &Path,
+    info: CachedInfo,
+    cached: bool,
+) {
+    let _ = app.emit(
+        "preview-ready",
+        PreviewReady {
+            job_id,
+            path: path.to_string_lossy().to_string(),
+            thumbnail_path: thumbnail.to_string_lossy().to_string(),
+            width: info.width,
+            height: info.height,
+            cached,
+        },
+    );
+}
+
+// ─── Cache ──────────────────────────────────────────────────────────────────
+
+pub fn cache_dir() -> CommandResult<PathBuf> {
+    Ok(home::cache_dir()?.join("thumbnails"))
+}
+
+/// Cache key for a file's current version: changes whenever its size or
+/// mtime does, so stale entries are never read.
+pub fn cache_key(path: &Path) -> Option<String> {
+    use sha2::{Digest, Sha256};
+
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(meta.len().to_le_bytes());
+    hasher.update(mtime.to_le_bytes());
+    Some(hex::encode(hasher.finalize()))
+}
+
+fn thumbnail_path(path: &Path) -> Option<PathBuf> {
+    Some(cache_dir().ok()?.join(format!("{}.png", cache_key(path)?)))
+}
+
+fn read_cached(thumbnail: &Path) -> Option<CachedInfo> {
+    if !thumbnail.is_file() {
+        return None;
+    }
+    let raw = fs::read(thumbnail.with_extension("json")).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// Images directly in `dir`, skipping hidden files, sorted like
+/// `list_directory`.
+fn previewable(dir: &Path, max_items: usize) -> CommandResult<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            !name.starts_with('.') && IMAGE_EXTENSIONS.contains(&ext.as_str()) && path.is_file()
+        })
+        .collect();
+    files.sort_by_key(|path| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase()
+    });
+    files.truncate(max_items);
+    Ok(files)
+}
@@ -0,0 +1,274 @@
+// Provider response cache
+//
+// `provider_fetch` can answer repeated reads, like a conversation list or
+// account details, from a local copy instead of calling the provider again.
+// It's opt-in per call with `cache`:
+// - `bypass` (the default): always call the provider, and keep nothing.
+// - `prefer`: answer from a fresh copy if there is one; otherwise call the
+//   provider and keep a copy of the answer.
+// - `only`: answer from a fresh copy or fail with `NotFound`; never call.
+//
+// Only `GET` and `HEAD` requests are cached, and only successful responses
+// with an inline body. A copy is keyed by the session (provider and
+// account), method, URL, request headers, and a hash of the body, and stays
+// fresh for the TTL of the request's usage category
+// (`settings.provider_cache.ttl_secs`, falling back to `default_ttl_secs`;
+// 0 turns caching off for a category). Answers from the cache carry
+// `from_cache` and `cached_at`, and aren't provider calls in the usage
+// ledger.
+//
+// Copies are sealed with the session vault key, like session payloads, in
+// `response-cache/` in the session's directory. Nothing is cached when the
+// vault isn't available. A new login for the session and `delete_session`
+// clear its copies, and an expired copy is dropped when it's next looked
+// up. Across all sessions the cache is kept under `max_bytes` by removing
+// the least recently used copies after each write. The total is
+// `provider_cache_bytes` in diagnostics.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::provider_fetch::{BodyEncoding, ProviderRequest, ProviderResponse};
+use crate::{secrets, sessions, settings};
+
+const CACHE_DIR: &str = "response-cache";
+const ENTRY_EXTENSION: &str = "bin";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    #[default]
+    Bypass,
+    Prefer,
+    Only,
+}
+
+/// Where a request's copy lives and how long it stays fresh.
+pub struct CacheKey {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    stored_at: String,
+    expires_at: String,
+    status: u16,
+    headers: std::collections::HashMap<String, String>,
+    body: String,
+    body_encoding: BodyEncoding,
+}
+
+// ─── Lookup ─────────────────────────────────────────────────────────────────
+
+/// The cache key for `request` in the session at `dir`, or `None` when it
+/// can't be cached.
+pub fn key(dir: &Path, request: &ProviderRequest, category: &str) -> Option<CacheKey> {
+    let method = request.method.to_ascii_uppercase();
+    if method != "GET" && method != "HEAD" {
+        return None;
+    }
+    let cache = settings::load_settings().provider_cache;
+    let ttl = cache
+        .ttl_secs
+        .get(category)
+        .copied()
+        .unwrap_or(cache.default_ttl_secs);
+    if ttl == 0 || cache.max_bytes == 0 || !secrets::vault_available() {
+        return None;
+    }
+
+    Some(CacheKey {
+        path: dir.join(CACHE_DIR).join(entry_name(&method, request)),
+        ttl: Duration::from_secs(ttl),
+    })
+}
+
+/// The copy's file name: a hash of everything that can change the answer.
+fn entry_name(method: &str, request: &ProviderRequest) -> String {
+    let mut headers: Vec<_> = request
+        .headers
+        .iter()
+        .map(|(k, v)| (k.to_ascii_lowercase(), v.as_str()))
+        .collect();
+    headers.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update([0]);
+    hasher.update(request.url.as_bytes());
+    for (name, value) in headers {
+        hasher.update([0]);
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        hasher.update(value.as_bytes());
+    }
+    hasher.update([0]);
+    hasher.update(Sha256::digest(request.body.as_deref().unwrap_or("")));
+    format!("{}.{}", hex::encode(hasher.finalize()), ENTRY_EXTENSION)
+}
+
+/// The fresh copy for `key`, marked as coming from the cache.
+pub fn lookup(key: &CacheKey) -> Option<ProviderResponse> {
+    let sealed = fs::read(&key.path).ok()?;
+    let entry: Option<Entry> = secrets::open(&sealed)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok());
+    let fresh = entry.filter(|e| {
+        chrono::DateTime::parse_from_rfc3339(&e.expires_at)
+            .is_ok_and(|expires| expires > chrono::Utc::now())
+    });
+    let Some(entry) = fresh else {
+        // Expired, or sealed with a key that's gone
+        let _ = fs::remove_file(&key.path);
+        return None;
+    };
+    // The modification time is the last use, for eviction
+    if let Ok(file) = fs::File::options().write(true).open(&key.path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Some(ProviderResponse {
+        status: entry.status,
+        headers: entry.headers,
+        body: Some(entry.body),
+        body_encoding: entry.body_encoding,
+        stream_id: None,
+        from_cache: true,
+        cached_at: Some(entry.stored_at),
+        redactions: 0,
+    })
+}
+
+// ─── Storing ────────────────────────────────────────────────────────────────
+
+/// Keep a copy of `response` under `key` if it's cacheable, then bring the
+/// cache back under its size limit.
+pub fn store(key: &CacheKey, response: &ProviderResponse) {
+    let Some(body) = response.body.clone() else {
+        return;
+    };
+    if !(200..300).contains(&response.status) {
+        return;
+    }
+    let now = chrono::Utc::now();
+    let ttl = chrono::Duration::from_std(key.ttl).unwrap_or_default();
+    let entry = Entry {
+        stored_at: now.to_rfc3339(),
+        expires_at: (now + ttl).to_rfc3339(),
+        status: response.status,
+        headers: response.headers.clone(),
+        body,
+        body_encoding: response.body_encoding,
+    };
+    let written = serde_json::to_vec(&entry)
+        .map_err(|e| e.to_string())
+        .and_then(|json| secrets::seal(&json))
+        .and_then(|sealed| {
+            let dir = key.path.parent().unwrap_or(Path::new("."));
+            fs::create_dir_all(dir)
+                .and_then(|()| fs::write(&key.path, sealed))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = written {
+        log::warn!("[provider_cache] can't keep a response: {}", e);
+        return;
+    }
+    evict(settings::load_settings().provider_cache.max_bytes);
+}
+
+/// Drop every copy kept for the session at `dir`.
+pub fn clear(dir: &Path) {
+    let cache = dir.join(CACHE_DIR);
+    if cache.exists() {
+        if let Err(e) = fs::remove_dir_all(&cache) {
+            log::warn!("[provider_cache] can't clear {}: {}", cache.display(), e);
+        }
+    }
+}
+
+/// Bytes the cache holds across all sessions.
+pub fn total_size() -> u64 {
+    entries().iter().map(|e| e.size).sum()
+}
+
+struct Stored {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+fn entries() -> Vec<Stored> {
+    let Ok(root) = sessions::sessions_root() else {
+        return Vec::new();
+    };
+    sessions::all_session_dirs(&root)
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir.join(CACHE_DIR)).ok())
+        .flat_map(|files| files.flatten())
+        .filter_map(|file| {
+            let meta = file.metadata().ok()?;
+            Some(Stored {
+                path: file.path(),
+                size: meta.len(),
+                last_used: meta.modified().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Remove the least recently used copies until the cache fits in
+/// `max_bytes`.
+fn evict(max_bytes: u64) {
+    let mut stored = entries();
+    let mut total: u64 = stored.iter().map(|e| e.size).sum();
+    if total <= max_bytes {
+        return;
+    }
+    stored.sort_by_key(|e| e.last_used);
+    let mut evicted = 0;
+    for entry in stored {
+        if total <= max_bytes {
+            break;
+        }
+        if fs::remove_file(&entry.path).is_ok() {
+            total -= entry.size;
+            evicted += 1;
+        }
+    }
+    log::info!("[provider_cache] evicted {} responses", evicted);
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn request(method: &str, url: &str) -> ProviderRequest {
+        serde_json::from_value(serde_json::json!({ "method": method, "url": url })).unwrap()
+    }
+
+    #[test]
+    fn copies_are_per_request_and_writes_are_never_cached() {
+        let _home = TestHome::new();
+        let get = request("GET", "https://chat.example.com/api/conversations");
+        let name = entry_name("GET", &get);
+        assert_eq!(name, entry_name("GET", &get));
+        assert_ne!(name, entry_name("HEAD", &get));
+
+        let mut with_header = request("GET", "https://chat.example.com/api/conversations");
+        with_header
+            .headers
+            .insert("Accept".into(), "application/json".into());
+        assert_ne!(name, entry_name("GET", &with_header));
+        let mut with_body = request("GET", "https://chat.example.com/api/conversations");
+        with_body.body = Some("{}".into());
+        assert_ne!(name, entry_name("GET", &with_body));
+
+        let post = request("POST", "https://chat.example.com/api/conversations");
+        assert!(key(Path::new("session"), &post, "conversations").is_none());
+    }
+}
@@ -0,0 +1,877 @@
+// Provider request proxy
+//
+// The webview never sees session cookies. It describes a request to a
+// provider, and this module loads the tenant's decrypted session, attaches
+// the cookies and the user agent recorded at login, performs the call, and
+// returns the response with `Set-Cookie` stripped (the stored session is
+// updated instead).
+//
+// Requests are limited to HTTPS on the provider's allowed domains, including
+// redirects. Anything else is rejected and written to the audit log.
+//
+// A request carries only the cookies RFC 6265 would send to its URL: the
+// cookie's domain must match the host (exactly, for host-only cookies), its
+// path must be a prefix of the request path, and it must not be expired.
+// SameSite is recorded but not applied, since these requests don't come from
+// another site.
+//
+// Bodies over `INLINE_BODY_LIMIT` are not returned inline: the response is
+// parked under a stream id and read in chunks with `read_provider_response`.
+// A parked body unread for `PARKED_TTL` is dropped, as are those of a window
+// that closes, and at most `MAX_PARKED` are kept; parking another drops the
+// one idle longest. Dropping closes the connection.
+//
+// Each call is recorded in the usage ledger (see `provider_usage`) once its
+// body has been read. With `cache`, repeated reads can be answered from a
+// local copy instead (see `provider_cache`).
+
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE, USER_AGENT};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::policy::{self, CommandGroup};
+use crate::provider_cache::{self, CacheMode};
+use crate::provider_stream::StreamEndReason;
+use crate::provider_usage::{self, Call};
+use crate::sessions::{SameSite, SessionPayload, StoredCookie};
+use crate::{
+    audit, http, mock_provider, providers, redaction, sessions, settings, tenant_windows, vault,
+};
+
+const INLINE_BODY_LIMIT: usize = 2 * 1024 * 1024;
+const DEFAULT_CHUNK_BYTES: usize = 256 * 1024;
+const PARKED_TTL: Duration = Duration::from_secs(120);
+const MAX_PARKED: usize = 8;
+const MAX_REDIRECTS: usize = 10;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct ProviderRequest {
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    /// Endpoint category for the usage ledger, e.g. `chat`; taken from the
+    /// URL when not given.
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    Utf8,
+    Base64,
+}
+
+#[derive(Serialize)]
+pub struct ProviderResponse {
+    pub status: u16,
+    /// Response headers, `Set-Cookie` removed. Repeated headers are joined
+    /// with `, `.
+    pub headers: HashMap<String, String>,
+    /// Inline body, or `None` when the body is too large and `stream_id` is set.
+    pub body: Option<String>,
+    pub body_encoding: BodyEncoding,
+    pub stream_id: Option<String>,
+    /// Answered from `provider_cache` without calling the provider.
+    pub from_cache: bool,
+    /// When the cached copy was fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_at: Option<String>,
+    /// Matches `redaction` replaced in the request body.
+    pub redactions: usize,
+}
+
+#[derive(Serialize)]
+pub struct ResponseChunk {
+    /// Base64 bytes.
+    pub data: String,
+    pub done: bool,
+}
+
+struct PendingBody {
+    buffered: Vec<u8>,
+    response: Option<reqwest::Response>,
+    status: u16,
+    call: Call,
+    /// Label of the window that made the request.
+    window: String,
+    last_read: Instant,
+}
+
+/// Managed state: large response bodies waiting to be read.
+#[derive(Default)]
+pub struct ProviderResponses {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<String, PendingBody>>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Perform an authenticated request to a provider on behalf of the webview.
+#[tauri::command]
+pub async fn provider_fetch(
+    window: tauri::WebviewWindow,
+    responses: State<'_, ProviderResponses>,
+    tenant_id: Option<String>,
+    provider_id: String,
+    mut request: ProviderRequest,
+    cache: Option<CacheMode>,
+) -> CommandResult<ProviderResponse> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    let session = ProviderSession::load(&tenant_id, &provider_id)?;
+    let redactions = redaction::redact_request(&tenant_id, &mut request)?;
+    if mock_provider::is_mock(&provider_id) {
+        let url = session.check_url(&request.url)?;
+        let payload = session.payload.lock().unwrap();
+        let mut response = mock_provider::respond(&request.method, url.path(), &payload);
+        response.redactions = redactions;
+        return Ok(response);
+    }
+    let cache_key = match cache.unwrap_or_default() {
+        CacheMode::Bypass => None,
+        mode => {
+            // Domains may have been narrowed since the copy was kept
+            session.check_url(&request.url)?;
+            let key = provider_cache::key(&session.dir, &request, &session.category(&request));
+            if let Some(mut hit) = key.as_ref().and_then(provider_cache::lookup) {
+                hit.redactions = redactions;
+                return Ok(hit);
+            }
+            if mode == CacheMode::Only {
+                return Err(CommandError::new(
+                    ErrorCode::NotFound,
+                    format!("No cached response for {}", request.url),
+                )
+                .with("url", &request.url));
+            }
+            key
+        }
+    };
+    let builder = http::builder(&settings::load_settings())?;
+    let call = session.start_call(&request, false);
+    let (url, request) = session.prepare(request, builder)?;
+
+    let mut response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            call.finish(None, StreamEndReason::ConnectFailed);
+            return Err(
+                CommandError::new(ErrorCode::Network, e.to_string()).with("url", url.as_str())
+            );
+        }
+    };
+
+    session.store_set_cookies(response.url().clone(), response.headers());
+    let status = response.status().as_u16();
+    let headers = response_headers(response.headers());
+
+    let oversized = response
+        .content_length()
+        .is_some_and(|len| len as usize > INLINE_BODY_LIMIT);
+    let mut buffered = Vec::new();
+    if !oversized {
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    call.add_response_bytes(chunk.len());
+                    buffered.extend_from_slice(&chunk);
+                    if buffered.len() > INLINE_BODY_LIMIT {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    call.finish(Some(status), StreamEndReason::Disconnected);
+                    return Err(network(e));
+                }
+            }
+        }
+        if buffered.len() <= INLINE_BODY_LIMIT {
+            call.finish_with_status(status);
+            let (body, body_encoding) = encode_body(buffered);
+            let response = ProviderResponse {
+                status,
+                headers,
+                body: Some(body),
+                body_encoding,
+                stream_id: None,
+                from_cache: false,
+                cached_at: None,
+                redactions,
+            };
+            if let Some(key) = &cache_key {
+                provider_cache::store(key, &response);
+            }
+            return Ok(response);
+        }
+    }
+
+    let stream_id = responses.park(PendingBody {
+        buffered,
+        response: Some(response),
+        status,
+        call,
+        window: window.label().to_string(),
+        last_read: Instant::now(),
+    });
+    Ok(ProviderResponse {
+        status,
+        headers,
+        body: None,
+        body_encoding: BodyEncoding::Base64,
+        stream_id: Some(stream_id),
+        from_cache: false,
+        cached_at: None,
+        redactions,
+    })
+}
+
+/// Read the next chunk of a large response. The stream id is released once
+/// `done` is returned, or when the body is dropped unread.
+#[tauri::command]
+pub async fn read_provider_response(
+    responses: State<'_, ProviderResponses>,
+    stream_id: String,
+    max_bytes: Option<usize>,
+) -> CommandResult<ResponseChunk> {
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_CHUNK_BYTES).max(1);
+    // Taken out of the map so the lock isn't held across awaits
+    let mut pending = responses.take(&stream_id).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("Unknown stream: {}", stream_id),
+        )
+        .with("stream_id", &stream_id)
+    })?;
+
+    while pending.buffered.len() < max_bytes {
+        let Some(response) = pending.response.as_mut() else {
+            break;
+        };
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                pending.call.add_response_bytes(chunk.len());
+                pending.buffered.extend_from_slice(&chunk);
+            }
+            Ok(None) => {
+                pending.response = None;
+                pending.call.clone().finish_with_status(pending.status);
+            }
+            Err(e) => {
+                pending
+                    .call
+                    .finish(Some(pending.status), StreamEndReason::Disconnected);
+                return Err(network(e));
+            }
+        }
+    }
+
+    let take = pending.buffered.len().min(max_bytes);
+    let data: Vec<u8> = pending.buffered.drain(..take).collect();
+    let done = pending.response.is_none() && pending.buffered.is_empty();
+    if !done {
+        pending.last_read = Instant::now();
+        responses.pending.lock().unwrap().insert(stream_id, pending);
+    }
+
+    Ok(ResponseChunk {
+        data: base64::engine::general_purpose::STANDARD.encode(data),
+        done,
+    })
+}
+
+// ─── Parked Bodies ──────────────────────────────────────────────────────────
+
+impl ProviderResponses {
+    /// Park a body for `read_provider_response`; returns its stream id.
+    fn park(&self, body: PendingBody) -> String {
+        let stream_id = format!("resp-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut pending = self.pending.lock().unwrap();
+        let expired = expire(&mut pending);
+        let mut evicted = Vec::new();
+        while pending.len() >= MAX_PARKED {
+            let Some(idlest) = pending
+                .iter()
+                .min_by_key(|(_, body)| body.last_read)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            evicted.extend(pending.remove_entry(&idlest));
+        }
+        pending.insert(stream_id.clone(), body);
+        drop(pending);
+        release(expired, "unread for too long");
+        release(evicted, "too many parked");
+        stream_id
+    }
+
+    /// Take a body out to read it, dropping expired ones first.
+    fn take(&self, stream_id: &str) -> Option<PendingBody> {
+        let mut pending = self.pending.lock().unwrap();
+        let dropped = expire(&mut pending);
+        let body = pending.remove(stream_id);
+        drop(pending);
+        release(dropped, "unread for too long");
+        body
+    }
+
+    fn release_window(&self, label: &str) {
+        let mut pending = self.pending.lock().unwrap();
+        let ids: Vec<String> = pending
+            .iter()
+            .filter(|(_, body)| body.window == label)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let dropped = ids
+            .iter()
+            .filter_map(|id| pending.remove_entry(id))
+            .collect();
+        drop(pending);
+        release(dropped, "window closed");
+    }
+}
+
+/// Drop the bodies `label`'s window hadn't finished reading when it closed.
+pub fn window_closed(app: &tauri::AppHandle, label: &str) {
+    app.state::<ProviderResponses>().release_window(label);
+}
+
+fn expire(pending: &mut HashMap<String, PendingBody>) -> Vec<(String, PendingBody)> {
+    let stale: Vec<String> = pending
+        .iter()
+        .filter(|(_, body)| body.last_read.elapsed() > PARKED_TTL)
+        .map(|(id, _)| id.clone())
+        .collect();
+    stale
+        .iter()
+        .filter_map(|id| pending.remove_entry(id))
+        .collect()
+}
+
+/// Close dropped bodies' connections and record their calls as cancelled.
+fn release(dropped: Vec<(String, PendingBody)>, why: &str) {
+    for (stream_id, body) in dropped {
+        log::info!("[provider_fetch] dropped {} ({})", stream_id, why);
+        // A body read to the end has recorded its call already
+        if body.response.is_some() {
+            body.call
+                .finish(Some(body.status), StreamEndReason::Cancelled);
+        }
+    }
+}
+
+// ─── Session ────────────────────────────────────────────────────────────────
+
+/// A tenant's captured provider session, ready to authenticate requests.
+pub struct ProviderSession {
+    tenant_id: String,
+    provider_id: String,
+    dir: PathBuf,
+    allowed_domains: Vec<String>,
+    user_agent: Option<String>,
+    extra_headers: HashMap<String, String>,
+    created_at: Option<String>,
+    payload: Mutex<SessionPayload>,
+}
+
+impl ProviderSession {
+    pub fn load(tenant_id: &str, provider_id: &str) -> CommandResult<Self> {
+        sessions::validate_id("tenant_id", tenant_id)
+            .and_then(|_| sessions::validate_id("provider_id", provider_id))
+            .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+        policy::check_provider(Some(tenant_id), provider_id)?;
+        policy::check(Some(tenant_id), CommandGroup::ProviderFetch)?;
+        vault::ensure_unlocked()?;
+        let config = providers::login_config(provider_id)?;
+
+        let not_found = || {
+            CommandError::new(
+                ErrorCode::SessionNotFound,
+                format!("No session for {} / {}", tenant_id, provider_id),
+            )
+            .with("tenant_id", tenant_id)
+            .with("provider_id", provider_id)
+        };
+        let dir = sessions::find_session_dir(&sessions::sessions_root()?, tenant_id, provider_id)
+            .ok_or_else(not_found)?;
+        let payload = sessions::load_payload(&dir)?.ok_or_else(not_found)?;
+        let meta = sessions::read_meta(&dir);
+
+        Ok(Self {
+            tenant_id: tenant_id.to_string(),
+            provider_id: provider_id.to_string(),
+            dir,
+            allowed_domains: config.allowed_domains,
+            user_agent: meta.as_ref().and_then(|m| m.user_agent.clone()),
+            created_at: meta.as_ref().map(|m| m.created_at.clone()),
+            extra_headers: meta.map(|m| m.extra_headers).unwrap_or_default(),
+            payload: Mutex::new(payload),
+        })
+    }
+
+    /// Parse `raw` and reject anything but HTTPS to an allowed domain.
+    fn check_url(&self, raw: &str) -> CommandResult<Url> {
+        let url = Url::parse(raw).map_err(|e| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid URL {}: {}", raw, e),
+            )
+        })?;
+        if is_allowed(&self.allowed_domains, &url) {
+            return Ok(url);
+        }
+        reject(&self.tenant_id, &self.provider_id, &url, "request");
+        Err(CommandError::new(
+            ErrorCode::DomainNotAllowed,
+            format!("{} is not an allowed domain for {}", url, self.provider_id),
+        )
+        .with("url", url.as_str())
+        .with("allowed_domains", &self.allowed_domains))
+    }
+
+    pub fn provider_id(&self) -> &str {
+        &self.provider_id
+    }
+
+    /// Start a usage ledger entry for `request`. Nothing is recorded until
+    /// the call is finished.
+    pub fn start_call(&self, request: &ProviderRequest, streamed: bool) -> Call {
+        let category = self.category(request);
+        Call::start(
+            &self.tenant_id,
+            &self.provider_id,
+            self.created_at.clone(),
+            &request.method,
+            category,
+            request.body.as_ref().map_or(0, String::len),
+            streamed,
+        )
+    }
+
+    /// The usage category of `request`.
+    fn category(&self, request: &ProviderRequest) -> String {
+        match Url::parse(&request.url) {
+            Ok(url) => provider_usage::category(request.category.as_deref(), &url),
+            Err(_) => "other".to_string(),
+        }
+    }
+
+    /// Validate `request` and build it on a client from `builder`, with the
+    /// session's cookies, user agent, and redirect policy applied.
+    pub fn prepare(
+        &self,
+        request: ProviderRequest,
+        builder: reqwest::ClientBuilder,
+    ) -> CommandResult<(Url, reqwest::RequestBuilder)> {
+        let url = self.check_url(&request.url)?;
+        let method =
+            Method::from_bytes(request.method.to_uppercase().as_bytes()).map_err(|_| {
+                CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid HTTP method: {}", request.method),
+                )
+            })?;
+
+        let mut prepared = self
+            .client(builder)?
+            .request(method, url.clone())
+            .headers(self.headers(&url, &request.headers)?);
+        if let Some(body) = request.body {
+            prepared = prepared.body(body);
+        }
+        Ok((url, prepared))
+    }
+
+    /// Client that only follows redirects within the allowed domains.
+    fn client(&self, builder: reqwest::ClientBuilder) -> CommandResult<reqwest::Client> {
+        let allowed = self.allowed_domains.clone();
+        let tenant_id = self.tenant_id.clone();
+        let provider_id = self.provider_id.clone();
+        let policy = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_allowed(&allowed, attempt.url()) {
+                attempt.follow()
+            } else {
+                reject(&tenant_id, &provider_id, attempt.url(), "redirect");
+                attempt.stop()
+            }
+        });
+
+        let mut builder = builder.redirect(policy);
+        if let Some(ua) = &self.user_agent {
+            builder = builder.user_agent(ua);
+        }
+        builder
+            .build()
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+    }
+
+    /// Caller headers plus the recorded login headers and stored cookies.
+    /// Callers cannot supply their own `Cookie` header.
+    fn headers(&self, url: &Url, extra: &HashMap<String, String>) -> CommandResult<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.extra_headers.iter().chain(extra) {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                CommandError::new(ErrorCode::InvalidInput, format!("Invalid header: {}", name))
+            })?;
+            if name == COOKIE || name == USER_AGENT {
+                continue;
+            }
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid value for header {}", name),
+                )
+            })?;
+            headers.insert(name, value);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let payload = self.payload.lock().unwrap();
+        let mut matching: Vec<&StoredCookie> = payload
+            .cookies
+            .iter()
+            .filter(|c| c.expires_at.is_none_or(|at| at > now))
+            .filter(|c| cookie_matches(c, url))
+            .collect();
+        // Longer paths first, as browsers send them
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let cookie = matching
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if !cookie.is_empty() {
+            let value = HeaderValue::from_str(&cookie).map_err(|_| {
+                CommandError::new(ErrorCode::Internal, "Stored cookie is not a valid header")
+            })?;
+            headers.insert(COOKIE, value);
+        }
+        Ok(headers)
+    }
+
+    /// Fold `Set-Cookie` headers into the stored session.
+    pub fn store_set_cookies(&self, url: Url, headers: &HeaderMap) {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let now = chrono::Utc::now().timestamp();
+        let mut payload = self.payload.lock().unwrap();
+        let mut changed = false;
+
+        for raw in headers.get_all(SET_COOKIE) {
+            let Some(cookie) = raw
+                .to_str()
+                .ok()
+                .and_then(|s| tauri::webview::cookie::Cookie::parse(s.to_string()).ok())
+            else {
+                continue;
+            };
+            let host_only = cookie.domain().is_none();
+            let domain = cookie.domain().map(str::to_string).unwrap_or(host.clone());
+            // A provider can't plant cookies for hosts outside its own domains,
+            // nor a host for a domain it isn't part of
+            if !providers::domain_allowed(&self.allowed_domains, &domain)
+                || !domain_matches(&domain, false, &host)
+            {
+                continue;
+            }
+            let path = cookie
+                .path()
+                .filter(|p| p.starts_with('/'))
+                .map(str::to_string)
+                .unwrap_or_else(|| default_path(url.path()));
+            let expires_at = cookie
+                .max_age()
+                .map(|age| now + age.whole_seconds())
+                .or_else(|| cookie.expires_datetime().map(|t| t.unix_timestamp()));
+
+            payload.cookies.retain(|c| {
+                !(c.name == cookie.name() && same_domain(&c.domain, &domain) && c.path == path)
+            });
+            if expires_at.is_none_or(|at| at > now) {
+                payload.cookies.push(StoredCookie {
+                    name: cookie.name().to_string(),
+                    value: cookie.value().to_string(),
+                    domain,
+                    expires_at,
+                    path,
+                    secure: cookie.secure().unwrap_or(false),
+                    http_only: cookie.http_only().unwrap_or(false),
+                    same_site: same_site(&cookie),
+                    host_only,
+                });
+            }
+            changed = true;
+        }
+
+        if changed {
+            payload.updated_at = chrono::Utc::now().to_rfc3339();
+            if let Err(e) = sessions::save_payload(&self.dir, &payload) {
+                log::warn!(
+                    "[provider_fetch] failed to update session for {} / {}: {}",
+                    self.tenant_id,
+                    self.provider_id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+fn is_allowed(allowed: &[String], url: &Url) -> bool {
+    url.scheme() == "https"
+        && url
+            .host_str()
+            .is_some_and(|host| providers::domain_allowed(allowed, host))
+}
+
+fn reject(tenant_id: &str, provider_id: &str, url: &Url, kind: &str) {
+    log::warn!(
+        "[provider_fetch] rejected {} to {} for {} / {}",
+        kind,
+        url,
+        tenant_id,
+        provider_id
+    );
+    audit::record(
+        "provider_fetch_rejected",
+        Some(tenant_id),
+        serde_json::json!({
+            "provider_id": provider_id,
+            "kind": kind,
+            // Query strings can carry tokens
+            "host": url.host_str(),
+            "scheme": url.scheme(),
+        }),
+    );
+}
+
+/// Whether `cookie` belongs on a request to `url` (RFC 6265, section 5.4).
+pub fn cookie_matches(cookie: &StoredCookie, url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    (!cookie.secure || url.scheme() == "https")
+        && domain_matches(&cookie.domain, cookie.host_only, host)
+        && path_matches(&cookie.path, url.path())
+}
+
+/// RFC 6265 domain matching. A leading dot on `domain` is ignored, and IP
+/// addresses only ever match themselves.
+fn domain_matches(domain: &str, host_only: bool, host: &str) -> bool {
+    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if domain.is_empty() {
+        return false;
+    }
+    if host == domain {
+        return true;
+    }
+    let is_ip = host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[');
+    !host_only && !is_ip && host.ends_with(&format!(".{}", domain))
+}
+
+/// RFC 6265 path matching: `/docs` covers `/docs`, `/docs/` and `/docs/a`,
+/// but not `/docsearch`.
+fn path_matches(cookie_path: &str, request_path: &str) -> bool {
+    let request_path = if request_path.is_empty() {
+        "/"
+    } else {
+        request_path
+    };
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The path a cookie set without one gets: the request path's folder.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".into(),
+        Some(at) => request_path[..at].to_string(),
+    }
+}
+
+pub fn same_site(cookie: &tauri::webview::cookie::Cookie) -> Option<SameSite> {
+    use tauri::webview::cookie::SameSite as Attr;
+    cookie.same_site().map(|s| match s {
+        Attr::Strict => SameSite::Strict,
+        Attr::Lax => SameSite::Lax,
+        Attr::None => SameSite::None,
+    })
+}
+
+fn same_domain(a: &str, b: &str) -> bool {
+    a.trim_start_matches('.')
+        .eq_ignore_ascii_case(b.trim_start_matches('.'))
+}
+
+pub fn response_headers(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut out: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        if name == SET_COOKIE {
+            continue;
+        }
+        let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+        out.entry(name.as_str().to_string())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    out
+}
+
+fn encode_body(bytes: Vec<u8>) -> (String, BodyEncoding) {
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, BodyEncoding::Utf8),
+        Err(e) => (
+            base64::engine::general_purpose::STANDARD.encode(e.into_bytes()),
+            BodyEncoding::Base64,
+        ),
+    }
+}
+
+fn network(e: reqwest::Error) -> CommandError {
+    CommandError::new(ErrorCode::Network, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(domain: &str, path: &str, host_only: bool, secure: bool) -> StoredCookie {
+        StoredCookie {
+            name: "sid".into(),
+            value: "1".into(),
+            domain: domain.into(),
+            expires_at: None,
+            path: path.into(),
+            secure,
+            http_only: true,
+            same_site: None,
+            host_only,
+        }
+    }
+
+    fn sent(cookie: &StoredCookie, url: &str) -> bool {
+        cookie_matches(cookie, &Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn domain_cookies_cover_subdomains_but_not_neighbours() {
+        for domain in ["openai.com", ".openai.com", "OpenAI.com"] {
+            let c = cookie(domain, "/", false, false);
+            assert!(sent(&c, "https://openai.com/"), "{}", domain);
+            assert!(sent(&c, "https://chat.openai.com/"), "{}", domain);
+            assert!(sent(&c, "https://a.b.openai.com/"), "{}", domain);
+            assert!(!sent(&c, "https://notopenai.com/"), "{}", domain);
+            assert!(!sent(&c, "https://openai.com.evil.io/"), "{}", domain);
+        }
+        // A subdomain's cookies stay off its parent and its siblings
+        let chat = cookie("chat.openai.com", "/", false, false);
+        assert!(!sent(&chat, "https://openai.com/"));
+        assert!(!sent(&chat, "https://auth0.openai.com/"));
+    }
+
+    #[test]
+    fn host_only_cookies_match_the_exact_host() {
+        let c = cookie("chat.openai.com", "/", true, false);
+        assert!(sent(&c, "https://chat.openai.com/c/1"));
+        assert!(sent(&c, "https://CHAT.openai.com/"));
+        assert!(!sent(&c, "https://eu.chat.openai.com/"));
+    }
+
+    #[test]
+    fn ip_hosts_never_match_as_subdomains() {
+        assert!(domain_matches("10.0.0.1", false, "10.0.0.1"));
+        assert!(!domain_matches("0.0.1", false, "10.0.0.1"));
+        assert!(!domain_matches("", false, "example.com"));
+    }
+
+    #[test]
+    fn paths_match_on_segment_boundaries() {
+        let docs = cookie("example.com", "/docs", false, false);
+        assert!(sent(&docs, "https://example.com/docs"));
+        assert!(sent(&docs, "https://example.com/docs/"));
+        assert!(sent(&docs, "https://example.com/docs/a/b"));
+        assert!(!sent(&docs, "https://example.com/docsearch"));
+        assert!(!sent(&docs, "https://example.com/"));
+
+        let slash = cookie("example.com", "/api/", false, false);
+        assert!(sent(&slash, "https://example.com/api/v1"));
+        assert!(!sent(&slash, "https://example.com/api"));
+
+        assert_eq!(default_path(""), "/");
+        assert_eq!(default_path("/login"), "/");
+        assert_eq!(default_path("/auth/login"), "/auth");
+        assert_eq!(default_path("/auth/callback/"), "/auth/callback");
+    }
+
+    #[test]
+    fn secure_cookies_need_https() {
+        let c = cookie("example.com", "/", false, true);
+        assert!(sent(&c, "https://example.com/"));
+        assert!(!sent(&c, "http://example.com/"));
+    }
+
+    fn parked(window: &str, idle: Duration) -> PendingBody {
+        PendingBody {
+            buffered: vec![0; 4],
+            response: None,
+            status: 200,
+            call: Call::start("acme", "claude", None, "GET", "chat".into(), 0, false),
+            window: window.into(),
+            last_read: Instant::now() - idle,
+        }
+    }
+
+    #[test]
+    fn parked_bodies_are_capped_and_expire() {
+        let responses = ProviderResponses::default();
+        let idlest = responses.park(parked("main", Duration::from_secs(60)));
+        // Each idle less than the one before, so the next to go is `ids[0]`
+        let ids: Vec<String> = (0..MAX_PARKED)
+            .map(|i| responses.park(parked("main", Duration::from_secs((MAX_PARKED - i) as u64))))
+            .collect();
+        assert_eq!(responses.pending.lock().unwrap().len(), MAX_PARKED);
+        assert!(responses.take(&idlest).is_none());
+
+        let stale = responses.park(parked("main", PARKED_TTL * 2));
+        assert!(responses.take(&ids[0]).is_none());
+        assert!(responses.take(&ids[1]).is_some());
+        assert!(responses.take(&stale).is_none());
+    }
+
+    #[test]
+    fn closing_a_window_drops_only_its_bodies() {
+        let responses = ProviderResponses::default();
+        let ours = responses.park(parked("tenant-acme", Duration::ZERO));
+        let theirs = responses.park(parked("main", Duration::ZERO));
+        responses.release_window("tenant-acme");
+        assert!(responses.take(&ours).is_none());
+        assert!(responses.take(&theirs).is_some());
+    }
+}
@@ -0,0 +1,425 @@
+// Provider SSE relay
+//
+// Chat endpoints stream server-sent events; buffering them in
+// `provider_fetch` would hold back the whole generation. `provider_fetch_stream`
+// makes the same authenticated request and relays each event to the window
+// that started it as `provider-stream-chunk`. Every stream ends with exactly one
+// `provider-stream-end` carrying a reason code, whether it completed, was
+// cancelled, was rate limited, or dropped mid-stream. The same end goes into
+// the usage ledger (see `provider_usage`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::provider_fetch::{ProviderRequest, ProviderSession};
+use crate::provider_usage::Call;
+use crate::{http, redaction, settings, tenant_windows};
+
+/// Concurrent streams allowed per provider, across tenants.
+const MAX_STREAMS_PER_PROVIDER: usize = 4;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+struct StreamChunk {
+    stream_id: String,
+    /// SSE `event:` field; `message` when the server didn't name it.
+    event: String,
+    data: String,
+    /// SSE `id:` field, when present.
+    id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEndReason {
+    /// The server closed the stream normally.
+    Completed,
+    /// `cancel_provider_stream` was called.
+    Cancelled,
+    /// 429 or 503; see `retry_after_secs`.
+    RateLimited,
+    /// Any other non-success status.
+    HttpError,
+    /// The request could not be sent.
+    ConnectFailed,
+    /// The connection dropped or went idle after the stream started.
+    Disconnected,
+}
+
+#[derive(Serialize, Clone)]
+struct StreamEnd {
+    stream_id: String,
+    reason: StreamEndReason,
+    status: Option<u16>,
+    retry_after_secs: Option<u64>,
+    message: Option<String>,
+}
+
+impl StreamEnd {
+    fn new(stream_id: &str, reason: StreamEndReason) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            reason,
+            status: None,
+            retry_after_secs: None,
+            message: None,
+        }
+    }
+}
+
+struct ActiveStream {
+    provider_id: String,
+    /// Label of the requesting window; its events go only there.
+    window: String,
+    task: Option<tauri::async_runtime::JoinHandle<()>>,
+    call: Call,
+}
+
+/// Managed state: SSE streams currently relaying.
+#[derive(Default)]
+pub struct ProviderStreams {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<String, ActiveStream>>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Start an authenticated streaming request and return its stream id.
+///
+/// Validation errors (unknown session, disallowed domain, stream limit) are
+/// returned directly; everything after that arrives as events.
+#[tauri::command]
+pub fn provider_fetch_stream(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    streams: State<'_, ProviderStreams>,
+    tenant_id: Option<String>,
+    provider_id: String,
+    mut request: ProviderRequest,
+) -> CommandResult<String> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    let session = ProviderSession::load(&tenant_id, &provider_id)?;
+    redaction::redact_request(&tenant_id, &mut request)?;
+    let builder = http::streaming_builder(&settings::load_settings())?;
+    let call = session.start_call(&request, true);
+    let (_, request) = session.prepare(request, builder)?;
+
+    let stream_id = format!("sse-{}", streams.next_id.fetch_add(1, Ordering::Relaxed));
+    {
+        let mut active = streams.active.lock().unwrap();
+        let open = active
+            .values()
+            .filter(|s| s.provider_id == provider_id)
+            .count();
+        if open >= MAX_STREAMS_PER_PROVIDER {
+            return Err(CommandError::new(
+                ErrorCode::StreamLimitReached,
+                format!("Too many concurrent streams for {}", provider_id),
+            )
+            .with("provider_id", &provider_id)
+            .with("limit", MAX_STREAMS_PER_PROVIDER));
+        }
+        // Reserved before spawning so a fast-finishing task can't race the insert
+        active.insert(
+            stream_id.clone(),
+            ActiveStream {
+                provider_id: provider_id.clone(),
+                window: window.label().to_string(),
+                task: None,
+                call: call.clone(),
+            },
+        );
+    }
+
+    let task = {
+        let app = app.clone();
+        let stream_id = stream_id.clone();
+        let window = window.label().to_string();
+        tauri::async_runtime::spawn(async move {
+            let end = relay(&app, &window, &session, &stream_id, request, &call).await;
+            finish(&app, &stream_id, end);
+        })
+    };
+    if let Some(entry) = streams.active.lock().unwrap().get_mut(&stream_id) {
+        entry.task = Some(task);
+    }
+    Ok(stream_id)
+}
+
+/// Abort a stream. Returns false when it had already ended.
+#[tauri::command]
+pub fn cancel_provider_stream(
+    app: AppHandle,
+    streams: State<'_, ProviderStreams>,
+    stream_id: String,
+) -> bool {
+    streams.cancel(&app, &stream_id)
+}
+
+impl ProviderStreams {
+    pub fn cancel(&self, app: &AppHandle, stream_id: &str) -> bool {
+        let Some(entry) = self.active.lock().unwrap().remove(stream_id) else {
+            return false;
+        };
+        // Dropping the task drops the response, which closes the connection
+        if let Some(task) = entry.task {
+            task.abort();
+        }
+        entry.call.finish(None, StreamEndReason::Cancelled);
+        let _ = app.emit_to(
+            entry.window.as_str(),
+            "provider-stream-end",
+            StreamEnd::new(stream_id, StreamEndReason::Cancelled),
+        );
+        true
+    }
+
+    /// Cancel every open stream; returns their ids.
+    pub fn cancel_all(&self, app: &AppHandle) -> Vec<String> {
+        let ids: Vec<String> = self.active.lock().unwrap().keys().cloned().collect();
+        ids.into_iter().filter(|id| self.cancel(app, id)).collect()
+    }
+}
+
+// ─── Relay ──────────────────────────────────────────────────────────────────
+
+async fn relay(
+    app: &AppHandle,
+    window: &str,
+    session: &ProviderSession,
+    stream_id: &str,
+    request: reqwest::RequestBuilder,
+    call: &Call,
+) -> StreamEnd {
+    let mut response = match request.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            return StreamEnd {
+                message: Some(e.to_string()),
+                ..StreamEnd::new(stream_id, StreamEndReason::ConnectFailed)
+            }
+        }
+    };
+    session.store_set_cookies(response.url().clone(), response.headers());
+
+    let status = response.status();
+    if !status.is_success() {
+        let rate_limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status == reqwest::StatusCode::SERVICE_UNAVAILABLE;
+        let reason = if rate_limited {
+            StreamEndReason::RateLimited
+        } else {
+            StreamEndReason::HttpError
+        };
+        return StreamEnd {
+            status: Some(status.as_u16()),
+            retry_after_secs: retry_after(response.headers()),
+            message: Some(format!("HTTP {}", status)),
+            ..StreamEnd::new(stream_id, reason)
+        };
+    }
+
+    let mut parser = SseParser::default();
+    loop {
+        match response.chunk().await {
+            Ok(Some(bytes)) => {
+                call.add_response_bytes(bytes.len());
+                for event in parser.feed(&bytes) {
+                    emit_chunk(app, window, stream_id, event);
+                }
+            }
+            Ok(None) => {
+                if let Some(event) = parser.finish() {
+                    emit_chunk(app, window, stream_id, event);
+                }
+                return StreamEnd {
+                    status: Some(status.as_u16()),
+                    ..StreamEnd::new(stream_id, StreamEndReason::Completed)
+                };
+            }
+            Err(e) => {
+                log::warn!(
+                    "[provider_stream] {} for {} dropped: {}",
+                    stream_id,
+                    session.provider_id(),
+                    e
+                );
+                return StreamEnd {
+                    status: Some(status.as_u16()),
+                    message: Some(e.to_string()),
+                    ..StreamEnd::new(stream_id, StreamEndReason::Disconnected)
+                };
+            }
+        }
+    }
+}
+
+fn emit_chunk(app: &AppHandle, window: &str, stream_id: &str, event: SseEvent) {
+    let _ = app.emit_to(
+        window,
+        "provider-stream-chunk",
+        StreamChunk {
+            stream_id: stream_id.to_string(),
+            event: event.event,
+            data: event.data,
+            id: event.id,
+        },
+    );
+}
+
+/// Release the slot and emit the terminal event, unless cancel already did.
+fn finish(app: &AppHandle, stream_id: &str, end: StreamEnd) {
+    let streams = app.state::<ProviderStreams>();
+    let removed = streams.active.lock().unwrap().remove(stream_id);
+    if let Some(entry) = removed {
+        entry.call.finish(end.status, end.reason);
+        let _ = app.emit_to(entry.window.as_str(), "provider-stream-end", end);
+    }
+}
+
+/// `Retry-After` in seconds. HTTP-date values are converted relative to now.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let raw = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(secs) = raw.trim().parse::<u64>() {
+        return Some(secs);
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(raw.trim()).ok()?;
+    Some((at.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64)
+}
+
+// ─── SSE Parsing ────────────────────────────────────────────────────────────
+
+struct SseEvent {
+    event: String,
+    data: String,
+    id: Option<String>,
+}
+
+/// Incremental `text/event-stream` parser. Bytes are buffered until a full
+/// line arrives, so multi-byte characters split across chunks survive.
+#[derive(Default)]
+struct SseParser {
+    pending: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.pending.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            events.extend(self.line(&line));
+        }
+        events
+    }
+
+    /// The event still open when the stream ends; servers often close
+    /// without the blank line that would dispatch it.
+    fn finish(&mut self) -> Option<SseEvent> {
+        let rest = std::mem::take(&mut self.pending);
+        self.line(&rest).or_else(|| self.line(b""))
+    }
+
+    fn line(&mut self, raw: &[u8]) -> Option<SseEvent> {
+        let line = String::from_utf8_lossy(raw);
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if line.is_empty() {
+            let event = (!self.data.is_empty()).then(|| SseEvent {
+                event: self.event.take().unwrap_or_else(|| "message".into()),
+                data: self.data.join("\n"),
+                id: self.id.clone(),
+            });
+            self.data.clear();
+            self.event = None;
+            return event;
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&[u8]]) -> Vec<(String, String, Option<String>)> {
+        let mut parser = SseParser::default();
+        let mut events: Vec<SseEvent> = chunks.iter().flat_map(|c| parser.feed(c)).collect();
+        events.extend(parser.finish());
+        events
+            .into_iter()
+            .map(|e| (e.event, e.data, e.id))
+            .collect()
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> (String, String, Option<String>) {
+        (event.into(), data.into(), id.map(Into::into))
+    }
+
+    #[test]
+    fn data_lines_join_and_comments_are_skipped() {
+        let events = parse(&[b": keep-alive\nevent: delta\ndata: one\n: mid\ndata:two\nid: 7\n\n"]);
+        assert_eq!(events, [event("delta", "one\ntwo", Some("7"))]);
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_like_lf() {
+        let events = parse(&[b"data: a\r\n\r\nevent: done\r\ndata: b\r\n\r\n"]);
+        assert_eq!(
+            events,
+            [event("message", "a", None), event("done", "b", None)]
+        );
+    }
+
+    #[test]
+    fn events_split_across_chunks_are_reassembled() {
+        let text = "id: 1\ndata: h\u{e9}llo\n\ndata: world\n\n".as_bytes();
+        // Mid-field, mid-character, and between the two line breaks
+        let events = parse(&[&text[..4], &text[4..14], &text[14..20], &text[20..]]);
+        assert_eq!(
+            events,
+            [
+                event("message", "h\u{e9}llo", Some("1")),
+                event("message", "world", Some("1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_last_event_dispatches_without_a_trailing_blank_line() {
+        assert_eq!(
+            parse(&[b"data: first\n\ndata: last"]),
+            [
+                event("message", "first", None),
+                event("message", "last", None)
+            ]
+        );
+        assert_eq!(
+            parse(&[b"data: last\r\n"]),
+            [event("message", "last", None)]
+        );
+        assert_eq!(parse(&[b"data: done\n\n: bye"]).len(), 1);
+    }
+}
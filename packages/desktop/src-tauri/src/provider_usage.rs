@@ -0,0 +1,533 @@
+// Provider usage ledger
+//
+// Every call `provider_fetch` and `provider_fetch_stream` make for a tenant
+// is recorded in `provider_usage.jsonl` (state): who made it, which session
+// powered it, when, how many body bytes went each way, how long it took, and
+// how it ended. `get_provider_usage` totals the ledger by day, provider, or
+// account, and `export_results` writes its entries as CSV or JSON.
+//
+// Entries never hold URLs, headers, or bodies. The endpoint is reduced to a
+// category: the one the caller names in the request, or the URL's first path
+// segment that reads like a word (`/backend-api/conversation` is
+// `conversation`), so ids in paths stay out. An account is a session, named
+// by when it was captured; logging in again starts a new one. Calls to the
+// mock provider aren't recorded.
+//
+// Entries older than `usage_retention_days` are dropped by the daily
+// `provider_usage_retention` job; `purge_provider_usage` drops them on demand.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::provider_stream::StreamEndReason;
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+use crate::{home, notes, sessions, settings};
+
+const USAGE_FILE: &str = "provider_usage.jsonl";
+const MAX_CATEGORY_CHARS: usize = 32;
+/// Path segments that say nothing about the endpoint.
+const GENERIC_SEGMENTS: &[&str] = &["api", "backend-api", "rest", "public", "internal"];
+
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Day, provider, and session; the ones not grouped by are `None`.
+type GroupKey = (Option<String>, Option<String>, Option<String>);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UsageEntry {
+    /// When the call started.
+    pub at: String,
+    pub tenant_id: String,
+    pub provider_id: String,
+    /// When the session that made the call was captured.
+    pub session: Option<String>,
+    pub category: String,
+    pub method: String,
+    /// Made by `provider_fetch_stream`.
+    pub streamed: bool,
+    /// HTTP status; `None` when no response arrived.
+    pub status: Option<u16>,
+    pub outcome: StreamEndReason,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGrouping {
+    /// Local calendar days.
+    #[default]
+    Day,
+    Provider,
+    /// Provider and session.
+    Account,
+}
+
+/// Dates (`YYYY-MM-DD`, local midnight) or RFC 3339 times; `end` is
+/// exclusive. Either side may be left open.
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct UsageRange {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsageTotals {
+    /// Set when grouped by day.
+    pub day: Option<String>,
+    /// Set when grouped by provider or account.
+    pub provider_id: Option<String>,
+    /// Set when grouped by account.
+    pub session: Option<String>,
+    pub requests: u64,
+    /// Calls that didn't complete with a success status.
+    pub failed: u64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PurgeReport {
+    pub removed: usize,
+    pub kept: usize,
+}
+
+/// A call in flight. Clones share the response byte count, so a stream's
+/// relay task and its cancel can both see it.
+#[derive(Clone)]
+pub struct Call {
+    entry: UsageEntry,
+    started: Instant,
+    response_bytes: Arc<AtomicU64>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Ledger totals for a tenant in `range`, oldest or alphabetically first.
+#[tauri::command]
+pub async fn get_provider_usage(
+    tenant_id: String,
+    range: Option<UsageRange>,
+    group_by: Option<UsageGrouping>,
+) -> CommandResult<Vec<UsageTotals>> {
+    let range = range.unwrap_or_default();
+    let group_by = group_by.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || {
+        Ok(totals(&entries(&tenant_id, &range)?, group_by))
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Drop entries from before `before`, or all of them, for one tenant or
+/// every tenant.
+#[tauri::command]
+pub async fn purge_provider_usage(
+    tenant_id: Option<String>,
+    before: Option<String>,
+) -> CommandResult<PurgeReport> {
+    if let Some(tenant_id) = &tenant_id {
+        validate_tenant(tenant_id)?;
+    }
+    let before = before.as_deref().map(parse_bound).transpose()?;
+    tauri::async_runtime::spawn_blocking(move || purge(tenant_id.as_deref(), before))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Recording ──────────────────────────────────────────────────────────────
+
+impl Call {
+    pub fn start(
+        tenant_id: &str,
+        provider_id: &str,
+        session: Option<String>,
+        method: &str,
+        category: String,
+        request_bytes: usize,
+        streamed: bool,
+    ) -> Self {
+        Self {
+            entry: UsageEntry {
+                at: Utc::now().to_rfc3339(),
+                tenant_id: tenant_id.to_string(),
+                provider_id: provider_id.to_string(),
+                session,
+                category,
+                method: method.to_ascii_uppercase(),
+                streamed,
+                status: None,
+                outcome: StreamEndReason::ConnectFailed,
+                request_bytes: request_bytes as u64,
+                response_bytes: 0,
+                duration_ms: 0,
+            },
+            started: Instant::now(),
+            response_bytes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn add_response_bytes(&self, bytes: usize) {
+        self.response_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Record the call as ended with `status`, judged by the status alone.
+    pub fn finish_with_status(self, status: u16) {
+        let outcome = match status {
+            429 | 503 => StreamEndReason::RateLimited,
+            200..=399 => StreamEndReason::Completed,
+            _ => StreamEndReason::HttpError,
+        };
+        self.finish(Some(status), outcome);
+    }
+
+    /// Record the call. Failures are logged, never surfaced.
+    pub fn finish(self, status: Option<u16>, outcome: StreamEndReason) {
+        let entry = UsageEntry {
+            status,
+            outcome,
+            response_bytes: self.response_bytes.load(Ordering::Relaxed),
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            ..self.entry
+        };
+        if let Err(e) = append(&entry) {
+            log::warn!(
+                "[provider_usage] failed to record a call for {} / {}: {}",
+                entry.tenant_id,
+                entry.provider_id,
+                e
+            );
+        }
+    }
+}
+
+/// The ledger category for a call to `url`: `named` when it's usable,
+/// otherwise the first word-like path segment, otherwise `other`.
+pub fn category(named: Option<&str>, url: &reqwest::Url) -> String {
+    if let Some(named) = named {
+        let clean: String = named
+            .trim()
+            .to_ascii_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            .take(MAX_CATEGORY_CHARS)
+            .collect();
+        if !clean.is_empty() {
+            return clean;
+        }
+    }
+    url.path_segments()
+        .into_iter()
+        .flatten()
+        .map(|segment| segment.to_ascii_lowercase())
+        .find(|segment| {
+            !segment.is_empty()
+                && segment.len() <= MAX_CATEGORY_CHARS
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || matches!(c, '_' | '-'))
+                && !GENERIC_SEGMENTS.contains(&segment.as_str())
+        })
+        .unwrap_or_else(|| "other".to_string())
+}
+
+fn append(entry: &UsageEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let path = usage_path().map_err(|e| e.to_string())?;
+    let _guard = FILE_LOCK.lock().unwrap();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+// ─── Reading ────────────────────────────────────────────────────────────────
+
+/// A tenant's entries in `range`, oldest first.
+pub fn entries(tenant_id: &str, range: &UsageRange) -> CommandResult<Vec<UsageEntry>> {
+    validate_tenant(tenant_id)?;
+    let start = range.start.as_deref().map(parse_bound).transpose()?;
+    let end = range.end.as_deref().map(parse_bound).transpose()?;
+    let mut entries: Vec<(DateTime<Utc>, UsageEntry)> = read_all()?
+        .into_iter()
+        .filter(|e| e.tenant_id == tenant_id)
+        .filter_map(|e| Some((started_at(&e)?, e)))
+        .filter(|(at, _)| start.is_none_or(|start| *at >= start))
+        .filter(|(at, _)| end.is_none_or(|end| *at < end))
+        .collect();
+    entries.sort_by_key(|(at, _)| *at);
+    Ok(entries.into_iter().map(|(_, e)| e).collect())
+}
+
+fn totals(entries: &[UsageEntry], group_by: UsageGrouping) -> Vec<UsageTotals> {
+    let mut groups: BTreeMap<GroupKey, UsageTotals> = BTreeMap::new();
+    for entry in entries {
+        let day =
+            started_at(entry).map(|at| at.with_timezone(&Local).format("%Y-%m-%d").to_string());
+        let key = match group_by {
+            UsageGrouping::Day => (day, None, None),
+            UsageGrouping::Provider => (None, Some(entry.provider_id.clone()), None),
+            UsageGrouping::Account => {
+                (None, Some(entry.provider_id.clone()), entry.session.clone())
+            }
+        };
+        let group = groups.entry(key.clone()).or_insert_with(|| UsageTotals {
+            day: key.0,
+            provider_id: key.1,
+            session: key.2,
+            ..Default::default()
+        });
+        group.requests += 1;
+        let succeeded = entry.outcome == StreamEndReason::Completed
+            && entry.status.is_some_and(|s| (200..400).contains(&s));
+        if !succeeded {
+            group.failed += 1;
+        }
+        group.request_bytes += entry.request_bytes;
+        group.response_bytes += entry.response_bytes;
+        group.duration_ms += entry.duration_ms;
+    }
+    groups.into_values().collect()
+}
+
+fn read_all() -> CommandResult<Vec<UsageEntry>> {
+    let path = usage_path()?;
+    let _guard = FILE_LOCK.lock().unwrap();
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+fn started_at(entry: &UsageEntry) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&entry.at)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+// ─── Retention ──────────────────────────────────────────────────────────────
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(
+        "provider_usage_retention",
+        "Drop provider usage entries past the retention period",
+        Schedule::Every(Duration::from_secs(24 * 60 * 60)),
+        JobLoad::Light,
+        true,
+        |_| sweep(),
+    );
+}
+
+fn sweep() -> Result<Option<String>, String> {
+    let days = settings::load_settings().usage_retention_days;
+    if days == 0 {
+        return Ok(Some("Retention is off".into()));
+    }
+    let cutoff = Utc::now() - chrono::TimeDelta::days(days as i64);
+    let report = purge(None, Some(cutoff)).map_err(|e| e.to_string())?;
+    if report.removed > 0 {
+        log::info!(
+            "[provider_usage] retention sweep removed {} entries",
+            report.removed
+        );
+    }
+    Ok(Some(format!("{} entries removed", report.removed)))
+}
+
+fn purge(tenant_id: Option<&str>, before: Option<DateTime<Utc>>) -> CommandResult<PurgeReport> {
+    let path = usage_path()?;
+    let _guard = FILE_LOCK.lock().unwrap();
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(PurgeReport {
+                removed: 0,
+                kept: 0,
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut kept = String::new();
+    let mut removed = 0;
+    for line in raw.lines().filter(|l| !l.trim().is_empty()) {
+        // Lines that don't parse are kept; they aren't this code's to drop
+        let drop = serde_json::from_str::<UsageEntry>(line).is_ok_and(|entry| {
+            tenant_id.is_none_or(|t| entry.tenant_id == t)
+                && before.is_none_or(|before| started_at(&entry).is_some_and(|at| at < before))
+        });
+        if drop {
+            removed += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    if removed > 0 {
+        notes::write_atomic(&path, kept.as_bytes())?;
+    }
+    Ok(PurgeReport {
+        removed,
+        kept: kept.lines().count(),
+    })
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+fn usage_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join(USAGE_FILE))
+}
+
+fn validate_tenant(tenant_id: &str) -> CommandResult<()> {
+    sessions::validate_id("tenant_id", tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))
+}
+
+fn parse_bound(raw: &str) -> CommandResult<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d") {
+        let midnight = date.and_time(NaiveTime::MIN);
+        return Ok(Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc))
+            .unwrap_or_else(|| midnight.and_utc()));
+    }
+    DateTime::parse_from_rfc3339(raw.trim())
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid date or time: {}", raw),
+            )
+            .with("value", raw)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn call(tenant: &str, provider: &str, session: &str, bytes: usize) -> Call {
+        let call = Call::start(
+            tenant,
+            provider,
+            Some(session.to_string()),
+            "post",
+            "conversation".into(),
+            bytes,
+            false,
+        );
+        call.add_response_bytes(bytes * 10);
+        call
+    }
+
+    #[test]
+    fn categories_leave_ids_out() {
+        let url = |raw: &str| reqwest::Url::parse(raw).unwrap();
+        let chat = url("https://chatgpt.com/backend-api/conversation/6f1c-22/stream");
+        assert_eq!(category(None, &chat), "conversation");
+        assert_eq!(category(Some(" Chat Send!"), &chat), "chatsend");
+        assert_eq!(category(Some("  "), &chat), "conversation");
+        let ids = url("https://claude.ai/api/5f2a9c01/v1");
+        assert_eq!(category(None, &ids), "other");
+    }
+
+    #[test]
+    fn totals_group_by_provider_and_account() {
+        let _home = TestHome::new();
+        call("acme", "chatgpt", "2026-10-01T09:00:00Z", 3).finish_with_status(200);
+        call("acme", "chatgpt", "2026-10-01T09:00:00Z", 5).finish_with_status(429);
+        call("acme", "chatgpt", "2026-10-12T08:00:00Z", 1).finish_with_status(200);
+        call("acme", "claude", "2026-10-02T10:00:00Z", 2)
+            .finish(None, StreamEndReason::ConnectFailed);
+        call("other", "claude", "2026-10-02T10:00:00Z", 7).finish_with_status(200);
+
+        let acme = entries("acme", &UsageRange::default()).unwrap();
+        assert_eq!(acme.len(), 4);
+        assert!(acme
+            .iter()
+            .all(|e| e.tenant_id == "acme" && e.method == "POST"));
+        assert_eq!(acme[1].outcome, StreamEndReason::RateLimited);
+
+        let by_provider = totals(&acme, UsageGrouping::Provider);
+        assert_eq!(
+            by_provider,
+            vec![
+                UsageTotals {
+                    provider_id: Some("chatgpt".into()),
+                    requests: 3,
+                    failed: 1,
+                    request_bytes: 9,
+                    response_bytes: 90,
+                    duration_ms: by_provider[0].duration_ms,
+                    ..Default::default()
+                },
+                UsageTotals {
+                    provider_id: Some("claude".into()),
+                    requests: 1,
+                    failed: 1,
+                    request_bytes: 2,
+                    response_bytes: 20,
+                    duration_ms: by_provider[1].duration_ms,
+                    ..Default::default()
+                },
+            ]
+        );
+        let by_account = totals(&acme, UsageGrouping::Account);
+        assert_eq!(by_account.len(), 3);
+        assert_eq!(
+            by_account[0].session.as_deref(),
+            Some("2026-10-01T09:00:00Z")
+        );
+        assert_eq!(by_account[0].requests, 2);
+        let by_day = totals(&acme, UsageGrouping::Day);
+        assert_eq!(by_day.len(), 1);
+        assert_eq!(by_day[0].requests, 4);
+
+        // Everything was recorded just now
+        let later = UsageRange {
+            start: Some((Utc::now() + chrono::TimeDelta::hours(1)).to_rfc3339()),
+            end: None,
+        };
+        assert!(entries("acme", &later).unwrap().is_empty());
+        assert!(entries("../acme", &UsageRange::default()).is_err());
+    }
+
+    #[test]
+    fn purge_drops_one_tenant_or_old_entries() {
+        let _home = TestHome::new();
+        call("acme", "chatgpt", "s", 1).finish_with_status(200);
+        call("other", "chatgpt", "s", 1).finish_with_status(200);
+
+        let report = purge(Some("acme"), None).unwrap();
+        assert_eq!((report.removed, report.kept), (1, 1));
+        assert!(entries("acme", &UsageRange::default()).unwrap().is_empty());
+
+        let yesterday = Utc::now() - chrono::TimeDelta::days(1);
+        assert_eq!(purge(None, Some(yesterday)).unwrap().removed, 0);
+        let soon = Utc::now() + chrono::TimeDelta::minutes(1);
+        assert_eq!(purge(None, Some(soon)).unwrap().removed, 1);
+        assert_eq!(purge(None, None).unwrap().kept, 0);
+    }
+}
@@ -0,0 +1,815 @@
+// Provider login support
+//
+// Built-in login configs for the session-based providers, optionally
+// overridden per field from `providers.json` in the config directory:
+//
+//   { "gemini": { "user_agent": "...", "window_size": { "width": 520, "height": 760 } } }
+//
+// The provider login window is an ordinary webview whose data directory is
+// the tenant-scoped session dir, so captured cookies never mix between tenants.
+// A monitor watches its navigations and page titles and emits to the window
+// that opened the login:
+//
+//   provider-login-succeeded  — a success indicator matched
+//   provider-login-challenge  — CAPTCHA / "unusual activity" interstitial
+//   provider-login-failed     — the provider rejected the sign-in
+//   provider-login-timeout    — no success within the timeout
+//
+// On success the window's cookies for the provider's allowed domains are
+// captured into the session payload (see `sessions`), where only the backend
+// can read them.
+//
+// A simulated `mock` provider for development and tests is also offered while
+// `developer.mock_provider` is on; see `mock_provider`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::session_profile::{self, ProfileSource, SessionProfile};
+use crate::sessions::{SessionPayload, StoredCookie};
+use crate::{
+    audit, http, i18n, integrity, mock_provider, platform, policy, provider_cache, provider_fetch,
+    sessions, settings, tenant_windows, vault,
+};
+
+/// Provider ids with built-in login support.
+pub const KNOWN_PROVIDERS: &[&str] = &["chatgpt", "claude", "gemini", "perplexity"];
+
+/// Google rejects sign-in from user agents it identifies as embedded webviews
+/// ("This browser or app may not be secure"); a desktop Firefox UA gets through.
+const DESKTOP_FIREFOX_UA: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+const DEFAULT_LOGIN_TIMEOUT_SECS: u64 = 300;
+
+/// Cloudflare's bot check, shown by most providers before their login page.
+const CLOUDFLARE_CHALLENGE: &[&str] = &[
+    "challenges.cloudflare.com",
+    "/cdn-cgi/challenge-platform",
+    "just a moment",
+];
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct WindowSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        Self {
+            width: 480.0,
+            height: 720.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProviderLoginConfig {
+    pub provider_id: String,
+    pub login_url: String,
+    pub success_indicators: Vec<String>,
+    /// Hosts (and their subdomains) whose cookies belong to this session and
+    /// that `provider_fetch` may call.
+    pub allowed_domains: Vec<String>,
+    /// URL or page-title fragments for CAPTCHA / verification interstitials.
+    pub challenge_indicators: Vec<String>,
+    /// URL or page-title fragments for pages where the login was rejected.
+    pub failure_indicators: Vec<String>,
+    pub timeout_secs: u64,
+    /// `None` keeps the platform webview's default user agent.
+    pub user_agent: Option<String>,
+    pub window_size: WindowSize,
+    /// Sent with backend requests that replay this provider's session. The
+    /// platform webviews don't allow arbitrary headers on navigation.
+    pub extra_headers: HashMap<String, String>,
+    /// Cookies that carry the login itself; their expiry is the session's.
+    pub auth_cookies: Vec<String>,
+    /// Authenticated page probed when the auth cookies carry no expiry.
+    pub health_check_url: Option<String>,
+    /// Where the signed-in account's profile is read after a capture.
+    pub profile: Option<ProfileSource>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CaptureReport {
+    pub tenant_id: String,
+    pub provider_id: String,
+    pub cookie_count: usize,
+    /// False when no keychain was available and the payload is plaintext.
+    pub encrypted: bool,
+    /// User agent the login ran with, which replays must reuse.
+    pub user_agent: Option<String>,
+    /// Auth cookies scoped too narrowly to reach the whole provider.
+    pub warnings: Vec<String>,
+    /// `None` when the provider has no profile or it couldn't be read.
+    pub profile: Option<SessionProfile>,
+}
+
+/// A providers.json entry. Every field is optional and replaces the built-in
+/// value when present.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct ProviderOverride {
+    login_url: Option<String>,
+    success_indicators: Option<Vec<String>>,
+    allowed_domains: Option<Vec<String>>,
+    challenge_indicators: Option<Vec<String>>,
+    failure_indicators: Option<Vec<String>>,
+    timeout_secs: Option<u64>,
+    user_agent: Option<String>,
+    window_size: Option<WindowSize>,
+    extra_headers: Option<HashMap<String, String>>,
+    auth_cookies: Option<Vec<String>>,
+    health_check_url: Option<String>,
+    profile: Option<ProfileSource>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Get the login config for a provider (URL, success indicators, and the
+/// webview profile to use), with user overrides applied. Fails with
+/// `PolicyDenied` when the tenant's policy doesn't allow the provider; from a
+/// tenant window, `tenant_id` defaults to that tenant.
+#[tauri::command]
+pub fn get_provider_login_config(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    provider_id: String,
+) -> CommandResult<ProviderLoginConfig> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id).ok();
+    policy::check_provider(tenant_id.as_deref(), &provider_id)?;
+    login_config(&provider_id)
+}
+
+/// Open the provider login window for a tenant and return its label.
+///
+/// The window uses the provider's configured user agent and size, and stores
+/// its webview data inside the tenant's session directory. The user agent is
+/// recorded in `session.json` so the adapter layer can replay with the same one.
+/// From a tenant window, `tenant_id` may be omitted and defaults to that tenant.
+#[tauri::command]
+pub async fn open_provider_login(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    provider_id: String,
+) -> CommandResult<String> {
+    platform::desktop_only("provider_login")?;
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    // The captured session can't be written while the vault is locked
+    vault::ensure_unlocked()?;
+    policy::check_provider(Some(&tenant_id), &provider_id)?;
+    let config = login_config(&provider_id)?;
+    let session_dir =
+        sessions::ensure_session_dir_at(&sessions::sessions_root()?, &tenant_id, &provider_id)?;
+    sessions::record_login_profile(
+        &session_dir,
+        config.user_agent.clone(),
+        config.extra_headers.clone(),
+    )?;
+
+    let url: tauri::Url = config
+        .login_url
+        .parse()
+        .map_err(|e| format!("Invalid login URL {}: {}", config.login_url, e))?;
+    let proxy = http::webview_proxy_url(&settings::load_settings(), &url);
+    let label = format!("login-{}-{}", tenant_id, provider_id);
+
+    let monitor = Arc::new(LoginMonitor {
+        app: app.clone(),
+        window_label: label.clone(),
+        opener: window.label().to_string(),
+        tenant_id,
+        config: config.clone(),
+        state: Mutex::new(LoginState::default()),
+    });
+
+    let nav_monitor = monitor.clone();
+    let title_monitor = monitor.clone();
+    let mut builder = WebviewWindowBuilder::new(&app, &label, WebviewUrl::External(url))
+        .title(i18n::text(
+            "window.provider_login",
+            &[("provider_id", &provider_id)],
+        ))
+        .data_directory(session_dir.join("webview"))
+        .data_store_identifier(tenant_windows::data_store_id(&label))
+        .on_navigation(move |url| {
+            nav_monitor.observe(MatchSource::Url, url.as_str());
+            true
+        })
+        .on_document_title_changed(move |_window, title| {
+            title_monitor.observe(MatchSource::Title, &title);
+        });
+
+    #[cfg(desktop)]
+    {
+        builder = builder.inner_size(config.window_size.width, config.window_size.height);
+    }
+    if let Some(ua) = &config.user_agent {
+        builder = builder.user_agent(ua);
+    }
+    if let Some(proxy) = proxy {
+        builder = builder.proxy_url(proxy);
+    }
+
+    builder.build().map_err(|e| e.to_string())?;
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        monitor.timeout();
+    });
+
+    Ok(label)
+}
+
+/// Capture the login window's cookies into the tenant's session payload.
+///
+/// Runs automatically when a login succeeds; exposed for flows where the
+/// success indicator doesn't fire (e.g. SSO redirects back to a custom page).
+#[tauri::command]
+pub async fn capture_provider_session(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    provider_id: String,
+) -> CommandResult<CaptureReport> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    // Reading cookies can deadlock on the main thread (WebView2)
+    tauri::async_runtime::spawn_blocking(move || {
+        capture_from_window(&app, &tenant_id, &provider_id)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn capture_from_window(
+    app: &AppHandle,
+    tenant_id: &str,
+    provider_id: &str,
+) -> CommandResult<CaptureReport> {
+    vault::ensure_unlocked()?;
+    policy::check_provider(Some(tenant_id), provider_id)?;
+    let config = login_config(provider_id)?;
+    let cookies = if mock_provider::is_mock(provider_id) {
+        mock_provider::cookies()
+    } else {
+        window_cookies(app, tenant_id, provider_id, &config)?
+    };
+    let warnings = scope_warnings(&config, &cookies);
+    for warning in &warnings {
+        log::warn!("[providers] {} / {}: {}", tenant_id, provider_id, warning);
+    }
+    let report = save_capture(tenant_id, provider_id, cookies)?;
+    Ok(CaptureReport {
+        warnings,
+        profile: session_profile::capture(tenant_id, provider_id),
+        ..report
+    })
+}
+
+/// Auth cookies whose path isn't `/`, or that wouldn't be sent to the
+/// provider's health check URL. Missing cookies aren't reported here.
+fn scope_warnings(config: &ProviderLoginConfig, cookies: &[StoredCookie]) -> Vec<String> {
+    let probe = config
+        .health_check_url
+        .as_deref()
+        .and_then(|u| reqwest::Url::parse(u).ok());
+    let mut warnings = Vec::new();
+    for cookie in cookies
+        .iter()
+        .filter(|c| config.auth_cookies.contains(&c.name))
+    {
+        if cookie.path != "/" {
+            warnings.push(format!(
+                "Auth cookie {} is limited to path {} on {}",
+                cookie.name, cookie.path, cookie.domain
+            ));
+        } else if let Some(url) = probe.as_ref() {
+            if !provider_fetch::cookie_matches(cookie, url) {
+                warnings.push(format!(
+                    "Auth cookie {} is scoped to {} and won't be sent to {}",
+                    cookie.name,
+                    cookie.domain,
+                    url.host_str().unwrap_or_default()
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+/// The login window's cookies on the provider's allowed domains.
+fn window_cookies(
+    app: &AppHandle,
+    tenant_id: &str,
+    provider_id: &str,
+    config: &ProviderLoginConfig,
+) -> CommandResult<Vec<StoredCookie>> {
+    let label = format!("login-{}-{}", tenant_id, provider_id);
+    let window = app.get_webview_window(&label).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("No login window open for {} / {}", tenant_id, provider_id),
+        )
+        .with("window_label", &label)
+    })?;
+
+    let cookies = window
+        .cookies()
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+        .into_iter()
+        .filter_map(|c| {
+            let domain = c.domain()?.to_string();
+            domain_allowed(&config.allowed_domains, &domain).then(|| StoredCookie {
+                name: c.name().to_string(),
+                value: c.value().to_string(),
+                domain,
+                expires_at: c.expires_datetime().map(|t| t.unix_timestamp()),
+                path: c.path().unwrap_or("/").to_string(),
+                secure: c.secure().unwrap_or(false),
+                http_only: c.http_only().unwrap_or(false),
+                same_site: provider_fetch::same_site(&c),
+                host_only: false,
+            })
+        })
+        .collect();
+    Ok(cookies)
+}
+
+/// Write captured cookies as the tenant's session payload.
+pub fn save_capture(
+    tenant_id: &str,
+    provider_id: &str,
+    cookies: Vec<StoredCookie>,
+) -> CommandResult<CaptureReport> {
+    let dir = sessions::ensure_session_dir_at(&sessions::sessions_root()?, tenant_id, provider_id)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let payload = SessionPayload {
+        cookies,
+        captured_at: now.clone(),
+        updated_at: now,
+    };
+    let encrypted = sessions::save_payload(&dir, &payload)?;
+    provider_cache::clear(&dir);
+    // The new login may be another account
+    sessions::record_profile(&dir, None)?;
+    let user_agent = sessions::read_meta(&dir).and_then(|m| m.user_agent);
+
+    audit::record(
+        "provider_session_captured",
+        Some(tenant_id),
+        serde_json::json!({
+            "provider_id": provider_id,
+            "cookie_count": payload.cookies.len(),
+            "encrypted": encrypted,
+        }),
+    );
+
+    Ok(CaptureReport {
+        tenant_id: tenant_id.to_string(),
+        provider_id: provider_id.to_string(),
+        cookie_count: payload.cookies.len(),
+        encrypted,
+        user_agent,
+        warnings: Vec::new(),
+        profile: None,
+    })
+}
+
+/// Whether `host` is one of `allowed` or a subdomain of one.
+pub fn domain_allowed(allowed: &[String], host: &str) -> bool {
+    let host = host.trim_start_matches('.').to_lowercase();
+    allowed.iter().any(|d| {
+        let d = d.trim_start_matches('.').to_lowercase();
+        host == d || host.ends_with(&format!(".{}", d))
+    })
+}
+
+// ─── Login Monitor ──────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "lowercase")]
+enum MatchSource {
+    Url,
+    Title,
+}
+
+#[derive(Serialize, Clone)]
+struct LoginEvent {
+    window_label: String,
+    tenant_id: String,
+    provider_id: String,
+    /// The indicator that matched (absent for timeouts).
+    indicator: Option<String>,
+    source: Option<MatchSource>,
+    /// The URL or page title that matched.
+    value: Option<String>,
+}
+
+/// What an observed URL or title means for the login, as an event to emit.
+#[derive(Debug, PartialEq, Eq)]
+enum LoginStep {
+    Succeeded(String),
+    Challenge(String),
+    Failed(String),
+}
+
+#[derive(Default)]
+struct LoginState {
+    /// Set once the login succeeded or timed out; later matches are ignored.
+    resolved: bool,
+    last_challenge: Option<String>,
+    /// The failure reported last, until the page moves on. A user who
+    /// mistypes can still sign in, so a failure doesn't resolve the login.
+    last_failure: Option<String>,
+}
+
+impl LoginState {
+    fn observe(
+        &mut self,
+        config: &ProviderLoginConfig,
+        source: &MatchSource,
+        value: &str,
+    ) -> Option<LoginStep> {
+        if self.resolved {
+            return None;
+        }
+
+        if let Some(indicator) = find_indicator(&config.failure_indicators, value) {
+            // Error pages re-render too; report each failure once
+            if self.last_failure.as_deref() == Some(indicator.as_str()) {
+                return None;
+            }
+            self.last_failure = Some(indicator.clone());
+            return Some(LoginStep::Failed(indicator));
+        }
+        self.last_failure = None;
+
+        if let Some(indicator) = find_indicator(&config.challenge_indicators, value) {
+            // Challenge pages re-render while the user works through them
+            if self.last_challenge.as_deref() == Some(indicator.as_str()) {
+                return None;
+            }
+            self.last_challenge = Some(indicator.clone());
+            return Some(LoginStep::Challenge(indicator));
+        }
+        // The login page itself usually lives on the success domain
+        if !matches!(source, MatchSource::Url) || value.starts_with(&config.login_url) {
+            return None;
+        }
+        let indicator = find_indicator(&config.success_indicators, value)?;
+        self.resolved = true;
+        Some(LoginStep::Succeeded(indicator))
+    }
+
+    /// Whether the timeout should be reported; it resolves the login.
+    fn time_out(&mut self) -> bool {
+        !std::mem::replace(&mut self.resolved, true)
+    }
+}
+
+struct LoginMonitor {
+    app: AppHandle,
+    window_label: String,
+    /// Label of the window that called `open_provider_login`.
+    opener: String,
+    tenant_id: String,
+    config: ProviderLoginConfig,
+    state: Mutex<LoginState>,
+}
+
+impl LoginMonitor {
+    fn observe(&self, source: MatchSource, value: &str) {
+        let mut state = self.state.lock().unwrap();
+        let (event, indicator) = match state.observe(&self.config, &source, value) {
+            Some(LoginStep::Succeeded(indicator)) => {
+                self.capture_in_background();
+                ("provider-login-succeeded", indicator)
+            }
+            Some(LoginStep::Challenge(indicator)) => ("provider-login-challenge", indicator),
+            Some(LoginStep::Failed(indicator)) => ("provider-login-failed", indicator),
+            None => return,
+        };
+        self.emit(event, Some(indicator), Some(source), value);
+    }
+
+    fn capture_in_background(&self) {
+        let app = self.app.clone();
+        let opener = self.opener.clone();
+        let tenant_id = self.tenant_id.clone();
+        let provider_id = self.config.provider_id.clone();
+        std::thread::spawn(
+            move || match capture_from_window(&app, &tenant_id, &provider_id) {
+                Ok(report) => {
+                    let _ = app.emit_to(opener.as_str(), "provider-session-captured", report);
+                }
+                Err(e) => log::warn!(
+                    "[providers] session capture for {} / {} failed: {}",
+                    tenant_id,
+                    provider_id,
+                    e
+                ),
+            },
+        );
+    }
+
+    /// Fired once the timeout elapses. The window stays open; a login
+    /// finished after this is saved with `capture_provider_session`.
+    fn timeout(&self) {
+        if self.state.lock().unwrap().time_out() {
+            self.emit("provider-login-timeout", None, None, "");
+        }
+    }
+
+    fn emit(
+        &self,
+        event: &str,
+        indicator: Option<String>,
+        source: Option<MatchSource>,
+        value: &str,
+    ) {
+        let payload = LoginEvent {
+            window_label: self.window_label.clone(),
+            tenant_id: self.tenant_id.clone(),
+            provider_id: self.config.provider_id.clone(),
+            indicator,
+            source,
+            value: (!value.is_empty()).then(|| value.to_string()),
+        };
+        if let Err(e) = self.app.emit_to(self.opener.as_str(), event, payload) {
+            log::warn!("[providers] failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// Case-insensitive substring match against a URL or page title.
+fn find_indicator(indicators: &[String], value: &str) -> Option<String> {
+    let value = value.to_lowercase();
+    indicators
+        .iter()
+        .find(|i| value.contains(&i.to_lowercase()))
+        .cloned()
+}
+
+// ─── Config Resolution ──────────────────────────────────────────────────────
+
+/// The provider's login config with user overrides applied, regardless of
+/// policy.
+pub fn login_config(provider_id: &str) -> CommandResult<ProviderLoginConfig> {
+    let mut config = builtin_config(provider_id)?;
+    if let Some(over) = load_overrides().remove(provider_id) {
+        apply_override(&mut config, over);
+    }
+    Ok(config)
+}
+
+fn builtin_config(provider_id: &str) -> Result<ProviderLoginConfig, String> {
+    match provider_id {
+        "chatgpt" => Ok(ProviderLoginConfig {
+            provider_id: "chatgpt".into(),
+            login_url: "https://chatgpt.com/auth/login".into(),
+            success_indicators: vec!["chatgpt.com".into(), "chat.openai.com".into()],
+            allowed_domains: vec!["chatgpt.com".into(), "openai.com".into()],
+            challenge_indicators: strings(CLOUDFLARE_CHALLENGE),
+            failure_indicators: vec!["auth.openai.com/error".into(), "access denied".into()],
+            timeout_secs: DEFAULT_LOGIN_TIMEOUT_SECS,
+            user_agent: None,
+            window_size: WindowSize::default(),
+            extra_headers: HashMap::new(),
+            auth_cookies: vec!["__Secure-next-auth.session-token".into()],
+            health_check_url: Some("https://chatgpt.com/api/auth/session".into()),
+            profile: Some(ProfileSource {
+                url: "https://chatgpt.com/api/auth/session".into(),
+                account_id: Some("/user/id".into()),
+                email: Some("/user/email".into()),
+                display_name: Some("/user/name".into()),
+                plan: Some("/account/planType".into()),
+                avatar_url: Some("/user/image".into()),
+                ..Default::default()
+            }),
+        }),
+        "claude" => Ok(ProviderLoginConfig {
+            provider_id: "claude".into(),
+            login_url: "https://claude.ai/login".into(),
+            success_indicators: vec!["claude.ai/new".into(), "claude.ai/chat".into()],
+            allowed_domains: vec!["claude.ai".into()],
+            challenge_indicators: strings(CLOUDFLARE_CHALLENGE),
+            failure_indicators: vec![
+                "claude.ai/login?error".into(),
+                "app-unavailable-in-region".into(),
+            ],
+            timeout_secs: DEFAULT_LOGIN_TIMEOUT_SECS,
+            user_agent: None,
+            window_size: WindowSize::default(),
+            extra_headers: HashMap::new(),
+            auth_cookies: vec!["sessionKey".into()],
+            health_check_url: Some("https://claude.ai/api/organizations".into()),
+            profile: Some(ProfileSource {
+                url: "https://claude.ai/api/account".into(),
+                account_id: Some("/uuid".into()),
+                email: Some("/email_address".into()),
+                display_name: Some("/display_name".into()),
+                plan: Some("/memberships/0/organization/rate_limit_tier".into()),
+                ..Default::default()
+            }),
+        }),
+        "gemini" => Ok(ProviderLoginConfig {
+            provider_id: "gemini".into(),
+            login_url: "https://accounts.google.com".into(),
+            success_indicators: vec!["gemini.google.com".into()],
+            allowed_domains: vec!["google.com".into()],
+            challenge_indicators: vec![
+                "accounts.google.com/v3/signin/challenge".into(),
+                "google.com/sorry/".into(),
+                "recaptcha".into(),
+            ],
+            failure_indicators: vec![
+                "/signin/rejected".into(),
+                "couldn't sign you in".into(),
+                "this browser or app may not be secure".into(),
+            ],
+            timeout_secs: DEFAULT_LOGIN_TIMEOUT_SECS,
+            user_agent: Some(DESKTOP_FIREFOX_UA.into()),
+            // Google's account chooser needs more room than the default
+            window_size: WindowSize {
+                width: 520.0,
+                height: 760.0,
+            },
+            extra_headers: HashMap::new(),
+            auth_cookies: vec!["__Secure-1PSID".into(), "__Secure-3PSID".into()],
+            health_check_url: Some("https://gemini.google.com/app".into()),
+            // Gemini serves no JSON profile to read
+            profile: None,
+        }),
+        "perplexity" => Ok(ProviderLoginConfig {
+            provider_id: "perplexity".into(),
+            login_url: "https://www.perplexity.ai/signin".into(),
+            success_indicators: vec!["perplexity.ai".into()],
+            allowed_domains: vec!["perplexity.ai".into()],
+            challenge_indicators: strings(CLOUDFLARE_CHALLENGE),
+            failure_indicators: vec!["perplexity.ai/auth/error".into()],
+            timeout_secs: DEFAULT_LOGIN_TIMEOUT_SECS,
+            user_agent: None,
+            window_size: WindowSize::default(),
+            extra_headers: HashMap::new(),
+            auth_cookies: vec!["__Secure-next-auth.session-token".into()],
+            health_check_url: Some("https://www.perplexity.ai/api/auth/session".into()),
+            profile: Some(ProfileSource {
+                url: "https://www.perplexity.ai/api/auth/session".into(),
+                account_id: Some("/user/id".into()),
+                email: Some("/user/email".into()),
+                handle: Some("/user/username".into()),
+                display_name: Some("/user/name".into()),
+                plan: Some("/user/subscription_status".into()),
+                avatar_url: Some("/user/image".into()),
+            }),
+        }),
+        mock_provider::PROVIDER_ID if mock_provider::enabled() => Ok(mock_provider::login_config()),
+        _ => Err(format!("Unknown provider: {}", provider_id)),
+    }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn overrides_path() -> CommandResult<PathBuf> {
+    Ok(home::config_dir()?.join("providers.json"))
+}
+
+/// Read providers.json. A missing file means no overrides; a malformed one is
+/// logged and ignored so a typo can't lock users out of every provider.
+fn load_overrides() -> HashMap<String, ProviderOverride> {
+    overrides_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn apply_override(config: &mut ProviderLoginConfig, over: ProviderOverride) {
+    if let Some(url) = over.login_url {
+        config.login_url = url;
+    }
+    if let Some(indicators) = over.success_indicators {
+        config.success_indicators = indicators;
+    }
+    if let Some(domains) = over.allowed_domains {
+        config.allowed_domains = domains;
+    }
+    if let Some(indicators) = over.challenge_indicators {
+        config.challenge_indicators = indicators;
+    }
+    if let Some(indicators) = over.failure_indicators {
+        config.failure_indicators = indicators;
+    }
+    if let Some(secs) = over.timeout_secs {
+        config.timeout_secs = secs;
+    }
+    if let Some(ua) = over.user_agent {
+        // An empty string in providers.json means "use the webview default"
+        config.user_agent = (!ua.is_empty()).then_some(ua);
+    }
+    if let Some(size) = over.window_size {
+        config.window_size = size;
+    }
+    if let Some(headers) = over.extra_headers {
+        config.extra_headers = headers;
+    }
+    if let Some(names) = over.auth_cookies {
+        config.auth_cookies = names;
+    }
+    if let Some(url) = over.health_check_url {
+        config.health_check_url = (!url.is_empty()).then_some(url);
+    }
+    if let Some(profile) = over.profile {
+        config.profile = (!profile.url.is_empty()).then_some(profile);
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claude() -> ProviderLoginConfig {
+        builtin_config("claude").unwrap()
+    }
+
+    fn url(state: &mut LoginState, value: &str) -> Option<LoginStep> {
+        state.observe(&claude(), &MatchSource::Url, value)
+    }
+
+    #[test]
+    fn indicators_match_case_insensitively_anywhere() {
+        let indicators = strings(&["claude.ai/new", "Just a moment"]);
+        assert_eq!(
+            find_indicator(&indicators, "https://CLAUDE.ai/new?x=1").as_deref(),
+            Some("claude.ai/new")
+        );
+        assert_eq!(
+            find_indicator(&indicators, "just a moment...").as_deref(),
+            Some("Just a moment")
+        );
+        assert_eq!(find_indicator(&indicators, "https://claude.ai/login"), None);
+        assert_eq!(find_indicator(&[], "anything"), None);
+    }
+
+    #[test]
+    fn a_failed_attempt_can_still_be_followed_by_a_success() {
+        let mut state = LoginState::default();
+        assert_eq!(url(&mut state, "https://claude.ai/login"), None);
+        let failed = Some(LoginStep::Failed("claude.ai/login?error".into()));
+        assert_eq!(url(&mut state, "https://claude.ai/login?error=1"), failed);
+        // The error page re-rendering isn't a second failure
+        assert_eq!(url(&mut state, "https://claude.ai/login?error=1"), None);
+        assert_eq!(url(&mut state, "https://claude.ai/login"), None);
+        assert_eq!(url(&mut state, "https://claude.ai/login?error=2"), failed);
+
+        assert_eq!(
+            url(&mut state, "https://claude.ai/new"),
+            Some(LoginStep::Succeeded("claude.ai/new".into()))
+        );
+        assert_eq!(url(&mut state, "https://claude.ai/login?error=3"), None);
+        assert!(!state.time_out());
+    }
+
+    #[test]
+    fn a_challenge_is_reported_again_only_when_it_changes() {
+        let mut state = LoginState::default();
+        let title = |state: &mut LoginState| {
+            state.observe(&claude(), &MatchSource::Title, "Just a moment...")
+        };
+        assert_eq!(
+            title(&mut state),
+            Some(LoginStep::Challenge("just a moment".into()))
+        );
+        assert_eq!(title(&mut state), None);
+        assert_eq!(
+            url(&mut state, "https://challenges.cloudflare.com/turnstile"),
+            Some(LoginStep::Challenge("challenges.cloudflare.com".into()))
+        );
+        assert!(title(&mut state).is_some());
+        // Success only counts from a navigation
+        let success_title = state.observe(&claude(), &MatchSource::Title, "claude.ai/new");
+        assert_eq!(success_title, None);
+    }
+
+    #[test]
+    fn the_timeout_fires_once_and_ends_the_login() {
+        let mut state = LoginState::default();
+        assert!(state.time_out());
+        assert!(!state.time_out());
+        assert_eq!(url(&mut state, "https://claude.ai/new"), None);
+    }
+}
@@ -0,0 +1,652 @@
+// Quarantine
+//
+// Files from outside the machine (WhatsApp media, orchestrator downloads)
+// pass through `quarantine/` in the data directory before they reach a store.
+// Each one is checked there:
+// - its content signature against its extension, so an archive or an image
+//   named `.pdf` stands out;
+// - for executable content, an executable extension, or the executable bit,
+//   which is cleared either way;
+// - by the scanner in `settings.quarantine.scanner`, when one is set. It runs
+//   with `args`, `{path}` replaced by the file (or the path appended when no
+//   argument has it). Exit code 0 is clean and 1 is infected. Any other code,
+//   a scanner that won't start, or one still running after `timeout_secs`
+//   leaves the file unscanned, and unscanned files stay held.
+// A clean file moves straight on to its destination. Anything else is held
+// and announced with `file-quarantined`. `list_quarantined` shows what's
+// held. `release_quarantined` moves a file on anyway (never an infected one),
+// finishing the import it came from, and `discard_quarantined` deletes it.
+//
+// The verdict travels with the file: downloads record it in the artifact
+// index metadata as `scan`, and `whatsapp-media-imported` carries it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::artifact_download::{self, ConflictPolicy};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, dry_run, home, integrity, settings, whatsapp_media};
+
+const QUARANTINE_DIR: &str = "quarantine";
+const INDEX_FILE: &str = "index.json";
+/// Enough for every signature below.
+const SNIFF_BYTES: usize = 16;
+const STAGING_SUFFIX: &str = ".agentvbx-release";
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Leading bytes, what they are, and the extensions that fit them.
+const SIGNATURES: &[(&[u8], &str, &[&str])] = &[
+    (b"%PDF", "PDF document", &["pdf"]),
+    (b"\x89PNG", "PNG image", &["png"]),
+    (b"\xff\xd8\xff", "JPEG image", &["jpg", "jpeg"]),
+    (b"GIF8", "GIF image", &["gif"]),
+    (b"OggS", "Ogg audio", &["ogg", "oga", "opus"]),
+    (b"ID3", "MP3 audio", &["mp3"]),
+    (
+        b"PK\x03\x04",
+        "ZIP archive",
+        &["zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub"],
+    ),
+];
+const EXECUTABLE_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"MZ", "Windows executable"),
+    (b"\x7fELF", "Linux executable"),
+    (b"\xcf\xfa\xed\xfe", "macOS executable"),
+    (b"\xce\xfa\xed\xfe", "macOS executable"),
+    (b"\xca\xfe\xba\xbe", "macOS executable"),
+    (b"#!", "script"),
+];
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "app", "bat", "cmd", "com", "command", "cpl", "dll", "dmg", "exe", "hta", "jar", "js", "jse",
+    "lnk", "msi", "pkg", "ps1", "scr", "sh", "vbe", "vbs", "wsf",
+];
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+/// Where a file came from, and so how its import finishes.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Source {
+    Whatsapp {
+        tenant_id: String,
+        message_id: String,
+    },
+    Artifact {
+        tenant_id: String,
+        artifact_id: String,
+        version: Option<String>,
+    },
+}
+
+impl Source {
+    fn tenant_id(&self) -> &str {
+        match self {
+            Self::Whatsapp { tenant_id, .. } | Self::Artifact { tenant_id, .. } => tenant_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanOutcome {
+    Clean,
+    /// A heuristic matched.
+    Suspicious,
+    /// The scanner said so.
+    Infected,
+    /// The scanner failed, or timed out.
+    Unscanned,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ScanVerdict {
+    pub outcome: ScanOutcome,
+    /// What the heuristics and the scanner found.
+    pub reasons: Vec<String>,
+    /// The scanner's program, when one ran.
+    pub scanner: Option<String>,
+    pub scanned_at: String,
+}
+
+/// A held file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuarantinedFile {
+    pub id: String,
+    pub source: Source,
+    pub name: String,
+    /// Where it goes once released.
+    pub destination: String,
+    pub on_conflict: ConflictPolicy,
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub quarantined_at: String,
+    pub verdict: ScanVerdict,
+}
+
+impl QuarantinedFile {
+    /// Where the file is while it's held.
+    pub fn held_path(&self) -> CommandResult<PathBuf> {
+        held_path(&self.id, &self.name)
+    }
+}
+
+/// A file on its way in.
+pub struct Arrival {
+    pub source: Source,
+    pub destination: PathBuf,
+    pub on_conflict: ConflictPolicy,
+    pub sha256: String,
+}
+
+pub enum Admitted {
+    /// Clean, and now at `path`.
+    Placed {
+        path: PathBuf,
+        verdict: ScanVerdict,
+    },
+    Held(QuarantinedFile),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReleasedFile {
+    pub path: String,
+    pub file: QuarantinedFile,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Held files, oldest first.
+#[tauri::command]
+pub fn list_quarantined() -> Vec<QuarantinedFile> {
+    load_index()
+}
+
+/// Move a held file on to its destination and finish its import. Infected
+/// files can only be discarded.
+#[tauri::command]
+pub fn release_quarantined(
+    window: tauri::WebviewWindow,
+    id: String,
+) -> CommandResult<ReleasedFile> {
+    let _guard = LOCK.lock().unwrap();
+    let mut index = load_index();
+    let file = find(&index, &id)?.clone();
+    dry_run::check_writable(
+        window.label(),
+        Some(file.source.tenant_id()),
+        "release_quarantined",
+    )?;
+    if file.verdict.outcome == ScanOutcome::Infected {
+        return Err(CommandError::new(
+            ErrorCode::PolicyDenied,
+            format!("{} was found infected and can only be discarded", file.name),
+        )
+        .with("id", &id)
+        .with("reasons", &file.verdict.reasons));
+    }
+
+    let path = place(&file.held_path()?, &file)?;
+    index.retain(|f| f.id != id);
+    save_index(&index)?;
+    remove_held(&id);
+    finish_import(&file, &path)?;
+    audit::record(
+        "release_quarantined",
+        Some(file.source.tenant_id()),
+        serde_json::json!({ "id": id, "path": path, "outcome": file.verdict.outcome }),
+    );
+    log::info!("[quarantine] released {} to {}", id, path.display());
+    Ok(ReleasedFile {
+        path: path.to_string_lossy().to_string(),
+        file,
+    })
+}
+
+/// Delete a held file.
+#[tauri::command]
+pub fn discard_quarantined(id: String) -> CommandResult<QuarantinedFile> {
+    let _guard = LOCK.lock().unwrap();
+    let mut index = load_index();
+    let file = find(&index, &id)?.clone();
+    index.retain(|f| f.id != id);
+    save_index(&index)?;
+    remove_held(&id);
+    audit::record(
+        "discard_quarantined",
+        Some(file.source.tenant_id()),
+        serde_json::json!({ "id": id, "name": file.name, "outcome": file.verdict.outcome }),
+    );
+    log::info!("[quarantine] discarded {}", id);
+    Ok(file)
+}
+
+// ─── Admission ──────────────────────────────────────────────────────────────
+
+/// Quarantine `bytes` as `name`, check them, and place them if clean.
+pub fn admit_bytes(bytes: &[u8], name: &str, arrival: Arrival) -> CommandResult<Admitted> {
+    let id = new_id(&arrival.sha256);
+    let held = held_path(&id, name)?;
+    fs::create_dir_all(held.parent().unwrap_or(Path::new("")))?;
+    fs::write(&held, bytes)?;
+    admit(id.clone(), held, name, arrival).inspect_err(|_| remove_held(&id))
+}
+
+/// `admit_bytes` for a file already on disk, which is moved.
+pub fn admit_file(file: &Path, name: &str, arrival: Arrival) -> CommandResult<Admitted> {
+    let id = new_id(&arrival.sha256);
+    let held = held_path(&id, name)?;
+    fs::create_dir_all(held.parent().unwrap_or(Path::new("")))?;
+    move_file(file, &held)?;
+    admit(id.clone(), held.clone(), name, arrival).inspect_err(|_| {
+        // Back where the caller had it, e.g. a partial kept for a retry
+        let _ = move_file(&held, file);
+        remove_held(&id);
+    })
+}
+
+fn admit(id: String, held: PathBuf, name: &str, arrival: Arrival) -> CommandResult<Admitted> {
+    let verdict = scan(
+        &held,
+        name,
+        settings::load_settings().quarantine.scanner.as_ref(),
+    );
+    let file = QuarantinedFile {
+        id,
+        source: arrival.source,
+        name: name.to_string(),
+        destination: arrival.destination.to_string_lossy().to_string(),
+        on_conflict: arrival.on_conflict,
+        sha256: arrival.sha256,
+        size_bytes: fs::metadata(&held)?.len(),
+        quarantined_at: chrono::Utc::now().to_rfc3339(),
+        verdict,
+    };
+    if file.verdict.outcome == ScanOutcome::Clean {
+        let path = place(&held, &file)?;
+        remove_held(&file.id);
+        return Ok(Admitted::Placed {
+            path,
+            verdict: file.verdict,
+        });
+    }
+
+    let _guard = LOCK.lock().unwrap();
+    let mut index = load_index();
+    index.push(file.clone());
+    save_index(&index)?;
+    log::warn!(
+        "[quarantine] holding {} as {} ({:?}: {})",
+        file.name,
+        file.id,
+        file.verdict.outcome,
+        file.verdict.reasons.join("; ")
+    );
+    Ok(Admitted::Held(file))
+}
+
+/// Emit `file-quarantined` for a held file.
+pub fn announce(app: &AppHandle, file: &QuarantinedFile) {
+    let _ = app.emit("file-quarantined", file.clone());
+}
+
+/// The error for a command whose file was held.
+pub fn held_error(file: &QuarantinedFile) -> CommandError {
+    CommandError::new(
+        ErrorCode::Quarantined,
+        format!("{} is held in quarantine", file.name),
+    )
+    .with("id", &file.id)
+    .with("name", &file.name)
+    .with("outcome", file.verdict.outcome)
+    .with("reasons", &file.verdict.reasons)
+}
+
+/// Move a held file to its destination, staging it next to the destination
+/// first so the last step is a rename on that volume.
+fn place(held: &Path, file: &QuarantinedFile) -> CommandResult<PathBuf> {
+    let destination = Path::new(&file.destination);
+    if let Some(folder) = destination.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    let staged = destination.with_file_name(format!(".{}{}", file.id, STAGING_SUFFIX));
+    move_file(held, &staged)?;
+    artifact_download::place(&staged, destination, file.on_conflict).inspect_err(|_| {
+        if move_file(&staged, held).is_err() {
+            let _ = fs::remove_file(&staged);
+        }
+    })
+}
+
+/// The rest of the import a released file came from.
+fn finish_import(file: &QuarantinedFile, path: &Path) -> CommandResult<()> {
+    match &file.source {
+        Source::Whatsapp { tenant_id, .. } => {
+            whatsapp_media::record_import(tenant_id, &file.sha256, path)?;
+        }
+        Source::Artifact {
+            tenant_id,
+            artifact_id,
+            version,
+        } => {
+            artifact_download::record_download(
+                tenant_id,
+                artifact_id,
+                version.clone(),
+                path,
+                &file.sha256,
+                file.size_bytes,
+                &file.verdict,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn move_file(from: &Path, to: &Path) -> CommandResult<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Another volume
+    fs::copy(from, to)?;
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+// ─── Scanning ───────────────────────────────────────────────────────────────
+
+pub fn scan(path: &Path, name: &str, scanner: Option<&settings::ScannerSettings>) -> ScanVerdict {
+    let mut reasons = inspect(path, name);
+    let mut outcome = match reasons.is_empty() {
+        true => ScanOutcome::Clean,
+        false => ScanOutcome::Suspicious,
+    };
+    if let Some(scanner) = scanner {
+        let (scanned, reason) = run_scanner(scanner, path);
+        reasons.extend(reason);
+        outcome = match scanned {
+            ScanOutcome::Clean => outcome,
+            other => other,
+        };
+    }
+    ScanVerdict {
+        outcome,
+        reasons,
+        scanner: scanner.map(|s| s.program.clone()),
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// What the heuristics find; clears the executable bit.
+fn inspect(path: &Path, name: &str) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if EXECUTABLE_EXTENSIONS.contains(&extension.as_str()) {
+        reasons.push(format!("`.{}` files can run programs", extension));
+    }
+
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(SNIFF_BYTES as u64).read_to_end(&mut head);
+    }
+    if let Some((_, kind)) = EXECUTABLE_SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+    {
+        reasons.push(format!("the content is a {}", kind));
+    } else if let Some((_, kind, extensions)) = SIGNATURES
+        .iter()
+        .find(|(magic, _, _)| head.starts_with(magic))
+    {
+        if !extensions.contains(&extension.as_str()) {
+            reasons.push(match extension.is_empty() {
+                true => format!("the content is a {} but the name has no extension", kind),
+                false => format!(
+                    "the content is a {} but the name ends in .{}",
+                    kind, extension
+                ),
+            });
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            let mode = metadata.permissions().mode();
+            if mode & 0o111 != 0 {
+                reasons.push("the file was marked executable".into());
+                let _ = fs::set_permissions(path, fs::Permissions::from_mode(mode & !0o111));
+            }
+        }
+    }
+    reasons
+}
+
+/// Run the configured scanner on `path`: its outcome, and why if not clean.
+fn run_scanner(scanner: &settings::ScannerSettings, path: &Path) -> (ScanOutcome, Option<String>) {
+    let target = path.to_string_lossy();
+    let mut args: Vec<String> = scanner
+        .args
+        .iter()
+        .map(|a| a.replace("{path}", &target))
+        .collect();
+    if !scanner.args.iter().any(|a| a.contains("{path}")) {
+        args.push(target.to_string());
+    }
+    let unscanned = |why: String| (ScanOutcome::Unscanned, Some(why));
+
+    let mut child = match Command::new(&scanner.program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return unscanned(format!("the scanner didn't start: {}", e)),
+    };
+    let deadline = Instant::now() + Duration::from_secs(scanner.timeout_secs);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return unscanned(format!(
+                    "the scanner ran past {} seconds",
+                    scanner.timeout_secs
+                ));
+            }
+            Err(e) => return unscanned(format!("the scanner failed: {}", e)),
+        }
+    };
+    match status.code() {
+        Some(0) => (ScanOutcome::Clean, None),
+        Some(1) => (
+            ScanOutcome::Infected,
+            Some("the scanner found a threat".into()),
+        ),
+        Some(code) => unscanned(format!("the scanner exited with code {}", code)),
+        None => unscanned("the scanner was killed".into()),
+    }
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn quarantine_dir() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join(QUARANTINE_DIR))
+}
+
+fn new_id(sha256: &str) -> String {
+    format!(
+        "q-{}-{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%3f"),
+        &sha256[..sha256.len().min(8)]
+    )
+}
+
+fn held_path(id: &str, name: &str) -> CommandResult<PathBuf> {
+    Ok(quarantine_dir()?.join(id).join(name))
+}
+
+fn remove_held(id: &str) {
+    if let Ok(dir) = quarantine_dir() {
+        let _ = fs::remove_dir_all(dir.join(id));
+    }
+}
+
+fn find<'a>(index: &'a [QuarantinedFile], id: &str) -> CommandResult<&'a QuarantinedFile> {
+    index.iter().find(|f| f.id == id).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("Nothing in quarantine as {}", id),
+        )
+        .with("id", id)
+    })
+}
+
+fn load_index() -> Vec<QuarantinedFile> {
+    quarantine_dir()
+        .ok()
+        .and_then(|dir| integrity::load_json(&dir.join(INDEX_FILE), None))
+        .unwrap_or_default()
+}
+
+fn save_index(index: &[QuarantinedFile]) -> CommandResult<()> {
+    integrity::persist_json(&quarantine_dir()?.join(INDEX_FILE), index, None)
+}
+
+impl integrity::Validate for [QuarantinedFile] {
+    fn validate(&self) -> Result<(), String> {
+        match self.iter().find(|f| f.id.is_empty() || f.name.is_empty()) {
+            Some(file) => Err(format!("quarantined file {:?} has no id or name", file.id)),
+            None => Ok(()),
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn arrival(home: &TestHome, name: &str) -> Arrival {
+        Arrival {
+            source: Source::Whatsapp {
+                tenant_id: "acme".into(),
+                message_id: "m1".into(),
+            },
+            destination: home.join(&format!("Reports/{}", name)),
+            on_conflict: ConflictPolicy::KeepBoth,
+            sha256: "0123456789abcdef".into(),
+        }
+    }
+
+    #[test]
+    fn clean_files_go_straight_through() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let admitted = admit_bytes(
+            b"%PDF-1.7 ...",
+            "invoice.pdf",
+            arrival(&home, "invoice.pdf"),
+        );
+        let Ok(Admitted::Placed { path, verdict }) = admitted else {
+            panic!("not placed");
+        };
+        assert_eq!(path, home.join("Reports/invoice.pdf"));
+        assert_eq!(verdict.outcome, ScanOutcome::Clean);
+        assert!(list_quarantined().is_empty());
+    }
+
+    #[test]
+    fn mismatches_and_executables_are_held() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let name = "photo.jpg";
+        let Ok(Admitted::Held(held)) = admit_bytes(b"PK\x03\x04rest", name, arrival(&home, name))
+        else {
+            panic!("not held");
+        };
+        assert_eq!(held.verdict.outcome, ScanOutcome::Suspicious);
+        assert!(held.verdict.reasons[0].contains("ZIP archive"));
+        assert!(!home.join("Reports/photo.jpg").exists());
+
+        let name = "report.pdf";
+        let Ok(Admitted::Held(exe)) = admit_bytes(b"MZ\x90\x00", name, arrival(&home, name)) else {
+            panic!("not held");
+        };
+        assert!(exe.verdict.reasons[0].contains("Windows executable"));
+        let name = "setup.exe";
+        let Ok(Admitted::Held(_)) = admit_bytes(b"hello", name, arrival(&home, name)) else {
+            panic!("not held");
+        };
+        assert_eq!(list_quarantined().len(), 3);
+
+        discard_quarantined(exe.id.clone()).unwrap();
+        assert_eq!(list_quarantined().len(), 2);
+        assert!(!held_path(&exe.id, "report.pdf").unwrap().exists());
+        assert_eq!(
+            discard_quarantined(exe.id).unwrap_err().code,
+            ErrorCode::NotFound
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scanner_exit_codes_and_timeouts() {
+        let home = TestHome::new();
+        let file = home.write("sample.txt", "hello");
+        let scanner = |script: &str, timeout_secs: u64| settings::ScannerSettings {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into(), "scan".into(), "{path}".into()],
+            timeout_secs,
+        };
+        let outcome = |s: &settings::ScannerSettings| scan(&file, "sample.txt", Some(s)).outcome;
+
+        assert_eq!(outcome(&scanner("test -f \"$1\"", 5)), ScanOutcome::Clean);
+        assert_eq!(outcome(&scanner("exit 1", 5)), ScanOutcome::Infected);
+        assert_eq!(outcome(&scanner("exit 2", 5)), ScanOutcome::Unscanned);
+
+        // A scanner that hangs fails closed
+        let started = Instant::now();
+        let verdict = scan(&file, "sample.txt", Some(&scanner("sleep 30", 1)));
+        assert_eq!(verdict.outcome, ScanOutcome::Unscanned);
+        assert!(verdict.reasons[0].contains("ran past 1 seconds"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let missing = settings::ScannerSettings {
+            program: "/nonexistent/scanner".into(),
+            args: Vec::new(),
+            timeout_secs: 5,
+        };
+        assert_eq!(outcome(&missing), ScanOutcome::Unscanned);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_executable_bit_is_flagged_and_cleared() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = TestHome::new();
+        let file = home.write("tool.txt", "plain text");
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+        let verdict = scan(&file, "tool.txt", None);
+        assert_eq!(verdict.outcome, ScanOutcome::Suspicious);
+        assert_eq!(fs::metadata(&file).unwrap().permissions().mode() & 0o111, 0);
+    }
+}
@@ -0,0 +1,205 @@
+// Quick capture
+//
+// A global shortcut pops a small always-on-top window for jotting a note;
+// submitting appends it to the vault's inbox note and closes the window.
+// Within a workspace (see `workspaces`), the vault is the workspace's. The
+// window is created on demand and doesn't depend on the main window, so it
+// works while the app is hidden. Global shortcuts are desktop-only.
+
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, dry_run, notes, settings, workspaces};
+
+const WINDOW_LABEL: &str = "quick-capture";
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Register (or replace) the quick capture shortcut, e.g. `CmdOrCtrl+Shift+N`,
+/// and remember it for future launches.
+#[tauri::command]
+pub fn register_quick_capture_shortcut(app: AppHandle, accelerator: String) -> CommandResult<()> {
+    let mut settings = settings::load_settings();
+    register(
+        &app,
+        &accelerator,
+        settings.quick_capture.shortcut.as_deref(),
+    )?;
+    settings.quick_capture.shortcut = Some(accelerator);
+    settings::save_settings(&settings)?;
+    Ok(())
+}
+
+/// Append the captured text to the configured inbox note in `vault_path`,
+/// or in the vault of the tenant's `workspace_id`, and close the capture
+/// window.
+#[tauri::command]
+pub fn submit_quick_capture(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: String,
+    vault_path: Option<String>,
+    text: String,
+    workspace_id: Option<String>,
+) -> CommandResult<String> {
+    dry_run::check_writable(window.label(), Some(&tenant_id), "submit_quick_capture")?;
+    let vault_path = target(&tenant_id, vault_path, workspace_id.as_deref())?;
+    capture(&app, &tenant_id, &vault_path, &text)
+}
+
+/// The vault a capture goes to: `vault_path` when given, otherwise the
+/// workspace's vault.
+pub fn target(
+    tenant_id: &str,
+    vault_path: Option<String>,
+    workspace_id: Option<&str>,
+) -> CommandResult<String> {
+    if let Some(vault_path) = vault_path {
+        return Ok(vault_path);
+    }
+    match workspace_id {
+        Some(workspace_id) => Ok(workspaces::find(tenant_id, workspace_id)?
+            .capture_vault()?
+            .path),
+        None => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Capture needs a vault_path or a workspace_id",
+        )),
+    }
+}
+
+/// `submit_quick_capture` for callers without a window, like the automation
+/// API; they check `dry_run` themselves.
+pub fn capture(
+    app: &AppHandle,
+    tenant_id: &str,
+    vault_path: &str,
+    text: &str,
+) -> CommandResult<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Nothing to capture",
+        ));
+    }
+    let vault = Path::new(&vault_path);
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", vault_path));
+    }
+
+    let inbox = vault.join(&settings::load_settings().quick_capture.inbox_note);
+    let stamp = chrono::Local::now().format("%Y-%m-%d %H:%M");
+    notes::append(&inbox, &format!("- {} {}", stamp, text))?;
+    audit::record(
+        "quick_capture",
+        Some(tenant_id),
+        serde_json::json!({ "path": inbox.to_string_lossy() }),
+    );
+
+    close_window(app);
+    Ok(inbox.to_string_lossy().to_string())
+}
+
+// ─── Shortcut & Window ──────────────────────────────────────────────────────
+
+/// Re-register the saved shortcut at startup. Failures are logged: another
+/// app may have claimed it since.
+pub fn restore(app: &AppHandle) {
+    let Some(accelerator) = settings::load_settings().quick_capture.shortcut else {
+        return;
+    };
+    if let Err(e) = register(app, &accelerator, None) {
+        log::warn!(
+            "[quick_capture] can't restore shortcut {}: {}",
+            accelerator,
+            e
+        );
+    }
+}
+
+#[cfg(desktop)]
+fn register(app: &AppHandle, accelerator: &str, previous: Option<&str>) -> CommandResult<()> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+    let shortcut: Shortcut = accelerator.parse().map_err(|_| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid shortcut: {}", accelerator),
+        )
+        .with("accelerator", accelerator)
+    })?;
+    let shortcuts = app.global_shortcut();
+    if let Some(previous) = previous.and_then(|p| p.parse::<Shortcut>().ok()) {
+        if previous == shortcut && shortcuts.is_registered(shortcut) {
+            return Ok(());
+        }
+        let _ = shortcuts.unregister(previous);
+    }
+
+    shortcuts
+        .on_shortcut(shortcut, |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                if let Err(e) = show_window(app) {
+                    log::warn!("[quick_capture] can't open window: {}", e);
+                }
+            }
+        })
+        .map_err(|e| {
+            CommandError::new(
+                ErrorCode::ShortcutConflict,
+                format!("Shortcut {} is unavailable: {}", accelerator, e),
+            )
+            .with("accelerator", accelerator)
+        })
+}
+
+#[cfg(not(desktop))]
+fn register(_app: &AppHandle, _accelerator: &str, _previous: Option<&str>) -> CommandResult<()> {
+    crate::platform::desktop_only("global_shortcuts")
+}
+
+/// Release every global shortcut before exit.
+#[cfg(desktop)]
+pub fn shutdown(app: &AppHandle) {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+    if let Err(e) = app.global_shortcut().unregister_all() {
+        log::warn!("[quick_capture] failed to unregister shortcuts: {}", e);
+    }
+}
+
+#[cfg(not(desktop))]
+pub fn shutdown(_app: &AppHandle) {}
+
+#[cfg(desktop)]
+fn show_window(app: &AppHandle) -> tauri::Result<()> {
+    use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
+
+    if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+        window.show()?;
+        return window.set_focus();
+    }
+    let url = WebviewUrl::App("index.html?view=quick-capture".into());
+    WebviewWindowBuilder::new(app, WINDOW_LABEL, url)
+        .title(crate::i18n::text("window.quick_capture", &[]))
+        .inner_size(440.0, 180.0)
+        .resizable(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .focused(true)
+        .build()?;
+    Ok(())
+}
+
+fn close_window(app: &AppHandle) {
+    use tauri::Manager;
+    if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+        let _ = window.close();
+    }
+}
@@ -0,0 +1,439 @@
+// Read-only mode
+//
+// For demos and audits: while it's on, nothing the app does changes data.
+// `set_read_only_mode` turns it on with a reason and off again. Each change
+// is saved to `read_only.json` in the state directory, so a restart keeps
+// the mode, is audited as `read_only_mode`, and is announced with
+// `read-only-mode-changed`.
+//
+// Every command in `MUTATING` is refused with `ReadOnlyMode`, and the saved
+// `reason`, before it runs: writes, moves and deletes, uploads, session and
+// secret changes, store registration and rescans, and task acks. `guard`
+// wraps the invoke handler to do this, so a command can't forget to check.
+// Reads keep working. While the mode is on, the scheduler runs nothing on
+// its own, because every scheduled job writes something. WhatsApp media
+// isn't imported, assigned tasks aren't resumed, and the automation API's
+// rescan and quick capture endpoints are refused too. `get_health` reports
+// the mode.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, home, task_inbox};
+
+const STATE_FILE: &str = "read_only.json";
+
+/// Commands refused while the mode is on, and during maintenance (see
+/// `maintenance`).
+const MUTATING: &[&str] = &[
+    // Settings and configuration
+    "update_settings",
+    "set_job_enabled",
+    "set_job_schedule",
+    "run_job_now",
+    "register_quick_capture_shortcut",
+    "set_redaction_config",
+    "set_mime_override",
+    "create_workspace",
+    "update_workspace",
+    "delete_workspace",
+    "set_active_workspace",
+    "set_whatsapp_chat_policy",
+    "upsert_recipe",
+    "delete_recipe",
+    "run_recipe_now",
+    // Files and notes
+    "write_text_file",
+    "append_to_note",
+    "generate_rollup",
+    "move_note",
+    "bulk_edit_frontmatter",
+    "bulk_rename",
+    "install_vault_pack",
+    "uninstall_vault_pack",
+    "set_task_state",
+    "submit_quick_capture",
+    "encrypt_note",
+    "decrypt_note",
+    "git_snapshot",
+    "export_results",
+    "export_vault_graph",
+    "download_artifact",
+    "release_quarantined",
+    "discard_quarantined",
+    "extract_email_attachment",
+    "archive_url",
+    "restore_from_trash",
+    "empty_trash",
+    "create_backup",
+    "restore_backup",
+    "move_stray_data_dir",
+    "migrate_to_platform_dirs",
+    "clear_activity_history",
+    "purge_provider_usage",
+    "delete_crash_report",
+    // Stores
+    "register_store",
+    "reauthorize_store",
+    "relink_store",
+    "pick_and_register_store",
+    "update_store_rules",
+    "rescan_store",
+    "rescan_all_stores",
+    // Sending
+    "upload_artifact",
+    "submit_crash_report",
+    "set_artifact_metadata",
+    "prepare_audio_upload",
+    "upload_audio_chunks",
+    "send_whatsapp_message",
+    // Sessions, secrets, and tenants
+    "capture_provider_session",
+    "delete_session",
+    "refresh_session_profile",
+    "ensure_session_dir",
+    "migrate_sessions_layout",
+    "delete_tenant",
+    "set_secret",
+    "delete_secret",
+    "set_master_passphrase",
+    "change_master_passphrase",
+    // Assigned tasks
+    "receive_assigned_task",
+    "ack_task",
+];
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct ReadOnlyStatus {
+    pub enabled: bool,
+    pub reason: Option<String>,
+    /// When the mode was turned on.
+    pub since: Option<String>,
+}
+
+/// Managed state: the current mode, loaded from disk at launch.
+pub struct ReadOnlyMode(Mutex<ReadOnlyStatus>);
+
+impl Default for ReadOnlyMode {
+    fn default() -> Self {
+        Self(Mutex::new(load()))
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Turn read-only mode on, with the `reason` shown to callers it refuses,
+/// or off; turning it on needs a reason. Returns the new status.
+#[tauri::command]
+pub fn set_read_only_mode(
+    app: AppHandle,
+    state: State<'_, ReadOnlyMode>,
+    enabled: bool,
+    reason: Option<String>,
+) -> CommandResult<ReadOnlyStatus> {
+    let (status, changed) = state.set(enabled, reason)?;
+    if changed {
+        audit::record(
+            "read_only_mode",
+            None,
+            serde_json::json!({ "enabled": status.enabled, "reason": status.reason }),
+        );
+        let _ = app.emit("read-only-mode-changed", status.clone());
+        if !status.enabled {
+            task_inbox::resume(app.clone());
+        }
+    }
+    Ok(status)
+}
+
+// ─── Checks ─────────────────────────────────────────────────────────────────
+
+impl ReadOnlyMode {
+    pub fn status(&self) -> ReadOnlyStatus {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.0.lock().unwrap().enabled
+    }
+
+    /// `ReadOnlyMode` while the mode is on.
+    pub fn check(&self, command: &str) -> CommandResult<()> {
+        let status = self.0.lock().unwrap();
+        if !status.enabled {
+            return Ok(());
+        }
+        let reason = status.reason.clone().unwrap_or_default();
+        log::info!("[read_only] refused {}", command);
+        Err(CommandError::new(
+            ErrorCode::ReadOnlyMode,
+            format!("{} can't run in read-only mode", command),
+        )
+        .with("command", command)
+        .with("reason", reason))
+    }
+
+    /// Save the new mode; also returns whether it changed. Turning it on
+    /// again only updates the reason.
+    fn set(&self, enabled: bool, reason: Option<String>) -> CommandResult<(ReadOnlyStatus, bool)> {
+        let reason = reason
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        if enabled && reason.is_none() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                "Read-only mode needs a reason",
+            ));
+        }
+        let mut status = self.0.lock().unwrap();
+        let next = match enabled {
+            true => ReadOnlyStatus {
+                enabled,
+                reason,
+                since: status
+                    .since
+                    .clone()
+                    .filter(|_| status.enabled)
+                    .or_else(|| Some(chrono::Utc::now().to_rfc3339())),
+            },
+            false => ReadOnlyStatus::default(),
+        };
+        let changed = next != *status;
+        if changed {
+            save(&next)?;
+            log::info!(
+                "[read_only] {}",
+                if enabled { "enabled" } else { "disabled" }
+            );
+            *status = next;
+        }
+        Ok((status.clone(), changed))
+    }
+}
+
+pub fn is_mutating(command: &str) -> bool {
+    MUTATING.contains(&command)
+}
+
+/// Wrap the invoke handler so `MUTATING` commands are refused while the
+/// mode is on.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        if is_mutating(&command) {
+            let state = invoke.message.webview_ref().state::<ReadOnlyMode>();
+            if let Err(e) = state.check(&command) {
+                invoke.resolver.reject(e);
+                return true;
+            }
+        }
+        handler(invoke)
+    }
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn state_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join(STATE_FILE))
+}
+
+fn load() -> ReadOnlyStatus {
+    state_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save(status: &ReadOnlyStatus) -> CommandResult<()> {
+    let raw = serde_json::to_vec_pretty(status)
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    fs::write(state_path()?, raw)?;
+    Ok(())
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn the_mode_and_its_reason_survive_a_restart() {
+        let _home = TestHome::new();
+        let mode = ReadOnlyMode::default();
+        mode.check("write_text_file").unwrap();
+        assert!(mode.set(true, Some("  ".into())).is_err());
+
+        let (status, changed) = mode
+            .set(true, Some(" Audit with Example LLP ".into()))
+            .unwrap();
+        assert!(changed);
+        assert_eq!(status.reason.as_deref(), Some("Audit with Example LLP"));
+        let err = mode.check("write_text_file").unwrap_err();
+        assert_eq!(err.code, ErrorCode::ReadOnlyMode);
+        assert_eq!(err.params["reason"], "Audit with Example LLP");
+
+        let restarted = ReadOnlyMode::default();
+        assert_eq!(restarted.status(), status);
+        let (_, changed) = restarted
+            .set(true, Some("Audit with Example LLP".into()))
+            .unwrap();
+        assert!(!changed);
+
+        restarted.set(false, None).unwrap();
+        assert!(!ReadOnlyMode::default().enabled());
+    }
+
+    /// Commands that keep working while the mode is on: reads, and controls
+    /// that change no data (windows, the modes themselves, cancelling work).
+    /// A command missing from both lists fails `every_command_is_classified`.
+    const READS: &[&str] = &[
+        // Core and settings
+        "get_health",
+        "get_tenant_path",
+        "get_sessions_path",
+        "get_diagnostics",
+        "get_pending_crash_reports",
+        "get_startup_report",
+        "get_capabilities",
+        "set_devtools",
+        "get_debug_flags",
+        "get_error_catalog",
+        "get_settings",
+        "get_startup_timeline",
+        "get_system_state",
+        "get_volume_free_space",
+        "catch_up_events",
+        // Orchestrator
+        "test_orchestrator_connection",
+        "start_local_orchestrator",
+        "get_local_orchestrator_status",
+        "stop_local_orchestrator",
+        // Files and notes
+        "list_directory",
+        "read_text_file",
+        "read_text_file_with_hash",
+        "read_text_range",
+        "get_asset_url",
+        "prefetch_directory_previews",
+        "cancel_directory_previews",
+        "render_pdf_page",
+        "list_artifact_uploads",
+        "list_artifacts",
+        "query_artifacts",
+        "find_similar_notes",
+        "query_files",
+        "list_quarantined",
+        "preview_filename",
+        "detect_text_style",
+        "hash_file",
+        "get_content_digest",
+        "get_file_id",
+        "get_user_directories",
+        "list_app_directory",
+        "read_app_text_file",
+        "discover_obsidian_vaults",
+        "get_vault_bookmarks",
+        "resolve_daily_note",
+        "render_template",
+        "extract_tasks",
+        "get_git_status",
+        "get_note_stats",
+        "parse_ics",
+        "get_events",
+        "read_canvas",
+        "parse_email",
+        "list_mbox_messages",
+        "get_recent_activity",
+        "resolve_url_metadata",
+        "get_stray_data_dir",
+        "list_trash",
+        "list_mime_overrides",
+        "test_redaction",
+        "get_redaction_config",
+        // Stores
+        "list_stores",
+        "diff_store_snapshot",
+        "check_store_health",
+        // Providers, sessions, and secrets
+        "get_provider_login_config",
+        "open_provider_login",
+        "check_provider_session",
+        "provider_fetch",
+        "read_provider_response",
+        "provider_fetch_stream",
+        "cancel_provider_stream",
+        "get_provider_usage",
+        "list_sessions",
+        "get_secret",
+        "list_secret_keys",
+        "unlock_vault",
+        "get_automation_token",
+        // Windows, chats, and workspaces
+        "open_tenant_window",
+        "list_open_windows",
+        "focus_tenant_window",
+        "list_whatsapp_chats",
+        "list_workspaces",
+        // Jobs, tasks, and operations
+        "list_scheduled_jobs",
+        "list_assigned_tasks",
+        "list_recipes",
+        "list_recipe_runs",
+        "list_operations",
+        "cancel_operation",
+        "boost_operation",
+        // The modes themselves
+        "set_read_only_mode",
+        "enter_maintenance_mode",
+        "exit_maintenance_mode",
+    ];
+
+    /// Command names in lib.rs's `generate_handler!`, without module paths.
+    fn registered_commands() -> Vec<&'static str> {
+        let lib = include_str!("lib.rs");
+        let start = lib.find("generate_handler![").unwrap() + "generate_handler![".len();
+        let end = start + lib[start..].find(']').unwrap();
+        lib[start..end]
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| line.trim_end_matches(',').rsplit("::").next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_guarded_command_is_registered() {
+        let registered = registered_commands();
+        for command in MUTATING.iter().chain(READS) {
+            assert!(
+                registered.contains(command),
+                "{} isn't a registered command",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn every_command_is_classified() {
+        for command in registered_commands() {
+            let mutating = MUTATING.contains(&command);
+            let read = READS.contains(&command);
+            assert!(
+                mutating != read,
+                "{} must be in exactly one of MUTATING and READS",
+                command
+            );
+        }
+    }
+}
@@ -0,0 +1,1107 @@
+// Recipes
+//
+// Small local automations a tenant sets up without the orchestrator, e.g.
+// "when a file lands in ~/Inbox, hash it, move it into the vault's
+// attachments, and link it from today's daily note". A recipe is a trigger
+// and a list of steps, written as JSON or TOML, and saved per tenant in
+// `tenants/<tenant>/recipes.json`. Triggers:
+// - `watch`: a folder; each file that settles there runs the recipe. Files
+//   already there when the recipe is saved don't.
+// - `schedule`: a scheduler spec like `every 1h` or `daily 08:00`
+// - `manual`: only `run_recipe_now`
+// Steps run in order on the file that triggered the run:
+// - `hash`: its SHA-256, for `{sha256}`
+// - `move`: into the folder `to`, or into the attachment folder of
+//   `store_id` (with `to`, a folder relative to that store)
+// - `append_to_note`: `text` to the note `note`, or to today's daily note of
+//   `store_id`
+// - `notify`: emits `recipe-notification` with `message`
+// - `upload_artifact`: uploads it like `upload_artifact`
+// `text` and `message` fill in `{name}`, `{path}`, `{link}` (a wikilink to
+// the file), `{sha256}`, and `{date}`.
+//
+// Moves and appends must land in a connected store, and runs honor dry runs
+// (see `dry_run`), including a policy that forces them. A failing step stops
+// the run. Each run is kept in `tenants/<tenant>/recipe_runs.json` with every
+// step's outcome, the newest `MAX_RUNS` per tenant.
+//
+// The `recipes` job checks watch folders and schedules every minute. A file
+// a recipe wrote remembers the recipes that led to it. A watch that fires on
+// it for a recipe already in that chain, or past `MAX_CHAIN` recipes, is
+// recorded as `recursion_broken` and doesn't run, so recipes can't trigger
+// themselves or each other in a loop.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter};
+
+use crate::activity::{self, ActivityKind};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::integrity::{self, load_json};
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+use crate::stores::{self, StoreStatus};
+use crate::tenant_windows;
+use crate::text_style::TextWriteOptions;
+use crate::{artifact_upload, audit, dry_run, file_read, home, notes, sessions, store_kinds};
+
+const RECIPES_FILE: &str = "recipes.json";
+const RUNS_FILE: &str = "recipe_runs.json";
+const STATE_FILE: &str = "recipe_state.json";
+/// Runs kept per tenant.
+const MAX_RUNS: usize = 200;
+/// Recipes a file can pass through before a watch stops firing on it.
+const MAX_CHAIN: usize = 4;
+/// A file must sit unchanged this long before a watch picks it up.
+const SETTLE: Duration = Duration::from_secs(5);
+/// How long a written file remembers its chain.
+const PRODUCED_TTL: Duration = Duration::from_secs(60 * 60);
+/// The caller label triggered runs give `dry_run`.
+const CALLER: &str = "recipes";
+
+static LOCK: Mutex<()> = Mutex::new(());
+/// Files recipes wrote → the recipes that led to them.
+static PRODUCED: Mutex<BTreeMap<PathBuf, (Vec<String>, Instant)>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    pub trigger: Trigger,
+    pub steps: Vec<Step>,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    Watch { path: String },
+    Schedule { schedule: String },
+    Manual,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    Hash,
+    Move {
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        store_id: Option<String>,
+    },
+    AppendToNote {
+        #[serde(default)]
+        note: Option<String>,
+        #[serde(default)]
+        store_id: Option<String>,
+        text: String,
+    },
+    Notify {
+        message: String,
+    },
+    UploadArtifact {
+        #[serde(default)]
+        metadata: Option<serde_json::Value>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RunTrigger {
+    Manual,
+    Watch,
+    Schedule,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
+    /// Skipped because the file came from a recipe in the same chain.
+    RecursionBroken,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Succeeded,
+    /// Dry run: nothing was changed.
+    Planned,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepOutcome {
+    pub action: String,
+    pub status: StepStatus,
+    /// What the step did or would have done, or why it failed.
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecipeRun {
+    pub run_id: String,
+    pub recipe_id: String,
+    pub trigger: RunTrigger,
+    /// The file the run acted on.
+    pub path: Option<String>,
+    pub started_at: String,
+    pub finished_at: String,
+    pub outcome: RunOutcome,
+    pub dry_run: bool,
+    pub steps: Vec<StepOutcome>,
+    /// Index into `steps` of the step that stopped the run.
+    pub failed_step: Option<usize>,
+    /// The recipes that led to the file, for `recursion_broken` runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<String>,
+}
+
+/// Payload of `recipe-notification`.
+#[derive(Serialize, Clone)]
+pub struct RecipeNotification {
+    pub tenant_id: String,
+    pub recipe_id: String,
+    pub message: String,
+}
+
+/// What the job remembers between ticks, per tenant.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct RecipeState {
+    /// Recipe id → file names already seen in its watch folder.
+    seen: BTreeMap<String, BTreeSet<String>>,
+    /// Recipe id → when its schedule last fired.
+    last_scheduled: BTreeMap<String, String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn list_recipes(tenant_id: String) -> CommandResult<Vec<Recipe>> {
+    valid_tenant(&tenant_id)?;
+    Ok(load_recipes(&tenant_id))
+}
+
+/// Add a recipe or replace the one with its id. `recipe` is a JSON object,
+/// or a string of TOML.
+#[tauri::command]
+pub fn upsert_recipe(tenant_id: String, recipe: serde_json::Value) -> CommandResult<Recipe> {
+    valid_tenant(&tenant_id)?;
+    let recipe = parse(recipe)?;
+    validate(&recipe)?;
+
+    let _guard = LOCK.lock().unwrap();
+    let mut recipes = load_recipes(&tenant_id);
+    let previous = match recipes.iter_mut().find(|r| r.id == recipe.id) {
+        Some(existing) => Some(std::mem::replace(existing, recipe.clone())),
+        None => {
+            recipes.push(recipe.clone());
+            None
+        }
+    };
+    save_recipes(&tenant_id, &recipes)?;
+
+    // Files already waiting in a new watch folder don't run the recipe
+    if let Trigger::Watch { path } = &recipe.trigger {
+        if previous.map(|p| p.trigger) != Some(recipe.trigger.clone()) {
+            let mut state = load_state(&tenant_id);
+            let mut seen = BTreeSet::new();
+            if let Ok(dir) = expand(path) {
+                settled_files(&dir, &mut seen, Duration::ZERO);
+            }
+            state.seen.insert(recipe.id.clone(), seen);
+            save_state(&tenant_id, &state)?;
+        }
+    }
+    audit::record(
+        "upsert_recipe",
+        Some(&tenant_id),
+        serde_json::json!({ "recipe_id": recipe.id }),
+    );
+    Ok(recipe)
+}
+
+/// Delete a recipe; its run history is kept.
+#[tauri::command]
+pub fn delete_recipe(tenant_id: String, recipe_id: String) -> CommandResult<()> {
+    valid_tenant(&tenant_id)?;
+    let _guard = LOCK.lock().unwrap();
+    let mut recipes = load_recipes(&tenant_id);
+    let before = recipes.len();
+    recipes.retain(|r| r.id != recipe_id);
+    if recipes.len() == before {
+        return Err(not_found(&recipe_id));
+    }
+    save_recipes(&tenant_id, &recipes)?;
+    audit::record(
+        "delete_recipe",
+        Some(&tenant_id),
+        serde_json::json!({ "recipe_id": recipe_id }),
+    );
+    Ok(())
+}
+
+/// Run a recipe now, on `path`, or for a watch recipe without one, on each
+/// file in its folder. Recipes whose steps need no file run once.
+#[tauri::command]
+pub async fn run_recipe_now(
+    app: AppHandle,
+    window: tauri::Window,
+    tenant_id: String,
+    recipe_id: String,
+    path: Option<String>,
+    dry_run: Option<bool>,
+) -> CommandResult<Vec<RecipeRun>> {
+    valid_tenant(&tenant_id)?;
+    let dry_run = dry_run::requested(window.label(), Some(&tenant_id), dry_run);
+    tauri::async_runtime::spawn_blocking(move || {
+        let recipe = load_recipes(&tenant_id)
+            .into_iter()
+            .find(|r| r.id == recipe_id)
+            .ok_or_else(|| not_found(&recipe_id))?;
+        let files = match (path, &recipe.trigger) {
+            (Some(path), _) => vec![Some(PathBuf::from(path))],
+            (None, Trigger::Watch { path }) => {
+                let mut files = Vec::new();
+                settled_files(&expand(path)?, &mut BTreeSet::new(), Duration::ZERO)
+                    .into_iter()
+                    .for_each(|f| files.push(Some(f)));
+                files
+            }
+            (None, _) if recipe.steps.iter().any(Step::needs_file) => {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("Recipe {} needs a path to run on", recipe.id),
+                )
+                .with("recipe_id", &recipe.id));
+            }
+            (None, _) => vec![None],
+        };
+        Ok(files
+            .into_iter()
+            .map(|file| run(&app, &tenant_id, &recipe, RunTrigger::Manual, file, dry_run))
+            .collect())
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Past runs, newest first, optionally of one recipe.
+#[tauri::command]
+pub fn list_recipe_runs(
+    tenant_id: String,
+    recipe_id: Option<String>,
+    limit: Option<usize>,
+) -> CommandResult<Vec<RecipeRun>> {
+    valid_tenant(&tenant_id)?;
+    Ok(load_runs(&tenant_id)
+        .into_iter()
+        .rev()
+        .filter(|r| recipe_id.as_ref().is_none_or(|id| &r.recipe_id == id))
+        .take(limit.unwrap_or(50))
+        .collect())
+}
+
+// ─── Validation ─────────────────────────────────────────────────────────────
+
+fn parse(recipe: serde_json::Value) -> CommandResult<Recipe> {
+    let parsed = match recipe {
+        serde_json::Value::String(source) => toml::from_str(&source).map_err(|e| e.to_string()),
+        value => serde_json::from_value(value).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|e| CommandError::new(ErrorCode::ParseError, format!("Invalid recipe: {}", e)))
+}
+
+fn validate(recipe: &Recipe) -> CommandResult<()> {
+    let invalid = |message: String| {
+        Err(CommandError::new(ErrorCode::InvalidInput, message).with("recipe_id", &recipe.id))
+    };
+    if recipe.id.is_empty()
+        || !recipe
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return invalid(format!("Invalid recipe id: {:?}", recipe.id));
+    }
+    if recipe.name.trim().is_empty() {
+        return invalid("A recipe needs a name".into());
+    }
+    if recipe.steps.is_empty() {
+        return invalid("A recipe needs at least one step".into());
+    }
+    let watched = match &recipe.trigger {
+        Trigger::Watch { path } => {
+            let dir = expand(path)?;
+            if !dir.is_dir() {
+                return invalid(format!("Watch folder not found: {}", path));
+            }
+            Some(dir)
+        }
+        Trigger::Schedule { schedule } => {
+            Schedule::parse(schedule)?;
+            None
+        }
+        Trigger::Manual => None,
+    };
+    if matches!(recipe.trigger, Trigger::Schedule { .. })
+        && recipe.steps.iter().any(Step::needs_file)
+    {
+        return invalid("Scheduled recipes have no file for file steps".into());
+    }
+    for step in &recipe.steps {
+        let target = match step {
+            Step::Move { to, store_id } => Some(move_folder(to.as_deref(), store_id.as_deref())?),
+            Step::AppendToNote { note, store_id, .. } => {
+                Some(note_path(note.as_deref(), store_id.as_deref())?)
+            }
+            _ => None,
+        };
+        // A recipe writing into its own watch folder would run on its output
+        if let (Some(target), Some(watched)) = (target, &watched) {
+            if target.parent() == Some(watched.as_path()) || target == *watched {
+                return invalid(format!(
+                    "Step {} writes into the recipe's own watch folder",
+                    step.action()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Step {
+    fn action(&self) -> &'static str {
+        match self {
+            Step::Hash => "hash",
+            Step::Move { .. } => "move",
+            Step::AppendToNote { .. } => "append_to_note",
+            Step::Notify { .. } => "notify",
+            Step::UploadArtifact { .. } => "upload_artifact",
+        }
+    }
+
+    fn needs_file(&self) -> bool {
+        matches!(
+            self,
+            Step::Hash | Step::Move { .. } | Step::UploadArtifact { .. }
+        )
+    }
+}
+
+fn valid_tenant(tenant_id: &str) -> CommandResult<()> {
+    sessions::validate_id("tenant_id", tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))
+}
+
+fn not_found(recipe_id: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::NotFound,
+        format!("Unknown recipe: {}", recipe_id),
+    )
+    .with("recipe_id", recipe_id)
+}
+
+// ─── Running ────────────────────────────────────────────────────────────────
+
+/// The file a run is on, as steps move and hash it.
+struct Context {
+    file: Option<PathBuf>,
+    sha256: Option<String>,
+    /// The recipes that led here, this one last.
+    chain: Vec<String>,
+}
+
+fn run(
+    app: &AppHandle,
+    tenant_id: &str,
+    recipe: &Recipe,
+    trigger: RunTrigger,
+    file: Option<PathBuf>,
+    dry_run: bool,
+) -> RecipeRun {
+    let started_at = Utc::now().to_rfc3339();
+    let path = file.as_ref().map(|f| f.to_string_lossy().to_string());
+    let mut chain = file.as_deref().map(chain_of).unwrap_or_default();
+    chain.push(recipe.id.clone());
+    let mut context = Context {
+        file,
+        sha256: None,
+        chain,
+    };
+
+    let mut steps = Vec::new();
+    let mut failed_step = None;
+    for (i, step) in recipe.steps.iter().enumerate() {
+        match execute(app, tenant_id, recipe, step, &mut context, dry_run) {
+            Ok(detail) => steps.push(StepOutcome {
+                action: step.action().to_string(),
+                status: match dry_run && !matches!(step, Step::Hash) {
+                    true => StepStatus::Planned,
+                    false => StepStatus::Succeeded,
+                },
+                detail,
+            }),
+            Err(e) => {
+                log::warn!("[recipes] {} failed at {}: {}", recipe.id, step.action(), e);
+                steps.push(StepOutcome {
+                    action: step.action().to_string(),
+                    status: StepStatus::Failed,
+                    detail: Some(e.to_string()),
+                });
+                failed_step = Some(i);
+                break;
+            }
+        }
+    }
+    let run = RecipeRun {
+        run_id: run_id(),
+        recipe_id: recipe.id.clone(),
+        trigger,
+        path,
+        started_at,
+        finished_at: Utc::now().to_rfc3339(),
+        outcome: match failed_step {
+            Some(_) => RunOutcome::Failed,
+            None => RunOutcome::Succeeded,
+        },
+        dry_run,
+        steps,
+        failed_step,
+        chain: Vec::new(),
+    };
+    record_run(tenant_id, &run);
+    run
+}
+
+/// Run one step; returns what it did.
+fn execute(
+    app: &AppHandle,
+    tenant_id: &str,
+    recipe: &Recipe,
+    step: &Step,
+    context: &mut Context,
+    dry_run: bool,
+) -> CommandResult<Option<String>> {
+    match step {
+        Step::Hash => {
+            let sha256 = file_read::hash(context.file()?)?;
+            context.sha256 = Some(sha256.clone());
+            Ok(Some(sha256))
+        }
+        Step::Move { to, store_id } => {
+            let file = context.file()?.to_path_buf();
+            let folder = move_folder(to.as_deref(), store_id.as_deref())?;
+            let dest = folder.join(file.file_name().unwrap_or_default());
+            writable(&dest)?;
+            if dest.exists() {
+                return Err(CommandError::new(
+                    ErrorCode::Conflict,
+                    format!("{} already exists", dest.display()),
+                )
+                .with("path", dest.to_string_lossy()));
+            }
+            let detail = format!("{} → {}", file.display(), dest.display());
+            if !dry_run {
+                fs::create_dir_all(&folder)?;
+                move_file(&file, &dest)?;
+                produced(&dest, &context.chain);
+                activity::record(ActivityKind::Write, &dest, Some(tenant_id));
+            }
+            context.file = Some(dest);
+            Ok(Some(detail))
+        }
+        Step::AppendToNote {
+            note,
+            store_id,
+            text,
+        } => {
+            let note = note_path(note.as_deref(), store_id.as_deref())?;
+            writable(&note)?;
+            let existed = note.exists();
+            let text = context.fill(text);
+            let outcome =
+                notes::append_with(&note, &text, &TextWriteOptions::default(), None, dry_run)?;
+            if !dry_run && !existed {
+                produced(&note, &context.chain);
+            }
+            Ok(outcome
+                .planned
+                .or_else(|| Some(note.to_string_lossy().to_string())))
+        }
+        Step::Notify { message } => {
+            let message = context.fill(message);
+            if !dry_run {
+                let _ = app.emit(
+                    "recipe-notification",
+                    RecipeNotification {
+                        tenant_id: tenant_id.to_string(),
+                        recipe_id: recipe.id.clone(),
+                        message: message.clone(),
+                    },
+                );
+            }
+            Ok(Some(message))
+        }
+        Step::UploadArtifact { metadata } => {
+            let file = context.file()?.to_string_lossy().to_string();
+            if dry_run {
+                return Ok(Some(format!("Upload {}", file)));
+            }
+            let upload = tauri::async_runtime::block_on(artifact_upload::upload_artifact(
+                app.clone(),
+                tenant_id.to_string(),
+                file,
+                metadata.clone(),
+                None,
+            ))?;
+            Ok(Some(upload.file_id))
+        }
+    }
+}
+
+impl Context {
+    fn file(&self) -> CommandResult<&Path> {
+        self.file.as_deref().ok_or_else(|| {
+            CommandError::new(ErrorCode::InvalidInput, "This step needs a file to act on")
+        })
+    }
+
+    fn fill(&self, template: &str) -> String {
+        let name = self
+            .file
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = self
+            .file
+            .as_deref()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        template
+            .replace("{name}", &name)
+            .replace("{path}", &path)
+            .replace("{link}", &format!("[[{}]]", name))
+            .replace("{sha256}", self.sha256.as_deref().unwrap_or(""))
+            .replace("{date}", &Local::now().format("%Y-%m-%d").to_string())
+    }
+}
+
+/// Where a move step puts files.
+fn move_folder(to: Option<&str>, store_id: Option<&str>) -> CommandResult<PathBuf> {
+    match (to, store_id) {
+        (Some(to), None) => expand(to),
+        (to, Some(store_id)) => {
+            let root = PathBuf::from(stores::find_store(store_id)?.path);
+            Ok(match to {
+                Some(to) => root.join(relative(to)?),
+                None => store_kinds::attachment_folder(&root, &root),
+            })
+        }
+        (None, None) => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "A move step needs `to` or `store_id`",
+        )),
+    }
+}
+
+/// The note an append step writes to.
+fn note_path(note: Option<&str>, store_id: Option<&str>) -> CommandResult<PathBuf> {
+    match (note, store_id) {
+        (Some(note), None) => expand(note),
+        (note, Some(store_id)) => {
+            let root = PathBuf::from(stores::find_store(store_id)?.path);
+            Ok(match note {
+                Some(note) => root.join(relative(note)?),
+                None => store_kinds::daily_note_path(&root, Local::now().date_naive()),
+            })
+        }
+        (None, None) => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "An append_to_note step needs `note` or `store_id`",
+        )),
+    }
+}
+
+/// Recipes only write inside connected stores.
+fn writable(path: &Path) -> CommandResult<()> {
+    match stores::store_for_path(path) {
+        Some(store) if store.status != StoreStatus::NeedsReauthorization => Ok(()),
+        _ => Err(CommandError::new(
+            ErrorCode::PolicyDenied,
+            format!("{} isn't in a connected store", path.display()),
+        )
+        .with("path", path.to_string_lossy())),
+    }
+}
+
+/// An absolute path, or one under the home folder written `~/...`.
+fn expand(path: &str) -> CommandResult<PathBuf> {
+    let expanded = match path.strip_prefix("~/") {
+        Some(rest) => home::user_home()?.join(rest),
+        None => PathBuf::from(path),
+    };
+    if !expanded.is_absolute() || expanded.components().any(|c| c == Component::ParentDir) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Recipe paths must be absolute: {}", path),
+        )
+        .with("path", path));
+    }
+    Ok(expanded)
+}
+
+fn relative(path: &str) -> CommandResult<&Path> {
+    let relative = Path::new(path);
+    if relative.is_absolute() || relative.components().any(|c| c == Component::ParentDir) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Store paths must stay inside the store: {}", path),
+        )
+        .with("path", path));
+    }
+    Ok(relative)
+}
+
+fn move_file(from: &Path, to: &Path) -> CommandResult<()> {
+    if fs::rename(from, to).is_err() {
+        // Across volumes
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn run_id() -> String {
+    format!(
+        "run-{}-{}",
+        Utc::now().timestamp_millis(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+// ─── Recursion ──────────────────────────────────────────────────────────────
+
+fn produced(path: &Path, chain: &[String]) {
+    let mut produced = PRODUCED.lock().unwrap();
+    produced.retain(|_, (_, at)| at.elapsed() < PRODUCED_TTL);
+    produced.insert(path.to_path_buf(), (chain.to_vec(), Instant::now()));
+}
+
+/// The recipes that led to `path`, if a recipe wrote it.
+fn chain_of(path: &Path) -> Vec<String> {
+    PRODUCED
+        .lock()
+        .unwrap()
+        .get(path)
+        .map(|(chain, _)| chain.clone())
+        .unwrap_or_default()
+}
+
+fn loops(recipe: &Recipe, chain: &[String]) -> bool {
+    chain.contains(&recipe.id) || chain.len() >= MAX_CHAIN
+}
+
+fn recursion_broken(tenant_id: &str, recipe: &Recipe, file: &Path, chain: Vec<String>) {
+    log::warn!(
+        "[recipes] not running {} on {}: it came from {}",
+        recipe.id,
+        file.display(),
+        chain.join(" → ")
+    );
+    let now = Utc::now().to_rfc3339();
+    record_run(
+        tenant_id,
+        &RecipeRun {
+            run_id: run_id(),
+            recipe_id: recipe.id.clone(),
+            trigger: RunTrigger::Watch,
+            path: Some(file.to_string_lossy().to_string()),
+            started_at: now.clone(),
+            finished_at: now,
+            outcome: RunOutcome::RecursionBroken,
+            dry_run: false,
+            steps: Vec::new(),
+            failed_step: None,
+            chain,
+        },
+    );
+}
+
+// ─── Triggers ───────────────────────────────────────────────────────────────
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(
+        "recipes",
+        "Run recipes on new files in watch folders and on schedule",
+        Schedule::Every(Duration::from_secs(60)),
+        JobLoad::Light,
+        true,
+        |app| {
+            let tenants = home::data_dir().and_then(|dir| Ok(fs::read_dir(dir.join("tenants"))?));
+            let mut runs = 0;
+            for entry in tenants.into_iter().flatten().flatten() {
+                runs += check_tenant(app, &entry.file_name().to_string_lossy());
+            }
+            Ok(Some(format!("{} recipe runs", runs)))
+        },
+    );
+}
+
+/// Run the tenant's recipes that are due; returns how many ran.
+fn check_tenant(app: &AppHandle, tenant_id: &str) -> usize {
+    let recipes = load_recipes(tenant_id);
+    if recipes.is_empty() {
+        return 0;
+    }
+    let dry_run = dry_run::requested(CALLER, Some(tenant_id), None);
+    let mut state = load_state(tenant_id);
+    state
+        .seen
+        .retain(|id, _| recipes.iter().any(|r| &r.id == id));
+    let now = Utc::now();
+    let mut runs = 0;
+    for recipe in recipes.iter().filter(|r| r.enabled) {
+        match &recipe.trigger {
+            Trigger::Watch { path } => {
+                let Ok(dir) = expand(path) else {
+                    continue;
+                };
+                let seen = state.seen.entry(recipe.id.clone()).or_default();
+                for file in settled_files(&dir, seen, SETTLE) {
+                    let chain = chain_of(&file);
+                    if loops(recipe, &chain) {
+                        recursion_broken(tenant_id, recipe, &file, chain);
+                        continue;
+                    }
+                    run(
+                        app,
+                        tenant_id,
+                        recipe,
+                        RunTrigger::Watch,
+                        Some(file),
+                        dry_run,
+                    );
+                    runs += 1;
+                }
+            }
+            Trigger::Schedule { schedule } => {
+                let Ok(schedule) = Schedule::parse(schedule) else {
+                    continue;
+                };
+                let last = state
+                    .last_scheduled
+                    .get(&recipe.id)
+                    .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                    .map(|at| at.with_timezone(&Utc));
+                // A new schedule counts from when it was first seen
+                let due = match last {
+                    Some(last) => schedule.next_after(Some(last), now) <= now,
+                    None => false,
+                };
+                if due {
+                    run(app, tenant_id, recipe, RunTrigger::Schedule, None, dry_run);
+                    runs += 1;
+                }
+                if due || last.is_none() {
+                    state
+                        .last_scheduled
+                        .insert(recipe.id.clone(), now.to_rfc3339());
+                }
+            }
+            Trigger::Manual => {}
+        }
+    }
+    if let Err(e) = save_state(tenant_id, &state) {
+        log::warn!("[recipes] couldn't save state for {}: {}", tenant_id, e);
+    }
+    runs
+}
+
+/// Files in `dir` not in `seen` and unchanged for `settle`, which are added
+/// to it. Names no longer in the folder are forgotten, so a file that comes
+/// back runs again.
+fn settled_files(dir: &Path, seen: &mut BTreeSet<String>, settle: Duration) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut present = BTreeSet::new();
+    let mut arrived = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if name.starts_with('.') || !meta.is_file() {
+            continue;
+        }
+        present.insert(name.clone());
+        let age = meta
+            .modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .unwrap_or_default();
+        if age >= settle && seen.insert(name) {
+            arrived.push(entry.path());
+        }
+    }
+    seen.retain(|name| present.contains(name));
+    arrived.sort();
+    arrived
+}
+
+// ─── Relinking ──────────────────────────────────────────────────────────────
+
+/// Point every tenant's recipe paths under `old_root` (watch folders, and
+/// `move` and `append_to_note` targets given as paths) at the same place
+/// under `new_root`, after `relink_store` moved a store. Returns how many
+/// recipes changed.
+pub fn relink_paths(old_root: &Path, new_root: &Path) -> usize {
+    let relinked = |path: &mut String| {
+        let Some(rest) = expand(path)
+            .ok()
+            .and_then(|p| Some(p.strip_prefix(old_root).ok()?.to_path_buf()))
+        else {
+            return false;
+        };
+        *path = new_root.join(rest).to_string_lossy().to_string();
+        true
+    };
+
+    let _guard = LOCK.lock().unwrap();
+    let tenants = home::data_dir().and_then(|dir| Ok(fs::read_dir(dir.join("tenants"))?));
+    let mut changed = 0;
+    for entry in tenants.into_iter().flatten().flatten() {
+        let tenant_id = entry.file_name().to_string_lossy().to_string();
+        let mut recipes = load_recipes(&tenant_id);
+        let mut tenant_changed = 0;
+        for recipe in recipes.iter_mut() {
+            let mut paths: Vec<&mut String> = Vec::new();
+            if let Trigger::Watch { path } = &mut recipe.trigger {
+                paths.push(path);
+            }
+            for step in recipe.steps.iter_mut() {
+                match step {
+                    Step::Move {
+                        to: Some(path),
+                        store_id: None,
+                    }
+                    | Step::AppendToNote {
+                        note: Some(path),
+                        store_id: None,
+                        ..
+                    } => paths.push(path),
+                    _ => {}
+                }
+            }
+            let mut any = false;
+            for path in paths {
+                any |= relinked(path);
+            }
+            tenant_changed += usize::from(any);
+        }
+        if tenant_changed == 0 {
+            continue;
+        }
+        match save_recipes(&tenant_id, &recipes) {
+            Ok(()) => changed += tenant_changed,
+            Err(e) => log::warn!("[recipes] couldn't relink recipes of {}: {}", tenant_id, e),
+        }
+    }
+    changed
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn tenant_file(tenant_id: &str, file: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?.join(file))
+}
+
+fn load_recipes(tenant_id: &str) -> Vec<Recipe> {
+    tenant_file(tenant_id, RECIPES_FILE)
+        .ok()
+        .and_then(|path| load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn save_recipes(tenant_id: &str, recipes: &[Recipe]) -> CommandResult<()> {
+    integrity::persist_json(&tenant_file(tenant_id, RECIPES_FILE)?, recipes, None)
+}
+
+fn load_runs(tenant_id: &str) -> Vec<RecipeRun> {
+    tenant_file(tenant_id, RUNS_FILE)
+        .ok()
+        .and_then(|path| load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn record_run(tenant_id: &str, run: &RecipeRun) {
+    let _guard = LOCK.lock().unwrap();
+    let mut runs = load_runs(tenant_id);
+    runs.push(run.clone());
+    let excess = runs.len().saturating_sub(MAX_RUNS);
+    runs.drain(..excess);
+    let saved = tenant_file(tenant_id, RUNS_FILE)
+        .and_then(|path| integrity::persist_json(&path, runs.as_slice(), None));
+    if let Err(e) = saved {
+        log::warn!("[recipes] couldn't record run of {}: {}", run.recipe_id, e);
+    }
+}
+
+fn load_state(tenant_id: &str) -> RecipeState {
+    tenant_file(tenant_id, STATE_FILE)
+        .ok()
+        .and_then(|path| load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn save_state(tenant_id: &str, state: &RecipeState) -> CommandResult<()> {
+    integrity::persist_json(&tenant_file(tenant_id, STATE_FILE)?, state, None)
+}
+
+impl integrity::Validate for [Recipe] {
+    fn validate(&self) -> Result<(), String> {
+        let mut ids = BTreeSet::new();
+        for recipe in self {
+            if !ids.insert(&recipe.id) {
+                return Err(format!("recipe id {} is saved twice", recipe.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl integrity::Validate for [RecipeRun] {
+    fn validate(&self) -> Result<(), String> {
+        match self.iter().find(|r| r.run_id.is_empty()) {
+            Some(run) => Err(format!("run of {} has no id", run.recipe_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl integrity::Validate for RecipeState {
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn vault(home: &TestHome) -> String {
+        home.write("Vault/.obsidian/app.json", "{}");
+        let root = home.join("Vault").to_string_lossy().to_string();
+        stores::register_store("Vault".into(), root, None)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn recipes_are_parsed_from_toml_and_validated() {
+        let home = TestHome::new();
+        let store_id = vault(&home);
+        home.write("Inbox/.keep", "");
+        let inbox = home.join("Inbox").to_string_lossy().to_string();
+        let source = format!(
+            r#"
+id = "inbox-to-vault"
+name = "Inbox to vault"
+
+[trigger]
+type = "watch"
+path = "{inbox}"
+
+[[steps]]
+action = "hash"
+
+[[steps]]
+action = "move"
+store_id = "{store_id}"
+
+[[steps]]
+action = "append_to_note"
+store_id = "{store_id}"
+text = "- {{link}} ({{sha256}})"
+"#
+        );
+        let recipe = upsert_recipe("acme".into(), source.into()).unwrap();
+        assert!(recipe.enabled);
+        assert_eq!(recipe.steps.len(), 3);
+        assert_eq!(list_recipes("acme".into()).unwrap().len(), 1);
+        assert!(list_recipes("globex".into()).unwrap().is_empty());
+
+        let bad = |value: serde_json::Value| upsert_recipe("acme".into(), value).unwrap_err().code;
+        assert_eq!(
+            bad(
+                serde_json::json!({ "id": "x", "name": "X", "trigger": { "type": "manual" }, "steps": [] })
+            ),
+            ErrorCode::InvalidInput
+        );
+        assert_eq!(
+            bad(serde_json::json!({
+                "id": "x", "name": "X",
+                "trigger": { "type": "schedule", "schedule": "every 1h" },
+                "steps": [{ "action": "hash" }]
+            })),
+            ErrorCode::InvalidInput
+        );
+        assert_eq!(
+            bad(serde_json::json!({
+                "id": "loop", "name": "Loop",
+                "trigger": { "type": "watch", "path": inbox },
+                "steps": [{ "action": "move", "to": inbox }]
+            })),
+            ErrorCode::InvalidInput
+        );
+        assert_eq!(bad("id = ".into()), ErrorCode::ParseError);
+    }
+
+    #[test]
+    fn files_a_recipe_wrote_dont_run_it_again() {
+        let home = TestHome::new();
+        vault(&home);
+        let recipe = Recipe {
+            id: "tidy".into(),
+            name: "Tidy".into(),
+            enabled: true,
+            trigger: Trigger::Manual,
+            steps: vec![Step::Hash],
+        };
+        let written = home.join("Vault/scan.pdf");
+        produced(&written, &["tidy".to_string()]);
+        assert!(loops(&recipe, &chain_of(&written)));
+        assert!(!loops(&recipe, &chain_of(&home.join("Vault/other.pdf"))));
+
+        let long: Vec<String> = (0..MAX_CHAIN).map(|i| format!("r{}", i)).collect();
+        produced(&written, &long);
+        assert!(loops(&recipe, &chain_of(&written)));
+
+        // Writes outside connected stores are refused
+        assert!(writable(&home.join("Vault/Attachments/scan.pdf")).is_ok());
+        let err = writable(&home.join("Elsewhere/scan.pdf")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PolicyDenied);
+        assert!(expand("relative/path").is_err());
+    }
+}
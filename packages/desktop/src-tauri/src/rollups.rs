@@ -0,0 +1,616 @@
+// Periodic rollups
+//
+// `generate_rollup` summarizes a week (Monday to Sunday, ISO 8601) or a
+// month into the vault's weekly or monthly note. It reads each day's daily
+// note, found through the daily-notes settings (see
+// `store_kinds::daily_note_path`), and gathers:
+// - completed tasks
+// - the sections under headings that match `heading_selectors`: a heading's
+//   text in any case, optionally with its `#`s to pin the level (`## Wins`)
+// - notes created during the period, from a fresh scan (see `file_query`;
+//   only where the filesystem records creation times)
+// The periodic note's folder, name format, and template come from the
+// Periodic Notes plugin's settings, defaulting to `gggg-[W]ww` and `YYYY-MM`
+// in the vault root and a built-in template. The template is rendered by
+// `templates` with `{{daily_notes}}`, `{{completed_tasks}}`,
+// `{{highlights}}`, `{{new_notes}}`, `{{period_start}}`, `{{period_end}}`,
+// and `{{title}}`; `{{date}}` is the period's first day, not today.
+//
+// Everything comes in day and path order, so the same vault gives the same
+// rollup. An existing note is a `Conflict` unless `on_conflict` says to
+// append to it (without the template's frontmatter) or to overwrite it.
+// `dry_run` returns the content without writing, as does a policy that forces
+// dry runs (see `dry_run`).
+
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_query::{self, FileFilter, FileQuery, TimeRange};
+use crate::store_kinds;
+use crate::text_style::{self, TextWriteOptions};
+use crate::{frontmatter, note_stats, notes, stores, tasks, templates, write_guard};
+
+const DEFAULT_TEMPLATE: &str = "# {{title}}
+
+{{period_start}} to {{period_end}}
+
+## Daily notes
+
+{{daily_notes}}
+
+## Completed tasks
+
+{{completed_tasks}}
+
+## Highlights
+
+{{highlights}}
+
+## New notes
+
+{{new_notes}}
+";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Week,
+    Month,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RollupConflict {
+    /// Fail with `Conflict` and leave the existing note alone.
+    #[default]
+    Fail,
+    Append,
+    Overwrite,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RollupOptions {
+    pub heading_selectors: Vec<String>,
+    /// Vault-relative path of the template, instead of the plugin's.
+    pub template: Option<String>,
+    pub on_conflict: RollupConflict,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RollupAction {
+    Create,
+    Append,
+    Overwrite,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Rollup {
+    pub path: String,
+    pub relative_path: String,
+    pub action: RollupAction,
+    /// The text written, or that would be: the whole note, or what's appended.
+    pub content: String,
+    pub written: bool,
+    /// `YYYY-MM-DD`, inclusive.
+    pub period_start: String,
+    pub period_end: String,
+    /// Relative paths of the daily notes found.
+    pub daily_notes: Vec<String>,
+    pub completed_tasks: usize,
+    pub highlights: usize,
+    pub new_notes: Vec<String>,
+    #[serde(flatten)]
+    pub dry_run: DryRun,
+}
+
+/// `.obsidian/plugins/periodic-notes/data.json`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PeriodicNotesConfig {
+    weekly: PeriodConfig,
+    monthly: PeriodConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PeriodConfig {
+    folder: String,
+    format: String,
+    template: String,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Roll the `period` containing `date` (`YYYY-MM-DD`) up into its periodic
+/// note in `vault_path`.
+#[tauri::command]
+pub async fn generate_rollup(
+    window: tauri::WebviewWindow,
+    vault_path: String,
+    period: Period,
+    date: String,
+    options: Option<RollupOptions>,
+) -> CommandResult<Rollup> {
+    let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| {
+        CommandError::new(ErrorCode::InvalidInput, format!("Invalid date: {}", date))
+            .with("date", &date)
+    })?;
+    let mut options = options.unwrap_or_default();
+    options.dry_run = dry_run::requested(window.label(), None, Some(options.dry_run));
+    tauri::async_runtime::spawn_blocking(move || {
+        rollup(Path::new(&vault_path), period, day, &options)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+pub fn rollup(
+    root: &Path,
+    period: Period,
+    date: NaiveDate,
+    options: &RollupOptions,
+) -> CommandResult<Rollup> {
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", root.display()),
+        )
+        .with("path", root.to_string_lossy()));
+    }
+    let selectors = options
+        .heading_selectors
+        .iter()
+        .map(|raw| Selector::parse(raw))
+        .collect::<CommandResult<Vec<_>>>()?;
+    let (start, end) = period.bounds(date);
+    let config = period.config(root);
+    let path = period.note_path(root, &config, start);
+    let relative_path = relative_key(root, &path);
+    let template = load_template(
+        root,
+        options.template.as_deref().unwrap_or(&config.template),
+    )?;
+
+    let mut daily_notes = Vec::new();
+    let mut completed = Vec::new();
+    let mut highlights = Vec::new();
+    for day in start.iter_days().take_while(|day| *day <= end) {
+        let daily = store_kinds::daily_note_path(root, day);
+        let Ok(bytes) = fs::read(&daily) else {
+            continue;
+        };
+        let text = read_lf(&bytes);
+        let key = relative_key(root, &daily);
+        let link = link_to(&key);
+        for task in tasks::parse_tasks(&text).iter().filter(|t| t.completed) {
+            completed.push(format!("- {} ({})", task.description, link));
+        }
+        for (heading, body) in sections(&text, &selectors) {
+            highlights.push(format!("**{}** ({})\n\n{}", heading, link, body));
+        }
+        daily_notes.push(key);
+    }
+    let new_notes: Vec<String> = created_between(root, start, end)?
+        .into_iter()
+        .filter(|key| *key != relative_path && !daily_notes.contains(key))
+        .collect();
+
+    let list = |keys: &[String]| {
+        keys.iter()
+            .map(|key| format!("- {}", link_to(key)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let title = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let variables: HashMap<String, String> = [
+        ("title", title),
+        ("period_start", start.format("%Y-%m-%d").to_string()),
+        ("period_end", end.format("%Y-%m-%d").to_string()),
+        ("daily_notes", list(&daily_notes)),
+        ("completed_tasks", completed.join("\n")),
+        ("highlights", highlights.join("\n\n")),
+        ("new_notes", list(&new_notes)),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+    let midnight = start.and_hms_opt(0, 0, 0).unwrap();
+    let rendered = templates::render(&template, &variables, midnight).text;
+
+    let action = match (path.is_file(), options.on_conflict) {
+        (false, _) => RollupAction::Create,
+        (true, RollupConflict::Fail) => return Err(exists(&path)),
+        (true, RollupConflict::Append) => RollupAction::Append,
+        (true, RollupConflict::Overwrite) => RollupAction::Overwrite,
+    };
+    let content = match action {
+        RollupAction::Append => without_frontmatter(&rendered)
+            .trim_start_matches('\n')
+            .to_string(),
+        RollupAction::Create | RollupAction::Overwrite => rendered,
+    };
+    if !options.dry_run {
+        write(&path, &content, action)?;
+    }
+    let planned = DryRun::outcome(options.dry_run, || {
+        let verb = match action {
+            RollupAction::Create => "Create",
+            RollupAction::Append => "Append to",
+            RollupAction::Overwrite => "Overwrite",
+        };
+        format!("{} {} ({} bytes)", verb, relative_path, content.len())
+    });
+    Ok(Rollup {
+        path: path.to_string_lossy().to_string(),
+        relative_path,
+        action,
+        content,
+        written: !options.dry_run,
+        period_start: start.format("%Y-%m-%d").to_string(),
+        period_end: end.format("%Y-%m-%d").to_string(),
+        daily_notes,
+        completed_tasks: completed.len(),
+        highlights: highlights.len(),
+        new_notes,
+        dry_run: planned,
+    })
+}
+
+// ─── Periods ────────────────────────────────────────────────────────────────
+
+impl Period {
+    /// First and last day of the period containing `date`.
+    fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Week => {
+                let start = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                (start, start + Duration::days(6))
+            }
+            Period::Month => {
+                let start = date.with_day(1).unwrap();
+                let next = match date.month() {
+                    12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+                    month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+                };
+                (start, next.unwrap().pred_opt().unwrap())
+            }
+        }
+    }
+
+    fn config(self, root: &Path) -> PeriodConfig {
+        let config = fs::read_to_string(root.join(".obsidian/plugins/periodic-notes/data.json"))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<PeriodicNotesConfig>(&raw).ok())
+            .unwrap_or_default();
+        match self {
+            Period::Week => config.weekly,
+            Period::Month => config.monthly,
+        }
+    }
+
+    fn note_path(self, root: &Path, config: &PeriodConfig, start: NaiveDate) -> PathBuf {
+        let format = match (config.format.as_str(), self) {
+            ("", Period::Week) => "gggg-[W]ww",
+            ("", Period::Month) => "YYYY-MM",
+            (format, _) => format,
+        };
+        let name = format!(
+            "{}.md",
+            store_kinds::format_moment(start.and_time(chrono::NaiveTime::MIN), format)
+        );
+        root.join(config.folder.trim_matches('/')).join(name)
+    }
+}
+
+/// Markdown notes whose creation time falls within `start..=end` local time.
+fn created_between(root: &Path, start: NaiveDate, end: NaiveDate) -> CommandResult<Vec<String>> {
+    let midnight = |day: NaiveDate| {
+        chrono::Local
+            .from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .map(|t| t.to_rfc3339())
+    };
+    let query = FileQuery {
+        filter: FileFilter {
+            created: Some(TimeRange {
+                after: midnight(start),
+                before: midnight(end + Duration::days(1)),
+                within_days: None,
+            }),
+            extensions: vec!["md".to_string()],
+            ..FileFilter::default()
+        },
+        any_of: Vec::new(),
+    };
+    let snapshot = stores::scan_for_index(root)?;
+    Ok(file_query::matching(root, &snapshot, &query)?
+        .into_iter()
+        .map(|file| file.relative_path)
+        .collect())
+}
+
+// ─── Sections ───────────────────────────────────────────────────────────────
+
+struct Selector {
+    level: Option<u8>,
+    /// Lowercase.
+    text: String,
+}
+
+impl Selector {
+    fn parse(raw: &str) -> CommandResult<Selector> {
+        let raw = raw.trim();
+        let hashes = raw.chars().take_while(|&c| c == '#').count();
+        let (level, text) = match raw[hashes..].strip_prefix([' ', '\t']) {
+            Some(text) if (1..=6).contains(&hashes) => (Some(hashes as u8), text.trim()),
+            _ => (None, raw),
+        };
+        if text.is_empty() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Empty heading selector: {:?}", raw),
+            )
+            .with("selector", raw));
+        }
+        Ok(Selector {
+            level,
+            text: text.to_lowercase(),
+        })
+    }
+
+    fn matches(&self, heading: &note_stats::Heading) -> bool {
+        self.level.is_none_or(|level| level == heading.level)
+            && heading.text.to_lowercase() == self.text
+    }
+}
+
+/// (heading text, section body) for each heading a selector matches. A
+/// section runs to the next heading at the same or a higher level; empty
+/// sections are left out.
+fn sections(text: &str, selectors: &[Selector]) -> Vec<(String, String)> {
+    if selectors.is_empty() {
+        return Vec::new();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let headings = note_stats::compute(text).headings;
+    let mut found = Vec::new();
+    for (i, heading) in headings.iter().enumerate() {
+        if !selectors.iter().any(|s| s.matches(heading)) {
+            continue;
+        }
+        let end = headings[i + 1..]
+            .iter()
+            .find(|h| h.level <= heading.level)
+            .map_or(lines.len(), |h| h.line - 1);
+        let mut body = &lines[heading.line..end];
+        while let [first, rest @ ..] = body {
+            if !first.trim().is_empty() {
+                break;
+            }
+            body = rest;
+        }
+        while let [rest @ .., last] = body {
+            if !last.trim().is_empty() {
+                break;
+            }
+            body = rest;
+        }
+        if !body.is_empty() {
+            found.push((heading.text.clone(), body.join("\n")));
+        }
+    }
+    found
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+/// The template at vault-relative `relative` (`.md` optional), or the
+/// built-in one when there's none.
+fn load_template(root: &Path, relative: &str) -> CommandResult<String> {
+    let relative = relative.trim_matches('/');
+    if relative.is_empty() {
+        return Ok(DEFAULT_TEMPLATE.to_string());
+    }
+    let path = match relative.ends_with(".md") {
+        true => root.join(relative),
+        false => root.join(format!("{}.md", relative)),
+    };
+    let bytes = fs::read(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => CommandError::new(
+            ErrorCode::NotFound,
+            format!("Template not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()),
+        _ => e.into(),
+    })?;
+    Ok(read_lf(&bytes))
+}
+
+fn write(path: &Path, content: &str, action: RollupAction) -> CommandResult<()> {
+    if action == RollupAction::Append {
+        // Keep a blank line between the note and the rollup
+        return notes::append(path, &format!("\n{}", content));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _guard = write_guard::lock();
+    let current = write_guard::read_current(path)?;
+    if action == RollupAction::Create && current.is_some() {
+        return Err(exists(path));
+    }
+    let style = current.as_deref().map(text_style::detect);
+    let (ending, bom) = text_style::resolve(path, style.as_ref(), &TextWriteOptions::default())?;
+    notes::write_atomic(path, &text_style::encode(content, ending, bom))?;
+    activity::record(ActivityKind::Write, path, None);
+    Ok(())
+}
+
+fn exists(path: &Path) -> CommandError {
+    CommandError::new(
+        ErrorCode::Conflict,
+        format!("{} already exists", path.display()),
+    )
+    .with("path", path.to_string_lossy())
+}
+
+fn without_frontmatter(text: &str) -> &str {
+    let Some(fm) = frontmatter::parse(text) else {
+        return text;
+    };
+    let skip: usize = text
+        .split_inclusive('\n')
+        .take(fm.end + 1)
+        .map(str::len)
+        .sum();
+    &text[skip..]
+}
+
+fn read_lf(bytes: &[u8]) -> String {
+    text_style::decode(bytes, &text_style::detect(bytes)).replace("\r\n", "\n")
+}
+
+fn relative_key(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn link_to(key: &str) -> String {
+    format!("[[{}]]", key.strip_suffix(".md").unwrap_or(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn day(raw: &str) -> NaiveDate {
+        raw.parse().unwrap()
+    }
+
+    const WEEK: &str = "---\ntype: weekly\n---\n# 2024-W10 (Mar 4)\n2024-03-04..2024-03-10\n\n\
+        - Ship it ([[Daily/2024-03-04]])\n- Review PR ([[Daily/2024-03-06]])\n\n\
+        **Wins** ([[Daily/2024-03-04]])\n\nLaunched\n\n\
+        **wins** ([[Daily/2024-03-06]])\n\n- Fixed bug\n";
+
+    #[test]
+    fn rolls_a_week_of_daily_notes_into_the_weekly_template() {
+        let home = TestHome::new();
+        home.write("Vault/.obsidian/daily-notes.json", r#"{"folder": "Daily"}"#);
+        home.write(
+            "Vault/.obsidian/plugins/periodic-notes/data.json",
+            r#"{"weekly": {"folder": "Reviews", "template": "Templates/Weekly"}}"#,
+        );
+        home.write(
+            "Vault/Templates/Weekly.md",
+            "---\ntype: weekly\n---\n# {{title}} ({{date:MMM D}})\n\
+             {{period_start}}..{{period_end}}\n\n{{completed_tasks}}\n\n{{highlights}}\n",
+        );
+        home.write(
+            "Vault/Daily/2024-03-04.md",
+            "# Monday\n- [x] Ship it ✅ 2024-03-04\n- [ ] Not yet\n\n## Wins\n\nLaunched\n\n\
+             ## Notes\nmeh\n",
+        );
+        home.write(
+            "Vault/Daily/2024-03-06.md",
+            "- [X] Review PR\n## wins\n- Fixed bug\n",
+        );
+        home.write("Vault/Daily/2024-03-11.md", "- [x] Next week\n");
+        let vault = home.join("Vault");
+        let mut options = RollupOptions {
+            heading_selectors: vec!["## Wins".to_string()],
+            dry_run: true,
+            ..RollupOptions::default()
+        };
+
+        let planned = rollup(&vault, Period::Week, day("2024-03-06"), &options).unwrap();
+        assert_eq!(planned.relative_path, "Reviews/2024-W10.md");
+        assert_eq!(planned.action, RollupAction::Create);
+        assert_eq!(planned.content, WEEK);
+        assert_eq!(
+            planned.daily_notes,
+            ["Daily/2024-03-04.md", "Daily/2024-03-06.md"]
+        );
+        assert_eq!((planned.completed_tasks, planned.highlights), (2, 2));
+        assert!(!planned.written && !vault.join("Reviews").exists());
+
+        options.dry_run = false;
+        let written = rollup(&vault, Period::Week, day("2024-03-10"), &options).unwrap();
+        assert_eq!(written.content, planned.content);
+        let note = vault.join("Reviews/2024-W10.md");
+        assert_eq!(fs::read_to_string(&note).unwrap(), WEEK);
+
+        let err = rollup(&vault, Period::Week, day("2024-03-04"), &options).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        options.on_conflict = RollupConflict::Append;
+        let appended = rollup(&vault, Period::Week, day("2024-03-04"), &options).unwrap();
+        assert_eq!(appended.action, RollupAction::Append);
+        assert!(appended.content.starts_with("# 2024-W10"));
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            format!("{}\n{}", WEEK, appended.content)
+        );
+    }
+
+    #[test]
+    fn periods_cover_whole_iso_weeks_and_months() {
+        assert_eq!(
+            Period::Week.bounds(day("2024-03-10")),
+            (day("2024-03-04"), day("2024-03-10"))
+        );
+        assert_eq!(
+            Period::Month.bounds(day("2024-02-10")),
+            (day("2024-02-01"), day("2024-02-29"))
+        );
+        assert_eq!(
+            Period::Month.bounds(day("2024-12-31")),
+            (day("2024-12-01"), day("2024-12-31"))
+        );
+        let root = Path::new("/vault");
+        let config = PeriodConfig::default();
+        let (start, _) = Period::Week.bounds(day("2024-12-31"));
+        assert_eq!(
+            Period::Week.note_path(root, &config, start),
+            root.join("2025-W01.md")
+        );
+        assert_eq!(
+            Period::Month.note_path(root, &config, day("2024-12-01")),
+            root.join("2024-12.md")
+        );
+    }
+
+    #[test]
+    fn selectors_match_heading_text_and_optionally_level() {
+        let text = "# Wins\none\n## Wins\ntwo\n### Later\nthree\n## Other\n\n## WINS\n\n";
+        let any = [Selector::parse("wins").unwrap()];
+        assert_eq!(
+            sections(text, &any),
+            [
+                (
+                    "Wins".to_string(),
+                    "one\n## Wins\ntwo\n### Later\nthree\n## Other\n\n## WINS".to_string()
+                ),
+                ("Wins".to_string(), "two\n### Later\nthree".to_string()),
+            ]
+        );
+        let level = [Selector::parse("## Wins").unwrap()];
+        assert_eq!(sections(text, &level).len(), 1);
+        assert!(Selector::parse("  ").is_err());
+    }
+}
@@ -0,0 +1,560 @@
+// Scheduled jobs
+//
+// Recurring backend work registers here instead of running its own timer.
+// That covers store rescans, session expiry checks, and the trash retention
+// sweep. A job has an id, a default schedule, and a handler. A schedule is
+// `every <n><s|m|h|d>` or `daily HH:MM` in local time. The user can turn a job
+// off or give it a different schedule; those overrides are saved in
+// `settings.scheduler.jobs`.
+//
+// A job never runs twice at once. Its recent runs are saved to
+// `scheduler/runs.json` in the state directory, so `list_scheduled_jobs` can show them
+// and intervals carry over restarts. With `settings.scheduler.
+// respect_power_state` on, scheduled runs wait while the OS is in battery
+// saver. Heavy jobs also wait while `system_state` says to defer them.
+// Nothing runs on schedule in read-only mode (see `read_only`) or maintenance
+// mode (see `maintenance`). Otherwise `run_job_now` always runs.
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::maintenance::MaintenanceMode;
+use crate::read_only::ReadOnlyMode;
+use crate::{settings, system_state};
+
+/// How often due jobs are checked for.
+const TICK: Duration = Duration::from_secs(30);
+/// Nothing scheduled runs in the first minute after launch.
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_RUNS_KEPT: usize = 20;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+type Handler = Arc<dyn Fn(&AppHandle) -> Result<Option<String>, String> + Send + Sync>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Schedule {
+    Every(Duration),
+    /// At this local time each day.
+    Daily {
+        hour: u32,
+        minute: u32,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JobLoad {
+    Light,
+    /// Walks the disk or the network; deferred on low battery.
+    Heavy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOutcome {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JobRun {
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub outcome: JobOutcome,
+    /// The handler's summary or error.
+    pub message: Option<String>,
+    /// Started with `run_job_now`.
+    pub manual: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ScheduledJob {
+    pub id: String,
+    pub description: String,
+    pub schedule: String,
+    pub default_schedule: String,
+    pub load: JobLoad,
+    pub enabled: bool,
+    pub running: bool,
+    /// `None` while disabled.
+    pub next_run_at: Option<String>,
+    /// Recent runs, newest first.
+    pub runs: Vec<JobRun>,
+}
+
+struct Job {
+    description: &'static str,
+    default_schedule: Schedule,
+    load: JobLoad,
+    enabled_by_default: bool,
+    handler: Handler,
+}
+
+struct Inner {
+    jobs: BTreeMap<&'static str, Job>,
+    running: HashSet<String>,
+    runs: BTreeMap<String, Vec<JobRun>>,
+    started: DateTime<Utc>,
+    paused_for_power: bool,
+    paused_for_read_only: bool,
+    paused_for_maintenance: bool,
+}
+
+/// Managed state: registered jobs and their run history.
+pub struct Scheduler {
+    inner: Arc<(Mutex<Inner>, Condvar)>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new((
+                Mutex::new(Inner {
+                    jobs: BTreeMap::new(),
+                    running: HashSet::new(),
+                    runs: load_runs(),
+                    started: Utc::now(),
+                    paused_for_power: false,
+                    paused_for_read_only: false,
+                    paused_for_maintenance: false,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+}
+
+// ─── Schedules ──────────────────────────────────────────────────────────────
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim().to_ascii_lowercase();
+        if let Some(every) = spec.strip_prefix("every ") {
+            let every = every.trim();
+            let unit_at = every
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or("missing unit (s, m, h, or d)")?;
+            let count: u64 = every[..unit_at]
+                .parse()
+                .map_err(|_| format!("invalid count in {:?}", every))?;
+            let seconds = match every[unit_at..].trim() {
+                "s" => 1,
+                "m" => 60,
+                "h" => 3600,
+                "d" => 86400,
+                unit => return Err(format!("unknown unit {:?}", unit)),
+            };
+            let interval = Duration::from_secs(count.saturating_mul(seconds));
+            if interval < MIN_INTERVAL {
+                return Err("interval must be at least a minute".into());
+            }
+            return Ok(Schedule::Every(interval));
+        }
+        if let Some(time) = spec.strip_prefix("daily ") {
+            let (hour, minute) = time.trim().split_once(':').ok_or("expected daily HH:MM")?;
+            let hour: u32 = hour.parse().map_err(|_| "invalid hour")?;
+            let minute: u32 = minute.parse().map_err(|_| "invalid minute")?;
+            if hour > 23 || minute > 59 {
+                return Err("time out of range".into());
+            }
+            return Ok(Schedule::Daily { hour, minute });
+        }
+        Err("expected `every <n><s|m|h|d>` or `daily HH:MM`".into())
+    }
+
+    pub fn spec(&self) -> String {
+        match self {
+            Schedule::Every(interval) => {
+                let secs = interval.as_secs();
+                match secs {
+                    s if s % 86400 == 0 => format!("every {}d", s / 86400),
+                    s if s % 3600 == 0 => format!("every {}h", s / 3600),
+                    s if s % 60 == 0 => format!("every {}m", s / 60),
+                    s => format!("every {}s", s),
+                }
+            }
+            Schedule::Daily { hour, minute } => format!("daily {:02}:{:02}", hour, minute),
+        }
+    }
+
+    /// When a job last started at `last` (or never) should next run.
+    pub fn next_after(&self, last: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DateTime<Utc> {
+        match *self {
+            Schedule::Every(interval) => match last {
+                Some(last) => last + chrono::Duration::from_std(interval).unwrap_or_default(),
+                None => now,
+            },
+            Schedule::Daily { hour, minute } => {
+                let base = last.unwrap_or(now).with_timezone(&Local);
+                let mut date = base.date_naive();
+                loop {
+                    let at = date
+                        .and_hms_opt(hour, minute, 0)
+                        .and_then(|t| Local.from_local_datetime(&t).earliest());
+                    // A time skipped by a DST change moves on to the next day
+                    if let Some(at) = at.filter(|at| *at > base) {
+                        return at.with_timezone(&Utc);
+                    }
+                    date = date.succ_opt().unwrap_or(date);
+                }
+            }
+        }
+    }
+}
+
+// ─── Registration ───────────────────────────────────────────────────────────
+
+impl Scheduler {
+    /// Add a job. Handlers run on their own thread and return a short
+    /// summary for the run history.
+    pub fn register(
+        &self,
+        id: &'static str,
+        description: &'static str,
+        default_schedule: Schedule,
+        load: JobLoad,
+        enabled_by_default: bool,
+        handler: impl Fn(&AppHandle) -> Result<Option<String>, String> + Send + Sync + 'static,
+    ) {
+        self.inner.0.lock().unwrap().jobs.insert(
+            id,
+            Job {
+                description,
+                default_schedule,
+                load,
+                enabled_by_default,
+                handler: Arc::new(handler),
+            },
+        );
+    }
+
+    /// Start running due jobs; runs for the life of the app.
+    pub fn start(&self, app: AppHandle) {
+        let shared = self.inner.clone();
+        std::thread::spawn(move || loop {
+            let settings = settings::load_settings().scheduler;
+            let due: Vec<String> = {
+                let mut inner = shared.0.lock().unwrap();
+                let now = Utc::now();
+                let due: Vec<String> = inner
+                    .jobs
+                    .keys()
+                    .filter(|id| !inner.running.contains(**id))
+                    .filter(|id| next_run(&inner, id, &settings, now).is_some_and(|at| at <= now))
+                    .map(|id| id.to_string())
+                    .collect();
+
+                if due.is_empty() {
+                    due
+                } else {
+                    let state = system_state::current();
+                    let saving = settings.respect_power_state && state.battery_saver;
+                    if saving != inner.paused_for_power {
+                        log::info!(
+                            "[scheduler] {} for battery saver",
+                            if saving { "pausing" } else { "resuming" }
+                        );
+                        inner.paused_for_power = saving;
+                    }
+                    let read_only = app.state::<ReadOnlyMode>().enabled();
+                    if read_only != inner.paused_for_read_only {
+                        log::info!(
+                            "[scheduler] {} for read-only mode",
+                            if read_only { "pausing" } else { "resuming" }
+                        );
+                        inner.paused_for_read_only = read_only;
+                    }
+                    let maintenance = app.state::<MaintenanceMode>().enabled();
+                    if maintenance != inner.paused_for_maintenance {
+                        log::info!(
+                            "[scheduler] {} for maintenance mode",
+                            if maintenance { "pausing" } else { "resuming" }
+                        );
+                        inner.paused_for_maintenance = maintenance;
+                    }
+                    let deferred = state.throttle.heavy_jobs_deferred;
+                    due.into_iter()
+                        .filter(|_| !saving && !read_only && !maintenance)
+                        .filter(|id| {
+                            let heavy = inner.jobs[id.as_str()].load == JobLoad::Heavy;
+                            if heavy && deferred.is_some() {
+                                log::debug!("[scheduler] deferring {}: {:?}", id, deferred);
+                            }
+                            !(heavy && deferred.is_some())
+                        })
+                        .collect()
+                }
+            };
+
+            for id in due {
+                if let Ok(slot) = RunSlot::take(&shared, &id) {
+                    let app = app.clone();
+                    std::thread::spawn(move || execute(slot, &app, false));
+                }
+            }
+            let inner = shared.0.lock().unwrap();
+            let _ = shared.1.wait_timeout(inner, TICK).unwrap();
+        });
+    }
+
+    /// Ids of the jobs running now.
+    pub fn running_jobs(&self) -> Vec<String> {
+        self.inner
+            .0
+            .lock()
+            .unwrap()
+            .running
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn wake(&self) {
+        self.inner.1.notify_all();
+    }
+}
+
+/// Marks a job as running until dropped, so a panicking handler can't leave
+/// it stuck.
+struct RunSlot {
+    shared: Arc<(Mutex<Inner>, Condvar)>,
+    id: String,
+}
+
+impl RunSlot {
+    fn take(shared: &Arc<(Mutex<Inner>, Condvar)>, id: &str) -> CommandResult<Self> {
+        let mut inner = shared.0.lock().unwrap();
+        if !inner.jobs.contains_key(id) {
+            return Err(unknown_job(id));
+        }
+        if !inner.running.insert(id.to_string()) {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyRunning,
+                format!("Job {} is already running", id),
+            )
+            .with("job_id", id));
+        }
+        Ok(Self {
+            shared: shared.clone(),
+            id: id.to_string(),
+        })
+    }
+}
+
+impl Drop for RunSlot {
+    fn drop(&mut self) {
+        self.shared.0.lock().unwrap().running.remove(&self.id);
+    }
+}
+
+fn execute(slot: RunSlot, app: &AppHandle, manual: bool) -> JobRun {
+    let handler = slot.shared.0.lock().unwrap().jobs[slot.id.as_str()]
+        .handler
+        .clone();
+    let started_at = Utc::now();
+    let clock = Instant::now();
+    let result = handler(app);
+    let run = JobRun {
+        started_at: started_at.to_rfc3339(),
+        duration_ms: clock.elapsed().as_millis() as u64,
+        outcome: if result.is_ok() {
+            JobOutcome::Succeeded
+        } else {
+            JobOutcome::Failed
+        },
+        message: result.unwrap_or_else(Some),
+        manual,
+    };
+    match run.outcome {
+        JobOutcome::Succeeded => {
+            log::info!("[scheduler] {} finished in {}ms", slot.id, run.duration_ms)
+        }
+        JobOutcome::Failed => log::warn!("[scheduler] {} failed: {:?}", slot.id, run.message),
+    }
+
+    let mut inner = slot.shared.0.lock().unwrap();
+    let runs = inner.runs.entry(slot.id.clone()).or_default();
+    runs.push(run.clone());
+    if runs.len() > MAX_RUNS_KEPT {
+        runs.drain(..runs.len() - MAX_RUNS_KEPT);
+    }
+    if let Err(e) = save_runs(&inner.runs) {
+        log::warn!("[scheduler] saving run history failed: {}", e);
+    }
+    run
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn list_scheduled_jobs(state: State<'_, Scheduler>) -> Vec<ScheduledJob> {
+    let settings = settings::load_settings().scheduler;
+    let inner = state.inner.0.lock().unwrap();
+    inner
+        .jobs
+        .keys()
+        .map(|id| describe(&inner, id, &settings))
+        .collect()
+}
+
+/// Run a job now, whatever its schedule or power state, and wait for it.
+#[tauri::command]
+pub async fn run_job_now(
+    app: AppHandle,
+    state: State<'_, Scheduler>,
+    id: String,
+) -> CommandResult<JobRun> {
+    let slot = RunSlot::take(&state.inner, &id)?;
+    tauri::async_runtime::spawn_blocking(move || execute(slot, &app, true))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+#[tauri::command]
+pub fn set_job_enabled(
+    state: State<'_, Scheduler>,
+    id: String,
+    enabled: bool,
+) -> CommandResult<ScheduledJob> {
+    update_override(&state, &id, |job| job.enabled = Some(enabled))
+}
+
+/// Give a job a new schedule; `None` goes back to its default.
+#[tauri::command]
+pub fn set_job_schedule(
+    state: State<'_, Scheduler>,
+    id: String,
+    spec: Option<String>,
+) -> CommandResult<ScheduledJob> {
+    let spec = match spec {
+        Some(spec) => Some(
+            Schedule::parse(&spec)
+                .map_err(|e| {
+                    CommandError::new(ErrorCode::InvalidInput, format!("Invalid schedule: {}", e))
+                        .with("spec", &spec)
+                })?
+                .spec(),
+        ),
+        None => None,
+    };
+    update_override(&state, &id, |job| job.schedule = spec)
+}
+
+fn update_override(
+    state: &Scheduler,
+    id: &str,
+    change: impl FnOnce(&mut settings::JobOverride),
+) -> CommandResult<ScheduledJob> {
+    if !state.inner.0.lock().unwrap().jobs.contains_key(id) {
+        return Err(unknown_job(id));
+    }
+    let mut settings = settings::load_settings();
+    change(settings.scheduler.jobs.entry(id.to_string()).or_default());
+    settings::save_settings(&settings)?;
+    state.wake();
+
+    let inner = state.inner.0.lock().unwrap();
+    Ok(describe(&inner, id, &settings.scheduler))
+}
+
+fn unknown_job(id: &str) -> CommandError {
+    CommandError::new(ErrorCode::NotFound, format!("No scheduled job {}", id)).with("job_id", id)
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+/// The job's schedule and whether it's on, after the user's overrides.
+fn effective(job_id: &str, job: &Job, settings: &settings::SchedulerSettings) -> (Schedule, bool) {
+    let overrides = settings.jobs.get(job_id);
+    let schedule = overrides
+        .and_then(|o| o.schedule.as_deref())
+        .and_then(|spec| match Schedule::parse(spec) {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                log::warn!(
+                    "[scheduler] ignoring schedule {:?} for {}: {}",
+                    spec,
+                    job_id,
+                    e
+                );
+                None
+            }
+        })
+        .unwrap_or(job.default_schedule);
+    let enabled = overrides
+        .and_then(|o| o.enabled)
+        .unwrap_or(job.enabled_by_default);
+    (schedule, enabled)
+}
+
+fn next_run(
+    inner: &Inner,
+    id: &str,
+    settings: &settings::SchedulerSettings,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let (schedule, enabled) = effective(id, &inner.jobs[id], settings);
+    if !enabled {
+        return None;
+    }
+    // Manual runs don't push the schedule back
+    let last = inner
+        .runs
+        .get(id)
+        .and_then(|runs| runs.iter().rev().find(|r| !r.manual))
+        .and_then(|r| DateTime::parse_from_rfc3339(&r.started_at).ok())
+        .map(|t| t.with_timezone(&Utc));
+    let earliest = inner.started + chrono::Duration::from_std(STARTUP_DELAY).unwrap_or_default();
+    Some(schedule.next_after(last, now).max(earliest))
+}
+
+fn describe(inner: &Inner, id: &str, settings: &settings::SchedulerSettings) -> ScheduledJob {
+    let job = &inner.jobs[id];
+    let (schedule, enabled) = effective(id, job, settings);
+    ScheduledJob {
+        id: id.to_string(),
+        description: job.description.to_string(),
+        schedule: schedule.spec(),
+        default_schedule: job.default_schedule.spec(),
+        load: job.load,
+        enabled,
+        running: inner.running.contains(id),
+        next_run_at: next_run(inner, id, settings, Utc::now()).map(|t| t.to_rfc3339()),
+        runs: inner
+            .runs
+            .get(id)
+            .map(|runs| runs.iter().rev().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn runs_path() -> CommandResult<PathBuf> {
+    Ok(home::state_dir()?.join("scheduler").join("runs.json"))
+}
+
+fn load_runs() -> BTreeMap<String, Vec<JobRun>> {
+    runs_path()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_runs(runs: &BTreeMap<String, Vec<JobRun>>) -> Result<(), String> {
+    let path = runs_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_vec_pretty(runs).map_err(|e| e.to_string())?;
+    fs::write(path, raw).map_err(|e| e.to_string())
+}
@@ -0,0 +1,253 @@
+// Data file versions
+//
+// The JSON files we own carry a `schema_version`. A `Schema` holds the
+// migrations for one kind of file: the first upgrades version 1 to 2, the
+// next 2 to 3, and so on, so the current version is one more than the number
+// of migrations. A file written before it was versioned has no
+// `schema_version` and counts as version 1.
+//
+// `decode` runs the missing migrations in order as a file is loaded. The
+// upgrade happens in memory; the file is rewritten in the current format the
+// next time it's saved. A file from a newer version of the app is still read
+// as far as this version understands it, but `guard_write` refuses to replace
+// it with `DataFromNewerVersion`, since saving would drop whatever the newer
+// version added.
+//
+// Versioned: session metadata and payloads, settings, and the vault file.
+// Files whose top level is a list (the store registry, queues, and caches)
+// are not: adding the field means changing their shape, which builds from
+// before versioning would read as corrupt and discard.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+
+/// Upgrades a file's top-level object by one version, in place.
+pub type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+const VERSION_FIELD: &str = "schema_version";
+
+pub struct Schema {
+    /// Names the file in errors.
+    pub file: &'static str,
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    pub const fn new(file: &'static str, migrations: &'static [Migration]) -> Self {
+        Self { file, migrations }
+    }
+
+    pub const fn current(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    /// Parse `raw`, upgrading it to the current version first.
+    pub fn decode<T: DeserializeOwned>(&self, raw: &[u8]) -> Result<T, String> {
+        let value: Value = serde_json::from_slice(raw).map_err(|e| e.to_string())?;
+        serde_json::from_value(self.upgrade(value)?).map_err(|e| e.to_string())
+    }
+
+    /// Run the migrations `value` is missing. Newer versions pass through.
+    pub fn upgrade(&self, value: Value) -> Result<Value, String> {
+        let Value::Object(mut map) = value else {
+            return Err(format!("{} is not a JSON object", self.file));
+        };
+        let mut version = version_of(&map);
+        while version < self.current() {
+            (self.migrations[version as usize - 1])(&mut map).map_err(|e| {
+                format!(
+                    "Can't upgrade {} from version {}: {}",
+                    self.file, version, e
+                )
+            })?;
+            version += 1;
+            map.insert(VERSION_FIELD.to_string(), version.into());
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Serialize `value` with the current `schema_version`.
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T, pretty: bool) -> CommandResult<Vec<u8>> {
+        let mut value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        if let Value::Object(map) = &mut value {
+            map.insert(VERSION_FIELD.to_string(), self.current().into());
+        }
+        let bytes = if pretty {
+            serde_json::to_vec_pretty(&value)
+        } else {
+            serde_json::to_vec(&value)
+        };
+        Ok(bytes.map_err(|e| e.to_string())?)
+    }
+
+    /// Refuse to replace `existing`, the contents of `path`, when a newer
+    /// version of the app wrote it. Unparseable contents are left to the
+    /// caller's corruption handling.
+    pub fn guard_write(&self, path: &Path, existing: &[u8]) -> CommandResult<()> {
+        let Ok(Value::Object(map)) = serde_json::from_slice::<Value>(existing) else {
+            return Ok(());
+        };
+        let version = version_of(&map);
+        if version <= self.current() {
+            return Ok(());
+        }
+        log::warn!(
+            "[schema] not overwriting {} (version {}, this build supports {})",
+            path.display(),
+            version,
+            self.current()
+        );
+        Err(CommandError::new(
+            ErrorCode::DataFromNewerVersion,
+            format!(
+                "{} was written by a newer version of the app (format {}, this version supports {})",
+                path.display(),
+                version,
+                self.current()
+            ),
+        )
+        .with("path", path.to_string_lossy())
+        .with("schema_version", version)
+        .with("supported", self.current()))
+    }
+
+    /// `guard_write` against whatever is at `path` now.
+    pub fn guard_path(&self, path: &Path) -> CommandResult<()> {
+        match fs::read(path) {
+            Ok(existing) => self.guard_write(path, &existing),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn version_of(map: &Map<String, Value>) -> u32 {
+    map.get(VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .map_or(1, |v| v.clamp(1, u32::MAX as u64) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::SessionPayload;
+    use crate::settings::{self, Settings};
+    use crate::testing::TestHome;
+
+    const PAYLOAD_V1: &str = include_str!("../fixtures/schema/session-payload-v1.json");
+    const PAYLOAD_V2: &str = include_str!("../fixtures/schema/session-payload-v2.json");
+    const META_V1: &str = include_str!("../fixtures/schema/session-meta-v1.json");
+    const SETTINGS_V1: &str = include_str!("../fixtures/schema/config-v1.json");
+
+    fn add_title(map: &mut Map<String, Value>) -> Result<(), String> {
+        map.insert("title".into(), "untitled".into());
+        Ok(())
+    }
+
+    fn rename_title(map: &mut Map<String, Value>) -> Result<(), String> {
+        let title = map.remove("title").ok_or("no title")?;
+        map.insert("name".into(), title);
+        Ok(())
+    }
+
+    const NOTES: Schema = Schema::new("notes.json", &[add_title, rename_title]);
+
+    #[test]
+    fn migrations_run_in_order_from_the_stored_version() {
+        let upgraded = NOTES.upgrade(serde_json::json!({ "body": "x" })).unwrap();
+        assert_eq!(
+            upgraded,
+            serde_json::json!({ "body": "x", "name": "untitled", "schema_version": 3 })
+        );
+        // Version 2 only needs the second step
+        let upgraded = NOTES
+            .upgrade(serde_json::json!({ "title": "Plan", "schema_version": 2 }))
+            .unwrap();
+        assert_eq!(upgraded["name"], "Plan");
+        // Newer files are read as they are
+        let newer = serde_json::json!({ "name": "Plan", "schema_version": 9 });
+        assert_eq!(NOTES.upgrade(newer.clone()).unwrap(), newer);
+
+        let err = NOTES
+            .upgrade(serde_json::json!({ "schema_version": 2 }))
+            .unwrap_err();
+        assert!(err.contains("from version 2"), "{}", err);
+        assert!(NOTES.upgrade(serde_json::json!([])).is_err());
+    }
+
+    #[test]
+    fn session_payloads_from_each_version_load() {
+        let schema = &crate::sessions::PAYLOAD_SCHEMA;
+        let upgraded = schema
+            .upgrade(serde_json::from_str(PAYLOAD_V1).unwrap())
+            .unwrap();
+        assert_eq!(upgraded["schema_version"], 2);
+        let cookie = &upgraded["cookies"][0];
+        assert_eq!(cookie["path"], "/");
+        assert_eq!(cookie["secure"], false);
+        assert_eq!(cookie["http_only"], false);
+        assert_eq!(cookie["host_only"], false);
+        assert_eq!(cookie["same_site"], Value::Null);
+
+        let v1: SessionPayload = schema.decode(PAYLOAD_V1.as_bytes()).unwrap();
+        assert_eq!(v1.cookies[0].name, "sessionKey");
+        let v2: SessionPayload = schema.decode(PAYLOAD_V2.as_bytes()).unwrap();
+        assert_eq!(v2.cookies[0].path, "/api");
+        assert!(v2.cookies[0].secure && v2.cookies[0].host_only);
+
+        // Round trip stamps the current version
+        let encoded = schema.encode(&v1, false).unwrap();
+        let value: Value = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(value["schema_version"], schema.current());
+    }
+
+    #[test]
+    fn session_meta_and_settings_from_version_1_load() {
+        let meta: crate::sessions::SessionMeta = crate::sessions::META_SCHEMA
+            .decode(META_V1.as_bytes())
+            .unwrap();
+        assert_eq!(meta.schema_version, 1);
+        assert_eq!(meta.provider, "claude");
+
+        let settings: Settings = settings::SETTINGS_SCHEMA
+            .decode(SETTINGS_V1.as_bytes())
+            .unwrap();
+        assert_eq!(
+            settings.orchestrator_url,
+            "https://orchestrator.example.com"
+        );
+        assert_eq!(settings.session_expiry_warning_hours, 12);
+        // Sections added later take their defaults
+        assert!(!settings.automation.enabled);
+    }
+
+    #[test]
+    fn newer_files_are_not_overwritten() {
+        let home = TestHome::new();
+        let path = settings::settings_path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let newer = serde_json::json!({ "schema_version": 99, "from_the_future": true });
+        fs::write(&path, newer.to_string()).unwrap();
+
+        // Still readable
+        let _ = settings::load_settings();
+        let err = settings::save_settings(&Settings::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::DataFromNewerVersion);
+        assert_eq!(err.params["schema_version"], 99);
+        assert_eq!(err.params["supported"], settings::SETTINGS_SCHEMA.current());
+        let kept: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(kept, newer);
+
+        // Files this version wrote, or can't parse, are fine to replace
+        fs::write(&path, "{ not json").unwrap();
+        settings::save_settings(&Settings::default()).unwrap();
+        let saved: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], settings::SETTINGS_SCHEMA.current());
+        drop(home);
+    }
+}
@@ -0,0 +1,463 @@
+// OS keychain access
+//
+// Thin wrapper over the platform credential store (macOS Keychain, Windows
+// Credential Manager, Secret Service on Linux). Values are never logged.
+//
+// Also holds the session vault key used to encrypt provider session payloads
+// at rest; the payloads themselves are too large for some keychains.
+//
+// The generic secrets commands (orchestrator API key, webhook secrets,
+// WhatsApp credentials) are scoped by tenant and namespace; from a tenant
+// window they only reach that tenant's secrets. Namespaces the backend keeps
+// for itself, such as the note keys, are refused by the commands. When no
+// keychain service is present they fall back to an encrypted file. An index
+// of key names — never values — backs `list_secret_keys`, since keychains
+// can't be enumerated portably.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::sessions::validate_id;
+use crate::{audit, tenant_windows, vault};
+
+const SERVICE: &str = "com.agentvbx.desktop";
+
+/// Each tenant's note encryption key (see `note_encryption`).
+pub const NOTE_KEY_NAMESPACE: &str = "note_keys";
+/// Reachable through `write` and `read` only, never the commands.
+const RESERVED_NAMESPACES: &[&str] = &[NOTE_KEY_NAMESPACE];
+
+pub fn store(key: &str, value: &str) -> Result<(), String> {
+    entry(key)?
+        .set_password(value)
+        .map_err(|e| format!("Keychain write failed for {}: {}", key, e))
+}
+
+/// Returns `Ok(None)` when no secret is stored under `key`.
+pub fn load(key: &str) -> Result<Option<String>, String> {
+    match entry(key)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Keychain read failed for {}: {}", key, e)),
+    }
+}
+
+pub fn delete(key: &str) -> Result<(), String> {
+    match entry(key)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Keychain delete failed for {}: {}", key, e)),
+    }
+}
+
+fn entry(key: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, key).map_err(|e| e.to_string())
+}
+
+// ─── Session Vault Encryption ───────────────────────────────────────────────
+
+const VAULT_KEY: &str = "session-vault-key";
+const SEALED_MAGIC: &[u8; 4] = b"AVX1";
+
+/// Whether the OS keychain can be used at all on this machine.
+pub fn keychain_available() -> bool {
+    load(VAULT_KEY).is_ok()
+}
+
+/// Whether session payloads can be encrypted: a keychain, or a configured
+/// master passphrase.
+pub fn vault_available() -> bool {
+    vault::is_configured() || keychain_available()
+}
+
+/// Encrypt with the vault key (ChaCha20-Poly1305). Output is
+/// `AVX1 || nonce || ciphertext`.
+pub fn seal(plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    seal_with(&vault_key()?, plaintext)
+}
+
+pub fn open(sealed: &[u8]) -> Result<Vec<u8>, String> {
+    open_with(&vault_key()?, sealed)
+}
+
+pub fn seal_with(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let mut sealed = Vec::with_capacity(4 + nonce.len() + ciphertext.len());
+    sealed.extend_from_slice(SEALED_MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub fn open_with(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+    let body = sealed
+        .strip_prefix(SEALED_MAGIC.as_slice())
+        .filter(|b| b.len() > 12)
+        .ok_or("Not a sealed payload")?;
+    let (nonce, ciphertext) = body.split_at(12);
+    let cipher = ChaCha20Poly1305::new(key.into());
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed (wrong key or corrupted payload)".to_string())
+}
+
+/// The master passphrase key when one is configured; otherwise the keychain
+/// key, generated on first use.
+fn vault_key() -> Result<[u8; 32], String> {
+    use base64::Engine;
+    if let Some(key) = vault::passphrase_key() {
+        return key;
+    }
+    use chacha20poly1305::aead::{KeyInit, OsRng};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    if let Some(encoded) = load(VAULT_KEY)? {
+        let bytes = engine
+            .decode(encoded)
+            .map_err(|_| "Corrupted vault key in keychain".to_string())?;
+        return bytes
+            .try_into()
+            .map_err(|_| "Corrupted vault key in keychain".to_string());
+    }
+
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    store(VAULT_KEY, &engine.encode(key))?;
+    Ok(key.into())
+}
+
+// ─── Generic Secrets ────────────────────────────────────────────────────────
+
+pub const FALLBACK_FILE: &str = "secrets.enc";
+const FALLBACK_KEY_FILE: &str = "secrets.key";
+const INDEX_FILE: &str = "secrets-index.json";
+
+/// Serializes read-modify-write of the index and fallback files.
+pub static FILES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretsBackend {
+    Keychain,
+    /// `secrets.enc` in the data directory, keyed by a local key file.
+    EncryptedFile,
+}
+
+/// Which store generic secrets use, decided once per run.
+pub fn backend() -> SecretsBackend {
+    static BACKEND: OnceLock<SecretsBackend> = OnceLock::new();
+    *BACKEND.get_or_init(|| {
+        if keychain_available() {
+            SecretsBackend::Keychain
+        } else {
+            log::warn!("[secrets] no keychain service — using encrypted file fallback");
+            SecretsBackend::EncryptedFile
+        }
+    })
+}
+
+/// tenant → namespace → key names.
+type SecretIndex = BTreeMap<String, BTreeMap<String, BTreeSet<String>>>;
+
+#[tauri::command]
+pub fn set_secret(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    namespace: String,
+    key: String,
+    value: String,
+) -> CommandResult<()> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    public_namespace(&namespace)?;
+    write(&tenant_id, &namespace, &key, &value)
+}
+
+#[tauri::command]
+pub fn get_secret(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    namespace: String,
+    key: String,
+) -> CommandResult<Option<String>> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    public_namespace(&namespace)?;
+    read(&tenant_id, &namespace, &key)
+}
+
+/// `set_secret` for other modules.
+pub fn write(tenant_id: &str, namespace: &str, key: &str, value: &str) -> CommandResult<()> {
+    let name = secret_name(tenant_id, namespace, key)?;
+    let _guard = FILES_LOCK.lock().unwrap();
+    match backend() {
+        SecretsBackend::Keychain => store(&name, value)?,
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let mut values = read_fallback()?;
+            values.insert(name, value.to_string());
+            write_fallback(&values)?;
+        }
+    }
+
+    let mut index = read_index();
+    index
+        .entry(tenant_id.to_string())
+        .or_default()
+        .entry(namespace.to_string())
+        .or_default()
+        .insert(key.to_string());
+    write_index(&index)?;
+
+    audit::record(
+        "secret_set",
+        Some(tenant_id),
+        serde_json::json!({ "namespace": namespace, "key": key }),
+    );
+    Ok(())
+}
+
+/// `get_secret` for other modules.
+pub fn read(tenant_id: &str, namespace: &str, key: &str) -> CommandResult<Option<String>> {
+    let name = secret_name(tenant_id, namespace, key)?;
+    match backend() {
+        SecretsBackend::Keychain => Ok(load(&name)?),
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let _guard = FILES_LOCK.lock().unwrap();
+            Ok(read_fallback()?.remove(&name))
+        }
+    }
+}
+
+#[tauri::command]
+pub fn delete_secret(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    namespace: String,
+    key: String,
+) -> CommandResult<()> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    public_namespace(&namespace)?;
+    let name = secret_name(&tenant_id, &namespace, &key)?;
+    let _guard = FILES_LOCK.lock().unwrap();
+    match backend() {
+        SecretsBackend::Keychain => delete(&name)?,
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let mut values = read_fallback()?;
+            if values.remove(&name).is_some() {
+                write_fallback(&values)?;
+            }
+        }
+    }
+
+    let mut index = read_index();
+    if let Some(namespaces) = index.get_mut(&tenant_id) {
+        if let Some(keys) = namespaces.get_mut(&namespace) {
+            keys.remove(&key);
+            if keys.is_empty() {
+                namespaces.remove(&namespace);
+            }
+        }
+        if namespaces.is_empty() {
+            index.remove(&tenant_id);
+        }
+    }
+    write_index(&index)?;
+
+    audit::record(
+        "secret_deleted",
+        Some(&tenant_id),
+        serde_json::json!({ "namespace": namespace, "key": key }),
+    );
+    Ok(())
+}
+
+/// Key names stored in a namespace. Never returns values.
+#[tauri::command]
+pub fn list_secret_keys(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    namespace: String,
+) -> CommandResult<Vec<String>> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    public_namespace(&namespace)?;
+    validate(&tenant_id, &namespace, None)?;
+    Ok(read_index()
+        .get(&tenant_id)
+        .and_then(|namespaces| namespaces.get(&namespace))
+        .map(|keys| keys.iter().cloned().collect())
+        .unwrap_or_default())
+}
+
+fn public_namespace(namespace: &str) -> CommandResult<()> {
+    if RESERVED_NAMESPACES.contains(&namespace) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Secret namespace {} is reserved", namespace),
+        )
+        .with("namespace", namespace));
+    }
+    Ok(())
+}
+
+fn validate(tenant_id: &str, namespace: &str, key: Option<&str>) -> CommandResult<()> {
+    validate_id("tenant_id", tenant_id)
+        .and_then(|_| validate_id("namespace", namespace))
+        .and_then(|_| key.map_or(Ok(()), |k| validate_id("key", k)))
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))
+}
+
+/// Keychain entry name. Ids can't contain `:`, so the join is unambiguous.
+fn secret_name(tenant_id: &str, namespace: &str, key: &str) -> CommandResult<String> {
+    validate(tenant_id, namespace, Some(key))?;
+    Ok(format!("secret:{}:{}:{}", tenant_id, namespace, key))
+}
+
+fn secrets_path(file: &str) -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join(file))
+}
+
+fn read_index() -> SecretIndex {
+    secrets_path(INDEX_FILE)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn write_index(index: &SecretIndex) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(index).map_err(|e| e.to_string())?;
+    fs::write(secrets_path(INDEX_FILE)?, json).map_err(|e| e.to_string())
+}
+
+pub fn read_fallback() -> Result<BTreeMap<String, String>, String> {
+    let raw = match fs::read(secrets_path(FALLBACK_FILE)?) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let plain = open_with(&fallback_key()?, &raw)?;
+    serde_json::from_slice(&plain).map_err(|_| "Corrupted secrets file".to_string())
+}
+
+pub fn write_fallback(values: &BTreeMap<String, String>) -> Result<(), String> {
+    let (path, sealed) = seal_fallback_with(&fallback_key()?, values)?;
+    fs::write(path, sealed).map_err(|e| e.to_string())
+}
+
+/// The fallback file and `values` sealed under `key`, for re-encrypting
+/// without writing anything yet.
+pub fn seal_fallback_with(
+    key: &[u8; 32],
+    values: &BTreeMap<String, String>,
+) -> Result<(PathBuf, Vec<u8>), String> {
+    let json = serde_json::to_vec(values).map_err(|e| e.to_string())?;
+    Ok((secrets_path(FALLBACK_FILE)?, seal_with(key, &json)?))
+}
+
+/// The master passphrase key when configured, else a local key file
+/// readable only by the current user.
+fn fallback_key() -> Result<[u8; 32], String> {
+    use chacha20poly1305::aead::{KeyInit, OsRng};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    if let Some(key) = vault::passphrase_key() {
+        return key;
+    }
+
+    let path = secrets_path(FALLBACK_KEY_FILE)?;
+    if let Ok(bytes) = fs::read(&path) {
+        return bytes
+            .try_into()
+            .map_err(|_| format!("Corrupted key file {}", path.display()));
+    }
+
+    let key: [u8; 32] = ChaCha20Poly1305::generate_key(&mut OsRng).into();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).map_err(|e| e.to_string())?;
+    std::io::Write::write_all(&mut file, &key).map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+// ─── App Secrets ────────────────────────────────────────────────────────────
+
+// Secrets the app keeps for itself (proxy credentials) rather than for a
+// tenant. Same backend as the generic secrets, under a bare name, and left
+// out of the index.
+
+pub fn store_app(key: &str, value: &str) -> Result<(), String> {
+    let _guard = FILES_LOCK.lock().unwrap();
+    match backend() {
+        SecretsBackend::Keychain => store(key, value),
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let mut values = read_fallback()?;
+            values.insert(key.to_string(), value.to_string());
+            write_fallback(&values)
+        }
+    }
+}
+
+/// Returns `Ok(None)` when nothing is stored under `key`.
+pub fn load_app(key: &str) -> Result<Option<String>, String> {
+    match backend() {
+        SecretsBackend::Keychain => load(key),
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let _guard = FILES_LOCK.lock().unwrap();
+            Ok(read_fallback()?.remove(key))
+        }
+    }
+}
+
+/// A key that was never stored counts as deleted.
+pub fn delete_app(key: &str) -> Result<(), String> {
+    let _guard = FILES_LOCK.lock().unwrap();
+    match backend() {
+        SecretsBackend::Keychain => delete(key),
+        SecretsBackend::EncryptedFile => {
+            vault::ensure_unlocked()?;
+            let mut values = read_fallback()?;
+            if values.remove(key).is_some() {
+                write_fallback(&values)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_keys_are_out_of_reach_of_the_secret_commands() {
+        let err = public_namespace(NOTE_KEY_NAMESPACE).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert_eq!(err.params["namespace"], NOTE_KEY_NAMESPACE);
+        assert!(public_namespace("webhooks").is_ok());
+    }
+}
@@ -0,0 +1,284 @@
+// Session expiry checker
+//
+// Sessions die quietly and tasks fail hours later, so the `session_expiry`
+// job evaluates every stored session every few hours. The expiry comes from the
+// provider's auth cookies (captured with their timestamps); providers whose
+// cookies carry no expiry are checked with a low-frequency authenticated
+// probe instead. The verdict is written to `session.json` for
+// `list_sessions`, and sessions entering the warning window emit
+// `provider-session-expiring` and show a native notification asking the user
+// to sign in again. Desktop notifications can't carry actions, so the
+// re-login itself is offered by the UI on `provider-session-expiring`; on
+// mobile the notification carries the `session-relogin` action.
+// `check_provider_session` runs the same check for one session on demand.
+
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::http_retry::{self, RetryPolicy};
+use crate::provider_fetch::{ProviderRequest, ProviderSession};
+use crate::providers::{self, ProviderLoginConfig};
+use crate::sessions::{self, SessionHealth, SessionPayload, SessionStatus};
+use crate::{http, i18n, mock_provider, settings, tenant_windows, vault};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(3 * 60 * 60);
+/// Minimum gap between active probes of the same session.
+const PROBE_INTERVAL_SECS: i64 = 12 * 60 * 60;
+/// Notification action that opens the provider login for the session.
+const RELOGIN_ACTION: &str = "session-relogin";
+
+#[derive(Serialize, Clone)]
+struct SessionExpiring {
+    tenant_id: String,
+    provider_id: String,
+    status: SessionStatus,
+    expires_at: Option<String>,
+}
+
+pub fn register(scheduler: &Scheduler) {
+    scheduler.register(
+        "session_expiry",
+        "Check stored provider sessions for expiry",
+        Schedule::Every(CHECK_INTERVAL),
+        JobLoad::Light,
+        true,
+        |app| tauri::async_runtime::block_on(check_all(app)),
+    );
+}
+
+async fn check_all(app: &AppHandle) -> Result<Option<String>, String> {
+    if vault::status().locked {
+        log::debug!("[sessions] vault locked, skipping expiry check");
+        return Ok(Some("Skipped: vault locked".into()));
+    }
+    let mut checked = 0;
+    for dir in sessions::all_session_dirs(&sessions::sessions_root()?) {
+        match check_dir(app, &dir).await {
+            Ok(Some(_)) => checked += 1,
+            Ok(None) => {}
+            Err(e) => log::warn!("[sessions] can't check {}: {}", dir.display(), e),
+        }
+    }
+    Ok(Some(format!("{} sessions checked", checked)))
+}
+
+/// Check one session now, as the `session_expiry` job would.
+#[tauri::command]
+pub async fn check_provider_session(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    provider_id: String,
+) -> CommandResult<SessionHealth> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    vault::ensure_unlocked()?;
+    let not_found = || {
+        CommandError::new(
+            ErrorCode::SessionNotFound,
+            format!("No session for {} / {}", tenant_id, provider_id),
+        )
+        .with("tenant_id", &tenant_id)
+        .with("provider_id", &provider_id)
+    };
+    let dir = sessions::find_session_dir(&sessions::sessions_root()?, &tenant_id, &provider_id)
+        .ok_or_else(not_found)?;
+    check_dir(&app, &dir).await?.ok_or_else(not_found)
+}
+
+/// Evaluate, record, and notify for one session directory. `None` when
+/// there is nothing to check yet.
+async fn check_dir(app: &AppHandle, dir: &Path) -> CommandResult<Option<SessionHealth>> {
+    let warning_secs = settings::load_settings().session_expiry_warning_hours as i64 * 3600;
+    let Some(meta) = sessions::read_meta(dir) else {
+        return Ok(None);
+    };
+    // `None`: the directory exists but nobody has logged in yet
+    let Some(payload) = sessions::load_payload(dir)? else {
+        return Ok(None);
+    };
+    let config = providers::login_config(&meta.provider)?;
+
+    let previous = meta.health.status;
+    let health = evaluate(
+        &meta.tenant_id,
+        &config,
+        &payload,
+        meta.health,
+        warning_secs,
+    )
+    .await;
+    if let Err(e) = sessions::record_health(dir, health.clone()) {
+        log::warn!("[sessions] can't record status in {}: {}", dir.display(), e);
+    }
+
+    let alarming = matches!(
+        health.status,
+        SessionStatus::Expiring | SessionStatus::Expired
+    );
+    if alarming && health.status != previous {
+        notify(app, dir, &meta.tenant_id, &meta.provider, &health);
+    }
+    Ok(Some(health))
+}
+
+async fn evaluate(
+    tenant_id: &str,
+    config: &ProviderLoginConfig,
+    payload: &SessionPayload,
+    previous: SessionHealth,
+    warning_secs: i64,
+) -> SessionHealth {
+    if mock_provider::is_mock(&config.provider_id) {
+        return mock_provider::health(payload);
+    }
+    let now = chrono::Utc::now();
+    let checked_at = Some(now.to_rfc3339());
+
+    if let Some(expires) = session_expiry(payload, &config.auth_cookies) {
+        let remaining = expires - now.timestamp();
+        let status = if remaining <= 0 {
+            SessionStatus::Expired
+        } else if remaining <= warning_secs {
+            SessionStatus::Expiring
+        } else {
+            SessionStatus::Active
+        };
+        return SessionHealth {
+            status,
+            expires_at: chrono::DateTime::from_timestamp(expires, 0).map(|t| t.to_rfc3339()),
+            checked_at,
+            probed_at: previous.probed_at,
+        };
+    }
+
+    // No expiry to go on: probe, but rarely
+    let probed_recently = previous
+        .probed_at
+        .as_deref()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| now.timestamp() - t.timestamp() < PROBE_INTERVAL_SECS);
+    if probed_recently {
+        return SessionHealth {
+            checked_at,
+            ..previous
+        };
+    }
+
+    let status = probe(tenant_id, config).await;
+    SessionHealth {
+        // An inconclusive probe keeps the last verdict
+        status: if status == SessionStatus::Unknown {
+            previous.status
+        } else {
+            status
+        },
+        expires_at: None,
+        checked_at: checked_at.clone(),
+        probed_at: checked_at,
+    }
+}
+
+/// Unix expiry of the session: the earliest-expiring auth cookie, or when no
+/// auth cookies are known, the latest expiry of any cookie.
+fn session_expiry(payload: &SessionPayload, auth_cookies: &[String]) -> Option<i64> {
+    let auth: Vec<_> = payload
+        .cookies
+        .iter()
+        .filter(|c| auth_cookies.contains(&c.name))
+        .collect();
+    if !auth.is_empty() {
+        return auth.iter().filter_map(|c| c.expires_at).min();
+    }
+    payload.cookies.iter().filter_map(|c| c.expires_at).max()
+}
+
+async fn probe(tenant_id: &str, config: &ProviderLoginConfig) -> SessionStatus {
+    let Some(url) = config.health_check_url.clone() else {
+        return SessionStatus::Unknown;
+    };
+    let result = async {
+        let session = ProviderSession::load(tenant_id, &config.provider_id)?;
+        let request = ProviderRequest {
+            method: "GET".into(),
+            url,
+            headers: Default::default(),
+            body: None,
+            category: None,
+        };
+        let settings = settings::load_settings();
+        let (_, request) = session.prepare(request, http::builder(&settings)?)?;
+        let policy = RetryPolicy::from_settings(&settings.http);
+        let response = http_retry::send(request, &policy).await?;
+        session.store_set_cookies(response.url().clone(), response.headers());
+        Ok::<_, CommandError>(response)
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            if status == reqwest::StatusCode::UNAUTHORIZED
+                || status == reqwest::StatusCode::FORBIDDEN
+                || response.url().as_str().starts_with(&config.login_url)
+            {
+                SessionStatus::Expired
+            } else if status.is_success() {
+                SessionStatus::Active
+            } else {
+                SessionStatus::Unknown
+            }
+        }
+        Err(e) => {
+            log::debug!(
+                "[sessions] probe for {} / {} failed: {}",
+                tenant_id,
+                config.provider_id,
+                e
+            );
+            SessionStatus::Unknown
+        }
+    }
+}
+
+fn notify(app: &AppHandle, dir: &Path, tenant_id: &str, provider_id: &str, health: &SessionHealth) {
+    log::info!(
+        "[sessions] {} is {:?} (expires {:?})",
+        dir.display(),
+        health.status,
+        health.expires_at
+    );
+    let _ = app.emit(
+        "provider-session-expiring",
+        SessionExpiring {
+            tenant_id: tenant_id.to_string(),
+            provider_id: provider_id.to_string(),
+            status: health.status,
+            expires_at: health.expires_at.clone(),
+        },
+    );
+
+    let title = if health.status == SessionStatus::Expired {
+        "notification.session_expired"
+    } else {
+        "notification.session_expiring"
+    };
+    let params = [("tenant_id", tenant_id), ("provider_id", provider_id)];
+    let shown = app
+        .notification()
+        .builder()
+        .title(i18n::text(title, &params))
+        .body(i18n::text("notification.session_relogin", &params))
+        .action_type_id(RELOGIN_ACTION)
+        .extra("tenant_id", tenant_id)
+        .extra("provider_id", provider_id)
+        .show();
+    if let Err(e) = shown {
+        log::warn!("[sessions] can't show expiry notification: {}", e);
+    }
+}
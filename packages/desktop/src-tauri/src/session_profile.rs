@@ -0,0 +1,368 @@
+// Provider account profiles
+//
+// With several sessions, entries like "claude / default" say nothing about
+// which account each one is. Once a login is captured, the account profile
+// the provider serves to a signed-in user is read through the session
+// (`profile` in the login config: a URL plus JSON pointers into its
+// response) and kept in `session.json`, so `list_sessions` can show the
+// email or handle, display name, and plan tier where the provider reveals
+// one. `refresh_session_profile` reads it again later.
+//
+// A profile is a nicety: when it can't be read during a capture, the capture
+// still succeeds, just without one. An avatar is fetched, without the
+// session's cookies, only over HTTPS as an image up to `MAX_AVATAR_BYTES`,
+// and kept as a `data:` URL. Larger ones are dropped, not stored.
+//
+// Profiles name people, so their fields never go to the log, the audit log,
+// or error messages; `SessionProfile`'s `Debug` shows only which fields are
+// set.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::provider_fetch::{ProviderRequest, ProviderSession};
+use crate::providers::{self, ProviderLoginConfig};
+use crate::{http, mock_provider, sessions, settings, vault};
+
+const PROFILE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Profile responses are small; anything larger isn't one.
+const MAX_PROFILE_BYTES: usize = 256 * 1024;
+pub const MAX_AVATAR_BYTES: usize = 64 * 1024;
+
+/// Where a provider serves the signed-in account, and where each field is
+/// in the JSON it returns.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProfileSource {
+    pub url: String,
+    /// JSON pointers, e.g. `/user/email`.
+    pub account_id: Option<String>,
+    pub email: Option<String>,
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub plan: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SessionProfile {
+    /// The provider's id for the account.
+    pub account_id: Option<String>,
+    pub email: Option<String>,
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub plan: Option<String>,
+    /// `data:` URL of the avatar, when one was small enough to keep.
+    pub avatar: Option<String>,
+    pub fetched_at: String,
+}
+
+impl fmt::Debug for SessionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let set = |field: &Option<String>| if field.is_some() { "set" } else { "unset" };
+        f.debug_struct("SessionProfile")
+            .field("account_id", &set(&self.account_id))
+            .field("email", &set(&self.email))
+            .field("handle", &set(&self.handle))
+            .field("display_name", &set(&self.display_name))
+            .field("plan", &set(&self.plan))
+            .field("avatar", &set(&self.avatar))
+            .field("fetched_at", &self.fetched_at)
+            .finish()
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Read the account profile of a stored session again. `account_id`, once
+/// a profile has named one, must be the account the session belongs to.
+#[tauri::command]
+pub async fn refresh_session_profile(
+    tenant_id: String,
+    provider_id: String,
+    account_id: Option<String>,
+) -> CommandResult<SessionProfile> {
+    vault::ensure_unlocked()?;
+    let root = sessions::sessions_root()?;
+    let dir = sessions::find_session_dir(&root, &tenant_id, &provider_id)
+        .filter(|dir| {
+            let known = sessions::read_meta(dir)
+                .and_then(|m| m.profile)
+                .and_then(|p| p.account_id);
+            account_id.is_none() || known.is_none() || known == account_id
+        })
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::SessionNotFound,
+                format!("No session for {} / {}", tenant_id, provider_id),
+            )
+            .with("tenant_id", &tenant_id)
+            .with("provider_id", &provider_id)
+        })?;
+    let config = providers::login_config(&provider_id)?;
+    let profile = fetch(&tenant_id, &config).await?.ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::Unsupported,
+            format!("{} doesn't offer an account profile", provider_id),
+        )
+        .with("provider_id", &provider_id)
+    })?;
+    sessions::record_profile(&dir, Some(profile.clone()))
+        .map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    log::info!(
+        "[sessions] profile refreshed for {} / {}",
+        tenant_id,
+        provider_id
+    );
+    Ok(profile)
+}
+
+// ─── Fetching ───────────────────────────────────────────────────────────────
+
+/// Read and record the profile right after a capture. Failures are logged
+/// and give `None`; they never fail the capture.
+pub fn capture(tenant_id: &str, provider_id: &str) -> Option<SessionProfile> {
+    let result = tauri::async_runtime::block_on(async {
+        let config = providers::login_config(provider_id)?;
+        let profile = fetch(tenant_id, &config).await?;
+        if let Some(profile) = &profile {
+            let dir = sessions::ensure_session_dir_at(
+                &sessions::sessions_root()?,
+                tenant_id,
+                provider_id,
+            )?;
+            sessions::record_profile(&dir, Some(profile.clone()))?;
+        }
+        Ok::<_, CommandError>(profile)
+    });
+    result
+        .inspect_err(|e| {
+            log::warn!(
+                "[sessions] no profile for {} / {}: {}",
+                tenant_id,
+                provider_id,
+                e.message
+            )
+        })
+        .ok()
+        .flatten()
+}
+
+/// The signed-in account, or `None` for providers without a profile source.
+async fn fetch(
+    tenant_id: &str,
+    config: &ProviderLoginConfig,
+) -> CommandResult<Option<SessionProfile>> {
+    let Some(source) = &config.profile else {
+        return Ok(None);
+    };
+    let body = match mock_provider::is_mock(&config.provider_id) {
+        true => mock_body(tenant_id, source)?,
+        false => tokio::time::timeout(PROFILE_TIMEOUT, provider_body(tenant_id, config, source))
+            .await
+            .map_err(|_| CommandError::new(ErrorCode::Timeout, "Profile request timed out"))??,
+    };
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return Err(CommandError::new(
+            ErrorCode::ParseError,
+            "Profile response isn't JSON",
+        ));
+    };
+    let mut profile = extract(source, &json);
+    if let Some(url) = source.avatar_url.as_deref().and_then(|p| field(&json, p)) {
+        profile.avatar = tokio::time::timeout(PROFILE_TIMEOUT, avatar(&url))
+            .await
+            .ok()
+            .flatten();
+    }
+    Ok(Some(profile))
+}
+
+async fn provider_body(
+    tenant_id: &str,
+    config: &ProviderLoginConfig,
+    source: &ProfileSource,
+) -> CommandResult<Vec<u8>> {
+    let session = ProviderSession::load(tenant_id, &config.provider_id)?;
+    let request = ProviderRequest {
+        method: "GET".into(),
+        url: source.url.clone(),
+        headers: Default::default(),
+        body: None,
+        category: Some("profile".into()),
+    };
+    let (_, request) = session.prepare(request, http::builder(&settings::load_settings())?)?;
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()))?;
+    session.store_set_cookies(response.url().clone(), response.headers());
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CommandError::new(
+            ErrorCode::Network,
+            format!("Profile request failed with HTTP {}", status.as_u16()),
+        )
+        .with("status", status.as_u16()));
+    }
+    read_capped(&mut response, MAX_PROFILE_BYTES)
+        .await?
+        .ok_or_else(|| CommandError::new(ErrorCode::TooLarge, "Profile response is too large"))
+}
+
+fn mock_body(tenant_id: &str, source: &ProfileSource) -> CommandResult<Vec<u8>> {
+    let not_found = || CommandError::new(ErrorCode::SessionNotFound, "No mock session");
+    let dir = sessions::find_session_dir(
+        &sessions::sessions_root()?,
+        tenant_id,
+        mock_provider::PROVIDER_ID,
+    )
+    .ok_or_else(not_found)?;
+    let payload = sessions::load_payload(&dir)?.ok_or_else(not_found)?;
+    let path = reqwest::Url::parse(&source.url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_default();
+    let response = mock_provider::respond("GET", &path, &payload);
+    match (response.status, response.body) {
+        (200, Some(body)) => Ok(body.into_bytes()),
+        (status, _) => Err(CommandError::new(
+            ErrorCode::Network,
+            format!("Profile request failed with HTTP {}", status),
+        )
+        .with("status", status)),
+    }
+}
+
+/// The body, or `None` once it passes `cap` bytes.
+async fn read_capped(
+    response: &mut reqwest::Response,
+    cap: usize,
+) -> CommandResult<Option<Vec<u8>>> {
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > cap)
+    {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > cap {
+            return Ok(None);
+        }
+    }
+    Ok(Some(body))
+}
+
+/// The avatar at `url` as a `data:` URL, if it's a small enough image.
+async fn avatar(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url)
+        .ok()
+        .filter(|u| u.scheme() == "https")?;
+    let client = http::builder(&settings::load_settings())
+        .ok()?
+        .build()
+        .ok()?;
+    let mut response = client.get(url).send().await.ok()?;
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or_default().trim().to_string())
+        .filter(|m| m.starts_with("image/"))?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let bytes = read_capped(&mut response, MAX_AVATAR_BYTES).await.ok()??;
+    Some(data_url(&mime, &bytes))
+}
+
+fn data_url(mime: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+// ─── Fields ─────────────────────────────────────────────────────────────────
+
+fn extract(source: &ProfileSource, json: &serde_json::Value) -> SessionProfile {
+    let get = |pointer: &Option<String>| pointer.as_deref().and_then(|p| field(json, p));
+    SessionProfile {
+        account_id: get(&source.account_id),
+        email: get(&source.email),
+        handle: get(&source.handle),
+        display_name: get(&source.display_name),
+        plan: get(&source.plan),
+        avatar: None,
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+/// The non-empty string or number at `pointer`.
+fn field(json: &serde_json::Value, pointer: &str) -> Option<String> {
+    match json.pointer(pointer)? {
+        serde_json::Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> ProfileSource {
+        ProfileSource {
+            url: "https://claude.ai/api/account".into(),
+            account_id: Some("/uuid".into()),
+            email: Some("/email_address".into()),
+            handle: None,
+            display_name: Some("/display_name".into()),
+            plan: Some("/memberships/0/organization/rate_limit_tier".into()),
+            avatar_url: None,
+        }
+    }
+
+    #[test]
+    fn fields_come_from_their_pointers() {
+        let json = serde_json::json!({
+            "uuid": 42,
+            "email_address": "ada@example.com",
+            "display_name": "  ",
+            "memberships": [{ "organization": { "rate_limit_tier": "pro" } }],
+        });
+        let profile = extract(&source(), &json);
+        assert_eq!(profile.account_id.as_deref(), Some("42"));
+        assert_eq!(profile.email.as_deref(), Some("ada@example.com"));
+        assert_eq!(profile.display_name, None);
+        assert_eq!(profile.plan.as_deref(), Some("pro"));
+        assert_eq!(profile.handle, None);
+    }
+
+    #[test]
+    fn debug_output_never_shows_profile_fields() {
+        let profile = SessionProfile {
+            email: Some("ada@example.com".into()),
+            display_name: Some("Ada Lovelace".into()),
+            avatar: Some(data_url("image/png", b"\x89PNG")),
+            ..Default::default()
+        };
+        let shown = format!("{:?}", profile);
+        assert!(!shown.contains("ada@example.com"));
+        assert!(!shown.contains("Ada"));
+        assert!(!shown.contains("base64"));
+        assert!(shown.contains("email: \"set\""));
+    }
+}
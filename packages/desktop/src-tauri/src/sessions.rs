@@ -0,0 +1,754 @@
+// Provider session storage
+//
+// Sessions live in a nested layout so tenant and provider ids can never be
+// confused with each other:
+//
+//   <data directory>/sessions/<tenant_id>/<provider_id>/session.json
+//
+// Captured cookies live next to the metadata as the session payload:
+// `payload.enc`, sealed with the keychain-held vault key, or `payload.json`
+// with a warning on machines without a usable keychain.
+//
+// Older builds used a flat `sessions/<tenant_id>_<provider_id>/` layout, which
+// is ambiguous once either id contains an underscore (`acme_corp` + `claude`
+// vs `acme` + `corp_claude`). The flat layout is still read, and migrated into
+// the nested one on startup or via `migrate_sessions_layout`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::providers::KNOWN_PROVIDERS;
+use crate::schema::Schema;
+use crate::session_profile::SessionProfile;
+use crate::trash::{self, TrashEntry, TrashKind};
+use crate::{audit, dry_run, integrity, provider_cache, tenant_windows};
+
+/// `session.json` metadata, unchanged since it was first versioned.
+pub(crate) const META_SCHEMA: Schema = Schema::new("session metadata", &[]);
+/// Session payloads. Version 2 added the cookie scoping attributes.
+pub(crate) const PAYLOAD_SCHEMA: Schema = Schema::new("session payload", &[scope_cookies]);
+
+/// Current version of the `session.json` metadata format.
+pub const SESSION_SCHEMA_VERSION: u32 = META_SCHEMA.current();
+
+const SESSION_META_FILE: &str = "session.json";
+/// `integrity` marker name while `migrate_sessions_layout` runs.
+pub const LAYOUT_MIGRATION: &str = "sessions_layout";
+const PAYLOAD_SEALED_FILE: &str = "payload.enc";
+const PAYLOAD_PLAIN_FILE: &str = "payload.json";
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionMeta {
+    pub schema_version: u32,
+    pub tenant_id: String,
+    pub provider: String,
+    pub created_at: String,
+    /// User agent the login window ran with; replays must present the same one.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
+    /// Last evaluation by the expiry checker.
+    #[serde(default)]
+    pub health: SessionHealth,
+    /// The signed-in account; see `session_profile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<SessionProfile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    /// Not checked yet, or the check couldn't reach a verdict.
+    #[default]
+    Unknown,
+    Active,
+    /// Expires within the warning window.
+    Expiring,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SessionHealth {
+    pub status: SessionStatus,
+    pub expires_at: Option<String>,
+    pub checked_at: Option<String>,
+    /// Last active probe, for sessions whose cookies carry no expiry.
+    pub probed_at: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SessionSummary {
+    pub tenant_id: String,
+    pub provider_id: String,
+    pub created_at: String,
+    pub status: SessionStatus,
+    pub expires_at: Option<String>,
+    pub checked_at: Option<String>,
+    pub profile: Option<SessionProfile>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    /// Domain the cookie applies to, and its subdomains unless `host_only`.
+    pub domain: String,
+    /// Unix seconds; `None` for session cookies.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Sent only to requests under this path.
+    #[serde(default = "root_path")]
+    pub path: String,
+    /// Sent only over HTTPS.
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: Option<SameSite>,
+    /// Set without a `Domain` attribute, so sent to `domain` itself only.
+    /// Cookies read from the login webview don't say, and count as domain
+    /// cookies.
+    #[serde(default)]
+    pub host_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+fn root_path() -> String {
+    "/".into()
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SessionPayload {
+    pub cookies: Vec<StoredCookie>,
+    pub captured_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MovedSession {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SkippedSession {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SessionMigrationReport {
+    pub moved: Vec<MovedSession>,
+    pub skipped: Vec<SkippedSession>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Ensure the session storage directory exists and return its path.
+#[tauri::command]
+pub fn ensure_session_dir(provider_id: String, tenant_id: String) -> Result<String, String> {
+    let dir = ensure_session_dir_at(&sessions_root()?, &tenant_id, &provider_id)?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// Every stored session with its last known expiry status.
+#[tauri::command]
+pub fn list_sessions() -> Vec<SessionSummary> {
+    let Ok(root) = sessions_root() else {
+        return Vec::new();
+    };
+    all_session_dirs(&root)
+        .iter()
+        .filter_map(|dir| read_meta(dir))
+        .map(|meta| SessionSummary {
+            tenant_id: meta.tenant_id,
+            provider_id: meta.provider,
+            created_at: meta.created_at,
+            status: meta.health.status,
+            expires_at: meta.health.expires_at,
+            checked_at: meta.health.checked_at,
+            profile: meta.profile,
+        })
+        .collect()
+}
+
+/// Move a stored session to the trash; the user must log in again.
+#[tauri::command]
+pub fn delete_session(
+    window: tauri::WebviewWindow,
+    tenant_id: Option<String>,
+    provider_id: String,
+    dry_run: Option<bool>,
+) -> CommandResult<TrashEntry> {
+    let tenant_id = tenant_windows::resolve_tenant(window.label(), tenant_id)?;
+    let dry_run = dry_run::requested(window.label(), Some(&tenant_id), dry_run);
+    delete(tenant_id, provider_id, dry_run)
+}
+
+pub fn delete(tenant_id: String, provider_id: String, dry_run: bool) -> CommandResult<TrashEntry> {
+    // `acme` + `..` would otherwise resolve to the sessions root itself
+    for (field, id) in [("tenant_id", &tenant_id), ("provider_id", &provider_id)] {
+        validate_id(field, id).map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    }
+    let dir = find_session_dir(&sessions_root()?, &tenant_id, &provider_id).ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::SessionNotFound,
+            format!("No session for {}/{}", tenant_id, provider_id),
+        )
+        .with("tenant_id", &tenant_id)
+        .with("provider_id", &provider_id)
+    })?;
+    let label = format!("{}/{}", tenant_id, provider_id);
+    if dry_run {
+        return trash::plan_trash(TrashKind::Session, &label, &[dir]);
+    }
+    provider_cache::clear(&dir);
+    let entry = trash::move_to_trash(TrashKind::Session, &label, &[dir])?;
+    audit::record(
+        "session_deleted",
+        Some(&tenant_id),
+        serde_json::json!({ "provider_id": provider_id, "trash_entry": entry.id }),
+    );
+    Ok(entry)
+}
+
+/// Move flat `<tenant>_<provider>` session directories into the nested layout.
+#[tauri::command]
+pub fn migrate_sessions_layout() -> Result<SessionMigrationReport, String> {
+    let migration = integrity::Migration::begin(LAYOUT_MIGRATION)?;
+    let report = migrate_layout_at(&sessions_root()?)?;
+    migration.finish();
+    Ok(report)
+}
+
+/// Finish a layout migration that crashed midway. A directory that was
+/// moved but never got its `session.json` would otherwise be invisible.
+pub fn resume_layout_migration() -> Result<String, String> {
+    let root = sessions_root()?;
+    let mut repaired = 0;
+    for tenant in fs::read_dir(&root).into_iter().flatten().flatten() {
+        let tenant_id = tenant.file_name().to_string_lossy().to_string();
+        for provider in fs::read_dir(tenant.path()).into_iter().flatten().flatten() {
+            let provider_id = provider.file_name().to_string_lossy().to_string();
+            let dir = provider.path();
+            if !dir.is_dir()
+                || dir.join(SESSION_META_FILE).exists()
+                || !KNOWN_PROVIDERS.contains(&provider_id.as_str())
+            {
+                continue;
+            }
+            let created_at = chrono::Utc::now().to_rfc3339();
+            write_meta(&dir, &new_meta(&tenant_id, &provider_id, created_at))?;
+            repaired += 1;
+        }
+    }
+    let report = migrate_layout_at(&root)?;
+    Ok(format!(
+        "sessions layout: {} metadata files restored, {} directories moved",
+        repaired,
+        report.moved.len()
+    ))
+}
+
+// ─── Layout ─────────────────────────────────────────────────────────────────
+
+pub fn sessions_root() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("sessions"))
+}
+
+/// Nested session directory for a tenant/provider pair (not created).
+pub fn session_dir(root: &Path, tenant_id: &str, provider_id: &str) -> PathBuf {
+    root.join(tenant_id).join(provider_id)
+}
+
+/// Locate an existing session directory, falling back to the legacy flat
+/// layout for sessions that have not been migrated yet.
+pub fn find_session_dir(root: &Path, tenant_id: &str, provider_id: &str) -> Option<PathBuf> {
+    let nested = session_dir(root, tenant_id, provider_id);
+    if nested.is_dir() {
+        return Some(nested);
+    }
+    // Only a flat dir whose name reads back as exactly this pair; one that
+    // may belong to another tenant is left for `migrate_sessions_layout`
+    let name = format!("{}_{}", tenant_id, provider_id);
+    let ours = legacy_readings(&name) == [(tenant_id.to_string(), provider_id.to_string())];
+    let legacy = root.join(name);
+    if ours && legacy.is_dir() {
+        return Some(legacy);
+    }
+    None
+}
+
+/// Every nested `<tenant>/<provider>` session directory under `root`.
+pub fn all_session_dirs(root: &Path) -> Vec<PathBuf> {
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    subdirs(root)
+        .iter()
+        .flat_map(|tenant| subdirs(tenant))
+        .filter(|dir| dir.join(SESSION_META_FILE).is_file())
+        .collect()
+}
+
+pub fn ensure_session_dir_at(
+    root: &Path,
+    tenant_id: &str,
+    provider_id: &str,
+) -> Result<PathBuf, String> {
+    validate_id("tenant_id", tenant_id)?;
+    validate_id("provider_id", provider_id)?;
+
+    let dir = session_dir(root, tenant_id, provider_id);
+    if let Some(existing) = find_session_dir(root, tenant_id, provider_id) {
+        if existing != dir {
+            // Unmigrated flat dir for exactly this pair — adopt it in place
+            fs::create_dir_all(root.join(tenant_id)).map_err(|e| e.to_string())?;
+            fs::rename(&existing, &dir).map_err(|e| e.to_string())?;
+        }
+    }
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    if !dir.join(SESSION_META_FILE).exists() {
+        let meta = new_meta(tenant_id, provider_id, chrono::Utc::now().to_rfc3339());
+        write_meta(&dir, &meta)?;
+    }
+    Ok(dir)
+}
+
+/// Record the webview profile a login window was opened with.
+pub fn record_login_profile(
+    dir: &Path,
+    user_agent: Option<String>,
+    extra_headers: HashMap<String, String>,
+) -> Result<(), String> {
+    let mut meta = read_meta(dir).ok_or_else(|| {
+        format!(
+            "Missing or unreadable {} in {}",
+            SESSION_META_FILE,
+            dir.display()
+        )
+    })?;
+    meta.user_agent = user_agent;
+    meta.extra_headers = extra_headers;
+    write_meta(dir, &meta)
+}
+
+pub fn record_health(dir: &Path, health: SessionHealth) -> Result<(), String> {
+    let mut meta = read_meta(dir).ok_or_else(|| {
+        format!(
+            "Missing or unreadable {} in {}",
+            SESSION_META_FILE,
+            dir.display()
+        )
+    })?;
+    meta.health = health;
+    write_meta(dir, &meta)
+}
+
+pub fn record_profile(dir: &Path, profile: Option<SessionProfile>) -> Result<(), String> {
+    let mut meta = read_meta(dir).ok_or_else(|| {
+        format!(
+            "Missing or unreadable {} in {}",
+            SESSION_META_FILE,
+            dir.display()
+        )
+    })?;
+    meta.profile = profile;
+    write_meta(dir, &meta)
+}
+
+pub fn read_meta(dir: &Path) -> Option<SessionMeta> {
+    let raw = fs::read(dir.join(SESSION_META_FILE)).ok()?;
+    META_SCHEMA.decode(&raw).ok()
+}
+
+fn new_meta(tenant_id: &str, provider_id: &str, created_at: String) -> SessionMeta {
+    SessionMeta {
+        schema_version: SESSION_SCHEMA_VERSION,
+        tenant_id: tenant_id.to_string(),
+        provider: provider_id.to_string(),
+        created_at,
+        user_agent: None,
+        extra_headers: HashMap::new(),
+        health: SessionHealth::default(),
+        profile: None,
+    }
+}
+
+fn write_meta(dir: &Path, meta: &SessionMeta) -> Result<(), String> {
+    let path = dir.join(SESSION_META_FILE);
+    META_SCHEMA
+        .guard_path(&path)
+        .and_then(|()| META_SCHEMA.encode(meta, true))
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()))
+}
+
+// ─── Payload ────────────────────────────────────────────────────────────────
+
+pub fn load_payload(dir: &Path) -> Result<Option<SessionPayload>, String> {
+    let Some(raw) = read_payload(dir)? else {
+        return Ok(None);
+    };
+    PAYLOAD_SCHEMA
+        .decode(&raw)
+        .map(Some)
+        .map_err(|e| format!("Corrupted session payload in {}: {}", dir.display(), e))
+}
+
+/// The payload's JSON, unsealed.
+fn read_payload(dir: &Path) -> Result<Option<Vec<u8>>, String> {
+    let sealed = dir.join(PAYLOAD_SEALED_FILE);
+    if sealed.exists() {
+        let bytes = fs::read(&sealed).map_err(|e| e.to_string())?;
+        return crate::secrets::open(&bytes).map(Some);
+    }
+    match fs::read(dir.join(PAYLOAD_PLAIN_FILE)) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Persist the payload, encrypted when the keychain is usable. Returns
+/// whether it was encrypted.
+pub fn save_payload(dir: &Path, payload: &SessionPayload) -> CommandResult<bool> {
+    // A payload we can't unseal is replaced, as the capture that follows a
+    // lost vault key always has been
+    if let Ok(Some(existing)) = read_payload(dir) {
+        PAYLOAD_SCHEMA.guard_write(dir, &existing)?;
+    }
+    let json = PAYLOAD_SCHEMA.encode(payload, false)?;
+    let plain = dir.join(PAYLOAD_PLAIN_FILE);
+
+    if crate::secrets::vault_available() {
+        let sealed = crate::secrets::seal(&json)?;
+        fs::write(dir.join(PAYLOAD_SEALED_FILE), sealed)?;
+        if plain.exists() {
+            let _ = fs::remove_file(&plain);
+        }
+        Ok(true)
+    } else {
+        log::warn!(
+            "[sessions] no keychain or master passphrase — storing session for {} unencrypted",
+            dir.display()
+        );
+        fs::write(&plain, json)?;
+        Ok(false)
+    }
+}
+
+/// The sealed payload file for `dir` and the payload sealed under `key`, for
+/// re-encrypting without writing anything yet.
+pub fn seal_payload_with(
+    dir: &Path,
+    payload: &SessionPayload,
+    key: &[u8; 32],
+) -> CommandResult<(PathBuf, Vec<u8>)> {
+    if let Ok(Some(existing)) = read_payload(dir) {
+        PAYLOAD_SCHEMA.guard_write(dir, &existing)?;
+    }
+    let json = PAYLOAD_SCHEMA.encode(payload, false)?;
+    let sealed = crate::secrets::seal_with(key, &json)?;
+    Ok((dir.join(PAYLOAD_SEALED_FILE), sealed))
+}
+
+/// Drop the unencrypted copy once a sealed payload has replaced it.
+pub fn remove_plain_payload(dir: &Path) {
+    let plain = dir.join(PAYLOAD_PLAIN_FILE);
+    if plain.exists() {
+        let _ = fs::remove_file(&plain);
+    }
+}
+
+/// 1 → 2: cookies gained `path`, `secure`, `http_only`, `same_site`, and
+/// `host_only`. Old captures kept none of them, so they get the defaults that
+/// send the cookie everywhere it used to go.
+fn scope_cookies(payload: &mut serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+    let Some(cookies) = payload.get_mut("cookies") else {
+        return Ok(());
+    };
+    let cookies = cookies.as_array_mut().ok_or("cookies is not a list")?;
+    for cookie in cookies {
+        let cookie = cookie.as_object_mut().ok_or("cookie is not an object")?;
+        let defaults = [
+            ("path", serde_json::json!("/")),
+            ("secure", false.into()),
+            ("http_only", false.into()),
+            ("same_site", serde_json::Value::Null),
+            ("host_only", false.into()),
+        ];
+        for (key, value) in defaults {
+            cookie.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
+}
+
+/// Ids become directory names, so they must be a single safe path component.
+pub fn validate_id(field: &str, id: &str) -> Result<(), String> {
+    if id.is_empty()
+        || id == "."
+        || id == ".."
+        || id.starts_with('.')
+        || id.contains(['/', '\\', ':'])
+    {
+        return Err(format!("Invalid {}: {:?}", field, id));
+    }
+    Ok(())
+}
+
+// ─── Legacy Migration ───────────────────────────────────────────────────────
+
+/// Split a legacy `<tenant>_<provider>` directory name.
+///
+/// Only known provider ids are accepted as the suffix, so `acme_corp_claude`
+/// resolves to tenant `acme_corp` + provider `claude`. Names that don't end in
+/// a known provider are left alone rather than guessed at.
+pub fn parse_legacy_name(name: &str) -> Option<(String, String)> {
+    legacy_readings(name)
+        .into_iter()
+        .max_by_key(|(_, provider)| provider.len())
+}
+
+/// Every tenant/provider pair a legacy name could stand for.
+fn legacy_readings(name: &str) -> Vec<(String, String)> {
+    KNOWN_PROVIDERS
+        .iter()
+        .filter_map(|provider| {
+            let tenant = name.strip_suffix(provider)?.strip_suffix('_')?;
+            (!tenant.is_empty()).then(|| (tenant.to_string(), provider.to_string()))
+        })
+        .collect()
+}
+
+/// A tenant directory in the nested layout holds provider subdirectories with
+/// their own metadata files; a legacy directory holds webview data directly.
+fn is_nested_tenant_dir(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.path().join(SESSION_META_FILE).is_file())
+        })
+        .unwrap_or(false)
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+pub fn migrate_layout_at(root: &Path) -> Result<SessionMigrationReport, String> {
+    let mut report = SessionMigrationReport::default();
+    if !root.exists() {
+        return Ok(report);
+    }
+
+    let entries = fs::read_dir(root).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.')
+            || path.join(SESSION_META_FILE).exists()
+            || is_nested_tenant_dir(&path)
+            || is_empty_dir(&path)
+        {
+            continue;
+        }
+
+        let Some((tenant_id, provider_id)) = parse_legacy_name(&name) else {
+            // Either a nested tenant dir with no providers yet or something
+            // we don't recognise — never guess.
+            if name.contains('_') {
+                report.skipped.push(SkippedSession {
+                    path: path.to_string_lossy().to_string(),
+                    reason: "no known provider suffix".into(),
+                });
+            }
+            continue;
+        };
+
+        let target = session_dir(root, &tenant_id, &provider_id);
+        if target.exists() {
+            report.skipped.push(SkippedSession {
+                path: path.to_string_lossy().to_string(),
+                reason: format!("target already exists: {}", target.display()),
+            });
+            continue;
+        }
+
+        let created_at = fs::metadata(&path)
+            .and_then(|m| m.created().or_else(|_| m.modified()))
+            .ok()
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+        fs::create_dir_all(root.join(&tenant_id)).map_err(|e| e.to_string())?;
+        fs::rename(&path, &target).map_err(|e| e.to_string())?;
+        write_meta(&target, &new_meta(&tenant_id, &provider_id, created_at))?;
+
+        log::info!(
+            "[sessions] migrated {} -> {}",
+            path.display(),
+            target.display()
+        );
+        report.moved.push(MovedSession {
+            from: path.to_string_lossy().to_string(),
+            to: target.to_string_lossy().to_string(),
+        });
+    }
+
+    for skipped in &report.skipped {
+        log::warn!(
+            "[sessions] left {} in place: {}",
+            skipped.path,
+            skipped.reason
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    #[test]
+    fn legacy_names_split_on_a_known_provider_suffix() {
+        let split = |name| parse_legacy_name(name);
+        assert_eq!(
+            split("acme_corp_claude"),
+            Some(("acme_corp".into(), "claude".into()))
+        );
+        assert_eq!(
+            split("acme_chatgpt"),
+            Some(("acme".into(), "chatgpt".into()))
+        );
+        assert_eq!(split("acme_corp"), None);
+        assert_eq!(split("_claude"), None);
+        assert_eq!(split("acmeclaude"), None);
+    }
+
+    #[test]
+    fn migration_nests_legacy_directories_and_leaves_nested_ones() {
+        let _home = TestHome::with_fixture(BASIC_FIXTURE);
+        let root = sessions_root().unwrap();
+
+        let report = migrate_layout_at(&root).unwrap();
+        assert_eq!(report.moved.len(), 1);
+        assert!(report.skipped.is_empty());
+        let moved = session_dir(&root, "acme_corp", "chatgpt");
+        assert!(moved.join("Cookies").is_file());
+        assert!(!root.join("acme_corp_chatgpt").exists());
+
+        let mut sessions: Vec<(String, String)> = list_sessions()
+            .into_iter()
+            .map(|s| (s.tenant_id, s.provider_id))
+            .collect();
+        sessions.sort();
+        assert_eq!(
+            sessions,
+            [
+                ("acme".into(), "claude".into()),
+                ("acme_corp".into(), "chatgpt".into())
+            ]
+        );
+
+        // A second run has nothing left to do
+        assert!(migrate_layout_at(&root).unwrap().moved.is_empty());
+    }
+
+    #[test]
+    fn a_flat_dir_is_only_adopted_by_the_pair_its_name_parses_to() {
+        let home = TestHome::new();
+        let root = sessions_root().unwrap();
+        home.write(".agentvbx/sessions/acme_corp_claude/Cookies", "");
+
+        // `acme` + `corp_claude` joins to the same flat name
+        assert!(find_session_dir(&root, "acme", "corp_claude").is_none());
+        let other = ensure_session_dir_at(&root, "acme", "corp_claude").unwrap();
+        assert_eq!(other, session_dir(&root, "acme", "corp_claude"));
+        assert!(!other.join("Cookies").exists());
+        assert!(root.join("acme_corp_claude/Cookies").is_file());
+
+        let adopted = ensure_session_dir_at(&root, "acme_corp", "claude").unwrap();
+        assert!(adopted.join("Cookies").is_file());
+        assert!(!root.join("acme_corp_claude").exists());
+    }
+
+    #[test]
+    fn migration_skips_unknown_suffixes_and_taken_targets() {
+        let home = TestHome::new();
+        let root = sessions_root().unwrap();
+        ensure_session_dir_at(&root, "acme", "claude").unwrap();
+        home.write(".agentvbx/sessions/acme_unknown/Cookies", "");
+        home.write(".agentvbx/sessions/acme_claude/Cookies", "");
+
+        let report = migrate_layout_at(&root).unwrap();
+        assert!(report.moved.is_empty());
+        let mut reasons: Vec<&str> = report.skipped.iter().map(|s| s.reason.as_str()).collect();
+        reasons.sort();
+        assert_eq!(reasons.len(), 2);
+        assert_eq!(reasons[0], "no known provider suffix");
+        assert!(reasons[1].starts_with("target already exists"));
+    }
+
+    #[test]
+    fn ids_must_be_a_single_safe_path_component() {
+        for id in ["", ".", "..", ".hidden", "a/b", "a\\b", "c:"] {
+            assert!(validate_id("tenant_id", id).is_err(), "{:?}", id);
+        }
+        assert!(validate_id("tenant_id", "acme_corp-1").is_ok());
+    }
+
+    #[test]
+    fn delete_refuses_ids_that_leave_the_session_dir() {
+        let _home = TestHome::with_fixture(BASIC_FIXTURE);
+        let root = sessions_root().unwrap();
+        ensure_session_dir_at(&root, "acme", "claude").unwrap();
+
+        for (tenant_id, provider_id) in [("acme", ".."), ("..", "acme"), ("acme/claude", "..")] {
+            let Err(err) = delete(tenant_id.into(), provider_id.into(), false) else {
+                panic!("{}/{} was deleted", tenant_id, provider_id);
+            };
+            assert_eq!(
+                err.code,
+                ErrorCode::InvalidInput,
+                "{}/{}",
+                tenant_id,
+                provider_id
+            );
+        }
+        assert!(session_dir(&root, "acme", "claude").is_dir());
+    }
+}
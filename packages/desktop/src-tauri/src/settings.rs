@@ -0,0 +1,534 @@
+// User settings
+//
+// Persisted as `config.json` in the config directory. Every field has a default, so a
+// missing file or a file written by an older version loads cleanly, and one
+// written by a newer version is read but never overwritten (see `schema`).
+// Secrets never live here — anything sensitive goes through the `secrets`
+// module.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::schema::Schema;
+use crate::{automation, http, integrity};
+
+/// `config.json`; new fields so far have all been additions with defaults.
+pub(crate) const SETTINGS_SCHEMA: Schema = Schema::new("settings", &[]);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Base URL of the orchestrator API server.
+    pub orchestrator_url: String,
+    pub orchestrator_tls: OrchestratorTlsSettings,
+    pub proxy: ProxySettings,
+    pub http: HttpSettings,
+    /// Warn about provider sessions expiring within this many hours.
+    pub session_expiry_warning_hours: u32,
+    /// Default interval of the `store_rescan` job; 0 turns it off by default.
+    pub rescan_interval_minutes: u32,
+    pub quick_capture: QuickCaptureSettings,
+    pub automation: AutomationSettings,
+    /// Trash entries older than this many days are emptied; 0 keeps them.
+    pub trash_retention_days: u32,
+    /// Provider usage entries older than this many days are dropped; 0
+    /// keeps them.
+    pub usage_retention_days: u32,
+    pub provider_cache: ProviderCacheSettings,
+    pub limits: FileLimitSettings,
+    /// MIME types by lowercase extension, over the built-in table; see
+    /// `mime_map`.
+    pub mime_overrides: BTreeMap<String, String>,
+    /// Allow `git_snapshot` to commit in stores that are git repositories.
+    pub git_snapshots_enabled: bool,
+    pub whatsapp: WhatsAppSettings,
+    pub scheduler: SchedulerSettings,
+    pub power: PowerSettings,
+    pub io_throttle: IoThrottleSettings,
+    pub quarantine: QuarantineSettings,
+    pub operations: OperationSettings,
+    /// UI language such as `de`; `None` follows the system.
+    pub locale: Option<String>,
+    pub developer: DeveloperSettings,
+    /// Calendars `get_events` reads.
+    pub calendars: Vec<CalendarSource>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            orchestrator_url: "http://localhost:3000".into(),
+            orchestrator_tls: OrchestratorTlsSettings::default(),
+            proxy: ProxySettings::default(),
+            http: HttpSettings::default(),
+            session_expiry_warning_hours: 24,
+            rescan_interval_minutes: 60,
+            quick_capture: QuickCaptureSettings::default(),
+            automation: AutomationSettings::default(),
+            trash_retention_days: 30,
+            usage_retention_days: 90,
+            provider_cache: ProviderCacheSettings::default(),
+            limits: FileLimitSettings::default(),
+            mime_overrides: BTreeMap::new(),
+            git_snapshots_enabled: false,
+            whatsapp: WhatsAppSettings::default(),
+            scheduler: SchedulerSettings::default(),
+            power: PowerSettings::default(),
+            io_throttle: IoThrottleSettings::default(),
+            quarantine: QuarantineSettings::default(),
+            operations: OperationSettings::default(),
+            locale: None,
+            developer: DeveloperSettings::default(),
+            calendars: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct QuickCaptureSettings {
+    /// Global shortcut accelerator, registered at startup when set.
+    pub shortcut: Option<String>,
+    /// Note captures are appended to, relative to the vault root.
+    pub inbox_note: String,
+}
+
+impl Default for QuickCaptureSettings {
+    fn default() -> Self {
+        Self {
+            shortcut: None,
+            inbox_note: "Inbox.md".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AutomationSettings {
+    /// Serve the local automation API on 127.0.0.1.
+    pub enabled: bool,
+    pub port: u16,
+    /// Largest request body accepted.
+    pub max_body_bytes: u64,
+    /// Bearer token for requests. Moved to the keychain on save, so it is
+    /// never stored here; an empty string removes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Default for AutomationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7345,
+            max_body_bytes: 64 * 1024,
+            token: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WhatsAppSettings {
+    /// Local WhatsApp bridge that holds the linked clients.
+    pub bridge_url: String,
+    /// Folder incoming media is imported into, by tenant id. Must be inside a
+    /// connected store.
+    pub inbox_paths: BTreeMap<String, String>,
+    /// Also log each import to the store's daily note (Obsidian vaults only).
+    pub log_to_daily_note: bool,
+    /// Sends allowed per tenant per minute; 0 means no limit.
+    pub messages_per_minute: u32,
+    /// Largest attachment `send_whatsapp_message` accepts; 0 means no limit.
+    pub max_attachment_bytes: u64,
+}
+
+impl Default for WhatsAppSettings {
+    fn default() -> Self {
+        Self {
+            bridge_url: "http://127.0.0.1:3210".into(),
+            inbox_paths: BTreeMap::new(),
+            log_to_daily_note: false,
+            messages_per_minute: 20,
+            // WhatsApp's own cap for photos and videos
+            max_attachment_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    /// Hold scheduled jobs while the OS is in battery saver.
+    pub respect_power_state: bool,
+    /// The user's changes to each job, by job id.
+    pub jobs: BTreeMap<String, JobOverride>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerSettings {
+    /// On battery below this percentage, heavy background jobs wait; 0 never
+    /// defers them.
+    pub defer_heavy_below_percent: u8,
+    /// Hold orchestrator uploads on metered connections unless the caller
+    /// passes `allow_metered`.
+    pub pause_uploads_on_metered: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            defer_heavy_below_percent: 30,
+            pause_uploads_on_metered: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IoThrottleSettings {
+    /// Read ceiling for background operations; 0 is unlimited.
+    pub bytes_per_second: u64,
+    /// Pause after each file a background operation reads.
+    pub file_pause_ms: u64,
+    /// On battery or in battery saver, where stricter than the above.
+    pub battery_bytes_per_second: u64,
+    pub battery_file_pause_ms: u64,
+}
+
+impl Default for IoThrottleSettings {
+    fn default() -> Self {
+        Self {
+            bytes_per_second: 64 * 1024 * 1024,
+            file_pause_ms: 0,
+            battery_bytes_per_second: 8 * 1024 * 1024,
+            battery_file_pause_ms: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct QuarantineSettings {
+    /// Scanner for files from outside; without one only the heuristics run.
+    pub scanner: Option<ScannerSettings>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScannerSettings {
+    pub program: String,
+    /// `{path}` is replaced by the file; without it the path goes last.
+    pub args: Vec<String>,
+    /// Past this the file stays quarantined.
+    pub timeout_secs: u64,
+}
+
+impl Default for ScannerSettings {
+    fn default() -> Self {
+        Self {
+            program: String::new(),
+            args: Vec::new(),
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProviderCacheSettings {
+    /// Total size of cached provider responses across all sessions.
+    pub max_bytes: u64,
+    /// How long a cached response stays fresh, for categories not in
+    /// `ttl_secs`.
+    pub default_ttl_secs: u64,
+    /// Per usage category, such as `conversations`; 0 never caches it.
+    pub ttl_secs: BTreeMap<String, u64>,
+}
+
+impl Default for ProviderCacheSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024,
+            default_ttl_secs: 300,
+            ttl_secs: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OperationSettings {
+    /// Total weight of operations running at once; more wait in the queue.
+    pub max_concurrent_weight: u32,
+    /// Caps per operation kind (`store_rescan`, `backup`, …), replacing the
+    /// built-in ones.
+    pub per_kind_limits: BTreeMap<String, u32>,
+}
+
+impl Default for OperationSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_weight: 4,
+            per_kind_limits: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DeveloperSettings {
+    /// Offer the simulated `mock` provider. Release builds also need
+    /// `AGENTVBX_ENABLE_MOCK_PROVIDER=1`.
+    pub mock_provider: bool,
+    /// How long a mock session stays active after capture.
+    pub mock_session_active_secs: u64,
+    /// How long it is then reported as expiring before it expires.
+    pub mock_session_expiring_secs: u64,
+    /// Open devtools on the main window at launch, in debug builds. Also
+    /// `AGENTVBX_DEVTOOLS=1`.
+    pub open_devtools: bool,
+}
+
+impl Default for DeveloperSettings {
+    fn default() -> Self {
+        Self {
+            mock_provider: false,
+            mock_session_active_secs: 120,
+            mock_session_expiring_secs: 60,
+            open_devtools: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CalendarSource {
+    pub name: String,
+    /// An `.ics` file, or an `http`, `https`, or `webcal` feed URL.
+    pub location: String,
+    pub enabled: bool,
+}
+
+impl Default for CalendarSource {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            location: String::new(),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct JobOverride {
+    pub enabled: Option<bool>,
+    /// Replaces the job's default schedule, e.g. `every 30m` or `daily 03:00`.
+    pub schedule: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FileLimitSettings {
+    /// Largest file `read_text_file` returns without `force`.
+    pub max_text_read_bytes: u64,
+    /// Largest file `hash_file` hashes; 0 means no limit.
+    pub max_hash_bytes: u64,
+    /// Larger files get a sampled fingerprint in store snapshots instead of a
+    /// full hash; 0 hashes every file in full. See `fingerprint`.
+    pub full_hash_max_bytes: u64,
+    /// Largest range `read_text_range` returns at once.
+    pub max_preview_bytes: u64,
+    /// Writes smaller than this skip the free-space check.
+    pub space_check_min_bytes: u64,
+    /// Free space large writes must leave on the volume.
+    pub space_margin_bytes: u64,
+    /// Deadline in milliseconds for a file command by name, on any volume,
+    /// in place of its default; see `fs_deadline`.
+    pub timeout_ms: BTreeMap<String, u64>,
+}
+
+impl Default for FileLimitSettings {
+    fn default() -> Self {
+        Self {
+            max_text_read_bytes: 10 * 1024 * 1024,
+            max_hash_bytes: 0,
+            full_hash_max_bytes: 8 * 1024 * 1024,
+            max_preview_bytes: 1024 * 1024,
+            space_check_min_bytes: 16 * 1024 * 1024,
+            space_margin_bytes: 256 * 1024 * 1024,
+            timeout_ms: BTreeMap::new(),
+        }
+    }
+}
+
+/// TLS options for self-hosted orchestrators behind internal CAs.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct OrchestratorTlsSettings {
+    /// PEM file with extra CA certificates, trusted alongside the public roots.
+    pub ca_bundle_path: Option<String>,
+    /// Base64 SHA-256 of the server's SubjectPublicKeyInfo, optionally
+    /// prefixed `sha256/`. Checked for the orchestrator host only.
+    pub spki_pin: Option<String>,
+    /// Skip certificate validation entirely. Logged on every use and reported
+    /// as a warning by `get_health`.
+    pub allow_invalid_certs: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    /// Use the OS / environment proxy configuration.
+    #[default]
+    System,
+    /// Use `ProxySettings::url`.
+    Manual,
+    /// Connect directly, ignoring any system proxy.
+    None,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub mode: ProxyMode,
+    /// `http://`, `https://`, or `socks5://` proxy URL. Credentials are
+    /// stripped on save and kept in the keychain.
+    pub url: Option<String>,
+    /// Hosts that bypass the proxy (`internal.example.com`, `.corp`, `10.0.0.1`).
+    pub bypass: Vec<String>,
+}
+
+/// Retries and circuit breaking for requests sent through `http_retry`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HttpSettings {
+    /// Tries per request, counting the first; 1 turns retries off.
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubling with each one.
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+    /// A `Retry-After` longer than this isn't waited out; the response is
+    /// returned as it is.
+    pub max_retry_after_secs: u64,
+    /// Consecutive failures that open a host's circuit; 0 never opens it.
+    pub circuit_failure_threshold: u32,
+    /// How long an open circuit fails requests before letting one through.
+    pub circuit_cooldown_secs: u64,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_base_delay_ms: 250,
+            retry_max_delay_ms: 8_000,
+            max_retry_after_secs: 60,
+            circuit_failure_threshold: 5,
+            circuit_cooldown_secs: 30,
+        }
+    }
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_settings() -> Settings {
+    load_settings()
+}
+
+/// Merge a partial settings object into the stored settings.
+///
+/// `patch` uses the same shape as `Settings`; omitted fields are left as-is.
+#[tauri::command]
+pub fn update_settings(patch: serde_json::Value) -> CommandResult<Settings> {
+    let previous = load_settings();
+    let current = serde_json::to_value(&previous).map_err(|e| e.to_string())?;
+    let merged = merge_json(current, patch);
+    let mut settings: Settings = serde_json::from_value(merged).map_err(|e| {
+        CommandError::new(ErrorCode::InvalidInput, format!("Invalid settings: {}", e))
+    })?;
+
+    http::stash_proxy_credentials(&mut settings.proxy, &previous.proxy)?;
+    automation::stash_token(&mut settings.automation)?;
+    save_settings(&settings)?;
+    automation::apply(&settings.automation);
+    Ok(settings)
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+pub fn settings_path() -> CommandResult<PathBuf> {
+    Ok(home::config_dir()?.join("config.json"))
+}
+
+/// Load settings, falling back to defaults when the file is missing or
+/// unreadable so a bad edit never keeps the app from starting.
+pub fn load_settings() -> Settings {
+    settings_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, Some(&SETTINGS_SCHEMA)))
+        .unwrap_or_default()
+}
+
+pub fn save_settings(settings: &Settings) -> CommandResult<()> {
+    integrity::persist_json(&settings_path()?, settings, Some(&SETTINGS_SCHEMA))
+}
+
+impl integrity::Validate for Settings {
+    fn validate(&self) -> Result<(), String> {
+        if self.http.max_attempts == 0 {
+            return Err("http.max_attempts must be at least 1".into());
+        }
+        if self.automation.enabled && self.automation.port == 0 {
+            return Err("automation.port must be set to enable it".into());
+        }
+        Ok(())
+    }
+}
+
+/// Recursively merge `patch` into `base`; objects merge, everything else replaces.
+fn merge_json(base: serde_json::Value, patch: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match (base, patch) {
+        (Value::Object(mut base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Object(base)
+        }
+        (_, patch) => patch,
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn updates_without_a_proxy_never_touch_the_secret_store() {
+        let _home = TestHome::new();
+        // No proxy before or after; a keychain-less machine must not fail this
+        let settings = update_settings(serde_json::json!({
+            "limits": { "full_hash_max_bytes": 1024 }
+        }))
+        .unwrap();
+        assert_eq!(settings.limits.full_hash_max_bytes, 1024);
+        assert_eq!(settings.proxy.url, None);
+        assert_eq!(load_settings().limits.full_hash_max_bytes, 1024);
+    }
+}
@@ -0,0 +1,565 @@
+// Similar notes
+//
+// `find_similar_notes` looks for notes close to some text, so an agent can
+// check for an existing note before writing a new one. A note's body
+// (frontmatter removed, lowercased, split into words) is cut into
+// overlapping shingles of `SHINGLE_WORDS` words. Its signature is the
+// minimum of each of `NUM_HASHES` seeded hashes over those shingles
+// (MinHash), and the share of slots two signatures agree on estimates how
+// much their shingles overlap. The hashes use fixed seeds, so signatures and
+// rankings are the same on every machine; equal scores rank by path.
+//
+// Notes under `MIN_WORDS` words are never matched, and a query that short is
+// refused: a few shared words make short texts look alike.
+//
+// For a connected store the signatures are cached in
+// `similarity/<store id>.json` in the cache directory, stamped with each
+// note's size and mtime from the store's scan. A rescan re-signs the notes
+// that changed and drops the ones that went away; a query also re-signs
+// anything still stale, so results never lag the disk. Other folders are
+// signed on each call.
+//
+// A match's excerpt is the first run of its text whose shingles also appear
+// in the query, cut to `EXCERPT_CHARS`.
+//
+// Notes the vault bookmarks rank as if `BOOKMARK_BOOST` more slots agreed,
+// so they win close calls; their reported similarity is left as it is.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::mime_map::MimeMap;
+use crate::stores::{self, ConnectedStore, Snapshot};
+use crate::{home, notes, obsidian_bookmarks, text_style};
+
+const SHINGLE_WORDS: usize = 3;
+const NUM_HASHES: usize = 64;
+const MIN_WORDS: usize = 20;
+const EXCERPT_CHARS: usize = 240;
+const BOOKMARK_BOOST: usize = 2;
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 50;
+
+static CACHE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    notes: BTreeMap<String, CachedNote>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedNote {
+    size: u64,
+    modified: i64,
+    /// `None` for notes too short to match.
+    signature: Option<Vec<u32>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SimilarNote {
+    pub path: String,
+    pub relative_path: String,
+    /// Estimated share of shingles in common, 0 to 1.
+    pub similarity: f64,
+    pub excerpt: String,
+    pub bookmarked: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SimilarNotes {
+    pub matches: Vec<SimilarNote>,
+    /// Notes long enough to compare.
+    pub notes_compared: usize,
+    /// Notes under the minimum length.
+    pub notes_too_short: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Notes in `vault_path` most like `content_or_path`: a note's path
+/// (absolute or vault-relative), which is left out of the matches, or text.
+#[tauri::command]
+pub async fn find_similar_notes(
+    vault_path: String,
+    content_or_path: String,
+    limit: Option<usize>,
+) -> CommandResult<SimilarNotes> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let vault = PathBuf::from(&vault_path);
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", &vault_path));
+    }
+    tauri::async_runtime::spawn_blocking(move || find(&vault, &content_or_path, limit))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn find(vault: &Path, content_or_path: &str, limit: usize) -> CommandResult<SimilarNotes> {
+    let (query, own_key) = match query_note(vault, content_or_path) {
+        Some(key) => (read_note(&vault.join(&key))?, Some(key)),
+        None => (content_or_path.to_string(), None),
+    };
+    let query_words = words(body(&query));
+    if query_words.len() < MIN_WORDS {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "{} words is too short to compare; at least {} are needed",
+                query_words.len(),
+                MIN_WORDS
+            ),
+        )
+        .with("words", query_words.len())
+        .with("min_words", MIN_WORDS));
+    }
+    let query_shingles = shingles(body(&query), &query_words);
+    let query_signature = signature(&query_shingles);
+    let query_set: HashSet<u64> = query_shingles.into_iter().collect();
+
+    let cache = signatures(vault)?;
+    let bookmarked = obsidian_bookmarks::bookmarked_paths(vault);
+    let mut scored: Vec<(usize, &str)> = Vec::new();
+    let mut notes_too_short = 0;
+    for (key, note) in &cache.notes {
+        if Some(key) == own_key.as_ref() {
+            continue;
+        }
+        let Some(sig) = &note.signature else {
+            notes_too_short += 1;
+            continue;
+        };
+        let agreeing = sig
+            .iter()
+            .zip(&query_signature)
+            .filter(|(a, b)| a == b)
+            .count();
+        if agreeing > 0 {
+            scored.push((agreeing, key));
+        }
+    }
+    let notes_compared = cache.notes.len()
+        - notes_too_short
+        - own_key
+            .as_ref()
+            .map_or(0, |k| cache.notes.contains_key(k) as usize);
+    // Keys come in path order, so the sort keeps that order for equal scores
+    scored.sort_by_key(|&(agreeing, key)| {
+        let boost = if bookmarked.contains(key) {
+            BOOKMARK_BOOST
+        } else {
+            0
+        };
+        std::cmp::Reverse(agreeing + boost)
+    });
+    scored.truncate(limit);
+
+    let matches = scored
+        .into_iter()
+        .map(|(agreeing, key)| {
+            let path = vault.join(key);
+            let excerpt = read_note(&path)
+                .map(|text| excerpt(body(&text), &query_set))
+                .unwrap_or_default();
+            SimilarNote {
+                path: path.to_string_lossy().to_string(),
+                relative_path: key.to_string(),
+                similarity: agreeing as f64 / NUM_HASHES as f64,
+                excerpt,
+                bookmarked: bookmarked.contains(key),
+            }
+        })
+        .collect();
+    Ok(SimilarNotes {
+        matches,
+        notes_compared,
+        notes_too_short,
+    })
+}
+
+/// The vault key `content_or_path` names, if it's a path to a note there.
+fn query_note(vault: &Path, content_or_path: &str) -> Option<String> {
+    if content_or_path.contains('\n') {
+        return None;
+    }
+    let path = Path::new(content_or_path.trim());
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        vault.join(path)
+    };
+    if !path.is_file() {
+        return None;
+    }
+    let relative = path.strip_prefix(vault).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+// ─── Signatures ─────────────────────────────────────────────────────────────
+
+/// Signatures for every note in `vault`, cached when it's a connected store.
+fn signatures(vault: &Path) -> CommandResult<Cache> {
+    let snapshot = stores::scan_for_index(vault)?;
+    let store = stores::store_for_path(vault).filter(|s| Path::new(&s.path) == vault);
+    let Some(store) = store else {
+        let mut cache = Cache::default();
+        update(&mut cache, vault, &snapshot);
+        return Ok(cache);
+    };
+    let _guard = CACHE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut cache = load_cache(&store.id);
+    if update(&mut cache, vault, &snapshot) {
+        save_cache(&store.id, &cache)?;
+    }
+    Ok(cache)
+}
+
+/// Bring a store's cached signatures up to date with its new snapshot.
+/// Called after each rescan.
+pub fn refresh_store(store: &ConnectedStore, snapshot: &Snapshot) {
+    let _guard = CACHE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut cache = load_cache(&store.id);
+    if update(&mut cache, Path::new(&store.path), snapshot) {
+        if let Err(e) = save_cache(&store.id, &cache) {
+            log::warn!(
+                "[similar_notes] can't save signatures for {}: {}",
+                store.id,
+                e
+            );
+        }
+    }
+}
+
+/// Re-sign notes whose size or mtime changed and drop the ones that are
+/// gone. True if anything changed.
+fn update(cache: &mut Cache, root: &Path, snapshot: &Snapshot) -> bool {
+    let before = cache.notes.len();
+    cache
+        .notes
+        .retain(|key, _| snapshot.get(key).is_some_and(|e| !e.placeholder));
+    let mut changed = cache.notes.len() != before;
+
+    let mimes = MimeMap::load();
+    let stale: Vec<(&String, &stores::SnapshotEntry)> = snapshot
+        .iter()
+        .filter(|(key, entry)| mimes.is_markdown(key) && !entry.placeholder)
+        .filter(|(key, entry)| {
+            cache
+                .notes
+                .get(*key)
+                .is_none_or(|c| c.size != entry.size || c.modified != entry.modified)
+        })
+        .collect();
+    let signed: Vec<(String, CachedNote)> = stale
+        .par_iter()
+        .filter_map(|(key, entry)| {
+            let text = read_note(&root.join(key)).ok()?;
+            let body = body(&text);
+            let words = words(body);
+            let signature = (words.len() >= MIN_WORDS).then(|| signature(&shingles(body, &words)));
+            Some((
+                key.to_string(),
+                CachedNote {
+                    size: entry.size,
+                    modified: entry.modified,
+                    signature,
+                },
+            ))
+        })
+        .collect();
+    changed |= !signed.is_empty();
+    cache.notes.extend(signed);
+    changed
+}
+
+fn read_note(path: &Path) -> CommandResult<String> {
+    let bytes = fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => CommandError::new(
+            ErrorCode::NotFound,
+            format!("File not found: {}", path.display()),
+        )
+        .with("path", path.to_string_lossy()),
+        _ => e.into(),
+    })?;
+    Ok(text_style::decode(&bytes, &text_style::detect(&bytes)))
+}
+
+/// `text` without its frontmatter.
+fn body(text: &str) -> &str {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return text;
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            return &rest[offset..];
+        }
+    }
+    text
+}
+
+/// Byte ranges of the words in `text`: runs of letters and digits.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len()));
+    }
+    words
+}
+
+/// A hash of each run of `SHINGLE_WORDS` words, in order.
+fn shingles(text: &str, words: &[(usize, usize)]) -> Vec<u64> {
+    words
+        .windows(SHINGLE_WORDS)
+        .map(|window| {
+            let mut hash = FNV_OFFSET;
+            for (i, &(start, end)) in window.iter().enumerate() {
+                if i > 0 {
+                    hash = fnv1a(hash, b" ");
+                }
+                for c in text[start..end].chars().flat_map(char::to_lowercase) {
+                    hash = fnv1a(hash, c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+            }
+            hash
+        })
+        .collect()
+}
+
+fn signature(shingles: &[u64]) -> Vec<u32> {
+    (0..NUM_HASHES as u64)
+        .map(|slot| {
+            let seed = splitmix64(slot.wrapping_add(SEED_BASE));
+            shingles
+                .iter()
+                .map(|&shingle| (splitmix64(shingle ^ seed) >> 32) as u32)
+                .min()
+                .unwrap_or(u32::MAX)
+        })
+        .collect()
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const SEED_BASE: u64 = 0x5eed_0fa9_e07b_5853;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The first run of `text` whose shingles are also in `query`, on one line.
+fn excerpt(text: &str, query: &HashSet<u64>) -> String {
+    let words = words(text);
+    let hashes = shingles(text, &words);
+    let (start, end) = match hashes.iter().position(|h| query.contains(h)) {
+        Some(first) => {
+            let run = hashes[first..]
+                .iter()
+                .take_while(|h| query.contains(h))
+                .count();
+            (words[first].0, words[first + run - 1 + SHINGLE_WORDS - 1].1)
+        }
+        None => (0, text.len()),
+    };
+    let flat = text[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if flat.chars().count() <= EXCERPT_CHARS {
+        return flat;
+    }
+    let cut: String = flat.chars().take(EXCERPT_CHARS).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) => &cut[..space],
+        None => &cut,
+    };
+    format!("{}…", cut)
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn cache_path(store_id: &str) -> CommandResult<PathBuf> {
+    Ok(home::cache_dir()?
+        .join("similarity")
+        .join(format!("{}.json", store_id)))
+}
+
+fn load_cache(store_id: &str) -> Cache {
+    cache_path(store_id)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(store_id: &str, cache: &Cache) -> CommandResult<()> {
+    let path = cache_path(store_id)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_vec(cache).map_err(|e| e.to_string())?;
+    notes::write_atomic(&path, &json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    const LAUNCH: &str = "The launch plan covers the beta invite list, the pricing page \
+        copy, the press kit, support macros for the first week, and a rollback \
+        checklist in case the billing migration fails during the cutover window.";
+    const RETRO: &str = "Notes from the retro: the pricing page copy, the press kit, \
+        support macros for the first week were late. Next time start the design \
+        review earlier and book the photographer two weeks ahead of the shoot.";
+    const GARDEN: &str = "Tomatoes need staking by June. Water the beans every other \
+        morning, mulch the squash bed, and move the basil pots into the greenhouse \
+        before the first frost arrives in the valley this autumn season.";
+
+    fn vault(home: &TestHome) -> PathBuf {
+        home.write(
+            "Vault/Launch.md",
+            &format!("---\ntags: [work]\n---\n{}\n", LAUNCH),
+        );
+        home.write("Vault/Retro.md", RETRO);
+        home.write("Vault/garden/Garden.md", GARDEN);
+        home.write("Vault/Stub.md", "The launch plan covers the beta");
+        home.join("Vault")
+    }
+
+    #[test]
+    fn ranks_by_shared_shingles_and_skips_short_notes() {
+        let home = TestHome::new();
+        let vault = vault(&home);
+        let query = format!("Draft: {}", LAUNCH);
+        let found = find(&vault, &query, 5).unwrap();
+        let ranked: Vec<&str> = found
+            .matches
+            .iter()
+            .map(|m| m.relative_path.as_str())
+            .collect();
+        assert_eq!(ranked[..2], ["Launch.md", "Retro.md"]);
+        assert!(!ranked.contains(&"Stub.md"));
+        assert!(found.matches[0].similarity > found.matches[1].similarity);
+        assert_eq!(found.notes_compared, 3);
+        assert_eq!(found.notes_too_short, 1);
+        assert!(found.matches[1]
+            .excerpt
+            .starts_with("the pricing page copy, the press kit"));
+
+        // Same input, same answer
+        let again = find(&vault, &query, 5).unwrap();
+        let scores = |f: &SimilarNotes| f.matches.iter().map(|m| m.similarity).collect::<Vec<_>>();
+        assert_eq!(scores(&again), scores(&found));
+
+        // A path queries with that note, which isn't its own match
+        let by_path = find(&vault, "Launch.md", 5).unwrap();
+        assert_eq!(by_path.matches[0].relative_path, "Retro.md");
+        assert!(by_path
+            .matches
+            .iter()
+            .all(|m| m.relative_path != "Launch.md"));
+
+        let err = find(&vault, "too short", 5).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert_eq!(err.params["min_words"], MIN_WORDS);
+    }
+
+    #[test]
+    fn bookmarked_notes_win_close_calls() {
+        let home = TestHome::new();
+        home.write("Vault/A.md", RETRO);
+        home.write("Vault/B.md", RETRO);
+        let vault = home.join("Vault");
+        let first = |vault: &Path| find(vault, RETRO, 5).unwrap().matches[0].clone();
+        assert_eq!(first(&vault).relative_path, "A.md");
+
+        home.write(
+            "Vault/.obsidian/bookmarks.json",
+            r#"{"items": [{"type": "file", "path": "B.md"}]}"#,
+        );
+        let top = first(&vault);
+        assert_eq!(top.relative_path, "B.md");
+        assert!(top.bookmarked);
+        assert_eq!(top.similarity, 1.0);
+    }
+
+    #[test]
+    fn identical_bodies_match_fully() {
+        let text = format!("---\ntitle: x\n---\n{}", GARDEN);
+        let a = signature(&shingles(body(&text), &words(body(&text))));
+        let b = signature(&shingles(GARDEN, &words(GARDEN)));
+        assert_eq!(a, b);
+        assert_eq!(a.len(), NUM_HASHES);
+        // Case and punctuation don't count
+        let shouted = GARDEN.to_uppercase().replace(',', ";");
+        assert_eq!(signature(&shingles(&shouted, &words(&shouted))), a);
+    }
+
+    #[test]
+    fn store_signatures_follow_rescans() {
+        let home = TestHome::new();
+        let vault = vault(&home);
+        let store = stores::register_store(
+            "Vault".to_string(),
+            vault.to_string_lossy().to_string(),
+            None,
+        )
+        .unwrap();
+        let cached = load_cache(&store.id);
+        assert_eq!(cached.notes.len(), 4);
+        assert!(cached.notes["Stub.md"].signature.is_none());
+
+        fs::remove_file(vault.join("Retro.md")).unwrap();
+        home.write("Vault/Retro copy.md", RETRO);
+        stores::rescan(&store.id).unwrap();
+        let cached = load_cache(&store.id);
+        assert!(!cached.notes.contains_key("Retro.md"));
+        assert!(cached.notes["Retro copy.md"].signature.is_some());
+
+        let found = find(&vault, LAUNCH, 5).unwrap();
+        assert_eq!(found.matches[0].relative_path, "Launch.md");
+        assert_eq!(found.matches[0].similarity, 1.0);
+        assert_eq!(found.matches[1].relative_path, "Retro copy.md");
+    }
+}
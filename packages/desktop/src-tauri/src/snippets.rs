@@ -0,0 +1,181 @@
+// Listing snippets
+//
+// `list_directory` with `include_snippets` puts the opening of each text
+// file on its entry, so the store browser can show a note's first lines
+// without reading every file itself. A snippet is the first
+// `SNIPPET_LINES` non-blank lines of the body, frontmatter removed, cut to
+// `snippet_bytes` (default `DEFAULT_SNIPPET_BYTES`) on a character boundary.
+//
+// Only the head of a file is read, `HEAD_BYTES` past the snippet to leave
+// room for frontmatter, and it's decoded the way `text_style` detects it,
+// so UTF-16 notes read as text. Binaries, encrypted notes, files over
+// `limits.max_text_read_bytes`, and notes whose frontmatter runs past the
+// head have no snippet. To keep listings fast, only the first
+// `MAX_SNIPPET_ENTRIES` files of a listing get one, read in parallel.
+
+use rayon::prelude::*;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{frontmatter, settings, text_style, FileEntry};
+
+pub const DEFAULT_SNIPPET_BYTES: usize = 200;
+const MAX_SNIPPET_BYTES: usize = 4096;
+const MAX_SNIPPET_ENTRIES: usize = 200;
+const SNIPPET_LINES: usize = 2;
+/// Read past the snippet, for frontmatter.
+const HEAD_BYTES: usize = 4096;
+
+/// Attach snippets to the first `MAX_SNIPPET_ENTRIES` text files.
+pub fn attach(entries: &mut [FileEntry], snippet_bytes: Option<usize>) {
+    let snippet_bytes = snippet_bytes
+        .unwrap_or(DEFAULT_SNIPPET_BYTES)
+        .clamp(1, MAX_SNIPPET_BYTES);
+    let max_file_bytes = settings::load_settings().limits.max_text_read_bytes;
+    let mut candidates: Vec<&mut FileEntry> = entries
+        .iter_mut()
+        .filter(|e| {
+            !e.is_directory
+                && !e.encrypted
+                && e.size_bytes <= max_file_bytes
+                && is_text(&e.mime_type)
+        })
+        .take(MAX_SNIPPET_ENTRIES)
+        .collect();
+    candidates.par_iter_mut().for_each(|entry| {
+        entry.snippet = snippet(Path::new(&entry.path), snippet_bytes);
+    });
+}
+
+fn is_text(mime: &str) -> bool {
+    mime.starts_with("text/") || matches!(mime, "application/json")
+}
+
+/// The snippet of the file at `path`, if it has a readable text body.
+pub fn snippet(path: &Path, snippet_bytes: usize) -> Option<String> {
+    let mut head = Vec::new();
+    fs::File::open(path)
+        .ok()?
+        .take((HEAD_BYTES + snippet_bytes) as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+    let style = text_style::detect(&head);
+    if style.encoding == text_style::TextEncoding::Utf8 && looks_binary(&head) {
+        return None;
+    }
+    let text = text_style::decode(&head, &style);
+    // The head may end partway through a character
+    let text = text.trim_end_matches('\u{FFFD}');
+    let body = body(text)?;
+
+    let lines: Vec<&str> = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(SNIPPET_LINES)
+        .collect();
+    let mut snippet = lines.join("\n");
+    if snippet.len() > snippet_bytes {
+        let mut end = snippet_bytes;
+        while !snippet.is_char_boundary(end) {
+            end -= 1;
+        }
+        snippet.truncate(end);
+    }
+    (!snippet.is_empty()).then_some(snippet)
+}
+
+/// `text` without its frontmatter; `None` if the block doesn't close.
+fn body(text: &str) -> Option<&str> {
+    if !matches!(text.lines().next().map(str::trim_end), Some("---")) {
+        return Some(text);
+    }
+    let fm = frontmatter::parse(text)?;
+    let skip: usize = text
+        .split_inclusive('\n')
+        .take(fm.end + 1)
+        .map(str::len)
+        .sum();
+    Some(&text[skip..])
+}
+
+/// NUL bytes don't occur in UTF-8 text.
+fn looks_binary(head: &[u8]) -> bool {
+    head.contains(&0)
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    #[test]
+    fn snippets_skip_frontmatter_and_blank_lines() {
+        let home = TestHome::new();
+        home.write(
+            "Notes/Plan.md",
+            "---\ntags: [q3]\n---\n\n#  Q3 plan\n\nShip the importer.\nThen the rest.\n",
+        );
+        let path = home.join("Notes/Plan.md");
+        assert_eq!(
+            snippet(&path, 200).as_deref(),
+            Some("#  Q3 plan\nShip the importer.")
+        );
+        assert_eq!(snippet(&path, 6).as_deref(), Some("#  Q3 "));
+    }
+
+    #[test]
+    fn utf16_notes_decode_and_cuts_respect_characters() {
+        let home = TestHome::new();
+        let path = home.join("Übersicht.txt");
+        fs::write(&path, utf16le("Grüße aus Köln\r\nzweite Zeile\r\n")).unwrap();
+        assert_eq!(
+            snippet(&path, 200).as_deref(),
+            Some("Grüße aus Köln\nzweite Zeile")
+        );
+        // "ü" is two bytes; a cut inside it backs off
+        assert_eq!(snippet(&path, 3).as_deref(), Some("Gr"));
+    }
+
+    #[test]
+    fn binaries_and_unclosed_frontmatter_have_none() {
+        let home = TestHome::new();
+        let binary = home.join("data.txt");
+        fs::write(&binary, b"PK\x03\x04\x00\x00binary").unwrap();
+        assert_eq!(snippet(&binary, 200), None);
+
+        home.write(
+            "Long.md",
+            &format!("---\n{}---\nbody\n", "key: value\n".repeat(1000)),
+        );
+        assert_eq!(snippet(&home.join("Long.md"), 200), None);
+    }
+
+    #[test]
+    fn listings_cap_snippets_and_skip_other_types() {
+        let home = TestHome::new();
+        for i in 0..MAX_SNIPPET_ENTRIES + 5 {
+            home.write(&format!("Wide/note-{:04}.md", i), "hello\n");
+        }
+        home.write("Wide/a-photo.png", "not really a png\n");
+        let collator = crate::collation::Collator::new(Default::default(), true, "en");
+        let mut entries = crate::list_entries(&home.join("Wide"), &collator).unwrap();
+        attach(&mut entries, None);
+
+        assert_eq!(entries[0].name, "a-photo.png");
+        assert_eq!(entries[0].snippet, None);
+        let with_snippets = entries.iter().filter(|e| e.snippet.is_some()).count();
+        assert_eq!(with_snippets, MAX_SNIPPET_ENTRIES);
+        assert_eq!(entries[1].snippet.as_deref(), Some("hello"));
+        assert_eq!(entries.last().unwrap().snippet, None);
+    }
+}
@@ -0,0 +1,302 @@
+// Startup phases
+//
+// The backend starts in named phases, each timed into a timeline that
+// `get_startup_timeline` returns for the diagnostics panel:
+// - `integrity`: the data file check, before the app is built
+// - `shell`: crash reporting, the global shortcut plugin, quick capture, and
+//   devtools, in `setup` on the main thread
+// - then, on a `startup` thread so the window isn't held up by a slow disk:
+//   `home` (data directories), `migrations` (session layout), `settings`,
+//   `stores` (saved folder grants re-opened), `jobs` (maintenance mode kept
+//   from before a restart, scheduled jobs, and system state), and `listeners` (WhatsApp, automation, the task inbox)
+// After the last phase, `backend-ready` goes out once with the loaded
+// settings, the store list, this launch's integrity repairs, and the
+// timeline. It goes through `event_journal`, so a webview that loads late
+// finds it with `catch_up_events`.
+//
+// Until then, `guard` holds commands back: a command waits up to
+// `READY_WAIT` for the phases to finish, then fails with `NotReady` rather
+// than run on half-loaded state. The commands in `BEFORE_READY` only read
+// what exists from the start, so they run at once.
+
+use serde::Serialize;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::error::{CommandError, ErrorCode};
+use crate::integrity::{self, Finding};
+use crate::settings::{self, Settings};
+use crate::stores::{self, ConnectedStore};
+use crate::{
+    artifact_upload, automation, event_journal, home, maintenance, provider_usage, recipes,
+    scheduler, session_expiry, sessions, system_state, task_inbox, trash, whatsapp_media,
+    whatsapp_send,
+};
+
+/// How long a command sent before readiness waits for it.
+const READY_WAIT: Duration = Duration::from_secs(5);
+
+/// Commands that don't wait for readiness.
+const BEFORE_READY: &[&str] = &[
+    "get_health",
+    "get_startup_timeline",
+    "get_startup_report",
+    "get_diagnostics",
+    "get_pending_crash_reports",
+    "catch_up_events",
+    "get_error_catalog",
+];
+
+static TIMELINE: Mutex<Option<Timeline>> = Mutex::new(None);
+static READY_CHANGED: Condvar = Condvar::new();
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+struct Timeline {
+    started: Instant,
+    started_at: String,
+    phases: Vec<StartupPhase>,
+    ready_ms: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StartupPhase {
+    pub name: &'static str,
+    /// Milliseconds after startup began.
+    pub started_ms: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StartupTimeline {
+    pub started_at: String,
+    pub phases: Vec<StartupPhase>,
+    /// Milliseconds from start to `backend-ready`; `None` until then.
+    pub ready_ms: Option<u64>,
+}
+
+/// Payload of `backend-ready`.
+#[derive(Serialize, Clone)]
+pub struct BackendReady {
+    pub settings: Settings,
+    pub stores: Vec<ConnectedStore>,
+    /// What the integrity check found and repaired this launch.
+    pub repairs: Vec<Finding>,
+    pub timeline: StartupTimeline,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_startup_timeline() -> StartupTimeline {
+    timeline()
+}
+
+// ─── Phases ─────────────────────────────────────────────────────────────────
+
+/// Start the timeline; the first thing the app does.
+pub fn begin() {
+    *TIMELINE.lock().unwrap() = Some(Timeline {
+        started: Instant::now(),
+        started_at: chrono::Utc::now().to_rfc3339(),
+        phases: Vec::new(),
+        ready_ms: None,
+    });
+}
+
+/// Run `work` as the phase `name`.
+pub fn phase<T>(name: &'static str, work: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = work();
+    if let Some(timeline) = TIMELINE.lock().unwrap().as_mut() {
+        let offset = started.saturating_duration_since(timeline.started);
+        let duration_ms = started.elapsed().as_millis() as u64;
+        log::info!("[startup] {} took {} ms", name, duration_ms);
+        timeline.phases.push(StartupPhase {
+            name,
+            started_ms: offset.as_millis() as u64,
+            duration_ms,
+        });
+    }
+    result
+}
+
+/// The phases after `setup`, then `backend-ready`. Runs on its own thread.
+pub fn finish(app: &AppHandle) {
+    phase("home", || {
+        // Creates the data directory, or reports why there isn't one
+        home::check_at_startup(app);
+        integrity::log_report();
+    });
+    phase("migrations", || {
+        // Move flat `<tenant>_<provider>` session dirs into the nested layout
+        match sessions::migrate_sessions_layout() {
+            Ok(report) if !report.moved.is_empty() => {
+                log::info!(
+                    "[sessions] migrated {} legacy session dirs",
+                    report.moved.len()
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("[sessions] layout migration failed: {}", e),
+        }
+    });
+    let settings = phase("settings", settings::load_settings);
+    let stores = phase("stores", || {
+        stores::restore_grants();
+        stores::load_registry()
+    });
+    phase("jobs", || {
+        maintenance::restore(app);
+        system_state::spawn(app.clone());
+        let scheduler = app.state::<scheduler::Scheduler>();
+        session_expiry::register(&scheduler);
+        stores::register_jobs(&scheduler);
+        artifact_upload::register_jobs(&scheduler);
+        trash::register_jobs(&scheduler);
+        provider_usage::register_jobs(&scheduler);
+        recipes::register_jobs(&scheduler);
+        scheduler.start(app.clone());
+    });
+    phase("listeners", || {
+        whatsapp_media::listen(app);
+        whatsapp_send::spawn(app.clone());
+        automation::spawn(app.clone());
+        task_inbox::resume(app.clone());
+    });
+
+    let timeline = {
+        let mut guard = TIMELINE.lock().unwrap();
+        if let Some(timeline) = guard.as_mut() {
+            timeline.ready_ms = Some(timeline.started.elapsed().as_millis() as u64);
+        }
+        READY_CHANGED.notify_all();
+        snapshot(guard.as_ref())
+    };
+    log::info!("[startup] ready after {:?} ms", timeline.ready_ms);
+    event_journal::emit(
+        app,
+        "backend-ready",
+        BackendReady {
+            settings,
+            stores,
+            repairs: integrity::get_startup_report()
+                .map(|r| r.findings)
+                .unwrap_or_default(),
+            timeline,
+        },
+    );
+}
+
+pub fn timeline() -> StartupTimeline {
+    snapshot(TIMELINE.lock().unwrap().as_ref())
+}
+
+fn snapshot(timeline: Option<&Timeline>) -> StartupTimeline {
+    match timeline {
+        Some(t) => StartupTimeline {
+            started_at: t.started_at.clone(),
+            phases: t.phases.clone(),
+            ready_ms: t.ready_ms,
+        },
+        None => StartupTimeline {
+            started_at: String::new(),
+            phases: Vec::new(),
+            ready_ms: None,
+        },
+    }
+}
+
+// ─── Readiness ──────────────────────────────────────────────────────────────
+
+/// Whether the phases have finished. Without a timeline (tests, tools)
+/// there's nothing to wait for.
+pub fn is_ready() -> bool {
+    TIMELINE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_none_or(|t| t.ready_ms.is_some())
+}
+
+/// Wait up to `timeout` for readiness; whether it came.
+pub fn wait_ready(timeout: Duration) -> bool {
+    let guard = TIMELINE.lock().unwrap();
+    let (guard, _) = READY_CHANGED
+        .wait_timeout_while(guard, timeout, |t| {
+            t.as_ref().is_some_and(|t| t.ready_ms.is_none())
+        })
+        .unwrap();
+    guard.as_ref().is_none_or(|t| t.ready_ms.is_some())
+}
+
+fn not_ready(command: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::NotReady,
+        format!(
+            "{} was called before the backend finished starting",
+            command
+        ),
+    )
+    .with("command", command)
+    .with("phase", timeline().phases.last().map(|p| p.name))
+}
+
+/// Wrap the invoke handler so commands sent before readiness wait for it,
+/// off the calling thread, or fail with `NotReady`.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    let handler = Arc::new(handler);
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        if is_ready() || BEFORE_READY.contains(&command.as_str()) {
+            return handler(invoke);
+        }
+        let handler = handler.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if wait_ready(READY_WAIT) {
+                handler(invoke);
+            } else {
+                log::warn!("[startup] {} refused: not ready", command);
+                invoke.resolver.reject(not_ready(&command));
+            }
+        });
+        true
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_are_timed_in_order_and_readiness_waits() {
+        begin();
+        phase("first", || std::thread::sleep(Duration::from_millis(20)));
+        assert_eq!(phase("second", || 42), 42);
+        assert!(!is_ready());
+        assert!(!wait_ready(Duration::from_millis(10)));
+        assert_eq!(not_ready("list_stores").code, ErrorCode::NotReady);
+
+        let waiter = std::thread::spawn(|| wait_ready(Duration::from_secs(5)));
+        {
+            let mut guard = TIMELINE.lock().unwrap();
+            guard.as_mut().unwrap().ready_ms = Some(30);
+            READY_CHANGED.notify_all();
+        }
+        assert!(waiter.join().unwrap());
+        assert!(is_ready());
+
+        let timeline = timeline();
+        let names: Vec<_> = timeline.phases.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["first", "second"]);
+        assert!(timeline.phases[0].duration_ms >= 20);
+        assert!(timeline.phases[1].started_ms >= timeline.phases[0].started_ms + 20);
+        *TIMELINE.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,399 @@
+// Store health report
+//
+// `check_store_health` looks a connected store, or any folder, over for the
+// problems support asks about, and changes nothing. Each `Check` can be
+// turned off:
+// - `broken_links`: wikilinks and relative Markdown links that don't resolve
+//   to a file, by the rules `move_note` uses. Links to URLs aren't checked.
+// - `invalid_frontmatter`: a frontmatter block that's never closed, has a
+//   line that isn't `key: value`, or sets a key twice (see
+//   `frontmatter::block_problem`).
+// - `empty_files`: zero-byte files, leaving out cloud placeholders.
+// - `non_portable_names`: a path segment Windows won't accept (see
+//   `filenames`), or two paths that differ only in case.
+//   Leading dots are fine.
+// - `duplicate_names`: the same file name, in any case, in more than one
+//   folder, which makes `[[Name]]` ambiguous.
+//
+// A store's files come from its last scan, so the report covers what its
+// sync rules cover; a folder that isn't a store is walked. Notes are read
+// only when a check needs their text. The check runs as a `store_health`
+// operation, so a big store reports `operation-progress` and can be
+// cancelled, and the report is kept with the operation for `export_results`.
+//
+// Each check lists up to `max_examples` of its issues, with the total in
+// `counts`. New checks are new `Check` variants.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::filenames::{self, NameChange, SanitizeOptions, Target};
+use crate::note_links::LinkIndex;
+use crate::operations::{OperationHandle, OperationKind, Operations};
+use crate::stores::{self, Snapshot};
+use crate::{frontmatter, io_throttle, text_style};
+
+const DEFAULT_MAX_EXAMPLES: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    BrokenLinks,
+    InvalidFrontmatter,
+    EmptyFiles,
+    NonPortableNames,
+    DuplicateNames,
+}
+
+impl Check {
+    pub const ALL: [Check; 5] = [
+        Check::BrokenLinks,
+        Check::InvalidFrontmatter,
+        Check::EmptyFiles,
+        Check::NonPortableNames,
+        Check::DuplicateNames,
+    ];
+
+    /// Whether the check reads the text of notes.
+    fn reads_notes(self) -> bool {
+        matches!(self, Check::BrokenLinks | Check::InvalidFrontmatter)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HealthOptions {
+    /// Checks to skip.
+    pub disabled: Vec<Check>,
+    /// Issues listed per check; the counts are always complete.
+    pub max_examples: usize,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            disabled: Vec::new(),
+            max_examples: DEFAULT_MAX_EXAMPLES,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Issue {
+    pub check: Check,
+    /// Store-relative, `/`-separated.
+    pub path: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HealthReport {
+    pub store_id: Option<String>,
+    pub root: String,
+    pub files_checked: usize,
+    pub notes_read: usize,
+    /// Issues found by each check that ran, including those not listed.
+    pub counts: BTreeMap<Check, usize>,
+    /// Up to `max_examples` per check, in path order within each check.
+    pub issues: Vec<Issue>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Check the store with id `store`, or the folder at that path.
+#[tauri::command]
+pub async fn check_store_health(
+    app: AppHandle,
+    store: String,
+    options: Option<HealthOptions>,
+) -> CommandResult<HealthReport> {
+    let options = options.unwrap_or_default();
+    let op = Operations::start_async(&app, OperationKind::StoreHealth, &store, 1).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = io_throttle::scoped(op.background_io(), || {
+            files_of(&store)
+                .and_then(|(store_id, root, files)| check(&op, store_id, &root, &files, &options))
+        });
+        op.finish_storing(report)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// The store's id, its root, and its files.
+fn files_of(store: &str) -> CommandResult<(Option<String>, PathBuf, Snapshot)> {
+    if let Ok(connected) = stores::find_store(store) {
+        let mut files = stores::load_snapshot(&connected.id);
+        if files.is_empty() {
+            files = stores::scan_store(&connected)?;
+        }
+        return Ok((Some(connected.id), PathBuf::from(connected.path), files));
+    }
+    let root = Path::new(store);
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("No store or folder {}", store),
+        )
+        .with("store", store));
+    }
+    let store_id = stores::store_for_path(root)
+        .filter(|s| Path::new(&s.path) == root)
+        .map(|s| s.id);
+    Ok((store_id, root.to_path_buf(), stores::scan_for_index(root)?))
+}
+
+// ─── Checking ───────────────────────────────────────────────────────────────
+
+struct Findings {
+    max_examples: usize,
+    counts: BTreeMap<Check, usize>,
+    examples: BTreeMap<Check, Vec<Issue>>,
+}
+
+impl Findings {
+    fn add(&mut self, check: Check, path: &str, detail: impl Into<String>) {
+        *self.counts.entry(check).or_default() += 1;
+        let examples = self.examples.entry(check).or_default();
+        if examples.len() < self.max_examples {
+            examples.push(Issue {
+                check,
+                path: path.to_string(),
+                detail: detail.into(),
+            });
+        }
+    }
+}
+
+fn check(
+    op: &OperationHandle,
+    store_id: Option<String>,
+    root: &Path,
+    files: &Snapshot,
+    options: &HealthOptions,
+) -> CommandResult<HealthReport> {
+    let enabled: Vec<Check> = Check::ALL
+        .into_iter()
+        .filter(|c| !options.disabled.contains(c))
+        .collect();
+    let on = |check: Check| enabled.contains(&check);
+    let mut found = Findings {
+        max_examples: options.max_examples,
+        counts: enabled.iter().map(|&c| (c, 0)).collect(),
+        examples: BTreeMap::new(),
+    };
+    let index = on(Check::BrokenLinks).then(|| LinkIndex::new(files.keys().map(String::as_str)));
+    let reads_notes = enabled.iter().any(|c| c.reads_notes());
+    let total = files.len() as u64;
+    let mut notes_read = 0;
+
+    for (done, (key, entry)) in files.iter().enumerate() {
+        op.check_cancelled()?;
+        op.progress("checking", done as u64, Some(total), Some(key));
+        if on(Check::EmptyFiles) && entry.size == 0 && !entry.placeholder {
+            found.add(Check::EmptyFiles, key, "Empty file");
+        }
+        if on(Check::NonPortableNames) {
+            if let Some(problem) = non_portable(key) {
+                found.add(Check::NonPortableNames, key, problem);
+            }
+        }
+        if !reads_notes || entry.placeholder || !key.to_lowercase().ends_with(".md") {
+            continue;
+        }
+        let bytes = match fs::read(root.join(key)) {
+            Ok(bytes) => bytes,
+            // Gone since the last scan
+            Err(e) => {
+                log::debug!("[store_health] can't read {}: {}", key, e);
+                continue;
+            }
+        };
+        io_throttle::charge(bytes.len() as u64);
+        io_throttle::file_done();
+        notes_read += 1;
+        let text = text_style::decode(&bytes, &text_style::detect(&bytes));
+        if on(Check::InvalidFrontmatter) {
+            if let Some(problem) = frontmatter::block_problem(&text) {
+                found.add(Check::InvalidFrontmatter, key, problem);
+            }
+        }
+        if let Some(index) = &index {
+            for link in index.links(key, &text) {
+                if link.resolved.is_none() {
+                    found.add(
+                        Check::BrokenLinks,
+                        key,
+                        format!("Nothing at `{}`", link.target),
+                    );
+                }
+            }
+        }
+    }
+    if on(Check::NonPortableNames) {
+        case_collisions(files, &mut found);
+    }
+    if on(Check::DuplicateNames) {
+        duplicate_names(files, &mut found);
+    }
+    op.progress("checking", total, Some(total), None);
+
+    Ok(HealthReport {
+        store_id,
+        root: root.to_string_lossy().to_string(),
+        files_checked: files.len(),
+        notes_read,
+        counts: found.counts,
+        issues: found.examples.into_values().flatten().collect(),
+    })
+}
+
+/// Why a segment of `key` can't be written on Windows, if one can't.
+fn non_portable(key: &str) -> Option<String> {
+    let options = SanitizeOptions {
+        target: Target::Windows,
+        ..Default::default()
+    };
+    for segment in key.split('/') {
+        // Hidden files sync fine
+        let name = segment.trim_start_matches('.');
+        if name.is_empty() {
+            continue;
+        }
+        let sanitized = filenames::sanitize_filename(name, &options);
+        let problem = sanitized.changes.iter().find_map(|change| match change {
+            NameChange::Replaced { characters } => Some(format!(
+                "`{}` has characters Windows reserves: {}",
+                segment,
+                characters.iter().collect::<String>()
+            )),
+            NameChange::Trimmed => Some(format!(
+                "`{}` ends with a dot or space, which Windows drops",
+                segment
+            )),
+            NameChange::ReservedName { name } => {
+                Some(format!("`{}` is a Windows device name", name))
+            }
+            NameChange::Truncated { bytes } => Some(format!(
+                "`{}` is {} bytes, over the 255 allowed",
+                segment, bytes
+            )),
+            _ => None,
+        });
+        if problem.is_some() {
+            return problem;
+        }
+    }
+    None
+}
+
+/// Files in one folder whose paths differ only in case.
+fn case_collisions(files: &Snapshot, found: &mut Findings) {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for key in files.keys() {
+        match seen.get(&key.to_lowercase()) {
+            Some(first) => found.add(
+                Check::NonPortableNames,
+                key,
+                format!("Differs only in case from `{}`", first),
+            ),
+            None => {
+                seen.insert(key.to_lowercase(), key);
+            }
+        }
+    }
+}
+
+/// Each file whose name, in any case, is also used in another folder.
+fn duplicate_names(files: &Snapshot, found: &mut Findings) {
+    let mut by_name: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for key in files.keys() {
+        let name = key.rsplit('/').next().unwrap_or(key).to_lowercase();
+        by_name.entry(name).or_default().push(key);
+    }
+    let mut duplicates: Vec<(&str, usize)> = by_name
+        .values()
+        .filter(|keys| keys.len() > 1)
+        .flat_map(|keys| keys.iter().map(|&k| (k, keys.len())))
+        .collect();
+    duplicates.sort();
+    for (key, count) in duplicates {
+        found.add(
+            Check::DuplicateNames,
+            key,
+            format!("{} files share this name", count),
+        );
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(keys: &[(&str, u64)]) -> Snapshot {
+        keys.iter()
+            .map(|&(key, size)| {
+                let entry = stores::SnapshotEntry {
+                    size,
+                    modified: 0,
+                    placeholder: false,
+                    id: None,
+                    native: None,
+                    hash: None,
+                    fingerprint: None,
+                    bookmarked: false,
+                };
+                (key.to_string(), entry)
+            })
+            .collect()
+    }
+
+    fn findings(max_examples: usize) -> Findings {
+        Findings {
+            max_examples,
+            counts: BTreeMap::new(),
+            examples: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn names_windows_refuses() {
+        assert_eq!(non_portable(".obsidian/app.json"), None);
+        assert_eq!(non_portable("Notes/Plan.md"), None);
+        assert!(non_portable("Q3: Plan.md").unwrap().contains(':'));
+        assert!(non_portable("Drafts./a.md").unwrap().contains("Drafts."));
+        assert!(non_portable("aux.md").unwrap().contains("device name"));
+    }
+
+    #[test]
+    fn collisions_and_duplicates() {
+        let files = files(&[
+            ("Notes/Plan.md", 1),
+            ("Notes/plan.md", 1),
+            ("Archive/PLAN.md", 1),
+            ("Solo.md", 1),
+        ]);
+        let mut found = findings(2);
+        case_collisions(&files, &mut found);
+        assert_eq!(found.counts[&Check::NonPortableNames], 1);
+        assert_eq!(
+            found.examples[&Check::NonPortableNames][0].path,
+            "Notes/plan.md"
+        );
+
+        duplicate_names(&files, &mut found);
+        // All three counted, two listed
+        assert_eq!(found.counts[&Check::DuplicateNames], 3);
+        let listed: Vec<&str> = found.examples[&Check::DuplicateNames]
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect();
+        assert_eq!(listed, vec!["Archive/PLAN.md", "Notes/Plan.md"]);
+    }
+}
@@ -0,0 +1,290 @@
+// Store kinds
+//
+// Stores behave differently depending on what they are:
+// - Obsidian vaults honor the vault's own excluded-files setting and
+//   `.trash`, count notes, resolve daily-note paths from
+//   `.obsidian/daily-notes.json`, and put attachments where the vault's
+//   attachment setting says.
+// - Cloud folders (iCloud Drive, OneDrive, Dropbox, Google Drive) track
+//   online-only placeholders and treat an emptied root as an offline volume.
+// - Plain folders get the generic path.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::stores;
+
+/// Path fragments of the folders sync clients manage.
+const CLOUD_LOCATIONS: &[&str] = &[
+    "Library/Mobile Documents",
+    "Library/CloudStorage",
+    "iCloudDrive",
+    "iCloud Drive",
+    "OneDrive",
+    "Dropbox",
+    "Google Drive",
+    "My Drive",
+];
+const DEFAULT_DAILY_FORMAT: &str = "YYYY-MM-DD";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    ObsidianVault,
+    Folder,
+    CloudFolder,
+}
+
+/// `.obsidian/daily-notes.json`; all fields optional in Obsidian too.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DailyNotesConfig {
+    folder: String,
+    format: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct ObsidianAppConfig {
+    user_ignore_filters: Vec<String>,
+    attachment_folder_path: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DailyNote {
+    pub path: String,
+    pub exists: bool,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Path of the daily note for `date` (`YYYY-MM-DD`, default today) in an
+/// Obsidian store, following the vault's daily-notes settings.
+#[tauri::command]
+pub fn resolve_daily_note(store_id: String, date: Option<String>) -> CommandResult<DailyNote> {
+    let store = stores::find_store(&store_id)?;
+    if store.kind != StoreKind::ObsidianVault {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Store {} is not an Obsidian vault", store_id),
+        )
+        .with("store_id", &store_id));
+    }
+    let date = match date {
+        Some(raw) => chrono::NaiveDate::parse_from_str(&raw, "%Y-%m-%d").map_err(|_| {
+            CommandError::new(ErrorCode::InvalidInput, format!("Invalid date: {}", raw))
+        })?,
+        None => chrono::Local::now().date_naive(),
+    };
+    let path = daily_note_path(Path::new(&store.path), date);
+    Ok(DailyNote {
+        exists: path.is_file(),
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+// ─── Detection ──────────────────────────────────────────────────────────────
+
+pub fn detect(root: &Path) -> StoreKind {
+    if root.join(".obsidian").is_dir() {
+        return StoreKind::ObsidianVault;
+    }
+    let path = root.to_string_lossy().replace('\\', "/");
+    if CLOUD_LOCATIONS.iter().any(|loc| path.contains(loc)) {
+        return StoreKind::CloudFolder;
+    }
+    StoreKind::Folder
+}
+
+impl StoreKind {
+    /// Relative path prefixes this kind excludes on top of the generic rules.
+    pub fn ignored_prefixes(self, root: &Path) -> Vec<String> {
+        match self {
+            StoreKind::ObsidianVault => {
+                let mut prefixes = vec![".trash/".to_string()];
+                let app = fs::read_to_string(root.join(".obsidian/app.json"))
+                    .ok()
+                    .and_then(|raw| serde_json::from_str::<ObsidianAppConfig>(&raw).ok())
+                    .unwrap_or_default();
+                prefixes.extend(app.user_ignore_filters);
+                prefixes
+            }
+            StoreKind::Folder | StoreKind::CloudFolder => Vec::new(),
+        }
+    }
+
+    pub fn tracks_placeholders(self) -> bool {
+        self == StoreKind::CloudFolder
+    }
+
+    pub fn counts_notes(self) -> bool {
+        self == StoreKind::ObsidianVault
+    }
+
+    /// Whether a scan that finds nothing means the volume is offline rather
+    /// than that every file was deleted. Sync clients leave an empty mount
+    /// point behind when they stop.
+    pub fn empty_root_is_unreachable(self) -> bool {
+        self == StoreKind::CloudFolder
+    }
+}
+
+// ─── Attachments ────────────────────────────────────────────────────────────
+
+/// The folder new attachments of a note in `note_folder` go in, from the
+/// vault's setting: the root for `/` or none, beside the note for `./`,
+/// a subfolder of the note's folder for `./name`, else a vault folder.
+pub fn attachment_folder(root: &Path, note_folder: &Path) -> PathBuf {
+    let app = fs::read_to_string(root.join(".obsidian/app.json"))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<ObsidianAppConfig>(&raw).ok())
+        .unwrap_or_default();
+    let setting = app.attachment_folder_path.trim();
+    match setting.strip_prefix("./") {
+        Some(relative) => note_folder.join(relative.trim_matches('/')),
+        None if setting == "." => note_folder.to_path_buf(),
+        None => root.join(setting.trim_matches('/')),
+    }
+}
+
+// ─── Daily Notes ────────────────────────────────────────────────────────────
+
+pub fn daily_note_path(root: &Path, date: chrono::NaiveDate) -> PathBuf {
+    let config = fs::read_to_string(root.join(".obsidian/daily-notes.json"))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<DailyNotesConfig>(&raw).ok())
+        .unwrap_or_default();
+    let format = if config.format.is_empty() {
+        DEFAULT_DAILY_FORMAT
+    } else {
+        &config.format
+    };
+    // Formats may contain `/` to nest notes in dated folders, and time tokens
+    // (`YYYY-MM-DD HHmm`), which read as midnight
+    let name = format!(
+        "{}.md",
+        format_moment(date.and_time(chrono::NaiveTime::MIN), format)
+    );
+    root.join(config.folder.trim_matches('/')).join(name)
+}
+
+/// `when` formatted with the moment.js tokens Obsidian uses.
+pub fn format_moment(when: chrono::NaiveDateTime, format: &str) -> String {
+    when.format(&moment_to_chrono(when, format)).to_string()
+}
+
+enum Token {
+    Chrono(&'static str),
+    /// `Do`: `1st`, `2nd`, …
+    OrdinalDay,
+    /// `dd`: `Mo`, `Tu`, …
+    MinWeekday,
+}
+
+/// Translate a moment.js format into a chrono one. Tokens chrono has no
+/// specifier for are written out for `when` as literals.
+fn moment_to_chrono(when: chrono::NaiveDateTime, format: &str) -> String {
+    use chrono::Datelike;
+    use Token::*;
+
+    // Longer tokens before their prefixes
+    const TOKENS: &[(&str, Token)] = &[
+        ("YYYY", Chrono("%Y")),
+        ("YY", Chrono("%y")),
+        // Week numbering is always ISO 8601 (weeks start on Monday)
+        ("gggg", Chrono("%G")),
+        ("GGGG", Chrono("%G")),
+        ("ww", Chrono("%V")),
+        ("WW", Chrono("%V")),
+        ("w", Chrono("%-V")),
+        ("W", Chrono("%-V")),
+        ("MMMM", Chrono("%B")),
+        ("MMM", Chrono("%b")),
+        ("MM", Chrono("%m")),
+        ("M", Chrono("%-m")),
+        ("DDDD", Chrono("%j")),
+        ("DDD", Chrono("%-j")),
+        ("DD", Chrono("%d")),
+        ("Do", OrdinalDay),
+        ("D", Chrono("%-d")),
+        ("dddd", Chrono("%A")),
+        ("ddd", Chrono("%a")),
+        ("dd", MinWeekday),
+        ("d", Chrono("%w")),
+        ("HH", Chrono("%H")),
+        ("H", Chrono("%-H")),
+        ("hh", Chrono("%I")),
+        ("h", Chrono("%-I")),
+        ("mm", Chrono("%M")),
+        ("ss", Chrono("%S")),
+        ("A", Chrono("%p")),
+        ("a", Chrono("%P")),
+    ];
+    let mut out = String::new();
+    let mut rest = format;
+    'outer: while !rest.is_empty() {
+        // [literal] escapes
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some(end) = after.find(']') {
+                out.push_str(&after[..end].replace('%', "%%"));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        for (token, replacement) in TOKENS {
+            if let Some(after) = rest.strip_prefix(token) {
+                match replacement {
+                    Chrono(spec) => out.push_str(spec),
+                    OrdinalDay => out.push_str(&ordinal(when.day())),
+                    MinWeekday => out.push_str(&when.weekday().to_string()[..2]),
+                }
+                rest = after;
+                continue 'outer;
+            }
+        }
+        let ch = rest.chars().next().unwrap();
+        if ch == '%' {
+            out.push_str("%%");
+        } else {
+            out.push(ch);
+        }
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
+
+fn ordinal(n: u32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn daily_note_formats_with_time_tokens_read_as_midnight() {
+        let home = TestHome::new();
+        home.write(
+            "vault/.obsidian/daily-notes.json",
+            r#"{ "folder": "Daily/", "format": "YYYY/MM/YYYY-MM-DD HHmm" }"#,
+        );
+        let root = home.join("vault");
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(
+            daily_note_path(&root, date),
+            root.join("Daily/2024/03/2024-03-05 0000.md")
+        );
+    }
+}
@@ -0,0 +1,436 @@
+// Store relinking
+//
+// A store whose folder was moved or renamed shows as `unreachable` until it's
+// pointed at the new place. `relink_store` does that and keeps the store's id,
+// so its snapshot, file ids, workspaces, and similar-note signatures (all
+// keyed by store id and relative path) carry over. Before relinking it
+// samples up to `SAMPLE_SIZE` files from the last snapshot and looks for each
+// at the same relative path under the new folder. A file counts as there when
+// its size and mtime match, or `file_ids::matches` pairs it. The share found
+// is the report's `confidence`. Under `LOW_CONFIDENCE` the relink still goes
+// ahead, but the report carries a warning, since the folder may not be the
+// same content at all. A relink:
+// - saves the new path and a new folder grant, and allows the folder in the
+//   file system scope;
+// - moves recipe watch folders and path targets under the old root (see
+//   `recipes::relink_paths`);
+// - moves artifact records whose file was under the old root, so the
+//   store's artifact history follows it;
+// - rescans, which should find nothing changed.
+//
+// A rescan of a store whose root is gone, or empty when its snapshot isn't,
+// looks for the folder nearby. It checks directories up to two levels under
+// the old root's parent, the way `~/Documents/Client Vault` becomes
+// `~/Documents/Clients/Acme`. Only the cheap size-and-mtime comparison is
+// used there. A candidate whose confidence reaches `SUGGEST_CONFIDENCE` is
+// put on the rescan report as `relink_suggestion`, and announced as
+// `store-relink-suggested`. An emptied root with a suggestion counts as
+// unreachable, so its snapshot isn't thrown away.
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::bookmarks;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::stores::{self, ConnectedStore, RescanReport, Snapshot, SnapshotEntry, StoreStatus};
+use crate::{artifact_upload, audit, file_ids, recipes, settings};
+
+/// Snapshot files compared against a candidate folder.
+const SAMPLE_SIZE: usize = 32;
+/// Below this share of sampled files found, a relink warns.
+pub const LOW_CONFIDENCE: f64 = 0.5;
+/// A rescan suggests a folder at least this sure.
+pub const SUGGEST_CONFIDENCE: f64 = 0.8;
+/// Folders near a missing root compared before giving up.
+const MAX_CANDIDATES: usize = 200;
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RelinkCheck {
+    pub sampled: usize,
+    pub matched: usize,
+    /// `matched / sampled`; 0 with nothing to sample.
+    pub confidence: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RelinkReport {
+    pub store: ConnectedStore,
+    pub old_path: String,
+    pub check: RelinkCheck,
+    /// Set when `check.confidence` is under `LOW_CONFIDENCE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub recipes_updated: usize,
+    pub artifacts_updated: usize,
+    pub rescan: RescanReport,
+}
+
+/// Payload of `store-relink-suggested`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RelinkSuggestion {
+    pub store_id: String,
+    pub old_path: String,
+    pub suggested_path: String,
+    pub confidence: f64,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Point a store at the folder it was moved to; see the module comment.
+#[tauri::command]
+pub fn relink_store(
+    app: tauri::AppHandle,
+    store_id: String,
+    new_path: String,
+) -> CommandResult<RelinkReport> {
+    use tauri_plugin_fs::FsExt;
+
+    let report = relink(&store_id, &new_path)?;
+    if let Some(scope) = app.try_fs_scope() {
+        scope
+            .allow_directory(&new_path, true)
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    }
+    audit::record(
+        "relink_store",
+        None,
+        serde_json::json!({
+            "store_id": store_id,
+            "from": report.old_path,
+            "to": new_path,
+            "confidence": report.check.confidence,
+        }),
+    );
+    Ok(report)
+}
+
+pub fn relink(store_id: &str, new_path: &str) -> CommandResult<RelinkReport> {
+    let store = stores::find_store(store_id)?;
+    let new_root = validate(&store, new_path)?;
+    let check = compare(&stores::load_snapshot(&store.id), &new_root, true);
+    let warning = (check.confidence < LOW_CONFIDENCE).then(|| {
+        format!(
+            "Only {} of {} sampled files from {} were found in {}",
+            check.matched, check.sampled, store.name, new_path
+        )
+    });
+
+    let grant = bookmarks::create(new_path)
+        .map_err(|e| CommandError::new(ErrorCode::PermissionDenied, e).with("path", new_path))?;
+    stores::update_store(&store.id, |s| {
+        s.path = new_path.to_string();
+        s.grant = grant;
+        s.status = StoreStatus::Ok;
+    })?;
+
+    let old_root = Path::new(&store.path);
+    let recipes_updated = recipes::relink_paths(old_root, &new_root);
+    let artifacts_updated = artifact_upload::update_index(|index| {
+        let mut moved = 0;
+        for record in index.iter_mut() {
+            if let Ok(rest) = Path::new(&record.path).strip_prefix(old_root) {
+                record.path = new_root.join(rest).to_string_lossy().to_string();
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    })?;
+    let rescan = stores::rescan(&store.id)?;
+
+    log::info!(
+        "[store_relink] {} moved from {} to {} ({}/{} sampled files found)",
+        store.id,
+        store.path,
+        new_path,
+        check.matched,
+        check.sampled
+    );
+    Ok(RelinkReport {
+        store: stores::find_store(&store.id)?,
+        old_path: store.path,
+        check,
+        warning,
+        recipes_updated,
+        artifacts_updated,
+        rescan,
+    })
+}
+
+fn validate(store: &ConnectedStore, new_path: &str) -> CommandResult<PathBuf> {
+    let new_root = PathBuf::from(new_path);
+    if !new_root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Not a directory: {}", new_path),
+        )
+        .with("path", new_path));
+    }
+    if new_root == Path::new(&store.path) {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is already at {}", store.name, new_path),
+        )
+        .with("path", new_path));
+    }
+    let resolved = new_root.canonicalize()?;
+    for other in stores::load_registry()
+        .into_iter()
+        .filter(|s| s.id != store.id)
+    {
+        let Ok(other_root) = Path::new(&other.path).canonicalize() else {
+            continue;
+        };
+        if resolved.starts_with(&other_root) || other_root.starts_with(&resolved) {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyInStore,
+                format!("{} overlaps the store {}", new_path, other.name),
+            )
+            .with("path", new_path)
+            .with("store_id", &other.id)
+            .with("store_name", &other.name));
+        }
+    }
+    Ok(new_root)
+}
+
+// ─── Comparison ─────────────────────────────────────────────────────────────
+
+/// How much of `snapshot` is found under `root`. `thorough` also pairs files
+/// by native identity or content hash, which reads them.
+pub fn compare(snapshot: &Snapshot, root: &Path, thorough: bool) -> RelinkCheck {
+    let step = (snapshot.len() / SAMPLE_SIZE).max(1);
+    let sample: Vec<(&String, &SnapshotEntry)> =
+        snapshot.iter().step_by(step).take(SAMPLE_SIZE).collect();
+    let matched = sample
+        .iter()
+        .filter(|(key, old)| found(&root.join(key), old, thorough).unwrap_or(false))
+        .count();
+    RelinkCheck {
+        sampled: sample.len(),
+        matched,
+        confidence: match sample.len() {
+            0 => 0.0,
+            sampled => matched as f64 / sampled as f64,
+        },
+    }
+}
+
+fn found(path: &Path, old: &SnapshotEntry, thorough: bool) -> io::Result<bool> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    if metadata.len() == old.size && modified == old.modified {
+        return Ok(true);
+    }
+    if !thorough || old.placeholder {
+        return Ok(false);
+    }
+    let mut entry = SnapshotEntry {
+        size: metadata.len(),
+        modified,
+        placeholder: false,
+        id: None,
+        native: None,
+        hash: None,
+        fingerprint: None,
+        bookmarked: false,
+    };
+    let full_hash_max = settings::load_settings().limits.full_hash_max_bytes;
+    file_ids::identify(path, &mut entry, full_hash_max);
+    Ok(file_ids::matches(old, &entry).is_some())
+}
+
+// ─── Suggestions ────────────────────────────────────────────────────────────
+
+/// A folder near `store`'s missing or emptied root that holds its snapshot.
+pub fn suggest(store: &ConnectedStore, snapshot: &Snapshot) -> Option<RelinkSuggestion> {
+    if snapshot.is_empty() || bookmarks::is_content_uri(&store.path) {
+        return None;
+    }
+    let old_root = Path::new(&store.path);
+    let parent = old_root.parent()?;
+    let others: Vec<PathBuf> = stores::load_registry()
+        .into_iter()
+        .filter(|s| s.id != store.id)
+        .map(|s| PathBuf::from(s.path))
+        .collect();
+
+    let candidates = walkdir::WalkDir::new(parent)
+        .min_depth(1)
+        .max_depth(2)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0
+                || (e.file_type().is_dir()
+                    && e.path() != old_root
+                    && !e.file_name().to_string_lossy().starts_with('.')
+                    && !others.iter().any(|other| e.path().starts_with(other)))
+        })
+        .flatten()
+        .take(MAX_CANDIDATES);
+
+    let mut best: Option<(f64, PathBuf)> = None;
+    for candidate in candidates {
+        let check = compare(snapshot, candidate.path(), false);
+        if check.confidence >= SUGGEST_CONFIDENCE
+            && best.as_ref().is_none_or(|(c, _)| check.confidence > *c)
+        {
+            best = Some((check.confidence, candidate.into_path()));
+        }
+    }
+    let (confidence, path) = best?;
+    log::info!(
+        "[store_relink] {} may have moved to {} ({:.0}% of sampled files)",
+        store.id,
+        path.display(),
+        confidence * 100.0
+    );
+    Some(RelinkSuggestion {
+        store_id: store.id.clone(),
+        old_path: store.path.clone(),
+        suggested_path: path.to_string_lossy().to_string(),
+        confidence,
+    })
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact_upload::{ArtifactRecord, Transfer};
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn move_reports(home: &TestHome, to: &str) -> ConnectedStore {
+        let store = stores::list_stores().remove(0);
+        fs::create_dir_all(home.join(to).parent().unwrap()).unwrap();
+        fs::rename(home.join("Reports"), home.join(to)).unwrap();
+        store
+    }
+
+    #[test]
+    fn a_rescan_suggests_the_folder_the_store_moved_to() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        home.write("Unrelated/notes.md", "something else");
+        let store = move_reports(&home, "Clients/Acme");
+        // The old folder exists again, but empty
+        fs::create_dir(home.join("Reports")).unwrap();
+
+        let report = stores::rescan(&store.id).unwrap();
+        assert_eq!(report.status, StoreStatus::Unreachable);
+        assert_eq!(report.removed, 0);
+        let suggestion = report.relink_suggestion.unwrap();
+        assert_eq!(
+            Path::new(&suggestion.suggested_path),
+            home.join("Clients/Acme")
+        );
+        assert_eq!(suggestion.confidence, 1.0);
+        assert_eq!(stores::load_snapshot(&store.id).len(), 3);
+    }
+
+    #[test]
+    fn relinking_keeps_ids_and_moves_artifact_history() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let before = stores::snapshot_entry(&home.join("Reports/q1.pdf"))
+            .unwrap()
+            .1;
+        artifact_upload::index_artifact(ArtifactRecord {
+            artifact_id: "art-1".into(),
+            version: None,
+            tenant_id: "acme".into(),
+            file_id: before.id.clone().unwrap(),
+            path: home.join("Reports/q1.pdf").to_string_lossy().to_string(),
+            sha256: String::new(),
+            size_bytes: before.size,
+            transfer: Transfer::Uploaded,
+            recorded_at: "2026-01-01T00:00:00Z".into(),
+            metadata: Default::default(),
+        })
+        .unwrap();
+        let store = move_reports(&home, "Clients/Acme");
+        let new_path = home.join("Clients/Acme").to_string_lossy().to_string();
+
+        let report = relink(&store.id, &new_path).unwrap();
+        assert_eq!(report.store.id, store.id);
+        assert_eq!(report.store.path, new_path);
+        assert_eq!(report.check.confidence, 1.0);
+        assert!(report.warning.is_none());
+        assert_eq!(report.artifacts_updated, 1);
+        let rescan = &report.rescan;
+        assert_eq!((rescan.added, rescan.removed, rescan.modified), (0, 0, 0));
+
+        let after = stores::snapshot_entry(&home.join("Clients/Acme/q1.pdf"))
+            .unwrap()
+            .1;
+        assert_eq!(after.id, before.id);
+        let index = artifact_upload::load_index();
+        assert_eq!(Path::new(&index[0].path), home.join("Clients/Acme/q1.pdf"));
+    }
+
+    #[test]
+    fn a_folder_with_other_content_relinks_with_a_warning() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let store = stores::list_stores().remove(0);
+        home.write("Other/q1.pdf", "not the same file at all");
+        let other = home.join("Other").to_string_lossy().to_string();
+
+        let report = relink(&store.id, &other).unwrap();
+        assert_eq!(report.check.sampled, 3);
+        assert_eq!(report.check.matched, 0);
+        assert!(report.warning.unwrap().contains("0 of 3"));
+    }
+
+    #[test]
+    fn relinking_into_another_store_is_refused() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let store = stores::list_stores().remove(0);
+        home.write("Vault/a.md", "# A\n");
+        let vault = home.join("Vault").to_string_lossy().to_string();
+        stores::register_store("Vault".into(), vault, None).unwrap();
+
+        let inside = home.join("Vault").to_string_lossy().to_string();
+        let err = relink(&store.id, &inside).err().unwrap();
+        assert_eq!(err.code, ErrorCode::AlreadyInStore);
+        let missing = home.join("Nowhere").to_string_lossy().to_string();
+        assert_eq!(
+            relink(&store.id, &missing).err().unwrap().code,
+            ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn recipe_watch_folders_follow_the_store() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        fs::create_dir(home.join("Reports/inbox")).unwrap();
+        let inbox = home.join("Reports/inbox").to_string_lossy().to_string();
+        recipes::upsert_recipe(
+            "acme".into(),
+            serde_json::json!({
+                "id": "file-inbox",
+                "name": "File the inbox",
+                "trigger": { "type": "watch", "path": inbox },
+                "steps": [{ "action": "hash" }],
+            }),
+        )
+        .unwrap();
+        let store = move_reports(&home, "Moved");
+
+        let new_path = home.join("Moved").to_string_lossy().to_string();
+        assert_eq!(relink(&store.id, &new_path).unwrap().recipes_updated, 1);
+        let recipe = recipes::list_recipes("acme".into()).unwrap().remove(0);
+        assert_eq!(
+            recipe.trigger,
+            recipes::Trigger::Watch {
+                path: home.join("Moved/inbox").to_string_lossy().to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,1283 @@
+// Connected stores
+//
+// A store is a folder the user has connected to AGENTVBX: an Obsidian vault,
+// a plain folder, or a synced cloud folder (see `store_kinds` for how each
+// behaves). The registry lives at
+// `stores.json` in the config directory, and each store's last scan at
+// `stores/<id>/snapshot.json` in the data directory (relative path → size, mtime, and
+// the file's identity from `file_ids`).
+//
+// `rescan_store` re-walks a store and diffs it against the snapshot, so
+// changes made while the app was closed are picked up. Files that moved are
+// reported as renames and keep their ids. `diff_store_snapshot` reports the
+// same diff without saving it. The `store_rescan`
+// job rescans every store, one at a time so they don't all walk the disk at
+// once. `rescan_all_stores` does the same on demand, for every store or a
+// workspace's. A store whose root is missing (an
+// unmounted volume, a disconnected share) is marked `unreachable` and its
+// snapshot is kept — it is not reported as fully deleted. A folder that was
+// moved or renamed is found again by `store_relink`, which can suggest where
+// it went.
+//
+// Where the OS only grants a picked folder until restart, the store also
+// keeps a `bookmarks::Grant`. `restore_grants` re-opens the grants at startup.
+// A store whose grant is gone is `needs_reauthorization`. It isn't scanned
+// and its files aren't served until `reauthorize_store` picks the folder again.
+// `pick_and_register_store` connects folders straight from the native picker:
+// each pick gets its grant, file system scope, and first scan in one call.
+//
+// A store's `sync_rules::SyncRules` narrow what its snapshot holds, and with it
+// `file_count` and `note_count`. `update_store_rules` saves new rules and
+// rescans right away. The rescan's diff is what the change did: files it
+// brought in show as added, files it left out as removed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::bookmarks::{self, Grant};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_ids::{self, MatchConfidence};
+use crate::home;
+use crate::operations::{OperationHandle, OperationKind, Operations, ResultRef};
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+use crate::store_kinds::{self, StoreKind};
+use crate::store_relink::{self, RelinkSuggestion};
+use crate::sync_rules::{CompiledRules, SyncRules};
+use crate::{
+    fingerprint, git, i18n, integrity, io_throttle, obsidian_bookmarks, settings, similar_notes,
+    workspaces,
+};
+
+const REGISTRY_FILE: &str = "stores.json";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const IGNORE_FILE: &str = ".agentvbxignore";
+/// Operation weight of a rescan; it walks the whole store.
+const RESCAN_WEIGHT: u32 = 2;
+/// Always skipped, in addition to hidden entries and `.agentvbxignore`.
+const DEFAULT_IGNORES: &[&str] = &["node_modules", "$RECYCLE.BIN", "System Volume Information"];
+
+/// Serializes read-modify-write of the registry.
+static REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+/// Stores with a rescan in flight.
+static RESCANNING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreStatus {
+    #[default]
+    Ok,
+    /// The root path is missing, e.g. an unmounted volume.
+    Unreachable,
+    /// The saved folder grant no longer resolves; see `reauthorize_store`.
+    NeedsReauthorization,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConnectedStore {
+    pub id: String,
+    pub name: String,
+    pub kind: StoreKind,
+    pub path: String,
+    /// Files the store's rules matched at the last scan.
+    pub file_count: usize,
+    #[serde(default)]
+    pub status: StoreStatus,
+    #[serde(default)]
+    pub last_scanned_at: Option<String>,
+    /// Markdown notes, for Obsidian vaults.
+    #[serde(default)]
+    pub note_count: Option<usize>,
+    /// Online-only files, for cloud folders.
+    #[serde(default)]
+    pub placeholder_count: Option<usize>,
+    /// Inside a git work tree. Scans skip `.git` like any hidden folder.
+    #[serde(default)]
+    pub is_git_repo: bool,
+    /// Saved access to the root, where the OS requires one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<Grant>,
+    #[serde(default, skip_serializing_if = "SyncRules::is_empty")]
+    pub rules: SyncRules,
+}
+
+/// One file from the last scan.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    pub size: u64,
+    /// Unix seconds.
+    pub modified: i64,
+    /// Cloud placeholder whose content isn't on disk.
+    #[serde(default)]
+    pub placeholder: bool,
+    /// Stable id; see `file_ids`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Device and inode, or volume and file index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native: Option<String>,
+    /// SHA-256 of the content, for files up to `limits.full_hash_max_bytes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Sampled fingerprint of larger files; see `fingerprint`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Bookmarked in the vault; see `obsidian_bookmarks`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bookmarked: bool,
+}
+
+pub type Snapshot = BTreeMap<String, SnapshotEntry>;
+
+#[derive(Serialize, Clone)]
+pub struct RescanReport {
+    pub store_id: String,
+    pub status: StoreStatus,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    /// Not counted in `added` or `removed`.
+    pub renamed: Vec<RenamedFile>,
+    pub file_count: usize,
+    pub scanned_at: String,
+    /// The workspace the rescan was run for, by `rescan_all_stores`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    /// Where the store's folder seems to have moved; see `store_relink`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relink_suggestion: Option<RelinkSuggestion>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RescanAllReport {
+    pub workspace_id: Option<String>,
+    pub reports: Vec<RescanReport>,
+    /// Stores whose rescan failed; the rest still ran.
+    pub failed: Vec<String>,
+}
+
+/// Relative paths that changed since the last scan.
+#[derive(Serialize, Clone, Default)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub renamed: Vec<RenamedFile>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+    pub file_id: String,
+    pub confidence: MatchConfidence,
+}
+
+/// What `pick_and_register_store` asks the picker for.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PickOptions {
+    /// Pick files instead of folders; each file's folder becomes the store.
+    pub files: bool,
+    pub multiple: bool,
+    /// Overrides detection for every selection.
+    pub kind: Option<StoreKind>,
+}
+
+/// Payload of `store-rules-applied`.
+#[derive(Serialize, Clone)]
+pub struct RulesApplied {
+    pub store_id: String,
+    /// Files now in the store's snapshot that weren't before.
+    pub included: usize,
+    /// Files no longer in it.
+    pub excluded: usize,
+    pub file_count: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn list_stores() -> Vec<ConnectedStore> {
+    load_registry()
+}
+
+/// Connect a folder as a store and take its first snapshot.
+///
+/// `kind` overrides detection (a `.obsidian` folder means a vault; a path
+/// under a sync client's folder means a cloud folder).
+#[tauri::command]
+pub fn register_store(
+    name: String,
+    path: String,
+    kind: Option<StoreKind>,
+) -> CommandResult<ConnectedStore> {
+    let root = PathBuf::from(&path);
+    if !bookmarks::is_content_uri(&path) && !root.is_dir() {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("Not a directory: {}", path))
+                .with("path", &path),
+        );
+    }
+
+    let id = store_id_for(&root);
+    {
+        let _guard = REGISTRY_LOCK.lock().unwrap();
+        let mut stores = load_registry();
+        if let Some(existing) = stores.iter().find(|s| s.id == id) {
+            return Ok(existing.clone());
+        }
+        let grant = bookmarks::create(&path)
+            .map_err(|e| CommandError::new(ErrorCode::PermissionDenied, e).with("path", &path))?;
+        stores.push(ConnectedStore {
+            id: id.clone(),
+            name,
+            kind: kind.unwrap_or_else(|| store_kinds::detect(&root)),
+            path,
+            file_count: 0,
+            status: StoreStatus::Ok,
+            last_scanned_at: None,
+            note_count: None,
+            placeholder_count: None,
+            is_git_repo: git::is_repo(&root),
+            grant,
+            rules: SyncRules::default(),
+        });
+        save_registry(&stores)?;
+    }
+
+    rescan(&id)?;
+    find_store(&id)
+}
+
+/// Pick a `needs_reauthorization` store's folder again and save the new
+/// grant. `None` if the picker was dismissed. The folder may be picked at a
+/// new location; the store keeps its id.
+#[tauri::command]
+pub async fn reauthorize_store(
+    app: AppHandle,
+    store_id: String,
+) -> CommandResult<Option<ConnectedStore>> {
+    use tauri_plugin_dialog::{DialogExt, FilePath};
+
+    let store = find_store(&store_id)?;
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app.dialog().file().set_title(i18n::text(
+            "dialog.reconnect_store",
+            &[("name", &store.name)],
+        ));
+        if !bookmarks::is_content_uri(&store.path) {
+            dialog = dialog.set_directory(&store.path);
+        }
+        dialog.blocking_pick_folder()
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    let path = match picked {
+        None => return Ok(None),
+        Some(FilePath::Path(path)) => path.to_string_lossy().to_string(),
+        Some(FilePath::Url(url)) => url.to_string(),
+    };
+
+    let grant = bookmarks::create(&path)
+        .map_err(|e| CommandError::new(ErrorCode::PermissionDenied, e).with("path", &path))?;
+    update_store(&store_id, |s| {
+        s.path = path;
+        s.grant = grant;
+        s.status = StoreStatus::Ok;
+    })?;
+    rescan(&store_id)?;
+    find_store(&store_id).map(Some)
+}
+
+/// Open the native picker and connect what was picked, each selection as
+/// its own store, with its grant and first scan. A dismissed picker returns
+/// no stores. A selection inside a connected store fails with
+/// `AlreadyInStore` before anything is registered.
+#[tauri::command]
+pub async fn pick_and_register_store(
+    app: AppHandle,
+    options: PickOptions,
+) -> CommandResult<Vec<ConnectedStore>> {
+    use tauri_plugin_dialog::{DialogExt, FilePath};
+
+    let files = options.files;
+    let multiple = options.multiple;
+    let dialog_app = app.clone();
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        let dialog = dialog_app
+            .dialog()
+            .file()
+            .set_title(i18n::text("dialog.connect_store", &[]));
+        match (files, multiple) {
+            (false, false) => dialog.blocking_pick_folder().map(|p| vec![p]),
+            (false, true) => dialog.blocking_pick_folders(),
+            (true, false) => dialog.blocking_pick_file().map(|p| vec![p]),
+            (true, true) => dialog.blocking_pick_files(),
+        }
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    let Some(picked) = picked else {
+        return Ok(Vec::new());
+    };
+
+    let mut roots: Vec<String> = Vec::new();
+    for selection in picked {
+        let root = match selection {
+            FilePath::Path(path) if files => match path.parent() {
+                Some(parent) => parent.to_string_lossy().to_string(),
+                None => continue,
+            },
+            FilePath::Path(path) => path.to_string_lossy().to_string(),
+            FilePath::Url(url) if files => {
+                return Err(CommandError::new(
+                    ErrorCode::Unsupported,
+                    format!("Pick the folder holding {} instead", url),
+                )
+                .with("path", url.to_string()))
+            }
+            FilePath::Url(url) => url.to_string(),
+        };
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    check_new_roots(&roots)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        use tauri_plugin_fs::FsExt;
+
+        let mut registered = Vec::new();
+        for root in roots {
+            if let Some(scope) = app.try_fs_scope() {
+                if !bookmarks::is_content_uri(&root) {
+                    scope
+                        .allow_directory(&root, true)
+                        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+                }
+            }
+            let name = Path::new(&root)
+                .file_name()
+                .map_or_else(|| root.clone(), |n| n.to_string_lossy().to_string());
+            registered.push(register_store(name, root, options.kind)?);
+        }
+        Ok(registered)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// `AlreadyInStore` for a root inside a connected store, or inside another
+/// of `roots`.
+fn check_new_roots(roots: &[String]) -> CommandResult<()> {
+    for root in roots {
+        let path = Path::new(root);
+        if let Some(store) = store_containing(path) {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyInStore,
+                format!("{} is already in the store {}", root, store.name),
+            )
+            .with("path", root)
+            .with("store_id", &store.id)
+            .with("store_name", &store.name));
+        }
+        let Ok(resolved) = path.canonicalize() else {
+            continue;
+        };
+        let outer = roots.iter().filter(|other| *other != root).find(|other| {
+            Path::new(other)
+                .canonicalize()
+                .is_ok_and(|other| resolved.starts_with(other))
+        });
+        if let Some(outer) = outer {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyInStore,
+                format!("{} is inside {}, which was also picked", root, outer),
+            )
+            .with("path", root)
+            .with("store_name", outer));
+        }
+    }
+    Ok(())
+}
+
+/// Re-open saved folder grants; run at startup before anything scans.
+pub fn restore_grants() {
+    let _guard = REGISTRY_LOCK.lock().unwrap();
+    let mut stores = load_registry();
+    let mut changed = false;
+    for store in stores.iter_mut() {
+        let Some(grant) = &store.grant else {
+            continue;
+        };
+        match bookmarks::resolve(grant) {
+            Ok(resolved) => {
+                if resolved.root != store.path {
+                    log::info!(
+                        "[stores] {} moved from {} to {}",
+                        store.id,
+                        store.path,
+                        resolved.root
+                    );
+                    store.path = resolved.root;
+                    changed = true;
+                }
+                if let Some(refreshed) = resolved.refreshed {
+                    store.grant = Some(refreshed);
+                    changed = true;
+                }
+                if store.status == StoreStatus::NeedsReauthorization {
+                    store.status = StoreStatus::Ok;
+                    changed = true;
+                }
+            }
+            Err(e) => {
+                log::warn!("[stores] grant for {} lost: {}", store.id, e);
+                if store.status != StoreStatus::NeedsReauthorization {
+                    store.status = StoreStatus::NeedsReauthorization;
+                    changed = true;
+                }
+            }
+        }
+    }
+    if changed {
+        if let Err(e) = save_registry(&stores) {
+            log::error!("[stores] saving restored grants failed: {}", e);
+        }
+    }
+}
+
+/// Re-walk a store, diff it against the last snapshot, and emit
+/// `store-rescan-complete` with the counts.
+#[tauri::command]
+pub async fn rescan_store(app: AppHandle, store_id: String) -> CommandResult<RescanReport> {
+    let op =
+        Operations::start_async(&app, OperationKind::StoreRescan, &store_id, RESCAN_WEIGHT).await?;
+    let report = tauri::async_runtime::spawn_blocking(move || rescan_as(op, &store_id))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))??;
+    announce(&app, &report);
+    Ok(report)
+}
+
+/// Rescan every store, or only those of the tenant's `workspace_id`, one at
+/// a time. Each report goes out as `store-rescan-complete` as it finishes.
+#[tauri::command]
+pub async fn rescan_all_stores(
+    app: AppHandle,
+    tenant_id: Option<String>,
+    workspace_id: Option<String>,
+) -> CommandResult<RescanAllReport> {
+    let workspace = workspaces::resolve(tenant_id.as_deref(), workspace_id.as_deref())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let stores = match &workspace {
+            Some(workspace) => workspace.stores(),
+            None => load_registry(),
+        };
+        rescan_each(&app, &stores, workspace.map(|w| w.id))
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+/// What a rescan of the store would report, without saving anything.
+#[tauri::command]
+pub fn diff_store_snapshot(store_id: String) -> CommandResult<SnapshotDiff> {
+    let store = find_store(&store_id)?;
+    let root = Path::new(&store.path);
+    if store.status == StoreStatus::NeedsReauthorization || !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Store {} is not reachable at {}", store.id, store.path),
+        )
+        .with("store_id", &store.id)
+        .with("path", &store.path));
+    }
+    let mut current = scan_store(&store)?;
+    Ok(diff(root, &load_snapshot(&store.id), &mut current))
+}
+
+/// Replace a store's sync rules, rescan it under them, and emit
+/// `store-rules-applied` with what changed.
+#[tauri::command]
+pub async fn update_store_rules(
+    app: AppHandle,
+    store_id: String,
+    rules: SyncRules,
+) -> CommandResult<ConnectedStore> {
+    CompiledRules::compile(&rules)?;
+    find_store(&store_id)?;
+    update_store(&store_id, |s| s.rules = rules)?;
+
+    let op =
+        Operations::start_async(&app, OperationKind::StoreRescan, &store_id, RESCAN_WEIGHT).await?;
+    let id = store_id.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || rescan_as(op, &id))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))??;
+    let _ = app.emit(
+        "store-rules-applied",
+        RulesApplied {
+            store_id: store_id.clone(),
+            included: report.added,
+            excluded: report.removed,
+            file_count: report.file_count,
+        },
+    );
+    announce(&app, &report);
+    find_store(&store_id)
+}
+
+// ─── Rescan ─────────────────────────────────────────────────────────────────
+
+/// Emit `store-rescan-complete`, and `store-relink-suggested` when the report
+/// has a suggestion.
+fn announce(app: &AppHandle, report: &RescanReport) {
+    let _ = app.emit("store-rescan-complete", report.clone());
+    if let Some(suggestion) = &report.relink_suggestion {
+        let _ = app.emit("store-relink-suggested", suggestion.clone());
+    }
+}
+
+/// `rescan` under a registered operation.
+fn rescan_as(op: OperationHandle, store_id: &str) -> CommandResult<RescanReport> {
+    op.progress("scanning", 0, None, Some(store_id));
+    let report = io_throttle::scoped(op.background_io(), || rescan(store_id));
+    op.finish_with(report, |report| {
+        Some(ResultRef::Store {
+            store_id: report.store_id.clone(),
+        })
+    })
+}
+
+pub fn rescan(store_id: &str) -> CommandResult<RescanReport> {
+    let store = find_store(store_id)?;
+    {
+        let mut running = RESCANNING.lock().unwrap();
+        if !running
+            .get_or_insert_with(HashSet::new)
+            .insert(store.id.clone())
+        {
+            return Err(CommandError::new(
+                ErrorCode::AlreadyRunning,
+                format!("Store {} is already being rescanned", store.id),
+            )
+            .with("store_id", &store.id));
+        }
+    }
+    let result = rescan_inner(&store);
+    if let Some(running) = RESCANNING.lock().unwrap().as_mut() {
+        running.remove(&store.id);
+    }
+    result
+}
+
+fn rescan_inner(store: &ConnectedStore) -> CommandResult<RescanReport> {
+    let scanned_at = chrono::Utc::now().to_rfc3339();
+    let root = Path::new(&store.path);
+    let previous = load_snapshot(&store.id);
+
+    if store.status == StoreStatus::NeedsReauthorization {
+        return Ok(RescanReport {
+            store_id: store.id.clone(),
+            status: store.status,
+            added: 0,
+            removed: 0,
+            modified: 0,
+            renamed: Vec::new(),
+            file_count: previous.len(),
+            scanned_at,
+            workspace_id: None,
+            relink_suggestion: None,
+        });
+    }
+
+    let mut current = if root.is_dir() {
+        Some(scan_store(store)?)
+    } else {
+        None
+    };
+    let offline_mount = store.kind.empty_root_is_unreachable()
+        && current.as_ref().is_some_and(|c| c.is_empty())
+        && !previous.is_empty();
+    if offline_mount {
+        current = None;
+    }
+    // Gone, or emptied: the folder may have moved
+    let relink_suggestion = match &current {
+        Some(current) if !current.is_empty() => None,
+        _ => store_relink::suggest(store, &previous),
+    };
+    if relink_suggestion.is_some() {
+        current = None;
+    }
+
+    let Some(mut current) = current else {
+        log::warn!(
+            "[stores] {} unreachable at {}; keeping last snapshot",
+            store.id,
+            store.path
+        );
+        update_store(&store.id, |s| s.status = StoreStatus::Unreachable)?;
+        return Ok(RescanReport {
+            store_id: store.id.clone(),
+            status: StoreStatus::Unreachable,
+            added: 0,
+            removed: 0,
+            modified: 0,
+            renamed: Vec::new(),
+            file_count: previous.len(),
+            scanned_at,
+            workspace_id: None,
+            relink_suggestion,
+        });
+    };
+
+    let changes = diff(root, &previous, &mut current);
+    let (added, removed, modified) = (
+        changes.added.len(),
+        changes.removed.len(),
+        changes.modified.len(),
+    );
+
+    save_snapshot(&store.id, &current)?;
+    similar_notes::refresh_store(store, &current);
+    let file_count = current.len();
+    let note_count = store
+        .kind
+        .counts_notes()
+        .then(|| current.keys().filter(|k| k.ends_with(".md")).count());
+    let placeholder_count = store
+        .kind
+        .tracks_placeholders()
+        .then(|| current.values().filter(|e| e.placeholder).count());
+    let is_git_repo = git::is_repo(Path::new(&store.path));
+    update_store(&store.id, |s| {
+        s.status = StoreStatus::Ok;
+        s.file_count = file_count;
+        s.last_scanned_at = Some(scanned_at.clone());
+        s.note_count = note_count;
+        s.placeholder_count = placeholder_count;
+        s.is_git_repo = is_git_repo;
+    })?;
+
+    log::info!(
+        "[stores] rescanned {}: +{} -{} ~{} >{} ({} files)",
+        store.id,
+        added,
+        removed,
+        modified,
+        changes.renamed.len(),
+        file_count
+    );
+    Ok(RescanReport {
+        store_id: store.id.clone(),
+        status: StoreStatus::Ok,
+        added,
+        removed,
+        modified,
+        renamed: changes.renamed,
+        file_count,
+        scanned_at,
+        workspace_id: None,
+        relink_suggestion: None,
+    })
+}
+
+/// Diff `current` against `previous` and give every current entry its id. A
+/// file that appeared as another disappeared is a rename when
+/// `file_ids::matches` pairs them; exact matches are paired first. Files
+/// that share a fingerprint are hashed in full before pairing, in case it's
+/// a collision.
+fn diff(root: &Path, previous: &Snapshot, current: &mut Snapshot) -> SnapshotDiff {
+    let full_hash_max = settings::load_settings().limits.full_hash_max_bytes;
+    let mut changes = SnapshotDiff::default();
+    let mut added = Vec::new();
+    for (key, entry) in current.iter_mut() {
+        let Some(old) = previous.get(key) else {
+            file_ids::identify(&root.join(key), entry, full_hash_max);
+            added.push(key.clone());
+            continue;
+        };
+        entry.id = old.id.clone();
+        let changed = content_changed(old, entry);
+        if changed {
+            changes.modified.push(key.clone());
+        }
+        // Snapshots from before file ids have no identity to carry over, and
+        // ones from before fingerprints have no content for large files
+        if changed || (old.hash.is_none() && old.fingerprint.is_none()) {
+            file_ids::identify(&root.join(key), entry, full_hash_max);
+        } else {
+            entry.native = old.native.clone();
+            entry.hash = old.hash.clone();
+            entry.fingerprint = old.fingerprint.clone();
+        }
+    }
+    fingerprint::confirm_collisions(root, current);
+
+    let mut removed: Vec<&String> = previous
+        .keys()
+        .filter(|k| !current.contains_key(*k))
+        .collect();
+    let mut renames = Vec::new();
+    for confidence in [MatchConfidence::Exact, MatchConfidence::Content] {
+        added.retain(|key| {
+            let entry = current.get_mut(key).expect("added key is in the snapshot");
+            let Some(i) = removed
+                .iter()
+                .position(|old| file_ids::matches(&previous[*old], entry) == Some(confidence))
+            else {
+                return true;
+            };
+            let from = removed.remove(i);
+            entry.id = previous[from].id.clone();
+            renames.push((from.clone(), key.clone(), confidence));
+            false
+        });
+    }
+
+    let mut used: HashSet<String> = current.values().filter_map(|e| e.id.clone()).collect();
+    for (key, entry) in current.iter_mut().filter(|(_, e)| e.id.is_none()) {
+        let content = entry.hash.as_deref().or(entry.fingerprint.as_deref());
+        let mut id = file_ids::derive(entry.native.as_deref(), content, key);
+        // Copies share a content hash
+        if !used.insert(id.clone()) {
+            id = file_ids::derive(None, None, key);
+            used.insert(id.clone());
+        }
+        entry.id = Some(id);
+    }
+
+    changes.renamed = renames
+        .into_iter()
+        .map(|(from, to, confidence)| RenamedFile {
+            file_id: current[&to].id.clone().unwrap_or_default(),
+            from,
+            to,
+            confidence,
+        })
+        .collect();
+    changes.added = added;
+    changes.removed = removed.into_iter().cloned().collect();
+    changes
+}
+
+/// A placeholder being hydrated (or dehydrated) isn't an edit.
+fn content_changed(old: &SnapshotEntry, new: &SnapshotEntry) -> bool {
+    if old.placeholder != new.placeholder {
+        return false;
+    }
+    old.size != new.size || old.modified != new.modified
+}
+
+/// Walk `root`, honoring ignore rules. Keys are `/`-separated relative paths.
+pub fn scan(root: &Path, kind: StoreKind) -> Snapshot {
+    scan_with(root, kind, &CompiledRules::default())
+}
+
+/// `scan` narrowed to what the store's sync rules match.
+pub fn scan_store(store: &ConnectedStore) -> CommandResult<Snapshot> {
+    let rules = CompiledRules::compile(&store.rules)?;
+    Ok(scan_with(Path::new(&store.path), store.kind, &rules))
+}
+
+/// The files to index under `root`: `scan_store` when `root` is a store's
+/// root, else `scan`.
+pub fn scan_for_index(root: &Path) -> CommandResult<Snapshot> {
+    match store_for_path(root).filter(|s| Path::new(&s.path) == root) {
+        Some(store) => scan_store(&store),
+        None => Ok(scan(root, store_kinds::detect(root))),
+    }
+}
+
+fn scan_with(root: &Path, kind: StoreKind, rules: &CompiledRules) -> Snapshot {
+    let ignore = IgnoreRules::load(root, kind);
+    let mut snapshot = Snapshot::new();
+
+    let walker = walkdir::WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| {
+            if e.depth() == 0 {
+                return true;
+            }
+            if ignore.skips(root, e) {
+                return false;
+            }
+            !e.file_type().is_dir()
+                || e.path()
+                    .strip_prefix(root)
+                    .is_ok_and(|r| rules.allows_dir(&relative_key(r)))
+        });
+    for entry in walker.flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+
+        let (relative, placeholder) = match icloud_placeholder_target(relative) {
+            Some(target) if kind.tracks_placeholders() => (target, true),
+            _ => (
+                relative.to_path_buf(),
+                kind.tracks_placeholders() && is_cloud_placeholder(&metadata),
+            ),
+        };
+        let key = relative_key(&relative);
+        if !rules.allows_file(&key, (!placeholder).then_some(metadata.len())) {
+            continue;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).timestamp())
+            .unwrap_or_default();
+
+        snapshot.insert(
+            key,
+            SnapshotEntry {
+                size: metadata.len(),
+                modified,
+                placeholder,
+                id: None,
+                native: None,
+                hash: None,
+                fingerprint: None,
+                bookmarked: false,
+            },
+        );
+    }
+    if kind == StoreKind::ObsidianVault {
+        for key in obsidian_bookmarks::bookmarked_paths(root) {
+            if let Some(entry) = snapshot.get_mut(&key) {
+                entry.bookmarked = true;
+            }
+        }
+    }
+    snapshot
+}
+
+fn relative_key(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// ─── Ignore Rules & Placeholders ────────────────────────────────────────────
+
+/// Hidden entries, `DEFAULT_IGNORES`, the kind's own exclusions, and the
+/// store's `.agentvbxignore` (one name or `*.ext` pattern per line, `#`
+/// comments).
+struct IgnoreRules {
+    names: HashSet<String>,
+    suffixes: Vec<String>,
+    /// Relative path prefixes, e.g. Obsidian's excluded folders.
+    prefixes: Vec<String>,
+}
+
+impl IgnoreRules {
+    fn load(root: &Path, kind: StoreKind) -> Self {
+        let mut rules = Self {
+            names: DEFAULT_IGNORES.iter().map(|s| s.to_string()).collect(),
+            suffixes: Vec::new(),
+            prefixes: kind.ignored_prefixes(root),
+        };
+        let Ok(raw) = fs::read_to_string(root.join(IGNORE_FILE)) else {
+            return rules;
+        };
+        for line in raw.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix('*') {
+                Some(suffix) => rules.suffixes.push(suffix.to_string()),
+                None => {
+                    rules.names.insert(line.trim_end_matches('/').to_string());
+                }
+            }
+        }
+        rules
+    }
+
+    fn skips(&self, root: &Path, entry: &walkdir::DirEntry) -> bool {
+        let name = entry.file_name().to_string_lossy();
+        // iCloud placeholders are hidden files but stand in for real ones
+        if name.starts_with('.') && !name.ends_with(".icloud") {
+            return true;
+        }
+        if !self.prefixes.is_empty() {
+            let mut relative = entry
+                .path()
+                .strip_prefix(root)
+                .map(relative_key)
+                .unwrap_or_default();
+            if entry.file_type().is_dir() {
+                relative.push('/');
+            }
+            if self
+                .prefixes
+                .iter()
+                .any(|p| relative.starts_with(p.as_str()))
+            {
+                return true;
+            }
+        }
+        self.names.contains(name.as_ref()) || self.suffixes.iter().any(|s| name.ends_with(s))
+    }
+}
+
+/// iCloud replaces evicted `notes/Foo.md` with `notes/.Foo.md.icloud`.
+fn icloud_placeholder_target(relative: &Path) -> Option<PathBuf> {
+    let name = relative.file_name()?.to_str()?;
+    let original = name.strip_prefix('.')?.strip_suffix(".icloud")?;
+    Some(relative.with_file_name(original))
+}
+
+/// OneDrive / Dropbox online-only files on Windows.
+#[cfg(windows)]
+fn is_cloud_placeholder(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+    metadata.file_attributes() & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+        != 0
+}
+
+#[cfg(not(windows))]
+fn is_cloud_placeholder(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+// ─── Scheduler ──────────────────────────────────────────────────────────────
+
+/// Register the `store_rescan` job. Its default interval is
+/// `rescan_interval_minutes`; 0 leaves it off until the user turns it on.
+pub fn register_jobs(scheduler: &Scheduler) {
+    let minutes = settings::load_settings().rescan_interval_minutes;
+    scheduler.register(
+        "store_rescan",
+        "Rescan connected stores for changes",
+        Schedule::Every(Duration::from_secs(minutes.max(1) as u64 * 60)),
+        JobLoad::Heavy,
+        minutes > 0,
+        rescan_all,
+    );
+}
+
+fn rescan_all(app: &AppHandle) -> Result<Option<String>, String> {
+    let stores = load_registry();
+    let report = rescan_each(app, &stores, None);
+    if !report.failed.is_empty() {
+        return Err(format!(
+            "{} of {} stores failed to rescan",
+            report.failed.len(),
+            stores.len()
+        ));
+    }
+    Ok(Some(format!("{} stores rescanned", stores.len())))
+}
+
+/// Rescan `stores` in turn, skipping any already being rescanned.
+fn rescan_each(
+    app: &AppHandle,
+    stores: &[ConnectedStore],
+    workspace_id: Option<String>,
+) -> RescanAllReport {
+    let operations = app.state::<Operations>();
+    let mut all = RescanAllReport {
+        workspace_id,
+        reports: Vec::new(),
+        failed: Vec::new(),
+    };
+    for store in stores {
+        let result = operations
+            .start(app, OperationKind::StoreRescan, &store.id, RESCAN_WEIGHT)
+            .and_then(|op| rescan_as(op, &store.id));
+        match result {
+            Ok(mut report) => {
+                report.workspace_id = all.workspace_id.clone();
+                announce(app, &report);
+                all.reports.push(report);
+            }
+            Err(e) if e.code == ErrorCode::AlreadyRunning => {}
+            Err(e) => {
+                log::warn!("[stores] rescan of {} failed: {}", store.id, e);
+                all.failed.push(store.id.clone());
+            }
+        }
+    }
+    all
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn registry_path() -> CommandResult<PathBuf> {
+    Ok(home::config_dir()?.join(REGISTRY_FILE))
+}
+
+fn store_data_dir(store_id: &str) -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("stores").join(store_id))
+}
+
+pub fn load_registry() -> Vec<ConnectedStore> {
+    registry_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn save_registry(stores: &[ConnectedStore]) -> Result<(), String> {
+    Ok(integrity::persist_json(&registry_path()?, stores, None)?)
+}
+
+impl integrity::Validate for [ConnectedStore] {
+    fn validate(&self) -> Result<(), String> {
+        let mut ids = std::collections::HashSet::new();
+        for store in self {
+            if store.id.is_empty() || store.path.is_empty() {
+                return Err(format!("store {:?} has no id or path", store.name));
+            }
+            if !ids.insert(&store.id) {
+                return Err(format!("store id {} is registered twice", store.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Add stores from a backup that aren't registered locally. Returns how many.
+pub fn merge_registry(incoming: Vec<ConnectedStore>) -> Result<usize, String> {
+    let _guard = REGISTRY_LOCK.lock().unwrap();
+    let mut stores = load_registry();
+    let before = stores.len();
+    for store in incoming {
+        if !stores.iter().any(|s| s.id == store.id) {
+            stores.push(store);
+        }
+    }
+    let added = stores.len() - before;
+    if added > 0 {
+        save_registry(&stores)?;
+    }
+    Ok(added)
+}
+
+pub fn find_store(store_id: &str) -> CommandResult<ConnectedStore> {
+    load_registry()
+        .into_iter()
+        .find(|s| s.id == store_id)
+        .ok_or_else(|| {
+            CommandError::new(ErrorCode::NotFound, format!("Unknown store: {}", store_id))
+                .with("store_id", store_id)
+        })
+}
+
+/// The store whose root contains `path`, preferring the deepest root.
+pub fn store_for_path(path: &Path) -> Option<ConnectedStore> {
+    load_registry()
+        .into_iter()
+        .filter(|s| path.starts_with(&s.path))
+        .max_by_key(|s| s.path.len())
+}
+
+/// The last scan's entry for `path`, with its store.
+pub fn snapshot_entry(path: &Path) -> Option<(ConnectedStore, SnapshotEntry)> {
+    let store = store_for_path(path)?;
+    let key = relative_key(path.strip_prefix(&store.path).ok()?);
+    let entry = load_snapshot(&store.id).remove(&key)?;
+    Some((store, entry))
+}
+
+/// Like `store_for_path`, with symlinks and `..` resolved on both sides so a
+/// path can't escape its store. `path` must exist. Roots restored from a
+/// grant count like any other; stores awaiting reauthorization don't.
+pub fn store_containing(path: &Path) -> Option<ConnectedStore> {
+    let resolved = path.canonicalize().ok()?;
+    load_registry()
+        .into_iter()
+        .filter(|s| s.status != StoreStatus::NeedsReauthorization)
+        .filter_map(|s| Some((Path::new(&s.path).canonicalize().ok()?, s)))
+        .filter(|(root, _)| resolved.starts_with(root))
+        .max_by_key(|(root, _)| root.as_os_str().len())
+        .map(|(_, s)| s)
+}
+
+pub fn update_store(store_id: &str, change: impl FnOnce(&mut ConnectedStore)) -> CommandResult<()> {
+    let _guard = REGISTRY_LOCK.lock().unwrap();
+    let mut stores = load_registry();
+    // Disconnected mid-scan: nothing to update
+    if let Some(store) = stores.iter_mut().find(|s| s.id == store_id) {
+        change(store);
+        save_registry(&stores)?;
+    }
+    Ok(())
+}
+
+pub fn load_snapshot(store_id: &str) -> Snapshot {
+    store_data_dir(store_id)
+        .ok()
+        .and_then(|dir| fs::read(dir.join(SNAPSHOT_FILE)).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_snapshot(store_id: &str, snapshot: &Snapshot) -> Result<(), String> {
+    let dir = store_data_dir(store_id)?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_vec(snapshot).map_err(|e| e.to_string())?;
+    fs::write(dir.join(SNAPSHOT_FILE), json).map_err(|e| e.to_string())
+}
+
+/// Stable id from the canonical root path, so reconnecting a folder finds
+/// its existing store.
+fn store_id_for(root: &Path) -> String {
+    use sha2::{Digest, Sha256};
+    let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let digest = Sha256::digest(canonical.to_string_lossy().as_bytes());
+    format!("store-{}", &hex::encode(digest)[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    #[test]
+    fn registering_scans_the_store_without_hidden_files() {
+        let _home = TestHome::with_fixture(BASIC_FIXTURE);
+        let stores = list_stores();
+        assert_eq!(stores.len(), 1);
+        assert_eq!(stores[0].name, "Reports");
+        assert_eq!(stores[0].file_count, 3);
+        assert!(stores[0].grant.is_none());
+    }
+
+    #[test]
+    fn picks_inside_a_connected_store_are_refused() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let existing = list_stores().remove(0);
+        home.write("Reports/2024/q1.md", "# Q1\n");
+        home.write("Elsewhere/a.md", "# A\n");
+        home.write("Elsewhere/inner/b.md", "# B\n");
+        let root = |relative: &str| home.join(relative).to_string_lossy().to_string();
+
+        let err = check_new_roots(&[root("Elsewhere"), root("Reports/2024")]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::AlreadyInStore);
+        assert_eq!(err.params["store_id"], existing.id.as_str());
+        assert!(check_new_roots(&[root("Reports")]).is_err());
+
+        let nested = check_new_roots(&[root("Elsewhere"), root("Elsewhere/inner")]);
+        assert!(nested.is_err());
+        check_new_roots(&[root("Elsewhere")]).unwrap();
+    }
+
+    #[test]
+    fn registering_a_connected_folder_again_returns_the_existing_store() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let path = home.join("Reports").to_string_lossy().to_string();
+        let again = register_store("Renamed".into(), path, None).unwrap();
+        assert_eq!(again.name, "Reports");
+        assert_eq!(list_stores().len(), 1);
+    }
+
+    #[test]
+    fn a_renamed_file_keeps_its_id() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let store = list_stores().remove(0);
+        let before = snapshot_entry(&home.join("Reports/Summary.md")).unwrap().1;
+        fs::rename(home.join("Reports/Summary.md"), home.join("Reports/Q1.md")).unwrap();
+
+        let report = rescan(&store.id).unwrap();
+        assert_eq!((report.added, report.removed), (0, 0));
+        assert_eq!(report.renamed.len(), 1);
+        let renamed = &report.renamed[0];
+        assert_eq!(
+            (renamed.from.as_str(), renamed.to.as_str()),
+            ("Summary.md", "Q1.md")
+        );
+        assert_eq!(renamed.confidence, MatchConfidence::Exact);
+        assert_eq!(Some(&renamed.file_id), before.id.as_ref());
+
+        let identity = file_ids::identify_path(&home.join("Reports/Q1.md"));
+        assert!(identity.tracked);
+        assert_eq!(Some(identity.id), before.id);
+    }
+
+    #[test]
+    fn a_move_across_volumes_matches_by_content() {
+        let home = TestHome::new();
+        let root = home.join("Store");
+        home.write("Store/report.md", "quarterly numbers");
+        home.write("Store/empty.md", "");
+        let mut previous = scan(&root, StoreKind::Folder);
+        diff(&root, &Snapshot::new(), &mut previous);
+        // A new volume gives the file a new native identity
+        for entry in previous.values_mut() {
+            entry.native = Some("old-volume:1".into());
+        }
+        fs::rename(root.join("report.md"), root.join("moved.md")).unwrap();
+        fs::rename(root.join("empty.md"), root.join("blank.md")).unwrap();
+
+        let mut current = scan(&root, StoreKind::Folder);
+        let changes = diff(&root, &previous, &mut current);
+        assert_eq!(changes.renamed.len(), 1);
+        assert_eq!(changes.renamed[0].to, "moved.md");
+        assert_eq!(changes.renamed[0].confidence, MatchConfidence::Content);
+        assert_eq!(current["moved.md"].id, previous["report.md"].id);
+        // Empty files all hash alike, so they aren't paired by content
+        assert_eq!(changes.added, ["blank.md"]);
+        assert_eq!(changes.removed, ["empty.md"]);
+    }
+
+    #[test]
+    fn sync_rules_narrow_the_snapshot_and_changes_reconcile() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let store = list_stores().remove(0);
+        let ids: Vec<_> = load_snapshot(&store.id)
+            .into_values()
+            .map(|e| e.id)
+            .collect();
+
+        update_store(&store.id, |s| {
+            s.rules = SyncRules {
+                exclude: vec!["archive".into()],
+                extensions: vec!["pdf".into()],
+                ..Default::default()
+            }
+        })
+        .unwrap();
+        let narrowed = rescan(&store.id).unwrap();
+        assert_eq!((narrowed.added, narrowed.removed), (0, 2));
+        assert_eq!(list_stores()[0].file_count, 1);
+        assert_eq!(
+            scan_for_index(&home.join("Reports"))
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>(),
+            ["q1.pdf"]
+        );
+
+        update_store(&store.id, |s| s.rules = SyncRules::default()).unwrap();
+        let widened = rescan(&store.id).unwrap();
+        assert_eq!((widened.added, widened.removed), (2, 0));
+        // Ids derive from the native identity, so files coming back get theirs
+        let again: Vec<_> = load_snapshot(&store.id)
+            .into_values()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(again, ids);
+    }
+}
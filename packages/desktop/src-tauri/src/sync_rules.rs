@@ -0,0 +1,293 @@
+// Selective sync rules
+//
+// A store can cover less than its whole folder. Its `SyncRules` decide which
+// files are snapshotted, counted, and indexed:
+// - `include`: if any are given, a file must match one.
+// - `exclude`: a file matching any is left out.
+// - `max_file_bytes`: larger files are left out.
+// - `extensions`: if any are given, a file must have one (`md` or `.md`).
+//
+// Patterns are globs relative to the store root, with `/` separators. `*`
+// stays within one folder and `**` crosses folders. A pattern without a `/`
+// matches names at any depth. A pattern that matches a folder covers
+// everything inside it, so `Clients` and `Clients/**` mean the same. Matching
+// ignores case on macOS and Windows, as their filesystems do.
+//
+// Rules apply on top of the ignore rules in `stores`. Folders that no include
+// pattern can reach, and excluded folders, are never walked.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::stores;
+
+const CASE_SENSITIVE: bool = !cfg!(any(target_os = "macos", target_os = "windows"));
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct SyncRules {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub max_file_bytes: Option<u64>,
+    pub extensions: Vec<String>,
+}
+
+impl SyncRules {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+struct Rule {
+    pattern: glob::Pattern,
+    /// Leading folders with no glob characters, for pruning the walk.
+    literal: Vec<String>,
+}
+
+/// `SyncRules` ready to match; the default matches everything.
+#[derive(Default)]
+pub struct CompiledRules {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+    max_file_bytes: Option<u64>,
+    extensions: Vec<String>,
+}
+
+impl CompiledRules {
+    /// Fails with `InvalidInput` naming the first bad pattern.
+    pub fn compile(rules: &SyncRules) -> CommandResult<Self> {
+        let compile_all = |patterns: &[String]| -> CommandResult<Vec<Rule>> {
+            patterns.iter().map(|p| compile_rule(p)).collect()
+        };
+        Ok(Self {
+            include: compile_all(&rules.include)?,
+            exclude: compile_all(&rules.exclude)?,
+            max_file_bytes: rules.max_file_bytes,
+            extensions: rules
+                .extensions
+                .iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        })
+    }
+
+    /// Whether to walk the folder at `relative`.
+    pub fn allows_dir(&self, relative: &str) -> bool {
+        if matches_any(&self.exclude, relative) {
+            return false;
+        }
+        if self.include.is_empty() || matches_any(&self.include, relative) {
+            return true;
+        }
+        let parts = components(relative);
+        self.include.iter().any(|rule| {
+            // Either the folder leads to the pattern's literal part, or it is
+            // inside it and the glob part may match below
+            let shared = parts.len().min(rule.literal.len());
+            parts[..shared] == rule.literal[..shared]
+        })
+    }
+
+    /// Whether the file at `relative` belongs in the store. `size` is `None`
+    /// for cloud placeholders, whose local size means nothing.
+    pub fn allows_file(&self, relative: &str, size: Option<u64>) -> bool {
+        if !self.include.is_empty() && !matches_any(&self.include, relative) {
+            return false;
+        }
+        if matches_any(&self.exclude, relative) {
+            return false;
+        }
+        if let (Some(max), Some(size)) = (self.max_file_bytes, size) {
+            if size > max {
+                return false;
+            }
+        }
+        if !self.extensions.is_empty() {
+            let ext = Path::new(relative)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !self.extensions.contains(&ext) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The rules of the store containing `path`, to filter a listing with.
+pub struct StoreFilter {
+    root: PathBuf,
+    rules: CompiledRules,
+}
+
+impl StoreFilter {
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let store = stores::store_for_path(path)?;
+        if store.rules.is_empty() {
+            return None;
+        }
+        let rules = CompiledRules::compile(&store.rules)
+            .map_err(|e| log::warn!("[sync_rules] store {}: {}", store.id, e))
+            .ok()?;
+        Some(Self {
+            root: PathBuf::from(store.path),
+            rules,
+        })
+    }
+
+    pub fn allows(&self, path: &Path, is_directory: bool, size: u64) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        if is_directory {
+            self.rules.allows_dir(&relative)
+        } else {
+            self.rules.allows_file(&relative, Some(size))
+        }
+    }
+}
+
+fn compile_rule(raw: &str) -> CommandResult<Rule> {
+    let trimmed = raw.trim().trim_start_matches("./").trim_end_matches('/');
+    let anchored = if trimmed.contains('/') {
+        trimmed.to_string()
+    } else {
+        format!("**/{}", trimmed)
+    };
+    let pattern = glob::Pattern::new(&anchored).map_err(|e| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid sync rule {:?}: {}", raw, e),
+        )
+        .with("pattern", raw)
+    })?;
+    let literal = components(&anchored)
+        .into_iter()
+        .take_while(|part| !part.contains(['*', '?', '[']))
+        .collect();
+    Ok(Rule { pattern, literal })
+}
+
+/// `relative` or one of its folders matches a rule.
+fn matches_any(rules: &[Rule], relative: &str) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: CASE_SENSITIVE,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    let mut candidate = relative;
+    loop {
+        if rules
+            .iter()
+            .any(|r| r.pattern.matches_with(candidate, options))
+        {
+            return true;
+        }
+        match candidate.rsplit_once('/') {
+            Some((parent, _)) => candidate = parent,
+            None => return false,
+        }
+    }
+}
+
+fn components(relative: &str) -> Vec<String> {
+    relative
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            if CASE_SENSITIVE {
+                p.to_string()
+            } else {
+                p.to_lowercase()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(include: &[&str], exclude: &[&str]) -> CompiledRules {
+        CompiledRules::compile(&SyncRules {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn no_rules_match_everything() {
+        let all = CompiledRules::default();
+        assert!(all.allows_dir("any/folder"));
+        assert!(all.allows_file("any/file.bin", Some(u64::MAX)));
+    }
+
+    #[test]
+    fn a_folder_pattern_covers_its_contents() {
+        for include in ["Clients", "Clients/", "Clients/**"] {
+            let only = rules(&[include], &[]);
+            assert!(
+                only.allows_file("Clients/Acme/brief.md", None),
+                "{}",
+                include
+            );
+            assert!(!only.allows_file("Personal/diary.md", None), "{}", include);
+        }
+    }
+
+    #[test]
+    fn walks_only_folders_that_can_reach_an_include() {
+        let only = rules(&["Work/Clients/*/notes"], &[]);
+        assert!(only.allows_dir("Work"));
+        assert!(only.allows_dir("Work/Clients"));
+        assert!(only.allows_dir("Work/Clients/Acme"));
+        assert!(!only.allows_dir("Personal"));
+        assert!(!only.allows_dir("Work/Archive"));
+        assert!(only.allows_file("Work/Clients/Acme/notes/call.md", None));
+        assert!(!only.allows_file("Work/Clients/Acme/invoice.pdf", None));
+    }
+
+    #[test]
+    fn excludes_win_and_bare_names_match_at_any_depth() {
+        let some = rules(&["Clients"], &["*.tmp", "Clients/Archive"]);
+        assert!(!some.allows_file("Clients/Acme/draft.tmp", None));
+        assert!(!some.allows_dir("Clients/Archive"));
+        assert!(!some.allows_file("Clients/Archive/2019/old.md", None));
+        assert!(some.allows_file("Clients/Acme/brief.md", None));
+    }
+
+    #[test]
+    fn size_and_extension_limits() {
+        let limited = CompiledRules::compile(&SyncRules {
+            max_file_bytes: Some(1024),
+            extensions: vec![".MD".into(), "pdf".into()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(limited.allows_file("a/Note.md", Some(10)));
+        assert!(limited.allows_file("scan.PDF", Some(1024)));
+        assert!(!limited.allows_file("scan.pdf", Some(1025)));
+        assert!(!limited.allows_file("photo.png", Some(10)));
+        assert!(!limited.allows_file("Makefile", Some(10)));
+        // Placeholders have no real size yet
+        assert!(limited.allows_file("big.pdf", None));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let err = CompiledRules::compile(&SyncRules {
+            exclude: vec!["notes/[".into()],
+            ..Default::default()
+        })
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+        assert_eq!(err.params["pattern"], "notes/[");
+    }
+}
@@ -0,0 +1,169 @@
+// System state
+//
+// Whether the machine is on battery and whether the network is metered, so
+// background work can back off. A monitor thread re-reads the state every
+// minute and emits `system-state-changed` when it changes. The `throttle`
+// field says what is being held back right now, based on `settings.power`:
+// - Heavy scheduled jobs wait while on battery below
+//   `defer_heavy_below_percent`.
+// - Orchestrator uploads pause on metered connections while
+//   `pause_uploads_on_metered` is on, unless the caller overrides it.
+// Metered detection uses NetworkManager on Linux and the connection cost on
+// Windows. macOS doesn't expose either, so it reports `None`.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::maintenance::MaintenanceMode;
+use crate::{power, settings};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+static CURRENT: Mutex<Option<SystemState>> = Mutex::new(None);
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct SystemState {
+    /// `None` on machines without a battery.
+    pub on_battery: Option<bool>,
+    pub battery_percent: Option<u8>,
+    pub battery_saver: bool,
+    /// `None` where the OS doesn't say.
+    pub metered: Option<bool>,
+    pub throttle: Throttle,
+}
+
+#[derive(Serialize, Clone, PartialEq, Default, Debug)]
+pub struct Throttle {
+    /// Why heavy background jobs are waiting, if they are.
+    pub heavy_jobs_deferred: Option<String>,
+    /// Why orchestrator uploads are paused, if they are.
+    pub uploads_paused: Option<String>,
+}
+
+#[tauri::command]
+pub fn get_system_state(app: AppHandle) -> SystemState {
+    refresh(&app)
+}
+
+/// The last state the monitor saw, probing now if it hasn't run yet.
+pub fn current() -> SystemState {
+    if let Some(state) = CURRENT.lock().unwrap().clone() {
+        return state;
+    }
+    let state = probe();
+    *CURRENT.lock().unwrap() = Some(state.clone());
+    state
+}
+
+/// Start the monitor; runs for the life of the app, resting during
+/// maintenance.
+pub fn spawn(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        if !app.state::<MaintenanceMode>().enabled() {
+            refresh(&app);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    });
+}
+
+/// `Throttled` while uploads are paused, unless `allow_metered` overrides the
+/// metered-connection pause.
+pub fn check_upload_allowed(allow_metered: bool) -> CommandResult<()> {
+    match current().throttle.uploads_paused {
+        Some(reason) if !allow_metered => Err(CommandError::new(
+            ErrorCode::Throttled,
+            format!("Uploads paused: {}", reason),
+        )
+        .with("reason", "metered")
+        .with("setting", "power.pause_uploads_on_metered")),
+        _ => Ok(()),
+    }
+}
+
+fn refresh(app: &AppHandle) -> SystemState {
+    let state = probe();
+    let previous = CURRENT.lock().unwrap().replace(state.clone());
+    if previous.as_ref() != Some(&state) {
+        log::info!("[system_state] {:?}", state);
+        let _ = app.emit("system-state-changed", state.clone());
+    }
+    state
+}
+
+fn probe() -> SystemState {
+    let battery = power::battery();
+    let metered = metered();
+    let settings = settings::load_settings().power;
+
+    let mut throttle = Throttle::default();
+    if let Some(battery) = battery {
+        let threshold = settings.defer_heavy_below_percent;
+        match battery.percent {
+            Some(percent) if battery.discharging && percent < threshold => {
+                throttle.heavy_jobs_deferred =
+                    Some(format!("on battery at {}% (below {}%)", percent, threshold));
+            }
+            _ => {}
+        }
+    }
+    if settings.pause_uploads_on_metered && metered == Some(true) {
+        throttle.uploads_paused = Some("metered connection".into());
+    }
+
+    SystemState {
+        on_battery: battery.map(|b| b.discharging),
+        battery_percent: battery.and_then(|b| b.percent),
+        battery_saver: power::battery_saver_active(),
+        metered,
+        throttle,
+    }
+}
+
+// ─── Metered networks ───────────────────────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+fn metered() -> Option<bool> {
+    // NMMetered: 1 yes, 2 no, 3 guess yes, 4 guess no, 0 unknown
+    let output = std::process::Command::new("busctl")
+        .args([
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .ok()?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "u 1" | "u 3" => Some(true),
+        "u 2" | "u 4" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn metered() -> Option<bool> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "[Windows.Networking.Connectivity.NetworkInformation,\
+        Windows.Networking.Connectivity,ContentType=WindowsRuntime]::\
+        GetInternetConnectionProfile().GetConnectionCost().NetworkCostType";
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "Unrestricted" => Some(false),
+        "Fixed" | "Variable" => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn metered() -> Option<bool> {
+    None
+}
@@ -0,0 +1,555 @@
+// Orchestrator task inbox
+//
+// The orchestrator assigns the app work, like "hash and upload these 40
+// files", with `task.assign` messages on the frontend's WebSocket. The
+// frontend hands each one to `receive_assigned_task`, which writes it to
+// `orchestrator/tasks.json` (data directory) before anything runs, so an
+// assignment survives the app quitting mid-way.
+//
+// A task moves `received` → `in_progress` → `done` or `failed`. Its
+// `task_type` picks the local operation:
+// - `hash`: SHA-256 of each of `params.paths`.
+// - `upload`: `upload_artifact` for each of `params.paths`, with
+//   `params.metadata` and `params.allow_metered`. An upload that has to wait
+//   counts as done; the upload queue sends it later.
+// - `rescan`: `rescan_store` for `params.store_id`.
+// Any other type is parked as `unsupported` instead of dropped. Assigning a
+// task id that's already in the inbox returns the task as it is and runs
+// nothing.
+//
+// Every change is emitted as `assigned-task-status`, journaled, for the
+// frontend to report back over the socket. Once the orchestrator has the
+// outcome, the frontend calls `ack_task`; acknowledged tasks are kept for
+// `RETAIN` so a late duplicate is still recognized. At startup, tasks left
+// `received` or `in_progress` are dispatched again. Every operation above
+// is safe to repeat: uploads of content already queued or sent are
+// deduplicated.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::maintenance::MaintenanceMode;
+use crate::read_only::ReadOnlyMode;
+use crate::{artifact_upload, event_journal, file_read, home, integrity, sessions, stores};
+
+const MESSAGE_TYPE: &str = "task.assign";
+const SUPPORTED: &[&str] = &["hash", "upload", "rescan"];
+const RETAIN: chrono::Duration = chrono::Duration::days(7);
+
+/// Serializes read-modify-write of the inbox.
+static INBOX_LOCK: Mutex<()> = Mutex::new(());
+/// Tasks being worked on right now.
+static RUNNING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Received,
+    InProgress,
+    Done,
+    Failed,
+    /// The app has no operation for the task's type.
+    Unsupported,
+}
+
+impl TaskState {
+    fn finished(self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Unsupported)
+    }
+}
+
+/// A `task.assign` message.
+#[derive(Deserialize)]
+struct Assignment {
+    #[serde(rename = "type")]
+    message_type: String,
+    task_id: String,
+    tenant_id: String,
+    task_type: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AssignedTask {
+    pub task_id: String,
+    pub tenant_id: String,
+    pub task_type: String,
+    pub params: serde_json::Value,
+    pub state: TaskState,
+    /// What the operation returned; set on `done` and, for tasks over
+    /// several files, on `failed`.
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    /// Times the task was dispatched, restarts included.
+    pub attempts: u32,
+    pub received_at: String,
+    pub updated_at: String,
+    /// When the frontend reported the outcome to the orchestrator.
+    pub acked_at: Option<String>,
+}
+
+/// The outcome of a task the frontend carried out itself.
+#[derive(Deserialize)]
+pub struct TaskResult {
+    #[serde(default)]
+    pub output: Option<serde_json::Value>,
+    /// Set when the task failed.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Persist a `task.assign` message and start on it. A task id that's already
+/// in the inbox returns the stored task unchanged.
+#[tauri::command]
+pub fn receive_assigned_task(
+    app: AppHandle,
+    message: serde_json::Value,
+) -> CommandResult<AssignedTask> {
+    let assignment: Assignment = serde_json::from_value(message).map_err(|e| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid task assignment: {}", e),
+        )
+    })?;
+    let (task, is_new) = insert(assignment)?;
+    if !is_new {
+        log::info!(
+            "[task_inbox] {} was already assigned; ignoring the duplicate",
+            task.task_id
+        );
+        return Ok(task);
+    }
+
+    emit_status(&app, &task);
+    if task.state == TaskState::Received {
+        let task_id = task.task_id.clone();
+        tauri::async_runtime::spawn(async move { run(&app, &task_id).await });
+    }
+    Ok(task)
+}
+
+/// Tasks in the inbox, oldest first.
+#[tauri::command]
+pub fn list_assigned_tasks(tenant_id: Option<String>) -> Vec<AssignedTask> {
+    load_inbox()
+        .into_iter()
+        .filter(|t| tenant_id.as_ref().is_none_or(|id| *id == t.tenant_id))
+        .collect()
+}
+
+/// Record that the orchestrator has a task's outcome. For a task the
+/// frontend carried out itself, `result` is that outcome; a finished task
+/// keeps its own.
+#[tauri::command]
+pub fn ack_task(
+    app: AppHandle,
+    task_id: String,
+    result: Option<TaskResult>,
+) -> CommandResult<AssignedTask> {
+    let (task, changed) = acknowledge(&task_id, result)?;
+    if changed {
+        emit_status(&app, &task);
+    }
+    Ok(task)
+}
+
+// ─── Inbox ──────────────────────────────────────────────────────────────────
+
+/// Add an assignment to the inbox. Returns the stored task and whether it's
+/// new.
+fn insert(assignment: Assignment) -> CommandResult<(AssignedTask, bool)> {
+    if assignment.message_type != MESSAGE_TYPE {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Not a {} message: {}",
+                MESSAGE_TYPE, assignment.message_type
+            ),
+        )
+        .with("type", &assignment.message_type));
+    }
+    if assignment.task_id.trim().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Task assignment has no task_id",
+        ));
+    }
+    sessions::validate_id("tenant_id", &assignment.tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+
+    let _guard = INBOX_LOCK.lock().unwrap();
+    let mut inbox = load_inbox();
+    if let Some(existing) = inbox.iter().find(|t| t.task_id == assignment.task_id) {
+        return Ok((existing.clone(), false));
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let supported = SUPPORTED.contains(&assignment.task_type.as_str());
+    let task = AssignedTask {
+        state: if supported {
+            TaskState::Received
+        } else {
+            TaskState::Unsupported
+        },
+        error: (!supported).then(|| format!("Unsupported task type: {}", assignment.task_type)),
+        task_id: assignment.task_id,
+        tenant_id: assignment.tenant_id,
+        task_type: assignment.task_type,
+        params: assignment.params,
+        result: None,
+        attempts: 0,
+        received_at: now.clone(),
+        updated_at: now,
+        acked_at: None,
+    };
+    if !supported {
+        log::warn!(
+            "[task_inbox] {} has unsupported type {}",
+            task.task_id,
+            task.task_type
+        );
+    }
+    prune(&mut inbox);
+    inbox.push(task.clone());
+    save_inbox(&inbox)?;
+    Ok((task, true))
+}
+
+/// `ack_task` without the event. Returns the task and whether it changed.
+fn acknowledge(task_id: &str, result: Option<TaskResult>) -> CommandResult<(AssignedTask, bool)> {
+    let _guard = INBOX_LOCK.lock().unwrap();
+    let mut inbox = load_inbox();
+    let task = inbox
+        .iter_mut()
+        .find(|t| t.task_id == task_id)
+        .ok_or_else(|| unknown_task(task_id))?;
+    if task.acked_at.is_some() {
+        return Ok((task.clone(), false));
+    }
+
+    if !task.state.finished() {
+        let Some(result) = result else {
+            return Err(CommandError::new(
+                ErrorCode::Conflict,
+                format!("Task {} is still {:?}", task_id, task.state),
+            )
+            .with("task_id", task_id));
+        };
+        task.state = if result.error.is_some() {
+            TaskState::Failed
+        } else {
+            TaskState::Done
+        };
+        task.result = result.output;
+        task.error = result.error;
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    task.acked_at = Some(now.clone());
+    task.updated_at = now;
+    let task = task.clone();
+    save_inbox(&inbox)?;
+    Ok((task, true))
+}
+
+/// Apply `change` to a stored task and save it.
+fn update(task_id: &str, change: impl FnOnce(&mut AssignedTask)) -> CommandResult<AssignedTask> {
+    let _guard = INBOX_LOCK.lock().unwrap();
+    let mut inbox = load_inbox();
+    let task = inbox
+        .iter_mut()
+        .find(|t| t.task_id == task_id)
+        .ok_or_else(|| unknown_task(task_id))?;
+    change(task);
+    task.updated_at = chrono::Utc::now().to_rfc3339();
+    let task = task.clone();
+    save_inbox(&inbox)?;
+    Ok(task)
+}
+
+/// Drop tasks acknowledged more than `RETAIN` ago.
+fn prune(inbox: &mut Vec<AssignedTask>) {
+    let cutoff = chrono::Utc::now() - RETAIN;
+    inbox.retain(|t| {
+        t.acked_at
+            .as_deref()
+            .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+            .is_none_or(|at| at > cutoff)
+    });
+}
+
+fn unknown_task(task_id: &str) -> CommandError {
+    CommandError::new(ErrorCode::NotFound, format!("Unknown task: {}", task_id))
+        .with("task_id", task_id)
+}
+
+fn inbox_path() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("orchestrator").join("tasks.json"))
+}
+
+fn load_inbox() -> Vec<AssignedTask> {
+    inbox_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn save_inbox(inbox: &[AssignedTask]) -> CommandResult<()> {
+    integrity::persist_json(&inbox_path()?, inbox, None)
+}
+
+fn emit_status(app: &AppHandle, task: &AssignedTask) {
+    event_journal::emit(app, "assigned-task-status", task);
+}
+
+// ─── Executor ───────────────────────────────────────────────────────────────
+
+/// Dispatch the tasks a previous run left unfinished, unless read-only or
+/// maintenance mode is on; turning either off calls this again.
+pub fn resume(app: AppHandle) {
+    if app.state::<ReadOnlyMode>().enabled() || app.state::<MaintenanceMode>().enabled() {
+        return;
+    }
+    let pending: Vec<String> = load_inbox()
+        .into_iter()
+        .filter(|t| matches!(t.state, TaskState::Received | TaskState::InProgress))
+        .map(|t| t.task_id)
+        .collect();
+    if pending.is_empty() {
+        return;
+    }
+    log::info!("[task_inbox] resuming {} assigned tasks", pending.len());
+    tauri::async_runtime::spawn(async move {
+        for task_id in &pending {
+            run(&app, task_id).await;
+        }
+    });
+}
+
+/// Carry out one task and record the outcome.
+async fn run(app: &AppHandle, task_id: &str) {
+    if !RUNNING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(task_id.to_string())
+    {
+        return;
+    }
+    let outcome = match update(task_id, |t| {
+        t.state = TaskState::InProgress;
+        t.attempts += 1;
+    }) {
+        Ok(task) => {
+            emit_status(app, &task);
+            let result = dispatch(app, &task).await;
+            update(task_id, |t| match result {
+                Ok(output) => {
+                    t.state = TaskState::Done;
+                    t.result = Some(output);
+                    t.error = None;
+                }
+                Err((output, e)) => {
+                    log::warn!("[task_inbox] {} failed: {}", t.task_id, e);
+                    t.state = TaskState::Failed;
+                    t.result = output;
+                    t.error = Some(e.message);
+                }
+            })
+        }
+        Err(e) => Err(e),
+    };
+    if let Some(running) = RUNNING.lock().unwrap().as_mut() {
+        running.remove(task_id);
+    }
+    match outcome {
+        Ok(task) => emit_status(app, &task),
+        Err(e) => log::warn!("[task_inbox] can't update {}: {}", task_id, e),
+    }
+}
+
+/// A task's output, or why it failed along with what it got done.
+type Dispatched = Result<serde_json::Value, (Option<serde_json::Value>, CommandError)>;
+
+async fn dispatch(app: &AppHandle, task: &AssignedTask) -> Dispatched {
+    match task.task_type.as_str() {
+        "hash" => {
+            let paths = paths(&task.params).map_err(|e| (None, e))?;
+            let hashed = tauri::async_runtime::spawn_blocking(move || {
+                paths
+                    .iter()
+                    .map(|path| match file_read::hash(Path::new(path)) {
+                        Ok(sha256) => serde_json::json!({ "path": path, "sha256": sha256 }),
+                        Err(e) => serde_json::json!({ "path": path, "error": e.message }),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .map_err(|e| (None, CommandError::new(ErrorCode::Internal, e.to_string())))?;
+            per_file(hashed)
+        }
+        "upload" => {
+            let paths = paths(&task.params).map_err(|e| (None, e))?;
+            let metadata = task.params.get("metadata").cloned();
+            let allow_metered = task.params.get("allow_metered").and_then(|v| v.as_bool());
+            let mut uploaded = Vec::with_capacity(paths.len());
+            for path in paths {
+                let result = artifact_upload::upload_artifact(
+                    app.clone(),
+                    task.tenant_id.clone(),
+                    path.clone(),
+                    metadata.clone(),
+                    allow_metered,
+                )
+                .await;
+                uploaded.push(match result {
+                    Ok(upload) => serde_json::json!({
+                        "path": path,
+                        "upload_id": upload.upload_id,
+                        "status": upload.status,
+                        "artifact_id": upload.artifact_id,
+                    }),
+                    Err(e) => serde_json::json!({ "path": path, "error": e.message }),
+                });
+            }
+            per_file(uploaded)
+        }
+        "rescan" => {
+            let store_id = task
+                .params
+                .get("store_id")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| (None, missing_param("store_id")))?;
+            let report = stores::rescan_store(app.clone(), store_id.to_string())
+                .await
+                .map_err(|e| (None, e))?;
+            serde_json::to_value(report)
+                .map_err(|e| (None, CommandError::new(ErrorCode::Internal, e.to_string())))
+        }
+        other => Err((
+            None,
+            CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Unsupported task type: {}", other),
+            ),
+        )),
+    }
+}
+
+/// The outcome of a task over several files: done if every file was.
+fn per_file(files: Vec<serde_json::Value>) -> Dispatched {
+    let failed = files.iter().filter(|f| f.get("error").is_some()).count();
+    let total = files.len();
+    let output = serde_json::json!({ "files": files });
+    if failed == 0 {
+        return Ok(output);
+    }
+    Err((
+        Some(output),
+        CommandError::new(
+            ErrorCode::Internal,
+            format!("{} of {} files failed", failed, total),
+        ),
+    ))
+}
+
+fn paths(params: &serde_json::Value) -> CommandResult<Vec<String>> {
+    let paths: Vec<String> = params
+        .get("paths")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .ok_or_else(|| missing_param("paths"))?;
+    if paths.is_empty() {
+        return Err(missing_param("paths"));
+    }
+    Ok(paths)
+}
+
+fn missing_param(name: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::InvalidInput,
+        format!("Task is missing `{}`", name),
+    )
+    .with("param", name)
+}
+
+impl integrity::Validate for [AssignedTask] {
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .iter()
+            .find(|t| t.task_id.is_empty() || t.tenant_id.is_empty())
+        {
+            Some(task) => Err(format!("task {} has no id or tenant", task.task_type)),
+            None => Ok(()),
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn assignment(task_id: &str, task_type: &str) -> Assignment {
+        serde_json::from_value(serde_json::json!({
+            "type": "task.assign",
+            "task_id": task_id,
+            "tenant_id": "acme",
+            "task_type": task_type,
+            "params": { "paths": ["/tmp/a.txt"] },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn duplicates_are_ignored_and_unknown_types_parked() {
+        let _home = TestHome::new();
+        let (task, is_new) = insert(assignment("t-1", "hash")).unwrap();
+        assert!(is_new);
+        assert_eq!(task.state, TaskState::Received);
+
+        let (again, is_new) = insert(assignment("t-1", "upload")).unwrap();
+        assert!(!is_new);
+        assert_eq!(again.task_type, "hash");
+        assert_eq!(load_inbox().len(), 1);
+
+        let (parked, _) = insert(assignment("t-2", "transcode")).unwrap();
+        assert_eq!(parked.state, TaskState::Unsupported);
+        assert!(parked.error.is_some());
+        assert!(insert(assignment("", "hash")).is_err());
+    }
+
+    #[test]
+    fn acks_record_outcomes_only_for_unfinished_tasks() {
+        let _home = TestHome::new();
+        insert(assignment("t-1", "hash")).unwrap();
+        assert!(matches!(
+            acknowledge("t-1", None),
+            Err(e) if e.code == ErrorCode::Conflict
+        ));
+
+        let result = TaskResult {
+            output: None,
+            error: Some("disk full".into()),
+        };
+        let (task, changed) = acknowledge("t-1", Some(result)).unwrap();
+        assert!(changed);
+        assert_eq!(task.state, TaskState::Failed);
+        assert!(task.acked_at.is_some());
+        assert!(!acknowledge("t-1", None).unwrap().1);
+
+        insert(assignment("t-2", "transcode")).unwrap();
+        let (task, _) = acknowledge("t-2", None).unwrap();
+        assert_eq!(task.state, TaskState::Unsupported);
+        assert!(acknowledge("t-3", None).is_err());
+    }
+}
@@ -0,0 +1,523 @@
+// Tasks
+//
+// `extract_tasks` collects Markdown task list items (`- [ ] ...`) across a
+// vault or store, parsing the Tasks plugin's emoji dates: 📅 due,
+// ⏳ scheduled, 🛫 start, ✅ done. Code blocks and the vault's templates
+// folder are skipped. For very large vaults callers can pass a `stream_id`
+// to receive tasks in "tasks-batch" events instead of one large response.
+//
+// `set_task_state` checks a task off (or back on) in its file, guarded by the
+// line's expected text so a concurrent edit is reported instead of clobbered.
+// As a dry run (see `dry_run`) it returns the task as it would be.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::operations::{OperationHandle, OperationKind, Operations};
+use crate::text_style::{self, TextWriteOptions};
+use crate::{audit, io_throttle, notes, stores};
+
+const BATCH_SIZE: usize = 200;
+const DUE: char = '📅';
+const SCHEDULED: char = '⏳';
+const START: char = '🛫';
+const DONE: char = '✅';
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TaskOptions {
+    /// Only tasks that are neither done nor cancelled.
+    pub incomplete_only: bool,
+    /// Only tasks due before this date (`YYYY-MM-DD`).
+    pub due_before: Option<String>,
+    /// Overrides the templates folder read from the vault's settings.
+    pub templates_folder: Option<String>,
+    /// Emit "tasks-batch" events under this id instead of returning tasks.
+    pub stream_id: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Task {
+    pub path: String,
+    /// `/`-separated, relative to the scanned root.
+    pub relative_path: String,
+    /// 1-based.
+    pub line: usize,
+    pub raw: String,
+    /// The task text without its checkbox and date annotations.
+    pub description: String,
+    /// Checkbox character: ` ` open, `x` done, `-` cancelled, or a custom status.
+    pub status: char,
+    pub completed: bool,
+    pub due: Option<String>,
+    pub scheduled: Option<String>,
+    pub start: Option<String>,
+    pub completed_at: Option<String>,
+    #[serde(flatten)]
+    pub dry_run: DryRun,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TaskScan {
+    /// Empty when streamed.
+    pub tasks: Vec<Task>,
+    pub total: usize,
+    pub files_scanned: usize,
+}
+
+#[derive(Serialize, Clone)]
+struct TaskBatch {
+    stream_id: String,
+    tasks: Vec<Task>,
+    done: bool,
+}
+
+/// Position of a list item's checkbox within its line.
+pub struct Checkbox {
+    pub status: char,
+    /// Byte offset of the status character.
+    pub status_at: usize,
+    /// Byte offset where the task text starts.
+    pub body_at: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn extract_tasks(
+    app: AppHandle,
+    root_path: String,
+    options: Option<TaskOptions>,
+) -> CommandResult<TaskScan> {
+    let options = options.unwrap_or_default();
+    let due_before = options
+        .due_before
+        .as_deref()
+        .map(|raw| {
+            chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|_| {
+                CommandError::new(ErrorCode::InvalidInput, format!("Invalid date: {}", raw))
+                    .with("due_before", raw)
+            })
+        })
+        .transpose()?;
+    let root = Path::new(&root_path).to_path_buf();
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Directory not found: {}", root_path),
+        )
+        .with("path", &root_path));
+    }
+
+    let op = Operations::start_async(&app, OperationKind::TaskExtraction, &root_path, 1).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = io_throttle::scoped(op.background_io(), || {
+            scan_tasks(&app, &op, &root, &options, due_before)
+        });
+        op.finish_storing(result)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn scan_tasks(
+    app: &AppHandle,
+    op: &OperationHandle,
+    root: &Path,
+    options: &TaskOptions,
+    due_before: Option<chrono::NaiveDate>,
+) -> CommandResult<TaskScan> {
+    let mut scan = TaskScan {
+        tasks: Vec::new(),
+        total: 0,
+        files_scanned: 0,
+    };
+    let templates = options
+        .templates_folder
+        .clone()
+        .or_else(|| templates_folder(root))
+        .map(|folder| format!("{}/", folder.trim_matches('/')));
+
+    let files: Vec<String> = stores::scan_for_index(root)?
+        .into_keys()
+        .filter(|f| f.ends_with(".md"))
+        .collect();
+    let total = files.len() as u64;
+    for (i, relative) in files.into_iter().enumerate() {
+        op.check_cancelled()?;
+        op.progress("scanning", i as u64, Some(total), Some(&relative));
+        if templates.as_ref().is_some_and(|t| relative.starts_with(t)) {
+            continue;
+        }
+        let path = root.join(&relative);
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        io_throttle::charge(text.len() as u64);
+        io_throttle::file_done();
+        scan.files_scanned += 1;
+        for task in parse_tasks(&text) {
+            let task = Task {
+                path: path.to_string_lossy().to_string(),
+                relative_path: relative.clone(),
+                ..task
+            };
+            if options.incomplete_only && (task.completed || task.status == '-') {
+                continue;
+            }
+            if let Some(before) = due_before {
+                let due = task.due.as_deref().and_then(|d| d.parse().ok());
+                if due.is_none_or(|d: chrono::NaiveDate| d >= before) {
+                    continue;
+                }
+            }
+            scan.total += 1;
+            scan.tasks.push(task);
+            if let Some(id) = &options.stream_id {
+                if scan.tasks.len() >= BATCH_SIZE {
+                    emit_batch(app, id, std::mem::take(&mut scan.tasks), false);
+                }
+            }
+        }
+    }
+    if let Some(id) = &options.stream_id {
+        emit_batch(app, id, std::mem::take(&mut scan.tasks), true);
+    }
+    Ok(scan)
+}
+
+/// Check or uncheck the task on `line_number` (1-based). `expected_raw` is the
+/// line as last extracted; if the file has changed there, nothing is written
+/// and `Conflict` carries the current line. Checking adds a `✅` date
+/// (`completion_date`, default today); unchecking removes it.
+#[tauri::command]
+pub fn set_task_state(
+    window: tauri::WebviewWindow,
+    path: String,
+    line_number: usize,
+    expected_raw: String,
+    checked: bool,
+    completion_date: Option<String>,
+    dry_run: Option<bool>,
+) -> CommandResult<Task> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    let file = Path::new(&path);
+    let bytes = fs::read(file)?;
+    let style = text_style::detect(&bytes);
+    // Rejects encodings this can't write back
+    text_style::resolve(file, Some(&style), &TextWriteOptions::default())?;
+    let body = if style.has_bom {
+        &bytes[text_style::UTF8_BOM.len()..]
+    } else {
+        &bytes[..]
+    };
+    let text = std::str::from_utf8(body).map_err(|_| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not valid UTF-8 text", path),
+        )
+        .with("path", &path)
+    })?;
+
+    let mut lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let current = line_number
+        .checked_sub(1)
+        .and_then(|i| lines.get(i))
+        .map(|l| l.trim_end_matches(['\r', '\n']));
+    let conflict = |current: Option<&str>| {
+        CommandError::new(
+            ErrorCode::Conflict,
+            format!("Line {} of {} has changed", line_number, path),
+        )
+        .with("path", &path)
+        .with("line", line_number)
+        .with("current_line", current)
+    };
+    let Some(current) = current.filter(|c| *c == expected_raw) else {
+        return Err(conflict(current));
+    };
+    let checkbox = parse_checkbox(current).ok_or_else(|| conflict(Some(current)))?;
+
+    let date = match completion_date {
+        Some(raw) => chrono::NaiveDate::parse_from_str(&raw, "%Y-%m-%d").map_err(|_| {
+            CommandError::new(ErrorCode::InvalidInput, format!("Invalid date: {}", raw))
+        })?,
+        None => chrono::Local::now().date_naive(),
+    };
+    let updated = toggle_line(current, &checkbox, checked, date);
+    if dry_run {
+        let mut task = parse_task(&updated, line_number).ok_or_else(|| conflict(Some(&updated)))?;
+        task.path = path.clone();
+        task.dry_run = DryRun::planned(format!(
+            "Change line {} of {} to: {}",
+            line_number, path, updated
+        ));
+        return Ok(task);
+    }
+    let ending = &lines[line_number - 1][current.len()..];
+    let replaced = format!("{}{}", updated, ending);
+    lines[line_number - 1] = &replaced;
+
+    let mut out = Vec::with_capacity(bytes.len() + 16);
+    if style.has_bom {
+        out.extend_from_slice(text_style::UTF8_BOM);
+    }
+    out.extend_from_slice(lines.concat().as_bytes());
+    notes::write_atomic(file, &out)?;
+
+    audit::record(
+        "task_updated",
+        None,
+        serde_json::json!({ "path": path, "line": line_number, "checked": checked }),
+    );
+    let mut task = parse_task(&updated, line_number).ok_or_else(|| conflict(Some(&updated)))?;
+    task.path = path;
+    Ok(task)
+}
+
+/// `line` with its status set and the `✅` annotation added or removed.
+fn toggle_line(line: &str, checkbox: &Checkbox, checked: bool, date: chrono::NaiveDate) -> String {
+    let status = if checked { 'x' } else { ' ' };
+    let mut out = format!(
+        "{}{}{}",
+        &line[..checkbox.status_at],
+        status,
+        &line[checkbox.status_at + checkbox.status.len_utf8()..]
+    );
+    let done_at = out.find(DONE);
+    match (checked, done_at) {
+        (true, None) => {
+            let trimmed = out.trim_end().len();
+            out.truncate(trimmed);
+            out.push_str(&format!(" {} {}", DONE, date.format("%Y-%m-%d")));
+        }
+        (false, Some(at)) => {
+            // The emoji, an optional variation selector, and its date
+            let rest = out[at + DONE.len_utf8()..].trim_start_matches('\u{fe0f}');
+            let rest = rest.trim_start();
+            let after_date = rest
+                .get(..10)
+                .filter(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+                .map_or(rest, |_| &rest[10..]);
+            out = format!("{}{}", out[..at].trim_end(), after_date);
+        }
+        _ => {}
+    }
+    out
+}
+
+// ─── Parsing ────────────────────────────────────────────────────────────────
+
+/// Tasks in a note outside code blocks; paths are left for the caller.
+pub fn parse_tasks(text: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (fence, marker) {
+            (None, Some(m)) => fence = Some(m),
+            (Some(open), Some(m)) if open == m => fence = None,
+            (Some(_), _) => {}
+            (None, None) => {
+                if let Some(task) = parse_task(line, i + 1) {
+                    tasks.push(task);
+                }
+            }
+        }
+    }
+    tasks
+}
+
+fn parse_task(line: &str, number: usize) -> Option<Task> {
+    let checkbox = parse_checkbox(line)?;
+    let body = &line[checkbox.body_at..];
+    let date_after = |emoji: char| -> Option<String> {
+        let at = body.find(emoji)? + emoji.len_utf8();
+        let rest = body[at..].trim_start_matches('\u{fe0f}').trim_start();
+        let date = rest.get(..10)?;
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        Some(date.to_string())
+    };
+    let description = body
+        .find([DUE, SCHEDULED, START, DONE, '➕', '🔁', '⏫', '🔼', '🔽'])
+        .map_or(body, |at| &body[..at])
+        .trim()
+        .to_string();
+
+    Some(Task {
+        path: String::new(),
+        relative_path: String::new(),
+        line: number,
+        raw: line.to_string(),
+        description,
+        status: checkbox.status,
+        completed: matches!(checkbox.status, 'x' | 'X'),
+        due: date_after(DUE),
+        scheduled: date_after(SCHEDULED),
+        start: date_after(START),
+        completed_at: date_after(DONE),
+        dry_run: DryRun::default(),
+    })
+}
+
+/// The checkbox of a `-`, `*`, `+`, or numbered list item, if it has one.
+pub fn parse_checkbox(line: &str) -> Option<Checkbox> {
+    let indent = line.len() - line.trim_start().len();
+    let item = &line[indent..];
+    let marker = if item.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = item.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || !item[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+    let after = &item[marker..];
+    let gap = after.len() - after.trim_start().len();
+    if gap == 0 {
+        return None;
+    }
+
+    let open = indent + marker + gap;
+    let mut chars = line[open..].chars();
+    if chars.next()? != '[' {
+        return None;
+    }
+    let status = chars.next()?;
+    if chars.next()? != ']' {
+        return None;
+    }
+    let close = open + 1 + status.len_utf8();
+    let tail = &line[close + 1..];
+    if !tail.is_empty() && !tail.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(Checkbox {
+        status,
+        status_at: open + 1,
+        body_at: close + 1 + (tail.len() - tail.trim_start().len()),
+    })
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+/// Templates folder from the core Templates or Templater plugin settings.
+fn templates_folder(root: &Path) -> Option<String> {
+    let read = |relative: &str, field: &str| -> Option<String> {
+        let raw = fs::read_to_string(root.join(relative)).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&raw).ok()?;
+        let folder = json.get(field)?.as_str()?.trim_matches('/');
+        (!folder.is_empty()).then(|| folder.to_string())
+    };
+    read(".obsidian/templates.json", "folder").or_else(|| {
+        read(
+            ".obsidian/plugins/templater-obsidian/data.json",
+            "templates_folder",
+        )
+    })
+}
+
+fn emit_batch(app: &AppHandle, stream_id: &str, tasks: Vec<Task>, done: bool) {
+    let _ = app.emit(
+        "tasks-batch",
+        TaskBatch {
+            stream_id: stream_id.to_string(),
+            tasks,
+            done,
+        },
+    );
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(raw: &str) -> chrono::NaiveDate {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn emoji_dates_are_read_and_kept_out_of_the_description() {
+        let task = parse_task(
+            "- [ ] Ship it 📅 2024-03-05 ⏳ 2024-03-01 🛫 2024-02-28 #work",
+            3,
+        )
+        .unwrap();
+        assert_eq!(task.description, "Ship it");
+        assert_eq!(task.due.as_deref(), Some("2024-03-05"));
+        assert_eq!(task.scheduled.as_deref(), Some("2024-03-01"));
+        assert_eq!(task.start.as_deref(), Some("2024-02-28"));
+        assert_eq!((task.line, task.status, task.completed), (3, ' ', false));
+
+        // Emoji with a variation selector, as some keyboards insert it
+        let done = parse_task("* [X] Filed ✅\u{fe0f} 2024-03-06", 1).unwrap();
+        assert!(done.completed);
+        assert_eq!(done.completed_at.as_deref(), Some("2024-03-06"));
+        assert_eq!(done.description, "Filed");
+
+        let priority = parse_task("- [ ] Pay rent ⏫ 📅 2024-02-30", 1).unwrap();
+        assert_eq!(priority.description, "Pay rent");
+        assert_eq!(priority.due, None);
+        assert_eq!(parse_task("- [ ] Later 📅 soon", 1).unwrap().due, None);
+    }
+
+    #[test]
+    fn checkboxes_need_a_list_marker_a_gap_and_a_closed_box() {
+        let checkbox = parse_checkbox("  12) [/] in progress").unwrap();
+        assert_eq!(checkbox.status, '/');
+        assert_eq!((checkbox.status_at, checkbox.body_at), (7, 10));
+        assert_eq!(parse_checkbox("- [ ]").unwrap().body_at, 5);
+
+        for line in [
+            "-[ ] no gap",
+            "- [ ]x",
+            "- [ x] wide",
+            "1 [ ] no dot",
+            "[ ] bare",
+        ] {
+            assert!(parse_checkbox(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn tasks_in_code_blocks_are_skipped() {
+        let text = "- [ ] a\n```\n- [ ] b\n```\n~~~\n- [ ] c\n```\n- [ ] d\n~~~\n- [x] e\n";
+        let found: Vec<(usize, String)> = parse_tasks(text)
+            .into_iter()
+            .map(|t| (t.line, t.description))
+            .collect();
+        assert_eq!(found, [(1, "a".to_string()), (10, "e".to_string())]);
+    }
+
+    #[test]
+    fn toggling_adds_and_removes_the_done_date() {
+        let open = "- [ ] Ship 📅 2024-03-05  ";
+        let checkbox = parse_checkbox(open).unwrap();
+        assert_eq!(
+            toggle_line(open, &checkbox, true, date("2024-03-06")),
+            "- [x] Ship 📅 2024-03-05 ✅ 2024-03-06"
+        );
+
+        let done = "- [x] Ship ✅\u{fe0f} 2024-03-06 #later";
+        let checkbox = parse_checkbox(done).unwrap();
+        assert_eq!(
+            toggle_line(done, &checkbox, false, date("2024-03-07")),
+            "- [ ] Ship #later"
+        );
+        // Already dated: only the status changes
+        let redone = "- [ ] Ship ✅ 2024-03-06";
+        let checkbox = parse_checkbox(redone).unwrap();
+        assert_eq!(
+            toggle_line(redone, &checkbox, true, date("2024-03-07")),
+            "- [x] Ship ✅ 2024-03-06"
+        );
+    }
+}
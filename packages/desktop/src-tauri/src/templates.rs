@@ -0,0 +1,237 @@
+// Note templates
+//
+// `render_template` fills the subset of Obsidian core templates (and the
+// simplest Templater syntax) that note scaffolding needs:
+// - `{{date}}` / `{{date:FORMAT}}` and `{{time}}` / `{{time:FORMAT}}`, with
+//   moment.js formats as in Obsidian (defaults `YYYY-MM-DD` and `HH:mm`)
+// - `{{title}}` and any other `{{name}}` from the caller's variables
+// Unknown variables are left in place, or rejected in strict mode.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::store_kinds::format_moment;
+
+const DEFAULT_DATE_FORMAT: &str = "YYYY-MM-DD";
+const DEFAULT_TIME_FORMAT: &str = "HH:mm";
+
+/// `{ "path": "..." }` or `{ "content": "..." }`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSource {
+    Path(String),
+    Content(String),
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn render_template(
+    template: TemplateSource,
+    variables: Option<HashMap<String, String>>,
+    strict: Option<bool>,
+) -> CommandResult<String> {
+    let content = match template {
+        TemplateSource::Content(content) => content,
+        TemplateSource::Path(path) => fs::read_to_string(&path).map_err(|e| {
+            CommandError::new(
+                ErrorCode::NotFound,
+                format!("Can't read template {}: {}", path, e),
+            )
+            .with("path", &path)
+        })?,
+    };
+    let variables = variables.unwrap_or_default();
+    let rendered = render(&content, &variables, chrono::Local::now().naive_local());
+    if strict.unwrap_or(false) && !rendered.unknown.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Unknown template variables: {}",
+                rendered.unknown.join(", ")
+            ),
+        )
+        .with("unknown", &rendered.unknown));
+    }
+    Ok(rendered.text)
+}
+
+// ─── Rendering ──────────────────────────────────────────────────────────────
+
+pub struct Rendered {
+    pub text: String,
+    /// Variables with no value, in order of first use; left as written.
+    pub unknown: Vec<String>,
+}
+
+pub fn render(
+    template: &str,
+    variables: &HashMap<String, String>,
+    now: chrono::NaiveDateTime,
+) -> Rendered {
+    let mut text = String::with_capacity(template.len());
+    let mut unknown: Vec<String> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let placeholder = &rest[start..start + 2 + len + 2];
+        let expr = rest[start + 2..start + 2 + len].trim();
+        match resolve(expr, variables, now) {
+            Some(value) => text.push_str(&value),
+            None => {
+                let name = expr.to_string();
+                if !unknown.contains(&name) {
+                    unknown.push(name);
+                }
+                text.push_str(placeholder);
+            }
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    text.push_str(rest);
+    Rendered { text, unknown }
+}
+
+fn resolve(
+    expr: &str,
+    variables: &HashMap<String, String>,
+    now: chrono::NaiveDateTime,
+) -> Option<String> {
+    let (name, format) = match expr.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format.trim())),
+        None => (expr, None),
+    };
+    let format_now = |default: &str| {
+        let format = format.filter(|f| !f.is_empty()).unwrap_or(default);
+        format_moment(now, format)
+    };
+    match name {
+        // Caller values win over the built-ins, except for formatted dates
+        _ if format.is_none() && variables.contains_key(name) => variables.get(name).cloned(),
+        "date" => Some(format_now(DEFAULT_DATE_FORMAT)),
+        "time" => Some(format_now(DEFAULT_TIME_FORMAT)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_noon() -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_opt(12, 7, 0)
+            .unwrap()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn fills_dates_times_and_variables() {
+        let rendered = render(
+            "# {{title}}\n{{date}} {{time}} {{ date:dddd, D MMMM YYYY }}",
+            &vars(&[("title", "Standup")]),
+            at_noon(),
+        );
+        assert_eq!(
+            rendered.text,
+            "# Standup\n2024-03-05 12:07 Tuesday, 5 March 2024"
+        );
+        assert!(rendered.unknown.is_empty());
+    }
+
+    #[test]
+    fn caller_values_replace_plain_dates_only() {
+        let rendered = render(
+            "{{date}} / {{date:YYYY}}",
+            &vars(&[("date", "someday")]),
+            at_noon(),
+        );
+        assert_eq!(rendered.text, "someday / 2024");
+    }
+
+    #[test]
+    fn unknown_variables_stay_as_written_and_are_listed_once() {
+        let rendered = render(
+            "{{who}} met {{ who }} and {{what}}",
+            &HashMap::new(),
+            at_noon(),
+        );
+        assert_eq!(rendered.text, "{{who}} met {{ who }} and {{what}}");
+        assert_eq!(rendered.unknown, ["who", "what"]);
+    }
+
+    #[test]
+    fn an_unclosed_placeholder_is_left_alone() {
+        let rendered = render("{{title}} {{date", &vars(&[("title", "T")]), at_noon());
+        assert_eq!(rendered.text, "T {{date");
+    }
+
+    fn at(date: (i32, u32, u32), time: (u32, u32, u32)) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(time.0, time.1, time.2)
+            .unwrap()
+    }
+
+    #[test]
+    fn date_formats_match_obsidian_output() {
+        // Expected values are what Obsidian (moment.js, en) renders
+        let cases = [
+            ("YYYY-MM-DD", "2024-03-05"),
+            ("dddd, MMMM Do YYYY", "Tuesday, March 5th 2024"),
+            ("ddd D MMM YY", "Tue 5 Mar 24"),
+            ("dd d", "Tu 2"),
+            ("DDDD DDD", "065 65"),
+            ("GGGG-[W]WW", "2024-W10"),
+            ("YYYY-MM-DD HH:mm:ss", "2024-03-05 12:07:09"),
+            ("h:mm A", "12:07 PM"),
+            ("[Day] D [of] YYYY", "Day 5 of 2024"),
+            ("M/D 100%", "3/5 100%"),
+        ];
+        let when = at((2024, 3, 5), (12, 7, 9));
+        for (format, expected) in cases {
+            assert_eq!(format_moment(when, format), expected, "{}", format);
+        }
+        assert_eq!(
+            format_moment(at((2024, 1, 1), (9, 5, 0)), "DDDD, h:mm a"),
+            "001, 9:05 am"
+        );
+    }
+
+    #[test]
+    fn ordinal_days_follow_english_suffixes() {
+        let cases = [
+            (1, "1st"),
+            (2, "2nd"),
+            (3, "3rd"),
+            (4, "4th"),
+            (11, "11th"),
+            (12, "12th"),
+            (13, "13th"),
+            (21, "21st"),
+            (22, "22nd"),
+            (23, "23rd"),
+            (31, "31st"),
+        ];
+        for (day, expected) in cases {
+            let rendered = render(
+                "{{date:Do}}",
+                &HashMap::new(),
+                at((2024, 1, day), (0, 0, 0)),
+            );
+            assert_eq!(rendered.text, expected);
+        }
+    }
+}
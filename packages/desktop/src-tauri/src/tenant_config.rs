@@ -0,0 +1,66 @@
+// Tenant configuration
+//
+// Per-tenant choices that belong with the tenant rather than the device-wide
+// settings, persisted as `tenants/<tenant>/config.json` in the data
+// directory. Like `Settings`, every field has a default so older files load
+// cleanly.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::error::CommandResult;
+use crate::redaction::RedactionConfig;
+use crate::tenant_windows;
+use crate::workspaces::Workspace;
+
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TenantConfig {
+    /// What the agent may do with each WhatsApp chat, by chat id.
+    pub whatsapp_chats: BTreeMap<String, ChatPolicy>,
+    /// Rules for `redaction`, used when the tenant's policy redacts.
+    pub redaction: RedactionConfig,
+    pub workspaces: Vec<Workspace>,
+    /// Id of the workspace the tenant last switched to.
+    pub active_workspace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatPolicy {
+    Allow,
+    Deny,
+    /// The agent may read the chat to summarize it but not reply or act.
+    SummarizeOnly,
+}
+
+fn config_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?.join("config.json"))
+}
+
+pub fn load(tenant_id: &str) -> TenantConfig {
+    config_path(tenant_id)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Load, change, and save under a lock so concurrent updates don't clobber
+/// each other.
+pub fn update(tenant_id: &str, change: impl FnOnce(&mut TenantConfig)) -> Result<(), String> {
+    let _guard = CONFIG_LOCK.lock().unwrap();
+    let mut config = load(tenant_id);
+    change(&mut config);
+    let path = config_path(tenant_id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(path, raw).map_err(|e| e.to_string())
+}
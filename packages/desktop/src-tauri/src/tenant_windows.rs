@@ -0,0 +1,250 @@
+// Tenant windows
+//
+// Agencies work across client tenants, so each tenant gets its own window
+// (`tenant-<id>`) with its own webview profile under
+// `tenants/<id>/webview` in the data directory. Provider logins opened from a tenant
+// window go to that tenant's session storage without the frontend passing
+// the id, and can't be pointed at another tenant.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::trash::{self, TrashEntry, TrashKind};
+use crate::{audit, dry_run, i18n, platform, sessions};
+
+const TENANT_PREFIX: &str = "tenant-";
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowKind {
+    Main,
+    Tenant,
+    Login,
+    Other,
+}
+
+#[derive(Serialize, Clone)]
+pub struct OpenWindow {
+    pub label: String,
+    pub kind: WindowKind,
+    pub tenant_id: Option<String>,
+    pub title: Option<String>,
+    pub focused: bool,
+    pub visible: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct TenantWindowClosed {
+    tenant_id: String,
+    /// Other windows (e.g. logins) still open for the tenant.
+    remaining: usize,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Open (or focus) the window for a tenant. Returns its label.
+#[tauri::command]
+pub fn open_tenant_window(app: AppHandle, tenant_id: String) -> CommandResult<String> {
+    platform::desktop_only("tenant_windows")?;
+    sessions::validate_id("tenant_id", &tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    let label = tenant_label(&tenant_id);
+    if let Some(window) = app.get_webview_window(&label) {
+        focus(&window)?;
+        return Ok(label);
+    }
+
+    let url = WebviewUrl::App(PathBuf::from(format!("index.html?tenant={}", tenant_id)));
+    let mut builder = WebviewWindowBuilder::new(&app, &label, url)
+        .title(i18n::text("window.tenant", &[("tenant_id", &tenant_id)]))
+        .data_directory(tenant_webview_dir(&tenant_id)?)
+        .data_store_identifier(data_store_id(&label));
+    #[cfg(desktop)]
+    {
+        builder = builder.inner_size(1200.0, 800.0);
+    }
+    let window = builder
+        .build()
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+
+    let handle = app.clone();
+    let closed_tenant = tenant_id.clone();
+    window.on_window_event(move |event| {
+        if let tauri::WindowEvent::Destroyed = event {
+            on_tenant_window_closed(&handle, &closed_tenant);
+        }
+    });
+
+    log::info!("[windows] opened {}", label);
+    Ok(label)
+}
+
+#[tauri::command]
+pub fn list_open_windows(app: AppHandle) -> Vec<OpenWindow> {
+    let mut windows: Vec<OpenWindow> = app
+        .webview_windows()
+        .into_iter()
+        .map(|(label, window)| {
+            let (kind, tenant_id) = classify(&label);
+            OpenWindow {
+                kind,
+                tenant_id,
+                title: window.title().ok(),
+                focused: window.is_focused().unwrap_or(false),
+                visible: window.is_visible().unwrap_or(false),
+                label,
+            }
+        })
+        .collect();
+    windows.sort_by(|a, b| a.label.cmp(&b.label));
+    windows
+}
+
+#[tauri::command]
+pub fn focus_tenant_window(app: AppHandle, tenant_id: String) -> CommandResult<()> {
+    let window = app
+        .get_webview_window(&tenant_label(&tenant_id))
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::NotFound,
+                format!("No window open for tenant {}", tenant_id),
+            )
+            .with("tenant_id", &tenant_id)
+        })?;
+    focus(&window)
+}
+
+/// Close the tenant's windows and move its sessions and webview profile to
+/// the trash. Generic secrets stay in the keychain until deleted explicitly.
+#[tauri::command]
+pub fn delete_tenant(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    tenant_id: String,
+    dry_run: Option<bool>,
+) -> CommandResult<TrashEntry> {
+    let dry_run = dry_run::requested(window.label(), Some(&tenant_id), dry_run);
+    sessions::validate_id("tenant_id", &tenant_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    let sessions_dir = sessions::sessions_root()?.join(&tenant_id);
+    let tenant_dir = tenant_dir(&tenant_id)?;
+    if !sessions_dir.exists() && !tenant_dir.exists() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("No data for tenant {}", tenant_id),
+        )
+        .with("tenant_id", &tenant_id));
+    }
+    if dry_run {
+        return trash::plan_trash(TrashKind::Tenant, &tenant_id, &[sessions_dir, tenant_dir]);
+    }
+
+    // The webview profile is in use while any of the tenant's windows is open
+    for (label, window) in app.webview_windows() {
+        if classify(&label).1.as_deref() == Some(tenant_id.as_str()) {
+            let _ = window.destroy();
+        }
+    }
+
+    let entry = trash::move_to_trash(TrashKind::Tenant, &tenant_id, &[sessions_dir, tenant_dir])?;
+    audit::record(
+        "tenant_deleted",
+        Some(&tenant_id),
+        serde_json::json!({ "trash_entry": entry.id }),
+    );
+    Ok(entry)
+}
+
+// ─── Routing ────────────────────────────────────────────────────────────────
+
+/// The tenant a command acts for. Calls from a tenant window are pinned to
+/// that tenant; other windows must name one.
+pub fn resolve_tenant(caller_label: &str, requested: Option<String>) -> CommandResult<String> {
+    match (caller_label.strip_prefix(TENANT_PREFIX), requested) {
+        (Some(own), Some(requested)) if own != requested => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Window for tenant {} cannot act for tenant {}",
+                own, requested
+            ),
+        )
+        .with("tenant_id", requested)),
+        (Some(own), _) => Ok(own.to_string()),
+        (None, Some(requested)) => Ok(requested),
+        (None, None) => Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "tenant_id is required outside a tenant window",
+        )),
+    }
+}
+
+/// The tenant a window belongs to: its tenant window or one of its logins.
+pub fn window_tenant(label: &str) -> Option<String> {
+    classify(label).1
+}
+
+/// Stable per-profile id for WKWebView, which ignores `data_directory`.
+pub fn data_store_id(key: &str) -> [u8; 16] {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(key.as_bytes());
+    let mut id = [0u8; 16];
+    id.copy_from_slice(&digest[..16]);
+    id
+}
+
+fn tenant_label(tenant_id: &str) -> String {
+    format!("{}{}", TENANT_PREFIX, tenant_id)
+}
+
+pub fn tenant_dir(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("tenants").join(tenant_id))
+}
+
+fn tenant_webview_dir(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_dir(tenant_id)?.join("webview"))
+}
+
+fn classify(label: &str) -> (WindowKind, Option<String>) {
+    if label == "main" {
+        return (WindowKind::Main, None);
+    }
+    if let Some(tenant) = label.strip_prefix(TENANT_PREFIX) {
+        return (WindowKind::Tenant, Some(tenant.to_string()));
+    }
+    if let Some(rest) = label.strip_prefix("login-") {
+        // login-<tenant>-<provider>; provider ids never contain '-'
+        let tenant = rest.rsplit_once('-').map(|(t, _)| t.to_string());
+        return (WindowKind::Login, tenant);
+    }
+    (WindowKind::Other, None)
+}
+
+fn focus(window: &tauri::WebviewWindow) -> CommandResult<()> {
+    let internal = |e: tauri::Error| CommandError::new(ErrorCode::Internal, e.to_string());
+    window.unminimize().map_err(internal)?;
+    window.show().map_err(internal)?;
+    window.set_focus().map_err(internal)
+}
+
+fn on_tenant_window_closed(app: &AppHandle, tenant_id: &str) {
+    let remaining = app
+        .webview_windows()
+        .keys()
+        .filter(|label| classify(label).1.as_deref() == Some(tenant_id))
+        .count();
+    log::info!(
+        "[windows] closed window for {} ({} remaining)",
+        tenant_id,
+        remaining
+    );
+    let _ = app.emit(
+        "tenant-window-closed",
+        TenantWindowClosed {
+            tenant_id: tenant_id.to_string(),
+            remaining,
+        },
+    );
+}
@@ -0,0 +1,397 @@
+// Test support
+//
+// `TestHome` points `HOME`, and the XDG base directories, at a temporary
+// directory, so every data directory belongs to one test. `new` starts with
+// a `~/.agentvbx`, the legacy layout where all roots are that folder;
+// `platform` starts without one, so the platform directories are used. The environment is
+// process-wide, so tests holding a `TestHome` run one at a time; tests that
+// only exercise pure functions don't need one.
+//
+// Fixture homes are described in TOML, with paths relative to the home:
+// - `[settings]`: written to `config.json` as-is.
+// - `[[vault]]`: `path` plus `files`. The `.obsidian` folder is added.
+// - `[[store]]`: `name`, `path`, and `files`, connected with `register_store`.
+// - `[[session]]`: `tenant` and `provider`. `layout = "legacy"` makes the flat
+//   `<tenant>_<provider>` directory that predates the nested layout.
+// `files` maps relative paths to contents. Shared fixtures live in
+// `fixtures/homes`. `MockServer` stands in for a remote HTTP server.
+//
+// The benchmarks need fixtures too large to write out, and get them from the
+// builders under "Synthetic fixtures", which produce the same tree on every
+// run.
+//
+// Built for this crate's tests, and with the `testing` feature for other
+// crates. Those get the command logic through the functions at the bottom,
+// which need no running app and return what the frontend would receive.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{sessions, stores};
+
+pub const BASIC_FIXTURE: &str = include_str!("../fixtures/homes/basic.toml");
+
+static HOME_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Fixture {
+    settings: Option<toml::Table>,
+    vault: Vec<VaultFixture>,
+    store: Vec<StoreFixture>,
+    session: Vec<SessionFixture>,
+}
+
+#[derive(Deserialize)]
+struct VaultFixture {
+    path: String,
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct StoreFixture {
+    name: String,
+    path: String,
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct SessionFixture {
+    tenant: String,
+    provider: String,
+    #[serde(default)]
+    layout: SessionLayout,
+}
+
+#[derive(Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SessionLayout {
+    #[default]
+    Nested,
+    Legacy,
+}
+
+// ─── Home ───────────────────────────────────────────────────────────────────
+
+/// Environment variables pointed into the home, and their paths in it.
+const HOME_VARS: &[(&str, &str)] = &[
+    ("HOME", ""),
+    ("XDG_CONFIG_HOME", ".config"),
+    ("XDG_DATA_HOME", ".local/share"),
+    ("XDG_CACHE_HOME", ".cache"),
+    ("XDG_STATE_HOME", ".local/state"),
+];
+
+/// A temporary home directory, removed and unset on drop.
+pub struct TestHome {
+    dir: tempfile::TempDir,
+    previous: Vec<Option<OsString>>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestHome {
+    /// A home with everything in `~/.agentvbx`.
+    pub fn new() -> Self {
+        let home = Self::platform();
+        fs::create_dir_all(home.agentvbx()).expect("create agentvbx home");
+        home
+    }
+
+    /// A home without `~/.agentvbx`, using the platform directories.
+    pub fn platform() -> Self {
+        // A failed test poisons the lock; the next one still gets a fresh home
+        let lock = HOME_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().expect("create temp home");
+        let previous = HOME_VARS
+            .iter()
+            .map(|(var, relative)| {
+                let previous = std::env::var_os(var);
+                match *relative {
+                    "" => std::env::set_var(var, dir.path()),
+                    relative => std::env::set_var(var, dir.path().join(relative)),
+                }
+                previous
+            })
+            .collect();
+        Self {
+            dir,
+            previous,
+            _lock: lock,
+        }
+    }
+
+    /// A home scaffolded from a TOML fixture.
+    pub fn with_fixture(fixture: &str) -> Self {
+        let home = Self::new();
+        home.scaffold(fixture);
+        home
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn join(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    /// `~/.agentvbx` inside this home.
+    pub fn agentvbx(&self) -> PathBuf {
+        self.join(".agentvbx")
+    }
+
+    pub fn write(&self, relative: &str, contents: &str) -> PathBuf {
+        let path = self.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("create fixture directory");
+        }
+        fs::write(&path, contents).expect("write fixture file");
+        path
+    }
+
+    /// Add a fixture's settings, vaults, stores, and sessions to this home.
+    pub fn scaffold(&self, fixture: &str) {
+        let fixture: Fixture = toml::from_str(fixture).expect("parse fixture");
+
+        if let Some(settings) = fixture.settings {
+            let json = serde_json::to_vec_pretty(&settings).expect("serialize settings");
+            fs::write(crate::settings::settings_path().unwrap(), json).expect("write settings");
+        }
+        for vault in &fixture.vault {
+            fs::create_dir_all(self.join(&vault.path).join(".obsidian")).expect("create vault");
+            self.write_files(&vault.path, &vault.files);
+        }
+        for store in &fixture.store {
+            let root = self.join(&store.path);
+            fs::create_dir_all(&root).expect("create store");
+            self.write_files(&store.path, &store.files);
+            stores::register_store(store.name.clone(), root.to_string_lossy().to_string(), None)
+                .expect("register store");
+        }
+        let root = sessions::sessions_root().unwrap();
+        for session in &fixture.session {
+            match session.layout {
+                SessionLayout::Nested => {
+                    sessions::ensure_session_dir_at(&root, &session.tenant, &session.provider)
+                        .expect("create session");
+                }
+                SessionLayout::Legacy => {
+                    // Webview data as the old layout kept it, directly in the dir
+                    let dir = root.join(format!("{}_{}", session.tenant, session.provider));
+                    fs::create_dir_all(&dir).expect("create legacy session");
+                    fs::write(dir.join("Cookies"), "").expect("write legacy session");
+                }
+            }
+        }
+    }
+
+    fn write_files(&self, base: &str, files: &BTreeMap<String, String>) {
+        for (relative, contents) in files {
+            self.write(&format!("{}/{}", base, relative), contents);
+        }
+    }
+}
+
+impl Default for TestHome {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TestHome {
+    fn drop(&mut self) {
+        for ((var, _), previous) in HOME_VARS.iter().zip(&self.previous) {
+            match previous {
+                Some(value) => std::env::set_var(var, value),
+                None => std::env::remove_var(var),
+            }
+        }
+    }
+}
+
+// ─── Mock server ────────────────────────────────────────────────────────────
+
+/// An HTTP server on a free local port that answers with canned responses in
+/// order, repeating the last one. Runs until the test process exits.
+pub struct MockServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start(responses: Vec<String>) -> Self {
+        assert!(!responses.is_empty(), "a mock server needs a response");
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind mock server");
+        let port = listener.local_addr().expect("mock server address").port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                // Headers only; test requests carry no body
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                    head.push(byte[0]);
+                }
+                let request_line = String::from_utf8_lossy(&head)
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+                seen.lock().unwrap().push(request_line);
+                let response = &responses[i.min(responses.len() - 1)];
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { port, requests }
+    }
+
+    /// A raw response with `Content-Length` set and the connection closed.
+    pub fn response(status: u16, headers: &[(&str, &str)], body: &str) -> String {
+        let mut raw = format!("HTTP/1.1 {} Mock\r\n", status);
+        for (name, value) in headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        raw.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        raw
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    /// Request lines received so far, such as `GET /api/health HTTP/1.1`.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+// ─── Synthetic fixtures ─────────────────────────────────────────────────────
+
+const WORDS: &[&str] = &[
+    "alpha", "Bravo", "charlie", "Delta", "echo", "Foxtrot", "golf", "Hotel",
+];
+
+/// `count` entries directly in `dir`: every tenth a directory, the rest
+/// empty files with mixed-case names and a mix of extensions.
+pub fn wide_directory(dir: &Path, count: usize) {
+    fs::create_dir_all(dir).expect("create directory");
+    for i in 0..count {
+        let word = WORDS[i % WORDS.len()];
+        if i % 10 == 0 {
+            fs::create_dir(dir.join(format!("{}-{:05}", word, i))).expect("create subdirectory");
+        } else {
+            let ext = ["md", "pdf", "png", "txt"][i % 4];
+            fs::write(dir.join(format!("{}-{:05}.{}", word, i, ext)), "").expect("write file");
+        }
+    }
+}
+
+/// An Obsidian vault at `dir` with `notes` notes in two levels of folders,
+/// an attachment for every fifth note, and a `.obsidian` folder.
+pub fn synthetic_vault(dir: &Path, notes: usize) {
+    fs::create_dir_all(dir.join(".obsidian")).expect("create vault");
+    for i in 0..notes {
+        let folder = dir
+            .join(format!("area-{}", i % 10))
+            .join(format!("topic-{}", i % 50));
+        fs::create_dir_all(&folder).expect("create vault folder");
+        let word = WORDS[i % WORDS.len()];
+        let body = format!(
+            "# {} {}\n\nSee [[{}-{:05}]] and #tag{}.\n",
+            word,
+            i,
+            WORDS[(i + 1) % WORDS.len()],
+            i + 1,
+            i % 7
+        );
+        fs::write(folder.join(format!("{}-{:05}.md", word, i)), body).expect("write note");
+        if i % 5 == 0 {
+            fs::write(folder.join(format!("image-{:05}.png", i)), [0u8; 64])
+                .expect("write attachment");
+        }
+    }
+}
+
+/// A file of `len` bytes that takes no space on filesystems with sparse
+/// files; it reads back as zeros.
+pub fn sparse_file(path: &Path, len: u64) {
+    fs::File::create(path)
+        .and_then(|f| f.set_len(len))
+        .expect("create sparse file");
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+fn to_json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).expect("serialize command result")
+}
+
+pub fn list_directory(path: &Path) -> Result<serde_json::Value, String> {
+    let collator = crate::collation::Collator::new(Default::default(), true, "en");
+    crate::list_entries(path, &collator).map(to_json)
+}
+
+/// `list_directory` with `include_snippets`.
+pub fn list_directory_with_snippets(path: &Path) -> Result<serde_json::Value, String> {
+    let collator = crate::collation::Collator::new(Default::default(), true, "en");
+    let mut entries = crate::list_entries(path, &collator)?;
+    crate::snippets::attach(&mut entries, None);
+    Ok(to_json(entries))
+}
+
+/// Vaults under `roots`, as `discover_obsidian_vaults` reports them.
+pub fn discover_vaults(roots: &[PathBuf]) -> Result<serde_json::Value, serde_json::Value> {
+    let roots = roots
+        .iter()
+        .map(|r| r.to_string_lossy().to_string())
+        .collect();
+    crate::vaults_under(roots, |_, _| Ok(()))
+        .map(to_json)
+        .map_err(to_json)
+}
+
+pub fn guess_mime(filename: &str) -> String {
+    crate::guess_mime(filename)
+}
+
+/// The `note_count` vault discovery reports for `vault`.
+pub fn count_markdown_files(vault: &Path) -> usize {
+    crate::note_counts::count(vault)
+}
+
+pub fn hash_file(path: &Path) -> Result<String, serde_json::Value> {
+    crate::file_read::hash(path).map_err(to_json)
+}
+
+/// Build the snapshot a vault store is indexed with; returns its file count.
+pub fn index_vault(vault: &Path) -> usize {
+    stores::scan(vault, crate::store_kinds::StoreKind::ObsidianVault).len()
+}
+
+pub fn list_stores() -> serde_json::Value {
+    to_json(stores::list_stores())
+}
+
+pub fn list_sessions() -> serde_json::Value {
+    to_json(sessions::list_sessions())
+}
+
+pub fn migrate_sessions_layout() -> Result<serde_json::Value, String> {
+    sessions::migrate_sessions_layout().map(to_json)
+}
@@ -0,0 +1,250 @@
+// Text style preservation
+//
+// Vaults synced from Windows are often CRLF, sometimes with a UTF-8 BOM.
+// Rewriting them as LF makes sync tools report every line as changed, so
+// writes detect the existing file's dominant line ending and BOM and keep
+// them unless the caller overrides. New files default to LF without a BOM.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16le,
+    #[serde(rename = "utf-16be")]
+    Utf16be,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TextStyle {
+    /// Dominant line ending; `None` when the file has no line breaks.
+    pub line_ending: Option<LineEnding>,
+    /// Both endings occur in the file.
+    pub mixed_line_endings: bool,
+    pub has_bom: bool,
+    pub encoding: TextEncoding,
+}
+
+/// Explicit choices for a write; unset fields follow the existing file.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TextWriteOptions {
+    pub line_ending: Option<LineEnding>,
+    pub bom: Option<bool>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn detect_text_style(path: String) -> CommandResult<TextStyle> {
+    detect_file(Path::new(&path))?.ok_or_else(|| {
+        CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+            .with("path", &path)
+    })
+}
+
+// ─── Detection ──────────────────────────────────────────────────────────────
+
+pub fn detect(bytes: &[u8]) -> TextStyle {
+    let (encoding, has_bom) = if bytes.starts_with(UTF8_BOM) {
+        (TextEncoding::Utf8, true)
+    } else if bytes.starts_with(b"\xFF\xFE") {
+        (TextEncoding::Utf16le, true)
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        (TextEncoding::Utf16be, true)
+    } else {
+        (TextEncoding::Utf8, false)
+    };
+
+    let crlf = bytes.windows(2).filter(|w| w == b"\r\n").count();
+    let lf = bytes.iter().filter(|&&b| b == b'\n').count() - crlf;
+    let line_ending = match (crlf, lf) {
+        (0, 0) => None,
+        (crlf, lf) if crlf > lf => Some(LineEnding::Crlf),
+        _ => Some(LineEnding::Lf),
+    };
+    TextStyle {
+        line_ending,
+        mixed_line_endings: crlf > 0 && lf > 0,
+        has_bom,
+        encoding,
+    }
+}
+
+/// Style of the file at `path`, or `None` if it doesn't exist.
+pub fn detect_file(path: &Path) -> CommandResult<Option<TextStyle>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(detect(&bytes))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Decode file contents in their detected encoding, dropping the BOM.
+/// Invalid sequences become U+FFFD.
+pub fn decode(bytes: &[u8], style: &TextStyle) -> String {
+    let units = |to_u16: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|pair| to_u16([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match style.encoding {
+        TextEncoding::Utf16le => units(u16::from_le_bytes),
+        TextEncoding::Utf16be => units(u16::from_be_bytes),
+        TextEncoding::Utf8 => {
+            let body = if style.has_bom {
+                &bytes[UTF8_BOM.len()..]
+            } else {
+                bytes
+            };
+            String::from_utf8_lossy(body).into_owned()
+        }
+    }
+}
+
+// ─── Writing ────────────────────────────────────────────────────────────────
+
+/// Line ending and BOM to write with: overrides first, then the existing
+/// file's style, then LF without a BOM.
+pub fn resolve(
+    path: &Path,
+    existing: Option<&TextStyle>,
+    options: &TextWriteOptions,
+) -> CommandResult<(LineEnding, bool)> {
+    if let Some(style) = existing {
+        if style.encoding != TextEncoding::Utf8 {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!(
+                    "{} is {:?}; only UTF-8 is written",
+                    path.display(),
+                    style.encoding
+                ),
+            )
+            .with("path", path.to_string_lossy())
+            .with("encoding", style.encoding));
+        }
+    }
+    let ending = options
+        .line_ending
+        .or(existing.and_then(|s| s.line_ending))
+        .unwrap_or(LineEnding::Lf);
+    let bom = options.bom.unwrap_or(existing.is_some_and(|s| s.has_bom));
+    Ok((ending, bom))
+}
+
+/// `text` with every line break converted to `ending`.
+pub fn normalize(text: &str, ending: LineEnding) -> String {
+    let lf = text.replace("\r\n", "\n");
+    match ending {
+        LineEnding::Lf => lf,
+        LineEnding::Crlf => lf.replace('\n', "\r\n"),
+    }
+}
+
+/// Full file contents for `text` in the given style.
+pub fn encode(text: &str, ending: LineEnding, bom: bool) -> Vec<u8> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut bytes = Vec::with_capacity(text.len() + UTF8_BOM.len());
+    if bom {
+        bytes.extend_from_slice(UTF8_BOM);
+    }
+    bytes.extend_from_slice(normalize(text, ending).as_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crlf_and_bom_survive_a_round_trip() {
+        let original = b"\xEF\xBB\xBFtitle\r\nbody\r\n";
+        let style = detect(original);
+        assert_eq!(style.line_ending, Some(LineEnding::Crlf));
+        assert!(style.has_bom && !style.mixed_line_endings);
+
+        let text = decode(original, &style);
+        assert_eq!(text, "title\r\nbody\r\n");
+        // An editor hands back LF text
+        let edited = text.replace("\r\n", "\n") + "more\n";
+        let (ending, bom) = resolve(
+            Path::new("note.md"),
+            Some(&style),
+            &TextWriteOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            encode(&edited, ending, bom),
+            b"\xEF\xBB\xBFtitle\r\nbody\r\nmore\r\n"
+        );
+    }
+
+    #[test]
+    fn overrides_win_over_the_existing_style() {
+        let style = detect(b"\xEF\xBB\xBFa\r\nb\r\n");
+        let options = TextWriteOptions {
+            line_ending: Some(LineEnding::Lf),
+            bom: Some(false),
+        };
+        let (ending, bom) = resolve(Path::new("note.md"), Some(&style), &options).unwrap();
+        assert_eq!(encode("a\r\nb\r\n", ending, bom), b"a\nb\n");
+    }
+
+    #[test]
+    fn new_files_default_to_lf_without_bom() {
+        let (ending, bom) =
+            resolve(Path::new("new.md"), None, &TextWriteOptions::default()).unwrap();
+        assert_eq!((ending, bom), (LineEnding::Lf, false));
+    }
+
+    #[test]
+    fn mixed_endings_follow_the_majority() {
+        let style = detect(b"a\r\nb\nc\n");
+        assert_eq!(style.line_ending, Some(LineEnding::Lf));
+        assert!(style.mixed_line_endings);
+        assert_eq!(detect(b"no breaks").line_ending, None);
+    }
+
+    #[test]
+    fn utf16_is_decoded_but_never_written() {
+        let bytes = b"\xFF\xFEh\0i\0";
+        let style = detect(bytes);
+        assert_eq!(style.encoding, TextEncoding::Utf16le);
+        assert_eq!(decode(bytes, &style), "hi");
+        let err = resolve(
+            Path::new("wide.txt"),
+            Some(&style),
+            &TextWriteOptions::default(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+    }
+}
@@ -0,0 +1,349 @@
+// Trash
+//
+// Destructive operations on AGENTVBX data move it to
+// `trash/<timestamp>-<kind>-<id>/` in the data directory instead of deleting
+// it. Each entry holds the moved directories under `items/` and an
+// `entry.json` recording where they came from, so a restore is a rename
+// back. Entries older than `trash_retention_days` are emptied by the daily
+// `trash_retention` job. Deleting, restoring, and emptying can be dry runs
+// (see `dry_run`), which report the entry they would have made or moved.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::audit;
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::scheduler::{JobLoad, Schedule, Scheduler};
+use crate::sessions;
+use crate::settings;
+
+const ENTRY_FILE: &str = "entry.json";
+const ITEMS_DIR: &str = "items";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Session,
+    Tenant,
+    /// Data replaced by restoring a backup.
+    Restore,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashItem {
+    pub original_path: String,
+    /// Name of the moved directory under the entry's `items/`.
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashEntry {
+    pub id: String,
+    pub kind: TrashKind,
+    /// What was deleted, e.g. `acme/claude` for a session.
+    pub label: String,
+    pub trashed_at: String,
+    pub items: Vec<TrashItem>,
+    /// Computed when listing; not persisted.
+    #[serde(default, skip_deserializing)]
+    pub size_bytes: u64,
+    #[serde(flatten, default)]
+    pub dry_run: DryRun,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct EmptyTrashReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+    #[serde(flatten)]
+    pub dry_run: DryRun,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Trash entries, newest first.
+#[tauri::command]
+pub fn list_trash() -> Vec<TrashEntry> {
+    let Ok(root) = trash_root() else {
+        return Vec::new();
+    };
+    let mut entries: Vec<TrashEntry> = fs::read_dir(&root)
+        .map(|dirs| {
+            dirs.flatten()
+                .filter_map(|dir| read_entry(&dir.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    for entry in &mut entries {
+        entry.size_bytes = dir_size(&root.join(&entry.id));
+    }
+    entries.sort_by(|a, b| b.trashed_at.cmp(&a.trashed_at));
+    entries
+}
+
+/// Move a trash entry back where it came from. Refuses with `Conflict` when
+/// something newer than the deletion now lives there, unless `force`.
+#[tauri::command]
+pub fn restore_from_trash(
+    window: tauri::WebviewWindow,
+    entry_id: String,
+    force: Option<bool>,
+    dry_run: Option<bool>,
+) -> CommandResult<TrashEntry> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    restore(&entry_id, force.unwrap_or(false), dry_run)
+}
+
+/// Permanently delete entries trashed more than `older_than_days` ago, or
+/// everything when omitted.
+#[tauri::command]
+pub fn empty_trash(
+    window: tauri::WebviewWindow,
+    older_than_days: Option<u32>,
+    dry_run: Option<bool>,
+) -> CommandResult<EmptyTrashReport> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    empty(older_than_days, dry_run)
+}
+
+// ─── Restoring ──────────────────────────────────────────────────────────────
+
+pub fn restore(entry_id: &str, force: bool, dry_run: bool) -> CommandResult<TrashEntry> {
+    sessions::validate_id("entry_id", entry_id)
+        .map_err(|e| CommandError::new(ErrorCode::InvalidInput, e))?;
+    let dir = trash_root()?.join(entry_id);
+    let mut entry = read_entry(&dir).ok_or_else(|| {
+        CommandError::new(ErrorCode::NotFound, format!("No trash entry {}", entry_id))
+            .with("entry_id", entry_id)
+    })?;
+    let trashed_at = chrono::DateTime::parse_from_rfc3339(&entry.trashed_at)
+        .map(SystemTime::from)
+        .map_err(|e| format!("Corrupt trash entry {}: {}", entry_id, e))?;
+
+    // Check every item before moving any, so a conflict leaves nothing half-restored
+    for item in &entry.items {
+        let target = Path::new(&item.original_path);
+        if !force && target.exists() && newest_modification(target) > trashed_at {
+            return Err(CommandError::new(
+                ErrorCode::Conflict,
+                format!("{} has changed since it was deleted", item.original_path),
+            )
+            .with("path", &item.original_path));
+        }
+    }
+    if dry_run {
+        entry.size_bytes = dir_size(&dir);
+        let targets: Vec<&str> = entry
+            .items
+            .iter()
+            .map(|i| i.original_path.as_str())
+            .collect();
+        entry.dry_run =
+            DryRun::planned(format!("Restore {} to {}", entry.label, targets.join(", ")));
+        return Ok(entry);
+    }
+    for item in &entry.items {
+        let target = Path::new(&item.original_path);
+        if target.exists() {
+            fs::remove_dir_all(target)?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(dir.join(ITEMS_DIR).join(&item.name), target)?;
+    }
+    fs::remove_dir_all(&dir)?;
+
+    audit::record(
+        "trash_restored",
+        None,
+        serde_json::json!({ "entry_id": entry.id, "kind": entry.kind, "label": entry.label }),
+    );
+    Ok(entry)
+}
+
+// ─── Emptying ───────────────────────────────────────────────────────────────
+
+pub fn empty(older_than_days: Option<u32>, dry_run: bool) -> CommandResult<EmptyTrashReport> {
+    let cutoff =
+        older_than_days.map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+    let mut report = EmptyTrashReport::default();
+    for entry in list_trash() {
+        if let Some(cutoff) = cutoff {
+            let trashed_at = chrono::DateTime::parse_from_rfc3339(&entry.trashed_at);
+            if trashed_at.map(|t| t > cutoff).unwrap_or(true) {
+                continue;
+            }
+        }
+        if !dry_run {
+            fs::remove_dir_all(trash_root()?.join(&entry.id))?;
+        }
+        report.removed += 1;
+        report.freed_bytes += entry.size_bytes;
+    }
+    if dry_run {
+        report.dry_run = DryRun::planned(format!(
+            "Permanently delete {} trash entries ({} bytes)",
+            report.removed, report.freed_bytes
+        ));
+    } else if report.removed > 0 {
+        audit::record(
+            "trash_emptied",
+            None,
+            serde_json::json!({ "removed": report.removed, "older_than_days": older_than_days }),
+        );
+    }
+    Ok(report)
+}
+
+// ─── Trashing ───────────────────────────────────────────────────────────────
+
+/// Move `paths` into a new trash entry. Paths that don't exist are skipped;
+/// all paths must live on the same volume as the data directory.
+pub fn move_to_trash(kind: TrashKind, label: &str, paths: &[PathBuf]) -> CommandResult<TrashEntry> {
+    let mut entry = new_entry(kind, label)?;
+    let dir = trash_root()?.join(&entry.id);
+    fs::create_dir_all(dir.join(ITEMS_DIR))?;
+
+    for (i, path) in paths.iter().filter(|p| p.exists()).enumerate() {
+        let name = i.to_string();
+        fs::rename(path, dir.join(ITEMS_DIR).join(&name))?;
+        entry.items.push(TrashItem {
+            original_path: path.to_string_lossy().to_string(),
+            name,
+        });
+        // Written after every move so a failure part-way is still restorable
+        write_entry(&dir, &entry)?;
+    }
+    write_entry(&dir, &entry)?;
+    entry.size_bytes = dir_size(&dir);
+
+    log::info!("[trash] moved {} to {}", label, entry.id);
+    Ok(entry)
+}
+
+/// The entry `move_to_trash` would make, without moving anything.
+pub fn plan_trash(kind: TrashKind, label: &str, paths: &[PathBuf]) -> CommandResult<TrashEntry> {
+    let mut entry = new_entry(kind, label)?;
+    for (i, path) in paths.iter().filter(|p| p.exists()).enumerate() {
+        entry.items.push(TrashItem {
+            original_path: path.to_string_lossy().to_string(),
+            name: i.to_string(),
+        });
+        entry.size_bytes += dir_size(path);
+    }
+    entry.dry_run = DryRun::planned(format!(
+        "Move {} ({} bytes) to the trash",
+        label, entry.size_bytes
+    ));
+    Ok(entry)
+}
+
+fn new_entry(kind: TrashKind, label: &str) -> CommandResult<TrashEntry> {
+    let now = chrono::Utc::now();
+    let base = format!(
+        "{}-{}-{}",
+        now.format("%Y%m%dT%H%M%SZ"),
+        kind_name(kind),
+        label.replace(['/', '\\'], "_")
+    );
+    let mut id = base.clone();
+    let mut n = 1;
+    while trash_root()?.join(&id).exists() {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    Ok(TrashEntry {
+        id,
+        kind,
+        label: label.to_string(),
+        trashed_at: now.to_rfc3339(),
+        items: Vec::new(),
+        size_bytes: 0,
+        dry_run: DryRun::default(),
+    })
+}
+
+/// Total size of the trash, for the storage breakdown.
+pub fn trash_size() -> u64 {
+    trash_root().map_or(0, |root| dir_size(&root))
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(
+        "trash_retention",
+        "Empty trash entries past the retention period",
+        Schedule::Every(Duration::from_secs(24 * 60 * 60)),
+        JobLoad::Light,
+        true,
+        |_| sweep(),
+    );
+}
+
+/// Empty entries older than the configured retention.
+fn sweep() -> Result<Option<String>, String> {
+    let days = settings::load_settings().trash_retention_days;
+    if days == 0 {
+        return Ok(Some("Retention is off".into()));
+    }
+    let report = empty(Some(days), false).map_err(|e| e.to_string())?;
+    if report.removed > 0 {
+        log::info!(
+            "[trash] retention sweep removed {} entries ({} bytes)",
+            report.removed,
+            report.freed_bytes
+        );
+    }
+    Ok(Some(format!(
+        "{} entries removed, {} bytes freed",
+        report.removed, report.freed_bytes
+    )))
+}
+
+// ─── Helpers ────────────────────────────────────────────────────────────────
+
+fn trash_root() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join("trash"))
+}
+
+fn kind_name(kind: TrashKind) -> &'static str {
+    match kind {
+        TrashKind::Session => "session",
+        TrashKind::Tenant => "tenant",
+        TrashKind::Restore => "restore",
+    }
+}
+
+fn read_entry(dir: &Path) -> Option<TrashEntry> {
+    let raw = fs::read_to_string(dir.join(ENTRY_FILE)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn write_entry(dir: &Path, entry: &TrashEntry) -> CommandResult<()> {
+    let json = serde_json::to_string_pretty(entry).map_err(|e| e.to_string())?;
+    fs::write(dir.join(ENTRY_FILE), json)?;
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn newest_modification(path: &Path) -> SystemTime {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
@@ -0,0 +1,555 @@
+// URL metadata for smart paste
+//
+// `resolve_url_metadata` fetches a pasted URL so quick capture can write a
+// titled `[Title](url)` link, and a quote of the page's description, instead
+// of a bare URL:
+// - Only `http` and `https`. Redirects are followed by hand, at most
+//   `MAX_REDIRECTS`, and every hop's host must resolve to public addresses
+//   only; loopback, private, link-local, and other internal ranges fail with
+//   `AddressNotAllowed`. Without a proxy the connection goes to exactly the
+//   addresses that were checked, so a second DNS answer can't slip past.
+// - The whole fetch has `FETCH_TIMEOUT`, and at most `MAX_BYTES` of the body
+//   is read; the metadata lives in `<head>`, so a longer page is cut short
+//   rather than refused.
+// - HTML gives the title, description, site name, and canonical URL, from
+//   OpenGraph tags first, then Twitter cards, `<meta name>`, and `<title>`.
+//   The body is read as UTF-8. Other content types give just the final URL
+//   and the content type.
+// Results are cached by URL for `CACHE_TTL`; failures aren't.
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION};
+use reqwest::{redirect, Response, Url};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::html::{attr, clean, decode_entities, link_destination, tag};
+use crate::http;
+use crate::settings::{self, Settings};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BYTES: usize = 512 * 1024;
+const MAX_REDIRECTS: usize = 3;
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const CACHE_ENTRIES: usize = 200;
+
+static CACHE: Mutex<Option<HashMap<String, (Instant, UrlMetadata)>>> = Mutex::new(None);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UrlMetadata {
+    /// As pasted.
+    pub url: String,
+    /// After redirects.
+    pub final_url: String,
+    pub content_type: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub canonical_url: Option<String>,
+    /// `[Title](url)` to the canonical URL, or the final one; just the URL
+    /// when the page has no title.
+    pub markdown: String,
+    /// The description as a `> ` quote block.
+    pub quote: Option<String>,
+}
+
+/// What a page's `<head>` says about it.
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub canonical: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn resolve_url_metadata(url: String) -> CommandResult<UrlMetadata> {
+    let parsed = parse_url(&url)?;
+    if let Some(cached) = cached(parsed.as_str()) {
+        return Ok(cached);
+    }
+    let metadata = resolve(&url, &parsed, &settings::load_settings(), is_public).await?;
+    remember(parsed.as_str(), &metadata);
+    Ok(metadata)
+}
+
+async fn resolve(
+    pasted: &str,
+    url: &Url,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<UrlMetadata> {
+    tokio::time::timeout(FETCH_TIMEOUT, fetch(pasted, url, settings, allowed))
+        .await
+        .map_err(|_| {
+            CommandError::new(
+                ErrorCode::Network,
+                format!("{} took longer than {:?}", url, FETCH_TIMEOUT),
+            )
+            .with("url", url.as_str())
+        })?
+}
+
+// ─── Fetching ───────────────────────────────────────────────────────────────
+
+async fn fetch(
+    pasted: &str,
+    url: &Url,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<UrlMetadata> {
+    let accept = "text/html,application/xhtml+xml;q=0.9,*/*;q=0.5";
+    let (current, response) = get_public(url, settings, accept, allowed).await?;
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let mime = content_type
+        .as_deref()
+        .and_then(|t| t.split(';').next())
+        .map(|t| t.trim().to_ascii_lowercase());
+    let html = mime
+        .as_deref()
+        .is_none_or(|m| m == "text/html" || m == "application/xhtml+xml");
+    let mut page = PageMeta::default();
+    if html {
+        let (body, _) = read_capped(response, &current, MAX_BYTES).await?;
+        page = parse_head(&String::from_utf8_lossy(&body));
+    }
+
+    let canonical_url = page
+        .canonical
+        .as_deref()
+        .and_then(|c| current.join(c).ok())
+        .filter(|c| matches!(c.scheme(), "http" | "https"))
+        .map(String::from);
+    let target = canonical_url.as_deref().unwrap_or(current.as_str());
+    Ok(UrlMetadata {
+        url: pasted.to_string(),
+        final_url: current.to_string(),
+        content_type,
+        markdown: markdown_link(page.title.as_deref(), target),
+        quote: page.description.as_deref().map(quote),
+        title: page.title,
+        description: page.description,
+        site_name: page.site_name,
+        canonical_url,
+    })
+}
+
+/// A successful GET of `url`, following redirects by hand so every hop is
+/// checked against `allowed`, with the URL it ended at.
+pub async fn get_public(
+    url: &Url,
+    settings: &Settings,
+    accept: &str,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<(Url, Response)> {
+    let mut current = url.clone();
+    let mut redirects = 0;
+    loop {
+        let addrs = checked_addrs(&current, allowed).await?;
+        let mut builder = http::builder(settings)
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e))?
+            .redirect(redirect::Policy::none());
+        if let Some(host) = current.domain() {
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+        let response = client
+            .get(current.clone())
+            .header(ACCEPT, accept)
+            .send()
+            .await
+            .map_err(|e| network(&current, e.to_string()))?;
+        let status = response.status();
+        if !status.is_redirection() {
+            if !status.is_success() {
+                return Err(
+                    network(&current, format!("HTTP {}", status)).with("status", status.as_u16())
+                );
+            }
+            return Ok((current, response));
+        }
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| network(&current, format!("HTTP {} without a Location", status)))?;
+        if redirects == MAX_REDIRECTS {
+            return Err(network(&current, "Too many redirects".to_string())
+                .with("max_redirects", MAX_REDIRECTS));
+        }
+        let next = current
+            .join(location)
+            .map_err(|e| network(&current, format!("Bad redirect {}: {}", location, e)))?;
+        current = parse_url(next.as_str())?;
+        redirects += 1;
+    }
+}
+
+/// At most `max` bytes of the body, and whether there was more.
+pub async fn read_capped(
+    mut response: Response,
+    url: &Url,
+    max: usize,
+) -> CommandResult<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| network(url, e.to_string()))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > max {
+            body.truncate(max);
+            return Ok((body, true));
+        }
+    }
+    Ok((body, false))
+}
+
+pub fn parse_url(raw: &str) -> CommandResult<Url> {
+    let invalid = |why: &str| {
+        CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Invalid URL {}: {}", raw, why),
+        )
+        .with("url", raw)
+    };
+    let url = Url::parse(raw.trim()).map_err(|e| invalid(&e.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid("only http and https links are fetched"));
+    }
+    if url.host().is_none() {
+        return Err(invalid("no host"));
+    }
+    Ok(url)
+}
+
+/// The addresses `url`'s host resolves to, when every one is `allowed`.
+async fn checked_addrs(url: &Url, allowed: fn(IpAddr) -> bool) -> CommandResult<Vec<SocketAddr>> {
+    let port = url.port_or_known_default().unwrap_or(80);
+    let host = url.host_str().unwrap_or_default();
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => {
+            let domain = host.to_string();
+            tauri::async_runtime::spawn_blocking(move || {
+                (domain.as_str(), port)
+                    .to_socket_addrs()
+                    .map(Vec::from_iter)
+            })
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+            .map_err(|e| network(url, e.to_string()))?
+        }
+    };
+    if addrs.is_empty() {
+        return Err(network(url, "No addresses found".to_string()));
+    }
+    if let Some(addr) = addrs.iter().find(|a| !allowed(a.ip())) {
+        log::warn!(
+            "[url_metadata] refusing {}: {} is not a public address",
+            url,
+            addr.ip()
+        );
+        return Err(CommandError::new(
+            ErrorCode::AddressNotAllowed,
+            format!("{} points at a non-public address ({})", url, addr.ip()),
+        )
+        .with("url", url.as_str())
+        .with("address", addr.ip().to_string()));
+    }
+    Ok(addrs)
+}
+
+/// Whether `ip` is on the public internet: not loopback, private,
+/// link-local, shared (CGNAT), multicast, documentation, or reserved.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local, and the old site-local range
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        // Documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        // NAT64, which reaches IPv4 addresses through a translator
+        || (first == 0x0064 && ip.segments()[1] == 0xff9b))
+}
+
+pub fn network(url: &Url, message: String) -> CommandError {
+    CommandError::new(
+        ErrorCode::Network,
+        format!("Couldn't fetch {}: {}", url, message),
+    )
+    .with("url", url.as_str())
+}
+
+// ─── Cache ──────────────────────────────────────────────────────────────────
+
+fn cached(url: &str) -> Option<UrlMetadata> {
+    let cache = CACHE.lock().unwrap();
+    let (at, metadata) = cache.as_ref()?.get(url)?;
+    (at.elapsed() < CACHE_TTL).then(|| metadata.clone())
+}
+
+fn remember(url: &str, metadata: &UrlMetadata) {
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    if cache.len() >= CACHE_ENTRIES {
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, (at, _))| *at)
+            .map(|(url, _)| url.clone());
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(url.to_string(), (Instant::now(), metadata.clone()));
+}
+
+// ─── HTML ───────────────────────────────────────────────────────────────────
+
+/// Metadata from the tags before `<body>`. Comments, scripts, and styles are
+/// skipped; the first value given for each field wins.
+pub fn parse_head(html: &str) -> PageMeta {
+    let lower = html.to_ascii_lowercase();
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut title: Option<String> = None;
+    let mut canonical: Option<String> = None;
+    let mut at = 0;
+    while let Some(open) = html[at..].find('<') {
+        let start = at + open + 1;
+        if lower[start..].starts_with("!--") {
+            at = lower[start..]
+                .find("-->")
+                .map_or(html.len(), |end| start + end + 3);
+            continue;
+        }
+        let Some((name, attrs, end)) = tag(&html[start..]) else {
+            break;
+        };
+        at = start + end;
+        match name.as_str() {
+            "body" | "/head" => break,
+            "script" | "style" | "title" => {
+                let close = format!("</{}", name);
+                let content_end = lower[at..].find(&close).map_or(html.len(), |i| at + i);
+                if name == "title" && title.is_none() {
+                    title = Some(decode_entities(&html[at..content_end]));
+                }
+                at = content_end;
+            }
+            "meta" => {
+                let key = attr(&attrs, "property").or_else(|| attr(&attrs, "name"));
+                if let (Some(key), Some(content)) = (key, attr(&attrs, "content")) {
+                    meta.entry(key.to_ascii_lowercase())
+                        .or_insert_with(|| content.to_string());
+                }
+            }
+            "link" => {
+                let rel = attr(&attrs, "rel").unwrap_or_default().to_ascii_lowercase();
+                if canonical.is_none() && rel.split_whitespace().any(|r| r == "canonical") {
+                    canonical = attr(&attrs, "href").map(str::to_string);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let pick = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|key| meta.get(*key))
+            .map(|value| clean(value))
+            .find(|value| !value.is_empty())
+    };
+    PageMeta {
+        title: pick(&["og:title", "twitter:title"])
+            .or_else(|| title.map(|t| clean(&t)).filter(|t| !t.is_empty())),
+        description: pick(&["og:description", "description", "twitter:description"]),
+        site_name: pick(&["og:site_name", "application-name"]),
+        canonical: canonical
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .or_else(|| pick(&["og:url"])),
+    }
+}
+
+// ─── Markdown ───────────────────────────────────────────────────────────────
+
+fn markdown_link(title: Option<&str>, url: &str) -> String {
+    let url = link_destination(url);
+    match title {
+        Some(title) => {
+            let mut text = String::with_capacity(title.len());
+            for c in title.chars() {
+                if matches!(c, '\\' | '[' | ']') {
+                    text.push('\\');
+                }
+                text.push(c);
+            }
+            format!("[{}]({})", text, url)
+        }
+        None => url,
+    }
+}
+
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ProxyMode;
+    use crate::testing::MockServer;
+
+    fn direct() -> Settings {
+        let mut settings = Settings::default();
+        settings.proxy.mode = ProxyMode::None;
+        settings
+    }
+
+    fn fetch_local(url: &str) -> CommandResult<UrlMetadata> {
+        let parsed = parse_url(url)?;
+        tauri::async_runtime::block_on(resolve(url, &parsed, &direct(), |_| true))
+    }
+
+    #[test]
+    fn follows_redirects_and_prefers_open_graph_tags() {
+        let html = "<!doctype html><html><head>\n\
+            <!-- <title>Commented out</title> -->\n\
+            <meta charset=\"utf-8\">\n\
+            <title>  Fallback\n  title </title>\n\
+            <script>var t = \"<title>nope</title>\";</script>\n\
+            <meta property=\"og:title\" content=\"Rust &amp; [Friends]\">\n\
+            <meta name=\"description\" content='Line one &#8212; two'>\n\
+            <meta property=og:site_name content=Example>\n\
+            <link rel=\"canonical\" href=\"/canonical\">\n\
+            </head><body><meta property=\"og:description\" content=\"late\"></body></html>";
+        let server = MockServer::start(vec![
+            MockServer::response(302, &[("Location", "/page")], ""),
+            MockServer::response(200, &[("Content-Type", "text/html; charset=utf-8")], html),
+        ]);
+        let metadata = fetch_local(&server.url("/start")).unwrap();
+        assert_eq!(metadata.final_url, server.url("/page"));
+        assert_eq!(metadata.title.as_deref(), Some("Rust & [Friends]"));
+        assert_eq!(metadata.description.as_deref(), Some("Line one — two"));
+        assert_eq!(metadata.site_name.as_deref(), Some("Example"));
+        let canonical = server.url("/canonical");
+        assert_eq!(metadata.canonical_url.as_deref(), Some(canonical.as_str()));
+        assert_eq!(
+            metadata.markdown,
+            format!("[Rust & \\[Friends\\]]({})", canonical)
+        );
+        assert_eq!(metadata.quote.as_deref(), Some("> Line one — two"));
+        assert_eq!(server.requests().len(), 2);
+
+        let looping = MockServer::start(vec![MockServer::response(
+            301,
+            &[("Location", "/again")],
+            "",
+        )]);
+        let err = fetch_local(&looping.url("/")).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Network);
+        assert_eq!(looping.requests().len(), MAX_REDIRECTS + 1);
+    }
+
+    #[test]
+    fn other_content_types_give_only_the_url_and_type() {
+        let server = MockServer::start(vec![MockServer::response(
+            200,
+            &[("Content-Type", "application/pdf")],
+            "%PDF-1.7 <title>not html</title>",
+        )]);
+        let url = server.url("/paper.pdf");
+        let metadata = fetch_local(&url).unwrap();
+        assert_eq!(metadata.content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.markdown, url);
+
+        let err = fetch_local("file:///etc/passwd").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for url in ["http://127.0.0.1:9/", "http://[::1]/", "http://localhost/"] {
+            let parsed = parse_url(url).unwrap();
+            let err = tauri::async_runtime::block_on(resolve(url, &parsed, &direct(), is_public))
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::AddressNotAllowed, "{}", url);
+        }
+        for internal in [
+            "10.1.2.3",
+            "100.64.0.1",
+            "169.254.169.254",
+            "192.168.0.1",
+            "0.0.0.0",
+            "::ffff:192.168.0.1",
+            "fd00::1",
+            "fe80::1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public(internal.parse().unwrap()), "{}", internal);
+        }
+        for public in ["8.8.8.8", "93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_title_tag_and_a_bare_link() {
+        let page = parse_head("<HTML><HEAD><TITLE>Caf&#xE9; &lt;3</TITLE></HEAD>");
+        assert_eq!(page.title.as_deref(), Some("Café <3"));
+        assert_eq!(page.description, None);
+        assert_eq!(
+            markdown_link(page.title.as_deref(), "https://e.com/a (b)"),
+            "[Café <3](https://e.com/a%20%28b%29)"
+        );
+        assert_eq!(markdown_link(None, "https://e.com/"), "https://e.com/");
+    }
+}
@@ -0,0 +1,471 @@
+// Master passphrase
+//
+// Optional mode for machines without a keyring (headless Linux): a key derived
+// from the user's passphrase with Argon2id replaces the keychain vault key and
+// the local key file. The key only lives in memory, so the vault must be
+// unlocked once per run before session commands work; until then they fail
+// with `VaultLocked`.
+//
+// `vault.json` in the data directory stores the salt, the KDF parameters, and a sealed
+// check value used to verify the passphrase — never the key.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::home;
+use crate::schema::Schema;
+use crate::sessions::{self, SessionPayload};
+use crate::{audit, integrity, secrets};
+
+const VAULT_FILE: &str = "vault.json";
+/// The KDF parameters and check value; unchanged since it was versioned.
+const VAULT_SCHEMA: Schema = Schema::new("vault file", &[]);
+const CHECK_PLAINTEXT: &[u8] = b"agentvbx-vault-check";
+const MIN_PASSPHRASE_LEN: usize = 8;
+/// Failures allowed before attempts are throttled.
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The derived key, present once unlocked.
+static KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+static ATTEMPTS: Mutex<Attempts> = Mutex::new(Attempts {
+    failures: 0,
+    blocked_until: None,
+});
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    kdf: String,
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    check: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VaultStatus {
+    pub passphrase_configured: bool,
+    /// True when a passphrase is configured and not yet unlocked this run.
+    pub locked: bool,
+    /// Seconds until another unlock attempt is accepted.
+    pub retry_after_secs: Option<u64>,
+}
+
+struct Attempts {
+    failures: u32,
+    blocked_until: Option<Instant>,
+}
+
+/// Plaintext of everything the vault key protects, held while re-encrypting.
+struct VaultContents {
+    payloads: Vec<(PathBuf, SessionPayload)>,
+    secrets: BTreeMap<String, String>,
+}
+
+/// A re-encrypted file waiting beside the one it replaces.
+struct Staged {
+    temp: PathBuf,
+    target: PathBuf,
+    /// The bytes it replaces, put back if the swap fails.
+    previous: Option<Vec<u8>>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Turn on master passphrase mode and re-encrypt existing sessions and
+/// fallback secrets under the derived key.
+#[tauri::command]
+pub fn set_master_passphrase(passphrase: String) -> CommandResult<VaultStatus> {
+    if is_configured() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "A master passphrase is already set; use change_master_passphrase",
+        ));
+    }
+    check_strength(&passphrase)?;
+
+    let contents = read_contents()?;
+    let (file, key) = derive_new(&passphrase)?;
+    rekey(contents, &file, key)?;
+
+    audit::record("master_passphrase_set", None, serde_json::json!({}));
+    Ok(status())
+}
+
+#[tauri::command]
+pub fn unlock_vault(passphrase: String) -> CommandResult<VaultStatus> {
+    let file = read_vault_file()?.ok_or_else(not_configured)?;
+    let key = verify(&file, &passphrase)?;
+    *KEY.lock().unwrap() = Some(key);
+    Ok(status())
+}
+
+/// Re-encrypt everything under a new passphrase. Works whether or not the
+/// vault is currently unlocked; `old` is verified either way.
+#[tauri::command]
+pub fn change_master_passphrase(old: String, new: String) -> CommandResult<VaultStatus> {
+    let file = read_vault_file()?.ok_or_else(not_configured)?;
+    check_strength(&new)?;
+    let old_key = verify(&file, &old)?;
+    *KEY.lock().unwrap() = Some(old_key);
+
+    let contents = read_contents()?;
+    let (file, key) = derive_new(&new)?;
+    rekey(contents, &file, key)?;
+
+    audit::record("master_passphrase_changed", None, serde_json::json!({}));
+    Ok(status())
+}
+
+// ─── State ──────────────────────────────────────────────────────────────────
+
+pub fn is_configured() -> bool {
+    vault_path().is_ok_and(|path| path.is_file())
+}
+
+pub fn status() -> VaultStatus {
+    let configured = is_configured();
+    let retry_after_secs = ATTEMPTS
+        .lock()
+        .unwrap()
+        .blocked_until
+        .and_then(|until| until.checked_duration_since(Instant::now()))
+        .map(|d| d.as_secs().max(1));
+    VaultStatus {
+        passphrase_configured: configured,
+        locked: configured && KEY.lock().unwrap().is_none(),
+        retry_after_secs,
+    }
+}
+
+/// `None` when no passphrase is configured; otherwise the key, or an error
+/// while the vault is locked.
+pub fn passphrase_key() -> Option<Result<[u8; 32], String>> {
+    if !is_configured() {
+        return None;
+    }
+    Some(
+        KEY.lock()
+            .unwrap()
+            .ok_or_else(|| "Vault is locked".to_string()),
+    )
+}
+
+/// Gate for commands that need session or fallback-secret plaintext.
+pub fn ensure_unlocked() -> CommandResult<()> {
+    if status().locked {
+        return Err(CommandError::new(
+            ErrorCode::VaultLocked,
+            "Unlock the vault with the master passphrase first",
+        ));
+    }
+    Ok(())
+}
+
+// ─── Key Derivation ─────────────────────────────────────────────────────────
+
+fn derive_new(passphrase: &str) -> CommandResult<(VaultFile, [u8; 32])> {
+    use chacha20poly1305::aead::rand_core::RngCore;
+    use chacha20poly1305::aead::OsRng;
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut file = VaultFile {
+        kdf: "argon2id".into(),
+        salt: b64().encode(salt),
+        m_cost: argon2::Params::DEFAULT_M_COST,
+        t_cost: argon2::Params::DEFAULT_T_COST,
+        p_cost: argon2::Params::DEFAULT_P_COST,
+        check: String::new(),
+    };
+    let key = derive(&file, passphrase)?;
+    file.check = b64().encode(secrets::seal_with(&key, CHECK_PLAINTEXT)?);
+    Ok((file, key))
+}
+
+fn derive(file: &VaultFile, passphrase: &str) -> CommandResult<[u8; 32]> {
+    let salt = b64().decode(&file.salt).map_err(|_| corrupted("salt"))?;
+    let params = argon2::Params::new(file.m_cost, file.t_cost, file.p_cost, Some(32))
+        .map_err(|_| corrupted("KDF parameters"))?;
+    let argon = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+    let mut key = [0u8; 32];
+    argon
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    Ok(key)
+}
+
+/// Check `passphrase` against the stored check value, throttling repeated
+/// failures with exponential backoff.
+fn verify(file: &VaultFile, passphrase: &str) -> CommandResult<[u8; 32]> {
+    if let Some(wait) = status().retry_after_secs {
+        return Err(CommandError::new(
+            ErrorCode::RateLimited,
+            format!("Too many wrong passphrases; retry in {}s", wait),
+        )
+        .with("retry_after_secs", wait));
+    }
+
+    let key = derive(file, passphrase)?;
+    let check = b64()
+        .decode(&file.check)
+        .map_err(|_| corrupted("check value"))?;
+    let mut attempts = ATTEMPTS.lock().unwrap();
+
+    if secrets::open_with(&key, &check).is_ok_and(|plain| plain == CHECK_PLAINTEXT) {
+        attempts.failures = 0;
+        attempts.blocked_until = None;
+        return Ok(key);
+    }
+
+    attempts.failures += 1;
+    if attempts.failures >= FREE_ATTEMPTS {
+        let backoff = Duration::from_secs(1 << (attempts.failures - FREE_ATTEMPTS).min(16));
+        attempts.blocked_until = Some(Instant::now() + backoff.min(MAX_BACKOFF));
+    }
+    log::warn!(
+        "[vault] wrong master passphrase ({} consecutive)",
+        attempts.failures
+    );
+    audit::record(
+        "vault_unlock_failed",
+        None,
+        serde_json::json!({ "consecutive_failures": attempts.failures }),
+    );
+    Err(
+        CommandError::new(ErrorCode::WrongPassphrase, "Wrong master passphrase")
+            .with("failures", attempts.failures),
+    )
+}
+
+fn check_strength(passphrase: &str) -> CommandResult<()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Master passphrase must be at least {} characters",
+                MIN_PASSPHRASE_LEN
+            ),
+        )
+        .with("min_length", MIN_PASSPHRASE_LEN));
+    }
+    Ok(())
+}
+
+// ─── Re-encryption ──────────────────────────────────────────────────────────
+
+/// Decrypt everything with the key currently in effect.
+fn read_contents() -> CommandResult<VaultContents> {
+    let mut payloads = Vec::new();
+    for dir in sessions::all_session_dirs(&sessions::sessions_root()?) {
+        if let Some(payload) = sessions::load_payload(&dir)? {
+            payloads.push((dir, payload));
+        }
+    }
+    let _guard = secrets::FILES_LOCK.lock().unwrap();
+    let secrets = if fallback_exists() {
+        secrets::read_fallback()?
+    } else {
+        BTreeMap::new()
+    };
+    Ok(VaultContents { payloads, secrets })
+}
+
+/// Re-write everything under `key` and switch to it. Every file is staged
+/// before any is replaced and `vault.json` is written last, so a failure
+/// leaves the old passphrase unlocking everything.
+fn rekey(contents: VaultContents, file: &VaultFile, key: [u8; 32]) -> CommandResult<()> {
+    let _guard = secrets::FILES_LOCK.lock().unwrap();
+    let staged = stage_contents(&contents, &key)?;
+    swap_in(&staged)?;
+    if let Err(e) = write_vault_file(file) {
+        roll_back(&staged);
+        return Err(e);
+    }
+    *KEY.lock().unwrap() = Some(key);
+
+    for (dir, _) in &contents.payloads {
+        sessions::remove_plain_payload(dir);
+    }
+    log::info!(
+        "[vault] re-encrypted {} session(s) and {} fallback secret(s)",
+        contents.payloads.len(),
+        contents.secrets.len()
+    );
+    Ok(())
+}
+
+fn stage_contents(contents: &VaultContents, key: &[u8; 32]) -> CommandResult<Vec<Staged>> {
+    let mut sealed = Vec::new();
+    for (dir, payload) in &contents.payloads {
+        sealed.push(sessions::seal_payload_with(dir, payload, key)?);
+    }
+    if !contents.secrets.is_empty() || fallback_exists() {
+        sealed.push(secrets::seal_fallback_with(key, &contents.secrets)?);
+    }
+
+    let mut staged = Vec::new();
+    for (target, bytes) in sealed {
+        let written = read_existing(&target)
+            .and_then(|previous| Ok((integrity::write_staged(&target, &bytes)?, previous)));
+        match written {
+            Ok((temp, previous)) => staged.push(Staged {
+                temp,
+                target,
+                previous,
+            }),
+            Err(e) => {
+                discard(&staged);
+                return Err(e);
+            }
+        }
+    }
+    Ok(staged)
+}
+
+/// Rename every staged file into place, undoing the ones already moved if
+/// one fails.
+fn swap_in(staged: &[Staged]) -> CommandResult<()> {
+    for (i, file) in staged.iter().enumerate() {
+        if let Err(e) = fs::rename(&file.temp, &file.target) {
+            roll_back(&staged[..i]);
+            discard(&staged[i..]);
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+fn roll_back(swapped: &[Staged]) {
+    for file in swapped {
+        let restored = match &file.previous {
+            Some(bytes) => fs::write(&file.target, bytes),
+            None => fs::remove_file(&file.target),
+        };
+        if let Err(e) = restored {
+            log::error!(
+                "[vault] could not restore {} after a failed re-encryption: {}",
+                file.target.display(),
+                e
+            );
+        }
+    }
+}
+
+fn discard(staged: &[Staged]) {
+    for file in staged {
+        let _ = fs::remove_file(&file.temp);
+    }
+}
+
+fn read_existing(path: &Path) -> CommandResult<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn fallback_exists() -> bool {
+    home::data_dirs().is_ok_and(|dirs| dirs.data.join(secrets::FALLBACK_FILE).is_file())
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn vault_path() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join(VAULT_FILE))
+}
+
+fn read_vault_file() -> CommandResult<Option<VaultFile>> {
+    match fs::read(vault_path()?) {
+        Ok(raw) => VAULT_SCHEMA
+            .decode(&raw)
+            .map(Some)
+            .map_err(|_| corrupted("vault file")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_vault_file(file: &VaultFile) -> CommandResult<()> {
+    let path = vault_path()?;
+    VAULT_SCHEMA.guard_path(&path)?;
+    integrity::write_with_backup(&path, &VAULT_SCHEMA.encode(file, true)?)
+}
+
+fn not_configured() -> CommandError {
+    CommandError::new(ErrorCode::NotFound, "No master passphrase is set")
+}
+
+fn corrupted(what: &str) -> CommandError {
+    let path = vault_path().unwrap_or_default();
+    CommandError::new(
+        ErrorCode::Internal,
+        format!("Corrupted {} in {}", what, path.display()),
+    )
+}
+
+fn b64() -> base64::engine::GeneralPurpose {
+    base64::engine::general_purpose::STANDARD
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn payload(captured_at: &str) -> SessionPayload {
+        SessionPayload {
+            cookies: Vec::new(),
+            captured_at: captured_at.into(),
+            updated_at: captured_at.into(),
+        }
+    }
+
+    #[test]
+    fn a_failed_change_leaves_the_old_passphrase_unlocking_everything() {
+        let _home = TestHome::new();
+        *KEY.lock().unwrap() = None;
+        let root = sessions::sessions_root().unwrap();
+        let mut dirs = Vec::new();
+        for (tenant, stamp) in [("acme", "2024-01-01"), ("globex", "2024-02-02")] {
+            let dir = sessions::ensure_session_dir_at(&root, tenant, "claude").unwrap();
+            sessions::save_payload(&dir, &payload(stamp)).unwrap();
+            dirs.push((dir, stamp));
+        }
+        let secret = BTreeMap::from([("secret:acme:api:key".to_string(), "v".to_string())]);
+        secrets::write_fallback(&secret).unwrap();
+        set_master_passphrase("old passphrase".into()).unwrap();
+
+        // The fallback file is staged last, after both sessions
+        let fallback = home::data_dir().unwrap().join(secrets::FALLBACK_FILE);
+        let blocker = integrity::staged_path(&fallback);
+        fs::create_dir(&blocker).unwrap();
+        assert!(
+            change_master_passphrase("old passphrase".into(), "new passphrase".into()).is_err()
+        );
+        fs::remove_dir(&blocker).unwrap();
+
+        *KEY.lock().unwrap() = None;
+        assert!(unlock_vault("new passphrase".into()).is_err());
+        unlock_vault("old passphrase".into()).unwrap();
+        for (dir, stamp) in &dirs {
+            let loaded = sessions::load_payload(dir).unwrap().unwrap();
+            assert_eq!(loaded.captured_at, *stamp);
+            assert!(!integrity::staged_path(&dir.join("payload.enc")).exists());
+        }
+        assert_eq!(secrets::read_fallback().unwrap(), secret);
+    }
+}
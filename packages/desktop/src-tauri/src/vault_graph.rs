@@ -0,0 +1,414 @@
+// Vault graph export
+//
+// `export_vault_graph` writes a vault's link graph to a file graph tools can
+// load:
+// - `graphml`: GraphML, for Gephi, yEd, and NetworkX.
+// - `dot`: Graphviz.
+// - `json`: `{ "nodes": [...] }`, each node listing its outgoing `links`,
+//   for D3 and scripts.
+// Every Markdown note the vault's index covers is a node, with its title
+// (the frontmatter `title`, else the file name), folder, tags, and word
+// count. Node ids are vault-relative paths. Notes with no links in or out
+// are still written, as isolated nodes. Each link to another note is an
+// edge typed `wikilink`, `embed`, or `markdown`, resolved as `move_note`
+// resolves them; a note that links to another twice has two edges. Links to
+// attachments and to notes that don't exist aren't edges, and are counted
+// in the report instead.
+//
+// Notes are read one at a time and written straight to a hidden partial
+// file next to the destination, as `export_results` does, so only the list
+// of file names is held in memory. New formats are new `GraphFormat`
+// variants; anything format-specific goes in `GraphExportOptions`.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::artifact_download::{self, ConflictPolicy};
+use crate::dry_run;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::frontmatter::{self, FieldValue};
+use crate::note_links::{LinkIndex, LinkKind};
+use crate::{note_stats, stores, text_style};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    Graphml,
+    Dot,
+    Json,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct GraphExportOptions {
+    pub on_conflict: ConflictPolicy,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GraphExportReport {
+    /// Where the file ended up, which `keep_both` may have renamed.
+    pub path: String,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Links to attachments or to notes that aren't in the vault.
+    pub skipped_links: usize,
+}
+
+#[derive(Serialize)]
+struct Node {
+    id: String,
+    title: String,
+    folder: String,
+    tags: Vec<String>,
+    words: usize,
+    links: Vec<Edge>,
+}
+
+#[derive(Serialize)]
+struct Edge {
+    target: String,
+    #[serde(rename = "type")]
+    kind: LinkKind,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Write the link graph of the vault at `vault_path` to `dest_path`, inside a
+/// connected store.
+#[tauri::command]
+pub async fn export_vault_graph(
+    window: tauri::WebviewWindow,
+    vault_path: String,
+    format: GraphFormat,
+    dest_path: String,
+    options: Option<GraphExportOptions>,
+) -> CommandResult<GraphExportReport> {
+    dry_run::check_writable(window.label(), None, "export_vault_graph")?;
+    let options = options.unwrap_or_default();
+    let vault = Path::new(&vault_path).to_path_buf();
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault_path),
+        )
+        .with("path", &vault_path));
+    }
+    let dest = artifact_download::check_destination(&dest_path)?;
+    tauri::async_runtime::spawn_blocking(move || export(&vault, format, &dest, &options))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Writing ────────────────────────────────────────────────────────────────
+
+fn export(
+    vault: &Path,
+    format: GraphFormat,
+    dest: &Path,
+    options: &GraphExportOptions,
+) -> CommandResult<GraphExportReport> {
+    let keys: Vec<String> = stores::scan_for_index(vault)?.into_keys().collect();
+    let index = LinkIndex::new(keys.iter().map(String::as_str));
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let part = dest.with_file_name(format!(".{}.partial", name));
+    let mut report = GraphExportReport {
+        path: String::new(),
+        nodes: 0,
+        edges: 0,
+        skipped_links: 0,
+    };
+    let written = File::create(&part)
+        .map_err(CommandError::from)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            begin(&mut out, format)?;
+            for key in keys.iter().filter(|k| is_note(k)) {
+                let node = read_node(vault, key, &index, &mut report.skipped_links);
+                write_node(&mut out, format, &node, report.nodes == 0)?;
+                report.nodes += 1;
+                report.edges += node.links.len();
+            }
+            end(&mut out, format)?;
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(())
+        })
+        .and_then(|()| artifact_download::place(&part, dest, options.on_conflict));
+    match written {
+        Ok(path) => {
+            report.path = path.to_string_lossy().to_string();
+            Ok(report)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e)
+        }
+    }
+}
+
+fn is_note(key: &str) -> bool {
+    key.to_lowercase().ends_with(".md")
+}
+
+/// A note and its edges. A note that can't be read is still a node.
+fn read_node(vault: &Path, key: &str, index: &LinkIndex, skipped: &mut usize) -> Node {
+    let (folder, file) = key.rsplit_once('/').unwrap_or(("", key));
+    let mut node = Node {
+        id: key.to_string(),
+        title: file[..file.len() - 3].to_string(),
+        folder: folder.to_string(),
+        tags: Vec::new(),
+        words: 0,
+        links: Vec::new(),
+    };
+    let bytes = match fs::read(vault.join(key)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::warn!("[vault_graph] can't read {}: {}", key, e);
+            return node;
+        }
+    };
+    let text = text_style::decode(&bytes, &text_style::detect(&bytes));
+    let title = frontmatter::parse(&text).and_then(|fm| match fm.get("title") {
+        Some(FieldValue::Scalar(title)) if !title.is_empty() => Some(title.clone()),
+        _ => None,
+    });
+    if let Some(title) = title {
+        node.title = title;
+    }
+    let stats = note_stats::compute(&text);
+    node.words = stats.words;
+    node.tags = stats.tag_names;
+    for link in index.links(key, &text) {
+        match link.resolved.filter(|target| is_note(target)) {
+            Some(target) => node.links.push(Edge {
+                target,
+                kind: link.kind,
+            }),
+            None => *skipped += 1,
+        }
+    }
+    node
+}
+
+fn begin(out: &mut impl Write, format: GraphFormat) -> io::Result<()> {
+    match format {
+        GraphFormat::Graphml => out.write_all(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+                "  <key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n",
+                "  <key id=\"folder\" for=\"node\" attr.name=\"folder\" attr.type=\"string\"/>\n",
+                "  <key id=\"tags\" for=\"node\" attr.name=\"tags\" attr.type=\"string\"/>\n",
+                "  <key id=\"words\" for=\"node\" attr.name=\"words\" attr.type=\"int\"/>\n",
+                "  <key id=\"type\" for=\"edge\" attr.name=\"type\" attr.type=\"string\"/>\n",
+                "  <graph id=\"vault\" edgedefault=\"directed\">\n",
+            )
+            .as_bytes(),
+        ),
+        GraphFormat::Dot => out.write_all(b"digraph vault {\n"),
+        GraphFormat::Json => out.write_all(b"{\"nodes\":["),
+    }
+}
+
+fn write_node(
+    out: &mut impl Write,
+    format: GraphFormat,
+    node: &Node,
+    first: bool,
+) -> CommandResult<()> {
+    match format {
+        GraphFormat::Graphml => {
+            writeln!(out, "    <node id=\"{}\">", xml_escape(&node.id))?;
+            writeln!(
+                out,
+                "      <data key=\"title\">{}</data>",
+                xml_escape(&node.title)
+            )?;
+            writeln!(
+                out,
+                "      <data key=\"folder\">{}</data>",
+                xml_escape(&node.folder)
+            )?;
+            // GraphML has no list type; tags can't contain spaces
+            writeln!(
+                out,
+                "      <data key=\"tags\">{}</data>",
+                xml_escape(&node.tags.join(" "))
+            )?;
+            writeln!(out, "      <data key=\"words\">{}</data>", node.words)?;
+            writeln!(out, "    </node>")?;
+            for edge in &node.links {
+                writeln!(
+                    out,
+                    "    <edge source=\"{}\" target=\"{}\"><data key=\"type\">{}</data></edge>",
+                    xml_escape(&node.id),
+                    xml_escape(&edge.target),
+                    kind_name(edge.kind)
+                )?;
+            }
+        }
+        GraphFormat::Dot => {
+            writeln!(
+                out,
+                "  {} [label={}, folder={}, tags={}, words={}];",
+                dot_quote(&node.id),
+                dot_quote(&node.title),
+                dot_quote(&node.folder),
+                dot_quote(&node.tags.join(" ")),
+                node.words
+            )?;
+            for edge in &node.links {
+                writeln!(
+                    out,
+                    "  {} -> {} [type={}];",
+                    dot_quote(&node.id),
+                    dot_quote(&edge.target),
+                    kind_name(edge.kind)
+                )?;
+            }
+        }
+        GraphFormat::Json => {
+            if !first {
+                out.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut *out, node).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn end(out: &mut impl Write, format: GraphFormat) -> io::Result<()> {
+    match format {
+        GraphFormat::Graphml => out.write_all(b"  </graph>\n</graphml>\n"),
+        GraphFormat::Dot => out.write_all(b"}\n"),
+        GraphFormat::Json => out.write_all(b"]}\n"),
+    }
+}
+
+fn kind_name(kind: LinkKind) -> &'static str {
+    match kind {
+        LinkKind::Wikilink => "wikilink",
+        LinkKind::Embed => "embed",
+        LinkKind::Markdown => "markdown",
+    }
+}
+
+/// Escaped for XML text and attributes, dropping characters XML 1.0 can't
+/// hold at all.
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c < ' ' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A DOT quoted string.
+fn dot_quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHome, BASIC_FIXTURE};
+
+    fn vault(home: &TestHome) -> std::path::PathBuf {
+        let vault = home.join("Documents/Work");
+        fs::write(
+            vault.join("Inbox.md"),
+            "---\ntitle: \"Inbox & <triage>\"\ntags: [gtd]\n---\n\
+             See [[Launch]] and ![[Launch#Plan]], [notes](projects/Launch.md).\n\
+             Also [[Missing]], ![[chart.png]], `[[Code]]` #review\n",
+        )
+        .unwrap();
+        fs::write(vault.join("projects/chart.png"), "").unwrap();
+        fs::write(vault.join("Lonely.md"), "Nobody links here").unwrap();
+        vault
+    }
+
+    fn run(home: &TestHome, format: GraphFormat, name: &str) -> (GraphExportReport, String) {
+        let dest = home.join(&format!("Reports/{}", name));
+        let report = export(&vault(home), format, &dest, &GraphExportOptions::default()).unwrap();
+        assert!(!home.join(&format!("Reports/.{}.partial", name)).exists());
+        (report, fs::read_to_string(dest).unwrap())
+    }
+
+    #[test]
+    fn json_lists_every_note_with_typed_edges() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let (report, written) = run(&home, GraphFormat::Json, "graph.json");
+        assert_eq!(report.nodes, 3);
+        assert_eq!(report.edges, 3);
+        // [[Missing]] and the image
+        assert_eq!(report.skipped_links, 2);
+
+        let graph: serde_json::Value = serde_json::from_str(&written).unwrap();
+        let nodes = graph["nodes"].as_array().unwrap();
+        let ids: Vec<&str> = nodes.iter().map(|n| n["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["Inbox.md", "Lonely.md", "projects/Launch.md"]);
+
+        let inbox = &nodes[0];
+        assert_eq!(inbox["title"], "Inbox & <triage>");
+        assert_eq!(inbox["folder"], "");
+        assert_eq!(inbox["tags"], serde_json::json!(["gtd", "review"]));
+        let types: Vec<&str> = inbox["links"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| {
+                assert_eq!(l["target"], "projects/Launch.md");
+                l["type"].as_str().unwrap()
+            })
+            .collect();
+        assert_eq!(types, ["wikilink", "embed", "markdown"]);
+
+        // Orphans are isolated nodes
+        assert_eq!(nodes[1]["links"], serde_json::json!([]));
+        assert_eq!(nodes[1]["words"], 3);
+        assert_eq!(nodes[2]["title"], "Launch");
+        assert_eq!(nodes[2]["folder"], "projects");
+    }
+
+    #[test]
+    fn graphml_and_dot_escape_titles() {
+        let home = TestHome::with_fixture(BASIC_FIXTURE);
+        let (report, graphml) = run(&home, GraphFormat::Graphml, "graph.graphml");
+        assert_eq!(report.edges, 3);
+        assert!(graphml.contains("<data key=\"title\">Inbox &amp; &lt;triage&gt;</data>"));
+        assert!(graphml.contains("<node id=\"Lonely.md\">"));
+        assert!(graphml.contains(
+            "<edge source=\"Inbox.md\" target=\"projects/Launch.md\"><data key=\"type\">embed</data></edge>"
+        ));
+        assert!(graphml.trim_end().ends_with("</graphml>"));
+
+        let (_, dot) = run(&home, GraphFormat::Dot, "graph.dot");
+        assert!(dot.starts_with("digraph vault {\n"));
+        assert!(dot.contains("\"Lonely.md\" [label=\"Lonely\", folder=\"\", tags=\"\", words=3];"));
+        assert!(dot.contains("\"Inbox.md\" -> \"projects/Launch.md\" [type=markdown];"));
+        assert_eq!(dot_quote("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+    }
+}
@@ -0,0 +1,685 @@
+// Vault packs
+//
+// A pack is a starter structure for a vault: folders, templates, and a
+// dashboard note. `install_vault_pack` takes one bundled with the app, by id
+// (see `BUNDLED`), or a `.tar.gz` downloaded from the orchestrator holding
+// `pack.json` and the pack's files under `files/`. The manifest declares the
+// pack's `id`, its `folders`, and each file's `path` and `sha256`. Before
+// anything is written, every path must be relative without `..`, the
+// archive must hold exactly the declared files, and each must match its
+// hash.
+//
+// Files already in the vault with the pack's content are left as they are.
+// Ones with other content are conflicts, and the install is refused with
+// them listed, so a dry run previews what would be created and what
+// conflicts. Files are staged next to their targets and renamed into place
+// only once all are staged. If any step fails, everything written so far is
+// removed. The files and folders the install created are recorded in
+// `vault_packs.json` (data directory) with their hashes.
+// `uninstall_vault_pack` removes those that are still unmodified, then the
+// created folders that are left empty. Files edited since install are left
+// in place and reported.
+
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::{self, DryRun};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{audit, home, integrity, write_guard};
+
+const INSTALLED_FILE: &str = "vault_packs.json";
+const MANIFEST_FILE: &str = "pack.json";
+const FILES_DIR: &str = "files";
+/// Uncompressed size an archive may unpack to.
+const MAX_PACK_BYTES: u64 = 50 * 1024 * 1024;
+const STAGING_SUFFIX: &str = ".agentvbx-pack";
+
+/// Id, name, folders, and files of the packs shipped with the app.
+const BUNDLED: &[BundledPack] = &[BundledPack {
+    id: "starter",
+    name: "Starter vault",
+    folders: &["Inbox", "Projects", "Archive", "Templates"],
+    files: &[
+        (
+            "Dashboard.md",
+            include_str!("../packs/starter/Dashboard.md"),
+        ),
+        (
+            "Templates/Daily note.md",
+            include_str!("../packs/starter/Templates/Daily note.md"),
+        ),
+        (
+            "Templates/Meeting.md",
+            include_str!("../packs/starter/Templates/Meeting.md"),
+        ),
+    ],
+}];
+
+struct BundledPack {
+    id: &'static str,
+    name: &'static str,
+    folders: &'static [&'static str],
+    files: &'static [(&'static str, &'static str)],
+}
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+/// `{ "bundled": "starter" }` or `{ "archive": "/path/to/pack.tar.gz" }`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PackSource {
+    Bundled(String),
+    Archive(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub folders: Vec<String>,
+    pub files: Vec<PackFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackFile {
+    /// Vault-relative, `/`-separated.
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PackInstall {
+    pub pack_id: String,
+    /// Files written, or that would be.
+    pub created: Vec<String>,
+    /// Already in the vault with the pack's content.
+    pub unchanged: Vec<String>,
+    /// In the vault with other content; a real install is refused.
+    pub conflicts: Vec<String>,
+    pub folders: Vec<String>,
+    #[serde(flatten)]
+    pub dry_run: DryRun,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PackUninstall {
+    pub pack_id: String,
+    pub removed: Vec<String>,
+    /// Edited since install, so left in place.
+    pub modified: Vec<String>,
+    /// Already gone.
+    pub missing: Vec<String>,
+    pub removed_folders: Vec<String>,
+}
+
+/// What an install created, for uninstalling it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct InstalledPack {
+    vault_path: String,
+    pack_id: String,
+    version: Option<String>,
+    installed_at: String,
+    files: Vec<PackFile>,
+    folders: Vec<String>,
+}
+
+/// A validated pack with its file contents.
+struct Pack {
+    manifest: PackManifest,
+    contents: BTreeMap<String, Vec<u8>>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn install_vault_pack(
+    window: tauri::WebviewWindow,
+    vault_path: String,
+    pack_source: PackSource,
+    dry_run: Option<bool>,
+) -> CommandResult<PackInstall> {
+    let dry_run = dry_run::requested(window.label(), None, dry_run);
+    install(Path::new(&vault_path), &pack_source, dry_run)
+}
+
+#[tauri::command]
+pub fn uninstall_vault_pack(vault_path: String, pack_id: String) -> CommandResult<PackUninstall> {
+    uninstall(Path::new(&vault_path), &pack_id)
+}
+
+fn install(vault: &Path, source: &PackSource, dry_run: bool) -> CommandResult<PackInstall> {
+    if !vault.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", vault.display()),
+        )
+        .with("path", vault.to_string_lossy()));
+    }
+    let pack = load(source)?;
+    let id = pack.manifest.id.clone();
+    let vault_key = vault.to_string_lossy().to_string();
+    if load_installed()
+        .iter()
+        .any(|i| i.vault_path == vault_key && i.pack_id == id)
+    {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!("Pack {} is already installed in this vault", id),
+        )
+        .with("pack_id", &id)
+        .with("path", &vault_key));
+    }
+
+    let _guard = write_guard::lock();
+    let mut report = PackInstall {
+        pack_id: id.clone(),
+        created: Vec::new(),
+        unchanged: Vec::new(),
+        conflicts: Vec::new(),
+        folders: Vec::new(),
+        dry_run: DryRun::default(),
+    };
+    for file in &pack.manifest.files {
+        match fs::read(vault.join(&file.path)) {
+            Ok(existing) if write_guard::sha256(&existing) == file.sha256 => {
+                report.unchanged.push(file.path.clone())
+            }
+            Ok(_) => report.conflicts.push(file.path.clone()),
+            Err(_) if vault.join(&file.path).exists() => report.conflicts.push(file.path.clone()),
+            Err(_) => report.created.push(file.path.clone()),
+        }
+    }
+    let mut folders: BTreeSet<String> = pack.manifest.folders.iter().cloned().collect();
+    for path in &report.created {
+        let mut parent = Path::new(path).parent();
+        while let Some(dir) = parent.filter(|d| !d.as_os_str().is_empty()) {
+            folders.insert(dir.to_string_lossy().replace('\\', "/"));
+            parent = dir.parent();
+        }
+    }
+    report.folders = folders
+        .into_iter()
+        .filter(|f| !vault.join(f).exists())
+        .collect();
+
+    report.dry_run = DryRun::outcome(dry_run, || {
+        format!(
+            "Install pack {}: create {} files and {} folders, {} conflicts",
+            id,
+            report.created.len(),
+            report.folders.len(),
+            report.conflicts.len()
+        )
+    });
+    if dry_run {
+        return Ok(report);
+    }
+    if !report.conflicts.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::Conflict,
+            format!(
+                "{} of the pack's files already exist with other content",
+                report.conflicts.len()
+            ),
+        )
+        .with("pack_id", &id)
+        .with("conflicts", report.conflicts.clone()));
+    }
+
+    write_all(vault, &pack, &report)?;
+    let mut installed = load_installed();
+    installed.push(InstalledPack {
+        vault_path: vault_key.clone(),
+        pack_id: id.clone(),
+        version: pack.manifest.version.clone(),
+        installed_at: chrono::Utc::now().to_rfc3339(),
+        files: pack
+            .manifest
+            .files
+            .iter()
+            .filter(|f| report.created.contains(&f.path))
+            .cloned()
+            .collect(),
+        folders: report.folders.clone(),
+    });
+    if let Err(e) = save_installed(&installed) {
+        rollback(vault, &report.created, &report.folders);
+        return Err(e);
+    }
+    for path in &report.created {
+        activity::record(ActivityKind::Write, &vault.join(path), None);
+    }
+    audit::record(
+        "install_vault_pack",
+        None,
+        serde_json::json!({ "pack_id": id, "vault": vault_key, "files": report.created.len() }),
+    );
+    Ok(report)
+}
+
+/// Stage every new file, then rename them all into place; on failure remove
+/// whatever was written.
+fn write_all(vault: &Path, pack: &Pack, report: &PackInstall) -> CommandResult<()> {
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let result = (|| -> CommandResult<()> {
+        for folder in &report.folders {
+            fs::create_dir_all(vault.join(folder))?;
+        }
+        for path in &report.created {
+            let target = vault.join(path);
+            let staging = staging_path(&target);
+            fs::write(&staging, &pack.contents[path])?;
+            staged.push((staging, target));
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        for (staging, _) in &staged {
+            let _ = fs::remove_file(staging);
+        }
+        rollback(vault, &[], &report.folders);
+        return Err(e);
+    }
+    let mut placed = Vec::new();
+    for (staging, target) in &staged {
+        if let Err(e) = fs::rename(staging, target) {
+            for (staging, _) in &staged {
+                let _ = fs::remove_file(staging);
+            }
+            rollback(vault, &placed, &report.folders);
+            return Err(e.into());
+        }
+        placed.push(relative_key(vault, target));
+    }
+    Ok(())
+}
+
+/// Remove files an install wrote and the folders it created, deepest first.
+fn rollback(vault: &Path, files: &[String], folders: &[String]) {
+    for path in files {
+        let _ = fs::remove_file(vault.join(path));
+    }
+    let mut folders: Vec<&String> = folders.iter().collect();
+    folders.sort_by_key(|f| std::cmp::Reverse(f.matches('/').count()));
+    for folder in folders {
+        // Only empty ones: remove_dir refuses the rest
+        let _ = fs::remove_dir(vault.join(folder));
+    }
+}
+
+fn uninstall(vault: &Path, pack_id: &str) -> CommandResult<PackUninstall> {
+    let vault_key = vault.to_string_lossy().to_string();
+    let mut installed = load_installed();
+    let Some(index) = installed
+        .iter()
+        .position(|i| i.vault_path == vault_key && i.pack_id == pack_id)
+    else {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Pack {} isn't installed in this vault", pack_id),
+        )
+        .with("pack_id", pack_id)
+        .with("path", &vault_key));
+    };
+    let record = installed.remove(index);
+
+    let _guard = write_guard::lock();
+    let mut report = PackUninstall {
+        pack_id: pack_id.to_string(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        missing: Vec::new(),
+        removed_folders: Vec::new(),
+    };
+    for file in &record.files {
+        let path = vault.join(&file.path);
+        match fs::read(&path) {
+            Ok(bytes) if write_guard::sha256(&bytes) == file.sha256 => {
+                fs::remove_file(&path)?;
+                report.removed.push(file.path.clone());
+            }
+            Ok(_) => report.modified.push(file.path.clone()),
+            Err(_) => report.missing.push(file.path.clone()),
+        }
+    }
+    let mut folders = record.folders.clone();
+    folders.sort_by_key(|f| std::cmp::Reverse(f.matches('/').count()));
+    for folder in folders {
+        if fs::remove_dir(vault.join(&folder)).is_ok() {
+            report.removed_folders.push(folder);
+        }
+    }
+    save_installed(&installed)?;
+    if !report.modified.is_empty() {
+        log::info!(
+            "[vault_packs] left {} modified files of {}",
+            report.modified.len(),
+            pack_id
+        );
+    }
+    audit::record(
+        "uninstall_vault_pack",
+        None,
+        serde_json::json!({ "pack_id": pack_id, "vault": vault_key, "kept": report.modified }),
+    );
+    Ok(report)
+}
+
+// ─── Packs ──────────────────────────────────────────────────────────────────
+
+fn load(source: &PackSource) -> CommandResult<Pack> {
+    let mut pack = match source {
+        PackSource::Bundled(id) => bundled(id)?,
+        PackSource::Archive(path) => from_archive(Path::new(path))?,
+    };
+    for file in &mut pack.manifest.files {
+        file.sha256 = file.sha256.to_lowercase();
+    }
+    validate(&pack)?;
+    Ok(pack)
+}
+
+fn bundled(id: &str) -> CommandResult<Pack> {
+    let bundled = BUNDLED.iter().find(|p| p.id == id).ok_or_else(|| {
+        CommandError::new(ErrorCode::NotFound, format!("Unknown pack: {}", id)).with("pack_id", id)
+    })?;
+    Ok(Pack {
+        manifest: PackManifest {
+            id: bundled.id.into(),
+            name: bundled.name.into(),
+            version: Some(env!("CARGO_PKG_VERSION").into()),
+            folders: bundled.folders.iter().map(|f| f.to_string()).collect(),
+            files: bundled
+                .files
+                .iter()
+                .map(|(path, content)| PackFile {
+                    path: path.to_string(),
+                    sha256: write_guard::sha256(content.as_bytes()),
+                })
+                .collect(),
+        },
+        contents: bundled
+            .files
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.as_bytes().to_vec()))
+            .collect(),
+    })
+}
+
+fn from_archive(archive: &Path) -> CommandResult<Pack> {
+    let file = File::open(archive).map_err(|e| {
+        CommandError::new(
+            ErrorCode::NotFound,
+            format!("Can't open pack {}: {}", archive.display(), e),
+        )
+        .with("path", archive.to_string_lossy())
+    })?;
+    let mut manifest = None;
+    let mut contents = BTreeMap::new();
+    let mut total = 0;
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    for entry in tar.entries().map_err(|e| invalid(archive, e))? {
+        let mut entry = entry.map_err(|e| invalid(archive, e))?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| invalid(archive, e))?
+            .to_string_lossy()
+            .replace('\\', "/");
+        total += entry.size();
+        if total > MAX_PACK_BYTES {
+            return Err(CommandError::new(
+                ErrorCode::TooLarge,
+                format!("Pack unpacks to more than {} bytes", MAX_PACK_BYTES),
+            )
+            .with("path", archive.to_string_lossy()));
+        }
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| invalid(archive, e))?;
+        let name = name.trim_start_matches("./");
+        if name == MANIFEST_FILE {
+            manifest = Some(
+                serde_json::from_slice::<PackManifest>(&bytes).map_err(|e| invalid(archive, e))?,
+            );
+        } else if let Some(path) = name.strip_prefix(&format!("{}/", FILES_DIR)) {
+            contents.insert(path.to_string(), bytes);
+        } else {
+            return Err(invalid(archive, format!("unexpected entry {}", name)));
+        }
+    }
+    let manifest = manifest.ok_or_else(|| invalid(archive, "no pack.json"))?;
+    Ok(Pack { manifest, contents })
+}
+
+/// Safe paths, no repeats, and exactly the declared files with their hashes.
+fn validate(pack: &Pack) -> CommandResult<()> {
+    let manifest = &pack.manifest;
+    let bad = |message: String| {
+        Err(CommandError::new(ErrorCode::InvalidInput, message).with("pack_id", &manifest.id))
+    };
+    if manifest.id.is_empty()
+        || !manifest
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return bad(format!("Invalid pack id: {:?}", manifest.id));
+    }
+    let mut declared = BTreeSet::new();
+    for path in manifest
+        .files
+        .iter()
+        .map(|f| &f.path)
+        .chain(&manifest.folders)
+    {
+        if !is_safe_relative(path) {
+            return bad(format!("Unsafe path in pack: {}", path));
+        }
+    }
+    for file in &manifest.files {
+        if !declared.insert(file.path.as_str()) {
+            return bad(format!("{} is declared twice", file.path));
+        }
+        match pack.contents.get(&file.path) {
+            Some(bytes) if write_guard::sha256(bytes) == file.sha256 => {}
+            Some(_) => return bad(format!("{} doesn't match its hash", file.path)),
+            None => return bad(format!("{} is declared but missing", file.path)),
+        }
+    }
+    if let Some(extra) = pack
+        .contents
+        .keys()
+        .find(|p| !declared.contains(p.as_str()))
+    {
+        return bad(format!("{} isn't declared in the manifest", extra));
+    }
+    Ok(())
+}
+
+fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn invalid(archive: &Path, detail: impl std::fmt::Display) -> CommandError {
+    CommandError::new(
+        ErrorCode::InvalidInput,
+        format!("Invalid pack {}: {}", archive.display(), detail),
+    )
+    .with("path", archive.to_string_lossy())
+}
+
+fn staging_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target.with_file_name(format!(".{}{}", name, STAGING_SUFFIX))
+}
+
+fn relative_key(vault: &Path, path: &Path) -> String {
+    path.strip_prefix(vault)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+// ─── Persistence ────────────────────────────────────────────────────────────
+
+fn installed_path() -> CommandResult<PathBuf> {
+    Ok(home::data_dir()?.join(INSTALLED_FILE))
+}
+
+fn load_installed() -> Vec<InstalledPack> {
+    installed_path()
+        .ok()
+        .and_then(|path| integrity::load_json(&path, None))
+        .unwrap_or_default()
+}
+
+fn save_installed(installed: &[InstalledPack]) -> CommandResult<()> {
+    integrity::persist_json(&installed_path()?, installed, None)
+}
+
+impl integrity::Validate for [InstalledPack] {
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .iter()
+            .find(|i| i.pack_id.is_empty() || i.vault_path.is_empty())
+        {
+            Some(install) => Err(format!(
+                "install of {:?} has no pack or vault",
+                install.pack_id
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn archive(home: &TestHome, manifest: &serde_json::Value, files: &[(&str, &str)]) -> String {
+        let path = home.join("pack.tar.gz");
+        let gz = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        let mut add = |name: &str, bytes: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, bytes).unwrap();
+        };
+        add(MANIFEST_FILE, manifest.to_string().as_bytes());
+        for (name, content) in files {
+            add(name, content.as_bytes());
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn uninstall_keeps_files_edited_since_install() {
+        let home = TestHome::new();
+        home.write("Vault/Inbox/keep.md", "mine");
+        home.write(
+            "Vault/Templates/Meeting.md",
+            include_str!("../packs/starter/Templates/Meeting.md"),
+        );
+        let vault = home.join("Vault");
+        let source = PackSource::Bundled("starter".into());
+
+        let preview = install(&vault, &source, true).unwrap();
+        assert!(preview.dry_run.would_have);
+        assert_eq!(preview.unchanged, ["Templates/Meeting.md"]);
+        assert!(!vault.join("Dashboard.md").exists());
+
+        let report = install(&vault, &source, false).unwrap();
+        assert_eq!(report.created, ["Dashboard.md", "Templates/Daily note.md"]);
+        assert_eq!(report.folders, ["Archive", "Projects"]);
+        assert!(install(&vault, &source, false).is_err());
+
+        home.write("Vault/Dashboard.md", "# My dashboard\n");
+        let removed = uninstall(&vault, "starter").unwrap();
+        assert_eq!(removed.removed, ["Templates/Daily note.md"]);
+        assert_eq!(removed.modified, ["Dashboard.md"]);
+        assert!(vault.join("Dashboard.md").exists());
+        // Files the pack didn't create stay, and so do their folders
+        assert!(vault.join("Templates/Meeting.md").exists());
+        assert!(vault.join("Inbox/keep.md").exists());
+        assert!(!vault.join("Projects").exists());
+        assert!(uninstall(&vault, "starter").is_err());
+    }
+
+    #[test]
+    fn archives_must_match_their_manifest() {
+        let home = TestHome::new();
+        home.write("Vault/Notes/Plan.md", "existing");
+        let vault = home.join("Vault");
+        let plan = "# Plan\n";
+        let manifest = |path: &str, sha256: &str| {
+            serde_json::json!({
+                "id": "client-onboarding",
+                "name": "Client onboarding",
+                "files": [{ "path": path, "sha256": sha256 }],
+            })
+        };
+        let good = write_guard::sha256(plan.as_bytes());
+
+        let source = |path| PackSource::Archive(path);
+        let traversal = archive(
+            &home,
+            &manifest("../escape.md", &good),
+            &[("files/escape.md", plan)],
+        );
+        assert!(install(&vault, &source(traversal), true).is_err());
+        let wrong_hash = archive(
+            &home,
+            &manifest("Notes/Plan.md", "00"),
+            &[("files/Notes/Plan.md", plan)],
+        );
+        assert!(install(&vault, &source(wrong_hash), true).is_err());
+        let undeclared = archive(
+            &home,
+            &manifest("Notes/Plan.md", &good),
+            &[("files/Notes/Plan.md", plan), ("files/extra.md", "")],
+        );
+        assert!(install(&vault, &source(undeclared), true).is_err());
+
+        let conflicting = archive(
+            &home,
+            &manifest("Notes/Plan.md", &good),
+            &[("files/Notes/Plan.md", plan)],
+        );
+        let preview = install(&vault, &source(conflicting.clone()), true).unwrap();
+        assert_eq!(preview.conflicts, ["Notes/Plan.md"]);
+        let err = install(&vault, &source(conflicting), false).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert_eq!(
+            fs::read_to_string(vault.join("Notes/Plan.md")).unwrap(),
+            "existing"
+        );
+    }
+}
@@ -0,0 +1,741 @@
+// Web archiving
+//
+// `archive_url` saves a web page into a store as a Markdown note, for
+// reading later even after the page changes or disappears:
+// - The page is fetched the way smart paste fetches links (see
+//   `url_metadata::get_public`): `http` and `https` only, every redirect
+//   checked against internal addresses, at most `MAX_PAGE_BYTES`.
+// - The article is picked out of the page's furniture and converted to
+//   Markdown by `html`. A page that isn't HTML, or where fewer than
+//   `MIN_ARTICLE_CHARS` of text survive, fails with `ContentNotExtractable`
+//   rather than leaving an empty note.
+// - Images are downloaded into the vault's attachment folder (see
+//   `store_kinds::attachment_folder`), at most `MAX_IMAGES` of at most
+//   `MAX_IMAGE_BYTES` each, and linked relative to the note. An image with
+//   the same bytes as one in the batch or already in the folder is reused.
+//   Images that can't be fetched keep their web URL and are reported.
+// - The note is named after the page's title, in `folder` (`Clippings` by
+//   default), with `title`, `source`, `archived`, and `site` frontmatter.
+// Attachments and the note are written under the write lock. If any write
+// fails, the attachments and folders this archive created are removed, so a
+// failed archive leaves nothing behind.
+
+use chrono::{DateTime, Local};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::filenames::{sanitize_filename, NameChange, SanitizeOptions};
+use crate::frontmatter::{self, Edit};
+use crate::html::{self, Document};
+use crate::settings::{self, Settings};
+use crate::text_style::{self, TextWriteOptions};
+use crate::url_metadata::{self, get_public, is_public, read_capped};
+use crate::{notes, store_kinds, write_guard};
+
+const PAGE_TIMEOUT: Duration = Duration::from_secs(30);
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(20);
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_IMAGES: usize = 50;
+const MIN_ARTICLE_CHARS: usize = 200;
+const MAX_NAME_BYTES: usize = 120;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ArchiveOptions {
+    /// Store-relative folder for the note.
+    pub folder: String,
+    /// Store-relative folder for images, instead of the vault's setting.
+    pub attachment_folder: Option<String>,
+    pub download_images: bool,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        ArchiveOptions {
+            folder: "Clippings".to_string(),
+            attachment_folder: None,
+            download_images: true,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ArchivedPage {
+    pub path: String,
+    pub relative_path: String,
+    pub title: String,
+    /// The page's canonical URL, or where redirects ended.
+    pub source_url: String,
+    /// Store-relative paths of the images written.
+    pub attachments: Vec<String>,
+    /// Store-relative paths of images that were already there.
+    pub reused_attachments: Vec<String>,
+    pub skipped_images: Vec<SkippedImage>,
+    pub words: usize,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SkippedImage {
+    pub url: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    TooLarge,
+    NotAnImage,
+    AddressNotAllowed,
+    Failed,
+    /// Past `MAX_IMAGES`.
+    TooMany,
+}
+
+/// A page ready to write.
+struct Clipping {
+    root: PathBuf,
+    note_folder: PathBuf,
+    attachment_folder: PathBuf,
+    url: Url,
+    source_url: String,
+    title: String,
+    site_name: Option<String>,
+    doc: Document,
+    content: html::NodeId,
+    images: Vec<Image>,
+    skipped_images: Vec<SkippedImage>,
+}
+
+struct Image {
+    url: Url,
+    bytes: Vec<u8>,
+    extension: &'static str,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn archive_url(
+    window: tauri::WebviewWindow,
+    url: String,
+    vault_path: String,
+    options: Option<ArchiveOptions>,
+) -> CommandResult<ArchivedPage> {
+    dry_run::check_writable(window.label(), None, "archive_url")?;
+    let parsed = url_metadata::parse_url(&url)?;
+    let options = options.unwrap_or_default();
+    let settings = settings::load_settings();
+    let clipping = fetch(
+        &parsed,
+        Path::new(&vault_path),
+        &options,
+        &settings,
+        is_public,
+    )
+    .await?;
+    let now = Local::now();
+    tauri::async_runtime::spawn_blocking(move || save(clipping, now))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+// ─── Fetching ───────────────────────────────────────────────────────────────
+
+async fn fetch(
+    url: &Url,
+    root: &Path,
+    options: &ArchiveOptions,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<Clipping> {
+    if !root.is_dir() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("Vault not found: {}", root.display()),
+        )
+        .with("path", root.to_string_lossy()));
+    }
+    let note_folder = root.join(inside(&options.folder)?);
+    let attachment_folder = match &options.attachment_folder {
+        Some(folder) => root.join(inside(folder)?),
+        None => store_kinds::attachment_folder(root, &note_folder),
+    };
+
+    let (url, page) = tokio::time::timeout(PAGE_TIMEOUT, fetch_page(url, settings, allowed))
+        .await
+        .map_err(|_| timed_out(url, PAGE_TIMEOUT))??;
+    let Some(page) = page else {
+        return Err(not_extractable(&url, 0));
+    };
+    let head = url_metadata::parse_head(&page);
+    let doc = html::parse(&page);
+    let content = doc.main_content();
+    let chars = content.map_or(0, |id| doc.text(id).chars().count());
+    let Some(content) = content.filter(|_| chars >= MIN_ARTICLE_CHARS) else {
+        return Err(not_extractable(&url, chars));
+    };
+
+    let title = head
+        .title
+        .clone()
+        .or_else(|| doc.find("h1").map(|h1| doc.text(h1)))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| url.host_str().unwrap_or_default().to_string());
+    let source_url = head
+        .canonical
+        .as_deref()
+        .and_then(|c| url.join(c).ok())
+        .filter(|c| matches!(c.scheme(), "http" | "https"))
+        .unwrap_or_else(|| url.clone())
+        .to_string();
+
+    let mut images = Vec::new();
+    let mut skipped_images = Vec::new();
+    if options.download_images {
+        for (i, image_url) in doc.image_urls(content, &url).into_iter().enumerate() {
+            let skip = |reason| SkippedImage {
+                url: image_url.to_string(),
+                reason,
+            };
+            if i >= MAX_IMAGES {
+                skipped_images.push(skip(SkipReason::TooMany));
+                continue;
+            }
+            let fetched =
+                tokio::time::timeout(IMAGE_TIMEOUT, fetch_image(&image_url, settings, allowed))
+                    .await
+                    .unwrap_or(Err(SkipReason::Failed));
+            match fetched {
+                Ok((bytes, extension)) => images.push(Image {
+                    url: image_url,
+                    bytes,
+                    extension,
+                }),
+                Err(reason) => skipped_images.push(skip(reason)),
+            }
+        }
+    }
+
+    Ok(Clipping {
+        root: root.to_path_buf(),
+        note_folder,
+        attachment_folder,
+        url,
+        source_url,
+        title,
+        site_name: head.site_name,
+        doc,
+        content,
+        images,
+        skipped_images,
+    })
+}
+
+/// The final URL, and the page's text when it is HTML.
+async fn fetch_page(
+    url: &Url,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> CommandResult<(Url, Option<String>)> {
+    let accept = "text/html,application/xhtml+xml;q=0.9,*/*;q=0.5";
+    let (url, response) = get_public(url, settings, accept, allowed).await?;
+    let mime = mime_of(&response);
+    let html = mime
+        .as_deref()
+        .is_none_or(|m| m == "text/html" || m == "application/xhtml+xml");
+    if !html {
+        return Ok((url, None));
+    }
+    let (body, truncated) = read_capped(response, &url, MAX_PAGE_BYTES).await?;
+    if truncated {
+        log::warn!(
+            "[web_archive] {} is over {} bytes; archiving the start",
+            url,
+            MAX_PAGE_BYTES
+        );
+    }
+    let text = String::from_utf8_lossy(&body).to_string();
+    Ok((url, Some(text)))
+}
+
+async fn fetch_image(
+    url: &Url,
+    settings: &Settings,
+    allowed: fn(IpAddr) -> bool,
+) -> Result<(Vec<u8>, &'static str), SkipReason> {
+    let (url, response) = get_public(url, settings, "image/*", allowed)
+        .await
+        .map_err(|e| {
+            log::warn!("[web_archive] skipping image {}: {}", url, e.message);
+            match e.code {
+                ErrorCode::AddressNotAllowed => SkipReason::AddressNotAllowed,
+                _ => SkipReason::Failed,
+            }
+        })?;
+    let extension = mime_of(&response)
+        .as_deref()
+        .and_then(image_extension)
+        .ok_or(SkipReason::NotAnImage)?;
+    let (bytes, truncated) = read_capped(response, &url, MAX_IMAGE_BYTES)
+        .await
+        .map_err(|_| SkipReason::Failed)?;
+    if truncated {
+        return Err(SkipReason::TooLarge);
+    }
+    Ok((bytes, extension))
+}
+
+fn mime_of(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|t| t.split(';').next())
+        .map(|t| t.trim().to_ascii_lowercase())
+}
+
+fn image_extension(mime: &str) -> Option<&'static str> {
+    Some(match mime {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/avif" => "avif",
+        "image/bmp" => "bmp",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => return None,
+    })
+}
+
+// ─── Writing ────────────────────────────────────────────────────────────────
+
+fn save(clipping: Clipping, now: DateTime<Local>) -> CommandResult<ArchivedPage> {
+    let _guard = write_guard::lock();
+    let mut created = Created::default();
+    let result = write_all(&clipping, now, &mut created);
+    if result.is_err() {
+        created.remove();
+    }
+    result
+}
+
+fn write_all(
+    clipping: &Clipping,
+    now: DateTime<Local>,
+    created: &mut Created,
+) -> CommandResult<ArchivedPage> {
+    let options = SanitizeOptions {
+        max_bytes: MAX_NAME_BYTES,
+        ..Default::default()
+    };
+
+    // Images first, so the note can link to where they landed
+    let mut links: HashMap<Url, String> = HashMap::new();
+    let mut by_hash: HashMap<String, PathBuf> = HashMap::new();
+    let mut attachments = Vec::new();
+    let mut reused_attachments = Vec::new();
+    for image in &clipping.images {
+        let hash = write_guard::sha256(&image.bytes);
+        let path = match by_hash.get(&hash) {
+            Some(path) => path.clone(),
+            None => {
+                let path = match existing_copy(&clipping.attachment_folder, &image.bytes, &hash) {
+                    Some(path) => {
+                        reused_attachments.push(relative_key(&clipping.root, &path));
+                        path
+                    }
+                    None => {
+                        created.folder(&clipping.attachment_folder)?;
+                        let name = format!("{}.{}", image_stem(&image.url), image.extension);
+                        let path = sanitize_filename(&name, &options)
+                            .unique_in(&clipping.attachment_folder, &options);
+                        fs::write(&path, &image.bytes)?;
+                        created.files.push(path.clone());
+                        attachments.push(relative_key(&clipping.root, &path));
+                        path
+                    }
+                };
+                by_hash.insert(hash, path.clone());
+                path
+            }
+        };
+        links.insert(
+            image.url.clone(),
+            relative_link(&clipping.note_folder, &path),
+        );
+    }
+
+    let markdown = clipping
+        .doc
+        .to_markdown(clipping.content, &clipping.url, &|url| {
+            links.get(url).cloned()
+        });
+    let body = if markdown.starts_with("# ") {
+        markdown
+    } else {
+        format!("# {}\n\n{}", clipping.title, markdown)
+    };
+    let mut edits = vec![
+        set("title", &clipping.title),
+        set("source", &clipping.source_url),
+        set("archived", &now.format("%Y-%m-%dT%H:%M:%S%:z").to_string()),
+    ];
+    if let Some(site) = &clipping.site_name {
+        edits.push(set("site", site));
+    }
+    let text =
+        frontmatter::apply(&body, &edits).map_err(|e| CommandError::new(ErrorCode::Internal, e))?;
+
+    let mut name = sanitize_filename(&format!("{}.md", clipping.title), &options);
+    if name.changes.contains(&NameChange::Blank) {
+        name = sanitize_filename("Clipping.md", &options);
+    }
+    created.folder(&clipping.note_folder)?;
+    let path = name.unique_in(&clipping.note_folder, &options);
+    let (ending, bom) = text_style::resolve(&path, None, &TextWriteOptions::default())?;
+    notes::write_atomic(&path, &text_style::encode(&text, ending, bom))?;
+    created.files.push(path.clone());
+
+    for attachment in &attachments {
+        activity::record(ActivityKind::Write, &clipping.root.join(attachment), None);
+    }
+    activity::record(ActivityKind::Write, &path, None);
+    log::info!(
+        "[web_archive] archived {} to {} with {} images",
+        clipping.url,
+        path.display(),
+        attachments.len() + reused_attachments.len()
+    );
+    Ok(ArchivedPage {
+        relative_path: relative_key(&clipping.root, &path),
+        path: path.to_string_lossy().to_string(),
+        title: clipping.title.clone(),
+        source_url: clipping.source_url.clone(),
+        attachments,
+        reused_attachments,
+        skipped_images: clipping.skipped_images.clone(),
+        words: clipping
+            .doc
+            .text(clipping.content)
+            .split_whitespace()
+            .count(),
+    })
+}
+
+/// What an archive has written so far, to undo it if a later write fails.
+#[derive(Default)]
+struct Created {
+    files: Vec<PathBuf>,
+    /// Deepest last.
+    folders: Vec<PathBuf>,
+}
+
+impl Created {
+    fn folder(&mut self, folder: &Path) -> CommandResult<()> {
+        let mut missing: Vec<PathBuf> = folder
+            .ancestors()
+            .take_while(|f| !f.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        fs::create_dir_all(folder)?;
+        missing.reverse();
+        self.folders.extend(missing);
+        Ok(())
+    }
+
+    fn remove(&self) {
+        for file in self.files.iter().rev() {
+            if let Err(e) = fs::remove_file(file) {
+                log::warn!("[web_archive] couldn't remove {}: {}", file.display(), e);
+            }
+        }
+        // Only empty folders go; `remove_dir` refuses the rest
+        for folder in self.folders.iter().rev() {
+            let _ = fs::remove_dir(folder);
+        }
+    }
+}
+
+/// A file in `folder` with exactly `bytes`.
+fn existing_copy(folder: &Path, bytes: &[u8], hash: &str) -> Option<PathBuf> {
+    fs::read_dir(folder)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.len() == bytes.len() as u64)
+        })
+        .map(|entry| entry.path())
+        .find(|path| fs::read(path).is_ok_and(|found| write_guard::sha256(&found) == hash))
+}
+
+/// The name an image's URL gives it, without its extension.
+fn image_stem(url: &Url) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(percent_decode)
+        .unwrap_or_default();
+    let stem = match segment.rsplit_once('.') {
+        Some((stem, _)) => stem.to_string(),
+        None => segment,
+    };
+    match stem.trim() {
+        "" => "image".to_string(),
+        stem => stem.to_string(),
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text.get(i + 1..i + 3).filter(|_| bytes[i] == b'%');
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// `path` relative to `folder`, with `/` separators.
+fn relative_link(folder: &Path, path: &Path) -> String {
+    let from: Vec<Component> = folder.components().collect();
+    let to: Vec<Component> = path.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/")
+}
+
+fn relative_key(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `folder` as a path under the store root.
+fn inside(folder: &str) -> CommandResult<PathBuf> {
+    let path = Path::new(folder.trim_matches('/'));
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not a folder inside the store", folder),
+        )
+        .with("folder", folder));
+    }
+    Ok(path.to_path_buf())
+}
+
+fn set(key: &str, value: &str) -> Edit {
+    Edit::Set {
+        key: key.to_string(),
+        value: Value::String(value.to_string()),
+    }
+}
+
+fn not_extractable(url: &Url, chars: usize) -> CommandError {
+    CommandError::new(
+        ErrorCode::ContentNotExtractable,
+        format!("No article text found on {}", url),
+    )
+    .with("url", url.as_str())
+    .with("text_chars", chars)
+}
+
+fn timed_out(url: &Url, limit: Duration) -> CommandError {
+    CommandError::new(
+        ErrorCode::Network,
+        format!("{} took longer than {:?}", url, limit),
+    )
+    .with("url", url.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ProxyMode;
+    use crate::testing::{MockServer, TestHome};
+    use chrono::TimeZone;
+
+    const ARTICLE: &str = "Archiving keeps a page readable after it changes, moves, or \
+        disappears, which happens to most links within a few years. A copy in \
+        the vault can be searched, linked, and annotated like any other note, \
+        and nothing breaks.";
+
+    fn archive(url: &str, root: &Path, options: &ArchiveOptions) -> CommandResult<ArchivedPage> {
+        let mut settings = Settings::default();
+        settings.proxy.mode = ProxyMode::None;
+        let url = url_metadata::parse_url(url)?;
+        let clipping =
+            tauri::async_runtime::block_on(fetch(&url, root, options, &settings, |_| true))?;
+        let now = Local.with_ymd_and_hms(2026, 3, 4, 9, 30, 0).unwrap();
+        save(clipping, now)
+    }
+
+    fn page(body: &str) -> String {
+        MockServer::response(
+            200,
+            &[("Content-Type", "text/html; charset=utf-8")],
+            &format!(
+                "<html><head><title>Link Rot</title>\
+                 <meta property=\"og:site_name\" content=\"Example\"></head>\
+                 <body><nav><a href=\"/\">Home</a></nav>{}</body></html>",
+                body
+            ),
+        )
+    }
+
+    fn png(bytes: &str) -> String {
+        MockServer::response(200, &[("Content-Type", "image/png")], bytes)
+    }
+
+    #[test]
+    fn archives_the_article_with_local_images() {
+        let home = TestHome::new();
+        home.write(
+            "vault/.obsidian/app.json",
+            r#"{"attachmentFolderPath": "Assets"}"#,
+        );
+        home.write("vault/Assets/old.png", "same pixels");
+        let body = format!(
+            "<article><h2>Why</h2><p>{}</p>\
+             <p><img src=\"/a.png\" alt=\"First\"> <img src=\"/b%20c.png\"></p>\
+             <p><img src=\"/a.png\"><img src=\"/page.html\"></p></article>",
+            ARTICLE
+        );
+        // The page, /a.png, /b%20c.png, then /page.html, which isn't an image
+        let server = MockServer::start(vec![
+            page(&body),
+            png("new pixels"),
+            png("same pixels"),
+            page(""),
+        ]);
+        let root = home.join("vault");
+        let archived = archive(&server.url("/post"), &root, &ArchiveOptions::default()).unwrap();
+
+        assert_eq!(archived.relative_path, "Clippings/Link Rot.md");
+        assert_eq!(archived.title, "Link Rot");
+        assert_eq!(archived.source_url, server.url("/post"));
+        assert_eq!(archived.attachments, vec!["Assets/a.png"]);
+        assert_eq!(archived.reused_attachments, vec!["Assets/old.png"]);
+        assert_eq!(
+            archived.skipped_images,
+            vec![SkippedImage {
+                url: server.url("/page.html"),
+                reason: SkipReason::NotAnImage,
+            }]
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("Assets/a.png")).unwrap(),
+            "new pixels"
+        );
+
+        let note = fs::read_to_string(root.join("Clippings/Link Rot.md")).unwrap();
+        assert!(note.starts_with("---\ntitle: Link Rot\n"), "{}", note);
+        assert!(note.contains("archived: "), "{}", note);
+        assert!(note.contains("2026-03-04T09:30:00"), "{}", note);
+        assert!(note.contains("site: Example\n"), "{}", note);
+        assert!(
+            note.contains("---\n# Link Rot\n\n## Why\n\nArchiving keeps"),
+            "{}",
+            note
+        );
+        assert!(!note.contains("Home"), "{}", note);
+        assert!(
+            note.contains(
+                "![First](../Assets/a.png) ![](../Assets/old.png)\n\n![](../Assets/a.png)"
+            ),
+            "{}",
+            note
+        );
+        assert!(
+            note.contains(&format!("![]({})", server.url("/page.html"))),
+            "{}",
+            note
+        );
+        // The article, three images, and the non-image; /a.png only once
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn refuses_pages_without_an_article() {
+        let home = TestHome::new();
+        home.write("vault/note.md", "");
+        let root = home.join("vault");
+
+        let server = MockServer::start(vec![page("<p>Just a short line.</p>")]);
+        let err = archive(&server.url("/"), &root, &ArchiveOptions::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ContentNotExtractable);
+
+        let pdf = MockServer::start(vec![MockServer::response(
+            200,
+            &[("Content-Type", "application/pdf")],
+            "%PDF-1.7",
+        )]);
+        let err = archive(&pdf.url("/paper.pdf"), &root, &ArchiveOptions::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ContentNotExtractable);
+        assert!(!root.join("Clippings").exists());
+
+        let options = ArchiveOptions {
+            folder: "../outside".to_string(),
+            ..Default::default()
+        };
+        let err = archive(&server.url("/"), &root, &options).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn a_failed_note_write_removes_new_attachments() {
+        let home = TestHome::new();
+        home.write("vault/note.md", "");
+        // A file where the note's folder should be, so the note can't be written
+        home.write("vault/Clippings", "");
+        let server = MockServer::start(vec![
+            page(&format!(
+                "<div class=\"content\"><p>{}</p><img src=\"x.png\"></div>",
+                ARTICLE
+            )),
+            png("pixels"),
+        ]);
+        let root = home.join("vault");
+        let options = ArchiveOptions {
+            attachment_folder: Some("Media/Web".to_string()),
+            ..Default::default()
+        };
+        assert!(archive(&server.url("/a/"), &root, &options).is_err());
+        assert!(!root.join("Media").exists());
+        assert_eq!(server.requests().len(), 2);
+    }
+}
@@ -0,0 +1,246 @@
+// WhatsApp chats
+//
+// The linked account's chats come from the local WhatsApp bridge at
+// `settings.whatsapp.bridge_url`. The user picks what the agent may do with
+// each one, and the choice is saved in the tenant config. Chat names and
+// listings are cached in the tenant folder only. Nothing here talks to the
+// orchestrator. Every command fails with `NotLinked` until the tenant has a
+// WhatsApp session.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::policy::{self, CommandGroup};
+use crate::tenant_config::{self, ChatPolicy};
+use crate::{audit, sessions, settings, tenant_windows};
+
+pub const PROVIDER_ID: &str = "whatsapp";
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 500;
+const BRIDGE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WhatsAppChat {
+    pub chat_id: String,
+    pub name: String,
+    pub is_group: bool,
+    pub last_message_at: Option<String>,
+    pub unread_count: u32,
+    /// `None` until the user chooses.
+    #[serde(default)]
+    pub policy: Option<ChatPolicy>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ChatPage {
+    pub chats: Vec<WhatsAppChat>,
+    pub total: usize,
+    /// Pass back as `offset` for the next page; `None` on the last one.
+    pub next_offset: Option<usize>,
+}
+
+/// A chat as the bridge reports it (whatsapp-web.js field names).
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BridgeChat {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    is_group: bool,
+    /// Unix seconds of the last message.
+    timestamp: Option<i64>,
+    #[serde(default)]
+    unread_count: u32,
+}
+
+#[derive(Deserialize)]
+struct BridgeChats {
+    chats: Vec<BridgeChat>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// A page of the linked account's chats, most recent first. `offset` 0
+/// refreshes from the bridge; later pages come from that same snapshot so
+/// paging stays consistent while new messages arrive.
+#[tauri::command]
+pub async fn list_whatsapp_chats(
+    tenant_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> CommandResult<ChatPage> {
+    ensure_linked(&tenant_id)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let cached = (offset > 0).then(|| load_chat_cache(&tenant_id)).flatten();
+    let chats = match cached {
+        Some(chats) => chats,
+        None => {
+            let chats = fetch_chats(&tenant_id).await?;
+            save_chat_cache(&tenant_id, &chats)?;
+            chats
+        }
+    };
+
+    let policies = tenant_config::load(&tenant_id).whatsapp_chats;
+    let total = chats.len();
+    let page = chats
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|mut chat| {
+            chat.policy = policies.get(&chat.chat_id).copied();
+            chat
+        })
+        .collect();
+    Ok(ChatPage {
+        chats: page,
+        total,
+        next_offset: (offset + limit < total).then_some(offset + limit),
+    })
+}
+
+/// Save what the agent may do with a chat.
+#[tauri::command]
+pub fn set_whatsapp_chat_policy(
+    tenant_id: String,
+    chat_id: String,
+    policy: ChatPolicy,
+) -> CommandResult<()> {
+    ensure_linked(&tenant_id)?;
+    if chat_id.trim().is_empty() {
+        return Err(CommandError::new(ErrorCode::InvalidInput, "Empty chat id"));
+    }
+    tenant_config::update(&tenant_id, |config| {
+        config.whatsapp_chats.insert(chat_id.clone(), policy);
+    })?;
+    // Chat ids are phone numbers for direct chats; keep them out of the log
+    audit::record(
+        "whatsapp_chat_policy_set",
+        Some(&tenant_id),
+        serde_json::json!({ "policy": policy }),
+    );
+    Ok(())
+}
+
+/// The saved policy for a chat, if the user has chosen one.
+pub fn chat_policy(tenant_id: &str, chat_id: &str) -> Option<ChatPolicy> {
+    tenant_config::load(tenant_id)
+        .whatsapp_chats
+        .get(chat_id)
+        .copied()
+}
+
+// ─── Bridge ─────────────────────────────────────────────────────────────────
+
+/// `NotLinked` unless the tenant has a WhatsApp session.
+pub fn ensure_linked(tenant_id: &str) -> CommandResult<()> {
+    sessions::validate_id("tenant_id", tenant_id)?;
+    policy::check(Some(tenant_id), CommandGroup::Whatsapp)?;
+    match sessions::find_session_dir(&sessions::sessions_root()?, tenant_id, PROVIDER_ID) {
+        Some(_) => Ok(()),
+        None => Err(not_linked(tenant_id)),
+    }
+}
+
+pub fn not_linked(tenant_id: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::NotLinked,
+        format!("No WhatsApp account is linked for {}", tenant_id),
+    )
+    .with("tenant_id", tenant_id)
+}
+
+/// Client for the local bridge: no proxy, short timeout.
+pub fn bridge_client() -> CommandResult<reqwest::Client> {
+    reqwest::Client::builder()
+        .no_proxy()
+        .timeout(BRIDGE_TIMEOUT)
+        .build()
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))
+}
+
+pub fn bridge_url(tenant_id: &str, path: &str) -> String {
+    format!(
+        "{}/tenants/{}/{}",
+        settings::load_settings()
+            .whatsapp
+            .bridge_url
+            .trim_end_matches('/'),
+        tenant_id,
+        path
+    )
+}
+
+async fn fetch_chats(tenant_id: &str) -> CommandResult<Vec<WhatsAppChat>> {
+    let url = bridge_url(tenant_id, "chats");
+    let response = bridge_client()?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?;
+    // The bridge answers 409 when the tenant's client isn't logged in
+    if response.status() == reqwest::StatusCode::CONFLICT {
+        return Err(not_linked(tenant_id));
+    }
+    let response = response
+        .error_for_status()
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?;
+    let body: BridgeChats = response
+        .json()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Network, e.to_string()).with("url", &url))?;
+
+    let mut chats: Vec<WhatsAppChat> = body
+        .chats
+        .into_iter()
+        .map(|chat| WhatsAppChat {
+            name: if chat.name.is_empty() {
+                chat.id.clone()
+            } else {
+                chat.name
+            },
+            chat_id: chat.id,
+            is_group: chat.is_group,
+            last_message_at: chat
+                .timestamp
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                .map(|t| t.to_rfc3339()),
+            unread_count: chat.unread_count,
+            policy: None,
+        })
+        .collect();
+    // RFC 3339 UTC strings sort chronologically
+    chats.sort_by(|a, b| b.last_message_at.cmp(&a.last_message_at));
+    Ok(chats)
+}
+
+// ─── Cache ──────────────────────────────────────────────────────────────────
+
+fn chat_cache_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?
+        .join("whatsapp")
+        .join("chats.json"))
+}
+
+fn load_chat_cache(tenant_id: &str) -> Option<Vec<WhatsAppChat>> {
+    let raw = fs::read(chat_cache_path(tenant_id).ok()?).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+fn save_chat_cache(tenant_id: &str, chats: &[WhatsAppChat]) -> CommandResult<()> {
+    let path = chat_cache_path(tenant_id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let raw = serde_json::to_vec(chats).map_err(|e| e.to_string())?;
+    fs::write(path, raw)?;
+    Ok(())
+}
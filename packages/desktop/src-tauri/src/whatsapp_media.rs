@@ -0,0 +1,276 @@
+// WhatsApp media import
+//
+// The WhatsApp bridge forwards incoming media to the backend as
+// `whatsapp-media-received` events. Each file is written into the tenant's
+// configured inbox folder, in a subfolder per day. The inbox must be inside a
+// connected store. Media already imported (same SHA-256) is skipped. Filenames
+// are made safe for Windows, macOS, and Linux. Each import is recorded in
+// recent activity and announced with `whatsapp-media-imported`. It is also
+// logged to the store's daily note when that setting is on. Nothing is
+// imported in read-only mode.
+//
+// Media goes through `quarantine` first. Held media is announced with
+// `file-quarantined` instead, and finishes importing if it's released, without
+// the daily note line.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Listener, Manager};
+
+use crate::activity::{self, ActivityKind};
+use crate::artifact_download::ConflictPolicy;
+use crate::error::CommandResult;
+use crate::filenames::{sanitize_filename, NameChange, SanitizeOptions};
+use crate::maintenance::MaintenanceMode;
+use crate::policy::{self, CommandGroup};
+use crate::quarantine::{self, Admitted, Arrival, ScanVerdict, Source};
+use crate::read_only::ReadOnlyMode;
+use crate::store_kinds::{self, StoreKind};
+use crate::{notes, sessions, settings, stores, tenant_windows};
+
+const MAX_NAME_BYTES: usize = 120;
+
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+/// Payload of `whatsapp-media-received`.
+#[derive(Deserialize)]
+struct MediaReceived {
+    tenant_id: String,
+    message_id: String,
+    chat_id: Option<String>,
+    /// Sender's display name or number.
+    from: Option<String>,
+    filename: Option<String>,
+    mime_type: String,
+    /// File contents, base64.
+    data: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MediaImported {
+    pub tenant_id: String,
+    pub message_id: String,
+    pub chat_id: Option<String>,
+    pub path: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub mime_type: String,
+    pub scan: ScanVerdict,
+}
+
+// ─── Listener ───────────────────────────────────────────────────────────────
+
+/// Start importing media the bridge forwards. Imports run off the event loop.
+pub fn listen(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen_any("whatsapp-media-received", move |event| {
+        let media: MediaReceived = match serde_json::from_str(event.payload()) {
+            Ok(media) => media,
+            Err(e) => {
+                log::warn!("[whatsapp_media] malformed payload: {}", e);
+                return;
+            }
+        };
+        if handle.state::<ReadOnlyMode>().enabled() {
+            log::info!(
+                "[whatsapp_media] read-only mode: not importing {}",
+                media.message_id
+            );
+            return;
+        }
+        if handle.state::<MaintenanceMode>().enabled() {
+            log::info!(
+                "[whatsapp_media] maintenance mode: not importing {}",
+                media.message_id
+            );
+            return;
+        }
+        let app = handle.clone();
+        std::thread::spawn(move || match import(&app, &media) {
+            Ok(Some(imported)) => {
+                let _ = app.emit("whatsapp-media-imported", imported);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!(
+                "[whatsapp_media] import of {} failed: {}",
+                media.message_id,
+                e
+            ),
+        });
+    });
+}
+
+/// Write one media file into the tenant's inbox; `None` if it was skipped.
+fn import(app: &AppHandle, media: &MediaReceived) -> Result<Option<MediaImported>, String> {
+    sessions::validate_id("tenant_id", &media.tenant_id)?;
+    policy::check(Some(&media.tenant_id), CommandGroup::Whatsapp)?;
+    let settings = settings::load_settings();
+    let Some(inbox) = settings.whatsapp.inbox_paths.get(&media.tenant_id) else {
+        log::debug!("[whatsapp_media] no inbox for tenant {}", media.tenant_id);
+        return Ok(None);
+    };
+    let inbox = PathBuf::from(inbox);
+    // The inbox may not exist yet; check the deepest folder that does
+    let store = inbox
+        .ancestors()
+        .find(|p| p.exists())
+        .and_then(stores::store_containing)
+        .filter(|_| !inbox.components().any(|c| c == Component::ParentDir));
+    let Some(store) = store else {
+        return Err(format!(
+            "inbox {} is not inside a connected store",
+            inbox.display()
+        ));
+    };
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(media.data.trim())
+        .map_err(|e| format!("invalid media data: {}", e))?;
+    let sha256 = hex::encode(Sha256::digest(&bytes));
+
+    let guard = INDEX_LOCK.lock().unwrap();
+    let mut index = load_index(&media.tenant_id);
+    if index.get(&sha256).is_some_and(|p| Path::new(p).exists()) {
+        log::info!(
+            "[whatsapp_media] skipped duplicate {} from {}",
+            sha256,
+            media.message_id
+        );
+        return Ok(None);
+    }
+
+    let now = chrono::Local::now();
+    let folder = inbox.join(now.format("%Y-%m-%d").to_string());
+    fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+    let options = SanitizeOptions {
+        max_bytes: MAX_NAME_BYTES,
+        ..Default::default()
+    };
+    let mut name = media
+        .filename
+        .as_deref()
+        .map(|n| sanitize_filename(n, &options))
+        .filter(|n| !n.changes.contains(&NameChange::Blank))
+        .unwrap_or_else(|| {
+            let fallback = format!(
+                "whatsapp-{}{}",
+                media.message_id,
+                extension_for(&media.mime_type)
+            );
+            sanitize_filename(&fallback, &options)
+        });
+    let path = name.unique_in(&folder, &options);
+    let arrival = Arrival {
+        source: Source::Whatsapp {
+            tenant_id: media.tenant_id.clone(),
+            message_id: media.message_id.clone(),
+        },
+        destination: path.clone(),
+        on_conflict: ConflictPolicy::KeepBoth,
+        sha256: sha256.clone(),
+    };
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (path, scan) = match quarantine::admit_bytes(&bytes, &file_name, arrival)? {
+        Admitted::Placed { path, verdict } => (path, verdict),
+        Admitted::Held(file) => {
+            // Held media counts as imported, so it isn't quarantined twice
+            let held = file.held_path()?;
+            index.insert(sha256, held.to_string_lossy().to_string());
+            save_index(&media.tenant_id, &index)?;
+            quarantine::announce(app, &file);
+            return Ok(None);
+        }
+    };
+
+    index.insert(sha256.clone(), path.to_string_lossy().to_string());
+    save_index(&media.tenant_id, &index)?;
+    drop(guard);
+
+    activity::record(ActivityKind::Import, &path, Some(&media.tenant_id));
+    if settings.whatsapp.log_to_daily_note && store.kind == StoreKind::ObsidianVault {
+        let root = Path::new(&store.path);
+        let note = store_kinds::daily_note_path(root, now.date_naive());
+        let link = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+        let from = media.from.as_deref().unwrap_or("WhatsApp");
+        let line = format!("- {} {} sent ![[{}]]", now.format("%H:%M"), from, link);
+        if let Err(e) = notes::append(&note, &line) {
+            log::warn!("[whatsapp_media] daily note log failed: {}", e);
+        }
+    }
+
+    log::info!(
+        "[whatsapp_media] imported {} to {}",
+        media.message_id,
+        path.display()
+    );
+    Ok(Some(MediaImported {
+        tenant_id: media.tenant_id.clone(),
+        message_id: media.message_id.clone(),
+        chat_id: media.chat_id.clone(),
+        path: path.to_string_lossy().to_string(),
+        sha256,
+        size_bytes: bytes.len() as u64,
+        mime_type: media.mime_type.clone(),
+        scan,
+    }))
+}
+
+/// Finish importing media released from quarantine to `path`.
+pub fn record_import(tenant_id: &str, sha256: &str, path: &Path) -> Result<(), String> {
+    let _guard = INDEX_LOCK.lock().unwrap();
+    let mut index = load_index(tenant_id);
+    index.insert(sha256.to_string(), path.to_string_lossy().to_string());
+    save_index(tenant_id, &index)?;
+    activity::record(ActivityKind::Import, path, Some(tenant_id));
+    Ok(())
+}
+
+// ─── Filenames ──────────────────────────────────────────────────────────────
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type.split(';').next().unwrap_or("").trim() {
+        "image/jpeg" => ".jpg",
+        "image/png" => ".png",
+        "image/webp" => ".webp",
+        "image/gif" => ".gif",
+        "audio/ogg" => ".ogg",
+        "audio/mpeg" => ".mp3",
+        "audio/mp4" => ".m4a",
+        "video/mp4" => ".mp4",
+        "application/pdf" => ".pdf",
+        _ => "",
+    }
+}
+
+// ─── Index ──────────────────────────────────────────────────────────────────
+
+/// SHA-256 → imported path, per tenant.
+fn index_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?
+        .join("whatsapp")
+        .join("media-index.json"))
+}
+
+fn load_index(tenant_id: &str) -> BTreeMap<String, String> {
+    index_path(tenant_id)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(tenant_id: &str, index: &BTreeMap<String, String>) -> Result<(), String> {
+    let path = index_path(tenant_id)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_vec_pretty(index).map_err(|e| e.to_string())?;
+    fs::write(path, raw).map_err(|e| e.to_string())
+}
@@ -0,0 +1,450 @@
+// WhatsApp sending
+//
+// `send_whatsapp_message` checks the chat's policy and the per-tenant rate
+// limit, then writes the message to the tenant's outbox. It returns a message
+// id right away. A background worker sends outbox messages through the bridge
+// in order. When the bridge is unreachable they stay queued on disk and go out
+// once it's back, whether the worker's retry finds it first or the bridge's
+// `whatsapp-bridge-connected` event does. During maintenance they stay queued
+// too, and go out on the first retry after it. Status changes are emitted as
+// `whatsapp-message-status`. Delivered and read come from the bridge's
+// `whatsapp-message-ack` events.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::maintenance::MaintenanceMode;
+use crate::tenant_config::ChatPolicy;
+use crate::{audit, file_read, sessions, settings, stores, tenant_windows, whatsapp};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+/// Sent messages remembered for matching acks, per tenant.
+const MAX_TRACKED: usize = 500;
+
+static OUTBOX_LOCK: Mutex<()> = Mutex::new(());
+static PENDING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+static WAKE: Condvar = Condvar::new();
+static RECENT_SENDS: Mutex<Option<HashMap<String, VecDeque<Instant>>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MessageContent {
+    pub text: Option<String>,
+    /// File to attach; `text` becomes its caption.
+    pub attachment_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    Queued,
+    Sent,
+    Delivered,
+    Read,
+    /// The bridge rejected the message; it won't be retried.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct OutgoingMessage {
+    message_id: String,
+    chat_id: String,
+    text: Option<String>,
+    attachment_path: Option<String>,
+    queued_at: String,
+}
+
+#[derive(Serialize, Clone)]
+struct StatusEvent {
+    tenant_id: String,
+    message_id: String,
+    status: MessageStatus,
+    error: Option<String>,
+}
+
+/// Payload of `whatsapp-message-ack`, as whatsapp-web.js reports it.
+#[derive(Deserialize)]
+struct AckReceived {
+    tenant_id: String,
+    whatsapp_id: String,
+    /// 1 server, 2 device, 3 read, 4 played.
+    ack: i32,
+}
+
+#[derive(Deserialize)]
+struct BridgeSent {
+    id: String,
+}
+
+enum SendError {
+    /// The bridge is down or the client is disconnected; try again later.
+    Unavailable(String),
+    Rejected(String),
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Queue a message to a chat; returns its id. Progress arrives as
+/// `whatsapp-message-status` events.
+#[tauri::command]
+pub fn send_whatsapp_message(
+    app: AppHandle,
+    tenant_id: String,
+    chat_id: String,
+    content: MessageContent,
+) -> CommandResult<String> {
+    whatsapp::ensure_linked(&tenant_id)?;
+    let text = content.text.filter(|t| !t.trim().is_empty());
+    if text.is_none() && content.attachment_path.is_none() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Nothing to send",
+        ));
+    }
+
+    match whatsapp::chat_policy(&tenant_id, &chat_id) {
+        Some(ChatPolicy::Allow) => {}
+        policy => {
+            return Err(CommandError::new(
+                ErrorCode::PolicyDenied,
+                "Replies to this chat aren't allowed",
+            )
+            .with("chat_id", &chat_id)
+            .with("policy", policy));
+        }
+    }
+
+    let settings = settings::load_settings().whatsapp;
+    let attachment_path = match content.attachment_path {
+        Some(path) => Some(check_attachment(&path, settings.max_attachment_bytes)?),
+        None => None,
+    };
+    take_rate_slot(&tenant_id, settings.messages_per_minute)?;
+
+    let message = OutgoingMessage {
+        message_id: format!(
+            "msg-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ),
+        chat_id,
+        text,
+        attachment_path,
+        queued_at: chrono::Utc::now().to_rfc3339(),
+    };
+    {
+        let _guard = OUTBOX_LOCK.lock().unwrap();
+        let mut outbox = load_outbox(&tenant_id);
+        outbox.push(message.clone());
+        save_json(&outbox_path(&tenant_id)?, &outbox)?;
+    }
+    emit_status(
+        &app,
+        &tenant_id,
+        &message.message_id,
+        MessageStatus::Queued,
+        None,
+    );
+    wake(&tenant_id);
+    Ok(message.message_id)
+}
+
+fn check_attachment(path: &str, max_bytes: u64) -> CommandResult<String> {
+    let file = Path::new(path);
+    if stores::store_containing(file).is_none() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not inside a connected store", path),
+        )
+        .with("path", path));
+    }
+    let size = fs::metadata(file)?.len();
+    if max_bytes > 0 && size > max_bytes {
+        return Err(file_read::too_large(
+            file,
+            size,
+            max_bytes,
+            "whatsapp.max_attachment_bytes",
+        ));
+    }
+    Ok(file.canonicalize()?.to_string_lossy().to_string())
+}
+
+/// Count a send against the tenant's per-minute limit, or fail with
+/// `RateLimited` once it's used up.
+fn take_rate_slot(tenant_id: &str, per_minute: u32) -> CommandResult<()> {
+    if per_minute == 0 {
+        return Ok(());
+    }
+    let mut recent = RECENT_SENDS.lock().unwrap();
+    let sends = recent
+        .get_or_insert_with(HashMap::new)
+        .entry(tenant_id.to_string())
+        .or_default();
+    let window = Duration::from_secs(60);
+    while sends.front().is_some_and(|t| t.elapsed() >= window) {
+        sends.pop_front();
+    }
+    if sends.len() >= per_minute as usize {
+        let retry_after = window.saturating_sub(sends[0].elapsed()).as_secs() + 1;
+        return Err(CommandError::new(
+            ErrorCode::RateLimited,
+            format!("More than {} messages a minute", per_minute),
+        )
+        .with("retry_after_secs", retry_after)
+        .with("limit", per_minute));
+    }
+    sends.push_back(Instant::now());
+    Ok(())
+}
+
+// ─── Worker ─────────────────────────────────────────────────────────────────
+
+/// Start the outbox worker and the bridge event listeners. Outboxes left
+/// from a previous run are picked up.
+pub fn spawn(app: AppHandle) {
+    {
+        let mut pending = PENDING.lock().unwrap();
+        let pending = pending.get_or_insert_with(HashSet::new);
+        let tenants =
+            crate::home::data_dir().and_then(|dir| Ok(fs::read_dir(dir.join("tenants"))?));
+        for entry in tenants.into_iter().flatten().flatten() {
+            let tenant_id = entry.file_name().to_string_lossy().to_string();
+            if !load_outbox(&tenant_id).is_empty() {
+                pending.insert(tenant_id);
+            }
+        }
+    }
+
+    app.listen_any("whatsapp-bridge-connected", |event| {
+        #[derive(Deserialize)]
+        struct Connected {
+            tenant_id: String,
+        }
+        if let Ok(connected) = serde_json::from_str::<Connected>(event.payload()) {
+            wake(&connected.tenant_id);
+        }
+    });
+    let handle = app.clone();
+    app.listen_any(
+        "whatsapp-message-ack",
+        move |event| match serde_json::from_str::<AckReceived>(event.payload()) {
+            Ok(ack) => handle_ack(&handle, &ack),
+            Err(e) => log::warn!("[whatsapp_send] malformed ack: {}", e),
+        },
+    );
+
+    std::thread::spawn(move || {
+        // Tenants whose bridge was unavailable; retried when woken for them
+        // or after `RETRY_INTERVAL`
+        let mut blocked: HashSet<String> = HashSet::new();
+        loop {
+            let tenants: HashSet<String> = {
+                let mut pending = PENDING.lock().unwrap();
+                let mut timed_out = false;
+                if pending.as_ref().is_none_or(|p| p.is_empty()) {
+                    let (guard, wait) = WAKE.wait_timeout(pending, RETRY_INTERVAL).unwrap();
+                    pending = guard;
+                    timed_out = wait.timed_out();
+                }
+                let mut tenants: HashSet<String> =
+                    pending.get_or_insert_with(HashSet::new).drain().collect();
+                if timed_out {
+                    tenants.extend(blocked.drain());
+                }
+                tenants
+            };
+            if app.state::<MaintenanceMode>().enabled() {
+                blocked.extend(tenants);
+                continue;
+            }
+            for tenant_id in tenants {
+                blocked.remove(&tenant_id);
+                if !flush(&app, &tenant_id) {
+                    blocked.insert(tenant_id);
+                }
+            }
+        }
+    });
+}
+
+fn wake(tenant_id: &str) {
+    PENDING
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(tenant_id.to_string());
+    WAKE.notify_all();
+}
+
+/// Send a tenant's outbox in order. Returns false if the bridge was
+/// unavailable and messages remain.
+fn flush(app: &AppHandle, tenant_id: &str) -> bool {
+    if sessions::validate_id("tenant_id", tenant_id).is_err() {
+        return true;
+    }
+    loop {
+        let Some(message) = load_outbox(tenant_id).into_iter().next() else {
+            return true;
+        };
+        let result = tauri::async_runtime::block_on(deliver(tenant_id, &message));
+        let status = match result {
+            Ok(whatsapp_id) => {
+                track_sent(tenant_id, &whatsapp_id, &message.message_id);
+                audit::record(
+                    "whatsapp_message_sent",
+                    Some(tenant_id),
+                    serde_json::json!({
+                        "message_id": message.message_id,
+                        "attachment": message.attachment_path.is_some(),
+                    }),
+                );
+                (MessageStatus::Sent, None)
+            }
+            Err(SendError::Rejected(e)) => (MessageStatus::Failed, Some(e)),
+            Err(SendError::Unavailable(e)) => {
+                log::info!("[whatsapp_send] bridge unavailable, keeping outbox: {}", e);
+                return false;
+            }
+        };
+
+        {
+            let _guard = OUTBOX_LOCK.lock().unwrap();
+            let mut outbox = load_outbox(tenant_id);
+            outbox.retain(|m| m.message_id != message.message_id);
+            let saved = outbox_path(tenant_id)
+                .map_err(String::from)
+                .and_then(|path| save_json(&path, &outbox));
+            if let Err(e) = saved {
+                log::error!("[whatsapp_send] saving outbox failed: {}", e);
+                return false;
+            }
+        }
+        emit_status(app, tenant_id, &message.message_id, status.0, status.1);
+    }
+}
+
+async fn deliver(tenant_id: &str, message: &OutgoingMessage) -> Result<String, SendError> {
+    let client = whatsapp::bridge_client().map_err(|e| SendError::Unavailable(e.message))?;
+    let response = client
+        .post(whatsapp::bridge_url(tenant_id, "messages"))
+        .json(&serde_json::json!({
+            "chat_id": message.chat_id,
+            "text": message.text,
+            "attachment_path": message.attachment_path,
+        }))
+        .send()
+        .await
+        .map_err(|e| SendError::Unavailable(e.to_string()))?;
+
+    let status = response.status();
+    // 409: the tenant's client is disconnected; 5xx: bridge trouble
+    if status == reqwest::StatusCode::CONFLICT || status.is_server_error() {
+        return Err(SendError::Unavailable(format!("HTTP {}", status)));
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(SendError::Rejected(format!("HTTP {}: {}", status, body)));
+    }
+    response
+        .json::<BridgeSent>()
+        .await
+        .map(|sent| sent.id)
+        .map_err(|e| SendError::Rejected(e.to_string()))
+}
+
+fn handle_ack(app: &AppHandle, ack: &AckReceived) {
+    let status = match ack.ack {
+        2 => MessageStatus::Delivered,
+        3 | 4 => MessageStatus::Read,
+        _ => return,
+    };
+    if sessions::validate_id("tenant_id", &ack.tenant_id).is_err() {
+        return;
+    }
+    let sent: Vec<(String, String)> = sent_path(&ack.tenant_id)
+        .ok()
+        .and_then(|path| load_json(&path))
+        .unwrap_or_default();
+    if let Some((_, message_id)) = sent.iter().find(|(id, _)| *id == ack.whatsapp_id) {
+        emit_status(app, &ack.tenant_id, message_id, status, None);
+    }
+}
+
+fn emit_status(
+    app: &AppHandle,
+    tenant_id: &str,
+    message_id: &str,
+    status: MessageStatus,
+    error: Option<String>,
+) {
+    let _ = app.emit(
+        "whatsapp-message-status",
+        StatusEvent {
+            tenant_id: tenant_id.to_string(),
+            message_id: message_id.to_string(),
+            status,
+            error,
+        },
+    );
+}
+
+// ─── Storage ────────────────────────────────────────────────────────────────
+
+fn whatsapp_dir(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(tenant_windows::tenant_dir(tenant_id)?.join("whatsapp"))
+}
+
+fn outbox_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(whatsapp_dir(tenant_id)?.join("outbox.json"))
+}
+
+/// WhatsApp message id → our message id, for matching acks.
+fn sent_path(tenant_id: &str) -> CommandResult<PathBuf> {
+    Ok(whatsapp_dir(tenant_id)?.join("sent.json"))
+}
+
+fn load_outbox(tenant_id: &str) -> Vec<OutgoingMessage> {
+    outbox_path(tenant_id)
+        .ok()
+        .and_then(|path| load_json(&path))
+        .unwrap_or_default()
+}
+
+fn track_sent(tenant_id: &str, whatsapp_id: &str, message_id: &str) {
+    let _guard = OUTBOX_LOCK.lock().unwrap();
+    let Ok(path) = sent_path(tenant_id) else {
+        return;
+    };
+    let mut sent: Vec<(String, String)> = load_json(&path).unwrap_or_default();
+    sent.push((whatsapp_id.to_string(), message_id.to_string()));
+    if sent.len() > MAX_TRACKED {
+        sent.drain(..sent.len() - MAX_TRACKED);
+    }
+    if let Err(e) = save_json(&path, &sent) {
+        log::warn!("[whatsapp_send] saving sent ids failed: {}", e);
+    }
+}
+
+fn load_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let raw = fs::read(path).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let raw = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(path, raw).map_err(|e| e.to_string())
+}
@@ -0,0 +1,343 @@
+// Workspaces
+//
+// A workspace is a named group of a tenant's stores and provider accounts,
+// e.g. "Client X": the Client X vault, the Client X Dropbox folder, and the
+// WhatsApp account its chats come through. A workspace only names its
+// members. Dropping a store from one leaves the store connected, and a
+// store can be in any number of workspaces. Workspaces and the tenant's
+// active one are kept in the tenant's config (see `tenant_config`), so the
+// active workspace survives restarts.
+//
+// Context-sensitive commands take an optional `workspace_id` with the
+// `tenant_id`, and scope themselves to its members:
+// - `query_files` searches every member store
+// - `get_recent_activity` keeps entries in member stores
+// - `submit_quick_capture` writes to the workspace's vault
+// - `rescan_all_stores` rescans the member stores
+// A member store that has since been disconnected is skipped. Events from
+// scoped commands carry the `workspace_id`, and `set_active_workspace`
+// emits `active-workspace-changed`, so the UI can quiet inactive contexts.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::store_kinds::StoreKind;
+use crate::stores::{self, ConnectedStore};
+use crate::tenant_config;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// ─── Types ──────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub store_ids: Vec<String>,
+    /// Providers whose sessions belong to the workspace, e.g. `whatsapp`.
+    #[serde(default)]
+    pub provider_accounts: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkspaceList {
+    pub workspaces: Vec<Workspace>,
+    pub active_workspace_id: Option<String>,
+}
+
+/// Payload of `active-workspace-changed`.
+#[derive(Serialize, Clone)]
+pub struct ActiveWorkspaceChanged {
+    pub tenant_id: String,
+    pub workspace_id: Option<String>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn create_workspace(
+    tenant_id: String,
+    name: String,
+    store_ids: Vec<String>,
+    provider_accounts: Vec<String>,
+) -> CommandResult<Workspace> {
+    let workspace = Workspace {
+        id: format!(
+            "ws-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ),
+        name: valid_name(&name)?,
+        store_ids: known_stores(store_ids)?,
+        provider_accounts: members(provider_accounts),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let created = workspace.clone();
+    tenant_config::update(&tenant_id, |config| config.workspaces.push(created))?;
+    Ok(workspace)
+}
+
+/// Rename a workspace or replace its members; fields left out are kept.
+#[tauri::command]
+pub fn update_workspace(
+    tenant_id: String,
+    workspace_id: String,
+    name: Option<String>,
+    store_ids: Option<Vec<String>>,
+    provider_accounts: Option<Vec<String>>,
+) -> CommandResult<Workspace> {
+    let name = name.as_deref().map(valid_name).transpose()?;
+    let store_ids = store_ids.map(known_stores).transpose()?;
+    find(&tenant_id, &workspace_id)?;
+
+    let mut updated = None;
+    tenant_config::update(&tenant_id, |config| {
+        if let Some(workspace) = config.workspaces.iter_mut().find(|w| w.id == workspace_id) {
+            if let Some(name) = name {
+                workspace.name = name;
+            }
+            if let Some(store_ids) = store_ids {
+                workspace.store_ids = store_ids;
+            }
+            if let Some(accounts) = provider_accounts {
+                workspace.provider_accounts = members(accounts);
+            }
+            updated = Some(workspace.clone());
+        }
+    })?;
+    updated.ok_or_else(|| not_found(&workspace_id))
+}
+
+/// Delete a workspace; its stores stay connected. Deleting the active one
+/// leaves no workspace active.
+#[tauri::command]
+pub fn delete_workspace(
+    app: AppHandle,
+    tenant_id: String,
+    workspace_id: String,
+) -> CommandResult<()> {
+    find(&tenant_id, &workspace_id)?;
+    let mut was_active = false;
+    tenant_config::update(&tenant_id, |config| {
+        config.workspaces.retain(|w| w.id != workspace_id);
+        if config.active_workspace.as_deref() == Some(&workspace_id) {
+            config.active_workspace = None;
+            was_active = true;
+        }
+    })?;
+    if was_active {
+        emit_active(&app, &tenant_id, None);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_workspaces(tenant_id: String) -> WorkspaceList {
+    let config = tenant_config::load(&tenant_id);
+    WorkspaceList {
+        workspaces: config.workspaces,
+        active_workspace_id: config.active_workspace,
+    }
+}
+
+/// Make `workspace_id` the tenant's active workspace, or with none, go back
+/// to seeing everything.
+#[tauri::command]
+pub fn set_active_workspace(
+    app: AppHandle,
+    tenant_id: String,
+    workspace_id: Option<String>,
+) -> CommandResult<()> {
+    set_active(&tenant_id, workspace_id.as_deref())?;
+    emit_active(&app, &tenant_id, workspace_id);
+    Ok(())
+}
+
+fn set_active(tenant_id: &str, workspace_id: Option<&str>) -> CommandResult<()> {
+    if let Some(id) = workspace_id {
+        find(tenant_id, id)?;
+    }
+    tenant_config::update(tenant_id, |config| {
+        config.active_workspace = workspace_id.map(str::to_string)
+    })?;
+    Ok(())
+}
+
+fn emit_active(app: &AppHandle, tenant_id: &str, workspace_id: Option<String>) {
+    let _ = app.emit(
+        "active-workspace-changed",
+        ActiveWorkspaceChanged {
+            tenant_id: tenant_id.to_string(),
+            workspace_id,
+        },
+    );
+}
+
+// ─── Scoping ────────────────────────────────────────────────────────────────
+
+/// The workspace a scoped command was given, if any. A `workspace_id`
+/// needs the `tenant_id` it belongs to.
+pub fn resolve(
+    tenant_id: Option<&str>,
+    workspace_id: Option<&str>,
+) -> CommandResult<Option<Workspace>> {
+    let Some(workspace_id) = workspace_id else {
+        return Ok(None);
+    };
+    let Some(tenant_id) = tenant_id else {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "A workspace_id needs its tenant_id",
+        )
+        .with("workspace_id", workspace_id));
+    };
+    find(tenant_id, workspace_id).map(Some)
+}
+
+pub fn find(tenant_id: &str, workspace_id: &str) -> CommandResult<Workspace> {
+    tenant_config::load(tenant_id)
+        .workspaces
+        .into_iter()
+        .find(|w| w.id == workspace_id)
+        .ok_or_else(|| not_found(workspace_id).with("tenant_id", tenant_id))
+}
+
+impl Workspace {
+    pub fn has_store(&self, store_id: &str) -> bool {
+        self.store_ids.iter().any(|id| id == store_id)
+    }
+
+    /// Member stores still connected, in the workspace's order.
+    pub fn stores(&self) -> Vec<ConnectedStore> {
+        let registry = stores::load_registry();
+        self.store_ids
+            .iter()
+            .filter_map(|id| registry.iter().find(|s| &s.id == id).cloned())
+            .collect()
+    }
+
+    /// Where quick capture writes: the first member vault.
+    pub fn capture_vault(&self) -> CommandResult<ConnectedStore> {
+        self.stores()
+            .into_iter()
+            .find(|s| s.kind == StoreKind::ObsidianVault)
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorCode::NotFound,
+                    format!("Workspace {} has no vault to capture into", self.name),
+                )
+                .with("workspace_id", &self.id)
+            })
+    }
+}
+
+// ─── Validation ─────────────────────────────────────────────────────────────
+
+fn valid_name(name: &str) -> CommandResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "A workspace needs a name",
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// `store_ids` without repeats, each a connected store.
+fn known_stores(store_ids: Vec<String>) -> CommandResult<Vec<String>> {
+    let store_ids = members(store_ids);
+    for id in &store_ids {
+        stores::find_store(id)?;
+    }
+    Ok(store_ids)
+}
+
+fn members(ids: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for id in ids {
+        let id = id.trim().to_string();
+        if !id.is_empty() && !unique.contains(&id) {
+            unique.push(id);
+        }
+    }
+    unique
+}
+
+fn not_found(workspace_id: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::NotFound,
+        format!("Unknown workspace: {}", workspace_id),
+    )
+    .with("workspace_id", workspace_id)
+}
+
+// ─── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    fn store(home: &TestHome, folder: &str) -> String {
+        home.write(&format!("{}/.obsidian/app.json", folder), "{}");
+        let root = home.join(folder).to_string_lossy().to_string();
+        stores::register_store(folder.to_string(), root, None)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn removing_a_member_keeps_the_store() {
+        let home = TestHome::new();
+        let vault = store(&home, "ClientX");
+        let dropbox = store(&home, "Dropbox/ClientX");
+        let workspace = create_workspace(
+            "acme".into(),
+            " Client X ".into(),
+            vec![vault.clone(), dropbox.clone(), vault.clone()],
+            vec!["whatsapp".into()],
+        )
+        .unwrap();
+        assert_eq!(workspace.name, "Client X");
+        assert_eq!(workspace.store_ids, [vault.clone(), dropbox.clone()]);
+
+        let updated = update_workspace(
+            "acme".into(),
+            workspace.id.clone(),
+            None,
+            Some(vec![vault.clone()]),
+            None,
+        )
+        .unwrap();
+        assert!(!updated.has_store(&dropbox));
+        assert_eq!(updated.provider_accounts, ["whatsapp"]);
+        assert!(stores::find_store(&dropbox).is_ok());
+
+        let unknown = create_workspace("acme".into(), "Bad".into(), vec!["nope".into()], vec![]);
+        assert!(matches!(unknown, Err(e) if e.code == ErrorCode::NotFound));
+    }
+
+    #[test]
+    fn the_active_workspace_is_saved_per_tenant() {
+        let home = TestHome::new();
+        let vault = store(&home, "ClientX");
+        let workspace =
+            create_workspace("acme".into(), "Client X".into(), vec![vault], vec![]).unwrap();
+        set_active("acme", Some(&workspace.id)).unwrap();
+        assert_eq!(
+            list_workspaces("acme".into()).active_workspace_id,
+            Some(workspace.id.clone())
+        );
+        assert!(list_workspaces("globex".into()).workspaces.is_empty());
+        assert!(set_active("globex", Some(&workspace.id)).is_err());
+
+        let scoped = resolve(Some("acme"), Some(&workspace.id)).unwrap().unwrap();
+        assert_eq!(scoped.capture_vault().unwrap().name, "ClientX");
+        assert!(resolve(None, Some(&workspace.id)).is_err());
+        assert!(resolve(Some("acme"), None).unwrap().is_none());
+    }
+}
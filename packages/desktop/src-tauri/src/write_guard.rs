@@ -0,0 +1,280 @@
+// Conflict-safe writes
+//
+// A caller that reads a file, edits it, and writes it back passes the hash it
+// read as `expected_hash`. The write only happens if the file still hashes to
+// that. Otherwise it fails with `Conflict`, carrying the actual hash and a
+// line diff summary, and the file is left untouched. Guarded writes made by
+// the app hold `WRITE_LOCK` from the check through the write, so two agents
+// can't interleave. Another program can still change the file inside that
+// window.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::activity::{self, ActivityKind};
+use crate::dry_run::DryRun;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::text_style::{self, TextWriteOptions};
+use crate::{file_read, fs_deadline, settings};
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Clone)]
+pub struct TextWithHash {
+    pub content: String,
+    /// SHA-256 of the bytes on disk, as `hash_file` computes it.
+    pub hash: String,
+}
+
+/// How the file on disk differs from what the caller meant to write.
+#[derive(Serialize, Clone)]
+pub struct DiffSummary {
+    pub current_bytes: usize,
+    pub current_lines: usize,
+    /// 1-based line of the first difference; `None` with no proposed content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_changed_line: Option<usize>,
+    /// Lines of the current file the write would replace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<usize>,
+    /// Lines of the proposed content that replace them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<usize>,
+}
+
+// ─── Commands ───────────────────────────────────────────────────────────────
+
+/// Read a text file and hash the same bytes, for a later `expected_hash`.
+#[tauri::command]
+pub async fn read_text_file_with_hash(
+    path: String,
+    timeout_ms: Option<u64>,
+) -> CommandResult<TextWithHash> {
+    fs_deadline::run(
+        "read_text_file_with_hash",
+        Path::new(&path.clone()),
+        timeout_ms,
+        move || read_with_hash(path),
+    )
+    .await
+}
+
+fn read_with_hash(path: String) -> CommandResult<TextWithHash> {
+    let file = Path::new(&path);
+    let Some(bytes) = read_current(file)? else {
+        return Err(
+            CommandError::new(ErrorCode::NotFound, format!("File not found: {}", path))
+                .with("path", &path),
+        );
+    };
+    let limit = settings::load_settings().limits.max_text_read_bytes;
+    if bytes.len() as u64 > limit {
+        return Err(file_read::too_large(
+            file,
+            bytes.len() as u64,
+            limit,
+            "max_text_read_bytes",
+        ));
+    }
+    let hash = sha256(&bytes);
+    let content =
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    activity::record(ActivityKind::Read, file, None);
+    Ok(TextWithHash { content, hash })
+}
+
+// ─── Guard ──────────────────────────────────────────────────────────────────
+
+/// Hold across the check and the write.
+pub fn lock() -> MutexGuard<'static, ()> {
+    WRITE_LOCK.lock().unwrap()
+}
+
+/// Current contents of `path`, or `None` if it doesn't exist.
+pub fn read_current(path: &Path) -> CommandResult<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fail with `Conflict` unless `current` hashes to `expected`. A missing file
+/// never matches. `proposed` is the full text about to be written, if the
+/// write replaces the file, and fills in the diff summary.
+pub fn check(
+    path: &Path,
+    current: Option<&[u8]>,
+    expected: &str,
+    proposed: Option<&str>,
+) -> CommandResult<()> {
+    let actual = current.map(sha256);
+    if actual
+        .as_deref()
+        .is_some_and(|a| a.eq_ignore_ascii_case(expected))
+    {
+        return Ok(());
+    }
+    let text = current.map(String::from_utf8_lossy).unwrap_or_default();
+    log::warn!("[write_guard] {} changed since it was read", path.display());
+    Err(CommandError::new(
+        ErrorCode::Conflict,
+        format!("{} changed since it was read", path.display()),
+    )
+    .with("path", path.to_string_lossy())
+    .with("expected_hash", expected)
+    .with("actual_hash", actual)
+    .with("diff", summarize(&text, proposed)))
+}
+
+/// `write_text_file` once off the async runtime: the guarded check, then the
+/// write in the file's existing text style.
+pub fn write_text(
+    path: &Path,
+    content: &str,
+    options: &TextWriteOptions,
+    expected_hash: Option<&str>,
+    dry_run: bool,
+) -> CommandResult<DryRun> {
+    let _guard = lock();
+    let current = read_current(path)?;
+    if let Some(expected) = expected_hash {
+        check(path, current.as_deref(), expected, Some(content))?;
+    }
+    let existing = current.as_deref().map(text_style::detect);
+    let (ending, bom) = text_style::resolve(path, existing.as_ref(), options)?;
+    let bytes = text_style::encode(content, ending, bom);
+    if dry_run {
+        let verb = if current.is_some() {
+            "Replace"
+        } else {
+            "Create"
+        };
+        return Ok(DryRun::planned(format!(
+            "{} {} ({} bytes)",
+            verb,
+            path.display(),
+            bytes.len()
+        )));
+    }
+    fs::write(path, bytes)?;
+    activity::record(ActivityKind::Write, path, None);
+    Ok(DryRun::default())
+}
+
+pub fn sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn summarize(current: &str, proposed: Option<&str>) -> DiffSummary {
+    let old: Vec<&str> = current.lines().collect();
+    let mut summary = DiffSummary {
+        current_bytes: current.len(),
+        current_lines: old.len(),
+        first_changed_line: None,
+        lines_removed: None,
+        lines_added: None,
+    };
+    let Some(proposed) = proposed else {
+        return summary;
+    };
+    let new: Vec<&str> = proposed.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let removed = old.len() - prefix - suffix;
+    let added = new.len() - prefix - suffix;
+    if removed + added > 0 {
+        summary.first_changed_line = Some(prefix + 1);
+    }
+    summary.lines_removed = Some(removed);
+    summary.lines_added = Some(added);
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHome;
+
+    #[test]
+    fn matching_hash_passes_in_any_case() {
+        let current = b"one\ntwo\n";
+        let hash = sha256(current).to_uppercase();
+        assert!(check(Path::new("note.md"), Some(current), &hash, None).is_ok());
+    }
+
+    #[test]
+    fn a_change_since_the_read_is_a_conflict_with_a_diff() {
+        let read = b"one\ntwo\nthree\n";
+        // Another writer changed the middle line after the caller read it
+        let current = b"one\nTWO\nthree\n";
+        let err = check(
+            Path::new("note.md"),
+            Some(current),
+            &sha256(read),
+            Some("one\nmine\nthree\n"),
+        )
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert_eq!(err.params["actual_hash"], sha256(current));
+        let diff = &err.params["diff"];
+        assert_eq!(diff["first_changed_line"], 2);
+        assert_eq!(diff["lines_removed"], 1);
+        assert_eq!(diff["lines_added"], 1);
+    }
+
+    #[test]
+    fn a_missing_file_never_matches() {
+        let err = check(Path::new("gone.md"), None, &sha256(b""), None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert!(err.params["actual_hash"].is_null());
+    }
+
+    #[test]
+    fn an_external_edit_after_the_read_blocks_the_write() {
+        let home = TestHome::new();
+        let path = home.write("vault/note.md", "one\ntwo\n");
+        let read = tauri::async_runtime::block_on(read_text_file_with_hash(
+            path.to_string_lossy().to_string(),
+            None,
+        ))
+        .unwrap();
+        assert_eq!(read.content, "one\ntwo\n");
+
+        // Another program saves the file between our read and write
+        fs::write(&path, "one\nTHEIRS\n").unwrap();
+        let err = write_text(
+            &path,
+            "one\nmine\n",
+            &TextWriteOptions::default(),
+            Some(&read.hash),
+            false,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.code, ErrorCode::Conflict);
+        assert_eq!(err.params["diff"]["first_changed_line"], 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\nTHEIRS\n");
+
+        // With the fresh hash the write goes through
+        let fresh = sha256(&fs::read(&path).unwrap());
+        write_text(
+            &path,
+            "one\nmine\n",
+            &TextWriteOptions::default(),
+            Some(&fresh),
+            false,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\nmine\n");
+    }
+}